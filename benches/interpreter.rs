@@ -3,9 +3,11 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use sui_lang::Interpreter;
 
-fn fibonacci_benchmark(c: &mut Criterion) {
-    let code = r#"
-# 0 1 {
+/// Recursive Fibonacci; `n` controls the number of calls
+fn fibonacci_code(n: i64) -> String {
+    format!(
+        r#"
+# 0 1 {{
 < v0 a0 2
 ! v1 v0
 ? v1 1
@@ -17,17 +19,38 @@ $ v3 0 v2
 $ v5 0 v4
 + v6 v3 v5
 ^ v6
-}
-= g0 20
+}}
+= g0 {}
 $ g1 0 g0
-"#;
+"#,
+        n
+    )
+}
+
+fn fibonacci_benchmark(c: &mut Criterion) {
+    let code = fibonacci_code(20);
 
     c.bench_function("fibonacci(20)", |b| {
         b.iter(|| {
             let mut interp = Interpreter::new();
-            interp.run(black_box(code), &[]).unwrap();
+            interp.run(black_box(&code), &[]).unwrap();
+        })
+    });
+}
+
+/// Regression benchmark for call overhead (label lookup, function dispatch)
+fn fibonacci_25_benchmark(c: &mut Criterion) {
+    let code = fibonacci_code(25);
+
+    let mut group = c.benchmark_group("calls");
+    group.sample_size(10);
+    group.bench_function("fibonacci(25)", |b| {
+        b.iter(|| {
+            let mut interp = Interpreter::new();
+            interp.run(black_box(&code), &[]).unwrap();
         })
     });
+    group.finish();
 }
 
 fn loop_benchmark(c: &mut Criterion) {
@@ -96,6 +119,7 @@ fn simple_arithmetic_benchmark(c: &mut Criterion) {
 criterion_group!(
     benches,
     fibonacci_benchmark,
+    fibonacci_25_benchmark,
     loop_benchmark,
     array_benchmark,
     simple_arithmetic_benchmark
@@ -0,0 +1,31 @@
+//! Running arbitrary programs must never panic
+//!
+//! Programs are bounded by a step limit and a small call depth. Programs
+//! that read stdin or import modules are skipped.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sui_lang::interpreter::{Instruction, Interpreter, Parser};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(code) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let Ok((instructions, functions)) = Parser::parse(code) else {
+        return;
+    };
+    let side_effects = instructions
+        .iter()
+        .chain(functions.iter().flat_map(|f| f.body.iter()))
+        .any(|instr| matches!(instr, Instruction::Input { .. } | Instruction::Import { .. }));
+    if side_effects {
+        return;
+    }
+
+    let mut interp = Interpreter::new();
+    interp.set_max_steps(Some(100_000));
+    interp.set_max_stack_depth(64);
+    let _ = interp.run(code, &[]);
+});
@@ -0,0 +1,29 @@
+//! Lexing and parsing arbitrary input must never panic
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sui_lang::interpreter::{Lexer, Parser};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(code) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    for line in code.lines() {
+        for token in Lexer::tokenize_line(line) {
+            let _ = Lexer::parse_value(&token);
+        }
+    }
+
+    let strict = Parser::parse(code);
+    let (instructions, functions, errors) = Parser::parse_lossy(code);
+    let _ = Parser::validate(code);
+
+    // A successful strict parse is exactly a lossy parse without errors
+    if let Ok((strict_instructions, strict_functions)) = strict {
+        assert!(errors.is_empty());
+        assert_eq!(strict_instructions, instructions);
+        assert_eq!(strict_functions.len(), functions.len());
+    }
+});
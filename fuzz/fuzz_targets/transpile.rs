@@ -0,0 +1,16 @@
+//! Transpiling arbitrary programs must never panic
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sui_lang::transpiler::{Sui2Js, Sui2Py, Sui2Wasm};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(code) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let _ = Sui2Py::new().transpile_to_python(code);
+    let _ = Sui2Js::new().transpile_to_js(code);
+    let _ = Sui2Wasm::new().transpile_to_wat(code);
+});
@@ -0,0 +1,81 @@
+/*
+ * Sui (粋) - C API for embedding the interpreter
+ *
+ * Build the library with `cargo build --release --features ffi` and link
+ * against target/release/libsui_lang.so (.dylib on macOS). Strings returned
+ * by a handle stay valid until its next sui_run, sui_transpile,
+ * sui_validate or sui_free.
+ *
+ *     SuiInterpreter *sui = sui_new();
+ *     const char *argv[] = {"10"};
+ *     if (sui_run(sui, "* v0 g101 2\n. v0", argv, 1) == SUI_OK) {
+ *         for (size_t i = 0; i < sui_output_count(sui); i++)
+ *             puts(sui_get_output_line(sui, i));
+ *     } else {
+ *         fprintf(stderr, "%s\n", sui_last_error(sui));
+ *     }
+ *     sui_free(sui);
+ */
+
+#ifndef SUI_H
+#define SUI_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SUI_OK 0
+#define SUI_ERROR (-1)
+
+/* Interpreter with the output and error of its last run */
+typedef struct SuiHandle SuiInterpreter;
+
+/* Create an interpreter; free it with sui_free */
+SuiInterpreter *sui_new(void);
+
+/* Free an interpreter; NULL is ignored */
+void sui_free(SuiInterpreter *sui);
+
+/* Run a program with argc arguments (g100, g101, ...); argv may be NULL
+ * when argc is 0. Returns SUI_OK, or SUI_ERROR with the message in
+ * sui_last_error. Output printed before an error is kept. */
+int sui_run(SuiInterpreter *sui, const char *code, const char *const *argv, size_t argc);
+
+/* Transpile a program to target: "python", "javascript", "typescript" or
+ * "wat". Returns SUI_OK with the code in sui_result, or SUI_ERROR with the
+ * message in sui_last_error. */
+int sui_transpile(SuiInterpreter *sui, const char *target, const char *code);
+
+/* Check a program without running it. Returns the number of problems,
+ * whose messages become the output lines, or SUI_ERROR. */
+int sui_validate(SuiInterpreter *sui, const char *code);
+
+/* Number of output lines of the last run */
+size_t sui_output_count(const SuiInterpreter *sui);
+
+/* Output line index of the last run, or NULL when out of range */
+const char *sui_get_output_line(const SuiInterpreter *sui, size_t index);
+
+/* Code produced by the last sui_transpile, or NULL */
+const char *sui_result(const SuiInterpreter *sui);
+
+/* Error message of the last call, or NULL if it succeeded */
+const char *sui_last_error(const SuiInterpreter *sui);
+
+/* Stop runs after max_steps instructions; 0 removes the limit */
+void sui_set_limit(SuiInterpreter *sui, uint64_t max_steps);
+
+/* Maximum call depth before a run fails with a stack overflow */
+void sui_set_max_depth(SuiInterpreter *sui, size_t depth);
+
+/* Version of the library, such as "0.2.0" */
+const char *sui_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SUI_H */
@@ -0,0 +1,220 @@
+//! Structural comparison of Sui programs
+
+use super::minify::Canonical;
+use crate::interpreter::{Function, ParseError};
+use std::fmt;
+
+/// Part of a program an instruction belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Top-level code
+    Main,
+    /// The n-th function definition (0-based)
+    Function(usize),
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Main => write!(f, "main"),
+            Scope::Function(n) => write!(f, "function #{}", n),
+        }
+    }
+}
+
+/// One instruction present in only one of the programs
+///
+/// Instructions are shown in canonical form; `index` is the position within
+/// the canonical scope, where a function's header line is index 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// Only in the first program
+    Removed {
+        scope: Scope,
+        index: usize,
+        instruction: String,
+    },
+    /// Only in the second program
+    Added {
+        scope: Scope,
+        index: usize,
+        instruction: String,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Removed {
+                scope,
+                index,
+                instruction,
+            } => write!(f, "- {}:{}: {}", scope, index, instruction),
+            Difference::Added {
+                scope,
+                index,
+                instruction,
+            } => write!(f, "+ {}:{}: {}", scope, index, instruction),
+        }
+    }
+}
+
+/// Result of [`semantic_diff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SemanticDiff {
+    /// Whether both programs are the same after normalization
+    pub identical: bool,
+    /// Instructions that differ, grouped by scope in program order
+    pub differences: Vec<Difference>,
+}
+
+impl fmt::Display for SemanticDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.identical {
+            return write!(f, "Programs are structurally identical");
+        }
+        write!(f, "Programs differ in {} instruction(s):", self.differences.len())?;
+        for difference in &self.differences {
+            write!(f, "\n{}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two programs modulo formatting, comments and numbering
+///
+/// Both programs are normalized the way [`minify`](super::minify) numbers
+/// them (variables, labels and function IDs in order of first use), without
+/// collapsing temporaries. Programs that only differ in naming are
+/// identical; otherwise the differing instructions are listed per scope.
+///
+/// ```rust
+/// use sui_lang::analysis::semantic_diff;
+///
+/// let a = "= v3 1\n; print it\n. v3";
+/// let b = "= v0 1\n. v0";
+/// assert!(semantic_diff(a, b).unwrap().identical);
+/// ```
+pub fn semantic_diff(a: &str, b: &str) -> Result<SemanticDiff, ParseError> {
+    let a = Canonical::new(a, false)?;
+    let b = Canonical::new(b, false)?;
+
+    let mut differences = Vec::new();
+    let count = a.functions.len().max(b.functions.len());
+    for n in 0..count {
+        diff_lines(
+            Scope::Function(n),
+            &a.functions.get(n).map(function_lines).unwrap_or_default(),
+            &b.functions.get(n).map(function_lines).unwrap_or_default(),
+            &mut differences,
+        );
+    }
+    diff_lines(
+        Scope::Main,
+        &a.main.iter().map(|i| i.to_string()).collect::<Vec<_>>(),
+        &b.main.iter().map(|i| i.to_string()).collect::<Vec<_>>(),
+        &mut differences,
+    );
+
+    Ok(SemanticDiff {
+        identical: differences.is_empty(),
+        differences,
+    })
+}
+
+/// Header followed by the body of a function
+fn function_lines(func: &Function) -> Vec<String> {
+    std::iter::once(format!("# {} {} {{", func.id, func.arg_count))
+        .chain(func.body.iter().map(|i| i.to_string()))
+        .collect()
+}
+
+/// Line diff based on the longest common subsequence
+fn diff_lines(scope: Scope, a: &[String], b: &[String], out: &mut Vec<Difference>) {
+    // lcs[i][j] = length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(Difference::Removed {
+                scope,
+                index: i,
+                instruction: a[i].clone(),
+            });
+            i += 1;
+        } else {
+            out.push(Difference::Added {
+                scope,
+                index: j,
+                instruction: b[j].clone(),
+            });
+            j += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renaming_is_identical() {
+        let a = "# 3 1 {\n+ v4 a0 1\n^ v4\n}\n: 7\n$ g9 3 1\n. g9\n@ 7";
+        let b = "# 0 1 {\n+ v0 a0 1\n^ v0\n}\n\n: 0\n$ g0 0 1 ; call\n. g0\n@ 0";
+        let diff = semantic_diff(a, b).unwrap();
+        assert!(diff.identical, "{}", diff);
+    }
+
+    #[test]
+    fn test_reports_differing_instructions() {
+        let a = "# 0 1 {\n+ v0 a0 1\n^ v0\n}\n= v0 1\n. v0";
+        let b = "# 0 1 {\n- v0 a0 1\n^ v0\n}\n= v0 1\n. v0\n. 2";
+        let diff = semantic_diff(a, b).unwrap();
+        assert!(!diff.identical);
+        assert_eq!(
+            diff.differences,
+            vec![
+                Difference::Removed {
+                    scope: Scope::Function(0),
+                    index: 1,
+                    instruction: "+ v0 a0 1".to_string()
+                },
+                Difference::Added {
+                    scope: Scope::Function(0),
+                    index: 1,
+                    instruction: "- v0 a0 1".to_string()
+                },
+                Difference::Added {
+                    scope: Scope::Main,
+                    index: 2,
+                    instruction: ". 2".to_string()
+                },
+            ]
+        );
+        assert!(diff.to_string().contains("+ main:2: . 2"));
+    }
+
+    #[test]
+    fn test_extra_function() {
+        let diff = semantic_diff(". 1", "# 0 0 {\n^ 1\n}\n. 1").unwrap();
+        // Header and body of the new function
+        assert_eq!(diff.differences.len(), 2);
+        assert!(diff
+            .differences
+            .iter()
+            .all(|d| matches!(d, Difference::Added { scope: Scope::Function(0), .. })));
+    }
+}
@@ -0,0 +1,117 @@
+//! Type hints checked against inferred types
+
+use super::{infer_types, TypeSet};
+use crate::interpreter::{Lexer, ParseError, Parser, TypeHint};
+use std::fmt;
+
+/// Type hint the inferred types do not fit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintViolation {
+    /// Line number (1-based) of the hint
+    pub line: usize,
+    /// What the hint is about: a variable, or an argument or the return
+    /// value of a function
+    pub subject: String,
+    pub hint: TypeHint,
+    /// Types inference found
+    pub inferred: TypeSet,
+}
+
+impl fmt::Display for HintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is declared {} but may be {} at line {}", self.subject, self.hint, self.inferred, self.line)
+    }
+}
+
+/// Types a hint allows
+pub fn hint_types(hint: TypeHint) -> TypeSet {
+    match hint {
+        TypeHint::Int => TypeSet::INT,
+        TypeHint::Float => TypeSet::FLOAT,
+        TypeHint::Number => TypeSet::NUMBER,
+        TypeHint::String => TypeSet::STRING,
+        TypeHint::Array => TypeSet::ARRAY,
+    }
+}
+
+/// Check the type hints of a program against [`infer_types`]
+///
+/// Operand hints (`v0:i`) describe the value a variable holds after the line
+/// assigns it, or as the line reads it; `;@ fn` signatures describe every
+/// call's arguments and the values the function returns. A hint is violated
+/// when inference finds a type outside it. Where inference knows nothing,
+/// as for the arguments of functions nobody calls, hints are taken on
+/// trust.
+///
+/// ```rust
+/// use sui_lang::analysis::check_hints;
+///
+/// let violations = check_hints("= v0:i 1.5\n. v0").unwrap();
+/// assert_eq!(violations[0].to_string(), "v0 is declared int but may be float at line 1");
+/// ```
+pub fn check_hints(code: &str) -> Result<Vec<HintViolation>, ParseError> {
+    let annotations = Parser::annotations(code)?;
+    let metadata = Parser::metadata(code)?;
+    let info = infer_types(code);
+    let lines: Vec<&str> = code.lines().collect();
+
+    let mut violations = Vec::new();
+    let mut check = |line: usize, subject: String, hint: TypeHint, inferred: Option<TypeSet>| match inferred {
+        Some(inferred) if inferred != TypeSet::UNKNOWN && !hint_types(hint).contains(inferred) => {
+            violations.push(HintViolation { line, subject, hint, inferred })
+        }
+        _ => {}
+    };
+    for annotation in &annotations {
+        let tokens = Lexer::tokenize_line(lines[annotation.line - 1]);
+        let writes = Parser::syntax(&tokens[0]).is_some_and(|syntax| syntax.writes(annotation.operand));
+        let inferred = match writes {
+            true => info.after(annotation.line, &annotation.var),
+            false => info.before(annotation.line, &annotation.var),
+        };
+        check(annotation.line, annotation.var.clone(), annotation.hint, inferred);
+    }
+    for signature in &metadata.functions {
+        let id = signature.id;
+        for (k, &hint) in signature.params.iter().enumerate() {
+            let inferred = info.var(Some(id), &format!("a{}", k));
+            check(signature.line, format!("argument {} of function {}", k, id), hint, inferred);
+        }
+        if let Some(hint) = signature.returns {
+            check(signature.line, format!("the result of function {}", id), hint, info.returns(id));
+        }
+    }
+    violations.sort_by_key(|v| v.line);
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_hints() {
+        let code = ";@ fn 0 i -> i\n;@ fn 1 s -> s\n# 0 1 {\n/ v0:n a0 2\n^ v0\n}\n# 1 1 {\n^ a0\n}\n$ v0:i 0 3\n, v1\n+ v2 v1:s \"!\"\n$ v3 1 v2";
+        let found: Vec<String> = check_hints(code).unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            found,
+            [
+                "the result of function 0 is declared int but may be float at line 1",
+                "argument 0 of function 1 is declared string but may be float|string at line 2",
+                "the result of function 1 is declared string but may be float|string at line 2",
+                "v0 is declared int but may be float at line 10",
+                "v1 is declared string but may be int|float|string at line 12",
+            ]
+        );
+
+        // Hints are checked, not run
+        let code = "= v0:f 2.5\n+ v1:f v0:f 1\n. v1";
+        assert!(check_hints(code).unwrap().is_empty());
+        let mut interp = crate::Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), ["3.5"]);
+        assert_eq!(
+            check_hints("= v0:x 1").unwrap_err().to_string(),
+            "Parse error at line 1: Unknown type hint 'x' (expected i, f, n, s or a) on v0"
+        );
+    }
+}
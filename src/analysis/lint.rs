@@ -0,0 +1,324 @@
+//! Static checks for likely mistakes
+
+use super::infer_types_lines;
+use crate::interpreter::{Instruction, InstructionSyntax, Lexer, ParsedValue, Parser};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Kind of problem found by [`lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// Jump to a label that is not defined in the same function; execution
+    /// falls through to the next line instead
+    UndefinedLabel(i64),
+    /// Call to a function that is never defined
+    UndefinedFunction(i64),
+    /// Instruction after a jump or return that no label leads to
+    Unreachable,
+    /// `P` is only accepted as an alias of `R`
+    PythonFfi,
+    /// Read of a variable that is never assigned in its scope, or of an
+    /// argument beyond the function's argument count; it reads as 0, and
+    /// strict mode rejects it
+    UndefinedVariable(String),
+    /// Operation whose operand types never fit together, such as adding a
+    /// string to an array; the values are silently converted
+    TypeMismatch(String),
+    /// Write to an argument; arguments are read-only, so the write is
+    /// ignored, and strict mode rejects it
+    ArgumentWrite(String),
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintKind::UndefinedLabel(label) => write!(f, "Jump to undefined label {}", label),
+            LintKind::UndefinedFunction(id) => write!(f, "Call to undefined function {}", id),
+            LintKind::Unreachable => write!(f, "Unreachable instruction"),
+            LintKind::PythonFfi => write!(f, "'P' is an alias of 'R'"),
+            LintKind::UndefinedVariable(name) => write!(f, "Read of unassigned variable {}", name),
+            LintKind::TypeMismatch(operation) => write!(f, "Type-confused operation: {}", operation),
+            LintKind::ArgumentWrite(name) => write!(f, "Write to argument {} is ignored", name),
+        }
+    }
+}
+
+/// Warning for one source line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// Line number (1-based)
+    pub line: usize,
+    /// What was found
+    pub kind: LintKind,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}", self.kind, self.line)
+    }
+}
+
+/// Check a program for code that parses but likely does not do what was
+/// meant
+///
+/// Lines that fail to parse are skipped; [`Parser::validate`] reports them.
+///
+/// ```rust
+/// use sui_lang::analysis::{lint, LintKind};
+///
+/// let lints = lint("@ 1\n. 2");
+/// assert_eq!(lints[0].kind, LintKind::UndefinedLabel(1));
+/// assert_eq!(lints[1].kind, LintKind::Unreachable);
+/// ```
+pub fn lint(code: &str) -> Vec<Lint> {
+    let lines: Vec<&str> = code.lines().collect();
+    let (instructions, _) = Parser::parse_recovering(code);
+    lint_lines(&lines, &instructions)
+}
+
+/// [`lint`] for source already parsed line by line, one instruction per
+/// line as from [`Parser::parse_recovering`]
+pub fn lint_lines<S: AsRef<str>>(lines: &[S], instructions: &[Instruction]) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    // Executable lines of each scope, keyed by the function header line
+    let mut scopes: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+    let mut open = Vec::new();
+    let mut functions = HashSet::new();
+    let mut imports = false;
+    for (i, instr) in instructions.iter().enumerate() {
+        match instr {
+            Instruction::FuncDef { id, .. } => {
+                functions.insert(*id);
+                open.push(i);
+            }
+            Instruction::FuncEnd => {
+                open.pop();
+            }
+            Instruction::Empty | Instruction::Comment | Instruction::Invalid { .. } => {}
+            _ => {
+                imports |= matches!(instr, Instruction::Import { .. });
+                scopes.entry(open.last().copied()).or_default().push(i);
+            }
+        }
+    }
+
+    let globals: HashSet<String> = scopes
+        .values()
+        .flatten()
+        .flat_map(|&i| operands(lines[i].as_ref(), false))
+        .filter(|var| var.starts_with('g'))
+        .collect();
+
+    for (header, scope) in &scopes {
+        let argc = match header.map(|h| &instructions[h]) {
+            Some(Instruction::FuncDef { argc, .. }) => *argc,
+            _ => 0,
+        };
+        let locals: HashSet<String> = scope
+            .iter()
+            .flat_map(|&i| operands(lines[i].as_ref(), false))
+            .filter(|var| var.starts_with('v'))
+            .collect();
+
+        for &i in scope {
+            let mut seen = HashSet::new();
+            for var in operands(lines[i].as_ref(), true) {
+                let (prefix, idx) = Lexer::split_variable(&var);
+                let undefined = match prefix {
+                    'v' => !locals.contains(&var),
+                    'a' => idx >= argc,
+                    // Arguments fill g100 and up; imported modules may assign globals
+                    'g' => idx < 100 && !imports && !globals.contains(&var),
+                    _ => false,
+                };
+                if undefined && seen.insert(var.clone()) {
+                    lints.push(Lint {
+                        line: i + 1,
+                        kind: LintKind::UndefinedVariable(var),
+                    });
+                }
+            }
+            for var in operands(lines[i].as_ref(), false).into_iter().chain(updated(lines[i].as_ref())) {
+                if var.starts_with('a') {
+                    lints.push(Lint {
+                        line: i + 1,
+                        kind: LintKind::ArgumentWrite(var),
+                    });
+                }
+            }
+        }
+    }
+
+    for lines in scopes.values() {
+        let labels: HashSet<i64> = lines
+            .iter()
+            .filter_map(|&i| match instructions[i] {
+                Instruction::Label { id } => Some(id),
+                _ => None,
+            })
+            .collect();
+
+        let mut reachable = true;
+        for &i in lines {
+            let instr = &instructions[i];
+            let mut warn = |kind| lints.push(Lint { line: i + 1, kind });
+
+            if let Instruction::Label { .. } = instr {
+                reachable = true;
+            } else if !reachable {
+                warn(LintKind::Unreachable);
+            }
+
+            match instr {
+                Instruction::Jump { label } | Instruction::CondJump { label, .. } | Instruction::Try { label, .. }
+                    if !labels.contains(label) =>
+                {
+                    warn(LintKind::UndefinedLabel(*label));
+                }
+                Instruction::Switch { labels: table, .. } => {
+                    for label in table.iter().filter(|label| !labels.contains(label)) {
+                        warn(LintKind::UndefinedLabel(*label));
+                    }
+                }
+                // Imported modules may define the function
+                Instruction::Call { func_id, .. } if !imports && !functions.contains(func_id) => {
+                    warn(LintKind::UndefinedFunction(*func_id));
+                }
+                _ => {}
+            }
+
+            if matches!(instr, Instruction::Jump { .. } | Instruction::Return { .. }) {
+                reachable = false;
+            }
+        }
+    }
+
+    for mismatch in infer_types_lines(instructions).mismatches() {
+        lints.push(Lint {
+            line: mismatch.line,
+            kind: LintKind::TypeMismatch(mismatch.operation.clone()),
+        });
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        if Lexer::token_spans(line.as_ref()).first().map(|(_, op)| *op) == Some("P") {
+            lints.push(Lint {
+                line: i + 1,
+                kind: LintKind::PythonFfi,
+            });
+        }
+    }
+
+    lints.sort_by_key(|l| l.line);
+    lints
+}
+
+/// Variables a line reads, or assigns if `reads` is false
+fn operands(line: &str, reads: bool) -> Vec<String> {
+    variables(line, |syntax, k| if reads { syntax.reads(k) } else { syntax.writes(k) })
+}
+
+/// Variables a line modifies in place, such as the array of `{`
+fn updated(line: &str) -> Vec<String> {
+    variables(line, |syntax, k| syntax.updates(k))
+}
+
+/// Variable operands of a line at the positions `pick` selects, without
+/// their type hints
+fn variables(line: &str, pick: impl Fn(&InstructionSyntax, usize) -> bool) -> Vec<String> {
+    let spans = Lexer::token_spans(line);
+    let Some(syntax) = spans.first().and_then(|(_, op)| Parser::syntax(op)) else {
+        return Vec::new();
+    };
+    spans[1..]
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| pick(syntax, *k))
+        .filter_map(|(_, (_, token))| {
+            let token = token.split_once(':').map_or(*token, |(var, _)| var);
+            match Lexer::parse_value(token) {
+                ParsedValue::Variable(var) => Some(var),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(usize, LintKind)> {
+        lint(code).into_iter().map(|l| (l.line, l.kind)).collect()
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let code = "# 0 1 {\n< v0 a0 2\n? v0 1\n^ a0\n: 1\n^ 1\n}\n$ g0 0 5\n. g0";
+        assert_eq!(kinds(code), []);
+    }
+
+    #[test]
+    fn test_labels_are_per_function() {
+        let code = ": 1\n# 0 0 {\n@ 1\n}\n@ 1";
+        assert_eq!(kinds(code), [(3, LintKind::UndefinedLabel(1))]);
+        assert_eq!(kinds("= v0 1\nJ v0 1 2 3\n: 2"), [(2, LintKind::UndefinedLabel(1)), (2, LintKind::UndefinedLabel(3))]);
+    }
+
+    #[test]
+    fn test_unreachable_until_label() {
+        let code = "@ 1\n. 1\n; note\n. 2\n: 1\n^ 0\n. 3";
+        assert_eq!(
+            kinds(code),
+            [(2, LintKind::Unreachable), (4, LintKind::Unreachable), (7, LintKind::Unreachable)]
+        );
+    }
+
+    #[test]
+    fn test_calls_and_python_ffi() {
+        assert_eq!(
+            kinds("$ v0 3\nP v1 \"len\" \"ab\"\n= v2 \"P\""),
+            [(1, LintKind::UndefinedFunction(3)), (2, LintKind::PythonFfi)]
+        );
+        assert_eq!(kinds("_ \"lib.sui\"\n$ v0 3"), []);
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        let code = "# 0 1 {\n+ v0 a0 a1\n^ v3\n}\n$ g0 0 v0\n. g0\n. g5\n. g101\n. v0";
+        assert_eq!(
+            kinds(code),
+            [
+                (2, LintKind::UndefinedVariable("a1".to_string())),
+                (3, LintKind::UndefinedVariable("v3".to_string())),
+                (5, LintKind::UndefinedVariable("v0".to_string())),
+                (7, LintKind::UndefinedVariable("g5".to_string())),
+                (9, LintKind::UndefinedVariable("v0".to_string())),
+            ]
+        );
+        // Globals may be assigned in any scope or by an imported module
+        assert_eq!(kinds("# 0 0 {\n= g1 1\n^ 0\n}\n. g1"), []);
+        assert_eq!(kinds("_ \"lib.sui\"\n. g1"), []);
+        assert_eq!(kinds("{ v0 0 1"), [(1, LintKind::UndefinedVariable("v0".to_string()))]);
+        // Type hints are not part of the name
+        assert_eq!(kinds("= v0:i 1\n+ v1 v0:i 2\n. v1"), []);
+    }
+
+    #[test]
+    fn test_argument_write() {
+        let code = "# 0 1 {\n- a0 a0 1\n^ a0\n}\n# 1 1 {\n{ a0 0 1\n^ a0\n}\n[ v0 1\n$ v1 0 3\n$ v2 1 v0";
+        assert_eq!(
+            kinds(code),
+            [(2, LintKind::ArgumentWrite("a0".to_string())), (6, LintKind::ArgumentWrite("a0".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        assert_eq!(
+            kinds("[ v0 2\n+ v1 \"a\" v0\n. v1"),
+            [(2, LintKind::TypeMismatch("string + array".to_string()))]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Program canonicalization and minification
+
+use crate::interpreter::{Function, Instruction, Lexer, ParseError, ParsedValue, Parser};
+use std::collections::HashMap;
+
+/// First global that holds command-line arguments (`g100` = argc)
+const FIRST_ARG_GLOBAL: i64 = 100;
+
+/// Operand position within an instruction
+enum Slot<'a> {
+    /// Variable written (or, for `{` and `N`, updated in place)
+    Target(&'a mut String),
+    /// Variable or literal read
+    Value(&'a mut String),
+    /// Label definition or jump target
+    Label(&'a mut i64),
+    /// Function definition or call target
+    Function(&'a mut i64),
+}
+
+/// Visit every renumberable operand of an instruction in source order
+fn for_each_slot(instr: &mut Instruction, f: &mut dyn FnMut(Slot<'_>)) {
+    match instr {
+        Instruction::Assign { target, value } => {
+            f(Slot::Target(target));
+            f(Slot::Value(value));
+        }
+        Instruction::Add { result, a, b }
+        | Instruction::Sub { result, a, b }
+        | Instruction::Mul { result, a, b }
+        | Instruction::Div { result, a, b }
+        | Instruction::Mod { result, a, b }
+        | Instruction::Lt { result, a, b }
+        | Instruction::Gt { result, a, b }
+        | Instruction::Le { result, a, b }
+        | Instruction::Ge { result, a, b }
+        | Instruction::Eq { result, a, b }
+        | Instruction::And { result, a, b }
+        | Instruction::Or { result, a, b } => {
+            f(Slot::Target(result));
+            f(Slot::Value(a));
+            f(Slot::Value(b));
+        }
+        Instruction::Not { result, a } => {
+            f(Slot::Target(result));
+            f(Slot::Value(a));
+        }
+        Instruction::CondJump { cond, label } => {
+            f(Slot::Value(cond));
+            f(Slot::Label(label));
+        }
+        Instruction::Jump { label } => f(Slot::Label(label)),
+        Instruction::Switch { value, labels } => {
+            f(Slot::Value(value));
+            labels.iter_mut().for_each(|label| f(Slot::Label(label)));
+        }
+        Instruction::Label { id } => f(Slot::Label(id)),
+        Instruction::FuncDef { id, .. } => f(Slot::Function(id)),
+        Instruction::Call {
+            result,
+            func_id,
+            args,
+        } => {
+            f(Slot::Target(result));
+            f(Slot::Function(func_id));
+            args.iter_mut().for_each(|arg| f(Slot::Value(arg)));
+        }
+        Instruction::Return { value } | Instruction::Output { value } | Instruction::Raise { value } => {
+            f(Slot::Value(value))
+        }
+        Instruction::Try { err, label } => {
+            f(Slot::Target(err));
+            f(Slot::Label(label));
+        }
+        Instruction::ArrayCreate { var, size } => {
+            f(Slot::Target(var));
+            f(Slot::Value(size));
+        }
+        Instruction::ArrayRead { result, arr, idx, indices } => {
+            f(Slot::Target(result));
+            f(Slot::Value(arr));
+            f(Slot::Value(idx));
+            indices.iter_mut().for_each(|i| f(Slot::Value(i)));
+        }
+        Instruction::ArrayWrite { arr, idx, indices, value } => {
+            f(Slot::Target(arr));
+            f(Slot::Value(idx));
+            indices.iter_mut().for_each(|i| f(Slot::Value(i)));
+            f(Slot::Value(value));
+        }
+        Instruction::Iter { iter, source } => {
+            f(Slot::Target(iter));
+            f(Slot::Value(source));
+        }
+        Instruction::Next { done, var, iter } => {
+            f(Slot::Target(done));
+            f(Slot::Target(var));
+            f(Slot::Target(iter));
+        }
+        Instruction::Input { var } => f(Slot::Target(var)),
+        Instruction::RustFFI { result, args, .. } => {
+            f(Slot::Target(result));
+            args.iter_mut().for_each(|arg| f(Slot::Value(arg)));
+        }
+        Instruction::Import { .. }
+        | Instruction::Const { .. }
+        | Instruction::EndTry
+        | Instruction::FuncEnd
+        | Instruction::Comment
+        | Instruction::Empty
+        | Instruction::Invalid { .. } => {}
+    }
+}
+
+/// Variable named by an operand, as the interpreter reads it there
+fn variable(operand: &str, target: bool) -> Option<(char, i64)> {
+    let (prefix, idx) = if target {
+        Lexer::split_variable(operand)
+    } else {
+        match Lexer::parse_value(operand) {
+            ParsedValue::Variable(var) => Lexer::split_variable(&var),
+            _ => return None,
+        }
+    };
+    matches!(prefix, 'v' | 'g' | 'a').then_some((prefix, idx))
+}
+
+/// Assigns dense numbers in order of first appearance
+#[derive(Default)]
+struct Numbering {
+    map: HashMap<i64, i64>,
+}
+
+impl Numbering {
+    fn get(&mut self, old: i64) -> i64 {
+        let next = self.map.len() as i64;
+        *self.map.entry(old).or_insert(next)
+    }
+}
+
+/// A program after canonicalization
+#[derive(Debug, Clone)]
+pub(crate) struct Canonical {
+    /// Function definitions, in source order
+    pub functions: Vec<Function>,
+    /// Top-level code
+    pub main: Vec<Instruction>,
+}
+
+impl Canonical {
+    /// Parse and canonicalize a program
+    ///
+    /// Comments and blank lines are dropped; locals and labels are numbered
+    /// densely per scope, globals below `g100` and function IDs across the
+    /// whole program. Programs with imports share globals and function IDs
+    /// with their modules, so those keep their numbers.
+    pub fn new(code: &str, collapse_temporaries: bool) -> Result<Self, ParseError> {
+        let (main, functions) = Parser::parse(code)?;
+        let mut main = strip(main);
+        let mut functions: Vec<Function> = functions
+            .into_iter()
+            .map(|f| Function::new(f.id, f.arg_count, strip(f.body)))
+            .collect();
+
+        if collapse_temporaries {
+            collapse(&mut main);
+            for func in &mut functions {
+                collapse(&mut func.body);
+            }
+        }
+
+        let linked = main.iter().any(|i| matches!(i, Instruction::Import { .. }));
+        let mut globals = Numbering::default();
+        let mut function_ids = Numbering::default();
+
+        for func in &mut functions {
+            if !linked {
+                func.id = function_ids.get(func.id);
+            }
+            renumber(&mut func.body, linked, &mut globals, &mut function_ids);
+            *func = Function::new(func.id, func.arg_count, std::mem::take(&mut func.body));
+        }
+        renumber(&mut main, linked, &mut globals, &mut function_ids);
+
+        Ok(Self { functions, main })
+    }
+
+    /// Render as source code, functions first
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for func in &self.functions {
+            out.push_str(&format!("# {} {} {{\n", func.id, func.arg_count));
+            for instr in &func.body {
+                out.push_str(&format!("{}\n", instr));
+            }
+            out.push_str("}\n");
+        }
+        for instr in &self.main {
+            out.push_str(&format!("{}\n", instr));
+        }
+        out
+    }
+}
+
+/// Minify a program to reduce the tokens it costs
+///
+/// Strips comments and blank lines, collapses temporaries that are written
+/// once and read right away, and renumbers variables, labels and function
+/// IDs densely. The result behaves exactly like the input.
+///
+/// ```rust
+/// use sui_lang::analysis::minify;
+///
+/// let code = "; sum\n= v7 10\n+ v9 v7 5\n= v3 v9\n. v3\n";
+/// assert_eq!(minify(code).unwrap(), "+ v0 10 5\n. v0\n");
+/// ```
+pub fn minify(code: &str) -> Result<String, ParseError> {
+    Ok(Canonical::new(code, true)?.render())
+}
+
+/// Drop lines without effect; constant uses are already inlined
+fn strip(body: Vec<Instruction>) -> Vec<Instruction> {
+    body.into_iter()
+        .filter(|i| !matches!(i, Instruction::Comment | Instruction::Empty | Instruction::Const { .. }))
+        .collect()
+}
+
+/// Renumber locals and labels of one scope, and globals and function IDs
+/// unless the program is `linked` with modules
+fn renumber(
+    body: &mut [Instruction],
+    linked: bool,
+    globals: &mut Numbering,
+    function_ids: &mut Numbering,
+) {
+    let mut locals = Numbering::default();
+    let mut labels = Numbering::default();
+
+    let mut rename = |operand: &mut String, target: bool| {
+        let Some((prefix, idx)) = variable(operand, target) else {
+            return;
+        };
+        let idx = match prefix {
+            'v' => locals.get(idx),
+            'g' if !linked && idx < FIRST_ARG_GLOBAL => globals.get(idx),
+            _ => idx,
+        };
+        *operand = format!("{}{}", prefix, idx);
+    };
+
+    for instr in body.iter_mut() {
+        for_each_slot(instr, &mut |slot| match slot {
+            Slot::Target(operand) => rename(operand, true),
+            Slot::Value(operand) => rename(operand, false),
+            Slot::Label(label) => *label = labels.get(*label),
+            Slot::Function(id) if !linked => *id = function_ids.get(*id),
+            Slot::Function(_) => {}
+        });
+    }
+}
+
+/// Local variable read or written by an operand
+fn local(operand: &str, target: bool) -> Option<i64> {
+    match variable(operand, target) {
+        Some(('v', idx)) => Some(idx),
+        _ => None,
+    }
+}
+
+/// Number of times each local is mentioned in a scope
+fn local_uses(body: &mut [Instruction]) -> HashMap<i64, usize> {
+    let mut uses = HashMap::new();
+    for instr in body.iter_mut() {
+        for_each_slot(instr, &mut |slot| {
+            let var = match slot {
+                Slot::Target(operand) => local(operand, true),
+                Slot::Value(operand) => local(operand, false),
+                _ => None,
+            };
+            if let Some(idx) = var {
+                *uses.entry(idx).or_insert(0) += 1;
+            }
+        });
+    }
+    uses
+}
+
+/// Variable written by an instruction that does nothing but define it
+fn defined_local(instr: &mut Instruction) -> Option<i64> {
+    if matches!(instr, Instruction::ArrayWrite { .. } | Instruction::Next { .. }) {
+        return None;
+    }
+    let mut defined = None;
+    for_each_slot(instr, &mut |slot| {
+        if let Slot::Target(operand) = slot {
+            defined = local(operand, true);
+        }
+    });
+    defined
+}
+
+/// Remove temporaries that are written once and read by the next instruction
+///
+/// `OP vT ...` followed by `= vD vT` becomes `OP vD ...`, and `= vT x`
+/// followed by an instruction reading `vT` reads `x` directly, when `vT` is
+/// mentioned nowhere else in the scope. Self-assignments are dropped.
+fn collapse(body: &mut Vec<Instruction>) {
+    'restart: loop {
+        let uses = local_uses(body);
+        for i in 0..body.len() {
+            // `= vX vX`
+            if let Instruction::Assign { target, value } = &body[i] {
+                if variable(target, true).is_some() && variable(target, true) == variable(value, false)
+                {
+                    body.remove(i);
+                    continue 'restart;
+                }
+            }
+
+            if i + 1 >= body.len() {
+                break;
+            }
+            let Some(temp) = defined_local(&mut body[i]) else {
+                continue;
+            };
+            if uses.get(&temp) != Some(&2) {
+                continue;
+            }
+
+            // `OP vT ...` then `= vD vT`
+            if let Instruction::Assign { target, value } = &body[i + 1] {
+                let dest_ok = matches!(variable(target, true), Some(('v' | 'g', _)));
+                if dest_ok && local(value, false) == Some(temp) {
+                    let dest = target.clone();
+                    for_each_slot(&mut body[i], &mut |slot| {
+                        if let Slot::Target(operand) = slot {
+                            *operand = dest.clone();
+                        }
+                    });
+                    body.remove(i + 1);
+                    continue 'restart;
+                }
+            }
+
+            // `= vT x` then an instruction reading `vT`
+            if let Instruction::Assign { value, .. } = &body[i] {
+                let source = value.clone();
+                let mut replaced = false;
+                for_each_slot(&mut body[i + 1], &mut |slot| {
+                    if let Slot::Value(operand) = slot {
+                        if local(operand, false) == Some(temp) {
+                            *operand = source.clone();
+                            replaced = true;
+                        }
+                    }
+                });
+                if replaced {
+                    body.remove(i);
+                    continue 'restart;
+                }
+            }
+        }
+        break;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    fn run(code: &str) -> Vec<String> {
+        Interpreter::new().run(code, &["7".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_renumbering() {
+        let code = "# 4 1 {\n: 9\n+ v5 a0 g3\n^ v5\n}\n= g3 1\n= v8 g100\n$ v2 4 v8\n. v2";
+        assert_eq!(
+            minify(code).unwrap(),
+            "# 0 1 {\n: 0\n+ v0 a0 g0\n^ v0\n}\n= g0 1\n$ v0 0 g100\n. v0\n"
+        );
+    }
+
+    #[test]
+    fn test_temporaries_keep_array_copies() {
+        // `= v1 v0` copies the array, so the write must not reach v0
+        let code = "[ v0 2\n= v1 v0\n{ v1 0 5\n. v0";
+        let minified = minify(code).unwrap();
+        assert_eq!(run(&minified), run(code));
+        assert!(minified.contains("= v1 v0"));
+    }
+
+    #[test]
+    fn test_imports_keep_globals_and_functions() {
+        let canonical = Canonical::new("_ \"lib.sui\"\n= g5 1\n$ v3 7 g5", false).unwrap();
+        assert_eq!(canonical.render(), "_ \"lib.sui\"\n= g5 1\n$ v0 7 g5\n");
+    }
+
+    #[test]
+    fn test_examples_behave_the_same() {
+        for path in ["examples/fibonacci.sui", "examples/fizzbuzz.sui", "examples/list_sum.sui"] {
+            let code = std::fs::read_to_string(path).unwrap();
+            let minified = minify(&code).unwrap();
+            assert_eq!(run(&minified), run(&code), "{}", path);
+            assert!(minified.len() <= code.len());
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Static analysis of Sui programs
+//!
+//! [`token_report`] estimates how many LLM tokens a program costs, line by
+//! line, and compares the total against the equivalent transpiled Python.
+//! [`minify`] rewrites a program into its smallest canonical form, and
+//! [`semantic_diff`] compares two programs modulo naming and formatting.
+//! [`lint`] warns about code that parses but is likely wrong, such as jumps
+//! to undefined labels. [`infer_types`] computes the types each variable may
+//! hold, which `lint` uses to find type-confused operations and
+//! [`check_hints`] uses to check type hints such as `v0:i`. [`verify`] checks
+//! that array indexes stay in bounds, jumps land on labels and calls pass the
+//! right number of arguments, with counterexample traces where it can.
+//!
+//! ```rust
+//! use sui_lang::analysis::token_report;
+//!
+//! let report = token_report("= v0 10\n+ v1 v0 5\n. v1\n");
+//! assert_eq!(report.lines.len(), 3);
+//! assert!(report.python_tokens.is_some());
+//! ```
+
+mod diff;
+mod hints;
+mod lint;
+mod minify;
+mod tokens;
+mod typeinfer;
+mod verify;
+
+pub use diff::{semantic_diff, Difference, Scope, SemanticDiff};
+pub use hints::{check_hints, hint_types, HintViolation};
+pub use lint::{lint, lint_lines, Lint, LintKind};
+pub use minify::minify;
+pub use tokens::{
+    token_report, token_report_with, CharRatio, Cl100kHeuristic, LineTokens, TokenReport, Tokenizer,
+};
+pub use typeinfer::{infer_types, infer_types_lines, TypeInfo, TypeMismatch, TypeSet};
+pub use verify::{verify, verify_lines, Violation, ViolationKind};
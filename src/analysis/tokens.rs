@@ -0,0 +1,251 @@
+//! Token-cost estimation
+
+use crate::transpiler::Sui2Py;
+use std::fmt;
+
+/// Counts the tokens a language model spends on a piece of text
+///
+/// Implement this to plug in an exact tokenizer; the built-in ones are
+/// estimates that need no vocabulary files.
+pub trait Tokenizer {
+    /// Name shown in reports
+    fn name(&self) -> &str;
+
+    /// Number of tokens in `text`
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Estimate modelled on the `cl100k_base` BPE used by GPT-4-class models
+///
+/// Text is split the way `cl100k_base` pre-tokenizes it (a leading space
+/// joins the following word, digits group in threes, runs of punctuation
+/// stay together) and each piece is charged by length.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cl100kHeuristic;
+
+/// Estimate of one token per fixed number of characters
+#[derive(Debug, Clone, Copy)]
+pub struct CharRatio {
+    /// Average characters per token
+    pub chars_per_token: f64,
+}
+
+impl Default for CharRatio {
+    fn default() -> Self {
+        Self { chars_per_token: 4.0 }
+    }
+}
+
+/// Character classes used by the pre-tokenizer
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Letter,
+    Digit,
+    Punct,
+    Space,
+    Newline,
+}
+
+fn class(c: char) -> Class {
+    if c == '\n' || c == '\r' {
+        Class::Newline
+    } else if c.is_whitespace() {
+        Class::Space
+    } else if c.is_alphabetic() {
+        Class::Letter
+    } else if c.is_numeric() {
+        Class::Digit
+    } else {
+        Class::Punct
+    }
+}
+
+impl Tokenizer for Cl100kHeuristic {
+    fn name(&self) -> &str {
+        "cl100k heuristic"
+    }
+
+    fn count(&self, text: &str) -> usize {
+        let chars: Vec<char> = text.chars().collect();
+        let mut tokens = 0;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let mut start = i;
+            let mut kind = class(chars[i]);
+
+            // A single space joins the word, number or punctuation after it
+            if kind == Class::Space
+                && chars[i] == ' '
+                && i + 1 < chars.len()
+                && !matches!(class(chars[i + 1]), Class::Space | Class::Newline)
+            {
+                start = i + 1;
+                kind = class(chars[start]);
+            }
+
+            let mut end = start + 1;
+            while end < chars.len() && class(chars[end]) == kind {
+                end += 1;
+            }
+            let len = end - start;
+
+            tokens += match kind {
+                Class::Letter => (len + 4) / 5,
+                Class::Digit => (len + 2) / 3,
+                Class::Punct => (len + 1) / 2,
+                Class::Space | Class::Newline => 1,
+            };
+            i = end;
+        }
+
+        tokens
+    }
+}
+
+impl Tokenizer for CharRatio {
+    fn name(&self) -> &str {
+        "character ratio"
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// Token cost of one source line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineTokens {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Source text of the line
+    pub source: String,
+    /// Tokens for the line including its newline
+    pub tokens: usize,
+}
+
+/// Token costs of a Sui program compared with its Python equivalent
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenReport {
+    /// Name of the tokenizer that produced the counts
+    pub tokenizer: String,
+    /// Non-empty source lines
+    pub lines: Vec<LineTokens>,
+    /// Tokens for the whole program
+    pub total: usize,
+    /// Tokens for the transpiled Python, without comments and blank lines;
+    /// `None` if the program does not transpile
+    pub python_tokens: Option<usize>,
+    /// Lines of transpiled Python counted in `python_tokens`
+    pub python_lines: usize,
+}
+
+impl TokenReport {
+    /// Tokens saved relative to Python, as a fraction of the Python cost
+    ///
+    /// Negative when the Sui program is the more expensive one.
+    pub fn savings(&self) -> Option<f64> {
+        match self.python_tokens {
+            Some(python) if python > 0 => Some(1.0 - self.total as f64 / python as f64),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TokenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Token report ({})", self.tokenizer)?;
+        writeln!(f, "{:>6}  {:>6}  source", "line", "tokens")?;
+        for line in &self.lines {
+            writeln!(f, "{:>6}  {:>6}  {}", line.line, line.tokens, line.source)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Sui:     {} tokens ({} lines)", self.total, self.lines.len())?;
+        match self.python_tokens {
+            Some(python) => {
+                writeln!(f, "Python:  {} tokens ({} lines)", python, self.python_lines)?;
+                if let Some(savings) = self.savings() {
+                    if savings >= 0.0 {
+                        write!(f, "Savings: {:.1}% fewer tokens than Python", savings * 100.0)?;
+                    } else {
+                        write!(f, "Savings: {:.1}% more tokens than Python", -savings * 100.0)?;
+                    }
+                }
+                Ok(())
+            }
+            None => write!(f, "Python:  not available (program does not transpile)"),
+        }
+    }
+}
+
+/// Estimate token costs with the [`Cl100kHeuristic`] tokenizer
+pub fn token_report(code: &str) -> TokenReport {
+    token_report_with(code, &Cl100kHeuristic)
+}
+
+/// Estimate token costs with a custom tokenizer
+pub fn token_report_with(code: &str, tokenizer: &dyn Tokenizer) -> TokenReport {
+    let lines: Vec<LineTokens> = code
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| LineTokens {
+            line: i + 1,
+            source: line.to_string(),
+            tokens: tokenizer.count(&format!("{}\n", line)),
+        })
+        .collect();
+    let total = lines.iter().map(|l| l.tokens).sum();
+
+    let python: Option<Vec<String>> = Sui2Py::new().transpile_to_python(code).ok().map(|py| {
+        py.lines()
+            .filter(|line| {
+                let trimmed = line.trim();
+                !trimmed.is_empty() && !trimmed.starts_with('#')
+            })
+            .map(|line| format!("{}\n", line))
+            .collect()
+    });
+
+    TokenReport {
+        tokenizer: tokenizer.name().to_string(),
+        lines,
+        total,
+        python_tokens: python.as_ref().map(|py| py.iter().map(|l| tokenizer.count(l)).sum()),
+        python_lines: python.map_or(0, |py| py.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cl100k_heuristic() {
+        let t = Cl100kHeuristic;
+        assert_eq!(t.count(""), 0);
+        assert_eq!(t.count("= v0 10"), 4);
+        assert_eq!(t.count("print(x)\n"), 5);
+        assert_eq!(t.count("1234567"), 3);
+        assert_eq!(t.count("    x"), 2);
+    }
+
+    #[test]
+    fn test_report_against_python() {
+        let code = "; add\n= v0 10\n\n+ v1 v0 5\n. v1\n";
+        let report = token_report(code);
+        assert_eq!(report.lines.len(), 4);
+        assert_eq!(report.lines[1].line, 2);
+        assert_eq!(report.total, report.lines.iter().map(|l| l.tokens).sum::<usize>());
+        assert!(report.savings().unwrap() > 0.0);
+        assert!(report.to_string().contains("fewer tokens than Python"));
+    }
+
+    #[test]
+    fn test_custom_tokenizer() {
+        let report = token_report_with("= v0 1\n# 0 0 {", &CharRatio::default());
+        assert_eq!(report.tokenizer, "character ratio");
+        assert_eq!(report.lines[0].tokens, 2);
+        assert_eq!(report.python_tokens, None);
+    }
+}
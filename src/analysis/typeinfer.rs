@@ -0,0 +1,715 @@
+//! Abstract types of variables by forward dataflow
+
+use crate::interpreter::{Instruction, Lexer, ParsedValue, Parser};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+/// Set of runtime types a value may have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct TypeSet(u8);
+
+impl TypeSet {
+    /// No value reaches the variable
+    pub const EMPTY: TypeSet = TypeSet(0);
+    pub const INT: TypeSet = TypeSet(1);
+    pub const FLOAT: TypeSet = TypeSet(2);
+    pub const STRING: TypeSet = TypeSet(4);
+    pub const ARRAY: TypeSet = TypeSet(8);
+    /// `int|float`
+    pub const NUMBER: TypeSet = TypeSet(3);
+    /// Any type
+    pub const UNKNOWN: TypeSet = TypeSet(15);
+
+    /// Whether every type of `other` is in this set
+    pub fn contains(self, other: TypeSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the sets share a type
+    pub fn intersects(self, other: TypeSet) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The single types in this set
+    pub fn members(self) -> impl Iterator<Item = TypeSet> {
+        [TypeSet::INT, TypeSet::FLOAT, TypeSet::STRING, TypeSet::ARRAY]
+            .into_iter()
+            .filter(move |t| self.contains(*t))
+    }
+}
+
+impl BitOr for TypeSet {
+    type Output = TypeSet;
+
+    fn bitor(self, other: TypeSet) -> TypeSet {
+        TypeSet(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for TypeSet {
+    fn bitor_assign(&mut self, other: TypeSet) {
+        self.0 |= other.0;
+    }
+}
+
+/// Renders as `int|float`, `unknown` for any type and `none` for no type
+impl fmt::Display for TypeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TypeSet::UNKNOWN => return write!(f, "unknown"),
+            TypeSet::EMPTY => return write!(f, "none"),
+            _ => {}
+        }
+        let names: Vec<&str> = self
+            .members()
+            .map(|t| match t {
+                TypeSet::INT => "int",
+                TypeSet::FLOAT => "float",
+                TypeSet::STRING => "string",
+                _ => "array",
+            })
+            .collect();
+        write!(f, "{}", names.join("|"))
+    }
+}
+
+/// Operation whose operand types never fit together, so a value is silently
+/// coerced, such as adding a string to an array
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    /// Line number (1-based)
+    pub line: usize,
+    /// The operation with its operand types, such as `string + array`
+    pub operation: String,
+}
+
+/// Types inferred by [`infer_types`]
+#[derive(Debug, Clone, Default)]
+pub struct TypeInfo {
+    /// Types of locals and arguments, by function ID (`None` for top level)
+    scopes: BTreeMap<Option<i64>, BTreeMap<String, TypeSet>>,
+    /// Types of globals, which are shared by all scopes
+    globals: BTreeMap<String, TypeSet>,
+    /// Types each function returns
+    returns: BTreeMap<i64, TypeSet>,
+    mismatches: Vec<TypeMismatch>,
+    /// Types of variable operands as each line starts, by line (1-based)
+    before: HashMap<(usize, String), TypeSet>,
+    /// Types of the variables each line assigns, once it has
+    after: HashMap<(usize, String), TypeSet>,
+}
+
+impl TypeInfo {
+    /// Types a variable holds anywhere in the scope of function `func`
+    /// (`None` for top-level code); globals are the same in every scope
+    pub fn var(&self, func: Option<i64>, name: &str) -> Option<TypeSet> {
+        if name.starts_with('g') {
+            return self.globals.get(name).copied();
+        }
+        self.scopes.get(&func)?.get(name).copied()
+    }
+
+    /// Locals and arguments of a scope with their types, in name order
+    pub fn locals(&self, func: Option<i64>) -> impl Iterator<Item = (&str, TypeSet)> {
+        self.scopes.get(&func).into_iter().flatten().map(|(name, t)| (name.as_str(), *t))
+    }
+
+    /// Globals with their types, in name order
+    pub fn globals(&self) -> impl Iterator<Item = (&str, TypeSet)> {
+        self.globals.iter().map(|(name, t)| (name.as_str(), *t))
+    }
+
+    /// Types a function returns
+    pub fn returns(&self, func: i64) -> Option<TypeSet> {
+        self.returns.get(&func).copied()
+    }
+
+    /// Operations whose operand types never fit together, in line order
+    pub fn mismatches(&self) -> &[TypeMismatch] {
+        &self.mismatches
+    }
+
+    /// Types an operand of line `line` (1-based) may hold as the line
+    /// starts; `None` if it is not an operand or the line is unreachable
+    pub fn before(&self, line: usize, var: &str) -> Option<TypeSet> {
+        self.before.get(&(line, var.to_string())).copied()
+    }
+
+    /// Types a variable line `line` (1-based) assigns may hold after it
+    pub fn after(&self, line: usize, var: &str) -> Option<TypeSet> {
+        self.after.get(&(line, var.to_string())).copied()
+    }
+}
+
+/// Infer the types each variable may hold
+///
+/// Locals are tracked through the control flow of their function; globals,
+/// arguments, return values and array elements are merged over the whole
+/// program. Variables read before any assignment are 0, so they start as
+/// `int`. Lines that fail to parse are skipped.
+///
+/// ```rust
+/// use sui_lang::analysis::{infer_types, TypeSet};
+///
+/// let info = infer_types("= v0 1\n: 0\n/ v0 v0 2\n? v0 0\n. v0");
+/// assert_eq!(info.var(None, "v0"), Some(TypeSet::NUMBER));
+/// assert_eq!(info.var(None, "v0").unwrap().to_string(), "int|float");
+/// ```
+pub fn infer_types(code: &str) -> TypeInfo {
+    let (instructions, _) = Parser::parse_recovering(code);
+    infer_types_lines(&instructions)
+}
+
+/// [`infer_types`] for source already parsed line by line, one instruction
+/// per line as from [`Parser::parse_recovering`]
+pub fn infer_types_lines(instructions: &[Instruction]) -> TypeInfo {
+    // Executable lines of each scope, keyed by the function header line
+    let mut scopes: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+    let mut open = Vec::new();
+    for (i, instr) in instructions.iter().enumerate() {
+        match instr {
+            Instruction::FuncDef { .. } => open.push(i),
+            Instruction::FuncEnd => {
+                open.pop();
+            }
+            Instruction::Empty | Instruction::Comment | Instruction::Invalid { .. } => {}
+            _ => scopes.entry(open.last().copied()).or_default().push(i),
+        }
+    }
+    let scopes: Vec<Scope> = scopes
+        .into_iter()
+        .map(|(header, lines)| {
+            let func = header.and_then(|h| match instructions[h] {
+                Instruction::FuncDef { id, .. } => Some(id),
+                _ => None,
+            });
+            Scope { func, lines }
+        })
+        .collect();
+
+    let mut analyzer = Analyzer {
+        instructions,
+        tables: Tables::default(),
+        arities: HashMap::new(),
+        called: HashSet::new(),
+        func_globals: HashSet::new(),
+        report: None,
+    };
+    for scope in &scopes {
+        for &line in &scope.lines {
+            let instr = &instructions[line];
+            if let Instruction::Call { func_id, .. } = instr {
+                analyzer.called.insert(*func_id);
+            }
+            if scope.func.is_some() {
+                analyzer.func_globals.extend(written(instr).filter(|v| v.starts_with('g')).cloned());
+            }
+        }
+    }
+    for instr in instructions {
+        if let Instruction::FuncDef { id, argc } = instr {
+            analyzer.arities.insert(*id, *argc);
+        }
+    }
+
+    // The tables only grow, so this reaches a fixed point
+    loop {
+        let before = analyzer.tables.clone();
+        for scope in &scopes {
+            analyzer.run(scope);
+        }
+        if analyzer.tables == before {
+            break;
+        }
+    }
+
+    // Report from the final state before each line
+    let mut info = TypeInfo::default();
+    for scope in &scopes {
+        let states = analyzer.run(scope);
+        analyzer.report = Some(info);
+        for (pos, state) in states.into_iter().enumerate().take(scope.lines.len()) {
+            if let Some(mut state) = state {
+                analyzer.step(scope, &mut state, scope.lines[pos]);
+            }
+        }
+        info = analyzer.report.take().unwrap_or_default();
+    }
+    info.returns = analyzer.tables.returns.into_iter().collect();
+    info.mismatches.sort_by_key(|m| m.line);
+    info.mismatches.dedup();
+    info
+}
+
+/// Executable lines of a function body or of the top-level code
+struct Scope {
+    func: Option<i64>,
+    lines: Vec<usize>,
+}
+
+/// Types of the variables assigned so far on a path through a scope
+type State = HashMap<String, TypeSet>;
+
+/// Facts shared by all scopes, grown until no scope adds to them
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Tables {
+    /// Types assigned to each global
+    globals: HashMap<String, TypeSet>,
+    /// Types passed for each argument, by function ID and position
+    args: HashMap<(i64, i64), TypeSet>,
+    /// Types each function returns
+    returns: HashMap<i64, TypeSet>,
+    /// Types of the elements of arrays, and of the items of iterators, by
+    /// function ID (`None` for top level and globals) and variable
+    elements: HashMap<(Option<i64>, String), TypeSet>,
+}
+
+struct Analyzer<'a> {
+    instructions: &'a [Instruction],
+    tables: Tables,
+    /// Argument count of each function
+    arities: HashMap<i64, i64>,
+    /// Functions some line calls
+    called: HashSet<i64>,
+    /// Globals assigned inside some function, which calls may change
+    func_globals: HashSet<String>,
+    /// Types and mismatches, collected once the tables are final
+    report: Option<TypeInfo>,
+}
+
+impl Analyzer<'_> {
+    /// Propagate states through a scope, returning the state before each
+    /// line and after its end (`None` where unreachable)
+    fn run(&mut self, scope: &Scope) -> Vec<Option<State>> {
+        let instructions = self.instructions;
+        let n = scope.lines.len();
+        let mut labels = HashMap::new();
+        for (pos, &line) in scope.lines.iter().enumerate() {
+            if let Instruction::Label { id } = instructions[line] {
+                labels.insert(id, pos);
+            }
+        }
+
+        let mut states: Vec<Option<State>> = vec![None; n + 1];
+        states[0] = Some(State::new());
+        let mut work = VecDeque::from([0]);
+        while let Some(pos) = work.pop_front() {
+            if pos == n {
+                continue;
+            }
+            let Some(mut state) = states[pos].clone() else {
+                continue;
+            };
+            let instr = &instructions[scope.lines[pos]];
+            self.step(scope, &mut state, scope.lines[pos]);
+
+            let target = |label: &i64| labels.get(label).copied();
+            let successors: Vec<usize> = match instr {
+                Instruction::Return { .. } | Instruction::Raise { .. } => Vec::new(),
+                // Jumps to undefined labels do nothing
+                Instruction::Jump { label } => vec![target(label).unwrap_or(pos + 1)],
+                Instruction::CondJump { label, .. } | Instruction::Try { label, .. } => {
+                    std::iter::once(pos + 1).chain(target(label)).collect()
+                }
+                Instruction::Switch { labels, .. } => std::iter::once(pos + 1).chain(labels.iter().filter_map(target)).collect(),
+                _ => vec![pos + 1],
+            };
+            for next in successors {
+                let changed = match &mut states[next] {
+                    None => {
+                        states[next] = Some(state.clone());
+                        true
+                    }
+                    Some(existing) => self.join(scope, existing, &state),
+                };
+                if changed {
+                    work.push_back(next);
+                }
+            }
+        }
+
+        // Falling off the end of a function returns 0
+        if let (Some(func), Some(_)) = (scope.func, &states[n]) {
+            *self.tables.returns.entry(func).or_default() |= TypeSet::INT;
+        }
+        states
+    }
+
+    /// Merge `other` into `state`, returning whether `state` changed
+    fn join(&self, scope: &Scope, state: &mut State, other: &State) -> bool {
+        let mut changed = false;
+        let vars: HashSet<&String> = state.keys().chain(other.keys()).collect();
+        let vars: Vec<String> = vars.into_iter().cloned().collect();
+        for var in vars {
+            let joined = self.lookup(scope, state, &var) | self.lookup(scope, other, &var);
+            if state.get(&var) != Some(&joined) {
+                state.insert(var, joined);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Types of a variable in a state
+    fn lookup(&self, scope: &Scope, state: &State, var: &str) -> TypeSet {
+        if let Some(types) = state.get(var) {
+            return *types;
+        }
+        let (prefix, idx) = Lexer::split_variable(var);
+        match (prefix, scope.func) {
+            ('a', Some(func)) if !self.called.contains(&func) => TypeSet::UNKNOWN,
+            ('a', Some(func)) => self.tables.args.get(&(func, idx)).copied().unwrap_or_default(),
+            ('g', Some(_)) => self.tables.globals.get(var).copied().unwrap_or(TypeSet::INT),
+            // Command-line arguments
+            ('g', None) if idx > 100 => TypeSet::NUMBER | TypeSet::STRING,
+            _ => TypeSet::INT,
+        }
+    }
+
+    /// Types of an operand: a literal or a variable
+    fn value(&self, scope: &Scope, state: &State, val: &str) -> TypeSet {
+        match Lexer::parse_value(val) {
+            ParsedValue::Integer(_) => TypeSet::INT,
+            ParsedValue::Float(_) => TypeSet::FLOAT,
+            ParsedValue::String(_) => TypeSet::STRING,
+            ParsedValue::Variable(var) => self.lookup(scope, state, &var),
+        }
+    }
+
+    /// Key of a variable in [`Tables::elements`]
+    fn element_key(scope: &Scope, var: &str) -> (Option<i64>, String) {
+        let func = if var.starts_with('g') { None } else { scope.func };
+        (func, var.to_string())
+    }
+
+    /// Types of the elements of an array operand
+    ///
+    /// Reads past the end give 0.
+    fn elements(&self, scope: &Scope, val: &str) -> TypeSet {
+        let key = Self::element_key(scope, val);
+        self.tables.elements.get(&key).copied().unwrap_or_default() | TypeSet::INT
+    }
+
+    /// Assign a variable, with the types of its elements if it is an array
+    fn assign(&mut self, scope: &Scope, state: &mut State, var: &str, types: TypeSet, elements: TypeSet) {
+        state.insert(var.to_string(), types);
+        if var.starts_with('g') {
+            *self.tables.globals.entry(var.to_string()).or_default() |= types;
+        }
+        if types.contains(TypeSet::ARRAY) && !elements.is_empty() {
+            *self.tables.elements.entry(Self::element_key(scope, var)).or_default() |= elements;
+        }
+    }
+
+    /// Record an operation whose operand types never fit together
+    ///
+    /// Locals no path assigns are left to the unassigned variable lint.
+    fn mismatch(&mut self, state: &State, operands: &[&String], line: usize, operation: String) {
+        let unassigned = |var: &&String| var.starts_with('v') && !state.contains_key(var.as_str());
+        if operands.iter().any(unassigned) {
+            return;
+        }
+        if let Some(report) = &mut self.report {
+            report.mismatches.push(TypeMismatch { line: line + 1, operation });
+        }
+    }
+
+    /// Apply one instruction to a state
+    fn step(&mut self, scope: &Scope, state: &mut State, line: usize) {
+        let instructions = self.instructions;
+        let instr = &instructions[line];
+        let ty = |this: &Self, state: &State, val: &str| this.value(scope, state, val);
+
+        if let Some(mut report) = self.report.take() {
+            for var in instr.operands() {
+                if matches!(Lexer::parse_value(var), ParsedValue::Variable(_)) {
+                    *report.before.entry((line + 1, var.clone())).or_default() |= self.lookup(scope, state, var);
+                }
+            }
+            self.report = Some(report);
+        }
+
+        match instr {
+            Instruction::Assign { target, value } => {
+                let types = ty(self, state, value);
+                let elements = match Lexer::parse_value(value) {
+                    ParsedValue::Variable(_) => self.elements(scope, value),
+                    _ => TypeSet::EMPTY,
+                };
+                self.assign(scope, state, target, types, elements);
+            }
+            Instruction::Add { result, a, b }
+            | Instruction::Sub { result, a, b }
+            | Instruction::Mul { result, a, b }
+            | Instruction::Div { result, a, b }
+            | Instruction::Mod { result, a, b }
+            | Instruction::Lt { result, a, b }
+            | Instruction::Gt { result, a, b }
+            | Instruction::Le { result, a, b }
+            | Instruction::Ge { result, a, b } => {
+                let op = instr.to_string();
+                let op = &op[..1];
+                let (ta, tb) = (ty(self, state, a), ty(self, state, b));
+                let (types, fits) = binary(op, ta, tb);
+                if !fits {
+                    self.mismatch(state, &[a, b], line, format!("{} {} {}", ta, op, tb));
+                }
+                self.assign(scope, state, result, types, TypeSet::EMPTY);
+            }
+            Instruction::Eq { result, .. }
+            | Instruction::Not { result, .. }
+            | Instruction::And { result, .. }
+            | Instruction::Or { result, .. } => {
+                self.assign(scope, state, result, TypeSet::INT, TypeSet::EMPTY);
+            }
+            Instruction::Call { result, func_id, args } => {
+                let arity = self.arities.get(func_id).copied().unwrap_or_default();
+                for k in 0..arity {
+                    // Missing arguments read as 0
+                    let types = match args.get(k as usize) {
+                        Some(arg) => ty(self, state, arg),
+                        None => TypeSet::INT,
+                    };
+                    *self.tables.args.entry((*func_id, k)).or_default() |= types;
+                }
+                for var in &self.func_globals {
+                    let assigned = self.tables.globals.get(var).copied().unwrap_or_default();
+                    let types = self.lookup(scope, state, var) | assigned;
+                    state.insert(var.clone(), types);
+                }
+                let types = match self.arities.contains_key(func_id) {
+                    true => self.tables.returns.get(func_id).copied().unwrap_or_default(),
+                    false => TypeSet::UNKNOWN,
+                };
+                self.assign(scope, state, result, types, TypeSet::UNKNOWN);
+            }
+            Instruction::Return { value } => {
+                if let Some(func) = scope.func {
+                    let types = ty(self, state, value);
+                    *self.tables.returns.entry(func).or_default() |= types;
+                }
+            }
+            Instruction::Try { err, .. } => {
+                self.assign(scope, state, err, TypeSet::UNKNOWN, TypeSet::UNKNOWN);
+            }
+            Instruction::ArrayCreate { var, .. } => {
+                self.assign(scope, state, var, TypeSet::ARRAY, TypeSet::INT);
+            }
+            Instruction::ArrayRead { result, arr, indices, .. } => {
+                let array = ty(self, state, arr);
+                if !array.is_empty() && !array.contains(TypeSet::ARRAY) {
+                    self.mismatch(state, &[arr], line, format!("index into {}", array));
+                }
+                let types = match (array.contains(TypeSet::ARRAY), indices.is_empty()) {
+                    (false, _) => TypeSet::INT,
+                    (true, true) => self.elements(scope, arr),
+                    (true, false) => TypeSet::UNKNOWN,
+                };
+                self.assign(scope, state, result, types, TypeSet::UNKNOWN);
+            }
+            Instruction::ArrayWrite { arr, indices, value, .. } => {
+                let array = ty(self, state, arr);
+                if !array.is_empty() && !array.contains(TypeSet::ARRAY) {
+                    self.mismatch(state, &[arr], line, format!("index into {}", array));
+                }
+                let elements = match indices.is_empty() {
+                    true => ty(self, state, value),
+                    false => TypeSet::UNKNOWN,
+                };
+                let key = Self::element_key(scope, arr);
+                *self.tables.elements.entry(key).or_default() |= elements;
+            }
+            Instruction::Iter { iter, source } => {
+                // Numbers count up from 0; arrays yield their elements
+                let source_types = ty(self, state, source);
+                let mut items = TypeSet::EMPTY;
+                if source_types.intersects(TypeSet::NUMBER) {
+                    items |= TypeSet::INT;
+                }
+                if source_types.contains(TypeSet::ARRAY) {
+                    items |= match Lexer::parse_value(source) {
+                        ParsedValue::Variable(_) => self.elements(scope, source),
+                        _ => TypeSet::UNKNOWN,
+                    };
+                }
+                self.assign(scope, state, iter, TypeSet::ARRAY, items);
+            }
+            Instruction::Next { done, var, iter } => {
+                let items = self.elements(scope, iter);
+                self.assign(scope, state, done, TypeSet::INT, TypeSet::EMPTY);
+                self.assign(scope, state, var, items, TypeSet::UNKNOWN);
+            }
+            Instruction::Input { var } => {
+                self.assign(scope, state, var, TypeSet::NUMBER | TypeSet::STRING, TypeSet::EMPTY);
+            }
+            Instruction::RustFFI { result, func, args } => {
+                let args: Vec<TypeSet> = args.iter().map(|a| ty(self, state, a)).collect();
+                let types = builtin(func.trim_matches('"'), &args);
+                self.assign(scope, state, result, types, TypeSet::UNKNOWN);
+            }
+            _ => {}
+        }
+
+        if let Some(mut report) = self.report.take() {
+            for var in instr.operands() {
+                if !matches!(Lexer::parse_value(var), ParsedValue::Variable(_)) {
+                    continue;
+                }
+                let types = self.lookup(scope, state, var);
+                let vars = match var.starts_with('g') {
+                    true => &mut report.globals,
+                    false => report.scopes.entry(scope.func).or_default(),
+                };
+                *vars.entry(var.clone()).or_default() |= types;
+            }
+            for var in written(instr) {
+                *report.after.entry((line + 1, var.clone())).or_default() |= self.lookup(scope, state, var);
+            }
+            self.report = Some(report);
+        }
+    }
+}
+
+/// Variables an instruction assigns
+fn written(instr: &Instruction) -> impl Iterator<Item = &String> {
+    let vars = match instr {
+        Instruction::Assign { target, .. } => vec![target],
+        Instruction::Add { result, .. }
+        | Instruction::Sub { result, .. }
+        | Instruction::Mul { result, .. }
+        | Instruction::Div { result, .. }
+        | Instruction::Mod { result, .. }
+        | Instruction::Lt { result, .. }
+        | Instruction::Gt { result, .. }
+        | Instruction::Le { result, .. }
+        | Instruction::Ge { result, .. }
+        | Instruction::Eq { result, .. }
+        | Instruction::Not { result, .. }
+        | Instruction::And { result, .. }
+        | Instruction::Or { result, .. }
+        | Instruction::Call { result, .. }
+        | Instruction::ArrayRead { result, .. }
+        | Instruction::RustFFI { result, .. } => vec![result],
+        Instruction::ArrayCreate { var, .. } | Instruction::Input { var } => vec![var],
+        Instruction::ArrayWrite { arr, .. } => vec![arr],
+        Instruction::Iter { iter, .. } => vec![iter],
+        Instruction::Next { done, var, iter } => vec![done, var, iter],
+        Instruction::Try { err, .. } => vec![err],
+        _ => Vec::new(),
+    };
+    vars.into_iter()
+}
+
+/// Result types of an arithmetic or comparison operator, and whether some
+/// pair of operand types fits it without coercion
+fn binary(op: &str, a: TypeSet, b: TypeSet) -> (TypeSet, bool) {
+    if a.is_empty() || b.is_empty() {
+        return (TypeSet::EMPTY, true);
+    }
+    let mut result = TypeSet::EMPTY;
+    let mut fits = false;
+    for x in a.members() {
+        for y in b.members() {
+            let numbers = TypeSet::NUMBER.contains(x | y);
+            let strings = x == TypeSet::STRING && y == TypeSet::STRING;
+            result |= match op {
+                "<" | ">" => {
+                    fits |= numbers || strings;
+                    TypeSet::INT
+                }
+                "+" if strings => {
+                    fits = true;
+                    TypeSet::STRING
+                }
+                _ => {
+                    fits |= numbers;
+                    match (x | y, op) {
+                        (TypeSet::INT, "+" | "-" | "*" | "%") => TypeSet::INT,
+                        // Anything else is converted to floats
+                        _ => TypeSet::FLOAT,
+                    }
+                }
+            };
+        }
+    }
+    (result, fits)
+}
+
+/// Result types of a builtin function, given the types of its arguments
+fn builtin(func: &str, args: &[TypeSet]) -> TypeSet {
+    let first = args.first().copied().unwrap_or(TypeSet::INT);
+    // Regex and JSON builtins share names with others
+    match func {
+        "re.match" => return TypeSet::INT,
+        "re.find" => return TypeSet::STRING | TypeSet::INT,
+        "re.findall" => return TypeSet::ARRAY,
+        "re.replace" => return TypeSet::STRING,
+        "json.encode" => return TypeSet::STRING,
+        _ => {}
+    }
+    match func.rsplit('.').next().unwrap_or(func) {
+        "sqrt" | "pow" | "sin" | "cos" | "tan" | "log" | "log10" | "exp" | "float" | "now" | "monotonic" => {
+            TypeSet::FLOAT
+        }
+        "floor" | "ceil" | "len" | "find" | "contains" | "startswith" | "endswith" | "int" | "randint"
+        | "band" | "bor" | "bxor" | "shl" | "shr" | "bnot" | "send" | "sleep_ms" => TypeSet::INT,
+        "round" | "abs" | "max" | "min" | "sum" | "ipow" | "floordiv" => TypeSet::NUMBER,
+        "upper" | "lower" | "strip" | "replace" | "join" | "format" | "fmt" | "str" => TypeSet::STRING,
+        "split" | "array2d" | "fill" => TypeSet::ARRAY,
+        "concat" => TypeSet::STRING | TypeSet::ARRAY,
+        // Arrays and strings give the same kind back
+        "slice" | "reverse" | "sort" | "copy" => first,
+        _ => TypeSet::UNKNOWN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_through_branches() {
+        let code = "= v0 1\n? g0 1\n= v0 \"one\"\n: 1\n. v0\n= v1 2.5\n+ v2 v1 1";
+        let info = infer_types(code);
+        assert_eq!(info.var(None, "v0"), Some(TypeSet::INT | TypeSet::STRING));
+        assert_eq!(info.var(None, "v2"), Some(TypeSet::FLOAT));
+        assert_eq!(info.var(None, "v9"), None);
+    }
+
+    #[test]
+    fn test_functions_and_arrays() {
+        let code = "# 0 1 {\n+ v0 a0 1\n^ v0\n}\n[ g0 3\n{ g0 0 \"x\"\n] v0 g0 1\n$ v1 0 2\nR v2 \"len\" g0";
+        let info = infer_types(code);
+        assert_eq!(info.var(Some(0), "a0"), Some(TypeSet::INT));
+        assert_eq!(info.returns(0), Some(TypeSet::INT));
+        assert_eq!(info.var(None, "v1"), Some(TypeSet::INT));
+        assert_eq!(info.var(None, "g0"), Some(TypeSet::ARRAY));
+        assert_eq!(info.var(None, "v0"), Some(TypeSet::INT | TypeSet::STRING));
+        assert_eq!(info.var(None, "v2"), Some(TypeSet::INT));
+        // A function nobody calls may get any arguments
+        let info = infer_types("# 1 1 {\n^ a0\n}");
+        assert_eq!(info.var(Some(1), "a0"), Some(TypeSet::UNKNOWN));
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let code = "[ v0 2\n= v1 \"s\"\n+ v2 v1 v0\n+ v3 v1 \"t\"\n] v4 v1 0\n< v5 v1 3";
+        let found: Vec<(usize, String)> =
+            infer_types(code).mismatches().iter().map(|m| (m.line, m.operation.clone())).collect();
+        assert_eq!(
+            found,
+            [
+                (3, "string + array".to_string()),
+                (5, "index into string".to_string()),
+                (6, "string < int".to_string()),
+            ]
+        );
+        // Types that fit on some path are not reported
+        assert!(infer_types("= v0 1\n? g0 1\n= v0 \"s\"\n: 1\n+ v1 v0 1").mismatches().is_empty());
+    }
+}
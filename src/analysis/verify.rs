@@ -0,0 +1,859 @@
+//! Bounded verification of array indexes, jump targets and call arities
+
+use crate::interpreter::{Instruction, Lexer, ParsedValue, Parser};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Joins at a line before its ranges are widened to infinity
+const WIDEN_AFTER: usize = 2;
+
+/// Instructions run looking for a counterexample
+const MAX_STEPS: usize = 10_000;
+
+/// Kind of problem found by [`verify`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// Array read or write with an index that may be outside the array;
+    /// reads give 0 and writes are dropped
+    OutOfBounds { array: String, index: i64, size: i64 },
+    /// Jump or try to a label that is not defined in the same function
+    UndefinedLabel(i64),
+    /// Call to a function that is never defined
+    UndefinedFunction(i64),
+    /// Call with a different number of arguments than the function takes
+    ArgCount { func: i64, expected: i64, given: usize },
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::OutOfBounds { array, index, size } => {
+                write!(f, "Index {} out of bounds for {} of size {}", index, array, size)
+            }
+            ViolationKind::UndefinedLabel(label) => write!(f, "Jump to undefined label {}", label),
+            ViolationKind::UndefinedFunction(id) => write!(f, "Call to undefined function {}", id),
+            ViolationKind::ArgCount { func, expected, given } => {
+                write!(f, "Function {} takes {} arguments but is called with {}", func, expected, given)
+            }
+        }
+    }
+}
+
+/// Problem found by [`verify`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Line number (1-based)
+    pub line: usize,
+    /// What was found
+    pub kind: ViolationKind,
+    /// Lines run from the start of the program up to and including the
+    /// violating line, if a concrete run reaches it; empty otherwise
+    pub trace: Vec<usize>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}", self.kind, self.line)
+    }
+}
+
+/// Check that array indexes stay in bounds, that jumps land on labels and
+/// that calls pass as many arguments as their function takes
+///
+/// Indexes are tracked as integer ranges through the control flow of each
+/// function, narrowed by the comparisons that guard branches. An access is
+/// reported only when both the array size and the index range are known,
+/// which covers constant indexes and counters of simple loops. The
+/// top-level code is then run on integers alone for a bounded number of
+/// steps; violations it reaches get the lines that lead to them as a trace.
+/// Lines that fail to parse are skipped.
+///
+/// ```rust
+/// use sui_lang::analysis::{verify, ViolationKind};
+///
+/// let code = "[ g0 3\n= v0 0\n: 0\n> v1 v0 3\n? v1 1\n{ g0 v0 1\n+ v0 v0 1\n@ 0\n: 1";
+/// let violations = verify(code);
+/// assert_eq!(violations[0].line, 6);
+/// assert_eq!(
+///     violations[0].kind,
+///     ViolationKind::OutOfBounds { array: "g0".to_string(), index: 3, size: 3 }
+/// );
+/// assert_eq!(violations[0].trace.last(), Some(&6));
+/// ```
+pub fn verify(code: &str) -> Vec<Violation> {
+    let (instructions, _) = Parser::parse_recovering(code);
+    verify_lines(&instructions)
+}
+
+/// [`verify`] for source already parsed line by line, one instruction per
+/// line as from [`Parser::parse_recovering`]
+pub fn verify_lines(instructions: &[Instruction]) -> Vec<Violation> {
+    // Executable lines of each scope, keyed by the function header line
+    let mut scopes: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+    let mut open = Vec::new();
+    let mut arities = HashMap::new();
+    let mut imports = false;
+    for (i, instr) in instructions.iter().enumerate() {
+        match instr {
+            Instruction::FuncDef { id, argc } => {
+                arities.insert(*id, *argc);
+                open.push(i);
+            }
+            Instruction::FuncEnd => {
+                open.pop();
+            }
+            Instruction::Empty | Instruction::Comment | Instruction::Invalid { .. } => {}
+            _ => {
+                imports |= matches!(instr, Instruction::Import { .. });
+                scopes.entry(open.last().copied()).or_default().push(i);
+            }
+        }
+    }
+
+    let mut func_globals = HashSet::new();
+    for (header, lines) in &scopes {
+        if header.is_some() {
+            for &i in lines {
+                if let Some(var) = written(&instructions[i]) {
+                    if var.starts_with('g') {
+                        func_globals.insert(var.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut violations = Vec::new();
+    let mut main = None;
+    for (header, lines) in &scopes {
+        let scope = Scope::new(instructions, header.is_some(), lines);
+        let mut warn = |i: usize, kind| violations.push(Violation { line: i + 1, kind, trace: Vec::new() });
+
+        for &i in lines {
+            match &instructions[i] {
+                Instruction::Jump { label } | Instruction::CondJump { label, .. } | Instruction::Try { label, .. }
+                    if !scope.labels.contains_key(label) =>
+                {
+                    warn(i, ViolationKind::UndefinedLabel(*label));
+                }
+                Instruction::Switch { labels, .. } => {
+                    for label in labels.iter().filter(|label| !scope.labels.contains_key(label)) {
+                        warn(i, ViolationKind::UndefinedLabel(*label));
+                    }
+                }
+                Instruction::Call { func_id, args, .. } => match arities.get(func_id) {
+                    Some(&expected) if expected != args.len() as i64 => {
+                        warn(i, ViolationKind::ArgCount { func: *func_id, expected, given: args.len() });
+                    }
+                    // Imported modules may define the function
+                    None if !imports => warn(i, ViolationKind::UndefinedFunction(*func_id)),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let analyzer = Analyzer { instructions, scope: &scope, func_globals: &func_globals };
+        let states = analyzer.run();
+        for (pos, state) in states.iter().enumerate().take(lines.len()) {
+            let Some(state) = state else { continue };
+            let (arr, idx) = match &instructions[lines[pos]] {
+                Instruction::ArrayRead { arr, idx, .. } | Instruction::ArrayWrite { arr, idx, .. } => (arr, idx),
+                _ => continue,
+            };
+            let (Abs::Array(Some(size)), Abs::Int(range)) = (analyzer.value(state, arr), analyzer.value(state, idx))
+            else {
+                continue;
+            };
+            if range.is_bounded() && (range.lo < 0 || range.hi >= size) {
+                let index = if range.hi >= size { range.hi } else { range.lo };
+                warn(lines[pos], ViolationKind::OutOfBounds { array: arr.clone(), index, size });
+            }
+        }
+
+        if header.is_none() {
+            main = Some(scope);
+        }
+    }
+
+    // Confirm what a concrete run of the top level reaches
+    if let Some(scope) = main {
+        let lines: HashSet<usize> = violations.iter().map(|v| v.line).collect();
+        for (line, (trace, kind)) in counterexamples(instructions, &scope, &lines) {
+            let mut known = false;
+            for violation in violations.iter_mut().filter(|v| v.line == line) {
+                if let Some(kind) = &kind {
+                    violation.kind = kind.clone();
+                }
+                violation.trace = trace.clone();
+                known = true;
+            }
+            // Indexes the ranges lost track of
+            if let (false, Some(kind)) = (known, kind) {
+                violations.push(Violation { line, kind, trace });
+            }
+        }
+    }
+
+    violations.sort_by_key(|v| v.line);
+    violations
+}
+
+/// Executable lines of a function body or of the top-level code
+struct Scope<'a> {
+    is_func: bool,
+    lines: &'a [usize],
+    /// Position of each label in `lines`
+    labels: HashMap<i64, usize>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(instructions: &[Instruction], is_func: bool, lines: &'a [usize]) -> Self {
+        let labels = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, &i)| match instructions[i] {
+                Instruction::Label { id } => Some((id, pos)),
+                _ => None,
+            })
+            .collect();
+        Scope { is_func, lines, labels }
+    }
+
+    /// Positions that may run after the instruction at `pos`, with the
+    /// truth of the condition that leads to each, if any
+    fn successors(&self, instr: &Instruction, pos: usize) -> Vec<(usize, Option<bool>)> {
+        let target = |label: &i64| self.labels.get(label).copied();
+        match instr {
+            Instruction::Return { .. } | Instruction::Raise { .. } => Vec::new(),
+            // Jumps to undefined labels do nothing
+            Instruction::Jump { label } => vec![(target(label).unwrap_or(pos + 1), None)],
+            Instruction::CondJump { label, .. } => match target(label) {
+                Some(to) => vec![(pos + 1, Some(false)), (to, Some(true))],
+                None => vec![(pos + 1, None)],
+            },
+            Instruction::Try { label, .. } => std::iter::once(pos + 1).chain(target(label)).map(|p| (p, None)).collect(),
+            Instruction::Switch { labels, .. } => {
+                let mut next: Vec<(usize, Option<bool>)> = vec![(pos + 1, None)];
+                for to in labels.iter().filter_map(target) {
+                    if !next.contains(&(to, None)) {
+                        next.push((to, None));
+                    }
+                }
+                next
+            }
+            _ => vec![(pos + 1, None)],
+        }
+    }
+}
+
+/// Integer range with `i64::MIN` and `i64::MAX` standing for infinity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Range {
+    lo: i64,
+    hi: i64,
+}
+
+impl Range {
+    const ANY: Range = Range { lo: i64::MIN, hi: i64::MAX };
+
+    fn exact(n: i64) -> Self {
+        Range { lo: n, hi: n }
+    }
+
+    fn is_bounded(self) -> bool {
+        self.lo != i64::MIN && self.hi != i64::MAX
+    }
+
+    fn is_empty(self) -> bool {
+        self.lo > self.hi
+    }
+
+    fn join(self, other: Range) -> Range {
+        Range { lo: self.lo.min(other.lo), hi: self.hi.max(other.hi) }
+    }
+
+    fn meet(self, other: Range) -> Range {
+        Range { lo: self.lo.max(other.lo), hi: self.hi.min(other.hi) }
+    }
+
+    /// Send the bounds that grew in `newer` to infinity
+    fn widen(self, newer: Range) -> Range {
+        Range {
+            lo: if newer.lo < self.lo { i64::MIN } else { self.lo },
+            hi: if newer.hi > self.hi { i64::MAX } else { self.hi },
+        }
+    }
+
+    /// Values below `bound`
+    fn below(bound: i64) -> Range {
+        match bound {
+            i64::MAX => Range::ANY,
+            _ => Range { lo: i64::MIN, hi: bound - 1 },
+        }
+    }
+
+    /// Values above `bound`
+    fn above(bound: i64) -> Range {
+        match bound {
+            i64::MIN => Range::ANY,
+            _ => Range { lo: bound + 1, hi: i64::MAX },
+        }
+    }
+
+    fn add(self, other: Range) -> Range {
+        Range {
+            lo: if self.lo == i64::MIN || other.lo == i64::MIN { i64::MIN } else { self.lo.saturating_add(other.lo) },
+            hi: if self.hi == i64::MAX || other.hi == i64::MAX { i64::MAX } else { self.hi.saturating_add(other.hi) },
+        }
+    }
+
+    fn sub(self, other: Range) -> Range {
+        Range {
+            lo: if self.lo == i64::MIN || other.hi == i64::MAX { i64::MIN } else { self.lo.saturating_sub(other.hi) },
+            hi: if self.hi == i64::MAX || other.lo == i64::MIN { i64::MAX } else { self.hi.saturating_sub(other.lo) },
+        }
+    }
+
+    fn mul(self, other: Range) -> Range {
+        if !self.is_bounded() || !other.is_bounded() {
+            return Range::ANY;
+        }
+        let products = [
+            self.lo.checked_mul(other.lo),
+            self.lo.checked_mul(other.hi),
+            self.hi.checked_mul(other.lo),
+            self.hi.checked_mul(other.hi),
+        ];
+        match products.into_iter().collect::<Option<Vec<i64>>>() {
+            Some(products) => Range {
+                lo: products.iter().copied().min().unwrap_or(i64::MIN),
+                hi: products.iter().copied().max().unwrap_or(i64::MAX),
+            },
+            None => Range::ANY,
+        }
+    }
+
+    /// Remainder, which takes the sign of the dividend
+    fn rem(self, other: Range) -> Range {
+        if other.lo != other.hi || other.lo == 0 || other.lo == i64::MIN {
+            return Range::ANY;
+        }
+        let max = other.lo.abs() - 1;
+        Range {
+            lo: if self.lo >= 0 { 0 } else { -max },
+            hi: if self.hi <= 0 { 0 } else { max.min(self.hi) },
+        }
+    }
+}
+
+/// Abstract value of a variable
+#[derive(Debug, Clone, PartialEq)]
+enum Abs {
+    Int(Range),
+    /// Array, with its size if known
+    Array(Option<i64>),
+    Unknown,
+}
+
+impl Abs {
+    fn join(&self, other: &Abs) -> Abs {
+        match (self, other) {
+            (Abs::Int(a), Abs::Int(b)) => Abs::Int(a.join(*b)),
+            (Abs::Array(a), Abs::Array(b)) => Abs::Array(if a == b { *a } else { None }),
+            _ => Abs::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    Lt,
+    Gt,
+    Eq,
+}
+
+/// What a comparison result says about its operands: `a op b` is `holds`
+/// when the result is true
+#[derive(Debug, Clone, PartialEq)]
+struct Fact {
+    a: String,
+    op: Cmp,
+    b: String,
+    holds: bool,
+}
+
+/// Abstract values on a path through a scope
+#[derive(Debug, Clone, Default, PartialEq)]
+struct State {
+    vars: HashMap<String, Abs>,
+    /// Facts carried by comparison results, by result variable
+    facts: HashMap<String, Fact>,
+}
+
+struct Analyzer<'a> {
+    instructions: &'a [Instruction],
+    scope: &'a Scope<'a>,
+    /// Globals assigned inside some function, which calls may change
+    func_globals: &'a HashSet<String>,
+}
+
+impl Analyzer<'_> {
+    /// Propagate states through the scope, returning the state before each
+    /// line and after its end (`None` where unreachable)
+    fn run(&self) -> Vec<Option<State>> {
+        let lines = self.scope.lines;
+        let n = lines.len();
+        let mut states: Vec<Option<State>> = vec![None; n + 1];
+        let mut joins = vec![0; n + 1];
+        states[0] = Some(State::default());
+        let mut work = VecDeque::from([0]);
+        while let Some(pos) = work.pop_front() {
+            if pos == n {
+                continue;
+            }
+            let Some(mut state) = states[pos].clone() else {
+                continue;
+            };
+            let instr = &self.instructions[lines[pos]];
+            self.step(&mut state, instr);
+
+            for (next, taken) in self.scope.successors(instr, pos) {
+                let mut state = state.clone();
+                if let (Some(holds), Instruction::CondJump { cond, .. }) = (taken, instr) {
+                    if !self.refine(&mut state, cond, holds) {
+                        continue;
+                    }
+                }
+                let changed = match &mut states[next] {
+                    None => {
+                        states[next] = Some(state);
+                        true
+                    }
+                    Some(existing) => {
+                        joins[next] += 1;
+                        self.join(existing, &state, joins[next] > WIDEN_AFTER)
+                    }
+                };
+                if changed {
+                    work.push_back(next);
+                }
+            }
+        }
+        states
+    }
+
+    /// Merge `other` into `state`, returning whether `state` changed
+    fn join(&self, state: &mut State, other: &State, widen: bool) -> bool {
+        let mut changed = false;
+        let vars: HashSet<&String> = state.vars.keys().chain(other.vars.keys()).collect();
+        let vars: Vec<String> = vars.into_iter().cloned().collect();
+        for var in vars {
+            let old = self.lookup(state, &var);
+            let mut joined = old.join(&self.lookup(other, &var));
+            if let (true, Abs::Int(old), Abs::Int(new)) = (widen, &old, &joined) {
+                joined = Abs::Int(old.widen(*new));
+            }
+            if joined != old || !state.vars.contains_key(&var) {
+                changed |= joined != old;
+                state.vars.insert(var, joined);
+            }
+        }
+        let before = state.facts.len();
+        state.facts.retain(|var, fact| other.facts.get(var) == Some(fact));
+        changed || state.facts.len() != before
+    }
+
+    /// Abstract value of a variable in a state
+    fn lookup(&self, state: &State, var: &str) -> Abs {
+        if let Some(abs) = state.vars.get(var) {
+            return abs.clone();
+        }
+        let (prefix, idx) = Lexer::split_variable(var);
+        match prefix {
+            'v' => Abs::Int(Range::exact(0)),
+            'g' if self.scope.is_func => Abs::Unknown,
+            // Command-line arguments
+            'g' if idx == 100 => Abs::Int(Range { lo: 0, hi: i64::MAX }),
+            'g' if idx < 100 => Abs::Int(Range::exact(0)),
+            _ => Abs::Unknown,
+        }
+    }
+
+    /// Abstract value of an operand: a literal or a variable
+    fn value(&self, state: &State, val: &str) -> Abs {
+        match Lexer::parse_value(val) {
+            ParsedValue::Integer(n) => Abs::Int(Range::exact(n)),
+            ParsedValue::Float(_) | ParsedValue::String(_) => Abs::Unknown,
+            ParsedValue::Variable(var) => self.lookup(state, &var),
+        }
+    }
+
+    fn range(&self, state: &State, val: &str) -> Option<Range> {
+        match self.value(state, val) {
+            Abs::Int(range) => Some(range),
+            _ => None,
+        }
+    }
+
+    /// Assign a variable, dropping the facts that mention it
+    fn assign(&self, state: &mut State, var: &str, abs: Abs) {
+        state.facts.retain(|result, fact| result != var && fact.a != var && fact.b != var);
+        state.vars.insert(var.to_string(), abs);
+    }
+
+    /// Narrow a state to the paths where `cond` is `holds`, returning
+    /// false if there are none
+    fn refine(&self, state: &mut State, cond: &str, holds: bool) -> bool {
+        let Some(fact) = state.facts.get(cond).cloned() else {
+            return true;
+        };
+        let (Some(a), Some(b)) = (self.range(state, &fact.a), self.range(state, &fact.b)) else {
+            return true;
+        };
+        let (a_var, b_var, a, b) = match fact.op {
+            Cmp::Gt => (&fact.b, &fact.a, b, a),
+            _ => (&fact.a, &fact.b, a, b),
+        };
+        let (a, b) = match (fact.op, holds == fact.holds) {
+            (Cmp::Eq, true) => (a.meet(b), a.meet(b)),
+            (Cmp::Eq, false) => (a, b),
+            // a < b
+            (_, true) => (a.meet(Range::below(b.hi)), b.meet(Range::above(a.lo))),
+            // a >= b
+            (_, false) => (a.meet(Range { lo: b.lo, hi: i64::MAX }), b.meet(Range { lo: i64::MIN, hi: a.hi })),
+        };
+        if a.is_empty() || b.is_empty() {
+            return false;
+        }
+        for (var, range) in [(a_var, a), (b_var, b)] {
+            if let ParsedValue::Variable(var) = Lexer::parse_value(var) {
+                state.vars.insert(var, Abs::Int(range));
+            }
+        }
+        true
+    }
+
+    /// Apply one instruction to a state
+    fn step(&self, state: &mut State, instr: &Instruction) {
+        let arith = |this: &Self, state: &State, a: &str, b: &str, op: fn(Range, Range) -> Range| {
+            match (this.range(state, a), this.range(state, b)) {
+                (Some(a), Some(b)) => Abs::Int(op(a, b)),
+                _ => Abs::Unknown,
+            }
+        };
+        let boolean = Abs::Int(Range { lo: 0, hi: 1 });
+
+        match instr {
+            Instruction::Assign { target, value } => {
+                let abs = self.value(state, value);
+                self.assign(state, target, abs);
+            }
+            Instruction::Add { result, a, b } => {
+                let abs = arith(self, state, a, b, Range::add);
+                self.assign(state, result, abs);
+            }
+            Instruction::Sub { result, a, b } => {
+                let abs = arith(self, state, a, b, Range::sub);
+                self.assign(state, result, abs);
+            }
+            Instruction::Mul { result, a, b } => {
+                let abs = arith(self, state, a, b, Range::mul);
+                self.assign(state, result, abs);
+            }
+            Instruction::Mod { result, a, b } => {
+                let abs = arith(self, state, a, b, Range::rem);
+                self.assign(state, result, abs);
+            }
+            Instruction::Lt { result, a, b }
+            | Instruction::Gt { result, a, b }
+            | Instruction::Le { result, a, b }
+            | Instruction::Ge { result, a, b }
+            | Instruction::Eq { result, a, b } => {
+                // a <= b is !(a > b), and a >= b is !(a < b)
+                let (op, holds) = match instr {
+                    Instruction::Lt { .. } => (Cmp::Lt, true),
+                    Instruction::Gt { .. } => (Cmp::Gt, true),
+                    Instruction::Le { .. } => (Cmp::Gt, false),
+                    Instruction::Ge { .. } => (Cmp::Lt, false),
+                    _ => (Cmp::Eq, true),
+                };
+                self.assign(state, result, boolean);
+                // A result that is also an operand makes the fact stale
+                if result != a && result != b {
+                    state.facts.insert(result.clone(), Fact { a: a.clone(), op, b: b.clone(), holds });
+                }
+            }
+            Instruction::Not { result, a } => {
+                let fact = state.facts.get(a).cloned();
+                self.assign(state, result, boolean);
+                if let Some(mut fact) = fact.filter(|f| &f.a != result && &f.b != result) {
+                    fact.holds = !fact.holds;
+                    state.facts.insert(result.clone(), fact);
+                }
+            }
+            Instruction::And { result, .. } | Instruction::Or { result, .. } => {
+                self.assign(state, result, boolean);
+            }
+            Instruction::Call { result, .. } => {
+                for var in self.func_globals {
+                    self.assign(state, var, Abs::Unknown);
+                }
+                self.assign(state, result, Abs::Unknown);
+            }
+            Instruction::ArrayCreate { var, size } => {
+                let size = match self.range(state, size) {
+                    Some(Range { lo, hi }) if lo == hi => Some(lo.max(0)),
+                    _ => None,
+                };
+                self.assign(state, var, Abs::Array(size));
+            }
+            Instruction::RustFFI { result, func, args } => {
+                let name = match Lexer::parse_value(func) {
+                    ParsedValue::String(name) => name,
+                    _ => String::new(),
+                };
+                let first = args.first().map(|arg| self.value(state, arg));
+                let abs = match (name.rsplit('.').next().unwrap_or(""), first) {
+                    ("len", Some(Abs::Array(Some(size)))) => Abs::Int(Range::exact(size)),
+                    ("array2d", Some(Abs::Int(Range { lo, hi }))) if lo == hi => Abs::Array(Some(lo.max(0))),
+                    _ => Abs::Unknown,
+                };
+                self.assign(state, result, abs);
+            }
+            Instruction::Next { done, var, iter } => {
+                self.assign(state, done, boolean);
+                self.assign(state, var, Abs::Unknown);
+                self.assign(state, iter, Abs::Unknown);
+            }
+            _ => {
+                if let Some(var) = written(instr) {
+                    self.assign(state, var, Abs::Unknown);
+                }
+            }
+        }
+    }
+}
+
+/// The variable an instruction assigns, if any besides the extra outputs
+/// of `Next`
+fn written(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Assign { target: var, .. }
+        | Instruction::Add { result: var, .. }
+        | Instruction::Sub { result: var, .. }
+        | Instruction::Mul { result: var, .. }
+        | Instruction::Div { result: var, .. }
+        | Instruction::Mod { result: var, .. }
+        | Instruction::Lt { result: var, .. }
+        | Instruction::Gt { result: var, .. }
+        | Instruction::Le { result: var, .. }
+        | Instruction::Ge { result: var, .. }
+        | Instruction::Eq { result: var, .. }
+        | Instruction::Not { result: var, .. }
+        | Instruction::And { result: var, .. }
+        | Instruction::Or { result: var, .. }
+        | Instruction::Call { result: var, .. }
+        | Instruction::ArrayCreate { var, .. }
+        | Instruction::ArrayRead { result: var, .. }
+        | Instruction::Iter { iter: var, .. }
+        | Instruction::Next { var, .. }
+        | Instruction::Input { var }
+        | Instruction::RustFFI { result: var, .. }
+        | Instruction::Try { err: var, .. } => Some(var),
+        _ => None,
+    }
+}
+
+/// Value in a concrete run
+#[derive(Debug, Clone)]
+enum Concrete {
+    Int(i64),
+    Array(Vec<i64>),
+}
+
+/// Run the top-level code on integers and flat integer arrays, stopping at
+/// anything else (input, calls, strings, floats) or after [`MAX_STEPS`]
+///
+/// Returns the trace to the first time each of `lines` runs, and to each
+/// out-of-bounds access, with the concrete violation for the latter.
+fn counterexamples(
+    instructions: &[Instruction],
+    scope: &Scope,
+    lines: &HashSet<usize>,
+) -> BTreeMap<usize, (Vec<usize>, Option<ViolationKind>)> {
+    let mut found = BTreeMap::new();
+    let mut vars: HashMap<String, Concrete> = HashMap::new();
+    let mut trace = Vec::new();
+    let mut pos = 0;
+
+    let value = |vars: &HashMap<String, Concrete>, val: &str| -> Option<Concrete> {
+        match Lexer::parse_value(val) {
+            ParsedValue::Integer(n) => Some(Concrete::Int(n)),
+            ParsedValue::Variable(var) => match vars.get(&var) {
+                Some(value) => Some(value.clone()),
+                None => match Lexer::split_variable(&var) {
+                    ('v', _) => Some(Concrete::Int(0)),
+                    ('g', 100) => Some(Concrete::Int(0)),
+                    ('g', idx) if idx < 100 => Some(Concrete::Int(0)),
+                    _ => None,
+                },
+            },
+            _ => None,
+        }
+    };
+    let int = |vars: &HashMap<String, Concrete>, val: &str| match value(vars, val) {
+        Some(Concrete::Int(n)) => Some(n),
+        _ => None,
+    };
+
+    while pos < scope.lines.len() && trace.len() < MAX_STEPS {
+        let line = scope.lines[pos];
+        let instr = &instructions[line];
+        trace.push(line + 1);
+
+        if let Instruction::ArrayRead { arr, idx, .. } | Instruction::ArrayWrite { arr, idx, .. } = instr {
+            if let (Some(Concrete::Array(items)), Some(index)) = (value(&vars, arr), int(&vars, idx)) {
+                let size = items.len() as i64;
+                if index < 0 || index >= size {
+                    let kind = ViolationKind::OutOfBounds { array: arr.clone(), index, size };
+                    found.entry(line + 1).or_insert_with(|| (trace.clone(), Some(kind)));
+                }
+            }
+        } else if lines.contains(&(line + 1)) {
+            found.entry(line + 1).or_insert_with(|| (trace.clone(), None));
+        }
+
+        let binary = |a: &str, b: &str, op: fn(i64, i64) -> Option<i64>| {
+            op(int(&vars, a)?, int(&vars, b)?).map(Concrete::Int)
+        };
+        let (target, result) = match instr {
+            Instruction::Assign { target, value: val } => (target, value(&vars, val)),
+            Instruction::Add { result, a, b } => (result, binary(a, b, i64::checked_add)),
+            Instruction::Sub { result, a, b } => (result, binary(a, b, i64::checked_sub)),
+            Instruction::Mul { result, a, b } => (result, binary(a, b, i64::checked_mul)),
+            Instruction::Mod { result, a, b } => (result, binary(a, b, i64::checked_rem)),
+            Instruction::Lt { result, a, b } => (result, binary(a, b, |a, b| Some((a < b) as i64))),
+            Instruction::Gt { result, a, b } => (result, binary(a, b, |a, b| Some((a > b) as i64))),
+            Instruction::Le { result, a, b } => (result, binary(a, b, |a, b| Some((a <= b) as i64))),
+            Instruction::Ge { result, a, b } => (result, binary(a, b, |a, b| Some((a >= b) as i64))),
+            Instruction::Eq { result, a, b } => (result, binary(a, b, |a, b| Some((a == b) as i64))),
+            Instruction::And { result, a, b } => (result, binary(a, b, |a, b| Some((a != 0 && b != 0) as i64))),
+            Instruction::Or { result, a, b } => (result, binary(a, b, |a, b| Some((a != 0 || b != 0) as i64))),
+            Instruction::Not { result, a } => (result, int(&vars, a).map(|a| Concrete::Int((a == 0) as i64))),
+            Instruction::ArrayCreate { var, size } => match int(&vars, size) {
+                Some(size) if size <= MAX_STEPS as i64 => (var, Some(Concrete::Array(vec![0; size.max(0) as usize]))),
+                _ => break,
+            },
+            Instruction::ArrayRead { result, arr, idx, indices } if indices.is_empty() => {
+                let item = match (value(&vars, arr), int(&vars, idx)) {
+                    (Some(Concrete::Array(items)), Some(index)) => {
+                        Some(Concrete::Int(usize::try_from(index).ok().and_then(|i| items.get(i)).copied().unwrap_or(0)))
+                    }
+                    _ => None,
+                };
+                (result, item)
+            }
+            Instruction::ArrayWrite { arr, idx, indices, value: val } if indices.is_empty() => {
+                let (Some(index), Some(item)) = (int(&vars, idx), int(&vars, val)) else { break };
+                match vars.get_mut(arr) {
+                    Some(Concrete::Array(items)) => {
+                        if let Some(slot) = usize::try_from(index).ok().and_then(|i| items.get_mut(i)) {
+                            *slot = item;
+                        }
+                    }
+                    _ => break,
+                }
+                pos += 1;
+                continue;
+            }
+            Instruction::CondJump { cond, label } => {
+                let Some(cond) = int(&vars, cond) else { break };
+                pos = match scope.labels.get(label) {
+                    Some(&to) if cond != 0 => to,
+                    _ => pos + 1,
+                };
+                continue;
+            }
+            Instruction::Jump { label } => {
+                pos = scope.labels.get(label).map_or(pos + 1, |&to| to);
+                continue;
+            }
+            Instruction::Switch { value, labels } => {
+                let Some(value) = int(&vars, value) else { break };
+                let label = usize::try_from(value).ok().and_then(|k| labels.get(k));
+                pos = label.and_then(|label| scope.labels.get(label)).map_or(pos + 1, |&to| to);
+                continue;
+            }
+            Instruction::Label { .. } | Instruction::Output { .. } | Instruction::Const { .. } => {
+                pos += 1;
+                continue;
+            }
+            _ => break,
+        };
+        let Some(result) = result else { break };
+        if target.starts_with('a') {
+            break;
+        }
+        vars.insert(target.clone(), result);
+        pos += 1;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(usize, ViolationKind)> {
+        verify(code).into_iter().map(|v| (v.line, v.kind)).collect()
+    }
+
+    #[test]
+    fn test_loop_counter_bounds() {
+        // Off by one: the loop runs while v0 <= 5
+        let code = "[ v1 5\n= v0 0\n: 0\n> v2 v0 5\n? v2 1\n] v3 v1 v0\n+ v0 v0 1\n@ 0\n: 1";
+        let violations = verify(code);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].line, 6);
+        assert_eq!(violations[0].kind, ViolationKind::OutOfBounds { array: "v1".to_string(), index: 5, size: 5 });
+        assert_eq!(violations[0].trace[..4], [1, 2, 3, 4]);
+        assert_eq!(violations[0].trace.len(), 4 + 6 * 5 + 2);
+
+        // The fixed loop and a guarded read through len are in bounds
+        assert!(kinds("[ v1 5\n= v0 0\n: 0\n< v2 v0 5\n! v3 v2\n? v3 1\n] v4 v1 v0\n+ v0 v0 1\n@ 0\n: 1").is_empty());
+        let code = "# 0 1 {\n[ v0 a0\nR v1 \"len\" v0\n< v2 3 v1\n! v3 v2\n? v3 1\n] v4 v0 3\n: 1\n^ 0\n}";
+        assert!(kinds(code).is_empty());
+        // Unknown sizes and indexes are not reported
+        assert!(kinds("[ v0 g101\n] v1 v0 9\n[ v2 3\n$ v3 0\n] v4 v2 v3\n# 0 0 {\n^ 7\n}").is_empty());
+    }
+
+    #[test]
+    fn test_constant_indexes() {
+        let code = "[ g0 2\n{ g0 2 1\n] v0 g0 -1\n? g5 0\n] v1 g0 1";
+        assert_eq!(
+            kinds(code),
+            [
+                (2, ViolationKind::OutOfBounds { array: "g0".to_string(), index: 2, size: 2 }),
+                (3, ViolationKind::OutOfBounds { array: "g0".to_string(), index: -1, size: 2 }),
+                (4, ViolationKind::UndefinedLabel(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        let code = "# 0 2 {\n^ a0\n}\n$ v0 0 1\n$ v1 0 1 2\n$ v2 3";
+        let violations = verify(code);
+        assert_eq!(
+            violations.iter().map(|v| (v.line, v.kind.clone())).collect::<Vec<_>>(),
+            [
+                (4, ViolationKind::ArgCount { func: 0, expected: 2, given: 1 }),
+                (6, ViolationKind::UndefinedFunction(3)),
+            ]
+        );
+        // The run stops at the first call
+        assert_eq!(violations[0].trace, [4]);
+        assert!(violations[1].trace.is_empty());
+    }
+}
@@ -1,12 +1,30 @@
 //! Sui (粋) - Main interpreter CLI
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Instant;
 
-use sui_lang::interpreter::{Interpreter, Parser as SuiParser};
+use sui_lang::analysis::{check_hints, minify, token_report, verify, Cl100kHeuristic};
+use sui_lang::cli::debug::{debug, DebugArgs};
+use sui_lang::cli::transpile::{self, FromPythonArgs, JsArgs, PythonArgs, WasmArgs};
+use sui_lang::cli::exit::{self, RunResult};
+use sui_lang::cli::{report_diagnostics, ColorArgs, ReportFormat};
+use sui_lang::corpus::{CorpusProgram, CorpusRunner, Manifest, DEFAULT_MAX_STEPS};
+use sui_lang::debugger::explain::narrate;
+use sui_lang::feedback::{self, diagnose};
+use sui_lang::formatter::{format, FormatOptions};
+use sui_lang::grammar::Format;
+use sui_lang::mutate::test_mutants;
+use sui_lang::prompt::{prompt_pack, PackOptions};
+use sui_lang::snap::{self, SnapStatus, Snapshot};
+use sui_lang::spec;
+use sui_lang::transpiler::Sui2Pseudo;
+use sui_lang::interpreter::{
+    instruction_set, Clock, CompiledProgram, DivisionPolicy, Interpreter, Metadata, OutputMode, Parser as SuiParser, RunLogger, INSTRUCTION_TABLE,
+};
 
 #[derive(Parser)]
 #[command(name = "sui")]
@@ -26,10 +44,40 @@ Design Principles:
 Examples:
   sui examples/fibonacci.sui          # Run a Sui file
   sui examples/fib_args.sui 15        # Run with arguments
+  sui --strict examples/fizzbuzz.sui   # Fail on reads of unassigned variables
+  sui --division error prog.sui        # Fail on division by zero
+  sui --virtual-clock prog.sui         # Sleep instantly, with times from 0
+  sui --allow-env --allow-process glue.sui # Allow env.* and proc.run
+  sui --record run.bin prog.sui        # Run and record a trace for sui-debug --replay
+  sui --plugin ./libsqlite.so prog.sui # Load builtins from a plugin (plugins feature)
+  sui --check-args examples/fib_args.sui 15 # Check arguments against ;@ arg declarations
+  sui --help-program examples/fib_args.sui # Print a program's usage
   sui --validate examples/fizzbuzz.sui # Validate syntax
+  sui --validate --format json prog.sui # Errors and warnings as JSON
+  sui --color never prog.sui           # No ANSI colors (also NO_COLOR=1)
+  sui --tokens examples/fizzbuzz.sui   # Estimate token cost vs Python
+  sui --minify examples/fizzbuzz.sui   # Print a minified program
+  sui --pseudo examples/fizzbuzz.sui   # Print readable pseudocode
+  sui --verify examples/fizzbuzz.sui   # Check array bounds, jumps and calls
+  sui --typecheck prog.sui             # Check v0:i type hints against inferred types
+  sui --explain '+'                    # Explain an instruction
   sui --repl                           # Start interactive REPL
+  sui playground build --out site      # Write a static playground site
+  sui snap verify examples/            # Compare programs with their .snap files
+  sui corpus run examples/corpus.toml  # Check programs listed in a manifest
+  sui mutate examples/fib_args.sui     # Find changes its tests do not catch
+  sui check --format llm prog.sui      # Repair hints for a model, from errors and the .snap file
+  sui explain-run --max-steps 200 examples/fib_args.sui 5
+                                       # Narrate a run instruction by instruction
+  sui spec json > semantics.json       # Export instruction semantics with examples
+  sui prompt-pack --corpus examples/corpus.toml --tag loops --budget 2000
+                                       # Assemble an LLM prompt context
 "#)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Sui source file to run
     #[arg(value_name = "FILE")]
     file: Option<PathBuf>,
@@ -42,10 +90,97 @@ struct Cli {
     #[arg(short, long)]
     validate: bool,
 
+    #[command(flatten)]
+    color: ColorArgs,
+
+    /// Estimate the token cost of the source file and compare it with Python
+    #[arg(long)]
+    tokens: bool,
+
+    /// Print the source file minified (comments stripped, variables renumbered)
+    #[arg(long)]
+    minify: bool,
+
+    /// Print the source file as annotated pseudocode with inferred names
+    #[arg(long)]
+    pseudo: bool,
+
+    /// Check array bounds, jump targets and call arities without running
+    #[arg(long)]
+    verify: bool,
+
+    /// Check type hints (`v0:i`, `;@ fn` signatures) against inferred types
+    #[arg(long)]
+    typecheck: bool,
+
+    /// Print the program's usage from its `;@` metadata comments
+    #[arg(long)]
+    help_program: bool,
+
+    /// Explain an instruction, given by character or name
+    #[arg(long, value_name = "OP")]
+    explain: Option<String>,
+
     /// Start interactive REPL
     #[arg(short, long)]
     repl: bool,
 
+    #[command(flatten)]
+    run: RunOptions,
+}
+
+/// How `sui FILE` and `sui run` run the program
+#[derive(Args)]
+struct RunOptions {
+    /// Report as text or JSON: the run's output, error and statistics, or
+    /// the errors and warnings of --validate
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// Check the arguments against the program's `;@ arg` declarations
+    /// before running it
+    #[arg(long)]
+    check_args: bool,
+
+    /// Fail on reads of variables that were never assigned
+    #[arg(long)]
+    strict: bool,
+
+    /// What division by zero produces: nan, error or zero
+    #[arg(long, value_name = "POLICY", default_value = "nan")]
+    division: DivisionPolicy,
+
+    /// Stop the program after this many instructions
+    #[arg(long, value_name = "N")]
+    max_steps: Option<u64>,
+
+    /// Simulate time for the time builtins: it starts at 0 and sleeps return
+    /// at once
+    #[arg(long)]
+    virtual_clock: bool,
+
+    /// Let the program read and set environment variables (env.get, env.set)
+    #[arg(long)]
+    allow_env: bool,
+
+    /// Let the program run commands (proc.run)
+    #[arg(long)]
+    allow_process: bool,
+
+    /// Record the run to this file for `sui-debug --replay`
+    #[arg(long, value_name = "TRACE")]
+    record: Option<PathBuf>,
+
+    /// Append a JSON record of the run (code hash, args, output, error,
+    /// steps, times) to this file
+    #[arg(long, value_name = "FILE")]
+    log_jsonl: Option<PathBuf>,
+
+    /// Load builtins from a plugin, given by path or by name in
+    /// SUI_PLUGIN_PATH or ~/.sui/plugins (repeatable)
+    #[arg(long = "plugin", value_name = "PLUGIN")]
+    plugins: Vec<String>,
+
     /// Enable debug mode
     #[arg(short, long)]
     debug: bool,
@@ -55,6 +190,271 @@ struct Cli {
     verbose: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Run a program, the same as `sui FILE`
+    Run {
+        /// Sui source file, or program compiled by `sui compile`, to run
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Arguments to pass to the Sui program
+        #[arg(value_name = "ARGS")]
+        args: Vec<String>,
+
+        #[command(flatten)]
+        options: RunOptions,
+    },
+
+    /// Parse a program and the modules it imports once into a `.suic` file
+    /// that `sui run` starts without parsing
+    Compile {
+        /// Sui source file to compile
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Output file path (default: FILE with the .suic extension)
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+    },
+
+    /// Transpile a program to another language
+    #[command(subcommand)]
+    Transpile(TranspileCommand),
+
+    /// Convert Python to Sui (also `py2sui`)
+    FromPython(FromPythonArgs),
+
+    /// Step through a program or a recorded run (also `sui-debug`)
+    Debug(DebugArgs),
+
+    /// Start the language server on stdin and stdout (`sui-lsp`, built with
+    /// the lsp feature)
+    Lsp {
+        /// Show variable values from `sui debug --export` on this address
+        #[arg(long, value_name = "ADDR")]
+        attach: Option<String>,
+    },
+
+    /// Format programs in place
+    Fmt {
+        /// Programs, or directories to search for `.sui` files
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+
+        /// List the programs that are not formatted instead of rewriting
+        /// them; fails if there are any
+        #[arg(long)]
+        check: bool,
+
+        /// Spaces to indent each level of function body
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        indent: usize,
+
+        /// Line operands up in columns
+        #[arg(long)]
+        align: bool,
+    },
+
+    /// Report parse errors and likely mistakes, the same as `sui --validate`
+    Lint {
+        /// Program to check
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Report as text or JSON
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+
+    /// Test programs: verify `.snap` files, or run corpus manifests
+    /// (`.toml`)
+    Test {
+        /// Programs, directories to search for `.sui` files, or manifests
+        #[arg(value_name = "PATH", default_value = ".")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Start interactive REPL, the same as `sui --repl`
+    Repl,
+
+    /// Browser playground
+    #[command(subcommand)]
+    Playground(PlaygroundCommand),
+
+    /// Golden-output snapshots (`.snap` files next to programs)
+    #[command(subcommand)]
+    Snap(SnapCommand),
+
+    /// Programs checked against a manifest of arguments, input and
+    /// expected output
+    #[command(subcommand)]
+    Corpus(CorpusCommand),
+
+    /// Reference semantics: executable examples of every instruction
+    #[command(subcommand)]
+    Spec(SpecCommand),
+
+    /// Mutation testing: run a program's tests against small changes to
+    /// it and report the changes no test catches
+    Mutate {
+        /// Program to mutate
+        #[arg(value_name = "FILE")]
+        program: PathBuf,
+
+        /// Test with the cases of this corpus manifest that run the
+        /// program, instead of its `.snap` file
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+
+        /// Fail if fewer than this percentage of mutants are caught
+        #[arg(long, value_name = "PERCENT", default_value_t = 100.0)]
+        min_score: f64,
+
+        /// Instruction limit for each run, so mutants that loop are caught
+        #[arg(long, default_value_t = DEFAULT_MAX_STEPS)]
+        max_steps: u64,
+    },
+
+    /// Parse a program and run it against its `.snap` file (or once
+    /// without arguments), reporting what to fix
+    Check {
+        /// Program to check
+        #[arg(value_name = "FILE")]
+        program: PathBuf,
+
+        /// Report as text, terse hints for a model (llm), or JSON
+        #[arg(long, value_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+
+        /// Instruction limit for each run
+        #[arg(long, default_value_t = DEFAULT_MAX_STEPS)]
+        max_steps: u64,
+    },
+
+    /// Run a program and narrate each instruction it executes: the values
+    /// it read, what it stored where and where it jumped
+    ExplainRun {
+        /// Program to run
+        #[arg(value_name = "FILE")]
+        program: PathBuf,
+
+        /// Arguments to pass to the program
+        #[arg(value_name = "ARGS")]
+        args: Vec<String>,
+
+        /// Stop the run after this many instructions
+        #[arg(long, default_value_t = 1000)]
+        max_steps: u64,
+    },
+
+    /// Print a prompt context for writing Sui: the instruction table,
+    /// examples from a corpus and the grammar
+    PromptPack {
+        /// Corpus manifest to take examples from
+        #[arg(long, value_name = "MANIFEST")]
+        corpus: Option<PathBuf>,
+
+        /// Prefer examples with this tag; repeat for several
+        #[arg(long)]
+        tag: Vec<String>,
+
+        /// Most examples to include
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        examples: usize,
+
+        /// Most tokens (estimated); the grammar, then examples, then
+        /// instruction descriptions are dropped to fit
+        #[arg(long, value_name = "TOKENS")]
+        budget: Option<usize>,
+
+        /// Grammar notation: gbnf, ebnf or lark
+        #[arg(long, value_name = "FORMAT", default_value = "gbnf")]
+        grammar: Format,
+
+        /// Leave out the grammar
+        #[arg(long, conflicts_with = "grammar")]
+        no_grammar: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CheckFormat {
+    Text,
+    Llm,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum TranspileCommand {
+    /// Sui to Python (also `sui2py`)
+    Python(PythonArgs),
+
+    /// Sui to JavaScript or TypeScript (also `sui2js`)
+    Js(JsArgs),
+
+    /// Sui to WebAssembly (also `sui2wasm`)
+    Wasm(WasmArgs),
+}
+
+#[derive(Subcommand)]
+enum SpecCommand {
+    /// Print the syntax, documentation and examples of every instruction
+    /// as JSON
+    Json,
+
+    /// Run the examples against this interpreter
+    Check,
+}
+
+#[derive(Subcommand)]
+enum CorpusCommand {
+    /// Run the programs of a manifest; fails if any does not pass
+    Run {
+        /// Manifest file; program paths are relative to it
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
+
+        /// Only run programs whose name contains this
+        #[arg(long)]
+        filter: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapCommand {
+    /// Run programs and write their snapshots, keeping the `arg` and
+    /// `input` lines of existing ones
+    Update {
+        /// Programs, or directories to search for `.sui` files
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Run programs and compare them with their snapshots; fails on any
+    /// difference or missing snapshot
+    Verify {
+        /// Programs, or directories to search for `.sui` files
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PlaygroundCommand {
+    /// Write a static playground site: the page and the wasm-pack output it
+    /// loads
+    Build {
+        /// Directory to write the site to
+        #[arg(long, value_name = "DIR", default_value = "playground-site")]
+        out: PathBuf,
+
+        /// wasm-pack output (`wasm-pack build --target web --features wasm`)
+        #[arg(long, value_name = "DIR", default_value = "pkg")]
+        pkg: PathBuf,
+    },
+}
+
 fn print_demo() {
     println!("{}", "Sui (粋) - Programming Language for LLMs".cyan().bold());
     println!("{}", "=".repeat(50));
@@ -62,7 +462,13 @@ fn print_demo() {
     println!("Usage:");
     println!("  sui <file.sui> [args...]");
     println!("  sui --validate <file.sui>");
+    println!("  sui --tokens <file.sui>");
+    println!("  sui --minify <file.sui>");
+    println!("  sui --pseudo <file.sui>");
+    println!("  sui --verify <file.sui>");
+    println!("  sui --explain <op>");
     println!("  sui --repl");
+    println!("  sui playground build --out <dir>");
     println!();
     println!("Argument access:");
     println!("  g100 = argument count (argc)");
@@ -102,6 +508,7 @@ $ g1 0 g0
     println!("{}", "Result:".green());
 
     let mut interp = Interpreter::new();
+    interp.set_output_mode(OutputMode::Stdout);
     if let Err(e) = interp.run(fib_code, &[]) {
         eprintln!("Error: {}", e);
     }
@@ -128,6 +535,7 @@ $ g1 0 g0
     println!("{}", "Result:".green());
 
     let mut interp = Interpreter::new();
+    interp.set_output_mode(OutputMode::Stdout);
     if let Err(e) = interp.run(loop_code, &[]) {
         eprintln!("Error: {}", e);
     }
@@ -143,7 +551,76 @@ $ g1 0 g0
     println!("{} Maximum token efficiency", "✓".green());
 }
 
-fn validate_file(path: &PathBuf) -> bool {
+fn validate_file(path: &Path, format: ReportFormat) -> bool {
+    let code = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return false;
+        }
+    };
+    report_diagnostics(&code, format)
+}
+
+fn report_tokens(path: &Path) -> bool {
+    let code = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return false;
+        }
+    };
+
+    println!("{}", token_report(&code));
+    true
+}
+
+fn minify_file(path: &Path) -> bool {
+    let code = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return false;
+        }
+    };
+
+    match minify(&code) {
+        Ok(minified) => {
+            print!("{}", minified);
+            true
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            false
+        }
+    }
+}
+
+fn pseudo_file(path: &Path) -> bool {
+    let code = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return false;
+        }
+    };
+
+    match Sui2Pseudo::new().transpile_to_pseudo(&code) {
+        Ok(pseudo) => {
+            println!("{}", pseudo);
+            true
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            false
+        }
+    }
+}
+
+/// Number of trace lines shown before a violation
+const TRACE_TAIL: usize = 12;
+
+fn verify_file(path: &Path) -> bool {
     let code = match fs::read_to_string(path) {
         Ok(c) => c,
         Err(e) => {
@@ -152,29 +629,256 @@ fn validate_file(path: &PathBuf) -> bool {
         }
     };
 
-    let errors = SuiParser::validate(&code);
+    let violations = verify(&code);
+    if violations.is_empty() {
+        println!("{} Verification found no problems", "✓".green());
+        return true;
+    }
+    println!("{}", "Warnings:".yellow());
+    for v in &violations {
+        println!("  {}", v);
+        if !v.trace.is_empty() {
+            let skip = v.trace.len().saturating_sub(TRACE_TAIL);
+            let lines: Vec<String> = v.trace[skip..].iter().map(|l| l.to_string()).collect();
+            let more = if skip > 0 { format!("... ({} steps) ", skip) } else { String::new() };
+            println!("    counterexample: lines {}{}", more, lines.join(" "));
+        }
+    }
+    false
+}
 
-    if errors.is_empty() {
-        println!("{} Validation successful", "✓".green());
-        true
+fn typecheck_file(path: &Path) -> bool {
+    let code = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return false;
+        }
+    };
+
+    match check_hints(&code) {
+        Ok(violations) if violations.is_empty() => {
+            println!("{} Type hints agree with inferred types", "✓".green());
+            true
+        }
+        Ok(violations) => {
+            println!("{}", "Type hint violations:".red());
+            for v in &violations {
+                println!("  {}", v);
+            }
+            false
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            false
+        }
+    }
+}
+
+fn explain(query: &str) -> bool {
+    match instruction_set::lookup(query) {
+        Some(syntax) => {
+            print!("{}", syntax.explain());
+            true
+        }
+        None => {
+            eprintln!("{}: Unknown instruction: {}", "Error".red(), query);
+            let ops: Vec<&str> = INSTRUCTION_TABLE.iter().map(|s| s.op).collect();
+            eprintln!("Known instructions: {}", ops.join(" "));
+            false
+        }
+    }
+}
+
+/// Read the metadata of the program at `path`, reporting failures
+/// Whether `path` is a program compiled by `sui compile`
+fn is_compiled(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "suic")
+}
+
+fn program_metadata(path: &Path) -> Option<Metadata> {
+    let source = if is_compiled(path) {
+        CompiledProgram::load(path).map(|program| program.source)
     } else {
-        println!("{}", "Validation errors:".red());
-        for e in errors {
-            println!("  {}", e);
+        fs::read_to_string(path)
+    };
+    let code = match source {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return None;
+        }
+    };
+    match SuiParser::metadata(&code) {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            None
         }
-        false
     }
 }
 
-fn run_file(path: &PathBuf, args: &[String], debug: bool) {
+fn help_program(path: &Path) -> bool {
+    let Some(metadata) = program_metadata(path) else {
+        return false;
+    };
+    println!("{}", metadata.usage(&path.display().to_string()));
+    true
+}
+
+/// Messages for stderr, printed as they come or, for a JSON result, kept
+#[derive(Default)]
+struct Messages {
+    json: bool,
+    lines: Vec<String>,
+    /// The first error, which stopped the program from running
+    first_error: Option<String>,
+}
+
+impl Messages {
+    fn note(&mut self, text: String) {
+        if self.json {
+            self.lines.push(text);
+        } else {
+            eprintln!("{}", text);
+        }
+    }
+
+    fn error(&mut self, message: impl std::fmt::Display) {
+        self.first_error.get_or_insert_with(|| message.to_string());
+        if self.json {
+            self.lines.push(format!("Error: {}", message));
+        } else {
+            eprintln!("{}: {}", "Error".red(), message);
+        }
+    }
+}
+
+/// Whether `args` match the program's declared arguments; reports the
+/// problems and its usage if not
+fn check_args(path: &Path, args: &[String], messages: &mut Messages) -> bool {
+    let Some(metadata) = program_metadata(path) else {
+        return false;
+    };
+    let problems = metadata.check_args(args);
+    for problem in &problems {
+        messages.error(problem);
+    }
+    if !problems.is_empty() {
+        messages.note(format!("\n{}", metadata.usage(&path.display().to_string())));
+    }
+    problems.is_empty()
+}
+
+/// Run the program at `path` and report how it went, in JSON with
+/// `--format json`; returns the exit code
+fn run_file(path: &Path, args: &[String], cli: &RunOptions) -> i32 {
+    let json = cli.format == ReportFormat::Json;
+    let mut messages = Messages { json, ..Messages::default() };
     let mut interp = Interpreter::new();
-    interp.set_debug(debug);
+    let mut report = match prepare(&mut interp, path, args, cli, &mut messages) {
+        Ok(()) => {
+            let started = Instant::now();
+            // Use run_file for proper import path resolution
+            let result = if is_compiled(path) {
+                interp.run_compiled_file(path, args)
+            } else {
+                interp.run_file(path, args)
+            };
+            let elapsed = started.elapsed();
 
-    // Use run_file for proper import path resolution
-    if let Err(e) = interp.run_file(path, args) {
-        eprintln!("{}: {}", "Error".red(), e);
-        process::exit(1);
+            // Save the trace also when the run failed, for postmortem debugging
+            if let (Some(record), Some(mut trace)) = (&cli.record, interp.take_trace()) {
+                trace.error = result.as_ref().err().map(|e| e.to_string());
+                match trace.save(record) {
+                    Ok(()) => messages.note(format!("Recorded {} steps to {}", trace.steps(), record.display())),
+                    Err(e) => messages.error(format!("Failed to write trace: {}", e)),
+                }
+            }
+
+            let result = result.as_ref().map(|_| ());
+            if let Err(e) = result {
+                messages.error(e);
+            }
+            RunResult::new(result, interp.get_output().to_vec(), interp.steps(), elapsed)
+        }
+        Err(exit_code) => RunResult { exit_code, error: messages.first_error.take(), ..RunResult::default() },
+    };
+    if json {
+        report.stderr = messages.lines;
+        println!("{}", report.to_json());
+    }
+    report.exit_code
+}
+
+/// Set up `interp` for running `path` as `cli` asks; the exit code if it
+/// cannot run
+fn prepare(
+    interp: &mut Interpreter,
+    path: &Path,
+    args: &[String],
+    cli: &RunOptions,
+    messages: &mut Messages,
+) -> Result<(), i32> {
+    if !path.exists() {
+        messages.error(format!("File not found: {}", path.display()));
+        return Err(exit::FAILURE);
+    }
+    if cli.check_args && !check_args(path, args, messages) {
+        return Err(exit::VALIDATION_FAILED);
+    }
+
+    // A JSON result carries the output instead
+    interp.set_output_mode(if messages.json { OutputMode::Captured } else { OutputMode::Stdout });
+    interp.set_debug(cli.debug);
+    interp.set_strict(cli.strict);
+    interp.set_division_policy(cli.division);
+    interp.set_max_steps(cli.max_steps);
+    if cli.virtual_clock {
+        interp.set_clock(Clock::Virtual { epoch: 0.0 });
+    }
+    interp.allow_env(cli.allow_env);
+    interp.allow_process(cli.allow_process);
+    interp.set_recording(cli.record.is_some());
+    if let Some(log) = &cli.log_jsonl {
+        match RunLogger::open(log) {
+            Ok(logger) => interp.set_run_logger(logger),
+            Err(e) => {
+                messages.error(format!("Failed to open log '{}': {}", log.display(), e));
+                return Err(exit::FAILURE);
+            }
+        }
+    }
+    if !load_plugins(interp, &cli.plugins, messages) {
+        return Err(exit::FAILURE);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "plugins")]
+fn load_plugins(interp: &mut Interpreter, plugins: &[String], messages: &mut Messages) -> bool {
+    use sui_lang::plugins::Plugin;
+
+    for name in plugins {
+        match Plugin::find(name) {
+            Ok(plugin) => plugin.install(interp),
+            Err(e) => {
+                messages.error(e);
+                return false;
+            }
+        }
     }
+    true
+}
+
+#[cfg(not(feature = "plugins"))]
+fn load_plugins(_interp: &mut Interpreter, plugins: &[String], messages: &mut Messages) -> bool {
+    if !plugins.is_empty() {
+        messages.error("Plugin feature is not enabled");
+        messages.note("Compile with: cargo build --features plugins".to_string());
+        return false;
+    }
+    true
 }
 
 #[cfg(feature = "repl")]
@@ -195,8 +899,412 @@ fn run_repl() {
     process::exit(1);
 }
 
+fn build_playground(out: &Path, pkg: &Path) -> bool {
+    match sui_lang::playground::build(out, pkg) {
+        Ok(files) => {
+            for file in &files {
+                println!("  {}", file.display());
+            }
+            println!("{} Playground written to {}", "✓".green(), out.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            false
+        }
+    }
+}
+
+/// Compile `file` to `output`, by default next to it; returns the exit code
+fn compile(file: &Path, output: Option<&Path>) -> i32 {
+    let program = match Interpreter::new().compile_file(file) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            return exit::code(&e);
+        }
+    };
+    let output = output.map_or_else(|| file.with_extension("suic"), Path::to_path_buf);
+    if let Err(e) = program.save(&output) {
+        eprintln!("{}: Failed to write '{}': {}", "Error".red(), output.display(), e);
+        return exit::FAILURE;
+    }
+    println!("{} Compiled to {}", "✓".green(), output.display());
+    exit::SUCCESS
+}
+
+/// Format the programs in `paths` in place, or with `check` list those
+/// that are not formatted; `false` if any are not or anything failed
+fn format_programs(paths: &[PathBuf], check: bool, options: &FormatOptions) -> bool {
+    let mut failures = 0;
+    for program in snap::find_programs(paths) {
+        let code = match fs::read_to_string(&program) {
+            Ok(code) => code,
+            Err(e) => {
+                failures += 1;
+                eprintln!("{} {}: {}", "✗".red(), program.display(), e);
+                continue;
+            }
+        };
+        let formatted = format(&code, options);
+        if formatted == code {
+            continue;
+        }
+        if check {
+            failures += 1;
+            println!("{} {} is not formatted", "✗".red(), program.display());
+        } else if let Err(e) = fs::write(&program, formatted) {
+            failures += 1;
+            eprintln!("{} {}: {}", "✗".red(), program.display(), e);
+        } else {
+            println!("{} {} formatted", "✓".green(), program.display());
+        }
+    }
+    failures == 0
+}
+
+/// Run the corpus manifests in `paths` and verify the snapshots of the
+/// programs in the rest; `false` if anything failed
+fn test(paths: &[PathBuf]) -> bool {
+    let (manifests, programs): (Vec<PathBuf>, Vec<PathBuf>) =
+        paths.iter().cloned().partition(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    let mut success = true;
+    for manifest in &manifests {
+        success &= corpus_run(manifest, None);
+    }
+    if !programs.is_empty() || manifests.is_empty() {
+        success &= snap(&programs, true);
+    }
+    success
+}
+
+/// Run the `sui-lsp` binary installed next to this one, or else on the
+/// path, and return its exit code
+fn run_lsp(attach: Option<&str>) -> i32 {
+    let sibling = std::env::current_exe().ok().and_then(|exe| {
+        let path = exe.with_file_name(format!("sui-lsp{}", std::env::consts::EXE_SUFFIX));
+        path.exists().then_some(path)
+    });
+    let mut command = process::Command::new(sibling.unwrap_or_else(|| PathBuf::from("sui-lsp")));
+    if let Some(addr) = attach {
+        command.arg("--attach").arg(addr);
+    }
+    match command.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            eprintln!("{}: Failed to start sui-lsp: {}", "Error".red(), e);
+            eprintln!("Build it with: cargo build --features lsp");
+            1
+        }
+    }
+}
+
+/// Update or verify the snapshots of the programs in `paths`; `false` if
+/// verification found differences or anything failed
+fn snap(paths: &[PathBuf], verify: bool) -> bool {
+    let programs = snap::find_programs(paths);
+    let mut failures = 0;
+    for program in &programs {
+        let status = if verify { snap::verify(program) } else { snap::update(program) };
+        match status {
+            Ok(status @ (SnapStatus::Unchanged | SnapStatus::Created | SnapStatus::Updated)) => {
+                println!("{} {} ({})", "✓".green(), program.display(), status);
+            }
+            Ok(status) => {
+                failures += 1;
+                println!("{} {} ({})", "✗".red(), program.display(), status);
+                if let SnapStatus::Changed(diff) = status {
+                    for line in diff.lines() {
+                        match line.chars().next() {
+                            Some('-') => println!("    {}", line.red()),
+                            Some('+') => println!("    {}", line.green()),
+                            _ => println!("    {}", line),
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("{} {}: {}", "✗".red(), program.display(), e);
+            }
+        }
+    }
+    println!("{} programs, {} failed", programs.len(), failures);
+    failures == 0
+}
+
+/// Run the programs of the manifest at `path`; `false` if any did not
+/// pass or the manifest could not be read
+fn corpus_run(path: &Path, filter: Option<&String>) -> bool {
+    let manifest = match Manifest::load(path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            return false;
+        }
+    };
+    let mut runner = CorpusRunner::new();
+    runner.set_filter(filter.cloned());
+    let report = runner.run(&manifest);
+    for result in &report.results {
+        match &result.failure {
+            None => println!("{} {}", "✓".green(), result.name),
+            Some(failure) => {
+                println!("{} {}", "✗".red(), result.name);
+                for line in failure.lines() {
+                    println!("    {}", line);
+                }
+            }
+        }
+    }
+    println!(
+        "{} programs, {} passed, {} failed ({:.2}s)",
+        report.results.len(),
+        report.passed(),
+        report.failed(),
+        report.elapsed().as_secs_f64()
+    );
+    report.success()
+}
+
+/// Test case of `program`'s snapshot
+fn snapshot_case(program: &Path) -> Result<CorpusProgram, String> {
+    let path = snap::snap_path(program);
+    let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let snapshot = Snapshot::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(CorpusProgram {
+        path: program.to_path_buf(),
+        name: Some(path.display().to_string()),
+        args: snapshot.args,
+        stdin: snapshot.input,
+        expected: Some(snapshot.output),
+        error: snapshot.error,
+        ..CorpusProgram::default()
+    })
+}
+
+/// Test cases for mutating `program`: the manifest's programs that run
+/// it, or its snapshot
+fn mutation_cases(program: &Path, manifest: Option<&PathBuf>) -> Result<Vec<CorpusProgram>, String> {
+    let Some(manifest) = manifest else {
+        return Ok(vec![snapshot_case(program)?]);
+    };
+    let manifest = Manifest::load(manifest)?;
+    let target = program.canonicalize().map_err(|e| format!("{}: {}", program.display(), e))?;
+    let cases: Vec<CorpusProgram> = manifest
+        .programs
+        .into_iter()
+        .filter(|case| manifest.base_dir.join(&case.path).canonicalize().ok().as_ref() == Some(&target))
+        .collect();
+    if cases.is_empty() {
+        return Err(format!("the manifest has no programs running {}", program.display()));
+    }
+    Ok(cases)
+}
+
+/// Mutation test `program`; `false` if the score is below `min_score` or
+/// the tests could not run
+fn mutate(program: &Path, manifest: Option<&PathBuf>, min_score: f64, max_steps: u64) -> bool {
+    let report = mutation_cases(program, manifest).and_then(|cases| test_mutants(program, &cases, max_steps));
+    match report {
+        Ok(report) => {
+            for mutant in report.survivors() {
+                println!("{} survived {}", "✗".red(), mutant);
+            }
+            println!(
+                "{} mutants, {} killed, {} survived (score {:.1}%)",
+                report.results.len(),
+                report.killed(),
+                report.results.len() - report.killed(),
+                report.score()
+            );
+            report.score() >= min_score
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            false
+        }
+    }
+}
+
+/// Check `program` against its snapshot, or that it runs without errors
+/// if it has none, and report in `format`; `false` if it failed
+fn check(program: &Path, format: CheckFormat, max_steps: u64) -> bool {
+    let code = match fs::read_to_string(program) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: Failed to read file: {}", "Error".red(), e);
+            return false;
+        }
+    };
+    let case = if snap::snap_path(program).exists() {
+        match snapshot_case(program) {
+            Ok(case) => case,
+            Err(e) => {
+                eprintln!("{}: {}", "Error".red(), e);
+                return false;
+            }
+        }
+    } else {
+        CorpusProgram::default()
+    };
+
+    let hints = feedback::check(&code, program, &[case], max_steps).err().map(|failure| diagnose(&code, &failure));
+    match (format, &hints) {
+        (CheckFormat::Text, None) => println!("{} {} passes", "✓".green(), program.display()),
+        (CheckFormat::Text, Some(hints)) => {
+            println!("{} {}", "✗".red(), hints.summary);
+            for hint in &hints.hints {
+                println!("  {}", hint);
+            }
+        }
+        (CheckFormat::Llm, None) => println!("The program passes."),
+        (CheckFormat::Llm, Some(hints)) => println!("{}", hints),
+        (CheckFormat::Json, None) => println!("{{\"passed\":true}}"),
+        (CheckFormat::Json, Some(hints)) => println!("{{\"passed\":false,\"feedback\":{}}}", hints.to_json()),
+    }
+    hints.is_none()
+}
+
+/// Run a program recording it and print the narrative of the run; returns
+/// the exit code of the run
+fn explain_run(program: &Path, args: &[String], max_steps: u64) -> i32 {
+    let mut interp = Interpreter::new();
+    interp.set_recording(true);
+    interp.set_max_steps(Some(max_steps));
+    let result = interp.run_file(program, args);
+    match interp.take_trace() {
+        Some(mut trace) => {
+            trace.error = result.as_ref().err().map(|e| e.to_string());
+            println!("{}", narrate(&trace));
+        }
+        None => {
+            if let Err(e) = &result {
+                eprintln!("{}: {}", "Error".red(), e);
+            }
+        }
+    }
+    result.as_ref().map_or_else(exit::code, |_| exit::SUCCESS)
+}
+
+/// Print a prompt pack; `false` if it could not be built
+fn print_prompt_pack(corpus: Option<&PathBuf>, options: &PackOptions) -> bool {
+    let pack = match corpus.map(|path| Manifest::load(path)).transpose() {
+        Ok(manifest) => prompt_pack(manifest.as_ref(), options, &Cl100kHeuristic),
+        Err(e) => Err(e),
+    };
+    match pack {
+        Ok(pack) => {
+            print!("{}", pack);
+            true
+        }
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red(), e);
+            false
+        }
+    }
+}
+
 fn main() {
-    let cli = Cli::parse();
+    // Usage errors exit 1, keeping 2 for programs that do not parse
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        process::exit(if e.use_stderr() { exit::FAILURE } else { exit::SUCCESS });
+    });
+    cli.color.apply();
+
+    match &cli.command {
+        Some(Command::Run { file, args, options }) => {
+            process::exit(run_file(file, args, options));
+        }
+        Some(Command::Compile { file, output }) => {
+            process::exit(compile(file, output.as_deref()));
+        }
+        Some(Command::Transpile(TranspileCommand::Python(args))) => {
+            process::exit(transpile::python(args, "sui transpile python"));
+        }
+        Some(Command::Transpile(TranspileCommand::Js(args))) => {
+            process::exit(transpile::javascript(args, "sui transpile js"));
+        }
+        Some(Command::Transpile(TranspileCommand::Wasm(args))) => {
+            process::exit(transpile::wasm(args, "sui transpile wasm"));
+        }
+        Some(Command::FromPython(args)) => {
+            process::exit(transpile::from_python(args, "sui from-python"));
+        }
+        Some(Command::Debug(args)) => {
+            process::exit(debug(args.clone()));
+        }
+        Some(Command::Lsp { attach }) => {
+            process::exit(run_lsp(attach.as_deref()));
+        }
+        Some(Command::Fmt { paths, check, indent, align }) => {
+            let options = FormatOptions { align_columns: *align, indent_width: *indent };
+            let success = format_programs(paths, *check, &options);
+            process::exit(exit::checked(success));
+        }
+        Some(Command::Lint { file, format }) => {
+            let success = validate_file(file, *format);
+            process::exit(exit::checked(success));
+        }
+        Some(Command::Test { paths }) => {
+            let success = test(paths);
+            process::exit(exit::checked(success));
+        }
+        Some(Command::Repl) => {
+            run_repl();
+            return;
+        }
+        Some(Command::Playground(PlaygroundCommand::Build { out, pkg })) => {
+            let success = build_playground(out, pkg);
+            process::exit(if success { 0 } else { 1 });
+        }
+        Some(Command::Snap(command)) => {
+            let success = match command {
+                SnapCommand::Update { paths } => snap(paths, false),
+                SnapCommand::Verify { paths } => snap(paths, true),
+            };
+            process::exit(exit::checked(success));
+        }
+        Some(Command::Corpus(CorpusCommand::Run { manifest, filter })) => {
+            let success = corpus_run(manifest, filter.as_ref());
+            process::exit(exit::checked(success));
+        }
+        Some(Command::Spec(SpecCommand::Json)) => {
+            print!("{}", spec::semantics_json());
+            return;
+        }
+        Some(Command::Spec(SpecCommand::Check)) => {
+            let failures = spec::check();
+            for (example, e) in &failures {
+                println!("{} {} ({}): {}", "✗".red(), example.op, example.summary, e);
+            }
+            println!("{} examples, {} failed", spec::EXAMPLES.len(), failures.len());
+            process::exit(exit::checked(failures.is_empty()));
+        }
+        Some(Command::Mutate { program, manifest, min_score, max_steps }) => {
+            let success = mutate(program, manifest.as_ref(), *min_score, *max_steps);
+            process::exit(exit::checked(success));
+        }
+        Some(Command::Check { program, format, max_steps }) => {
+            let success = check(program, *format, *max_steps);
+            process::exit(exit::checked(success));
+        }
+        Some(Command::ExplainRun { program, args, max_steps }) => {
+            process::exit(explain_run(program, args, *max_steps));
+        }
+        Some(Command::PromptPack { corpus, tag, examples, budget, grammar, no_grammar }) => {
+            let options = PackOptions {
+                budget: *budget,
+                examples: *examples,
+                tags: tag.clone(),
+                grammar: (!no_grammar).then_some(*grammar),
+            };
+            let success = print_prompt_pack(corpus.as_ref(), &options);
+            process::exit(if success { 0 } else { 1 });
+        }
+        None => {}
+    }
 
     // REPL mode
     if cli.repl {
@@ -204,8 +1312,14 @@ fn main() {
         return;
     }
 
+    // Explain mode
+    if let Some(query) = &cli.explain {
+        let success = explain(query);
+        process::exit(if success { 0 } else { 1 });
+    }
+
     // If no file specified, show demo
-    let Some(file) = cli.file else {
+    let Some(file) = cli.file.clone() else {
         print_demo();
         return;
     };
@@ -218,10 +1332,46 @@ fn main() {
 
     // Validate mode
     if cli.validate {
-        let success = validate_file(&file);
+        let success = validate_file(&file, cli.run.format);
+        process::exit(exit::checked(success));
+    }
+
+    // Token report mode
+    if cli.tokens {
+        let success = report_tokens(&file);
+        process::exit(if success { 0 } else { 1 });
+    }
+
+    // Minify mode
+    if cli.minify {
+        let success = minify_file(&file);
+        process::exit(if success { 0 } else { 1 });
+    }
+
+    // Pseudocode mode
+    if cli.pseudo {
+        let success = pseudo_file(&file);
+        process::exit(if success { 0 } else { 1 });
+    }
+
+    // Verification mode
+    if cli.verify {
+        let success = verify_file(&file);
+        process::exit(exit::checked(success));
+    }
+
+    // Type hint checking mode
+    if cli.typecheck {
+        let success = typecheck_file(&file);
+        process::exit(exit::checked(success));
+    }
+
+    // Program usage mode
+    if cli.help_program {
+        let success = help_program(&file);
         process::exit(if success { 0 } else { 1 });
     }
 
     // Run mode
-    run_file(&file, &cli.args, cli.debug);
+    process::exit(run_file(&file, &cli.args, &cli.run));
 }
@@ -0,0 +1,35 @@
+//! Sui (粋) to WebAssembly compiler CLI, an alias of `sui transpile wasm`
+
+use clap::Parser;
+use std::process;
+
+use sui_lang::cli::transpile::{self, WasmArgs};
+use sui_lang::cli::ColorArgs;
+
+#[derive(Parser)]
+#[command(name = "sui2wasm")]
+#[command(author = "Sui Contributors")]
+#[command(version = sui_lang::VERSION)]
+#[command(about = "Sui (粋) to WebAssembly compiler")]
+#[command(long_about = r#"
+Compile Sui code to WebAssembly.
+
+Examples:
+  sui2wasm examples/fibonacci.sui              # Show WAT text
+  sui2wasm examples/fibonacci.sui -o fib.wasm  # Write a binary module
+  sui2wasm examples/fibonacci.sui -o fib.wat   # Write WAT text
+  sui2wasm examples/fibonacci.sui --run        # Compile and execute with Node.js
+"#)]
+struct Cli {
+    #[command(flatten)]
+    args: WasmArgs,
+
+    #[command(flatten)]
+    color: ColorArgs,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    cli.color.apply();
+    process::exit(transpile::wasm(&cli.args, "sui2wasm"));
+}
@@ -1,184 +1,311 @@
 //! Sui Language Server Protocol (LSP) implementation
 //!
 //! Provides IDE features for Sui language:
-//! - Diagnostics (syntax errors)
-//! - Hover information
+//! - Diagnostics (syntax errors), updated incrementally as lines change
+//! - Hover information, including the types inferred for a variable
+//! - Signature help while typing operands
 //! - Document symbols
+//! - Inlay hints naming each operand's role
+//! - Semantic highlighting
+//! - Go to definition and rename for variables, labels and function IDs
+//! - Lint warnings with quick fixes
+//! - Document and range formatting
+//! - Workspace symbol search for functions and labels
+//! - Errors for `_` imports that resolve to no file
+//! - "Evaluate to here" code lenses on labels, showing the variables when
+//!   the program first gets there
+//!
+//! Workspace symbols cover open documents; with the initialization option
+//! `{"workspaceFiles": true}` they also cover `.sui` files on disk under the
+//! workspace folders.
+//!
+//! With `--attach ADDR`, hovering a variable also shows its current value
+//! in a `sui-debug --export ADDR` session.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
-use sui_lang::interpreter::Parser;
+use sui_lang::debugger::session::fetch_snapshot;
+use sui_lang::formatter::{format, format_lines, FormatOptions};
+use sui_lang::ide::{
+    debug_value, evaluate_to_line, evaluation_lines, find_sui_files, highlight, inferred_type, instruction_doc, operand_hints,
+    quick_fixes, rename, signature_help, unresolved_imports, workspace_symbols, Document, Edit, LineCol, SymbolIndex, TokenKind,
+    WorkspaceSymbolKind,
+};
+use sui_lang::interpreter::Instruction;
+
+/// Command run by the evaluation code lenses, with the document URI and
+/// the 0-based line as arguments
+const EVALUATE_COMMAND: &str = "sui.evaluateToLine";
 
 /// Sui Language Server
 struct SuiLanguageServer {
     client: Client,
-    documents: tokio::sync::RwLock<HashMap<Url, String>>,
+    documents: tokio::sync::RwLock<HashMap<Url, Document>>,
+    /// Address of a `sui-debug --export` session
+    attach: Option<String>,
+    /// Folders scanned for on-disk files, if the client opted in
+    roots: tokio::sync::RwLock<Vec<PathBuf>>,
 }
 
 impl SuiLanguageServer {
-    fn new(client: Client) -> Self {
+    fn new(client: Client, attach: Option<String>) -> Self {
         Self {
             client,
             documents: tokio::sync::RwLock::new(HashMap::new()),
+            attach,
+            roots: tokio::sync::RwLock::new(Vec::new()),
         }
     }
 
-    /// Validate document and return diagnostics
-    async fn validate_document(&self, _uri: &Url, text: &str) -> Vec<Diagnostic> {
-        use sui_lang::interpreter::ParseError;
-
-        let errors = Parser::validate(text);
-        let mut diagnostics = Vec::new();
-
-        for error in errors {
-            let line_num = match &error {
-                ParseError::InvalidInstruction(_, line) => *line,
-                ParseError::MissingArguments(_, line, _, _) => *line,
-                ParseError::InvalidFunctionDef(line) => *line,
-                ParseError::UnmatchedBrace(line) => *line,
-                ParseError::General(line, _) => *line,
-            };
-
-            let line = line_num.saturating_sub(1) as u32;
-            diagnostics.push(Diagnostic {
-                range: Range {
-                    start: Position { line, character: 0 },
-                    end: Position { line, character: 100 },
-                },
-                severity: Some(DiagnosticSeverity::ERROR),
-                source: Some("sui".to_string()),
-                message: error.to_string(),
-                ..Default::default()
-            });
+    /// Current value of the variable at `position`, from the attached debugger
+    async fn debug_hover(&self, uri: &Url, doc: &Document, position: Position) -> Option<String> {
+        let addr = self.attach.clone()?;
+        let snapshot = tokio::task::spawn_blocking(move || fetch_snapshot(addr.as_str()))
+            .await
+            .ok()?
+            .ok()?;
+
+        // Only show values for the file being debugged
+        if let (Some(file), Ok(path)) = (&snapshot.file, uri.to_file_path()) {
+            if std::path::Path::new(file) != path {
+                return None;
+            }
         }
 
-        diagnostics
+        let (name, value) = debug_value(doc, line_col(position), &snapshot)?;
+        Some(format!("**{}** = `{}` ({})", name, value.value, value.type_name))
     }
 
-    /// Get hover information for a position
-    fn get_hover_info(&self, text: &str, position: Position) -> Option<String> {
-        let lines: Vec<&str> = text.lines().collect();
-        let line_idx = position.line as usize;
-
-        if line_idx >= lines.len() {
-            return None;
-        }
+    /// Types the variable at `position` may hold
+    fn type_hover(&self, doc: &Document, position: Position) -> Option<String> {
+        let (name, types) = inferred_type(doc, line_col(position))?;
+        Some(format!("`{}: {}`", name, types))
+    }
 
-        let line = lines[line_idx].trim();
+    /// Diagnostics for the current state of a document
+    ///
+    /// The document re-parses only edited lines, so this stays cheap on large
+    /// files. Imports count as resolved if the target is open or on disk.
+    fn validate_document(
+        &self,
+        uri: &Url,
+        doc: &Document,
+        open: &HashSet<PathBuf>,
+    ) -> Vec<Diagnostic> {
+        let imports = match uri.to_file_path() {
+            Ok(path) => unresolved_imports(&path, doc, |p| open.contains(p) || p.is_file()),
+            Err(_) => Vec::new(),
+        };
+
+        let errors = doc
+            .errors()
+            .into_iter()
+            .map(|error| (error.line(), DiagnosticSeverity::ERROR, error.to_string()))
+            .chain(
+                imports
+                    .into_iter()
+                    .map(|import| (import.line + 1, DiagnosticSeverity::ERROR, import.to_string())),
+            );
+        let warnings = doc
+            .lints()
+            .into_iter()
+            .map(|lint| (lint.line, DiagnosticSeverity::WARNING, lint.kind.to_string()));
+
+        errors
+            .chain(warnings)
+            .map(|(line_num, severity, message)| {
+                let line_idx = line_num.saturating_sub(1);
+                Diagnostic {
+                    range: line_range(line_idx, doc.line(line_idx).unwrap_or("")),
+                    severity: Some(severity),
+                    source: Some("sui".to_string()),
+                    message,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
 
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with(';') {
-            return None;
+    /// Re-publish diagnostics for every open document
+    ///
+    /// Opening or closing a file can change which imports resolve elsewhere.
+    async fn publish_all(&self) {
+        let published: Vec<_> = {
+            let documents = self.documents.read().await;
+            let open = open_paths(&documents);
+            documents
+                .iter()
+                .map(|(uri, doc)| (uri.clone(), self.validate_document(uri, doc, &open)))
+                .collect()
+        };
+        for (uri, diagnostics) in published {
+            self.client.publish_diagnostics(uri, diagnostics, None).await;
         }
-
-        let first_char = line.chars().next()?;
-
-        Some(match first_char {
-            '=' => "**Assignment**\n\n`= var value`\n\nAssigns a value to a variable.".to_string(),
-            '+' => "**Addition**\n\n`+ result a b`\n\nAdds two values and stores in result.".to_string(),
-            '-' => "**Subtraction**\n\n`- result a b`\n\nSubtracts b from a and stores in result.".to_string(),
-            '*' => "**Multiplication**\n\n`* result a b`\n\nMultiplies two values and stores in result.".to_string(),
-            '/' => "**Division**\n\n`/ result a b`\n\nDivides a by b and stores in result.".to_string(),
-            '%' => "**Modulo**\n\n`% result a b`\n\nComputes a mod b and stores in result.".to_string(),
-            '<' => "**Less Than**\n\n`< result a b`\n\nReturns 1 if a < b, else 0.".to_string(),
-            '>' => "**Greater Than**\n\n`> result a b`\n\nReturns 1 if a > b, else 0.".to_string(),
-            '~' => "**Equality**\n\n`~ result a b`\n\nReturns 1 if a == b, else 0.".to_string(),
-            '!' => "**Logical NOT**\n\n`! result a`\n\nReturns 1 if a is 0, else 0.".to_string(),
-            '&' => "**Logical AND**\n\n`& result a b`\n\nReturns 1 if both are non-zero.".to_string(),
-            '|' => "**Logical OR**\n\n`| result a b`\n\nReturns 1 if either is non-zero.".to_string(),
-            '?' => "**Conditional Jump**\n\n`? cond label`\n\nJumps to label if cond is non-zero.".to_string(),
-            '@' => "**Unconditional Jump**\n\n`@ label`\n\nJumps to the specified label.".to_string(),
-            ':' => "**Label Definition**\n\n`: label`\n\nDefines a jump target.".to_string(),
-            '#' => "**Function Definition**\n\n`# id argc {`\n\nDefines a function with given id and argument count.".to_string(),
-            '}' => "**Function End**\n\n`}`\n\nEnds a function definition.".to_string(),
-            '$' => "**Function Call**\n\n`$ result func args...`\n\nCalls function and stores result.".to_string(),
-            '^' => "**Return**\n\n`^ value`\n\nReturns a value from function.".to_string(),
-            '[' => "**Array Create**\n\n`[ var size`\n\nCreates an array of given size.".to_string(),
-            ']' => "**Array Read**\n\n`] result arr idx`\n\nReads value from array at index.".to_string(),
-            '{' => "**Array Write**\n\n`{ arr idx value`\n\nWrites value to array at index.".to_string(),
-            '.' => "**Output**\n\n`. value`\n\nPrints the value to output.".to_string(),
-            ',' => "**Input**\n\n`, var`\n\nReads input into variable.".to_string(),
-            'R' | 'P' => "**FFI Call**\n\n`R result \"func\" args...`\n\nCalls a builtin function.\n\nAvailable: math.sqrt, pow, sin, cos, len, abs, max, min, round, int, float, str, random.randint".to_string(),
-            _ => return None,
-        })
     }
 
     /// Get document symbols (functions and labels)
-    fn get_symbols(&self, text: &str, _uri: &Url) -> Vec<DocumentSymbol> {
+    fn get_symbols(&self, doc: &Document, _uri: &Url) -> Vec<DocumentSymbol> {
         let mut symbols = Vec::new();
 
-        for (line_idx, line) in text.lines().enumerate() {
-            let trimmed = line.trim();
-
-            // Function definition
-            if trimmed.starts_with('#') {
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if parts.len() >= 3 {
-                    let func_id = parts[1];
-                    let argc = parts[2];
-                    #[allow(deprecated)]
-                    symbols.push(DocumentSymbol {
-                        name: format!("function {}", func_id),
-                        detail: Some(format!("{} args", argc)),
-                        kind: SymbolKind::FUNCTION,
-                        tags: None,
-                        deprecated: None,
-                        range: Range {
-                            start: Position { line: line_idx as u32, character: 0 },
-                            end: Position { line: line_idx as u32, character: line.len() as u32 },
-                        },
-                        selection_range: Range {
-                            start: Position { line: line_idx as u32, character: 0 },
-                            end: Position { line: line_idx as u32, character: line.len() as u32 },
-                        },
-                        children: None,
-                    });
-                }
-            }
+        for ((line_idx, line), instr) in doc.lines().iter().enumerate().zip(doc.instructions()) {
+            let (name, detail, kind) = match instr {
+                Instruction::FuncDef { id, argc } => (
+                    format!("function {}", id),
+                    Some(format!("{} args", argc)),
+                    SymbolKind::FUNCTION,
+                ),
+                Instruction::Label { id } => (format!("label {}", id), None, SymbolKind::KEY),
+                _ => continue,
+            };
 
-            // Label definition
-            if trimmed.starts_with(':') {
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    let label = parts[1];
-                    #[allow(deprecated)]
-                    symbols.push(DocumentSymbol {
-                        name: format!("label {}", label),
-                        detail: None,
-                        kind: SymbolKind::KEY,
-                        tags: None,
-                        deprecated: None,
-                        range: Range {
-                            start: Position { line: line_idx as u32, character: 0 },
-                            end: Position { line: line_idx as u32, character: line.len() as u32 },
-                        },
-                        selection_range: Range {
-                            start: Position { line: line_idx as u32, character: 0 },
-                            end: Position { line: line_idx as u32, character: line.len() as u32 },
-                        },
-                        children: None,
-                    });
-                }
-            }
+            #[allow(deprecated)]
+            symbols.push(DocumentSymbol {
+                name,
+                detail,
+                kind,
+                tags: None,
+                deprecated: None,
+                range: line_range(line_idx, line),
+                selection_range: line_range(line_idx, line),
+                children: None,
+            });
         }
 
         symbols
     }
 }
 
+/// File paths of the open documents
+fn open_paths(documents: &HashMap<Url, Document>) -> HashSet<PathBuf> {
+    documents.keys().filter_map(|uri| uri.to_file_path().ok()).collect()
+}
+
+/// Convert an LSP position for [`Document::edit`]
+fn line_col(position: Position) -> LineCol {
+    LineCol {
+        line: position.line as usize,
+        character: position.character as usize,
+    }
+}
+
+/// Formatter settings from the client
+///
+/// `suiAlignColumns` (bool) and `suiIndentWidth` (number) are read from the
+/// formatting properties; both default to off.
+fn format_options(options: &FormattingOptions) -> FormatOptions {
+    let align_columns = matches!(
+        options.properties.get("suiAlignColumns"),
+        Some(FormattingProperty::Bool(true))
+    );
+    let indent_width = match options.properties.get("suiIndentWidth") {
+        Some(FormattingProperty::Number(n)) => (*n).max(0) as usize,
+        _ => 0,
+    };
+    FormatOptions {
+        align_columns,
+        indent_width,
+    }
+}
+
+/// Convert an edit from the ide module
+fn text_edit(edit: Edit) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position {
+                line: edit.start.line as u32,
+                character: edit.start.character as u32,
+            },
+            end: Position {
+                line: edit.end.line as u32,
+                character: edit.end.character as u32,
+            },
+        },
+        new_text: edit.text,
+    }
+}
+
+/// Range of `length` UTF-16 code units starting at a column
+fn span_range(line: usize, start: usize, length: usize) -> Range {
+    Range {
+        start: Position { line: line as u32, character: start as u32 },
+        end: Position { line: line as u32, character: (start + length) as u32 },
+    }
+}
+
+/// Range covering a whole source line
+fn line_range(line_idx: usize, line: &str) -> Range {
+    let line_idx = line_idx as u32;
+    Range {
+        start: Position { line: line_idx, character: 0 },
+        end: Position { line: line_idx, character: line.encode_utf16().count() as u32 },
+    }
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for SuiLanguageServer {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let workspace_files = params
+            .initialization_options
+            .as_ref()
+            .and_then(|options| options.get("workspaceFiles"))
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        if workspace_files {
+            let mut roots: Vec<PathBuf> = params
+                .workspace_folders
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect();
+            if roots.is_empty() {
+                #[allow(deprecated)]
+                let root = params.root_uri.and_then(|uri| uri.to_file_path().ok());
+                roots.extend(root);
+            }
+            *self.roots.write().await = roots;
+        }
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                }),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(false) }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![EVALUATE_COMMAND.to_string()],
+                    work_done_progress_options: Default::default(),
+                }),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                        legend: SemanticTokensLegend {
+                            token_types: TokenKind::ALL
+                                .iter()
+                                .map(|kind| SemanticTokenType::new(kind.name()))
+                                .collect(),
+                            token_modifiers: Vec::new(),
+                        },
+                        full: Some(SemanticTokensFullOptions::Bool(true)),
+                        ..Default::default()
+                    }),
+                ),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -200,28 +327,38 @@ impl LanguageServer for SuiLanguageServer {
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         let uri = params.text_document.uri;
-        let text = params.text_document.text;
+        let doc = Document::new(&params.text_document.text);
 
-        self.documents.write().await.insert(uri.clone(), text.clone());
-
-        let diagnostics = self.validate_document(&uri, &text).await;
-        self.client.publish_diagnostics(uri, diagnostics, None).await;
+        self.documents.write().await.insert(uri, doc);
+        self.publish_all().await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri;
 
-        if let Some(change) = params.content_changes.into_iter().next() {
-            let text = change.text;
-            self.documents.write().await.insert(uri.clone(), text.clone());
+        let diagnostics = {
+            let mut documents = self.documents.write().await;
+            let open = open_paths(&documents);
+            let doc = documents.entry(uri.clone()).or_default();
+            for change in params.content_changes {
+                match change.range {
+                    Some(range) => {
+                        doc.edit(line_col(range.start), line_col(range.end), &change.text)
+                    }
+                    None => doc.replace(&change.text),
+                }
+            }
+            self.validate_document(&uri, doc, &open)
+        };
 
-            let diagnostics = self.validate_document(&uri, &text).await;
-            self.client.publish_diagnostics(uri, diagnostics, None).await;
-        }
+        self.client.publish_diagnostics(uri, diagnostics, None).await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.documents.write().await.remove(&params.text_document.uri);
+        let uri = params.text_document.uri;
+        self.documents.write().await.remove(&uri);
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        self.publish_all().await;
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -229,19 +366,63 @@ impl LanguageServer for SuiLanguageServer {
         let position = params.text_document_position_params.position;
 
         let documents = self.documents.read().await;
-        if let Some(text) = documents.get(uri) {
-            if let Some(info) = self.get_hover_info(text, position) {
-                return Ok(Some(Hover {
-                    contents: HoverContents::Markup(MarkupContent {
-                        kind: MarkupKind::Markdown,
-                        value: info,
-                    }),
-                    range: None,
-                }));
-            }
+        let Some(doc) = documents.get(uri) else {
+            return Ok(None);
+        };
+
+        let sections: Vec<String> = [
+            self.debug_hover(uri, doc, position).await,
+            self.type_hover(doc, position),
+            instruction_doc(doc, position.line as usize),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if sections.is_empty() {
+            return Ok(None);
         }
 
-        Ok(None)
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: sections.join("\n\n---\n\n"),
+            }),
+            range: None,
+        }))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(uri) else {
+            return Ok(None);
+        };
+        let Some(help) = signature_help(doc, line_col(position)) else {
+            return Ok(None);
+        };
+
+        let parameters = help
+            .parameters
+            .iter()
+            .map(|&(start, end)| ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start as u32, end as u32]),
+                documentation: None,
+            })
+            .collect::<Vec<_>>();
+        // An out-of-range index highlights nothing once all operands are typed
+        let active = help.active.unwrap_or(parameters.len()) as u32;
+
+        Ok(Some(SignatureHelp {
+            signatures: vec![SignatureInformation {
+                label: help.label,
+                documentation: Some(Documentation::String(help.syntax.name.to_string())),
+                parameters: Some(parameters),
+                active_parameter: Some(active),
+            }],
+            active_signature: Some(0),
+            active_parameter: Some(active),
+        }))
     }
 
     async fn document_symbol(
@@ -251,13 +432,321 @@ impl LanguageServer for SuiLanguageServer {
         let uri = &params.text_document.uri;
 
         let documents = self.documents.read().await;
-        if let Some(text) = documents.get(uri) {
-            let symbols = self.get_symbols(text, uri);
+        if let Some(doc) = documents.get(uri) {
+            let symbols = self.get_symbols(doc, uri);
             return Ok(Some(DocumentSymbolResponse::Nested(symbols)));
         }
 
         Ok(None)
     }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let documents = self.documents.read().await;
+        let open = open_paths(&documents);
+
+        // Files on disk that are not open; open documents take precedence
+        let on_disk: Vec<(PathBuf, Document)> = self
+            .roots
+            .read()
+            .await
+            .iter()
+            .flat_map(|root| find_sui_files(root))
+            .filter(|path| !open.contains(path))
+            .filter_map(|path| {
+                let text = std::fs::read_to_string(&path).ok()?;
+                Some((path, Document::new(&text)))
+            })
+            .collect();
+
+        let mut uris: HashMap<PathBuf, Url> = HashMap::new();
+        let mut files: Vec<(PathBuf, &Document)> = Vec::new();
+        for (uri, doc) in documents.iter() {
+            // Untitled buffers are keyed by their URI text
+            let path = uri
+                .to_file_path()
+                .unwrap_or_else(|_| PathBuf::from(uri.as_str()));
+            uris.insert(path.clone(), uri.clone());
+            files.push((path, doc));
+        }
+        for (path, doc) in &on_disk {
+            if let Ok(uri) = Url::from_file_path(path) {
+                uris.insert(path.clone(), uri);
+                files.push((path.clone(), doc));
+            }
+        }
+
+        let symbols = workspace_symbols(&files, &params.query)
+            .into_iter()
+            .filter_map(|symbol| {
+                let uri = uris.get(&symbol.path)?.clone();
+                let doc = files.iter().find(|(path, _)| *path == symbol.path)?.1;
+                #[allow(deprecated)]
+                let info = SymbolInformation {
+                    name: symbol.name,
+                    kind: match symbol.kind {
+                        WorkspaceSymbolKind::Function => SymbolKind::FUNCTION,
+                        WorkspaceSymbolKind::Label => SymbolKind::KEY,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri,
+                        range: line_range(symbol.line, doc.line(symbol.line).unwrap_or("")),
+                    },
+                    container_name: symbol.container,
+                };
+                Some(info)
+            })
+            .collect();
+
+        Ok(Some(symbols))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let range = params.range;
+
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let lines = range.start.line as usize..range.end.line as usize + 1;
+        let hints = operand_hints(doc, lines)
+            .into_iter()
+            .map(|hint| InlayHint {
+                position: Position {
+                    line: hint.position.line as u32,
+                    character: hint.position.character as u32,
+                },
+                label: InlayHintLabel::String(format!("{}:", hint.role)),
+                kind: Some(InlayHintKind::PARAMETER),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: Some(true),
+                data: None,
+            })
+            .collect();
+
+        Ok(Some(hints))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        // Positions are encoded relative to the previous token
+        let mut data = Vec::new();
+        let (mut prev_line, mut prev_start) = (0, 0);
+        for token in highlight(doc) {
+            let delta_line = token.line - prev_line;
+            let delta_start = if delta_line == 0 { token.start - prev_start } else { token.start };
+            data.push(SemanticToken {
+                delta_line: delta_line as u32,
+                delta_start: delta_start as u32,
+                length: token.length as u32,
+                token_type: token.kind.index() as u32,
+                token_modifiers_bitset: 0,
+            });
+            prev_line = token.line;
+            prev_start = token.start;
+        }
+
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+
+        let index = SymbolIndex::new(doc);
+        let definition = index
+            .at(line_col(position))
+            .and_then(|occ| index.definition(&occ.symbol));
+
+        Ok(definition.map(|def| {
+            GotoDefinitionResponse::Scalar(Location {
+                uri: uri.clone(),
+                range: span_range(def.line, def.start, def.length),
+            })
+        }))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+
+        let edits = rename(doc, line_col(position), &params.new_name)
+            .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?
+            .into_iter()
+            .map(text_edit)
+            .collect();
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri, edits)])),
+            ..Default::default()
+        }))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let range = params.range;
+
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+
+        let lines = range.start.line as usize..range.end.line as usize + 1;
+        let actions = quick_fixes(doc, lines)
+            .into_iter()
+            .map(|fix| {
+                // Attach the diagnostics on the fixed line
+                let diagnostics = params
+                    .context
+                    .diagnostics
+                    .iter()
+                    .filter(|d| d.range.start.line as usize == fix.line)
+                    .cloned()
+                    .collect();
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(diagnostics),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(
+                            uri.clone(),
+                            fix.edits.into_iter().map(text_edit).collect(),
+                        )])),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        Ok(Some(actions))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+
+        let lenses = evaluation_lines(doc)
+            .into_iter()
+            .map(|line| CodeLens {
+                range: line_range(line, doc.line(line).unwrap_or("")),
+                command: Some(Command {
+                    title: "Evaluate to here".to_string(),
+                    command: EVALUATE_COMMAND.to_string(),
+                    arguments: Some(vec![serde_json::json!(uri), serde_json::json!(line)]),
+                }),
+                data: None,
+            })
+            .collect();
+
+        Ok(Some(lenses))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<serde_json::Value>> {
+        if params.command != EVALUATE_COMMAND {
+            return Ok(None);
+        }
+        let uri = params.arguments.first().and_then(|uri| uri.as_str()).and_then(|uri| Url::parse(uri).ok());
+        let line = params.arguments.get(1).and_then(|line| line.as_u64());
+        let (Some(uri), Some(line)) = (uri, line) else {
+            return Ok(None);
+        };
+        let Some(doc) = self.documents.read().await.get(&uri).cloned() else {
+            return Ok(None);
+        };
+
+        // Programs may run for a while; keep the server responsive
+        let Ok(state) = tokio::task::spawn_blocking(move || evaluate_to_line(&doc, line as usize)).await else {
+            return Ok(None);
+        };
+        self.client.show_message(MessageType::INFO, &state).await;
+        Ok(Some(serde_json::Value::String(state)))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let text = doc.text();
+        let formatted = format(&text, &format_options(&params.options));
+        if formatted == text {
+            return Ok(Some(Vec::new()));
+        }
+
+        // Replace everything, up to past the last line
+        Ok(Some(vec![TextEdit {
+            range: Range {
+                start: Position { line: 0, character: 0 },
+                end: Position { line: doc.lines().len() as u32, character: 0 },
+            },
+            new_text: formatted,
+        }]))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        // The formatter keeps one line per source line, so lines map 1:1
+        let formatted = format_lines(&doc.text(), &format_options(&params.options));
+        let first = params.range.start.line as usize;
+        let last = params.range.end.line as usize;
+        let edits = formatted
+            .into_iter()
+            .enumerate()
+            .skip(first)
+            .take(last.saturating_sub(first) + 1)
+            .filter_map(|(i, new_text)| {
+                let line = doc.line(i)?;
+                (line != new_text).then(|| TextEdit {
+                    range: line_range(i, line),
+                    new_text,
+                })
+            })
+            .collect();
+
+        Ok(Some(edits))
+    }
 }
 
 #[tokio::main]
@@ -265,6 +754,15 @@ async fn main() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| SuiLanguageServer::new(client));
+    // sui-lsp [--attach ADDR]
+    let mut attach = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--attach" {
+            attach = args.next();
+        }
+    }
+
+    let (service, socket) = LspService::new(|client| SuiLanguageServer::new(client, attach));
     Server::new(stdin, stdout, socket).serve(service).await;
 }
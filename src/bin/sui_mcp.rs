@@ -0,0 +1,42 @@
+//! Sui Model Context Protocol (MCP) server
+//!
+//! Lets LLM agents run, validate, transpile and step through Sui programs
+//! over stdio, with limits enforced by the server.
+
+use clap::Parser;
+use std::io;
+use std::time::Duration;
+use sui_lang::mcp::{Limits, McpServer};
+
+#[derive(Parser)]
+#[command(name = "sui-mcp")]
+#[command(about = "MCP server exposing Sui tools over stdio")]
+#[command(version)]
+struct Args {
+    /// Most instructions a run, or a debug_step call, may execute
+    #[arg(long, default_value_t = 1_000_000)]
+    max_steps: u64,
+
+    /// Deepest call stack a run may reach
+    #[arg(long, default_value_t = 256)]
+    max_depth: usize,
+
+    /// Most wall-clock milliseconds a run may take
+    #[arg(long, default_value_t = 5000)]
+    timeout_ms: u64,
+}
+
+fn main() {
+    let args = Args::parse();
+    let limits = Limits {
+        max_steps: args.max_steps,
+        max_depth: args.max_depth,
+        timeout: Duration::from_millis(args.timeout_ms),
+        ..Limits::default()
+    };
+    let mut server = McpServer::new(limits);
+    if let Err(e) = server.serve(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,66 @@
+//! Sui HTTP execution service
+//!
+//! Runs, validates and transpiles Sui programs posted as JSON, with limits
+//! enforced by the server.
+
+use clap::Parser;
+use std::path::PathBuf;
+use std::time::Duration;
+use sui_lang::mcp::Limits;
+use sui_lang::serve::{serve, ServeOptions};
+
+#[derive(Parser)]
+#[command(name = "sui-serve")]
+#[command(about = "HTTP service for running Sui programs")]
+#[command(version)]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+
+    /// Most instructions a run may execute
+    #[arg(long, default_value_t = 1_000_000)]
+    max_steps: u64,
+
+    /// Deepest call stack a run may reach
+    #[arg(long, default_value_t = 256)]
+    max_depth: usize,
+
+    /// Most wall-clock milliseconds a run may take
+    #[arg(long, default_value_t = 5000)]
+    timeout_ms: u64,
+
+    /// Requests handled at once; more get 503
+    #[arg(long, default_value_t = 16)]
+    max_concurrent: usize,
+
+    /// Keep the results of this many runs in memory and answer repeated
+    /// runs from them
+    #[arg(long, default_value_t = 0)]
+    cache_size: usize,
+
+    /// Also keep every run result in this directory, across restarts
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let options = ServeOptions {
+        limits: Limits {
+            max_steps: args.max_steps,
+            max_depth: args.max_depth,
+            timeout: Duration::from_millis(args.timeout_ms),
+            ..Limits::default()
+        },
+        max_concurrent: args.max_concurrent,
+        cache_size: args.cache_size,
+        cache_dir: args.cache_dir,
+        ..ServeOptions::default()
+    };
+    println!("Serving on {}", args.addr);
+    if let Err(e) = serve(&args.addr, options) {
+        eprintln!("Error serving on '{}': {}", args.addr, e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,302 @@
+//! Cache of run results for evaluation pipelines
+//!
+//! Pipelines that score many candidate programs often run the same one
+//! again with the same inputs. [`RunCache`] remembers what such runs
+//! printed, how they failed and what they used, keyed on a [`CacheKey`] of the code's
+//! hash, the arguments, the input lines and the limits. It keeps the most
+//! recently used results in memory and, optionally, every result in a
+//! directory so later processes can reuse them:
+//!
+//! ```rust
+//! use sui_lang::cache::{CacheKey, CachedRun, RunCache};
+//! use sui_lang::interpreter::RunStats;
+//!
+//! let mut cache = RunCache::new(1024);
+//! let key = CacheKey::new(". g101", &["7".to_string()], &[], &[("max_steps", 1000)]);
+//! assert!(cache.get(&key).is_none());
+//! let stats = RunStats { steps: 1, ..RunStats::default() };
+//! cache.insert(key.clone(), CachedRun { output: vec!["7".to_string()], error: None, stats });
+//! assert_eq!(cache.get(&key).unwrap().output, ["7"]);
+//! ```
+//!
+//! Only cache runs whose result depends on nothing but the key: not runs
+//! that timed out, read the clock or environment, or ran commands.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::interpreter::code_hash;
+use crate::interpreter::json::{decode, write_string};
+use crate::interpreter::{FunctionUsage, RunStats, Value};
+
+/// Identity of a run: runs with the same key give the same result
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Key for running `code` with `args`, reading `input`, under `limits`
+    /// given as name and value pairs
+    pub fn new(code: &str, args: &[String], input: &[String], limits: &[(&str, u64)]) -> Self {
+        // Length prefixes keep the parts from running into each other
+        let mut text = String::new();
+        let mut part = |s: &str| {
+            text.push_str(&s.len().to_string());
+            text.push(':');
+            text.push_str(s);
+        };
+        part(&code_hash(code));
+        part(&args.len().to_string());
+        args.iter().for_each(|arg| part(arg));
+        part(&input.len().to_string());
+        input.iter().for_each(|line| part(line));
+        for (name, value) in limits {
+            part(name);
+            part(&value.to_string());
+        }
+        CacheKey(code_hash(&text))
+    }
+
+    /// The key as 64 hex digits
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// What a run printed, why it failed if it did, and what it used
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedRun {
+    pub output: Vec<String>,
+    pub error: Option<String>,
+    pub stats: RunStats,
+}
+
+impl CachedRun {
+    fn encode(&self) -> String {
+        let mut text = String::from("{\"error\":");
+        match &self.error {
+            Some(error) => write_string(&mut text, error),
+            None => text.push_str("null"),
+        }
+        text.push_str(",\"output\":[");
+        for (i, line) in self.output.iter().enumerate() {
+            if i > 0 {
+                text.push(',');
+            }
+            write_string(&mut text, line);
+        }
+        let _ = write!(text, "],\"stats\":{{\"functions\":{{");
+        for (i, (id, usage)) in self.stats.functions.iter().enumerate() {
+            if i > 0 {
+                text.push(',');
+            }
+            let _ = write!(
+                text,
+                "\"{}\":{{\"allocated\":{},\"calls\":{},\"steps\":{}}}",
+                id, usage.allocated, usage.calls, usage.steps
+            );
+        }
+        let _ = write!(text, "}},\"steps\":{}}}}}", self.stats.steps);
+        text
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let value = decode(text).ok()?;
+        let error = match value.field("error")? {
+            Value::Null => None,
+            Value::String(error) => Some(error.to_string()),
+            _ => return None,
+        };
+        let Value::Array(lines) = value.field("output")? else {
+            return None;
+        };
+        let output = lines.iter().map(|line| line.to_string()).collect();
+        // Results cached before stats were kept have none, and are run again
+        let stats = value.field("stats")?;
+        let Value::Struct(functions) = stats.field("functions")? else {
+            return None;
+        };
+        let functions = functions
+            .iter()
+            .map(|(id, usage)| {
+                let count = |name| usage.field(name).map(|n| n.to_int() as u64);
+                let usage = FunctionUsage { calls: count("calls")?, steps: count("steps")?, allocated: count("allocated")? };
+                Some((id.parse().ok()?, usage))
+            })
+            .collect::<Option<_>>()?;
+        let stats = RunStats { steps: stats.field("steps")?.to_int() as u64, functions };
+        Some(CachedRun { output, error, stats })
+    }
+}
+
+/// Least-recently-used cache of run results, optionally backed by a
+/// directory
+pub struct RunCache {
+    capacity: usize,
+    /// Results with the tick of their last use
+    entries: HashMap<CacheKey, (CachedRun, u64)>,
+    /// Keys by the tick of their last use, oldest first
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    dir: Option<PathBuf>,
+    hits: u64,
+    misses: u64,
+}
+
+impl RunCache {
+    /// Cache holding up to `capacity` results in memory
+    pub fn new(capacity: usize) -> Self {
+        RunCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            dir: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Cache that also keeps every result as a file in `dir`, creating it
+    /// if needed
+    pub fn with_dir(capacity: usize, dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(RunCache { dir: Some(dir), ..Self::new(capacity) })
+    }
+
+    /// Result stored for `key`, from memory or else from the directory
+    pub fn get(&mut self, key: &CacheKey) -> Option<CachedRun> {
+        let found = match self.entries.get(key) {
+            Some((run, _)) => Some(run.clone()),
+            None => self.load(key),
+        };
+        match found {
+            Some(run) => {
+                self.hits += 1;
+                self.remember(key.clone(), run.clone());
+                Some(run)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Store the result of a run
+    ///
+    /// A result that cannot be written to the directory is still kept in
+    /// memory.
+    pub fn insert(&mut self, key: CacheKey, run: CachedRun) {
+        if let Some(dir) = &self.dir {
+            let _ = fs::write(dir.join(format!("{}.json", key.as_str())), run.encode());
+        }
+        self.remember(key, run);
+    }
+
+    /// Results held in memory
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lookups that found a result
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups that found nothing
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    fn load(&self, key: &CacheKey) -> Option<CachedRun> {
+        let dir = self.dir.as_ref()?;
+        let text = fs::read_to_string(dir.join(format!("{}.json", key.as_str()))).ok()?;
+        CachedRun::decode(&text)
+    }
+
+    /// Keep `run` in memory as the most recently used, evicting the least
+    /// recently used beyond capacity
+    fn remember(&mut self, key: CacheKey, run: CachedRun) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, old)) = self.entries.insert(key.clone(), (run, self.tick)) {
+            self.recency.remove(&old);
+        }
+        self.recency.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> CachedRun {
+        CachedRun { output: vec![line.to_string()], error: None, stats: RunStats::default() }
+    }
+
+    #[test]
+    fn test_key() {
+        let key = |code: &str, args: &[&str], input: &[&str], steps: u64| {
+            let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            CacheKey::new(code, &strings(args), &strings(input), &[("max_steps", steps)])
+        };
+        assert_eq!(key(". 1", &["a"], &[], 10), key(". 1", &["a"], &[], 10));
+        assert_ne!(key(". 1", &["a"], &[], 10), key(". 2", &["a"], &[], 10));
+        assert_ne!(key(". 1", &["a"], &[], 10), key(". 1", &["a"], &[], 20));
+        // The same strings split differently between args and input
+        assert_ne!(key(". 1", &["a", "b"], &[], 10), key(". 1", &["a"], &["b"], 10));
+        assert_eq!(key(". 1", &[], &[], 10).as_str().len(), 64);
+    }
+
+    #[test]
+    fn test_lru() {
+        let key = |code: &str| CacheKey::new(code, &[], &[], &[]);
+        let mut cache = RunCache::new(2);
+        cache.insert(key("a"), run("a"));
+        cache.insert(key("b"), run("b"));
+        assert!(cache.get(&key("a")).is_some());
+        // "b" is now the least recently used
+        cache.insert(key("c"), run("c"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("b")).is_none());
+        assert_eq!(cache.get(&key("a")), Some(run("a")));
+        assert_eq!(cache.get(&key("c")), Some(run("c")));
+        assert_eq!((cache.hits(), cache.misses()), (3, 1));
+    }
+
+    #[test]
+    fn test_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = CacheKey::new(". 1", &[], &[], &[]);
+        let functions = [(-1, FunctionUsage { calls: 2, steps: 5, allocated: 10 })].into();
+        let failed = CachedRun {
+            output: vec!["line \"1\"\n".to_string()],
+            error: Some("Step limit exceeded: 5".to_string()),
+            stats: RunStats { steps: 5, functions },
+        };
+
+        let mut cache = RunCache::with_dir(0, dir.path()).unwrap();
+        cache.insert(key.clone(), failed.clone());
+        assert!(cache.is_empty());
+
+        let mut later = RunCache::with_dir(8, dir.path()).unwrap();
+        assert_eq!(later.get(&key), Some(failed));
+        assert_eq!(later.len(), 1);
+    }
+}
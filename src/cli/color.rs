@@ -0,0 +1,103 @@
+//! Colored terminal output of the command-line tools
+//!
+//! Every binary takes `--color auto|always|never` from [`ColorArgs`] and
+//! calls [`ColorArgs::apply`] before printing. `auto` colors only when stdout and
+//! stderr are terminals and `NO_COLOR` is unset, so logs and CI output stay
+//! free of ANSI escapes. Without the `colored-output` feature output is never
+//! colored.
+
+use clap::Args;
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+/// The `--color` flag
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct ColorArgs {
+    /// Color output: auto, always or never
+    #[arg(long, value_name = "WHEN", default_value = "auto", global = true)]
+    pub color: ColorChoice,
+}
+
+impl ColorArgs {
+    /// Turn colors on or off for the rest of the process
+    pub fn apply(&self) {
+        self.color.apply();
+    }
+}
+
+/// When to color output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    /// Always color, even when `NO_COLOR` is set
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color, given the value of `NO_COLOR` and whether output
+    /// goes to a terminal
+    pub fn enabled(self, no_color: Option<&str>, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => cfg!(feature = "colored-output"),
+            ColorChoice::Never => false,
+            // An empty NO_COLOR does not count as set
+            ColorChoice::Auto => {
+                cfg!(feature = "colored-output") && no_color.map_or(true, str::is_empty) && terminal
+            }
+        }
+    }
+
+    /// Turn colors on or off for the rest of the process
+    pub fn apply(self) {
+        let no_color = std::env::var("NO_COLOR").ok();
+        let terminal = std::io::stdout().is_terminal() && std::io::stderr().is_terminal();
+        colored::control::set_override(self.enabled(no_color.as_deref(), terminal));
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    /// Parse `auto`, `always` or `never`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice '{}' (expected auto, always or never)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled() {
+        let colored = cfg!(feature = "colored-output");
+        assert_eq!(ColorChoice::Auto.enabled(None, true), colored);
+        assert_eq!(ColorChoice::Auto.enabled(Some(""), true), colored);
+        assert!(!ColorChoice::Auto.enabled(Some("1"), true));
+        assert!(!ColorChoice::Auto.enabled(None, false));
+        assert_eq!(ColorChoice::Always.enabled(Some("1"), false), colored);
+        assert!(!ColorChoice::Never.enabled(None, true));
+        assert_eq!("never".parse(), Ok(ColorChoice::Never));
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+}
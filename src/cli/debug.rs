@@ -0,0 +1,141 @@
+//! Interactive step debugger for Sui programs, and viewer for runs
+//! recorded with `sui --record`
+//!
+//! `sui debug` and the `sui-debug` binary that aliases it.
+
+use clap::Args;
+use std::fs;
+use std::path::Path;
+
+use crate::debugger::replay::Replay;
+use crate::debugger::Debugger;
+use crate::interpreter::{OutputMode, Trace};
+
+/// Debugging a program or a recorded run
+#[derive(Debug, Clone, Args)]
+pub struct DebugArgs {
+    /// Sui source file to debug
+    #[arg(required_unless_present = "replay")]
+    pub file: Option<String>,
+
+    /// Navigate a run recorded with `sui --record` instead of running FILE
+    #[arg(long, value_name = "TRACE", conflicts_with = "export")]
+    pub replay: Option<String>,
+
+    /// Set breakpoints at these lines (comma-separated)
+    #[arg(short, long, value_delimiter = ',')]
+    pub breakpoints: Option<Vec<usize>>,
+
+    /// Serve variable values to `sui-lsp --attach` on this address
+    #[arg(long, value_name = "ADDR")]
+    pub export: Option<String>,
+
+    /// Accept remote debugging clients on this address (127.0.0.1:4711 if
+    /// omitted) instead of reading commands from the terminal
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = "127.0.0.1:4711", conflicts_with = "replay")]
+    pub serve: Option<String>,
+
+    /// Token remote clients must send with `auth`
+    #[arg(long, requires = "serve")]
+    pub token: Option<String>,
+
+    /// Let remote clients inspect the program but not run it
+    #[arg(long, requires = "serve")]
+    pub read_only: bool,
+}
+
+#[cfg(feature = "remote")]
+fn serve(debugger: &mut Debugger, addr: &str, token: Option<String>, read_only: bool) -> i32 {
+    use crate::debugger::remote::RemoteOptions;
+    use std::net::ToSocketAddrs;
+
+    let exposed = addr.to_socket_addrs().is_ok_and(|mut addrs| addrs.any(|a| !a.ip().is_loopback()));
+    if exposed {
+        eprintln!("Warning: '{}' is reachable from other machines, and remote debugging is not encrypted", addr);
+        eprintln!("Serve on 127.0.0.1 and connect through an SSH tunnel or a TLS proxy instead");
+    }
+    println!("Serving remote debugging on {}", addr);
+    let options = RemoteOptions { token, read_only, ..RemoteOptions::default() };
+    if let Err(e) = debugger.serve(addr, &options) {
+        eprintln!("Error serving on '{}': {}", addr, e);
+        return 1;
+    }
+    0
+}
+
+#[cfg(not(feature = "remote"))]
+fn serve(_debugger: &mut Debugger, _addr: &str, _token: Option<String>, _read_only: bool) -> i32 {
+    eprintln!("Error: remote debugging feature is not enabled");
+    eprintln!("Compile with: cargo build --features remote");
+    1
+}
+
+fn replay(path: &str, breakpoints: Option<Vec<usize>>) -> i32 {
+    let trace = match Trace::load(Path::new(path)) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error reading trace '{}': {}", path, e);
+            return 1;
+        }
+    };
+
+    let mut replay = Replay::new(trace);
+    for bp in breakpoints.unwrap_or_default() {
+        replay.set_breakpoint(bp);
+        println!("Breakpoint set at line {}", bp);
+    }
+    replay.run_interactive();
+    0
+}
+
+/// `sui debug`; returns the exit code
+pub fn debug(args: DebugArgs) -> i32 {
+    if let Some(path) = &args.replay {
+        return replay(path, args.breakpoints);
+    }
+    let file = args.file.unwrap_or_default();
+
+    let code = match fs::read_to_string(&file) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", file, e);
+            return 1;
+        }
+    };
+
+    let mut debugger = Debugger::new();
+    // Captured too, for the remote `output` command
+    debugger.set_output_mode(OutputMode::Both);
+
+    if let Err(e) = debugger.load(&code) {
+        eprintln!("Parse error: {}", e);
+        return 1;
+    }
+    let path = fs::canonicalize(&file).unwrap_or_else(|_| file.clone().into());
+    debugger.set_source_path(&path.display().to_string());
+
+    // Share the current frame with the language server
+    if let Some(addr) = &args.export {
+        match debugger.export(addr.as_str()) {
+            Ok(local) => println!("Exporting debug state on {}", local),
+            Err(e) => {
+                eprintln!("Error exporting on '{}': {}", addr, e);
+                return 1;
+            }
+        }
+    }
+
+    if let Some(bps) = args.breakpoints {
+        for bp in bps {
+            debugger.set_breakpoint(bp);
+            println!("Breakpoint set at line {}", bp);
+        }
+    }
+
+    if let Some(addr) = &args.serve {
+        return serve(&mut debugger, addr, args.token, args.read_only);
+    }
+
+    debugger.run_interactive();
+    0
+}
@@ -0,0 +1,163 @@
+//! Exit codes of `sui`, and the JSON result of `sui run --format json`
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | Success |
+//! | 1 | Any other failure: bad flags, a missing file, failed I/O |
+//! | 2 | The program, or a module it imports, does not parse or load |
+//! | 3 | The program failed at run time |
+//! | 4 | The program ran out of steps, stack or memory, or a step hook stopped it |
+//! | 5 | A check failed: `--validate`, `--verify`, `--typecheck`, `--check-args`, `sui lint`, `sui test`, ... |
+//!
+//! Scripts and agents can branch on the code without reading the output.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::interpreter::json::write_string;
+use crate::InterpreterError;
+
+pub const SUCCESS: i32 = 0;
+pub const FAILURE: i32 = 1;
+pub const PARSE_ERROR: i32 = 2;
+pub const RUNTIME_ERROR: i32 = 3;
+pub const LIMIT_EXCEEDED: i32 = 4;
+pub const VALIDATION_FAILED: i32 = 5;
+
+/// Exit code for a run that failed with `error`
+pub fn code(error: &InterpreterError) -> i32 {
+    match error {
+        InterpreterError::Parse(_) | InterpreterError::ModuleNotFound(_) | InterpreterError::CircularImport(_) => {
+            PARSE_ERROR
+        }
+        InterpreterError::StepLimitExceeded(_)
+        | InterpreterError::FunctionLimitExceeded { .. }
+        | InterpreterError::StackOverflow
+        | InterpreterError::ArrayTooLarge(_)
+        | InterpreterError::IntegerTooLarge(_)
+        | InterpreterError::Interrupted { .. } => LIMIT_EXCEEDED,
+        _ => RUNTIME_ERROR,
+    }
+}
+
+/// Exit code of a check: [`SUCCESS`] or [`VALIDATION_FAILED`]
+pub fn checked(success: bool) -> i32 {
+    if success {
+        SUCCESS
+    } else {
+        VALIDATION_FAILED
+    }
+}
+
+/// Short name of an exit code, as `kind` in [`RunResult::to_json`]
+pub fn kind(code: i32) -> &'static str {
+    match code {
+        SUCCESS => "ok",
+        PARSE_ERROR => "parse",
+        RUNTIME_ERROR => "runtime",
+        LIMIT_EXCEEDED => "limit",
+        VALIDATION_FAILED => "validation",
+        _ => "failure",
+    }
+}
+
+/// Outcome of running a program
+#[derive(Debug, Clone, Default)]
+pub struct RunResult {
+    /// Lines the program printed
+    pub output: Vec<String>,
+    /// Messages `sui` printed, or in JSON mode would have printed, to stderr
+    pub stderr: Vec<String>,
+    pub exit_code: i32,
+    /// Instructions executed
+    pub steps: u64,
+    pub elapsed: Duration,
+    /// Why the run failed
+    pub error: Option<String>,
+    /// Source line of the error, if it names one
+    pub line: Option<usize>,
+}
+
+impl RunResult {
+    /// Result of a run that ended with `result`
+    pub fn new(result: Result<(), &InterpreterError>, output: Vec<String>, steps: u64, elapsed: Duration) -> Self {
+        let (exit_code, error, line) = match result {
+            Ok(()) => (SUCCESS, None, None),
+            Err(e) => (code(e), Some(e.to_string()), e.line()),
+        };
+        RunResult { output, stderr: Vec::new(), exit_code, steps, elapsed, error, line }
+    }
+
+    /// `{"output", "stderr", "exit_code", "stats": {"steps", "elapsed_ms"},
+    /// "error": null or {"kind", "message", "line"}}` on one line
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"output\":[");
+        for (i, line) in self.output.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_string(&mut out, line);
+        }
+        out.push_str("],\"stderr\":");
+        let stderr: String = self.stderr.iter().map(|line| format!("{}\n", line)).collect();
+        write_string(&mut out, &stderr);
+        let _ = write!(
+            out,
+            ",\"exit_code\":{},\"stats\":{{\"steps\":{},\"elapsed_ms\":{:.3}}},\"error\":",
+            self.exit_code,
+            self.steps,
+            self.elapsed.as_secs_f64() * 1000.0
+        );
+        match &self.error {
+            Some(message) => {
+                let _ = write!(out, "{{\"kind\":\"{}\",\"message\":", kind(self.exit_code));
+                write_string(&mut out, message);
+                match self.line {
+                    Some(line) => {
+                        let _ = write!(out, ",\"line\":{}}}", line);
+                    }
+                    None => out.push_str(",\"line\":null}"),
+                }
+            }
+            None => out.push_str("null"),
+        }
+        out.push('}');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+
+    fn run(code: &str) -> RunResult {
+        let mut interp = Interpreter::new();
+        interp.set_max_steps(Some(100));
+        let result = interp.run(code, &[]);
+        RunResult::new(result.as_ref().map(|_| ()), interp.get_output().to_vec(), interp.steps(), Duration::ZERO)
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(run(". 1").exit_code, SUCCESS);
+        assert_eq!(run("+ v0 1").exit_code, PARSE_ERROR);
+        assert_eq!(run("E \"boom\"").exit_code, RUNTIME_ERROR);
+        assert_eq!(run(": 0\n@ 0").exit_code, LIMIT_EXCEEDED);
+        assert_eq!(checked(false), VALIDATION_FAILED);
+    }
+
+    #[test]
+    fn test_run_result_json() {
+        assert_eq!(
+            run(". \"a\"\n. 2").to_json(),
+            r#"{"output":["a","2"],"stderr":"","exit_code":0,"stats":{"steps":2,"elapsed_ms":0.000},"error":null}"#
+        );
+        let mut result = run(". 1\nE \"boom\"");
+        result.stderr.push("Recorded 2 steps to run.bin".to_string());
+        assert_eq!(
+            result.to_json(),
+            r#"{"output":["1"],"stderr":"Recorded 2 steps to run.bin\n","exit_code":3,"stats":{"steps":2,"elapsed_ms":0.000},"error":{"kind":"runtime","message":"Uncaught error at line 2: boom","line":2}}"#
+        );
+    }
+}
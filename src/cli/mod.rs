@@ -0,0 +1,20 @@
+//! Building blocks of the command-line tools
+//!
+//! `sui` runs programs and, through subcommands, everything else: `sui
+//! transpile python|js|wasm`, `sui from-python`, `sui debug` and so on. The
+//! `sui2py`, `sui2js`, `sui2wasm`, `py2sui` and `sui-debug` binaries remain
+//! as aliases of those subcommands. Both parse the same flag definitions
+//! from this module and share its messages and reports, so an alias and its
+//! subcommand behave alike. [`exit`] defines what their exit codes mean.
+
+mod color;
+pub mod debug;
+pub mod exit;
+mod output;
+pub mod transpile;
+
+pub use color::{ColorArgs, ColorChoice};
+pub use output::{
+    diagnostics_json, error, exit_code, failure, read_source, report_diagnostics, success, usage, write_output,
+    ReportFormat,
+};
@@ -0,0 +1,150 @@
+//! Messages, files and reports shared by the binaries
+
+use clap::ValueEnum;
+use colored::Colorize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitStatus;
+
+use crate::analysis::lint;
+use crate::ide::{lint_diagnostics, parse_diagnostics};
+use crate::interpreter::Parser;
+
+/// How `--validate` and `sui lint` report problems
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ReportFormat {
+    /// Warnings and errors, a line each
+    #[default]
+    Text,
+    /// `{"valid": ..., "diagnostics": [...]}`
+    Json,
+}
+
+/// Print `Error: message` to stderr
+pub fn error(message: impl fmt::Display) {
+    failure("Error", message);
+}
+
+/// Print `label: message` to stderr, such as `Transpile error: ...`
+pub fn failure(label: &str, message: impl fmt::Display) {
+    eprintln!("{}: {}", label.red(), message);
+}
+
+/// Print `✓ message`
+pub fn success(message: impl fmt::Display) {
+    println!("{} {}", "✓".green(), message);
+}
+
+/// Read a source file, reporting a missing or unreadable one
+pub fn read_source(path: &Path) -> Option<String> {
+    if !path.exists() {
+        error(format!("File not found: {}", path.display()));
+        return None;
+    }
+    match fs::read_to_string(path) {
+        Ok(code) => Some(code),
+        Err(e) => {
+            error(format!("Failed to read file: {}", e));
+            None
+        }
+    }
+}
+
+/// Write `contents` to `path` and print `✓ {what} saved to path`; `false`
+/// if writing failed
+pub fn write_output(path: &Path, contents: impl AsRef<[u8]>, what: &str) -> bool {
+    match fs::write(path, contents) {
+        Ok(()) => {
+            success(format!("{} saved to {}", what, path.display()));
+            true
+        }
+        Err(e) => {
+            error(format!("Failed to write file: {}", e));
+            false
+        }
+    }
+}
+
+/// Exit code of a tool the binary ran, such as Node.js; 1 if it did not
+/// start
+pub fn exit_code(status: io::Result<ExitStatus>, tool: &str) -> i32 {
+    match status {
+        Ok(status) if status.success() => 0,
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            error(format!("Failed to run {}: {}", tool, e));
+            1
+        }
+    }
+}
+
+/// Print a title and usage lines, each `name command` with its comment
+/// aligned
+pub fn usage(title: &str, name: &str, commands: &[(&str, &str)]) {
+    println!("{}", title.cyan().bold());
+    println!("{}", "=".repeat(50));
+    println!();
+    println!("Usage:");
+    let lines: Vec<String> = commands.iter().map(|(command, _)| format!("{} {}", name, command)).collect();
+    let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    for (line, (_, comment)) in lines.iter().zip(commands) {
+        println!("  {:<width$} # {}", line, comment, width = width);
+    }
+    println!();
+}
+
+/// Print the parse errors and lint warnings of `code` in `format`; `false`
+/// if it does not parse
+pub fn report_diagnostics(code: &str, format: ReportFormat) -> bool {
+    if format == ReportFormat::Json {
+        println!("{}", diagnostics_json(code));
+        return parse_diagnostics(code).is_empty();
+    }
+
+    let warnings = lint(code);
+    if !warnings.is_empty() {
+        println!("{}", "Warnings:".yellow());
+        for w in warnings {
+            println!("  {}", w);
+        }
+    }
+
+    let errors = Parser::validate(code);
+    if errors.is_empty() {
+        success("Validation successful");
+        true
+    } else {
+        println!("{}", "Validation errors:".red());
+        for e in errors {
+            println!("  {}", e);
+        }
+        false
+    }
+}
+
+/// `{"valid": ..., "diagnostics": [...]}` with the parse errors and lint
+/// warnings of `code` in line order
+pub fn diagnostics_json(code: &str) -> String {
+    let mut diagnostics = parse_diagnostics(code);
+    let valid = diagnostics.is_empty();
+    diagnostics.extend(lint_diagnostics(code));
+    diagnostics.sort_by_key(|d| d.line);
+    let items: Vec<String> = diagnostics.iter().map(|d| d.to_json()).collect();
+    format!("{{\"valid\":{},\"diagnostics\":[{}]}}", valid, items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_json() {
+        assert_eq!(diagnostics_json(". 1"), r#"{"valid":true,"diagnostics":[]}"#);
+        assert_eq!(
+            diagnostics_json("@ 1\nX"),
+            r#"{"valid":false,"diagnostics":[{"line":1,"column":1,"severity":"warning","message":"Jump to undefined label 1"},{"line":2,"column":1,"severity":"error","message":"Invalid instruction 'X' at line 2"}]}"#
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Transpiling between Sui and Python, JavaScript and WebAssembly
+//!
+//! `sui transpile python|js|wasm` and `sui from-python`, and the `sui2py`,
+//! `sui2js`, `sui2wasm` and `py2sui` binaries that alias them. Without a
+//! file each prints its usage and a sample.
+
+use clap::Args;
+use colored::Colorize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{self, Command, ExitStatus, Stdio};
+
+use super::output::{error, exit_code, failure, read_source, usage, write_output};
+use crate::interpreter::DivisionPolicy;
+use crate::transpiler::{Py2Sui, Sui2Js, Sui2Py, Sui2Wasm};
+
+/// Sui to Python
+#[derive(Debug, Clone, Args)]
+pub struct PythonArgs {
+    /// Sui source file to convert
+    #[arg(value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Output file path
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Convert and run immediately
+    #[arg(long)]
+    pub run: bool,
+
+    /// What division by zero produces: nan, error or zero
+    #[arg(long, value_name = "POLICY", default_value = "nan")]
+    pub division: DivisionPolicy,
+
+    /// Arguments to pass when running
+    #[arg(value_name = "ARGS", last = true)]
+    pub args: Vec<String>,
+}
+
+/// Sui to JavaScript or TypeScript
+#[derive(Debug, Clone, Args)]
+pub struct JsArgs {
+    /// Sui source file to convert
+    #[arg(value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Output file path
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Convert and run immediately with Node.js
+    #[arg(long)]
+    pub run: bool,
+
+    /// Generate browser-compatible code (no Node.js APIs)
+    #[arg(long)]
+    pub browser: bool,
+
+    /// Generate ES module code
+    #[arg(long)]
+    pub esm: bool,
+
+    /// Generate TypeScript, declaring variables with their inferred types
+    #[arg(long)]
+    pub ts: bool,
+
+    /// What division by zero produces: nan, error or zero
+    #[arg(long, value_name = "POLICY", default_value = "nan")]
+    pub division: DivisionPolicy,
+
+    /// Arguments to pass when running
+    #[arg(value_name = "ARGS", last = true)]
+    pub args: Vec<String>,
+}
+
+/// Sui to WebAssembly
+#[derive(Debug, Clone, Args)]
+pub struct WasmArgs {
+    /// Sui source file to compile
+    #[arg(value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Output file path (.wasm for binary, anything else for WAT text)
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Compile and run immediately with Node.js
+    #[arg(long)]
+    pub run: bool,
+
+    /// Write the JavaScript host loader to this path
+    #[arg(long, value_name = "HOST")]
+    pub host: Option<PathBuf>,
+}
+
+/// Python to Sui
+#[derive(Debug, Clone, Args)]
+pub struct FromPythonArgs {
+    /// Python source file to convert
+    #[arg(value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Output file path
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<PathBuf>,
+}
+
+const SAMPLE: &str = r#"
+= v0 10
++ v1 v0 5
+. v1
+"#;
+
+/// Print a sample program and its translation
+fn print_sample(heading: &str, from: &str, code: &str, to: &str, translated: Result<String, impl std::fmt::Display>) {
+    println!("{}", heading.yellow());
+    println!("{}", "-".repeat(50));
+    println!("{}", format!("{}:", from).green());
+    println!("{}", code.trim());
+    println!();
+    println!("{}", format!("{}:", to).green());
+    match translated {
+        Ok(result) => println!("{}", result),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+/// `sui transpile python`; `name` is the command in the usage text.
+/// Returns the exit code
+pub fn python(args: &PythonArgs, name: &str) -> i32 {
+    let Some(file) = &args.file else {
+        usage(
+            "Sui (粋) to Python Transpiler",
+            name,
+            &[
+                ("<file.sui>", "Show converted code"),
+                ("<file.sui> -o out.py", "Output to file"),
+                ("<file.sui> --run", "Convert and execute"),
+            ],
+        );
+        print_sample("Sample:", "Sui", SAMPLE, "Python", Sui2Py::new().transpile_to_python(SAMPLE));
+        return 0;
+    };
+    let Some(code) = read_source(file) else {
+        return 1;
+    };
+
+    let mut transpiler = Sui2Py::new();
+    transpiler.set_division_policy(args.division);
+    let python_code = match transpiler.transpile_to_python(&code) {
+        Ok(c) => c,
+        Err(e) => {
+            failure("Transpile error", e);
+            return 1;
+        }
+    };
+
+    if let Some(output_path) = &args.output {
+        if !write_output(output_path, &python_code, "Output") {
+            return 1;
+        }
+        0
+    } else if args.run {
+        let status = Command::new("python3").arg("-c").arg(&python_code).args(&args.args).status();
+        exit_code(status, "Python")
+    } else {
+        println!("{}", python_code);
+        0
+    }
+}
+
+/// Run the code with Node.js, reading it from stdin so that the program's
+/// arguments start at `process.argv[2]` as with a script file
+fn run_with_node(js_code: &str, esm: bool, args: &[String]) -> io::Result<ExitStatus> {
+    let mut cmd = Command::new("node");
+    if esm {
+        cmd.arg("--input-type=module");
+    }
+    cmd.arg("-").args(args).stdin(Stdio::piped());
+
+    let mut child = cmd.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(js_code.as_bytes())?;
+    }
+    child.wait()
+}
+
+/// `sui transpile js`; `name` is the command in the usage text. Returns
+/// the exit code
+pub fn javascript(args: &JsArgs, name: &str) -> i32 {
+    let Some(file) = &args.file else {
+        usage(
+            "Sui (粋) to JavaScript Transpiler",
+            name,
+            &[
+                ("<file.sui>", "Show converted code"),
+                ("<file.sui> -o out.js", "Output to file"),
+                ("<file.sui> --run", "Convert and execute with Node.js"),
+                ("<file.sui> --browser", "Generate browser-compatible code"),
+            ],
+        );
+        print_sample("Sample:", "Sui", SAMPLE, "JavaScript", Sui2Js::new().transpile_to_js(SAMPLE));
+        return 0;
+    };
+    let Some(code) = read_source(file) else {
+        return 1;
+    };
+
+    let mut transpiler = Sui2Js::new();
+    transpiler.set_nodejs(!args.browser);
+    transpiler.set_esm(args.esm);
+    transpiler.set_typescript(args.ts);
+    transpiler.set_division_policy(args.division);
+    let js_code = match transpiler.transpile_to_js(&code) {
+        Ok(c) => c,
+        Err(e) => {
+            failure("Transpile error", e);
+            return 1;
+        }
+    };
+
+    if let Some(output_path) = &args.output {
+        if !write_output(output_path, &js_code, "Output") {
+            return 1;
+        }
+        0
+    } else if args.run {
+        if args.ts {
+            error("--run executes JavaScript; drop --ts");
+            return 1;
+        }
+        match run_with_node(&js_code, args.esm, &args.args) {
+            // No JavaScript engine is embedded, so --run depends on Node.js
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                error("--run needs Node.js (`node`) on PATH; write the code out with -o and run it elsewhere");
+                1
+            }
+            status => exit_code(status, "Node.js"),
+        }
+    } else {
+        println!("{}", js_code);
+        0
+    }
+}
+
+/// `sui transpile wasm`; `name` is the command in the usage text. Returns
+/// the exit code
+pub fn wasm(args: &WasmArgs, name: &str) -> i32 {
+    let Some(file) = &args.file else {
+        usage(
+            "Sui (粋) to WebAssembly Compiler",
+            name,
+            &[
+                ("<file.sui>", "Show WAT text"),
+                ("<file.sui> -o out.wasm", "Write a binary module"),
+                ("<file.sui> -o out.wat", "Write WAT text"),
+                ("<file.sui> --run", "Compile and execute with Node.js"),
+            ],
+        );
+        print_sample("Sample:", "Sui", SAMPLE, "WAT", Sui2Wasm::new().transpile_to_wat(SAMPLE));
+        return 0;
+    };
+    let Some(code) = read_source(file) else {
+        return 1;
+    };
+
+    let mut compiler = Sui2Wasm::new();
+    if let Some(host_path) = &args.host {
+        if !write_output(host_path, Sui2Wasm::js_host(), "Host") {
+            return 1;
+        }
+    }
+
+    if let Some(output_path) = &args.output {
+        let binary = output_path.extension().is_some_and(|ext| ext == "wasm");
+        let result = if binary {
+            compiler.compile_to_wasm(&code)
+        } else {
+            compiler.transpile_to_wat(&code).map(String::into_bytes)
+        };
+        match result {
+            Ok(bytes) if write_output(output_path, &bytes, "Output") => 0,
+            Ok(_) => 1,
+            Err(e) => {
+                failure("Compile error", e);
+                1
+            }
+        }
+    } else if args.run {
+        let wasm = match compiler.compile_to_wasm(&code) {
+            Ok(b) => b,
+            Err(e) => {
+                failure("Compile error", e);
+                return 1;
+            }
+        };
+
+        // Node.js needs the module and its host on disk
+        let dir = std::env::temp_dir().join(format!("sui2wasm-{}", process::id()));
+        let module_path = dir.join("module.wasm");
+        let host_path = dir.join("host.js");
+        let written = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&module_path, &wasm))
+            .and_then(|_| fs::write(&host_path, Sui2Wasm::js_host()));
+        if let Err(e) = written {
+            error(format!("Failed to write file: {}", e));
+            return 1;
+        }
+
+        let status = Command::new("node").arg(&host_path).arg(&module_path).status();
+        let _ = fs::remove_dir_all(&dir);
+        exit_code(status, "Node.js")
+    } else {
+        match compiler.transpile_to_wat(&code) {
+            Ok(wat) => {
+                println!("{}", wat);
+                0
+            }
+            Err(e) => {
+                failure("Compile error", e);
+                1
+            }
+        }
+    }
+}
+
+/// `sui from-python`; `name` is the command in the usage text. Returns the
+/// exit code
+pub fn from_python(args: &FromPythonArgs, name: &str) -> i32 {
+    let Some(file) = &args.file else {
+        usage(
+            "Python to Sui (粋) Transpiler",
+            name,
+            &[("<file.py>", "Show converted code"), ("<file.py> -o out.sui", "Output to file")],
+        );
+        let fibonacci = r#"
+def fibonacci(n):
+    if n < 2:
+        return n
+    return fibonacci(n - 1) + fibonacci(n - 2)
+
+result = fibonacci(10)
+print(result)
+"#;
+        let count = r#"
+x = 0
+while x < 10:
+    print(x)
+    x = x + 1
+"#;
+        print_sample("Sample 1 - Fibonacci:", "Python", fibonacci, "Sui", Py2Sui::new().transpile_to_sui(fibonacci));
+        println!();
+        print_sample("Sample 2 - While Loop:", "Python", count, "Sui", Py2Sui::new().transpile_to_sui(count));
+        return 0;
+    };
+    let Some(code) = read_source(file) else {
+        return 1;
+    };
+
+    let sui_code = match Py2Sui::new().transpile_to_sui(&code) {
+        Ok(c) => c,
+        Err(e) => {
+            failure("Transpile error", e);
+            return 1;
+        }
+    };
+
+    if let Some(output_path) = &args.output {
+        if !write_output(output_path, &sui_code, "Output") {
+            return 1;
+        }
+    } else {
+        println!("{}", sui_code);
+    }
+    0
+}
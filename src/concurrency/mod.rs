@@ -0,0 +1,195 @@
+//! Cooperative multitasking of Sui programs on one thread
+//!
+//! A [`Scheduler`] runs several programs, its tasks, in turns. A task runs
+//! until it calls `R v0 "yield"`, has executed its time slice of
+//! instructions, or finishes; then the next unfinished task in spawn order
+//! continues. Tasks can yield anywhere, also inside function calls. No time
+//! is measured, so the same programs always interleave the same way.
+//!
+//! Each task has its own interpreter, so globals and output are per task;
+//! [`Scheduler::transcript`] gives all output in the order it was printed.
+//!
+//! ```rust
+//! use sui_lang::concurrency::Scheduler;
+//!
+//! let mut scheduler = Scheduler::new();
+//! let ping = scheduler.spawn(". \"ping\"\nR v0 \"yield\"\n. \"ping\"", &[]).unwrap();
+//! let pong = scheduler.spawn(". \"pong\"\nR v0 \"yield\"\n. \"pong\"", &[]).unwrap();
+//! scheduler.run();
+//! let lines: Vec<_> = scheduler.transcript().iter().map(|(_, line)| line.as_str()).collect();
+//! assert_eq!(lines, ["ping", "pong", "ping", "pong"]);
+//! assert_eq!(scheduler.output(ping), ["ping", "ping"]);
+//! assert_eq!(scheduler.transcript()[1].0, pong);
+//! ```
+
+use crate::interpreter::{Interpreter, InterpreterError, Program};
+
+/// Index of a task in spawn order
+pub type TaskId = usize;
+
+/// Where a task stands
+#[derive(Debug)]
+pub enum TaskStatus {
+    /// Waiting for its next turn
+    Ready,
+    /// Ran to the end
+    Finished,
+    /// Stopped on an uncaught error
+    Failed(InterpreterError),
+}
+
+impl TaskStatus {
+    /// Whether the task will run again
+    pub fn is_ready(&self) -> bool {
+        matches!(self, TaskStatus::Ready)
+    }
+}
+
+struct Task {
+    interp: Interpreter,
+    program: Program,
+    status: TaskStatus,
+    /// Output lines already copied to the transcript
+    printed: usize,
+}
+
+/// Round-robin scheduler of Sui programs
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    /// Instructions a task runs before the next one gets a turn
+    time_slice: Option<u64>,
+    /// Task to try first on the next turn
+    next: TaskId,
+    /// Every output line, tagged with its task
+    transcript: Vec<(TaskId, String)>,
+}
+
+impl Scheduler {
+    /// Scheduler where tasks only switch when they yield or finish
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also switch tasks after `instructions` instructions, so a task that
+    /// never yields cannot starve the others; `None` turns this off
+    pub fn set_time_slice(&mut self, instructions: Option<u64>) {
+        self.time_slice = instructions;
+    }
+
+    /// Add a task running `code`, with `args` as for [`Interpreter::run`]
+    ///
+    /// The code is parsed and its imports loaded now; nothing runs until
+    /// the task's first turn.
+    pub fn spawn(&mut self, code: &str, args: &[String]) -> Result<TaskId, InterpreterError> {
+        self.spawn_with(Interpreter::new(), code, args)
+    }
+
+    /// Add a task run by `interp`, keeping its settings such as step
+    /// limits and channels
+    pub fn spawn_with(&mut self, mut interp: Interpreter, code: &str, args: &[String]) -> Result<TaskId, InterpreterError> {
+        let program = interp.start(code, args)?;
+        self.tasks.push(Task { interp, program, status: TaskStatus::Ready, printed: 0 });
+        Ok(self.tasks.len() - 1)
+    }
+
+    /// Give the next ready task one turn, returning which task ran, or
+    /// `None` once every task is done
+    pub fn step(&mut self) -> Option<TaskId> {
+        let count = self.tasks.len();
+        let id = (0..count).map(|k| (self.next + k) % count).find(|&id| self.tasks[id].status.is_ready())?;
+        let task = &mut self.tasks[id];
+        match task.interp.run_slice(&task.program, self.time_slice) {
+            Ok(true) => task.status = TaskStatus::Finished,
+            Ok(false) => {}
+            Err(error) => task.status = TaskStatus::Failed(error),
+        }
+        let output = task.interp.get_output();
+        self.transcript.extend(output[task.printed..].iter().map(|line| (id, line.clone())));
+        task.printed = output.len();
+        self.next = id + 1;
+        Some(id)
+    }
+
+    /// Run until every task has finished or failed
+    pub fn run(&mut self) {
+        while self.step().is_some() {}
+    }
+
+    /// Number of tasks spawned
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Status of task `id`
+    ///
+    /// # Panics
+    /// If no task `id` was spawned.
+    pub fn status(&self, id: TaskId) -> &TaskStatus {
+        &self.tasks[id].status
+    }
+
+    /// Output of task `id` so far
+    ///
+    /// # Panics
+    /// If no task `id` was spawned.
+    pub fn output(&self, id: TaskId) -> &[String] {
+        self.tasks[id].interp.get_output()
+    }
+
+    /// Output of all tasks in the order it was printed
+    pub fn transcript(&self) -> &[(TaskId, String)] {
+        &self.transcript
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yield_in_calls() {
+        // Counts to a0, yielding from a recursive function after each number
+        let counter = "# 0 2 {\n. a1\n< v0 a1 a0\n! v0 v0\n? v0 1\nR v1 \"yield\"\n+ v2 a1 1\n$ v3 0 a0 v2\n: 1\n^ 0\n}\n$ g0 0 g101 g102";
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.spawn(counter, &["3".to_string(), "1".to_string()]).unwrap();
+        let b = scheduler.spawn(counter, &["12".to_string(), "10".to_string()]).unwrap();
+        scheduler.run();
+
+        let order: Vec<(TaskId, &str)> = scheduler.transcript().iter().map(|(id, line)| (*id, line.as_str())).collect();
+        assert_eq!(order, [(a, "1"), (b, "10"), (a, "2"), (b, "11"), (a, "3"), (b, "12")]);
+        assert!(matches!(scheduler.status(a), TaskStatus::Finished));
+        assert_eq!(scheduler.output(b), ["10", "11", "12"]);
+    }
+
+    #[test]
+    fn test_time_slice() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_time_slice(Some(3));
+        // Never yields and never ends
+        let spinner = scheduler.spawn(": 0\n@ 0", &[]).unwrap();
+        let worker = scheduler.spawn("= v0 0\n: 0\n+ v0 v0 1\n< v1 v0 5\n? v1 0\n. v0", &[]).unwrap();
+        while scheduler.status(worker).is_ready() {
+            scheduler.step();
+        }
+        assert_eq!(scheduler.output(worker), ["5"]);
+        assert!(scheduler.status(spinner).is_ready());
+    }
+
+    #[test]
+    fn test_failed_task() {
+        let mut scheduler = Scheduler::new();
+        let failing = scheduler.spawn("T v0 1\nR v1 \"yield\"\nE \"caught\"\nU\n: 1\n. v0\nE \"boom\"", &[]).unwrap();
+        let other = scheduler.spawn("R v0 \"yield\"\n. 1", &[]).unwrap();
+        scheduler.run();
+        assert_eq!(scheduler.output(failing), ["caught"]);
+        assert!(matches!(scheduler.status(failing), TaskStatus::Failed(InterpreterError::Raised { line: 7, .. })));
+        assert!(matches!(scheduler.status(other), TaskStatus::Finished));
+        assert!(scheduler.spawn("+ v0", &[]).is_err());
+        assert_eq!(scheduler.len(), 2);
+    }
+}
@@ -0,0 +1,379 @@
+//! Corpus manifests: the subset of TOML they use
+//!
+//! Top-level keys set defaults; each `[[program]]` table describes one run.
+//! Values are strings (`"..."` with escapes, or `'...'` literal), integers,
+//! booleans and arrays of those, which may span lines.
+
+use std::path::{Path, PathBuf};
+
+/// A program of a corpus, with how to run it and what it should do
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorpusProgram {
+    /// Source file, relative to the manifest
+    pub path: PathBuf,
+    /// Name in reports; defaults to the path
+    pub name: Option<String>,
+    /// Arguments, as on the command line
+    pub args: Vec<String>,
+    /// Lines for `,` to read
+    pub stdin: Vec<String>,
+    /// Exact output expected, if checked
+    pub expected: Option<Vec<String>>,
+    /// Text the error must contain, for programs that should fail
+    pub error: Option<String>,
+    /// Instruction limit, overriding the manifest's
+    pub max_steps: Option<u64>,
+    /// What the program shows, such as `recursion`, for picking examples
+    pub tags: Vec<String>,
+}
+
+impl CorpusProgram {
+    /// Name shown in reports
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.path.display().to_string())
+    }
+}
+
+/// Programs to run and check together
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    /// Directory the program paths are relative to
+    pub base_dir: PathBuf,
+    /// Instruction limit for programs that set none
+    pub max_steps: Option<u64>,
+    pub programs: Vec<CorpusProgram>,
+}
+
+/// Parsed TOML value
+#[derive(Debug, Clone, PartialEq)]
+enum TomlValue {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<TomlValue>),
+}
+
+impl TomlValue {
+    fn kind(&self) -> &'static str {
+        match self {
+            TomlValue::String(_) => "a string",
+            TomlValue::Integer(_) => "an integer",
+            TomlValue::Boolean(_) => "a boolean",
+            TomlValue::Array(_) => "an array",
+        }
+    }
+
+    /// Text of a string, or of a number or boolean written bare
+    fn to_text(&self) -> Option<String> {
+        match self {
+            TomlValue::String(s) => Some(s.clone()),
+            TomlValue::Integer(n) => Some(n.to_string()),
+            TomlValue::Boolean(b) => Some(b.to_string()),
+            TomlValue::Array(_) => None,
+        }
+    }
+}
+
+impl Manifest {
+    /// Read the manifest at `path`; programs are relative to its directory
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Self::parse(&text, base_dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse manifest text with programs relative to `base_dir`
+    pub fn parse(text: &str, base_dir: impl Into<PathBuf>) -> Result<Self, String> {
+        let mut manifest = Manifest { base_dir: base_dir.into(), ..Manifest::default() };
+        let mut parser = TomlParser { chars: text.chars().collect(), pos: 0, line: 1 };
+        // Program being read, with the line of its header
+        let mut current: Option<(CorpusProgram, usize)> = None;
+
+        loop {
+            parser.skip_space(true);
+            let Some(c) = parser.peek() else {
+                break;
+            };
+            let line = parser.line;
+            if c == '[' {
+                if !parser.eat_str("[[") {
+                    return Err(format!("line {}: only [[program]] tables are supported", line));
+                }
+                let name = parser.until("]]").ok_or(format!("line {}: unclosed table header", line))?;
+                if name.trim() != "program" {
+                    return Err(format!("line {}: unknown table '{}'", line, name.trim()));
+                }
+                if let Some((program, header)) = current.take() {
+                    manifest.programs.push(finish(program, header)?);
+                }
+                current = Some((CorpusProgram::default(), line));
+                continue;
+            }
+
+            let key = parser.key().ok_or(format!("line {}: expected a key", line))?;
+            parser.skip_space(false);
+            if !parser.eat_str("=") {
+                return Err(format!("line {}: expected '=' after '{}'", line, key));
+            }
+            parser.skip_space(false);
+            let value = parser.value().map_err(|e| format!("line {}: {}", parser.line, e))?;
+            let set = match &mut current {
+                Some((program, _)) => set_program_key(program, &key, value),
+                None => set_manifest_key(&mut manifest, &key, value),
+            };
+            set.map_err(|e| format!("line {}: {}", line, e))?;
+            parser.skip_space(false);
+            if !matches!(parser.peek(), None | Some('\n')) {
+                return Err(format!("line {}: unexpected text after the value of '{}'", line, key));
+            }
+        }
+        if let Some((program, header)) = current {
+            manifest.programs.push(finish(program, header)?);
+        }
+        Ok(manifest)
+    }
+}
+
+fn finish(program: CorpusProgram, header: usize) -> Result<CorpusProgram, String> {
+    if program.path.as_os_str().is_empty() {
+        return Err(format!("line {}: program has no path", header));
+    }
+    Ok(program)
+}
+
+fn set_manifest_key(manifest: &mut Manifest, key: &str, value: TomlValue) -> Result<(), String> {
+    match key {
+        "max_steps" => manifest.max_steps = Some(integer(key, value)?),
+        _ => return Err(format!("unknown key '{}'", key)),
+    }
+    Ok(())
+}
+
+fn set_program_key(program: &mut CorpusProgram, key: &str, value: TomlValue) -> Result<(), String> {
+    match key {
+        "path" => program.path = PathBuf::from(string(key, value)?),
+        "name" => program.name = Some(string(key, value)?),
+        "args" => program.args = strings(key, value)?,
+        "stdin" => program.stdin = strings(key, value)?,
+        "expected" => program.expected = Some(strings(key, value)?),
+        "error" => program.error = Some(string(key, value)?),
+        "max_steps" => program.max_steps = Some(integer(key, value)?),
+        "tags" => program.tags = strings(key, value)?,
+        _ => return Err(format!("unknown key '{}'", key)),
+    }
+    Ok(())
+}
+
+fn string(key: &str, value: TomlValue) -> Result<String, String> {
+    match value {
+        TomlValue::String(s) => Ok(s),
+        other => Err(format!("'{}' must be a string, not {}", key, other.kind())),
+    }
+}
+
+fn integer(key: &str, value: TomlValue) -> Result<u64, String> {
+    match value {
+        TomlValue::Integer(n) if n >= 0 => Ok(n as u64),
+        TomlValue::Integer(_) => Err(format!("'{}' must not be negative", key)),
+        other => Err(format!("'{}' must be an integer, not {}", key, other.kind())),
+    }
+}
+
+/// An array of strings, numbers or booleans as text, or a string split
+/// into lines
+fn strings(key: &str, value: TomlValue) -> Result<Vec<String>, String> {
+    match value {
+        TomlValue::Array(items) => items
+            .iter()
+            .map(|item| item.to_text().ok_or(format!("'{}' must not hold nested arrays", key)))
+            .collect(),
+        TomlValue::String(s) => Ok(s.lines().map(str::to_string).collect()),
+        other => Err(format!("'{}' must be an array, not {}", key, other.kind())),
+    }
+}
+
+struct TomlParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl TomlParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let matches = s.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            s.chars().for_each(|_| {
+                self.bump();
+            });
+        }
+        matches
+    }
+
+    /// Skip spaces and comments, and newlines too if `newlines`
+    fn skip_space(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => {}
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                    continue;
+                }
+                _ => break,
+            }
+            self.bump();
+        }
+    }
+
+    /// Text up to `end`, consuming both, on one line
+    fn until(&mut self, end: &str) -> Option<String> {
+        let mut text = String::new();
+        while !self.eat_str(end) {
+            match self.peek() {
+                None | Some('\n') => return None,
+                Some(c) => text.push(c),
+            }
+            self.bump();
+        }
+        Some(text)
+    }
+
+    fn key(&mut self) -> Option<String> {
+        let mut key = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-') {
+            key.push(c);
+            self.bump();
+        }
+        (!key.is_empty()).then_some(key)
+    }
+
+    fn value(&mut self) -> Result<TomlValue, String> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(TomlValue::String),
+            Some('\'') => {
+                self.bump();
+                self.until("'").map(TomlValue::String).ok_or("unclosed string".to_string())
+            }
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_space(true);
+                    if self.eat_str("]") {
+                        return Ok(TomlValue::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_space(true);
+                    if !self.eat_str(",") {
+                        self.skip_space(true);
+                        return if self.eat_str("]") { Ok(TomlValue::Array(items)) } else { Err("expected ',' or ']'".to_string()) };
+                    }
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+')) {
+                    word.push(c);
+                    self.bump();
+                }
+                match word.as_str() {
+                    "true" => Ok(TomlValue::Boolean(true)),
+                    "false" => Ok(TomlValue::Boolean(false)),
+                    _ => word.replace('_', "").parse().map(TomlValue::Integer).map_err(|_| format!("invalid value '{}'", word)),
+                }
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err("unclosed string".to_string()),
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('u') => {
+                            let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                            u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or("invalid \\u escape")?
+                        }
+                        _ => return Err("invalid escape".to_string()),
+                    };
+                    s.push(escaped);
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+# Defaults
+max_steps = 1_000
+
+[[program]]
+path = "fib_args.sui"   # tenth number
+args = ["10", 3]
+expected = [
+    "55",
+    'C:\raw',
+]
+
+[[program]]
+name = "echo"
+path = "echo.sui"
+stdin = "a\nb"
+error = "line 2"
+max_steps = 5
+tags = ["io", "errors"]
+"#;
+        let manifest = Manifest::parse(text, "corpus").unwrap();
+        assert_eq!(manifest.max_steps, Some(1000));
+        assert_eq!(manifest.programs.len(), 2);
+        let fib = &manifest.programs[0];
+        assert_eq!((fib.path.to_str(), &fib.args[..]), (Some("fib_args.sui"), &["10".to_string(), "3".to_string()][..]));
+        assert_eq!(fib.expected.as_deref(), Some(&["55".to_string(), "C:\\raw".to_string()][..]));
+        let echo = &manifest.programs[1];
+        assert_eq!((echo.display_name(), &echo.stdin[..]), ("echo".to_string(), &["a".to_string(), "b".to_string()][..]));
+        assert_eq!((echo.error.as_deref(), echo.max_steps), (Some("line 2"), Some(5)));
+        assert_eq!(echo.tags, ["io", "errors"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| Manifest::parse(text, "").unwrap_err();
+        assert_eq!(error("[table]"), "line 1: only [[program]] tables are supported");
+        assert_eq!(error("[[program]]\npath = 3"), "line 2: 'path' must be a string, not an integer");
+        assert_eq!(error("[[program]]\nargs = [\"a\"\n"), "line 3: expected ',' or ']'");
+        assert_eq!(error("[[program]]\nargs = []"), "line 1: program has no path");
+        assert_eq!(error("colour = 1"), "line 1: unknown key 'colour'");
+        assert_eq!(error("[[program]]\npath = \"a\" b"), "line 2: unexpected text after the value of 'path'");
+    }
+}
@@ -0,0 +1,274 @@
+//! Running a corpus of programs against expected results
+//!
+//! A [`Manifest`] lists programs with their arguments, input and expected
+//! output or error; [`CorpusRunner`] runs them all and reports which
+//! passed. `sui corpus run manifest.toml` does the same from the shell:
+//!
+//! ```toml
+//! max_steps = 1_000_000
+//!
+//! [[program]]
+//! path = "fib_args.sui"
+//! args = ["15"]
+//! expected = ["610"]
+//! tags = ["recursion"]
+//!
+//! [[program]]
+//! name = "rejects bad input"
+//! path = "parse.sui"
+//! stdin = ["not a number"]
+//! error = "Uncaught error"
+//! ```
+//!
+//! A program passes if its output equals `expected` (when given) and it
+//! fails with an error containing `error` (when given) or succeeds (when
+//! not). Programs run from their files with a virtual clock, no
+//! environment or commands, and `stdin` as their only input.
+
+mod manifest;
+
+pub use manifest::{CorpusProgram, Manifest};
+
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::interpreter::{Clock, Interpreter, InterpreterError};
+
+/// Instruction limit for programs when the manifest sets none
+pub const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+
+/// Result of one program of a corpus
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    /// Name of the program in the manifest
+    pub name: String,
+    /// What it printed
+    pub output: Vec<String>,
+    /// What it failed with, if it did
+    pub error: Option<String>,
+    /// Why it did not pass, or `None` if it did
+    pub failure: Option<String>,
+    /// Instructions executed
+    pub steps: u64,
+    pub elapsed: Duration,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Results of running a corpus, in manifest order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorpusReport {
+    pub results: Vec<CaseResult>,
+}
+
+impl CorpusReport {
+    /// Programs that passed
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    /// Programs that did not pass
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Whether every program passed
+    pub fn success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// Time spent running the programs
+    pub fn elapsed(&self) -> Duration {
+        self.results.iter().map(|r| r.elapsed).sum()
+    }
+}
+
+impl fmt::Display for CorpusReport {
+    /// A line per program, the reason under each failure, and a summary
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            match &result.failure {
+                None => writeln!(f, "PASS {}", result.name)?,
+                Some(failure) => {
+                    writeln!(f, "FAIL {}", result.name)?;
+                    for line in failure.lines() {
+                        writeln!(f, "     {}", line)?;
+                    }
+                }
+            }
+        }
+        write!(
+            f,
+            "{} programs: {} passed, {} failed ({:.2}s)",
+            self.results.len(),
+            self.passed(),
+            self.failed(),
+            self.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// Runs the programs of a manifest
+#[derive(Debug, Clone, Default)]
+pub struct CorpusRunner {
+    /// Only run programs whose name contains this
+    filter: Option<String>,
+}
+
+impl CorpusRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only run programs whose name contains `filter`
+    pub fn set_filter(&mut self, filter: Option<String>) {
+        self.filter = filter;
+    }
+
+    /// Run the programs of `manifest` and check them
+    pub fn run(&self, manifest: &Manifest) -> CorpusReport {
+        let results = manifest
+            .programs
+            .iter()
+            .filter(|program| self.filter.as_ref().map_or(true, |f| program.display_name().contains(f.as_str())))
+            .map(|program| run_program(manifest, program))
+            .collect();
+        CorpusReport { results }
+    }
+}
+
+fn run_program(manifest: &Manifest, program: &CorpusProgram) -> CaseResult {
+    let mut interp = Interpreter::new();
+    interp.set_max_steps(Some(program.max_steps.or(manifest.max_steps).unwrap_or(DEFAULT_MAX_STEPS)));
+    run_case(interp, program, &manifest.base_dir.join(&program.path), None)
+}
+
+/// Run the program at `path`, or `code` as if it were at `path`, on
+/// `interp` with the arguments and input of `program`, and check the run
+/// against it
+pub(crate) fn run_case(mut interp: Interpreter, program: &CorpusProgram, path: &Path, code: Option<&str>) -> CaseResult {
+    let start = Instant::now();
+    let (output, result) = execute(&mut interp, program, path, code);
+    let elapsed = start.elapsed();
+    let error = result.err().map(|e| e.to_string());
+    let failure = program.check(&output, error.as_deref());
+    CaseResult { name: program.display_name(), output, error, failure, steps: interp.steps(), elapsed }
+}
+
+/// Run as [`run_case`] does, returning the output lines and how the run
+/// ended
+pub(crate) fn execute(
+    interp: &mut Interpreter,
+    program: &CorpusProgram,
+    path: &Path,
+    code: Option<&str>,
+) -> (Vec<String>, Result<(), InterpreterError>) {
+    interp.set_clock(Clock::Virtual { epoch: 0.0 });
+    interp.set_input_queue(program.stdin.clone());
+    let result = match code {
+        Some(code) => {
+            interp.set_current_file(Some(path.to_path_buf()));
+            interp.run_incremental(code, &program.args)
+        }
+        None => interp.run_file(path, &program.args),
+    };
+    // Entries holding newlines print as several lines
+    let output = interp.get_output().iter().flat_map(|entry| entry.split('\n')).map(str::to_string).collect();
+    (output, result.map(|_| ()))
+}
+
+impl CorpusProgram {
+    /// Why a run printing `output` and failing with `error` does not do
+    /// what this program should, or `None` if it does
+    pub fn check(&self, output: &[String], error: Option<&str>) -> Option<String> {
+        match (&self.error, error) {
+            (None, Some(error)) => Some(format!("failed: {}", error)),
+            (Some(expected), None) => Some(format!("succeeded, expected an error containing '{}'", expected)),
+            (Some(expected), Some(error)) if !error.contains(expected.as_str()) => {
+                Some(format!("failed with '{}', expected an error containing '{}'", error, expected))
+            }
+            _ => match &self.expected {
+                Some(expected) if expected.as_slice() != output => Some(output_mismatch(expected, output)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Where the output first differs from the expected output
+fn output_mismatch(expected: &[String], output: &[String]) -> String {
+    let line = expected.iter().zip(output).take_while(|(e, o)| e == o).count();
+    let show = |lines: &[String]| lines.get(line).map_or("(end of output)".to_string(), |l| format!("{:?}", l));
+    format!(
+        "output differs at line {}\nexpected: {}\n  actual: {}",
+        line + 1,
+        show(expected),
+        show(output)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_runner() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("sum.sui"), "+ v0 g101 g102\n. v0").unwrap();
+        fs::write(dir.path().join("echo.sui"), ", v0\n. v0\nE v0").unwrap();
+        fs::write(dir.path().join("loop.sui"), ": 0\n@ 0").unwrap();
+        let manifest = Manifest::parse(
+            r#"
+max_steps = 1000
+
+[[program]]
+path = "sum.sui"
+args = [2, 3]
+expected = ["5"]
+
+[[program]]
+name = "wrong sum"
+path = "sum.sui"
+args = [2, 2]
+expected = ["5"]
+
+[[program]]
+path = "echo.sui"
+stdin = ["boom"]
+expected = ["boom"]
+error = "boom"
+
+[[program]]
+path = "loop.sui"
+"#,
+            dir.path(),
+        )
+        .unwrap();
+
+        let report = CorpusRunner::new().run(&manifest);
+        let failures: Vec<Option<&str>> = report.results.iter().map(|r| r.failure.as_deref()).collect();
+        assert_eq!(
+            failures,
+            [
+                None,
+                Some("output differs at line 1\nexpected: \"5\"\n  actual: \"4\""),
+                None,
+                Some("failed: Step limit exceeded: 1000"),
+            ]
+        );
+        assert_eq!((report.passed(), report.failed()), (2, 2));
+        let text = report.to_string();
+        assert!(text.starts_with("PASS sum.sui\nFAIL wrong sum\n     output differs at line 1\n"));
+        assert!(text.contains("4 programs: 2 passed, 2 failed"));
+
+        let mut runner = CorpusRunner::new();
+        runner.set_filter(Some("echo".to_string()));
+        assert!(runner.run(&manifest).success());
+    }
+}
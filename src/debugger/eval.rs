@@ -0,0 +1,131 @@
+//! Side-effect-free evaluation of expressions in the paused frame
+
+use std::collections::HashMap;
+
+use super::Debugger;
+use crate::interpreter::{Lexer, Value};
+use crate::transpiler::expr2sui;
+
+/// Instructions an expression may use, all of which only compute a value
+const PURE_OPS: [&str; 16] = ["=", "+", "-", "*", "/", "%", "<", ">", "<=", ">=", "~", "!", "&", "|", "]", "R"];
+
+impl Debugger {
+    /// Evaluate an expression in the current frame without changing any
+    /// variable
+    ///
+    /// Accepts a single operand (`v0`, `"s"`), an instruction without its
+    /// result operand (`+ v0 v1`, `] g0 v2`, `R "len" g0`), or an infix
+    /// formula as for [`expr2sui`] (`v0+v1*2`, `g0[v2] == 0`).
+    ///
+    /// ```rust
+    /// use sui_lang::debugger::Debugger;
+    /// use sui_lang::Value;
+    ///
+    /// let mut dbg = Debugger::new();
+    /// dbg.load("= v0 3\n[ g0 2\n{ g0 1 7").unwrap();
+    /// dbg.resume();
+    /// assert_eq!(dbg.evaluate("* v0 2"), Ok(Value::Integer(6)));
+    /// assert_eq!(dbg.evaluate("g0[1] + v0"), Ok(Value::Integer(10)));
+    /// ```
+    pub fn evaluate(&self, expr: &str) -> Result<Value, String> {
+        let tokens = Lexer::tokenize_line(expr.trim());
+        match tokens.as_slice() {
+            [] => Err("empty expression".to_string()),
+            [operand] => Ok(self.resolve(operand)),
+            [op, operands @ ..] if PURE_OPS.contains(&op.as_str()) => {
+                self.apply(op, operands, &HashMap::new())
+            }
+            _ => {
+                // Temporaries are numbered past every `v` in the formula, so
+                // they shadow no variable it reads
+                let lines = expr2sui(&format!("v0 = {}", expr)).map_err(|e| e.to_string())?;
+                let mut temps = HashMap::new();
+                for line in &lines {
+                    let tokens = Lexer::tokenize_line(line);
+                    let (op, result, operands) = match tokens.as_slice() {
+                        [op, result, operands @ ..] if PURE_OPS.contains(&op.as_str()) => (op, result, operands),
+                        _ => return Err(format!("cannot evaluate `{}`", line)),
+                    };
+                    let value = self.apply(op, operands, &temps)?;
+                    temps.insert(result.clone(), value);
+                }
+                Ok(temps.remove("v0").unwrap_or_default())
+            }
+        }
+    }
+
+    /// Value of an instruction applied to operands, reading `temps` before
+    /// the frame
+    fn apply(&self, op: &str, operands: &[String], temps: &HashMap<String, Value>) -> Result<Value, String> {
+        let values: Vec<Value> = operands
+            .iter()
+            .map(|operand| temps.get(operand).cloned().unwrap_or_else(|| self.resolve(operand)))
+            .collect();
+        let arg = |i: usize| values.get(i).cloned().ok_or_else(|| format!("`{}` needs more operands", op));
+        let truth = |b: bool| Value::Integer(b as i64);
+        Ok(match op {
+            "=" => arg(0)?,
+            "+" => arg(0)?.add(&arg(1)?),
+            "-" => arg(0)?.sub(&arg(1)?),
+            "*" => arg(0)?.mul(&arg(1)?),
+            "/" => arg(0)?.div(&arg(1)?),
+            "%" => arg(0)?.modulo(&arg(1)?),
+            "<" => arg(0)?.lt(&arg(1)?),
+            ">" => arg(0)?.gt(&arg(1)?),
+            "<=" => arg(0)?.le(&arg(1)?),
+            ">=" => arg(0)?.ge(&arg(1)?),
+            "~" => arg(0)?.eq_val(&arg(1)?),
+            "!" => truth(!arg(0)?.is_truthy()),
+            "&" => truth(arg(0)?.is_truthy() && arg(1)?.is_truthy()),
+            "|" => truth(arg(0)?.is_truthy() || arg(1)?.is_truthy()),
+            "]" => {
+                let path: Vec<i64> = values.iter().skip(1).map(Value::to_int).collect();
+                if path.is_empty() {
+                    return Err("`]` needs an index".to_string());
+                }
+                arg(0)?.element(&path)
+            }
+            // Builtins are pure
+            _ => self.call_builtin(&arg(0)?.to_string(), &values[1..]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::DebugEvent;
+
+    fn paused(code: &str) -> Debugger {
+        let mut dbg = Debugger::new();
+        dbg.load(code).unwrap();
+        dbg.resume();
+        dbg
+    }
+
+    #[test]
+    fn test_evaluate_forms() {
+        let dbg = paused("= v0 4\n= v1 2.5\n[ g0 3\n{ g0 2 9\n= v5 \"ab\"");
+        assert_eq!(dbg.evaluate("v0"), Ok(Value::Integer(4)));
+        assert_eq!(dbg.evaluate("+ v0 v1"), Ok(Value::Float(6.5)));
+        assert_eq!(dbg.evaluate("] g0 2"), Ok(Value::Integer(9)));
+        assert_eq!(dbg.evaluate("R \"len\" v5"), Ok(Value::Integer(2)));
+        assert_eq!(dbg.evaluate("(v0 + g0[2]) * 2 == 26"), Ok(Value::Integer(1)));
+        assert_eq!(dbg.evaluate("v0 % 3 != 1"), Ok(Value::Integer(0)));
+        assert!(dbg.evaluate("+ v0").is_err());
+        assert!(dbg.evaluate("v0 +").is_err());
+
+        // Temporaries do not leak into the frame
+        assert_eq!(dbg.locals().len(), 3);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut dbg = Debugger::new();
+        dbg.load("= v0 0\n: 0\n+ v0 v0 1\n< v1 v0 10\n? v1 0\n. v0").unwrap();
+        dbg.set_conditional_breakpoint(4, "v0 == 7");
+        assert!(matches!(dbg.resume(), DebugEvent::Breakpoint(4)));
+        assert_eq!(dbg.inspect("v0"), Some(Value::Integer(7)));
+        assert!(matches!(dbg.resume(), DebugEvent::Finished));
+    }
+}
@@ -0,0 +1,176 @@
+//! Narrating a recorded run
+//!
+//! [`narrate`] turns a [`Trace`] into an account of every instruction the
+//! run executed: the values it read, what it stored where, what it printed
+//! and where control went. `sui explain-run` records a run and prints its
+//! narrative, for teaching and for grounding a model's reasoning about a
+//! program in what the program actually does.
+
+use std::fmt::Write;
+
+use crate::interpreter::json::encode;
+use crate::interpreter::{Lexer, Trace, TraceEvent, Value};
+
+/// Account of the run in `trace`, a numbered entry per step
+///
+/// Calls are indented under the instruction that made them. The last line
+/// says how the run ended.
+pub fn narrate(trace: &Trace) -> String {
+    let source: Vec<&str> = trace.source.lines().collect();
+    let steps: Vec<usize> = (0..trace.events.len())
+        .filter(|&i| matches!(trace.events[i], TraceEvent::Step { .. }))
+        .collect();
+    let mut out = String::new();
+
+    let first = steps.first().copied().unwrap_or(trace.events.len());
+    let args: Vec<String> = trace.events[..first]
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::Write { var, value, .. } => Some(format!("{} = {}", var, encode(value))),
+            _ => None,
+        })
+        .collect();
+    if !args.is_empty() {
+        let _ = writeln!(out, "Arguments: {}", args.join(", "));
+    }
+
+    for (n, &i) in steps.iter().enumerate() {
+        let TraceEvent::Step { line, depth, operands, .. } = &trace.events[i] else {
+            continue;
+        };
+        let end = steps.get(n + 1).copied().unwrap_or(trace.events.len());
+        let next = steps.get(n + 1).and_then(|&j| match &trace.events[j] {
+            TraceEvent::Step { line, depth, .. } => Some((*line, *depth)),
+            _ => None,
+        });
+        let text = source.get(line.wrapping_sub(1)).map_or("", |text| text.trim());
+        let indent = "  ".repeat(*depth);
+        let _ = writeln!(out, "{}{}. line {}: {}", indent, n + 1, line, text);
+
+        let mut notes = Vec::new();
+        let reads: Vec<String> =
+            operands.iter().filter(|(token, _)| is_variable(token)).map(|(token, value)| show(token, value)).collect();
+        if !reads.is_empty() {
+            notes.push(format!("reads {}", reads.join(", ")));
+        }
+        let landing = match next {
+            Some((to, d)) if d == *depth => format!(" (line {})", to),
+            _ => String::new(),
+        };
+        if let Some(control) = control(&Lexer::tokenize_line(text), operands, &landing) {
+            notes.push(control);
+        }
+        for event in &trace.events[i + 1..end] {
+            match event {
+                TraceEvent::Write { depth: d, var, value } if d < depth && var.starts_with('v') => {
+                    notes.push(format!("stores {} in {} of the caller", encode(value), var))
+                }
+                TraceEvent::Write { var, value, .. } => notes.push(format!("stores {} in {}", encode(value), var)),
+                TraceEvent::Output(text) => notes.push(format!("prints {:?}", text)),
+                TraceEvent::Step { .. } => {}
+            }
+        }
+        for note in notes {
+            let _ = writeln!(out, "{}   {}", indent, note);
+        }
+    }
+
+    let count = format!("{} step{}", steps.len(), if steps.len() == 1 { "" } else { "s" });
+    match &trace.error {
+        Some(error) => {
+            let _ = write!(out, "Stopped after {}: {}", count, error);
+        }
+        None => {
+            let _ = write!(out, "Finished after {}", count);
+        }
+    }
+    out
+}
+
+/// Where the instruction `tokens` sends control, or what it hands over
+fn control(tokens: &[String], operands: &[(String, Value)], landing: &str) -> Option<String> {
+    let value = |k: usize| operands.get(k).map(|(token, value)| show(token, value));
+    match tokens.first()?.as_str() {
+        "?" => {
+            let (_, cond) = operands.first()?;
+            Some(if cond.is_truthy() {
+                format!("{} is true: jumps to label {}{}", value(0)?, tokens.get(2)?, landing)
+            } else {
+                format!("{} is false: continues", value(0)?)
+            })
+        }
+        "@" => Some(format!("jumps to label {}{}", tokens.get(1)?, landing)),
+        "J" => {
+            let labels = tokens.get(2..)?;
+            let (_, selector) = operands.first()?;
+            Some(match selector.table_index(labels.len()) {
+                Some(k) => format!("{} selects entry {}: jumps to label {}{}", value(0)?, k, labels[k], landing),
+                None => format!("{} selects no entry: continues", value(0)?),
+            })
+        }
+        "$" => {
+            let args: Vec<String> =
+                operands.iter().enumerate().map(|(k, (_, value))| format!("a{} = {}", k, encode(value))).collect();
+            Some(match args.is_empty() {
+                true => format!("calls function {}", tokens.get(2)?),
+                false => format!("calls function {} with {}", tokens.get(2)?, args.join(", ")),
+            })
+        }
+        "^" => Some(format!("returns {}", encode(&operands.first()?.1))),
+        "E" => Some(format!("raises {}", encode(&operands.first()?.1))),
+        _ => None,
+    }
+}
+
+/// An operand as read: `v0 = 3` for variables, the value for literals
+fn show(token: &str, value: &Value) -> String {
+    match is_variable(token) {
+        true => format!("{} = {}", token, encode(value)),
+        false => encode(value),
+    }
+}
+
+fn is_variable(token: &str) -> bool {
+    let mut chars = token.chars();
+    matches!(chars.next(), Some('v' | 'g' | 'a'))
+        && !chars.as_str().is_empty()
+        && chars.all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+
+    fn explain(code: &str, args: &[&str], max_steps: u64) -> String {
+        let mut interp = Interpreter::new();
+        interp.set_recording(true);
+        interp.set_max_steps(Some(max_steps));
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let result = interp.run(code, &args);
+        let mut trace = interp.take_trace().unwrap();
+        trace.error = result.err().map(|e| e.to_string());
+        narrate(&trace)
+    }
+
+    #[test]
+    fn test_narrate() {
+        let code = "# 0 1 {\n* v0 a0 2\n^ v0\n}\n$ v0 0 g100\n< v1 v0 5\n? v1 0\n. v0\n: 0";
+        assert_eq!(
+            explain(code, &["x"], 100),
+            "Arguments: g100 = 1, g101 = \"x\"\n\
+             1. line 5: $ v0 0 g100\n   reads g100 = 1\n   calls function 0 with a0 = 1\n\
+             \x20 2. line 2: * v0 a0 2\n     reads a0 = 1\n     stores 2 in v0\n\
+             \x20 3. line 3: ^ v0\n     reads v0 = 2\n     returns 2\n     stores 2 in v0 of the caller\n\
+             4. line 6: < v1 v0 5\n   reads v0 = 2\n   stores 1 in v1\n\
+             5. line 7: ? v1 0\n   reads v1 = 1\n   v1 = 1 is true: jumps to label 0 (line 9)\n\
+             6. line 9: : 0\n\
+             Finished after 6 steps"
+        );
+
+        let narrative = explain("= v0 0\n: 0\n. v0\n+ v0 v0 1\n@ 0", &[], 6);
+        assert!(narrative.contains("3. line 3: . v0\n   reads v0 = 0\n   prints \"0\"\n"));
+        assert!(narrative.contains("5. line 5: @ 0\n   jumps to label 0 (line 2)\n"));
+        assert!(narrative.ends_with("Stopped after 6 steps: Step limit exceeded: 6"));
+    }
+}
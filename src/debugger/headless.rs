@@ -0,0 +1,265 @@
+//! Driving the debugger from code instead of a terminal
+//!
+//! A [`DebugSession`] never touches stdin or stdout: `,` reads lines given
+//! to [`DebugSession::feed`], and `.` output is collected. Each step returns an
+//! [`Update`]: what stopped the program and the complete [`SessionState`]
+//! at that point. With the `serde` feature every type here serializes, so
+//! test harnesses and GUIs can exchange them as JSON.
+//!
+//! ```rust
+//! use sui_lang::debugger::headless::{DebugSession, SessionEvent};
+//! use sui_lang::Value;
+//!
+//! let mut session = DebugSession::new("= v0 2\n* v1 v0 21\n. v1").unwrap();
+//! session.set_breakpoint(3);
+//! let update = session.resume();
+//! assert_eq!(update.event, SessionEvent::Breakpoint { line: 3 });
+//! assert_eq!(update.state.locals["v1"], Value::Integer(42));
+//! assert_eq!(session.resume().state.output, ["42"]);
+//! ```
+
+use std::collections::BTreeMap;
+
+use super::{DebugEvent, DebugState, Debugger, Reloaded};
+use crate::interpreter::{ParseError, Value};
+
+/// Why the program stopped
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event", rename_all = "lowercase"))]
+pub enum SessionEvent {
+    /// One instruction ran
+    Step { line: usize },
+    /// Paused before a breakpoint's line
+    Breakpoint { line: usize },
+    /// Ran past the last instruction
+    Finished,
+    /// Stopped on an uncaught error
+    Error { message: String },
+    /// Paused before `.` at `line` prints `value`
+    Output { line: usize, value: Value },
+    /// Paused before `line` calls the builtin `name`
+    Ffi { line: usize, name: String, args: Vec<Value> },
+    /// Paused before `,` at `line` until input is fed
+    Input { line: usize },
+}
+
+/// A call in progress, outermost first
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrameState {
+    /// Function ID (-1 for main)
+    pub func_id: i64,
+    /// Line the frame is at
+    pub line: usize,
+}
+
+/// Everything visible in the debugger at one point
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SessionState {
+    /// Whether the program can still run
+    pub status: DebugState,
+    /// Line of the current instruction (0 before the first)
+    pub line: usize,
+    /// Function of the current frame (-1 for main)
+    pub func_id: i64,
+    /// Frames below the current one
+    pub call_stack: Vec<FrameState>,
+    /// Variables by name (`v0`, `g1`)
+    pub locals: BTreeMap<String, Value>,
+    pub globals: BTreeMap<String, Value>,
+    /// Arguments of the current frame, `a0` first
+    pub args: Vec<Value>,
+    /// Every line printed so far
+    pub output: Vec<String>,
+    /// Input lines fed but not read yet
+    pub input: Vec<String>,
+    /// Breakpoint lines, sorted
+    pub breakpoints: Vec<usize>,
+}
+
+impl SessionState {
+    /// JSON form of the state
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Result of running the program until it stops
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Update {
+    pub event: SessionEvent,
+    pub state: SessionState,
+}
+
+impl Update {
+    /// JSON form of the update
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Debugger without a terminal
+pub struct DebugSession {
+    debugger: Debugger,
+}
+
+impl DebugSession {
+    /// Load a program, paused before its first instruction
+    pub fn new(code: &str) -> Result<Self, ParseError> {
+        let mut debugger = Debugger::new();
+        debugger.set_input_queue(Vec::new());
+        debugger.load(code)?;
+        Ok(Self { debugger })
+    }
+
+    /// Run one instruction; calls run to completion
+    pub fn step(&mut self) -> Update {
+        let event = self.debugger.step();
+        self.update(event)
+    }
+
+    /// Run until a breakpoint, the end, or an error
+    pub fn resume(&mut self) -> Update {
+        let event = self.debugger.resume();
+        self.update(event)
+    }
+
+    /// Run until the program finishes or fails, ignoring breakpoints
+    pub fn run_to_end(&mut self) -> Update {
+        let dbg = &mut self.debugger;
+        let breakpoints = std::mem::take(&mut dbg.breakpoints);
+        let ffi_breaks = std::mem::take(&mut dbg.ffi_breaks);
+        let break_on_output = std::mem::replace(&mut dbg.break_on_output, false);
+        let event = dbg.resume();
+        dbg.breakpoints = breakpoints;
+        dbg.ffi_breaks = ffi_breaks;
+        dbg.break_on_output = break_on_output;
+        self.update(event)
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.debugger.set_breakpoint(line);
+    }
+
+    /// Break at `line` only when `condition` is true
+    pub fn set_conditional_breakpoint(&mut self, line: usize, condition: &str) {
+        self.debugger.set_conditional_breakpoint(line, condition);
+    }
+
+    pub fn remove_breakpoint(&mut self, line: usize) {
+        self.debugger.remove_breakpoint(line);
+    }
+
+    /// Pause before every `.`
+    pub fn set_output_break(&mut self, enabled: bool) {
+        self.debugger.set_output_break(enabled);
+    }
+
+    /// Pause before calls to the builtin `name`
+    pub fn set_ffi_break(&mut self, name: &str) {
+        self.debugger.set_ffi_break(name);
+    }
+
+    pub fn remove_ffi_break(&mut self, name: &str) {
+        self.debugger.remove_ffi_break(name);
+    }
+
+    /// Queue a line for `,` to read
+    pub fn feed(&mut self, line: &str) {
+        self.debugger.feed_input(line);
+    }
+
+    /// Value of an expression in the current frame, as for
+    /// [`Debugger::evaluate`]
+    pub fn evaluate(&self, expr: &str) -> Result<Value, String> {
+        self.debugger.evaluate(expr)
+    }
+
+    /// Assign to a variable or array element, as for [`Debugger::set_var`]
+    pub fn set_var(&mut self, name: &str, value: Value) -> Result<(), String> {
+        self.debugger.set_var(name, value)
+    }
+
+    /// Swap in edited source, as for [`Debugger::reload`]
+    pub fn reload(&mut self, code: &str) -> Result<Reloaded, ParseError> {
+        self.debugger.reload(code)
+    }
+
+    /// Current state, without running anything
+    pub fn state(&self) -> SessionState {
+        let dbg = &self.debugger;
+        let named = |prefix: char, vars: &std::collections::HashMap<i64, Value>| {
+            vars.iter().map(|(i, v)| (format!("{}{}", prefix, i), v.clone())).collect()
+        };
+        let mut breakpoints: Vec<usize> = dbg.breakpoints().iter().copied().collect();
+        breakpoints.sort_unstable();
+        SessionState {
+            status: dbg.state(),
+            line: dbg.current_line(),
+            func_id: dbg.current_frame.func_id,
+            call_stack: dbg.call_stack().iter().map(|f| FrameState { func_id: f.func_id, line: f.line }).collect(),
+            locals: named('v', dbg.locals()),
+            globals: named('g', dbg.globals()),
+            args: dbg.args().to_vec(),
+            output: dbg.output().to_vec(),
+            input: dbg.queued_input(),
+            breakpoints,
+        }
+    }
+
+    /// Underlying debugger
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    fn update(&self, event: DebugEvent) -> Update {
+        let event = match event {
+            DebugEvent::Step => SessionEvent::Step { line: self.debugger.current_line() },
+            DebugEvent::Breakpoint(line) => SessionEvent::Breakpoint { line },
+            DebugEvent::Finished => SessionEvent::Finished,
+            DebugEvent::Error(message) => SessionEvent::Error { message },
+            DebugEvent::Output(value) => SessionEvent::Output { line: self.debugger.current_line(), value },
+            DebugEvent::Ffi { name, args } => SessionEvent::Ffi { line: self.debugger.current_line(), name, args },
+            DebugEvent::InputNeeded => SessionEvent::Input { line: self.debugger.current_line() },
+        };
+        Update { event, state: self.state() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_events() {
+        let mut session = DebugSession::new("= v0 0\n: 0\n+ v0 v0 1\n< v1 v0 3\n? v1 0\n. v0\nE \"boom\"").unwrap();
+        assert_eq!(session.step().event, SessionEvent::Step { line: 1 });
+        session.set_conditional_breakpoint(4, "v0 == 2");
+        let update = session.resume();
+        assert_eq!(update.event, SessionEvent::Breakpoint { line: 4 });
+        assert_eq!(update.state.locals["v0"], Value::Integer(2));
+        assert_eq!(update.state.breakpoints, [4]);
+        assert_eq!(update.state.status, DebugState::Paused);
+
+        let update = session.run_to_end();
+        assert!(matches!(update.event, SessionEvent::Error { .. }));
+        assert_eq!(update.state.output, ["3"]);
+        assert_eq!(update.state.breakpoints, [4]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_session_json() {
+        let mut session = DebugSession::new("[ g0 2\n{ g0 1 \"s\"\n. 1").unwrap();
+        session.set_breakpoint(3);
+        let json: serde_json::Value = serde_json::from_str(&session.resume().to_json()).unwrap();
+        assert_eq!(json["event"], serde_json::json!({"event": "breakpoint", "line": 3}));
+        assert_eq!(json["state"]["globals"]["g0"], serde_json::json!([0, "s"]));
+        assert_eq!(json["state"]["status"], "paused");
+    }
+}
@@ -4,15 +4,42 @@
 //! - Breakpoints (by line number)
 //! - Step/Next/Continue
 //! - Variable inspection
-//! - Call stack viewing
+//! - Call stack viewing and per-function call counts
+//! - Exporting the current frame to other processes (see [`session`])
+//! - Navigating recorded runs forward and backward (see [`replay`])
+//! - Narrating recorded runs step by step (see [`explain`])
+//! - Reloading edited source while paused
+//! - Scripted sessions without a terminal (see [`headless`])
+//! - Remote control over TCP or WebSocket (see `remote`, with the `remote`
+//!   feature)
 
-use std::collections::{HashMap, HashSet};
+#[cfg(feature = "remote")]
+pub mod remote;
+mod eval;
+pub mod explain;
+pub mod headless;
+mod profile;
+mod reload;
+pub mod replay;
+pub mod session;
+
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{self, BufRead, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+
+pub use profile::{CallProfile, CallStats};
+pub use reload::Reloaded;
+use profile::Profiler;
+use session::{Snapshot, SnapshotServer, SnapshotValue};
 
-use crate::interpreter::{Function, Instruction, Lexer, Parser, ParseError, ParsedValue, Value};
+use crate::interpreter::{
+    Function, Instruction, Lexer, OutputMode, Parser, ParseError, ParsedValue, Value, LANGUAGE_VERSION,
+    MAX_ARRAY_SIZE,
+};
 
 /// Debugger state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
 pub enum DebugState {
     /// Running normally
     Running,
@@ -35,6 +62,12 @@ pub enum DebugEvent {
     Finished,
     /// Error occurred
     Error(String),
+    /// Paused before `.` prints this value
+    Output(Value),
+    /// Paused before a builtin is called with these arguments
+    Ffi { name: String, args: Vec<Value> },
+    /// Paused before `,` because no input is queued
+    InputNeeded,
 }
 
 /// Stack frame for debugging
@@ -48,11 +81,19 @@ pub struct StackFrame {
     pub locals: HashMap<i64, Value>,
     /// Function arguments
     pub args: Vec<Value>,
+    /// Try handlers `(err, label)`, innermost last
+    pub handlers: Vec<(String, i64)>,
 }
 
 /// Sui debugger
 pub struct Debugger {
     breakpoints: HashSet<usize>,
+    /// Expressions that must be true for a breakpoint to stop, by line
+    conditions: HashMap<usize, String>,
+    /// Whether to pause before every `.`
+    break_on_output: bool,
+    /// Builtins to pause before calling
+    ffi_breaks: HashSet<String>,
     state: DebugState,
     current_line: usize,
     instructions: Vec<(usize, Instruction)>,
@@ -61,15 +102,26 @@ pub struct Debugger {
     call_stack: Vec<StackFrame>,
     current_frame: StackFrame,
     output: Vec<String>,
+    output_mode: OutputMode,
+    /// Lines for `,` to read instead of stdin, once set
+    input: Option<VecDeque<String>>,
     labels: HashMap<i64, usize>,
     ip: usize,
     source_lines: Vec<String>,
+    source_path: Option<String>,
+    session: Option<SnapshotServer>,
+    /// Value of the `E` being unwound, if any
+    raised: Option<Value>,
+    profiler: Profiler,
 }
 
 impl Debugger {
     pub fn new() -> Self {
         Self {
             breakpoints: HashSet::new(),
+            conditions: HashMap::new(),
+            break_on_output: false,
+            ffi_breaks: HashSet::new(),
             state: DebugState::Paused,
             current_line: 0,
             instructions: Vec::new(),
@@ -81,22 +133,25 @@ impl Debugger {
                 line: 0,
                 locals: HashMap::new(),
                 args: Vec::new(),
+                handlers: Vec::new(),
             },
             output: Vec::new(),
+            output_mode: OutputMode::default(),
+            input: None,
             labels: HashMap::new(),
             ip: 0,
             source_lines: Vec::new(),
+            source_path: None,
+            session: None,
+            raised: None,
+            profiler: Profiler::default(),
         }
     }
 
     pub fn load(&mut self, code: &str) -> Result<(), ParseError> {
         self.source_lines = code.lines().map(|s| s.to_string()).collect();
-        let (instructions, functions) = Parser::parse(code)?;
-
-        self.instructions.clear();
-        for (i, instr) in instructions.iter().enumerate() {
-            self.instructions.push((i + 1, instr.clone()));
-        }
+        let (instructions, functions) = Parser::parse_numbered(code, LANGUAGE_VERSION)?;
+        self.instructions = instructions;
 
         self.labels.clear();
         for (i, (_, instr)) in self.instructions.iter().enumerate() {
@@ -115,16 +170,37 @@ impl Debugger {
         self.global_vars.clear();
         self.call_stack.clear();
         self.current_frame = StackFrame {
-            func_id: -1, line: 0, locals: HashMap::new(), args: Vec::new(),
+            func_id: -1, line: 0, locals: HashMap::new(), args: Vec::new(), handlers: Vec::new(),
         };
         self.output.clear();
+        self.raised = None;
+        self.profiler = Profiler::default();
         Ok(())
     }
 
-    pub fn set_breakpoint(&mut self, line: usize) { self.breakpoints.insert(line); }
-    pub fn remove_breakpoint(&mut self, line: usize) { self.breakpoints.remove(&line); }
-    pub fn clear_breakpoints(&mut self) { self.breakpoints.clear(); }
+    pub fn set_breakpoint(&mut self, line: usize) { self.breakpoints.insert(line); self.conditions.remove(&line); }
+    /// Break at `line` only when `condition`, as for [`evaluate`](Self::evaluate),
+    /// is true; a condition that fails to evaluate breaks too
+    pub fn set_conditional_breakpoint(&mut self, line: usize, condition: &str) {
+        self.breakpoints.insert(line);
+        self.conditions.insert(line, condition.to_string());
+    }
+    pub fn remove_breakpoint(&mut self, line: usize) { self.breakpoints.remove(&line); self.conditions.remove(&line); }
+    pub fn clear_breakpoints(&mut self) { self.breakpoints.clear(); self.conditions.clear(); }
     pub fn breakpoints(&self) -> &HashSet<usize> { &self.breakpoints }
+    /// Pause before every `.`, reporting the value about to print
+    pub fn set_output_break(&mut self, enabled: bool) { self.break_on_output = enabled; }
+    /// Pause before calls to the builtin `name` (`sqrt` or `math.sqrt`),
+    /// reporting the arguments
+    pub fn set_ffi_break(&mut self, name: &str) { self.ffi_breaks.insert(name.to_string()); }
+    pub fn remove_ffi_break(&mut self, name: &str) { self.ffi_breaks.remove(name); }
+    /// Read `,` input from these lines instead of stdin; when they run
+    /// out, the program pauses with [`DebugEvent::InputNeeded`]
+    pub fn set_input_queue(&mut self, lines: Vec<String>) { self.input = Some(lines.into()); }
+    /// Queue one more line of input
+    pub fn feed_input(&mut self, line: &str) { self.input.get_or_insert_with(VecDeque::new).push_back(line.to_string()); }
+    /// Input lines not read yet
+    pub fn queued_input(&self) -> Vec<String> { self.input.iter().flatten().cloned().collect() }
     pub fn state(&self) -> DebugState { self.state }
     pub fn current_line(&self) -> usize { self.current_line }
     pub fn source_at(&self, line: usize) -> Option<&str> {
@@ -134,8 +210,7 @@ impl Debugger {
     fn resolve(&self, val: &str) -> Value {
         match Lexer::parse_value(val) {
             ParsedValue::Variable(var) => {
-                let prefix = var.chars().next().unwrap();
-                let idx: i64 = var[1..].parse().unwrap_or(0);
+                let (prefix, idx) = Lexer::split_variable(&var);
                 match prefix {
                     'v' => self.current_frame.locals.get(&idx).cloned().unwrap_or_default(),
                     'g' => self.global_vars.get(&idx).cloned().unwrap_or_default(),
@@ -145,13 +220,12 @@ impl Debugger {
             }
             ParsedValue::Integer(n) => Value::Integer(n),
             ParsedValue::Float(f) => Value::Float(f),
-            ParsedValue::String(s) => Value::String(s),
+            ParsedValue::String(s) => Value::from(s),
         }
     }
 
     fn assign(&mut self, var: &str, value: Value) {
-        let prefix = var.chars().next().unwrap_or('v');
-        let idx: i64 = var[1..].parse().unwrap_or(0);
+        let (prefix, idx) = Lexer::split_variable(var);
         match prefix {
             'v' => { self.current_frame.locals.insert(idx, value); }
             'g' => { self.global_vars.insert(idx, value); }
@@ -160,10 +234,15 @@ impl Debugger {
     }
 
     fn run_instruction(&mut self, instr: &Instruction) -> Result<Option<i64>, String> {
+        self.profiler.instruction(self.current_frame.func_id);
         match instr {
-            Instruction::Empty | Instruction::Comment | Instruction::FuncDef { .. } | Instruction::FuncEnd | Instruction::Import { .. } => {
+            Instruction::Empty | Instruction::Comment | Instruction::Const { .. } | Instruction::FuncDef { .. }
+            | Instruction::FuncEnd | Instruction::Import { .. } => {
                 // Import is handled during loading, no-op during execution
             }
+            Instruction::Invalid { error, .. } => {
+                return Err(error.to_string());
+            }
             Instruction::Assign { target, value } => {
                 let val = self.resolve(value);
                 self.assign(target, val);
@@ -196,6 +275,14 @@ impl Debugger {
                 let val = self.resolve(a).gt(&self.resolve(b));
                 self.assign(result, val);
             }
+            Instruction::Le { result, a, b } => {
+                let val = self.resolve(a).le(&self.resolve(b));
+                self.assign(result, val);
+            }
+            Instruction::Ge { result, a, b } => {
+                let val = self.resolve(a).ge(&self.resolve(b));
+                self.assign(result, val);
+            }
             Instruction::Eq { result, a, b } => {
                 let val = self.resolve(a).eq_val(&self.resolve(b));
                 self.assign(result, val);
@@ -216,16 +303,20 @@ impl Debugger {
                 if self.resolve(cond).is_truthy() { return Ok(Some(*label)); }
             }
             Instruction::Jump { label } => { return Ok(Some(*label)); }
+            Instruction::Switch { value, labels } => {
+                if let Some(index) = self.resolve(value).table_index(labels.len()) { return Ok(Some(labels[index])); }
+            }
             Instruction::Label { .. } => {}
             Instruction::Call { result, func_id, args } => {
                 let resolved_args: Vec<Value> = args.iter().map(|a| self.resolve(a)).collect();
                 let old_frame = std::mem::replace(&mut self.current_frame, StackFrame {
                     func_id: *func_id, line: 0, locals: HashMap::new(),
-                    args: resolved_args,
+                    args: resolved_args, handlers: Vec::new(),
                 });
                 self.call_stack.push(old_frame);
                 let func = self.functions.get(func_id).cloned()
                     .ok_or_else(|| format!("Undefined function: {}", func_id))?;
+                self.profiler.enter(*func_id, self.call_stack.len());
                 let mut func_labels: HashMap<i64, usize> = HashMap::new();
                 for (i, instr) in func.body.iter().enumerate() {
                     if let Instruction::Label { id } = instr { func_labels.insert(*id, i); }
@@ -233,7 +324,18 @@ impl Debugger {
                 let mut fi = 0;
                 let mut return_val = Value::Integer(0);
                 while fi < func.body.len() {
-                    let jump = self.run_instruction(&func.body[fi])?;
+                    let jump = match self.run_instruction(&func.body[fi]) {
+                        Ok(jump) => jump,
+                        Err(error) => match self.catch(error) {
+                            Ok(label) => Some(label),
+                            Err(error) => {
+                                // Unwind into the caller
+                                self.profiler.exit(*func_id);
+                                self.current_frame = self.call_stack.pop().unwrap();
+                                return Err(error);
+                            }
+                        },
+                    };
                     if let Instruction::Return { value } = &func.body[fi] {
                         return_val = self.resolve(value);
                         break;
@@ -242,51 +344,80 @@ impl Debugger {
                         if let Some(&pos) = func_labels.get(&label) { fi = pos; } else { fi += 1; }
                     } else { fi += 1; }
                 }
+                self.profiler.exit(*func_id);
                 self.current_frame = self.call_stack.pop().unwrap();
                 self.assign(result, return_val);
             }
             Instruction::Return { .. } => {}
+            Instruction::Try { err, label } => {
+                self.current_frame.handlers.push((err.clone(), *label));
+            }
+            Instruction::EndTry => { self.current_frame.handlers.pop(); }
+            Instruction::Raise { value } => {
+                let value = self.resolve(value);
+                self.raised = Some(value.clone());
+                return Err(format!("Uncaught error: {}", value));
+            }
             Instruction::ArrayCreate { var, size } => {
-                let size = self.resolve(size).to_int() as usize;
-                self.assign(var, Value::Array(vec![Value::Integer(0); size]));
-            }
-            Instruction::ArrayRead { result, arr, idx } => {
-                let array = self.resolve(arr);
-                let index = self.resolve(idx).to_int();
-                let val = match array {
-                    Value::Array(ref a) if index >= 0 && (index as usize) < a.len() => a[index as usize].clone(),
-                    _ => Value::Integer(0),
-                };
+                let size = self.resolve(size).to_int();
+                if size > MAX_ARRAY_SIZE as i64 {
+                    return Err(format!("Array size too large: {}", size));
+                }
+                self.assign(var, Value::from(vec![Value::Integer(0); size.max(0) as usize]));
+            }
+            Instruction::ArrayRead { result, arr, idx, indices } => {
+                let path = self.index_path(idx, indices);
+                let val = self.resolve(arr).element(&path);
                 self.assign(result, val);
             }
-            Instruction::ArrayWrite { arr, idx, value } => {
-                let index = self.resolve(idx).to_int();
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
+                let path = self.index_path(idx, indices);
                 let val = self.resolve(value);
-                let prefix = arr.chars().next().unwrap_or('v');
-                let var_idx: i64 = arr[1..].parse().unwrap_or(0);
+                let (prefix, var_idx) = Lexer::split_variable(arr);
                 let array = match prefix {
                     'v' => self.current_frame.locals.get_mut(&var_idx),
                     'g' => self.global_vars.get_mut(&var_idx),
                     _ => None,
                 };
-                if let Some(Value::Array(ref mut a)) = array {
-                    if index >= 0 && (index as usize) < a.len() { a[index as usize] = val; }
+                if let Some(array) = array { array.set_element(&path, val); }
+            }
+            Instruction::Iter { iter, source } => {
+                let val = self.resolve(source).iterator();
+                self.assign(iter, val);
+            }
+            Instruction::Next { done, var, iter } => {
+                let mut state = self.resolve(iter);
+                match state.advance() {
+                    Some(item) => {
+                        self.assign(iter, state);
+                        self.assign(var, item);
+                        self.assign(done, Value::Integer(0));
+                    }
+                    None => self.assign(done, Value::Integer(1)),
                 }
             }
             Instruction::Output { value } => {
                 let val = self.resolve(value);
                 let output = val.to_string();
-                self.output.push(output.clone());
-                println!("{}", output);
+                if self.output_mode.prints() { println!("{}", output); }
+                if self.output_mode.captures() { self.output.push(output); }
             }
             Instruction::Input { var } => {
-                print!("> ");
-                io::stdout().flush().ok();
-                let stdin = io::stdin();
-                let line = stdin.lock().lines().next().unwrap_or(Ok(String::new())).unwrap_or_default();
+                let line = match &mut self.input {
+                    // Inside a call, which cannot pause
+                    Some(queue) => queue.pop_front().ok_or("no input queued")?,
+                    None => {
+                        if self.output_mode.prints() {
+                            print!("> ");
+                            io::stdout().flush().ok();
+                        }
+                        let stdin = io::stdin();
+                        stdin.lock().lines().next().unwrap_or(Ok(String::new())).unwrap_or_default()
+                    }
+                };
                 let val = if let Ok(n) = line.trim().parse::<i64>() { Value::Integer(n) }
                 else if let Ok(f) = line.trim().parse::<f64>() { Value::Float(f) }
-                else { Value::String(line.trim().to_string()) };
+                else { Value::from(line.trim()) };
                 self.assign(var, val);
             }
             Instruction::RustFFI { result, func, args } => {
@@ -299,6 +430,10 @@ impl Debugger {
         Ok(None)
     }
 
+    fn index_path(&self, idx: &str, indices: &[String]) -> Vec<i64> {
+        std::iter::once(idx).chain(indices.iter().map(String::as_str)).map(|i| self.resolve(i).to_int()).collect()
+    }
+
     fn call_builtin(&self, func: &str, args: &[Value]) -> Value {
         let func_name = func.rsplit('.').next().unwrap_or(func);
         match func_name {
@@ -307,6 +442,11 @@ impl Debugger {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
                 if x.fract() == 0.0 { Value::Integer(x.abs() as i64) } else { Value::Float(x.abs()) }
             }
+            "array2d" => {
+                let rows = args.first().map(|v| v.to_int()).unwrap_or(0);
+                let cols = args.get(1).map(|v| v.to_int()).unwrap_or(0);
+                Value::array2d(rows, cols).unwrap_or(Value::Integer(0))
+            }
             "len" => match args.first() {
                 Some(Value::String(s)) => Value::Integer(s.len() as i64),
                 Some(Value::Array(a)) => Value::Integer(a.len() as i64),
@@ -316,6 +456,31 @@ impl Debugger {
         }
     }
 
+    /// Pass an error to the innermost try handler of the current frame
+    ///
+    /// Returns the handler label, or the error if there is no handler.
+    fn catch(&mut self, error: String) -> Result<i64, String> {
+        let Some((err, label)) = self.current_frame.handlers.pop() else {
+            return Err(error);
+        };
+        let value = self.raised.take().unwrap_or_else(|| Value::from(error));
+        self.assign(&err, value);
+        Ok(label)
+    }
+
+    /// Move past an executed instruction, unwinding to a handler on error
+    fn advance(&mut self, outcome: Result<Option<i64>, String>) -> Result<(), String> {
+        let jump = match outcome {
+            Ok(jump) => jump,
+            Err(error) => Some(self.catch(error)?),
+        };
+        match jump.and_then(|label| self.labels.get(&label)) {
+            Some(&pos) => self.ip = pos,
+            None => self.ip += 1,
+        }
+        Ok(())
+    }
+
     pub fn step(&mut self) -> DebugEvent {
         if self.ip >= self.instructions.len() {
             self.state = DebugState::Finished;
@@ -324,11 +489,13 @@ impl Debugger {
         let (line, instr) = self.instructions[self.ip].clone();
         self.current_line = line;
         self.current_frame.line = line;
-        match self.run_instruction(&instr) {
-            Ok(jump) => {
-                if let Some(label) = jump {
-                    if let Some(&pos) = self.labels.get(&label) { self.ip = pos; } else { self.ip += 1; }
-                } else { self.ip += 1; }
+        if self.starved(&instr) {
+            self.state = DebugState::Paused;
+            return DebugEvent::InputNeeded;
+        }
+        let outcome = self.run_instruction(&instr);
+        match self.advance(outcome) {
+            Ok(()) => {
                 if self.ip >= self.instructions.len() {
                     self.state = DebugState::Finished;
                     DebugEvent::Finished
@@ -341,8 +508,43 @@ impl Debugger {
         }
     }
 
+    /// Whether a breakpoint stops before `line` runs
+    fn breaks_at(&self, line: usize) -> bool {
+        self.breakpoints.contains(&line)
+            && self.conditions.get(&line).map_or(true, |cond| self.evaluate(cond).map_or(true, |v| v.is_truthy()))
+    }
+
+    /// Whether `instr` is a `,` with nothing queued to read
+    fn starved(&self, instr: &Instruction) -> bool {
+        matches!(instr, Instruction::Input { .. }) && self.input.as_ref().is_some_and(VecDeque::is_empty)
+    }
+
+    /// Why to pause before instruction `ip` runs, if at all
+    fn stop_before(&self, ip: usize) -> Option<DebugEvent> {
+        let (line, instr) = &self.instructions[ip];
+        if self.breaks_at(*line) {
+            return Some(DebugEvent::Breakpoint(*line));
+        }
+        match instr {
+            Instruction::Output { value } if self.break_on_output => Some(DebugEvent::Output(self.resolve(value))),
+            Instruction::RustFFI { func, args, .. } if !self.ffi_breaks.is_empty() => {
+                let name = self.resolve(func).to_string();
+                let short = name.rsplit('.').next().unwrap_or(&name);
+                if !self.ffi_breaks.contains(&name) && !self.ffi_breaks.contains(short) {
+                    return None;
+                }
+                let args = args.iter().map(|a| self.resolve(a)).collect();
+                Some(DebugEvent::Ffi { name, args })
+            }
+            _ => None,
+        }
+    }
+
     pub fn resume(&mut self) -> DebugEvent {
         self.state = DebugState::Running;
+        // Only a fresh run stops at its first line; otherwise it is the
+        // line already stopped at
+        let mut at_start = self.current_line == 0;
         loop {
             if self.ip >= self.instructions.len() {
                 self.state = DebugState::Finished;
@@ -351,24 +553,26 @@ impl Debugger {
             let (line, instr) = self.instructions[self.ip].clone();
             self.current_line = line;
             self.current_frame.line = line;
-            if self.breakpoints.contains(&line) && self.state == DebugState::Running {
+            if std::mem::take(&mut at_start) {
+                if let Some(event) = self.stop_before(self.ip) {
+                    self.state = DebugState::Paused;
+                    return event;
+                }
+            }
+            if self.starved(&instr) {
                 self.state = DebugState::Paused;
-                return DebugEvent::Breakpoint(line);
+                return DebugEvent::InputNeeded;
             }
-            match self.run_instruction(&instr) {
-                Ok(jump) => {
-                    if let Some(label) = jump {
-                        if let Some(&pos) = self.labels.get(&label) { self.ip = pos; } else { self.ip += 1; }
-                    } else { self.ip += 1; }
-                }
+            let outcome = self.run_instruction(&instr);
+            match self.advance(outcome) {
+                Ok(()) => {}
                 Err(e) => { self.state = DebugState::Finished; return DebugEvent::Error(e); }
             }
             if self.ip < self.instructions.len() {
-                let next_line = self.instructions[self.ip].0;
-                if self.breakpoints.contains(&next_line) {
-                    self.current_line = next_line;
+                if let Some(event) = self.stop_before(self.ip) {
+                    self.current_line = self.instructions[self.ip].0;
                     self.state = DebugState::Paused;
-                    return DebugEvent::Breakpoint(next_line);
+                    return event;
                 }
             }
         }
@@ -379,31 +583,120 @@ impl Debugger {
     pub fn args(&self) -> &[Value] { &self.current_frame.args }
     pub fn call_stack(&self) -> &[StackFrame] { &self.call_stack }
     pub fn output(&self) -> &[String] { &self.output }
-    pub fn inspect(&self, expr: &str) -> Option<Value> { Some(self.resolve(expr)) }
+    pub fn inspect(&self, expr: &str) -> Option<Value> { self.evaluate(expr).ok() }
+
+    /// Change a variable of the current frame, or an element of an array
+    /// variable such as `g0[2]` or `v1[0][v2]`
+    ///
+    /// Indices are expressions as for [`evaluate`](Self::evaluate). Fails if
+    /// the name is not a variable, an argument is beyond the frame's
+    /// arguments, or an index is out of bounds.
+    pub fn set_var(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let (var, indices) = match name.split_once('[') {
+            Some((var, rest)) => {
+                let indices = rest.strip_suffix(']').ok_or_else(|| format!("expected `]` in {}", name))?;
+                let path = indices
+                    .split("][")
+                    .map(|index| self.evaluate(index).map(|v| v.to_int()))
+                    .collect::<Result<Vec<i64>, String>>()?;
+                (var.trim(), path)
+            }
+            None => (name.trim(), Vec::new()),
+        };
+        if !matches!(Lexer::parse_value(var), ParsedValue::Variable(_)) {
+            return Err(format!("{} is not a variable", var));
+        }
+        let (prefix, idx) = Lexer::split_variable(var);
+        let slot = match prefix {
+            'v' => self.current_frame.locals.entry(idx).or_default(),
+            'g' => self.global_vars.entry(idx).or_default(),
+            _ => usize::try_from(idx)
+                .ok()
+                .and_then(|i| self.current_frame.args.get_mut(i))
+                .ok_or_else(|| format!("{} is not an argument of this frame", var))?,
+        };
+        if indices.is_empty() {
+            *slot = value;
+        } else if !slot.set_element(&indices, value) {
+            return Err(format!("{} is out of bounds", name));
+        }
+        Ok(())
+    }
+
+    /// Record the file being debugged, reported in snapshots
+    pub fn set_source_path(&mut self, path: &str) { self.source_path = Some(path.to_string()); }
+
+    /// Choose where `.` output goes; captured output is kept in
+    /// [`output`](Self::output). Defaults to [`OutputMode::Captured`].
+    pub fn set_output_mode(&mut self, mode: OutputMode) { self.output_mode = mode; }
+
+    /// Variables of the current frame
+    pub fn snapshot(&self) -> Snapshot {
+        let render = |value: &Value| SnapshotValue {
+            type_name: value.type_name().to_string(),
+            value: value.to_string(),
+        };
+        let locals = self.current_frame.locals.iter().map(|(i, v)| (format!("v{}", i), render(v)));
+        let globals = self.global_vars.iter().map(|(i, v)| (format!("g{}", i), render(v)));
+        let args = self.current_frame.args.iter().enumerate().map(|(i, v)| (format!("a{}", i), render(v)));
+        Snapshot {
+            file: self.source_path.clone(),
+            func_id: self.current_frame.func_id,
+            variables: locals.chain(globals).chain(args).collect(),
+        }
+    }
+
+    /// Serve snapshots on `addr` for `sui-lsp --attach`
+    ///
+    /// The interactive debugger publishes a new snapshot before each prompt.
+    pub fn export(&mut self, addr: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let server = SnapshotServer::bind(addr)?;
+        server.publish(&self.snapshot());
+        let local = server.local_addr();
+        self.session = Some(server);
+        Ok(local)
+    }
+
+    /// Update the exported snapshot, if exporting
+    pub fn publish_snapshot(&self) {
+        if let Some(server) = &self.session { server.publish(&self.snapshot()); }
+    }
 
     pub fn run_interactive(&mut self) {
+        // stdin carries commands, so `,` reads what `feed` queues
+        self.input.get_or_insert_with(VecDeque::new);
         println!("Sui Debugger - Type 'help' for commands\n");
         if let Some(src) = self.source_at(1) { println!("=> 1: {}", src); }
         let stdin = io::stdin();
         loop {
+            self.publish_snapshot();
             print!("(sui-dbg) ");
             io::stdout().flush().ok();
             let mut input = String::new();
             if stdin.lock().read_line(&mut input).is_err() { break; }
-            let cmd: Vec<&str> = input.trim().split_whitespace().collect();
+            let cmd: Vec<&str> = input.split_whitespace().collect();
             if cmd.is_empty() { continue; }
+            // Everything after the command word, for expressions
+            let rest = input.trim().split_once(char::is_whitespace).map_or("", |(_, r)| r.trim());
             match cmd[0] {
                 "help" | "h" => {
                     println!("Commands:");
                     println!("  step, s        - Run one instruction");
                     println!("  continue, c    - Continue until breakpoint");
                     println!("  break N, b N   - Set breakpoint at line N");
+                    println!("  break N if E   - Break at line N when expression E is true");
                     println!("  delete N, d N  - Remove breakpoint at line N");
+                    println!("  break-output   - Pause before every output (delete-output to stop)");
+                    println!("  break-ffi NAME - Pause before calls to builtin NAME (delete-ffi NAME)");
                     println!("  list, l        - Show source around current line");
                     println!("  locals         - Show local variables");
                     println!("  globals        - Show global variables");
-                    println!("  print E, p E   - Inspect expression E");
+                    println!("  print E, p E   - Evaluate E: v0, + v0 v1, ] g0 v2 or v0+v1*2");
+                    println!("  set X E        - Assign E to variable or element X (v0, g0[2])");
+                    println!("  feed VALUE     - Queue a line of program input");
                     println!("  backtrace, bt  - Show call stack");
+                    println!("  calls          - Show call counts and instructions per function");
+                    println!("  reload [FILE]  - Load edited source, keeping variables and breakpoints");
                     println!("  quit, q        - Exit debugger");
                 }
                 "step" | "s" => {
@@ -419,8 +712,16 @@ impl Debugger {
                 "break" | "b" => {
                     if let Some(line_str) = cmd.get(1) {
                         if let Ok(line) = line_str.parse::<usize>() {
-                            self.set_breakpoint(line);
-                            println!("Breakpoint set at line {}", line);
+                            match rest.split_once(" if ") {
+                                Some((_, cond)) => {
+                                    self.set_conditional_breakpoint(line, cond.trim());
+                                    println!("Breakpoint set at line {} if {}", line, cond.trim());
+                                }
+                                None => {
+                                    self.set_breakpoint(line);
+                                    println!("Breakpoint set at line {}", line);
+                                }
+                            }
                         }
                     } else { println!("Breakpoints: {:?}", self.breakpoints); }
                 }
@@ -432,6 +733,33 @@ impl Debugger {
                         }
                     }
                 }
+                "break-output" => {
+                    self.set_output_break(true);
+                    println!("Pausing before output");
+                }
+                "delete-output" => {
+                    self.set_output_break(false);
+                    println!("No longer pausing before output");
+                }
+                "break-ffi" | "delete-ffi" => {
+                    let Some(name) = cmd.get(1) else {
+                        let mut names: Vec<_> = self.ffi_breaks.iter().collect();
+                        names.sort();
+                        println!("FFI breakpoints: {:?}", names);
+                        continue;
+                    };
+                    if cmd[0] == "break-ffi" {
+                        self.set_ffi_break(name);
+                        println!("Pausing before calls to {}", name);
+                    } else {
+                        self.remove_ffi_break(name);
+                        println!("No longer pausing before calls to {}", name);
+                    }
+                }
+                "feed" => {
+                    self.feed_input(rest);
+                    println!("Queued input: {}", rest);
+                }
                 "list" | "l" => {
                     let start = self.current_line.saturating_sub(3);
                     let end = (self.current_line + 4).min(self.source_lines.len());
@@ -454,8 +782,19 @@ impl Debugger {
                     for (idx, val) in vars { println!("  g{} = {}", idx, val); }
                 }
                 "print" | "p" => {
-                    if let Some(expr) = cmd.get(1) {
-                        if let Some(val) = self.inspect(expr) { println!("{} = {}", expr, val); }
+                    match self.evaluate(rest) {
+                        Ok(val) => println!("{} = {}", rest, val),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+                "set" => {
+                    let Some((name, expr)) = rest.split_once(char::is_whitespace) else {
+                        println!("Usage: set VAR EXPR");
+                        continue;
+                    };
+                    match self.evaluate(expr).and_then(|val| self.set_var(name, val)) {
+                        Ok(()) => println!("{} = {}", name, self.evaluate(name).unwrap_or_default()),
+                        Err(e) => println!("Error: {}", e),
                     }
                 }
                 "backtrace" | "bt" => {
@@ -467,6 +806,25 @@ impl Debugger {
                     let name = if self.current_frame.func_id < 0 { "main".to_string() } else { format!("func_{}", self.current_frame.func_id) };
                     println!("  #0 {} at line {} (current)", name, self.current_line);
                 }
+                "reload" => {
+                    let Some(path) = cmd.get(1).map(|p| p.to_string()).or_else(|| self.source_path.clone()) else {
+                        println!("Usage: reload FILE");
+                        continue;
+                    };
+                    let code = match std::fs::read_to_string(&path) {
+                        Ok(code) => code,
+                        Err(e) => { println!("Error reading '{}': {}", path, e); continue; }
+                    };
+                    match self.reload(&code) {
+                        Ok(Reloaded::Continued(line)) => {
+                            println!("Reloaded {}", path);
+                            if let Some(src) = self.source_at(line) { println!("=> {}: {}", line, src); }
+                        }
+                        Ok(Reloaded::Restarted) => println!("Reloaded {}; restarting from the beginning", path),
+                        Err(e) => println!("Parse error: {}", e),
+                    }
+                }
+                "calls" => self.print_call_profile(),
                 "quit" | "q" => { println!("Exiting debugger."); break; }
                 _ => { println!("Unknown command: {}. Type 'help' for commands.", cmd[0]); }
             }
@@ -484,6 +842,18 @@ impl Debugger {
             }
             DebugEvent::Finished => { println!("Done."); }
             DebugEvent::Error(e) => { println!("Error: {}", e); }
+            DebugEvent::Output(value) => {
+                println!("Output at line {}: {}", self.current_line, value);
+                if let Some(src) = self.source_at(self.current_line) { println!("=> {}: {}", self.current_line, src); }
+            }
+            DebugEvent::InputNeeded => {
+                println!("Waiting for input at line {}; queue it with feed VALUE", self.current_line);
+            }
+            DebugEvent::Ffi { name, args } => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                println!("Call to {}({}) at line {}", name, args.join(", "), self.current_line);
+                if let Some(src) = self.source_at(self.current_line) { println!("=> {}: {}", self.current_line, src); }
+            }
         }
     }
 }
@@ -525,4 +895,84 @@ mod tests {
         dbg.step();
         assert_eq!(dbg.locals().get(&1), Some(&Value::Integer(100)));
     }
+
+    #[test]
+    fn test_debugger_try_raise() {
+        let mut dbg = Debugger::new();
+        dbg.load("# 0 0 {\nE \"boom\"\n}\nT v0 0\n$ v1 0\n: 0\n. v0\nE 1").unwrap();
+        dbg.step();
+        dbg.step();
+        // Unwinding leaves the function and lands on the handler label
+        assert_eq!(dbg.call_stack().len(), 0);
+        assert_eq!(dbg.locals().get(&0), Some(&Value::from("boom".to_string())));
+        let event = dbg.resume();
+        assert!(matches!(event, DebugEvent::Error(ref e) if e == "Uncaught error: 1"));
+        assert_eq!(dbg.output(), ["boom"]);
+    }
+
+    #[test]
+    fn test_debugger_set_var() {
+        let mut dbg = Debugger::new();
+        dbg.load("= v0 1\n[ g0 2\n] v1 g0 1\n+ v2 v0 v1").unwrap();
+        dbg.step();
+        dbg.step();
+        dbg.set_var("v0", Value::Integer(41)).unwrap();
+        dbg.set_var("g3", Value::from("hello")).unwrap();
+        dbg.set_var("g0[v0 - 40]", Value::Integer(7)).unwrap();
+        assert_eq!(dbg.inspect("g0"), Some(Value::from(vec![Value::Integer(0), Value::Integer(7)])));
+        assert_eq!(dbg.inspect("g3"), Some(Value::from("hello")));
+        assert!(dbg.set_var("g0[5]", Value::Integer(1)).is_err());
+        assert!(dbg.set_var("a0", Value::Integer(1)).is_err());
+        assert!(dbg.set_var("42", Value::Integer(1)).is_err());
+        dbg.resume();
+        assert_eq!(dbg.locals().get(&2), Some(&Value::Integer(48)));
+    }
+
+    #[test]
+    fn test_debugger_output_and_ffi_breaks() {
+        let mut dbg = Debugger::new();
+        dbg.load("= v0 16\nR v1 \"math.sqrt\" v0\n. v1\nR v2 \"abs\" -3\n. v2").unwrap();
+        dbg.set_ffi_break("sqrt");
+        dbg.set_output_break(true);
+        match dbg.resume() {
+            DebugEvent::Ffi { name, args } => {
+                assert_eq!(name, "math.sqrt");
+                assert_eq!(args, [Value::Integer(16)]);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(dbg.current_line(), 2);
+        assert!(matches!(dbg.resume(), DebugEvent::Output(Value::Float(f)) if f == 4.0));
+        dbg.set_output_break(false);
+        assert!(matches!(dbg.resume(), DebugEvent::Finished));
+        assert_eq!(dbg.output(), ["4.0", "3"]);
+    }
+
+    #[test]
+    fn test_debugger_input_queue() {
+        let mut dbg = Debugger::new();
+        dbg.load(", v0\n, v1\n+ v2 v0 v1").unwrap();
+        dbg.set_input_queue(vec!["40".to_string()]);
+        assert!(matches!(dbg.resume(), DebugEvent::InputNeeded));
+        assert_eq!(dbg.current_line(), 2);
+        assert!(matches!(dbg.step(), DebugEvent::InputNeeded));
+        dbg.feed_input("2");
+        assert!(matches!(dbg.resume(), DebugEvent::Finished));
+        assert_eq!(dbg.locals().get(&2), Some(&Value::Integer(42)));
+        assert!(dbg.queued_input().is_empty());
+    }
+
+    #[test]
+    fn test_debugger_export() {
+        let mut dbg = Debugger::new();
+        dbg.load("= v0 42\n= g1 \"hi\"").unwrap();
+        let addr = dbg.export("127.0.0.1:0").unwrap();
+        dbg.step();
+        dbg.step();
+        dbg.publish_snapshot();
+        let snapshot = session::fetch_snapshot(addr).unwrap();
+        assert_eq!(snapshot.func_id, -1);
+        assert_eq!(snapshot.get("v0").unwrap().value, "42");
+        assert_eq!(snapshot.get("g1").unwrap().type_name, "str");
+    }
 }
@@ -0,0 +1,126 @@
+//! Per-function call counts, for spotting runaway recursion
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::Debugger;
+
+/// Totals for one function over every call so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallStats {
+    /// Times the function was called
+    pub calls: u64,
+    /// Instructions run by the function and everything it called; nested
+    /// recursive calls are counted once
+    pub total_instructions: u64,
+    /// Instructions run by the function itself
+    pub self_instructions: u64,
+    /// Most calls in progress when the function was entered
+    pub max_depth: usize,
+}
+
+/// Calls made while debugging, accumulated since the program was loaded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallProfile {
+    /// Stats by function ID
+    pub functions: BTreeMap<i64, CallStats>,
+    /// Instructions run at the top level
+    pub top_level_instructions: u64,
+    /// Most calls ever in progress at once
+    pub max_depth: usize,
+}
+
+/// Accumulates a [`CallProfile`] as instructions run
+#[derive(Debug, Default)]
+pub(super) struct Profiler {
+    profile: CallProfile,
+    /// Instructions run so far
+    executed: u64,
+    /// Calls of each function in progress, with the instruction count when
+    /// the outermost began
+    active: HashMap<i64, (usize, u64)>,
+}
+
+impl Profiler {
+    /// Count one instruction run by `func_id` (-1 for the top level)
+    pub(super) fn instruction(&mut self, func_id: i64) {
+        self.executed += 1;
+        if func_id < 0 {
+            self.profile.top_level_instructions += 1;
+        } else {
+            self.profile.functions.entry(func_id).or_default().self_instructions += 1;
+        }
+    }
+
+    /// A call to `func_id` began, with `depth` calls now in progress
+    pub(super) fn enter(&mut self, func_id: i64, depth: usize) {
+        let stats = self.profile.functions.entry(func_id).or_default();
+        stats.calls += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        self.profile.max_depth = self.profile.max_depth.max(depth);
+        let executed = self.executed;
+        self.active.entry(func_id).or_insert((0, executed)).0 += 1;
+    }
+
+    /// A call to `func_id` returned or unwound
+    pub(super) fn exit(&mut self, func_id: i64) {
+        let Some((open, start)) = self.active.get_mut(&func_id) else { return };
+        *open -= 1;
+        if *open == 0 {
+            let spent = self.executed - *start;
+            self.active.remove(&func_id);
+            if let Some(stats) = self.profile.functions.get_mut(&func_id) {
+                stats.total_instructions += spent;
+            }
+        }
+    }
+}
+
+impl Debugger {
+    /// Calls made since the program was loaded
+    pub fn call_profile(&self) -> &CallProfile {
+        &self.profiler.profile
+    }
+
+    /// Print the call profile, busiest function first
+    pub(super) fn print_call_profile(&self) {
+        let profile = self.call_profile();
+        if profile.functions.is_empty() {
+            println!("No calls yet.");
+            return;
+        }
+        let mut rows: Vec<_> = profile.functions.iter().collect();
+        rows.sort_by_key(|(id, stats)| (std::cmp::Reverse(stats.total_instructions), **id));
+        println!("{:<12} {:>10} {:>12} {:>12} {:>9}", "function", "calls", "total", "self", "depth");
+        for (id, stats) in rows {
+            println!(
+                "{:<12} {:>10} {:>12} {:>12} {:>9}",
+                format!("func_{}", id), stats.calls, stats.total_instructions, stats.self_instructions, stats.max_depth
+            );
+        }
+        println!("{:<12} {:>10} {:>12} {:>12}", "main", "", "", profile.top_level_instructions);
+        println!("Max depth: {}", profile.max_depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::Debugger;
+
+    #[test]
+    fn test_call_profile() {
+        // Naive Fibonacci: fib(n) calls itself twice per level
+        let code = "# 0 1 {\n< v0 a0 2\n? v0 1\n- v1 a0 1\n$ v2 0 v1\n- v3 a0 2\n$ v4 0 v3\n+ v5 v2 v4\n^ v5\n: 1\n^ a0\n}\n$ g0 0 10\n. g0";
+        let mut dbg = Debugger::new();
+        dbg.load(code).unwrap();
+        dbg.resume();
+        assert_eq!(dbg.output(), ["55"]);
+
+        let profile = dbg.call_profile();
+        let fib = &profile.functions[&0];
+        assert_eq!(fib.calls, 177);
+        assert_eq!(fib.max_depth, 10);
+        assert_eq!(fib.total_instructions, fib.self_instructions);
+        assert_eq!(profile.top_level_instructions, 2);
+        assert_eq!(profile.max_depth, 10);
+    }
+}
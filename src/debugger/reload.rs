@@ -0,0 +1,217 @@
+//! Swapping in edited source while paused
+
+use std::collections::HashMap;
+
+use super::{DebugState, Debugger, StackFrame};
+use crate::interpreter::{Instruction, ParseError, Parser, LANGUAGE_VERSION};
+
+/// Largest number of changed lines compared pairwise; past it, changed
+/// regions are matched by position only
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+/// Where [`Debugger::reload`] left the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reloaded {
+    /// Paused before the instruction at this line of the new source, with
+    /// all variables kept
+    Continued(usize),
+    /// Back at the start, with globals kept; the paused instruction had no
+    /// counterpart in the new source, or the program had finished
+    Restarted,
+}
+
+impl Debugger {
+    /// Replace the program with edited source, keeping the session
+    ///
+    /// Lines of the old source are matched to the new one by content, and
+    /// breakpoints move with their lines. Globals are kept. If the
+    /// instruction the program is paused before, or a later one, is still
+    /// there, the program continues from it with its locals; otherwise it
+    /// restarts. On a parse error nothing changes.
+    ///
+    /// ```rust
+    /// use sui_lang::debugger::{Debugger, Reloaded};
+    ///
+    /// let mut dbg = Debugger::new();
+    /// dbg.load("= g0 1\n. g0\n. 2").unwrap();
+    /// dbg.step();
+    /// assert_eq!(dbg.reload("= g0 1\n; new line\n. g0\n. 3"), Ok(Reloaded::Continued(3)));
+    /// dbg.resume();
+    /// assert_eq!(dbg.output(), ["1", "3"]);
+    /// ```
+    pub fn reload(&mut self, code: &str) -> Result<Reloaded, ParseError> {
+        let (instructions, functions) = Parser::parse_numbered(code, LANGUAGE_VERSION)?;
+        let new_lines: Vec<String> = code.lines().map(|s| s.to_string()).collect();
+        let map = remap_lines(&self.source_lines, &new_lines);
+        let moved = |line: usize| map.get(line.wrapping_sub(1)).copied().unwrap_or(line);
+
+        self.breakpoints = self.breakpoints.iter().map(|&line| moved(line)).collect();
+        self.conditions = self.conditions.drain().map(|(line, cond)| (moved(line), cond)).collect();
+
+        // The paused instruction, or the first after it that survived
+        let resume_at = match (self.state, self.instructions.get(self.ip)) {
+            (DebugState::Finished, _) | (_, None) => None,
+            (_, Some(&(line, _))) => {
+                let target = moved(line);
+                instructions.iter().position(|(l, _)| *l >= target)
+            }
+        };
+        let started = self.current_line != 0;
+
+        self.source_lines = new_lines;
+        self.instructions = instructions;
+        self.labels.clear();
+        for (i, (_, instr)) in self.instructions.iter().enumerate() {
+            if let Instruction::Label { id } = instr {
+                self.labels.insert(*id, i);
+            }
+        }
+        self.functions = functions.into_iter().map(|f| (f.id, f)).collect::<HashMap<_, _>>();
+        self.raised = None;
+
+        match resume_at {
+            Some(ip) => {
+                self.ip = ip;
+                self.state = DebugState::Paused;
+                let line = self.instructions[ip].0;
+                if started {
+                    self.current_line = line;
+                }
+                Ok(Reloaded::Continued(line))
+            }
+            None => {
+                self.ip = 0;
+                self.state = DebugState::Paused;
+                self.current_line = 0;
+                self.call_stack.clear();
+                self.current_frame = StackFrame {
+                    func_id: -1, line: 0, locals: HashMap::new(), args: Vec::new(), handlers: Vec::new(),
+                };
+                self.output.clear();
+                Ok(Reloaded::Restarted)
+            }
+        }
+    }
+}
+
+/// New line number (1-based) for each old line, in order
+///
+/// Identical lines are matched as in a diff; a changed line maps to the
+/// same offset in the changed region between the matches around it, or to
+/// the line after the region if the new region is shorter.
+fn remap_lines(old: &[String], new: &[String]) -> Vec<usize> {
+    let (n, m) = (old.len(), new.len());
+    let same = |i: usize, j: usize| old[i].trim() == new[j].trim();
+    let prefix = (0..n.min(m)).take_while(|&i| same(i, i)).count();
+    let suffix = (0..(n - prefix).min(m - prefix)).take_while(|&k| same(n - 1 - k, m - 1 - k)).count();
+
+    // Matched pairs (0-based), plus sentinels before and after both sources
+    let mut anchors: Vec<(isize, isize)> = vec![(-1, -1)];
+    anchors.extend((0..prefix).map(|i| (i as isize, i as isize)));
+    let (middle_old, middle_new) = (prefix..n - suffix, prefix..m - suffix);
+    if middle_old.len() * middle_new.len() <= MAX_DIFF_CELLS {
+        anchors.extend(common_lines(old, new, middle_old, middle_new));
+    }
+    anchors.extend((0..suffix).rev().map(|k| ((n - 1 - k) as isize, (m - 1 - k) as isize)));
+    anchors.push((n as isize, m as isize));
+
+    let last = m.max(1);
+    let mut map = Vec::with_capacity(n);
+    for pair in anchors.windows(2) {
+        let ((old_a, new_a), (old_b, new_b)) = (pair[0], pair[1]);
+        if old_a >= 0 {
+            map.push((new_a as usize + 1).min(last));
+        }
+        for i in old_a + 1..old_b {
+            let line = (new_a + (i - old_a)).min(new_b) as usize + 1;
+            map.push(line.min(last));
+        }
+    }
+    map
+}
+
+/// Longest common subsequence of two line ranges, as index pairs
+fn common_lines(
+    old: &[String],
+    new: &[String],
+    old_range: std::ops::Range<usize>,
+    new_range: std::ops::Range<usize>,
+) -> Vec<(isize, isize)> {
+    let a: Vec<&str> = old[old_range.clone()].iter().map(|s| s.trim()).collect();
+    let b: Vec<&str> = new[new_range.clone()].iter().map(|s| s.trim()).collect();
+    // lengths[i][j]: LCS length of a[i..] and b[j..]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push(((old_range.start + i) as isize, (new_range.start + j) as isize));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Value;
+
+    fn lines(code: &str) -> Vec<String> {
+        code.lines().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_remap_lines() {
+        let old = lines("a\nb\nc\nd\ne");
+        // Insert before b, change d
+        let new = lines("a\nx\nb\nc\nD\ne");
+        assert_eq!(remap_lines(&old, &new), [1, 3, 4, 5, 6]);
+        // Delete c and d
+        assert_eq!(remap_lines(&old, &lines("a\nb\ne")), [1, 2, 3, 3, 3]);
+        assert_eq!(remap_lines(&old, &[]), [1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_reload_keeps_session() {
+        let mut dbg = Debugger::new();
+        dbg.load("= g0 5\n= v0 1\n+ v1 v0 g0\n. v1\n. \"end\"").unwrap();
+        dbg.set_breakpoint(4);
+        dbg.step();
+        dbg.step();
+
+        // A comment shifts everything; the addition changes
+        let code = "; edited\n= g0 5\n= v0 1\n* v1 v0 g0\n. v1\n. \"end\"";
+        assert_eq!(dbg.reload(code), Ok(Reloaded::Continued(4)));
+        assert!(dbg.breakpoints().contains(&5));
+        assert_eq!(dbg.locals().get(&0), Some(&Value::Integer(1)));
+        assert!(matches!(dbg.resume(), crate::debugger::DebugEvent::Breakpoint(5)));
+        assert_eq!(dbg.inspect("v1"), Some(Value::Integer(5)));
+
+        // Parse errors leave the session alone
+        assert!(dbg.reload("+ v1").is_err());
+        assert_eq!(dbg.current_line(), 5);
+
+        // Once finished, the program restarts with its globals; the
+        // breakpoint's line is gone, so it moves to the end
+        dbg.resume();
+        assert_eq!(dbg.reload("+ g0 g0 1\n. g0"), Ok(Reloaded::Restarted));
+        assert!(matches!(dbg.resume(), crate::debugger::DebugEvent::Breakpoint(2)));
+        dbg.resume();
+        assert_eq!(dbg.output(), ["6"]);
+    }
+}
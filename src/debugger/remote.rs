@@ -0,0 +1,602 @@
+//! Debugging over a socket
+//!
+//! [`Debugger::serve`] lets an editor on another machine drive the
+//! debugger. Clients connect over TCP, one at a time, and send one JSON
+//! request per line; browser clients, such as a page running Sui in WASM,
+//! may open a WebSocket on the same port instead and send one request per
+//! text message. Every request gets one response:
+//!
+//! ```text
+//! {"id": 1, "command": "auth", "token": "secret"}
+//! {"id": 1, "success": true, "body": {}}
+//! {"id": 2, "command": "setBreakpoint", "line": 5, "condition": "v0 > 3"}
+//! {"id": 2, "success": true, "body": {"line": 5}}
+//! {"id": 3, "command": "continue"}
+//! {"id": 3, "success": true, "body": {"event": "breakpoint", "line": 5, "state": "paused"}}
+//! {"id": 4, "command": "evaluate", "expression": "v0"}
+//! {"id": 4, "success": true, "body": {"type": "int", "value": "42"}}
+//! ```
+//!
+//! Commands: `auth`, `state`, `step`, `continue`, `setBreakpoint`,
+//! `removeBreakpoint`, `setOutputBreak` (`enabled`), `setFfiBreak` and
+//! `removeFfiBreak` (`name`), `feed` (a `value` line for `,` to read),
+//! `setVariable` (`name` and a `value` expression), `locals`, `globals`,
+//! `stack`, `calls` (counts per function), `evaluate`, `output`, `source`,
+//! `disconnect` (ends the connection) and `quit` (ends
+//! [`Debugger::serve`]). `evaluate` and breakpoint conditions take any
+//! expression [`Debugger::evaluate`] does. With a token set, every command
+//! but `auth` fails until the connection authenticates. In read-only mode,
+//! commands that run the program or change breakpoints or variables fail.
+//!
+//! Traffic is neither encrypted nor integrity-protected, and a token sent
+//! with `auth` can be read by anyone on the path. Serve on a loopback
+//! address, such as [`DEFAULT_ADDR`], and reach it from other machines
+//! through an SSH tunnel (`ssh -L 4711:127.0.0.1:4711 host`) or a TLS
+//! proxy.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde_json::{json, Map, Value as Json};
+
+use super::{DebugEvent, DebugState, Debugger};
+use crate::interpreter::digest::sha1;
+use crate::interpreter::Value;
+
+/// Address to serve on when none is chosen, reachable only from this machine
+pub const DEFAULT_ADDR: &str = "127.0.0.1:4711";
+
+/// Appended to a client's key to accept a WebSocket handshake (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest WebSocket message accepted
+const MAX_MESSAGE: u64 = 1 << 20;
+
+/// Default of [`RemoteOptions::handshake_timeout`]
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Access control for [`Debugger::serve`]
+#[derive(Debug, Clone)]
+pub struct RemoteOptions {
+    /// Token clients must send with `auth` before any other command
+    pub token: Option<String>,
+    /// Allow inspection only: no stepping, continuing, or changes to
+    /// breakpoints or variables
+    pub read_only: bool,
+    /// Longest a new client may take to send its first line or WebSocket
+    /// handshake; clients are served one at a time, so a silent one would
+    /// otherwise lock everyone else out
+    pub handshake_timeout: Duration,
+}
+
+impl Default for RemoteOptions {
+    fn default() -> Self {
+        RemoteOptions { token: None, read_only: false, handshake_timeout: HANDSHAKE_TIMEOUT }
+    }
+}
+
+/// What the server does after a request
+enum Flow {
+    Continue,
+    Disconnect,
+    Quit,
+}
+
+impl Debugger {
+    /// Serve the remote debugging protocol on `addr` until a client sends
+    /// `quit`
+    pub fn serve(&mut self, addr: impl ToSocketAddrs, options: &RemoteOptions) -> io::Result<()> {
+        self.serve_listener(TcpListener::bind(addr)?, options)
+    }
+
+    /// [`Debugger::serve`] on a listener that is already bound
+    pub fn serve_listener(&mut self, listener: TcpListener, options: &RemoteOptions) -> io::Result<()> {
+        // Input comes from the client's `feed`, never the server's stdin
+        self.input.get_or_insert_with(VecDeque::new);
+        for stream in listener.incoming() {
+            let mut connection = match Connection::accept(stream?, options.handshake_timeout) {
+                Ok(connection) => connection,
+                Err(_) => continue,
+            };
+            let mut authenticated = options.token.is_none();
+            // A client that goes away ends its connection, not the server
+            while let Ok(Some(message)) = connection.recv() {
+                let (response, flow) = self.handle_request(&message, options, &mut authenticated);
+                if connection.send(&response.to_string()).is_err() {
+                    break;
+                }
+                match flow {
+                    Flow::Continue => {}
+                    Flow::Disconnect => break,
+                    Flow::Quit => return Ok(()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, message: &str, options: &RemoteOptions, authenticated: &mut bool) -> (Json, Flow) {
+        let request: Map<String, Json> = match serde_json::from_str(message) {
+            Ok(Json::Object(request)) => request,
+            _ => return (json!({"id": null, "success": false, "error": "malformed request"}), Flow::Continue),
+        };
+        let id = request.get("id").cloned().unwrap_or(Json::Null);
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let mut flow = Flow::Continue;
+
+        let result = if command == "auth" {
+            let token = request.get("token").and_then(Json::as_str).unwrap_or("");
+            if options.token.as_ref().map_or(true, |expected| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+                *authenticated = true;
+                Ok(json!({}))
+            } else {
+                Err("invalid token".to_string())
+            }
+        } else if !*authenticated {
+            Err("not authenticated".to_string())
+        } else if options.read_only
+            && matches!(
+                command,
+                "step" | "continue" | "setBreakpoint" | "removeBreakpoint" | "setOutputBreak" | "setFfiBreak"
+                    | "removeFfiBreak" | "feed" | "setVariable"
+            )
+        {
+            Err("read-only session".to_string())
+        } else {
+            let line = request.get("line").and_then(Json::as_u64).map(|l| l as usize);
+            match command {
+                "state" => Ok(self.remote_state()),
+                "step" => {
+                    let event = self.step();
+                    Ok(self.remote_event(&event))
+                }
+                "continue" => {
+                    let event = self.resume();
+                    Ok(self.remote_event(&event))
+                }
+                "setBreakpoint" | "removeBreakpoint" => match line {
+                    Some(line) => {
+                        let condition = request.get("condition").and_then(Json::as_str);
+                        if let (true, Some(condition)) = (command == "setBreakpoint", condition) {
+                            self.set_conditional_breakpoint(line, condition);
+                        } else if command == "setBreakpoint" {
+                            self.set_breakpoint(line);
+                        } else {
+                            self.remove_breakpoint(line);
+                        }
+                        Ok(json!({"line": line}))
+                    }
+                    None => Err("missing line".to_string()),
+                },
+                "setOutputBreak" => match request.get("enabled").and_then(Json::as_bool) {
+                    Some(enabled) => {
+                        self.set_output_break(enabled);
+                        Ok(json!({"enabled": enabled}))
+                    }
+                    None => Err("missing enabled".to_string()),
+                },
+                "setFfiBreak" | "removeFfiBreak" => match request.get("name").and_then(Json::as_str) {
+                    Some(name) => {
+                        if command == "setFfiBreak" {
+                            self.set_ffi_break(name);
+                        } else {
+                            self.remove_ffi_break(name);
+                        }
+                        Ok(json!({"name": name}))
+                    }
+                    None => Err("missing name".to_string()),
+                },
+                "feed" => match request.get("value").and_then(Json::as_str) {
+                    Some(value) => {
+                        self.feed_input(value);
+                        Ok(json!({"queued": self.queued_input().len()}))
+                    }
+                    None => Err("missing value".to_string()),
+                },
+                "setVariable" => {
+                    let field = |key: &str| request.get(key).and_then(Json::as_str).ok_or(format!("missing {}", key));
+                    let (name, expr) = match (field("name"), field("value")) {
+                        (Ok(name), Ok(expr)) => (name, expr),
+                        (Err(e), _) | (_, Err(e)) => return (json!({"id": id, "success": false, "error": e}), flow),
+                    };
+                    self.evaluate(expr).and_then(|value| {
+                        self.set_var(name, value.clone())?;
+                        Ok(remote_value(&value))
+                    })
+                }
+                "locals" => Ok(variables('v', self.current_frame.locals.iter())),
+                "globals" => Ok(variables('g', self.global_vars.iter())),
+                "stack" => {
+                    let frame = |func_id: i64, line: usize| json!({"func_id": func_id, "line": line});
+                    let mut frames: Vec<Json> = self.call_stack.iter().map(|f| frame(f.func_id, f.line)).collect();
+                    frames.push(frame(self.current_frame.func_id, self.current_line));
+                    Ok(json!({"frames": frames}))
+                }
+                "calls" => {
+                    let profile = self.call_profile();
+                    let functions: Vec<Json> = profile
+                        .functions
+                        .iter()
+                        .map(|(id, stats)| {
+                            json!({
+                                "func_id": id,
+                                "calls": stats.calls,
+                                "total_instructions": stats.total_instructions,
+                                "self_instructions": stats.self_instructions,
+                                "max_depth": stats.max_depth,
+                            })
+                        })
+                        .collect();
+                    Ok(json!({
+                        "functions": functions,
+                        "top_level_instructions": profile.top_level_instructions,
+                        "max_depth": profile.max_depth,
+                    }))
+                }
+                "evaluate" => match request.get("expression").and_then(Json::as_str) {
+                    Some(expr) => self.evaluate(expr).map(|v| remote_value(&v)),
+                    None => Err("missing expression".to_string()),
+                },
+                "output" => Ok(json!({"lines": self.output})),
+                "source" => Ok(json!({"lines": self.source_lines})),
+                "disconnect" => {
+                    flow = Flow::Disconnect;
+                    Ok(json!({}))
+                }
+                "quit" => {
+                    flow = Flow::Quit;
+                    Ok(json!({}))
+                }
+                _ => Err(format!("unknown command '{}'", command)),
+            }
+        };
+
+        let response = match result {
+            Ok(body) => json!({"id": id, "success": true, "body": body}),
+            Err(error) => json!({"id": id, "success": false, "error": error}),
+        };
+        (response, flow)
+    }
+
+    fn remote_state(&self) -> Json {
+        let state = match self.state {
+            DebugState::Running => "running",
+            DebugState::Paused => "paused",
+            DebugState::Stepping => "stepping",
+            DebugState::Finished => "finished",
+        };
+        json!({"state": state, "line": self.current_line, "func_id": self.current_frame.func_id})
+    }
+
+    fn remote_event(&self, event: &DebugEvent) -> Json {
+        let mut body = self.remote_state();
+        let (name, message) = match event {
+            DebugEvent::Breakpoint(_) => ("breakpoint", None),
+            DebugEvent::Step => ("step", None),
+            DebugEvent::Finished => ("finished", None),
+            DebugEvent::Error(e) => ("error", Some(e.clone())),
+            DebugEvent::Output(value) => {
+                body["value"] = remote_value(value);
+                ("output", None)
+            }
+            DebugEvent::InputNeeded => ("input", None),
+            DebugEvent::Ffi { name, args } => {
+                body["name"] = json!(name);
+                body["args"] = Json::Array(args.iter().map(remote_value).collect());
+                ("ffi", None)
+            }
+        };
+        body["event"] = json!(name);
+        if let Some(message) = message {
+            body["message"] = json!(message);
+        }
+        body
+    }
+}
+
+fn remote_value(value: &Value) -> Json {
+    json!({"type": value.type_name(), "value": value.to_string()})
+}
+
+/// Variables as an object from name to value
+fn variables<'a>(prefix: char, vars: impl Iterator<Item = (&'a i64, &'a Value)>) -> Json {
+    let vars: Map<String, Json> = vars.map(|(idx, v)| (format!("{}{}", prefix, idx), remote_value(v))).collect();
+    Json::Object(vars)
+}
+
+/// A client connection, speaking lines or WebSocket messages
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    websocket: bool,
+    /// First line, already read while detecting the protocol
+    pending: Option<String>,
+}
+
+impl Connection {
+    /// Detect the protocol from the first line, completing a WebSocket
+    /// handshake if it is an HTTP request, within `timeout`
+    fn accept(stream: TcpStream, timeout: Duration) -> io::Result<Self> {
+        stream.set_read_timeout(Some(timeout))?;
+        let connection = Self::handshake(stream)?;
+        // Once connected, a client may think as long as it likes
+        connection.writer.set_read_timeout(None)?;
+        Ok(connection)
+    }
+
+    fn handshake(stream: TcpStream) -> io::Result<Self> {
+        let writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut first = String::new();
+        reader.read_line(&mut first)?;
+        if !first.starts_with("GET ") {
+            return Ok(Connection { reader, writer, websocket: false, pending: Some(first) });
+        }
+
+        let mut key = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                    key = Some(value.trim().to_string());
+                }
+            }
+        }
+        let mut writer = writer;
+        let Some(key) = key else {
+            writer.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket request"));
+        };
+        write!(
+            writer,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            websocket_accept(&key)
+        )?;
+        Ok(Connection { reader, writer, websocket: true, pending: None })
+    }
+
+    /// Next request, or `None` once the client closes the connection
+    fn recv(&mut self) -> io::Result<Option<String>> {
+        if !self.websocket {
+            let mut line = self.pending.take().unwrap_or_default();
+            while line.trim().is_empty() {
+                line.clear();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+            }
+            return Ok(Some(line));
+        }
+
+        let mut message = Vec::new();
+        loop {
+            let mut head = [0u8; 2];
+            self.reader.read_exact(&mut head)?;
+            let (fin, opcode) = (head[0] & 0x80 != 0, head[0] & 0x0f);
+            let masked = head[1] & 0x80 != 0;
+            let len = match head[1] & 0x7f {
+                126 => {
+                    let mut n = [0u8; 2];
+                    self.reader.read_exact(&mut n)?;
+                    u16::from_be_bytes(n) as u64
+                }
+                127 => {
+                    let mut n = [0u8; 8];
+                    self.reader.read_exact(&mut n)?;
+                    u64::from_be_bytes(n)
+                }
+                n => n as u64,
+            };
+            if len + message.len() as u64 > MAX_MESSAGE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+            }
+            let mut mask = [0u8; 4];
+            if masked {
+                self.reader.read_exact(&mut mask)?;
+            }
+            let mut payload = vec![0u8; len as usize];
+            self.reader.read_exact(&mut payload)?;
+            if masked {
+                for (i, byte) in payload.iter_mut().enumerate() {
+                    *byte ^= mask[i % 4];
+                }
+            }
+            match opcode {
+                // Close
+                0x8 => return Ok(None),
+                // Ping
+                0x9 => self.send_frame(0xa, &payload)?,
+                // Pong
+                0xa => {}
+                // Text, binary or continuation
+                _ => {
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        return String::from_utf8(message)
+                            .map(Some)
+                            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "message is not UTF-8"));
+                    }
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, text: &str) -> io::Result<()> {
+        if self.websocket {
+            self.send_frame(0x1, text.as_bytes())
+        } else {
+            writeln!(self.writer, "{}", text)
+        }
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            n if n < 126 => frame.push(n as u8),
+            n if n <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            n => {
+                frame.push(127);
+                frame.extend_from_slice(&(n as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        self.writer.write_all(&frame)
+    }
+}
+
+/// `Sec-WebSocket-Accept` answer to a client's `Sec-WebSocket-Key`
+fn websocket_accept(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+/// Whether `a` equals `b`, in a time that does not depend on where they
+/// differ, so a client cannot find the token a byte at a time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff = a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y));
+    a.len() == b.len() && std::hint::black_box(diff) == 0
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn serve(code: &str, options: RemoteOptions) -> (std::net::SocketAddr, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let code = code.to_string();
+        let server = thread::spawn(move || {
+            let mut dbg = Debugger::new();
+            dbg.load(&code).unwrap();
+            dbg.serve_listener(listener, &options).unwrap();
+        });
+        (addr, server)
+    }
+
+    fn request(conn: &mut (BufReader<TcpStream>, TcpStream), text: &str) -> Json {
+        writeln!(conn.1, "{}", text).unwrap();
+        let mut line = String::new();
+        conn.0.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn connect(addr: std::net::SocketAddr) -> (BufReader<TcpStream>, TcpStream) {
+        let stream = TcpStream::connect(addr).unwrap();
+        (BufReader::new(stream.try_clone().unwrap()), stream)
+    }
+
+    #[test]
+    fn test_websocket_accept() {
+        // Example handshake from RFC 6455
+        assert_eq!(websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[test]
+    fn test_handshake_timeout() {
+        let options = RemoteOptions { handshake_timeout: Duration::from_millis(100), ..RemoteOptions::default() };
+        let (addr, server) = serve(". 1", options);
+        // Connects and says nothing, which must not lock the next client out
+        let _silent = TcpStream::connect(addr).unwrap();
+        let mut conn = connect(addr);
+        let response = request(&mut conn, r#"{"id": 1, "command": "state"}"#);
+        assert_eq!(response["success"], true);
+        request(&mut conn, r#"{"id": 2, "command": "quit"}"#);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_line_protocol() {
+        let options = RemoteOptions { token: Some("secret".to_string()), ..RemoteOptions::default() };
+        let (addr, server) = serve("= v0 42\n+ v1 v0 1\n. v1", options);
+        let mut conn = connect(addr);
+
+        let response = request(&mut conn, r#"{"id": 1, "command": "step"}"#);
+        assert_eq!(response["error"], "not authenticated");
+        let response = request(&mut conn, r#"{"id": 2, "command": "auth", "token": "wrong"}"#);
+        assert_eq!(response["success"], false);
+        let response = request(&mut conn, r#"{"id": 3, "command": "auth", "token": "secret"}"#);
+        assert_eq!(response["success"], true);
+
+        request(&mut conn, r#"{"id": 4, "command": "setBreakpoint", "line": 3}"#);
+        let response = request(&mut conn, r#"{"id": 5, "command": "continue"}"#);
+        assert_eq!(response["id"], 5);
+        assert_eq!(response["body"]["event"], "breakpoint");
+        assert_eq!(response["body"]["line"], 3);
+        let response = request(&mut conn, r#"{"id": 6, "command": "evaluate", "expression": "v1"}"#);
+        assert_eq!(response["body"], json!({"type": "int", "value": "43"}));
+        let response = request(&mut conn, r#"{"id": 7, "command": "setVariable", "name": "v0", "value": "v1 * 2"}"#);
+        assert_eq!(response["body"]["value"], "86");
+        let response = request(&mut conn, r#"{"id": 7, "command": "locals"}"#);
+        assert_eq!(response["body"]["v0"]["value"], "86");
+        let response = request(&mut conn, "not json");
+        assert_eq!(response["error"], "malformed request");
+
+        request(&mut conn, r#"{"id": 8, "command": "quit"}"#);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_read_only_websocket() {
+        let (addr, server) = serve("= v0 1", RemoteOptions { read_only: true, ..RemoteOptions::default() });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+        assert!(status.starts_with("HTTP/1.1 101"));
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut send = |text: &str| {
+            // Client frames are masked
+            let mask = [1u8, 2, 3, 4];
+            let mut frame = vec![0x81, 0x80 | text.len() as u8];
+            frame.extend_from_slice(&mask);
+            frame.extend(text.bytes().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+            stream.write_all(&frame).unwrap();
+            let mut head = [0u8; 2];
+            reader.read_exact(&mut head).unwrap();
+            let mut payload = vec![0u8; (head[1] & 0x7f) as usize];
+            reader.read_exact(&mut payload).unwrap();
+            serde_json::from_slice::<Json>(&payload).unwrap()
+        };
+        assert_eq!(send(r#"{"id": 1, "command": "step"}"#)["error"], "read-only session");
+        assert_eq!(send(r#"{"id": 2, "command": "state"}"#)["body"]["state"], "paused");
+        send(r#"{"id": 3, "command": "quit"}"#);
+        server.join().unwrap();
+    }
+}
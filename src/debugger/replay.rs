@@ -0,0 +1,399 @@
+//! Navigating a recorded run
+//!
+//! `sui --record trace.bin` saves a [`Trace`] of a run and
+//! `sui-debug --replay trace.bin` opens it in a [`Replay`], which moves
+//! forward and backward through the recorded instructions without running
+//! the program again. Input and arguments are whatever the recorded run got.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+use super::StackFrame;
+use crate::interpreter::{Lexer, ParsedValue, Trace, TraceEvent, Value};
+
+/// Variables and output after some prefix of a trace's events
+#[derive(Debug, Clone, Default)]
+struct ReplayState {
+    /// Frames of the calls in progress, top level first
+    frames: Vec<StackFrame>,
+    globals: HashMap<i64, Value>,
+    output: Vec<String>,
+    /// Values of the operands of the last step, the arguments if it is a call
+    operands: Vec<(String, Value)>,
+    /// Number of events applied
+    applied: usize,
+}
+
+impl ReplayState {
+    fn apply(&mut self, event: &TraceEvent) {
+        match event {
+            TraceEvent::Step { line, func_id, depth, operands } => {
+                if self.frames.len() > depth + 1 {
+                    self.frames.truncate(depth + 1);
+                }
+                while self.frames.len() < depth + 1 {
+                    let args = self.operands.iter().map(|(_, v)| v.clone()).collect();
+                    self.frames.push(StackFrame {
+                        func_id: *func_id,
+                        line: *line,
+                        locals: HashMap::new(),
+                        args,
+                        handlers: Vec::new(),
+                    });
+                }
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.line = *line;
+                }
+                self.operands = operands.clone();
+            }
+            TraceEvent::Write { depth, var, value } => {
+                let (prefix, idx) = Lexer::split_variable(var);
+                match prefix {
+                    'g' => {
+                        self.globals.insert(idx, value.clone());
+                    }
+                    'v' => {
+                        if let Some(frame) = self.frames.get_mut(*depth) {
+                            frame.locals.insert(idx, value.clone());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            TraceEvent::Output(line) => self.output.push(line.clone()),
+        }
+        self.applied += 1;
+    }
+}
+
+/// Debugger over a recorded run
+///
+/// Position `p` is the moment the `p`-th recorded instruction (from 0) is
+/// about to run; position [`len`](Self::len) is the end of the run.
+pub struct Replay {
+    trace: Trace,
+    source_lines: Vec<String>,
+    /// Index in `trace.events` of each step
+    steps: Vec<usize>,
+    position: usize,
+    breakpoints: HashSet<usize>,
+    state: ReplayState,
+}
+
+impl Replay {
+    pub fn new(trace: Trace) -> Self {
+        let source_lines = trace.source.lines().map(|s| s.to_string()).collect();
+        let steps = trace
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| matches!(e, TraceEvent::Step { .. }))
+            .map(|(i, _)| i)
+            .collect();
+        let mut replay = Self {
+            trace,
+            source_lines,
+            steps,
+            position: 0,
+            breakpoints: HashSet::new(),
+            state: ReplayState::default(),
+        };
+        replay.seek(0);
+        replay
+    }
+
+    /// Number of recorded instructions
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Move to a position, clamped to the end of the run
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.len());
+        // Events up to and including the step's own, which enters its frame
+        let target = match self.steps.get(self.position) {
+            Some(&event) => event + 1,
+            None => self.trace.events.len(),
+        };
+        if target < self.state.applied {
+            self.state = ReplayState::default();
+        }
+        while self.state.applied < target {
+            let event = &self.trace.events[self.state.applied];
+            self.state.apply(event);
+        }
+    }
+
+    /// Move one instruction forward, returning false at the end
+    pub fn step(&mut self) -> bool {
+        if self.position >= self.len() {
+            return false;
+        }
+        self.seek(self.position + 1);
+        true
+    }
+
+    /// Move one instruction back, returning false at the start
+    pub fn step_back(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.seek(self.position - 1);
+        true
+    }
+
+    /// Move forward to the next breakpoint, returning its line, or to the end
+    pub fn resume(&mut self) -> Option<usize> {
+        while self.step() {
+            if let Some(line) = self.current_line().filter(|l| self.breakpoints.contains(l)) {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// Move back to the previous breakpoint, returning its line, or to the
+    /// start
+    pub fn reverse(&mut self) -> Option<usize> {
+        while self.step_back() {
+            if let Some(line) = self.current_line().filter(|l| self.breakpoints.contains(l)) {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) { self.breakpoints.insert(line); }
+    pub fn remove_breakpoint(&mut self, line: usize) { self.breakpoints.remove(&line); }
+
+    /// Line of the instruction about to run, `None` at the end
+    pub fn current_line(&self) -> Option<usize> {
+        match self.trace.events.get(*self.steps.get(self.position)?) {
+            Some(TraceEvent::Step { line, .. }) => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// Operands the instruction about to run reads, with their values
+    pub fn operands(&self) -> &[(String, Value)] {
+        match self.steps.get(self.position) {
+            Some(_) => &self.state.operands,
+            None => &[],
+        }
+    }
+
+    fn frame(&self) -> Option<&StackFrame> { self.state.frames.last() }
+
+    pub fn locals(&self) -> HashMap<i64, Value> { self.frame().map(|f| f.locals.clone()).unwrap_or_default() }
+    pub fn globals(&self) -> &HashMap<i64, Value> { &self.state.globals }
+    pub fn args(&self) -> &[Value] { self.frame().map_or(&[], |f| f.args.as_slice()) }
+    /// Frames of the calls in progress, top level first and current last
+    pub fn call_stack(&self) -> &[StackFrame] { &self.state.frames }
+    /// Lines printed up to the current position
+    pub fn output(&self) -> &[String] { &self.state.output }
+    /// Error the recorded run ended with, if any
+    pub fn error(&self) -> Option<&str> { self.trace.error.as_deref() }
+
+    pub fn inspect(&self, expr: &str) -> Option<Value> {
+        Some(match Lexer::parse_value(expr) {
+            ParsedValue::Variable(var) => {
+                let (prefix, idx) = Lexer::split_variable(&var);
+                match prefix {
+                    'v' => self.frame()?.locals.get(&idx).cloned().unwrap_or_default(),
+                    'g' => self.state.globals.get(&idx).cloned().unwrap_or_default(),
+                    'a' => self.args().get(idx as usize).cloned().unwrap_or_default(),
+                    _ => return None,
+                }
+            }
+            ParsedValue::Integer(n) => Value::Integer(n),
+            ParsedValue::Float(f) => Value::Float(f),
+            ParsedValue::String(s) => Value::from(s),
+        })
+    }
+
+    fn source_at(&self, line: usize) -> Option<&str> {
+        self.source_lines.get(line.checked_sub(1)?).map(|s| s.as_str())
+    }
+
+    fn print_position(&self) {
+        match self.current_line() {
+            Some(line) => {
+                let src = self.source_at(line).unwrap_or("");
+                println!("[{}/{}] => {}: {}", self.position, self.len(), line, src);
+            }
+            None => {
+                println!("[{}/{}] End of recording.", self.position, self.len());
+                if let Some(error) = self.error() { println!("Run failed: {}", error); }
+            }
+        }
+    }
+
+    pub fn run_interactive(&mut self) {
+        println!("Sui Replay - {} recorded steps. Type 'help' for commands\n", self.len());
+        self.print_position();
+        let stdin = io::stdin();
+        loop {
+            print!("(sui-replay) ");
+            io::stdout().flush().ok();
+            let mut input = String::new();
+            if stdin.lock().read_line(&mut input).unwrap_or(0) == 0 { break; }
+            let cmd: Vec<&str> = input.split_whitespace().collect();
+            if cmd.is_empty() { continue; }
+            let count = cmd.get(1).and_then(|n| n.parse::<usize>().ok()).unwrap_or(1);
+            match cmd[0] {
+                "help" | "h" => {
+                    println!("Commands:");
+                    println!("  step [N], s     - Move N instructions forward");
+                    println!("  back [N], rs    - Move N instructions back");
+                    println!("  continue, c     - Move forward to the next breakpoint");
+                    println!("  reverse, rc     - Move back to the previous breakpoint");
+                    println!("  goto N          - Move to step N");
+                    println!("  break N, b N    - Set breakpoint at line N");
+                    println!("  delete N, d N   - Remove breakpoint at line N");
+                    println!("  list, l         - Show source around current line");
+                    println!("  operands, o     - Show the values the current instruction reads");
+                    println!("  locals          - Show local variables");
+                    println!("  globals         - Show global variables");
+                    println!("  print E, p E    - Inspect expression E");
+                    println!("  backtrace, bt   - Show call stack");
+                    println!("  output          - Show output printed so far");
+                    println!("  quit, q         - Exit");
+                }
+                "step" | "s" => { self.seek(self.position + count); self.print_position(); }
+                "back" | "rs" => { self.seek(self.position.saturating_sub(count)); self.print_position(); }
+                "continue" | "c" => {
+                    if let Some(line) = self.resume() { println!("Breakpoint at line {}", line); }
+                    self.print_position();
+                }
+                "reverse" | "rc" => {
+                    if let Some(line) = self.reverse() { println!("Breakpoint at line {}", line); }
+                    self.print_position();
+                }
+                "goto" => {
+                    match cmd.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                        Some(n) => { self.seek(n); self.print_position(); }
+                        None => println!("Usage: goto N"),
+                    }
+                }
+                "break" | "b" => {
+                    if let Some(line) = cmd.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                        self.set_breakpoint(line);
+                        println!("Breakpoint set at line {}", line);
+                    } else { println!("Breakpoints: {:?}", self.breakpoints); }
+                }
+                "delete" | "d" => {
+                    if let Some(line) = cmd.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                        self.remove_breakpoint(line);
+                        println!("Breakpoint removed at line {}", line);
+                    }
+                }
+                "list" | "l" => {
+                    let current = self.current_line().unwrap_or(0);
+                    let start = current.saturating_sub(3);
+                    let end = (current + 4).min(self.source_lines.len());
+                    for i in start..end {
+                        let marker = if i + 1 == current { "=>" } else { "  " };
+                        let bp = if self.breakpoints.contains(&(i + 1)) { "*" } else { " " };
+                        if let Some(src) = self.source_at(i + 1) { println!("{}{} {:3}: {}", marker, bp, i + 1, src); }
+                    }
+                }
+                "operands" | "o" => {
+                    for (operand, value) in self.operands() { println!("  {} = {}", operand, value); }
+                }
+                "locals" => {
+                    println!("Local variables:");
+                    let mut vars: Vec<_> = self.locals().into_iter().collect();
+                    vars.sort_by_key(|(k, _)| *k);
+                    for (idx, val) in vars { println!("  v{} = {}", idx, val); }
+                }
+                "globals" => {
+                    println!("Global variables:");
+                    let mut vars: Vec<_> = self.globals().iter().collect();
+                    vars.sort_by_key(|(k, _)| *k);
+                    for (idx, val) in vars { println!("  g{} = {}", idx, val); }
+                }
+                "print" | "p" => {
+                    if let Some(expr) = cmd.get(1) {
+                        if let Some(val) = self.inspect(expr) { println!("{} = {}", expr, val); }
+                    }
+                }
+                "backtrace" | "bt" => {
+                    println!("Call stack:");
+                    for (i, frame) in self.call_stack().iter().rev().enumerate() {
+                        let name = if frame.func_id < 0 { "main".to_string() } else { format!("func_{}", frame.func_id) };
+                        println!("  #{} {} at line {}", i, name, frame.line);
+                    }
+                }
+                "output" => { for line in self.output() { println!("{}", line); } }
+                "quit" | "q" => { println!("Exiting replay."); break; }
+                _ => { println!("Unknown command: {}. Type 'help' for commands.", cmd[0]); }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    fn record(code: &str, args: &[&str]) -> Trace {
+        let mut interp = Interpreter::new();
+        interp.set_recording(true);
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let result = interp.run(code, &args);
+        let mut trace = interp.take_trace().unwrap();
+        trace.error = result.err().map(|e| e.to_string());
+        trace
+    }
+
+    #[test]
+    fn test_replay_forward_and_back() {
+        let code = "# 0 1 {\n* v0 a0 2\n^ v0\n}\n= v0 g101\n$ v1 0 v0\n. v1\n= v0 0";
+        let mut replay = Replay::new(record(code, &["21"]));
+        assert_eq!(replay.len(), 6);
+        assert_eq!(replay.current_line(), Some(5));
+
+        // Inside the call: a fresh frame with the argument
+        replay.seek(2);
+        assert_eq!(replay.current_line(), Some(2));
+        assert_eq!(replay.args(), [Value::Integer(21)]);
+        assert!(replay.locals().is_empty());
+        assert_eq!(replay.call_stack().len(), 2);
+
+        replay.seek(4);
+        assert_eq!(replay.current_line(), Some(7));
+        assert_eq!(replay.inspect("v1"), Some(Value::Integer(42)));
+        assert_eq!(replay.operands(), [("v1".to_string(), Value::Integer(42))]);
+        assert!(replay.output().is_empty());
+
+        assert!(replay.step());
+        assert_eq!(replay.output(), ["42"]);
+        assert!(replay.step());
+        assert!(!replay.step());
+        assert_eq!(replay.inspect("v0"), Some(Value::Integer(0)));
+
+        // Backward without re-running
+        replay.set_breakpoint(2);
+        assert_eq!(replay.reverse(), Some(2));
+        assert_eq!(replay.position(), 2);
+        assert_eq!(replay.inspect("g101"), Some(Value::Integer(21)));
+        assert!(replay.output().is_empty());
+    }
+
+    #[test]
+    fn test_replay_failed_run() {
+        let trace = record("= v0 1\nE \"boom\"\n. v0", &[]);
+        let replay = Replay::new(Trace::decode(&trace.encode()).unwrap());
+        assert_eq!(replay.len(), 2);
+        assert_eq!(replay.error(), Some("Uncaught error at line 2: boom"));
+    }
+}
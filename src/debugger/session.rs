@@ -0,0 +1,195 @@
+//! Sharing a paused program's state with other processes
+//!
+//! `sui-debug --export ADDR` serves a [`Snapshot`] of the current frame over
+//! TCP: every connection receives the latest snapshot as text and is closed.
+//! `sui-lsp --attach ADDR` fetches it to show variable values on hover.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long [`fetch_snapshot`] waits for the debugger
+const FETCH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Value of one variable, rendered for display
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotValue {
+    /// Type name, as from [`Value::type_name`](crate::Value::type_name)
+    pub type_name: String,
+    /// Value as `.` would print it
+    pub value: String,
+}
+
+/// Variables visible in the debugger's current frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Source file being debugged, if known
+    pub file: Option<String>,
+    /// Function of the current frame (-1 for main)
+    pub func_id: i64,
+    /// Variables by name (`v0`, `g1`, `a0`)
+    pub variables: BTreeMap<String, SnapshotValue>,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self {
+            file: None,
+            func_id: -1,
+            variables: BTreeMap::new(),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Value of a variable, by name
+    pub fn get(&self, name: &str) -> Option<&SnapshotValue> {
+        self.variables.get(name)
+    }
+
+    /// Line-based text form sent over the wire
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        if let Some(file) = &self.file {
+            out.push_str(&format!("file {}\n", escape(file)));
+        }
+        out.push_str(&format!("frame {}\n", self.func_id));
+        for (name, value) in &self.variables {
+            out.push_str(&format!("var {} {} {}\n", name, value.type_name, escape(&value.value)));
+        }
+        out
+    }
+
+    /// Parse the output of [`Snapshot::encode`]
+    pub fn decode(text: &str) -> Option<Self> {
+        let mut snapshot = Snapshot::default();
+        for line in text.lines() {
+            let (key, rest) = line.split_once(' ')?;
+            match key {
+                "file" => snapshot.file = Some(unescape(rest)),
+                "frame" => snapshot.func_id = rest.parse().ok()?,
+                "var" => {
+                    let mut parts = rest.splitn(3, ' ');
+                    let name = parts.next()?.to_string();
+                    let type_name = parts.next()?.to_string();
+                    let value = unescape(parts.next()?);
+                    snapshot.variables.insert(name, SnapshotValue { type_name, value });
+                }
+                _ => return None,
+            }
+        }
+        Some(snapshot)
+    }
+}
+
+/// Serves the latest published snapshot over TCP
+pub struct SnapshotServer {
+    latest: Arc<Mutex<String>>,
+    addr: SocketAddr,
+}
+
+impl SnapshotServer {
+    /// Listen on `addr` (port 0 picks a free port) and serve from a
+    /// background thread
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let latest = Arc::new(Mutex::new(Snapshot::default().encode()));
+
+        let shared = Arc::clone(&latest);
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let text = shared.lock().map(|s| s.clone()).unwrap_or_default();
+                let _ = stream.write_all(text.as_bytes());
+            }
+        });
+
+        Ok(Self { latest, addr })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Replace the snapshot served to new connections
+    pub fn publish(&self, snapshot: &Snapshot) {
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = snapshot.encode();
+        }
+    }
+}
+
+/// Fetch the current snapshot from a [`SnapshotServer`]
+pub fn fetch_snapshot(addr: impl ToSocketAddrs) -> io::Result<Snapshot> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, FETCH_TIMEOUT)?;
+    stream.set_read_timeout(Some(FETCH_TIMEOUT))?;
+
+    let mut text = String::new();
+    stream.read_to_string(&mut text)?;
+    Snapshot::decode(&text).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed snapshot"))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Snapshot {
+        let mut snapshot = Snapshot {
+            file: Some("dir/a b.sui".to_string()),
+            ..Default::default()
+        };
+        snapshot.variables.insert(
+            "v0".to_string(),
+            SnapshotValue {
+                type_name: "str".to_string(),
+                value: "two\nlines \\ here".to_string(),
+            },
+        );
+        snapshot
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let snapshot = sample();
+        assert_eq!(Snapshot::decode(&snapshot.encode()), Some(snapshot));
+        assert_eq!(Snapshot::decode("bogus"), None);
+    }
+
+    #[test]
+    fn test_serve_and_fetch() {
+        let server = SnapshotServer::bind("127.0.0.1:0").unwrap();
+        server.publish(&sample());
+        let fetched = fetch_snapshot(server.local_addr()).unwrap();
+        assert_eq!(fetched.get("v0").unwrap().type_name, "str");
+    }
+}
@@ -0,0 +1,384 @@
+//! Repair feedback for generated programs
+//!
+//! A model writing Sui rarely gets a program right on the first attempt.
+//! [`check`] parses a program and runs it against test cases, and
+//! [`diagnose`] turns the [`Failure`] it finds into [`RepairHints`]: terse
+//! hints anchored to the offending lines, with the line's code and what to
+//! change, to send back to the model for another attempt. They render as
+//! plain text or, with [`RepairHints::to_json`], as JSON.
+//!
+//! ```rust
+//! use std::path::Path;
+//! use sui_lang::corpus::CorpusProgram;
+//! use sui_lang::feedback::{check, diagnose};
+//!
+//! let code = "= v0 1\n+ v1 v0\n. v1";
+//! let failure = check(code, Path::new("main.sui"), &[CorpusProgram::default()], 1000).unwrap_err();
+//! let hints = diagnose(code, &failure);
+//! assert_eq!(hints.hints[0].line, Some(2));
+//! assert!(hints.to_string().contains("line 2 (`+ v1 v0`): `+` takes 3 operands (`+ result a b`), got 2"));
+//! ```
+//!
+//! `sui check --format llm program.sui` prints the hints for a program
+//! tested against its `.snap` file.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::corpus::{execute, CorpusProgram};
+use crate::interpreter::json::write_string;
+use crate::interpreter::{instruction_set, Interpreter, InterpreterError, Lexer, ParseError, Parser, MAX_ARRAY_SIZE, MAX_INTEGER_BITS};
+
+/// Why a program is not done
+#[derive(Debug)]
+pub enum Failure {
+    /// Lines that do not parse
+    Parse(Vec<ParseError>),
+    /// A test case the program does not pass: what the run printed and the
+    /// error it stopped with, if any
+    Test { case: Box<CorpusProgram>, output: Vec<String>, error: Option<InterpreterError> },
+}
+
+/// Parse `code` and run it, as if it were the file at `path`, with the
+/// arguments and input of each case, at most `max_steps` instructions (or
+/// the case's own limit) each
+///
+/// Fails with every line that does not parse, or else with the first case
+/// that does not pass. A [`CorpusProgram::default`] case checks that the
+/// program runs without arguments and without errors.
+pub fn check(code: &str, path: &Path, cases: &[CorpusProgram], max_steps: u64) -> Result<(), Failure> {
+    let errors = Parser::validate(code);
+    if !errors.is_empty() {
+        return Err(Failure::Parse(errors));
+    }
+    for case in cases {
+        let mut interp = Interpreter::new();
+        interp.set_max_steps(Some(case.max_steps.unwrap_or(max_steps)));
+        let (output, result) = execute(&mut interp, case, path, Some(code));
+        let error = result.err();
+        if case.check(&output, error.as_ref().map(|e| e.to_string()).as_deref()).is_some() {
+            return Err(Failure::Test { case: Box::new(case.clone()), output, error });
+        }
+    }
+    Ok(())
+}
+
+/// One thing to fix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    /// Line of the program (1-based), if the problem has one
+    pub line: Option<usize>,
+    /// Source of that line
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.line, &self.code) {
+            (Some(line), Some(code)) => write!(f, "line {} (`{}`): {}", line, code, self.message),
+            (Some(line), None) => write!(f, "line {}: {}", line, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// What went wrong and how to fix it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairHints {
+    /// One sentence on how the program failed
+    pub summary: String,
+    pub hints: Vec<Hint>,
+}
+
+impl fmt::Display for RepairHints {
+    /// The summary, then a line per hint
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.summary)?;
+        for hint in &self.hints {
+            write!(f, "\n- {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl RepairHints {
+    /// `{"summary": ..., "hints": [{"line": 2, "code": ..., "message": ...}]}`,
+    /// with `null` for a missing line or code
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"summary\":");
+        write_string(&mut out, &self.summary);
+        out.push_str(",\"hints\":[");
+        for (i, hint) in self.hints.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            match hint.line {
+                Some(line) => out.push_str(&format!("{{\"line\":{},\"code\":", line)),
+                None => out.push_str("{\"line\":null,\"code\":"),
+            }
+            match &hint.code {
+                Some(code) => write_string(&mut out, code),
+                None => out.push_str("null"),
+            }
+            out.push_str(",\"message\":");
+            write_string(&mut out, &hint.message);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Hints for fixing `code`, which failed with `failure`
+pub fn diagnose(code: &str, failure: &Failure) -> RepairHints {
+    let lines: Vec<&str> = code.lines().collect();
+    let hint = |line: usize, message: String| Hint {
+        line: (line > 0).then_some(line),
+        code: lines.get(line.wrapping_sub(1)).map(|code| code.trim().to_string()),
+        message,
+    };
+    match failure {
+        Failure::Parse(errors) => RepairHints {
+            summary: format!("The program does not parse ({} {}).", errors.len(), plural(errors.len(), "error")),
+            hints: errors.iter().map(|e| hint(e.line(), parse_hint(e))).collect(),
+        },
+        Failure::Test { case, output, error } => {
+            let run = describe_run(case);
+            match (error, &case.error) {
+                (Some(error), None) => RepairHints {
+                    summary: format!("The program stopped with an error{}.", run),
+                    hints: runtime_hints(&lines, error).into_iter().map(|(line, message)| hint(line, message)).collect(),
+                },
+                (None, Some(expected)) => RepairHints {
+                    summary: format!("The program finished{}, but should have failed.", run),
+                    hints: vec![hint(0, format!("the run should stop with an error containing \"{}\"", expected))],
+                },
+                (Some(error), Some(expected)) if !error.to_string().contains(expected.as_str()) => RepairHints {
+                    summary: format!("The program failed with the wrong error{}.", run),
+                    hints: vec![hint(0, format!("it failed with \"{}\"; the error should contain \"{}\"", error, expected))],
+                },
+                _ => RepairHints {
+                    summary: format!("The program printed the wrong output{}.", run),
+                    hints: output_hints(case.expected.as_deref().unwrap_or_default(), output)
+                        .into_iter()
+                        .map(|message| hint(0, message))
+                        .collect(),
+                },
+            }
+        }
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        word.to_string()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+/// " with arguments 15 and input \"a\"", or "" for a plain run
+fn describe_run(case: &CorpusProgram) -> String {
+    let mut parts = Vec::new();
+    if !case.args.is_empty() {
+        parts.push(format!("{} {}", plural(case.args.len(), "argument"), case.args.join(" ")));
+    }
+    if !case.stdin.is_empty() {
+        let input: Vec<String> = case.stdin.iter().map(|line| format!("{:?}", line)).collect();
+        parts.push(format!("input {}", input.join(", ")));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" with {}", parts.join(" and "))
+    }
+}
+
+fn parse_hint(error: &ParseError) -> String {
+    match error {
+        ParseError::InvalidInstruction(op, _) => {
+            let ops: Vec<&str> = instruction_set::INSTRUCTION_TABLE.iter().map(|syntax| syntax.op).collect();
+            format!("`{}` is not an instruction; a line starts with one of {}", op, ops.join(" "))
+        }
+        ParseError::MissingArguments(op, _, expected, given) => match instruction_set::find(op) {
+            Some(syntax) if syntax.max_args().is_none() => {
+                format!("`{}` takes at least {} operands (`{}`), got {}", op, expected, syntax.signature(), given)
+            }
+            Some(syntax) => format!("`{}` takes {} operands (`{}`), got {}", op, expected, syntax.signature(), given),
+            None => format!("`{}` takes {} operands, got {}", op, expected, given),
+        },
+        ParseError::InvalidFunctionDef(_) => "a function definition is `# id argc {` with integer id and argc".to_string(),
+        ParseError::UnmatchedBrace(_) => {
+            "every `# id argc {` needs its own `}` line, and function definitions cannot nest".to_string()
+        }
+        ParseError::RequiresVersion(op, _, version) => {
+            format!("`{}` needs language version {}; declare that version or use other instructions", op, version)
+        }
+        ParseError::UnsupportedVersion(_, version, supported) => {
+            format!("the program declares version {}, but only up to {} is supported", version, supported)
+        }
+        ParseError::General(_, message) => message.clone(),
+    }
+}
+
+/// Lines (0 for none) and hints for a runtime error
+fn runtime_hints(lines: &[&str], error: &InterpreterError) -> Vec<(usize, String)> {
+    let hint = match error {
+        InterpreterError::Parse(e) => (e.line(), parse_hint(e)),
+        InterpreterError::Runtime { line, message } => (*line, message.clone()),
+        InterpreterError::UndefinedFunction(id) => {
+            // Anchor the hint at every call of the missing function
+            let calls: Vec<(usize, String)> = lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| {
+                    let tokens = Lexer::tokenize_line(line);
+                    tokens.len() > 2 && tokens[0] == "$" && tokens[2] == id.to_string()
+                })
+                .map(|(i, _)| (i + 1, format!("function {} is called but never defined; define it with `# {} argc {{`", id, id)))
+                .collect();
+            if !calls.is_empty() {
+                return calls;
+            }
+            (0, format!("function {} is called but never defined", id))
+        }
+        InterpreterError::UndefinedVariable { name, line } => {
+            (*line, format!("`{}` is read before it is assigned; assign it first, such as `= {} 0`", name, name))
+        }
+        InterpreterError::ArgumentWrite { name, line } => {
+            (*line, format!("`{}` is read-only; copy it into a local first, such as `= v0 {}`", name, name))
+        }
+        InterpreterError::IndexOutOfBounds { index, length: 0 } => (0, format!("index {} of an empty array", index)),
+        InterpreterError::IndexOutOfBounds { index, length } => {
+            (0, format!("index {} is outside an array of length {}; indices run from 0 to {}", index, length, length - 1))
+        }
+        InterpreterError::TypeError(message) => (0, message.clone()),
+        InterpreterError::DivisionByZero { line } => (*line, "the divisor is 0; check it before dividing".to_string()),
+        InterpreterError::Io(e) => (0, format!("input or output failed: {}", e)),
+        InterpreterError::StackOverflow => (
+            0,
+            "calls nest too deep; every recursive function needs a base case that returns with `^` without calling itself"
+                .to_string(),
+        ),
+        InterpreterError::ModuleNotFound(path) => {
+            (0, format!("module {} does not exist; `_` paths are relative to the importing file", path))
+        }
+        InterpreterError::CircularImport(path) => (0, format!("modules import each other in a cycle through {}", path)),
+        InterpreterError::ArrayTooLarge(size) => {
+            (0, format!("array size {} is over the maximum of {}", size, MAX_ARRAY_SIZE))
+        }
+        InterpreterError::IntegerTooLarge(bits) => (
+            0,
+            format!(
+                "an integer grew to {} bits, over the maximum of {}; check for a value multiplied or raised to a \
+                 power in a loop",
+                bits, MAX_INTEGER_BITS
+            ),
+        ),
+        InterpreterError::StepLimitExceeded(steps) => (
+            0,
+            format!(
+                "the program ran {} instructions without finishing; a loop probably never exits: check that its counter \
+                 changes and its `?` jumps to the label after the loop",
+                steps
+            ),
+        ),
+        InterpreterError::FunctionLimitExceeded { func_id, resource, limit, line } => (
+            *line,
+            format!("function {} may use at most {} {} per run; make it do less work", func_id, limit, resource),
+        ),
+        InterpreterError::Interrupted { line } => (*line, "the run was interrupted here".to_string()),
+        InterpreterError::Raised { value, line } => (
+            *line,
+            format!("`E` raised {} and nothing caught it; wrap the code in `T err label` ... `U` or do not raise", value),
+        ),
+        InterpreterError::PermissionDenied { builtin, capability, line } => {
+            (*line, format!("`{}` needs {}, which this run does not allow; do without it", builtin, capability))
+        }
+        InterpreterError::Yielded { line } => (*line, "`yield` only works in a scheduler; remove it".to_string()),
+    };
+    vec![hint]
+}
+
+/// Hints for output that differs from `expected`
+fn output_hints(expected: &[String], output: &[String]) -> Vec<String> {
+    let line = expected.iter().zip(output).take_while(|(e, o)| e == o).count();
+    let mut hints = vec![match (expected.get(line), output.get(line)) {
+        (Some(e), Some(o)) => format!("output line {} is {:?}, expected {:?}", line + 1, o, e),
+        (Some(e), None) => {
+            format!("the output stops after {} {}; line {} should be {:?}", line, plural(line, "line"), line + 1, e)
+        }
+        (None, Some(o)) => format!("output line {} ({:?}) should not be printed", line + 1, o),
+        (None, None) => "the output differs".to_string(),
+    }];
+    if expected.len() != output.len() {
+        hints.push(format!(
+            "{} {} expected, {} printed",
+            expected.len(),
+            plural(expected.len(), "line"),
+            output.len()
+        ));
+    }
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnose_case(code: &str, case: CorpusProgram) -> RepairHints {
+        let failure = check(code, Path::new("test.sui"), &[case], 1000).unwrap_err();
+        diagnose(code, &failure)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let code = "= v0 1\nx v0\n+ v1 v0\n. v1";
+        let hints = diagnose_case(code, CorpusProgram::default());
+        assert_eq!(hints.summary, "The program does not parse (2 errors).");
+        assert_eq!(hints.hints[0].line, Some(2));
+        assert!(hints.hints[0].message.starts_with("`x` is not an instruction; a line starts with one of _ C = + -"));
+        assert_eq!(hints.hints[1].to_string(), "line 3 (`+ v1 v0`): `+` takes 3 operands (`+ result a b`), got 2");
+        assert!(hints.to_string().starts_with("The program does not parse (2 errors).\n- line 2 (`x v0`): "));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let case = CorpusProgram { args: vec!["3".to_string()], ..CorpusProgram::default() };
+        let hints = diagnose_case("$ v0 2 g101\n. v0", case);
+        assert_eq!(hints.summary, "The program stopped with an error with argument 3.");
+        assert_eq!(
+            hints.hints[0].to_string(),
+            "line 1 (`$ v0 2 g101`): function 2 is called but never defined; define it with `# 2 argc {`"
+        );
+
+        let hints = diagnose_case(": 0\n@ 0", CorpusProgram::default());
+        assert_eq!(hints.hints[0].line, None);
+        assert!(hints.hints[0].message.starts_with("the program ran 1000 instructions without finishing"));
+    }
+
+    #[test]
+    fn test_failed_cases() {
+        let case = CorpusProgram {
+            stdin: vec!["4".to_string()],
+            expected: Some(vec!["4".to_string(), "8".to_string()]),
+            ..CorpusProgram::default()
+        };
+        let hints = diagnose_case(", v0\n. v0\n+ v0 v0 v0\n+ v0 v0 v0\n. v0", case.clone());
+        assert_eq!(hints.summary, "The program printed the wrong output with input \"4\".");
+        let messages: Vec<&str> = hints.hints.iter().map(|h| h.message.as_str()).collect();
+        assert_eq!(messages, ["output line 2 is \"16\", expected \"8\""]);
+        let hints = diagnose_case(", v0\n. v0", case);
+        assert_eq!(hints.hints[0].message, "the output stops after 1 line; line 2 should be \"8\"");
+        assert_eq!(hints.hints[1].message, "2 lines expected, 1 printed");
+
+        let case = CorpusProgram { error: Some("Uncaught".to_string()), ..CorpusProgram::default() };
+        let hints = diagnose_case(". 1", case);
+        assert_eq!(
+            hints.to_json(),
+            r#"{"summary":"The program finished, but should have failed.","hints":[{"line":null,"code":null,"message":"the run should stop with an error containing \"Uncaught\""}]}"#
+        );
+        assert!(check(". 1", Path::new("test.sui"), &[CorpusProgram::default()], 1000).is_ok());
+    }
+}
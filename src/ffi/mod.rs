@@ -0,0 +1,346 @@
+//! C ABI for embedding the interpreter from C, C++, Go and other hosts
+//!
+//! The functions here are exported from the `cdylib` build and declared in
+//! `include/sui.h`. A handle owns an [`Interpreter`] together with the
+//! output, result and error of its last call; strings it returns stay valid
+//! until the next call that runs, transpiles or validates, or `sui_free`. Every function
+//! accepts a null handle and treats it as an error, and a panic inside the
+//! interpreter is reported as a failed run rather than unwinding into C.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::interpreter::Parser;
+use crate::transpiler::{Sui2Js, Sui2Py, Sui2Wasm};
+use crate::Interpreter;
+
+/// Status of a successful call
+pub const SUI_OK: c_int = 0;
+/// Status of a call that failed; `sui_last_error` says why
+pub const SUI_ERROR: c_int = -1;
+
+/// Interpreter handle passed to C as an opaque pointer
+pub struct SuiHandle {
+    interpreter: Interpreter,
+    output: Vec<CString>,
+    result: Option<CString>,
+    error: Option<CString>,
+}
+
+/// C string for Rust text, dropping any NUL bytes
+fn to_c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+impl SuiHandle {
+    fn reset(&mut self) {
+        self.output.clear();
+        self.result = None;
+        self.error = None;
+    }
+
+    fn fail(&mut self, message: &str) -> c_int {
+        self.error = Some(to_c_string(message));
+        SUI_ERROR
+    }
+}
+
+/// C string as `&str`, or `None` for null or invalid UTF-8
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+/// Create an interpreter; free it with `sui_free`
+#[no_mangle]
+pub extern "C" fn sui_new() -> *mut SuiHandle {
+    let handle = SuiHandle { interpreter: Interpreter::new(), output: Vec::new(), result: None, error: None };
+    Box::into_raw(Box::new(handle))
+}
+
+/// Free an interpreter created by `sui_new`; null is ignored
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sui_free(handle: *mut SuiHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Run a program with `argc` arguments, collecting its output
+///
+/// Returns `SUI_OK`, or `SUI_ERROR` with the message in `sui_last_error`.
+/// Output printed before an error is kept.
+///
+/// # Safety
+///
+/// `handle` must come from `sui_new`; `code` and the `argc` strings of
+/// `argv` must be NUL-terminated; `argv` may be null when `argc` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn sui_run(
+    handle: *mut SuiHandle,
+    code: *const c_char,
+    argv: *const *const c_char,
+    argc: usize,
+) -> c_int {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return SUI_ERROR;
+    };
+    handle.reset();
+
+    let Some(code) = (unsafe { c_str(code) }) else {
+        return handle.fail("code is null or not UTF-8");
+    };
+    let mut args = Vec::with_capacity(argc);
+    for i in 0..argc {
+        match unsafe { c_str(if argv.is_null() { ptr::null() } else { *argv.add(i) }) } {
+            Some(arg) => args.push(arg.to_string()),
+            None => return handle.fail(&format!("argument {} is null or not UTF-8", i)),
+        }
+    }
+
+    let interpreter = &mut handle.interpreter;
+    let result = catch_unwind(AssertUnwindSafe(|| interpreter.run(code, &args)));
+    handle.output = handle.interpreter.get_output().iter().map(|line| to_c_string(line)).collect();
+    match result {
+        Ok(Ok(_)) => SUI_OK,
+        Ok(Err(e)) => handle.fail(&e.to_string()),
+        Err(_) => handle.fail("interpreter panicked"),
+    }
+}
+
+/// Transpile a program to `target`: `"python"`, `"javascript"`,
+/// `"typescript"` or `"wat"`
+///
+/// Returns `SUI_OK` with the code in `sui_result`, or `SUI_ERROR` with the
+/// message in `sui_last_error`.
+///
+/// # Safety
+///
+/// `handle` must come from `sui_new`; `target` and `code` must be
+/// NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn sui_transpile(handle: *mut SuiHandle, target: *const c_char, code: *const c_char) -> c_int {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return SUI_ERROR;
+    };
+    handle.reset();
+
+    let (Some(target), Some(code)) = (unsafe { c_str(target) }, unsafe { c_str(code) }) else {
+        return handle.fail("target or code is null or not UTF-8");
+    };
+    let result = catch_unwind(|| match target {
+        "python" => Some(Sui2Py::new().transpile_to_python(code)),
+        "javascript" => Some(Sui2Js::new().transpile_to_js(code)),
+        "typescript" => {
+            let mut transpiler = Sui2Js::new();
+            transpiler.set_typescript(true);
+            Some(transpiler.transpile_to_js(code))
+        }
+        "wat" => Some(Sui2Wasm::new().transpile_to_wat(code)),
+        _ => None,
+    });
+    match result {
+        Ok(Some(Ok(text))) => {
+            handle.result = Some(to_c_string(&text));
+            SUI_OK
+        }
+        Ok(Some(Err(e))) => handle.fail(&e.to_string()),
+        Ok(None) => handle.fail(&format!("unknown target: {}", target)),
+        Err(_) => handle.fail("transpiler panicked"),
+    }
+}
+
+/// Check a program without running it
+///
+/// Returns the number of problems found, whose messages become the output
+/// lines, or `SUI_ERROR` if `code` is unusable.
+///
+/// # Safety
+///
+/// `handle` must come from `sui_new`; `code` must be NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn sui_validate(handle: *mut SuiHandle, code: *const c_char) -> c_int {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return SUI_ERROR;
+    };
+    handle.reset();
+
+    let Some(code) = (unsafe { c_str(code) }) else {
+        return handle.fail("code is null or not UTF-8");
+    };
+    handle.output = Parser::validate(code).iter().map(|e| to_c_string(&e.to_string())).collect();
+    c_int::try_from(handle.output.len()).unwrap_or(c_int::MAX)
+}
+
+/// Number of output lines of the last run
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sui_output_count(handle: *const SuiHandle) -> usize {
+    unsafe { handle.as_ref() }.map_or(0, |h| h.output.len())
+}
+
+/// Output line `index` of the last run, or null when out of range
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sui_get_output_line(handle: *const SuiHandle, index: usize) -> *const c_char {
+    unsafe { handle.as_ref() }
+        .and_then(|h| h.output.get(index))
+        .map_or(ptr::null(), |line| line.as_ptr())
+}
+
+/// Code produced by the last `sui_transpile`, or null
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sui_result(handle: *const SuiHandle) -> *const c_char {
+    unsafe { handle.as_ref() }
+        .and_then(|h| h.result.as_ref())
+        .map_or(ptr::null(), |result| result.as_ptr())
+}
+
+/// Error message of the last call, or null if it succeeded
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sui_last_error(handle: *const SuiHandle) -> *const c_char {
+    unsafe { handle.as_ref() }
+        .and_then(|h| h.error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// Stop runs after `max_steps` instructions; 0 removes the limit
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sui_set_limit(handle: *mut SuiHandle, max_steps: u64) {
+    if let Some(handle) = unsafe { handle.as_mut() } {
+        handle.interpreter.set_max_steps((max_steps > 0).then_some(max_steps));
+    }
+}
+
+/// Maximum call depth before a run fails with a stack overflow
+///
+/// # Safety
+///
+/// `handle` must be null or come from `sui_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sui_set_max_depth(handle: *mut SuiHandle, depth: usize) {
+    if let Some(handle) = unsafe { handle.as_mut() } {
+        handle.interpreter.set_max_stack_depth(depth);
+    }
+}
+
+/// Version of the library, such as `"0.2.0"`; never freed
+#[no_mangle]
+pub extern "C" fn sui_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    fn text(s: *const c_char) -> Option<String> {
+        unsafe { c_str(s) }.map(str::to_string)
+    }
+
+    #[test]
+    fn test_run() {
+        let handle = sui_new();
+        let (four, x) = (c("4"), c("x"));
+        let args = [four.as_ptr(), x.as_ptr()];
+        unsafe {
+            assert_eq!(sui_run(handle, c(". g100\n* v0 g101 2\n. v0\n. g102").as_ptr(), args.as_ptr(), 2), SUI_OK);
+            assert_eq!(sui_output_count(handle), 3);
+            assert_eq!(text(sui_get_output_line(handle, 1)).as_deref(), Some("8"));
+            assert!(sui_get_output_line(handle, 3).is_null());
+            assert!(sui_last_error(handle).is_null());
+
+            // A failed run keeps what it printed
+            assert_eq!(sui_run(handle, c(". 1\nE \"boom\"").as_ptr(), ptr::null(), 0), SUI_ERROR);
+            assert_eq!(sui_output_count(handle), 1);
+            assert!(text(sui_last_error(handle)).unwrap().contains("boom"));
+            sui_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let handle = sui_new();
+        unsafe {
+            sui_set_limit(handle, 100);
+            assert_eq!(sui_run(handle, c(": 0\n@ 0").as_ptr(), ptr::null(), 0), SUI_ERROR);
+            assert!(text(sui_last_error(handle)).unwrap().contains("Step limit exceeded"));
+            sui_set_limit(handle, 0);
+            assert_eq!(sui_run(handle, c(". 1").as_ptr(), ptr::null(), 0), SUI_OK);
+
+            sui_set_max_depth(handle, 10);
+            assert_eq!(sui_run(handle, c("# 0 0 {\n$ v0 0\n}\n$ v0 0").as_ptr(), ptr::null(), 0), SUI_ERROR);
+            assert_eq!(text(sui_last_error(handle)).as_deref(), Some("Stack overflow"));
+            sui_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_transpile_and_validate() {
+        let handle = sui_new();
+        unsafe {
+            assert_eq!(sui_transpile(handle, c("python").as_ptr(), c("= v0 1\n. v0").as_ptr()), SUI_OK);
+            assert!(text(sui_result(handle)).unwrap().contains("print(v0)"));
+            assert_eq!(sui_transpile(handle, c("typescript").as_ptr(), c(". 1").as_ptr()), SUI_OK);
+            assert_eq!(sui_transpile(handle, c("wat").as_ptr(), c(". 1").as_ptr()), SUI_OK);
+            assert!(text(sui_result(handle)).unwrap().contains("(module"));
+            assert_eq!(sui_transpile(handle, c("cobol").as_ptr(), c(". 1").as_ptr()), SUI_ERROR);
+            assert!(sui_result(handle).is_null());
+            assert_eq!(text(sui_last_error(handle)).as_deref(), Some("unknown target: cobol"));
+
+            assert_eq!(sui_validate(handle, c("= v0 1\n+ v1\nX 1").as_ptr()), 2);
+            assert!(text(sui_get_output_line(handle, 1)).unwrap().contains("line 3"));
+            assert_eq!(sui_validate(handle, c(". 1").as_ptr()), 0);
+            sui_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_null_arguments() {
+        unsafe {
+            assert_eq!(sui_run(ptr::null_mut(), c(". 1").as_ptr(), ptr::null(), 0), SUI_ERROR);
+            assert_eq!(sui_output_count(ptr::null()), 0);
+            sui_free(ptr::null_mut());
+
+            let handle = sui_new();
+            assert_eq!(sui_run(handle, ptr::null(), ptr::null(), 0), SUI_ERROR);
+            assert_eq!(sui_run(handle, c(". 1").as_ptr(), ptr::null(), 1), SUI_ERROR);
+            assert_eq!(text(sui_last_error(handle)).as_deref(), Some("argument 0 is null or not UTF-8"));
+            sui_free(handle);
+        }
+        assert_eq!(text(sui_version()).as_deref(), Some(crate::VERSION));
+    }
+}
@@ -0,0 +1,216 @@
+//! Source formatter for Sui
+//!
+//! Rewrites every line into canonical spacing: tokens separated by one
+//! space, trailing comments one space after the code, no trailing
+//! whitespace. Optionally indents function bodies and aligns operands into
+//! columns. Lines that do not parse are only re-indented, and the output
+//! keeps one line per input line, so editors can format a range in place.
+//!
+//! ```rust
+//! use sui_lang::formatter::{format, FormatOptions};
+//!
+//! let code = "=  v0   10 ;start\n+ v1 v0  5\n";
+//! assert_eq!(format(code, &FormatOptions::default()), "= v0 10 ; start\n+ v1 v0 5\n");
+//! ```
+
+use crate::interpreter::{Instruction, Lexer, Parser};
+
+/// Formatting settings
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FormatOptions {
+    /// Pad operands so that consecutive instructions line up in columns
+    pub align_columns: bool,
+    /// Spaces to indent each level of function body (0 keeps bodies flush)
+    pub indent_width: usize,
+}
+
+/// One source line split for formatting
+struct Line {
+    /// Nesting depth of function bodies
+    depth: usize,
+    /// Tokens of a valid instruction, or `None` for anything else
+    tokens: Option<Vec<String>>,
+    /// Trimmed source of lines that are not valid instructions
+    raw: String,
+    /// Trailing comment text after `;`
+    comment: Option<String>,
+}
+
+/// Format a whole program
+pub fn format(code: &str, options: &FormatOptions) -> String {
+    let mut lines = format_lines(code, options);
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Format each line of a program, one output line per `code.lines()` entry
+pub fn format_lines(code: &str, options: &FormatOptions) -> Vec<String> {
+    let lines = split(code);
+    let mut widths = vec![Vec::new(); lines.len()];
+
+    if options.align_columns {
+        // Align runs of consecutive instructions at the same depth
+        let mut start = 0;
+        while start < lines.len() {
+            let Some(depth) = lines[start].tokens.as_ref().map(|_| lines[start].depth) else {
+                start += 1;
+                continue;
+            };
+            let mut end = start;
+            while end < lines.len() && lines[end].tokens.is_some() && lines[end].depth == depth {
+                end += 1;
+            }
+
+            let mut run_widths: Vec<usize> = Vec::new();
+            for tokens in lines[start..end].iter().filter_map(|l| l.tokens.as_ref()) {
+                // The last token of a line is never padded
+                for (col, token) in tokens.iter().enumerate().take(tokens.len().saturating_sub(1)) {
+                    let width = token.chars().count();
+                    if col == run_widths.len() {
+                        run_widths.push(width);
+                    } else {
+                        run_widths[col] = run_widths[col].max(width);
+                    }
+                }
+            }
+            for w in &mut widths[start..end] {
+                w.clone_from(&run_widths);
+            }
+            start = end;
+        }
+    }
+
+    lines
+        .iter()
+        .zip(&widths)
+        .map(|(line, widths)| {
+            let mut out = " ".repeat(line.depth * options.indent_width);
+            match &line.tokens {
+                Some(tokens) => {
+                    for (col, token) in tokens.iter().enumerate() {
+                        if col > 0 {
+                            out.push(' ');
+                        }
+                        out.push_str(token);
+                        if col + 1 < tokens.len() {
+                            let width = widths.get(col).copied().unwrap_or(0);
+                            out.extend(std::iter::repeat(' ').take(width.saturating_sub(token.chars().count())));
+                        }
+                    }
+                }
+                None => out.push_str(&line.raw),
+            }
+            if let Some(comment) = &line.comment {
+                if !out.trim().is_empty() {
+                    out.push(' ');
+                }
+                out.push(';');
+                if !comment.is_empty() {
+                    out.push(' ');
+                    out.push_str(comment);
+                }
+            }
+            if out.trim().is_empty() {
+                out.clear();
+            }
+            out
+        })
+        .collect()
+}
+
+/// Split source into lines with their depth, tokens and comments
+fn split(code: &str) -> Vec<Line> {
+    let mut depth: usize = 0;
+    let mut lines = Vec::new();
+
+    for (i, source) in code.lines().enumerate() {
+        let spans = Lexer::token_spans(source);
+        let code_end = spans.last().map_or(0, |(byte, text)| byte + text.len());
+        let comment = source[code_end..]
+            .find(';')
+            .map(|offset| source[code_end + offset + 1..].trim().to_string());
+
+        let tokens: Vec<String> = spans.iter().map(|(_, text)| text.to_string()).collect();
+        let parsed = Parser::parse_line(&tokens, i + 1);
+
+        // A body is one level deeper than its header and closing brace
+        if matches!(parsed, Ok(Instruction::FuncEnd)) {
+            depth = depth.saturating_sub(1);
+        }
+        let line_depth = depth;
+        if matches!(parsed, Ok(Instruction::FuncDef { .. })) {
+            depth += 1;
+        }
+
+        let valid = !tokens.is_empty() && parsed.is_ok();
+        lines.push(Line {
+            depth: line_depth,
+            raw: if valid {
+                String::new()
+            } else {
+                source[..code_end].trim().to_string()
+            },
+            tokens: valid.then_some(tokens),
+            comment,
+        });
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_spacing() {
+        let code = "  = v0   \"a  b\"\t; note \n\n;comment\n.  v0  \n";
+        assert_eq!(
+            format(code, &FormatOptions::default()),
+            "= v0 \"a  b\" ; note\n\n; comment\n. v0\n"
+        );
+    }
+
+    #[test]
+    fn test_indent_function_bodies() {
+        let code = "# 0 1 {\n; body\n^ a0\n}\n$ v0 0 1\n";
+        let options = FormatOptions {
+            indent_width: 2,
+            ..Default::default()
+        };
+        assert_eq!(format(code, &options), "# 0 1 {\n  ; body\n  ^ a0\n}\n$ v0 0 1\n");
+    }
+
+    #[test]
+    fn test_align_columns() {
+        let code = "= v0 10\n+ v10 v0 5 ; sum\n. v10\n\n= g100 1\n";
+        let options = FormatOptions {
+            align_columns: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format(code, &options),
+            "= v0  10\n+ v10 v0 5 ; sum\n. v10\n\n= g100 1\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_lines_and_idempotence() {
+        let code = "X  1   2\n+ v0\n= v0 1";
+        let options = FormatOptions {
+            align_columns: true,
+            indent_width: 4,
+        };
+        let once = format(code, &options);
+        assert_eq!(once, "X  1   2\n+ v0\n= v0 1\n");
+        assert_eq!(format(&once, &options), once);
+        assert_eq!(format_lines(code, &options).len(), 3);
+    }
+}
@@ -0,0 +1,222 @@
+//! Comparing the interpreter with transpiled programs
+
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+use super::{random_program, GeneratorConfig};
+use crate::interpreter::Interpreter;
+use crate::transpiler::{Sui2Js, Sui2Py, Transpiler};
+
+/// Instructions the interpreter may run per program
+const MAX_STEPS: u64 = 1_000_000;
+
+/// A transpiler and the command that runs its output
+pub struct Backend {
+    transpiler: Box<dyn Transpiler>,
+    command: Vec<String>,
+}
+
+impl Backend {
+    /// Run the output of `transpiler` with `command`, which reads the
+    /// transpiled source from stdin and prints the program's output
+    pub fn new(transpiler: impl Transpiler + 'static, command: &[&str]) -> Self {
+        Backend { transpiler: Box::new(transpiler), command: command.iter().map(|s| s.to_string()).collect() }
+    }
+
+    /// [`Sui2Py`] output run by `python3`
+    pub fn python() -> Self {
+        Self::new(Sui2Py::new(), &["python3", "-"])
+    }
+
+    /// [`Sui2Js`] output run by `node`
+    pub fn javascript() -> Self {
+        Self::new(Sui2Js::new(), &["node", "-"])
+    }
+
+    /// Language the transpiler targets
+    pub fn language(&self) -> &str {
+        self.transpiler.language()
+    }
+
+    /// Transpile and run `code`, returning its output lines
+    pub fn run(&self, code: &str) -> Result<Vec<String>, String> {
+        let source = self.transpiler.transpile(code).map_err(|e| e.to_string())?;
+        let (program, args) = self.command.split_first().ok_or("empty command")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run {}: {}", program, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(source.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim_end().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+    }
+}
+
+/// A program whose transpiled output behaves differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// Seed the program was generated from, if it was
+    pub seed: Option<u64>,
+    /// Language of the backend that differs
+    pub language: String,
+    pub code: String,
+    /// What the interpreter printed, or the error it failed with
+    pub expected: Result<Vec<String>, String>,
+    /// What the transpiled program printed, or why it failed
+    pub actual: Result<Vec<String>, String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            write!(f, "seed {}: ", seed)?;
+        }
+        match (&self.expected, &self.actual) {
+            (Ok(expected), Ok(actual)) => {
+                let line = expected.iter().zip(actual).take_while(|(e, a)| e == a).count();
+                let show = |lines: &[String]| lines.get(line).map_or("(end of output)".to_string(), |l| format!("{:?}", l));
+                write!(
+                    f,
+                    "{} output differs at line {}: interpreter {}, {} {}",
+                    self.language,
+                    line + 1,
+                    show(expected),
+                    self.language,
+                    show(actual)
+                )
+            }
+            (Ok(_), Err(e)) => write!(f, "{} failed: {}", self.language, e),
+            (Err(e), _) => write!(f, "interpreter failed, {} did not: {}", self.language, e),
+        }
+    }
+}
+
+/// Runs programs through the interpreter and each [`Backend`]
+#[derive(Default)]
+pub struct Differential {
+    backends: Vec<Backend>,
+}
+
+impl Differential {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_backend(&mut self, backend: Backend) {
+        self.backends.push(backend);
+    }
+
+    /// The backends whose output for `code` differs from the interpreter's
+    ///
+    /// Failing on both sides counts as agreeing, whatever the messages.
+    pub fn check(&self, code: &str) -> Vec<Mismatch> {
+        let mut interp = Interpreter::new();
+        interp.set_max_steps(Some(MAX_STEPS));
+        let expected = match interp.run(code, &[]) {
+            // Entries holding newlines print as several lines
+            Ok(output) => Ok(output.iter().flat_map(|entry| entry.split('\n')).map(str::to_string).collect()),
+            Err(e) => Err(e.to_string()),
+        };
+        self.backends
+            .iter()
+            .filter_map(|backend| {
+                let actual = backend.run(code);
+                let agree = match (&expected, &actual) {
+                    (Ok(expected), Ok(actual)) => expected == actual,
+                    (Err(_), Err(_)) => true,
+                    _ => false,
+                };
+                (!agree).then(|| Mismatch {
+                    seed: None,
+                    language: backend.language().to_string(),
+                    code: code.to_string(),
+                    expected: expected.clone(),
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// [`check`](Self::check) the programs generated from `seeds`
+    pub fn run(&self, seeds: Range<u64>, config: &GeneratorConfig) -> Vec<Mismatch> {
+        seeds
+            .flat_map(|seed| {
+                self.check(&random_program(seed, config))
+                    .into_iter()
+                    .map(move |mismatch| Mismatch { seed: Some(seed), ..mismatch })
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::transpiler::TranspileError;
+
+    /// "Transpiles" to the output the interpreter prints, with `cat`
+    /// running it, optionally getting the last line wrong
+    struct Echo {
+        wrong: bool,
+    }
+
+    impl Transpiler for Echo {
+        fn transpile(&self, code: &str) -> Result<String, TranspileError> {
+            let mut output = Interpreter::new().run(code, &[]).map_err(|e| TranspileError::Codegen(e.to_string()))?;
+            if self.wrong {
+                output.push("extra".to_string());
+            }
+            Ok(output.iter().map(|line| format!("{}\n", line)).collect())
+        }
+
+        fn extension(&self) -> &str {
+            "txt"
+        }
+
+        fn language(&self) -> &str {
+            "Echo"
+        }
+    }
+
+    #[test]
+    fn test_differential() {
+        let mut differential = Differential::new();
+        differential.add_backend(Backend::new(Echo { wrong: false }, &["cat"]));
+        let config = GeneratorConfig::default();
+        assert_eq!(differential.run(0..5, &config), []);
+
+        differential.add_backend(Backend::new(Echo { wrong: true }, &["cat"]));
+        let mismatches = differential.run(0..3, &config);
+        assert_eq!(mismatches.len(), 3);
+        assert_eq!(mismatches[2].seed, Some(2));
+        assert!(mismatches[2].to_string().starts_with("seed 2: Echo output differs at line "));
+        assert!(mismatches[2].to_string().ends_with("interpreter (end of output), Echo \"extra\""));
+
+        let missing = Backend::new(Echo { wrong: false }, &["no-such-command-here"]);
+        assert!(missing.run(". 1").unwrap_err().starts_with("cannot run no-such-command-here"));
+    }
+
+    #[test]
+    fn test_default_config_agrees() {
+        // The defaults leave out what backends cannot match, so whatever
+        // they find is a transpiler bug; backends not installed are skipped
+        let mut differential = Differential::new();
+        for backend in [Backend::python(), Backend::javascript()] {
+            if Command::new(&backend.command[0]).arg("--version").output().is_ok() {
+                differential.add_backend(backend);
+            }
+        }
+        let mismatches = differential.run(0..40, &GeneratorConfig::default());
+        assert!(mismatches.is_empty(), "{}\n{}", mismatches[0], mismatches[0].code);
+    }
+}
@@ -0,0 +1,381 @@
+//! Random program generation for differential testing
+//!
+//! [`random_program`] builds a valid Sui program from a seed: functions
+//! that only call functions defined before them, counted loops, skipped
+//! blocks, arithmetic, comparisons, arrays and output. Every variable is
+//! assigned before it is read, divisors are nonzero literals and array
+//! indices are in bounds, so programs always terminate without errors.
+//! The same seed and [`GeneratorConfig`] give the same program.
+//!
+//! [`Differential`] runs generated programs through the interpreter and
+//! through transpiled code run by external commands, such as `python3` for
+//! [`Sui2Py`](crate::transpiler::Sui2Py) output, and reports programs whose
+//! output differs:
+//!
+//! ```rust,no_run
+//! use sui_lang::generator::{Backend, Differential, GeneratorConfig};
+//!
+//! let mut differential = Differential::new();
+//! differential.add_backend(Backend::python());
+//! differential.add_backend(Backend::javascript());
+//! for mismatch in differential.run(0..100, &GeneratorConfig::default()) {
+//!     println!("{}", mismatch);
+//! }
+//! ```
+
+mod differential;
+
+pub use differential::{Backend, Differential, Mismatch};
+
+/// Bounds on the programs [`random_program`] generates
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorConfig {
+    /// Most statements in the main code and in each function body,
+    /// counting those in nested blocks
+    pub statements: usize,
+    /// Most functions defined
+    pub functions: usize,
+    /// Most arguments a function takes
+    pub max_args: usize,
+    /// Deepest nesting of loops and skipped blocks
+    pub depth: usize,
+    /// Variables holding data in each scope
+    pub variables: usize,
+    /// Most times a loop runs
+    pub max_iterations: i64,
+    /// Integer literals are between `-max_constant` and `max_constant`
+    pub max_constant: i64,
+    /// Generate `/`; off by default, since JavaScript, having one number
+    /// type, prints a whole float such as `2.0` as `2`
+    pub division: bool,
+    /// Generate `%`
+    pub remainder: bool,
+    /// Generate an array per scope, read and written at constant indices
+    pub arrays: bool,
+    /// Generate output of string literals
+    pub strings: bool,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            statements: 16,
+            functions: 2,
+            max_args: 2,
+            depth: 2,
+            variables: 4,
+            max_iterations: 4,
+            max_constant: 20,
+            division: false,
+            remainder: true,
+            arrays: true,
+            strings: true,
+        }
+    }
+}
+
+/// Size of the generated arrays
+const ARRAY_SIZE: i64 = 4;
+
+/// String literals generated programs print
+const WORDS: &[&str] = &["a", "sui", "hello world", "x=1", ""];
+
+/// A valid program generated from `seed` within the bounds of `config`
+pub fn random_program(seed: u64, config: &GeneratorConfig) -> String {
+    let mut generator = Generator {
+        rng: Rng(seed),
+        config,
+        lines: Vec::new(),
+        arities: Vec::new(),
+        labels: 0,
+        budget: 0,
+        args: 0,
+    };
+    for id in 0..generator.rng.below(config.functions as u64 + 1) as usize {
+        generator.function(id);
+    }
+    generator.scope(generator.arities.len());
+    let mut code = generator.lines.join("\n");
+    code.push('\n');
+    code
+}
+
+/// splitmix64, so programs depend on nothing but the seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`, or 0 if `n` is 0
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next() % n
+        }
+    }
+
+    /// Uniform in `lo..=hi`
+    fn between(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + self.below((hi - lo) as u64 + 1) as i64
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// Statement kinds
+#[derive(Clone, Copy)]
+enum Kind {
+    Assign,
+    Arithmetic,
+    Compare,
+    Output,
+    Skip,
+    Loop,
+    Call,
+    ArrayWrite,
+    ArrayRead,
+    Text,
+}
+
+struct Generator<'a> {
+    rng: Rng,
+    config: &'a GeneratorConfig,
+    lines: Vec<String>,
+    /// Argument counts of the functions defined so far
+    arities: Vec<usize>,
+    /// Next label of the current scope
+    labels: usize,
+    /// Statements the current scope may still generate
+    budget: usize,
+    /// Arguments of the current function
+    args: usize,
+}
+
+impl Generator<'_> {
+    /// Locals holding data: `v0` onward
+    fn data(&self) -> usize {
+        self.config.variables.max(1)
+    }
+
+    /// Local holding the counter of a loop at nesting `depth`
+    fn counter(&self, depth: usize) -> String {
+        format!("v{}", self.data() + depth)
+    }
+
+    /// Local holding loop conditions
+    fn condition(&self) -> String {
+        format!("v{}", self.data() + self.config.depth)
+    }
+
+    /// Local holding the array
+    fn array(&self) -> String {
+        format!("v{}", self.data() + self.config.depth + 1)
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn variable(&mut self) -> String {
+        format!("v{}", self.rng.below(self.data() as u64))
+    }
+
+    fn constant(&mut self) -> i64 {
+        self.rng.between(-self.config.max_constant, self.config.max_constant)
+    }
+
+    /// A data variable, an argument or a literal
+    fn operand(&mut self) -> String {
+        match self.rng.below(5) {
+            0 | 1 => self.variable(),
+            2 if self.args > 0 => format!("a{}", self.rng.below(self.args as u64)),
+            _ => self.constant().to_string(),
+        }
+    }
+
+    fn function(&mut self, id: usize) {
+        self.args = self.rng.below(self.config.max_args as u64 + 1) as usize;
+        self.lines.push(format!("# {} {} {{", id, self.args));
+        self.scope(id);
+        let value = self.operand();
+        self.lines.push(format!("^ {}", value));
+        self.lines.push("}".to_string());
+        self.arities.push(self.args);
+        self.args = 0;
+    }
+
+    /// Initialize the locals of a scope and generate its statements;
+    /// functions below `callable` may be called
+    fn scope(&mut self, callable: usize) {
+        self.labels = 0;
+        self.budget = self.config.statements;
+        for i in 0..self.data() {
+            let value = self.operand();
+            // Reading an unassigned variable is an error
+            let value = if value.starts_with('v') { self.constant().to_string() } else { value };
+            self.lines.push(format!("= v{} {}", i, value));
+        }
+        if self.config.arrays {
+            let array = self.array();
+            self.lines.push(format!("[ {} {}", array, ARRAY_SIZE));
+        }
+        while self.budget > 0 {
+            self.statement(0, callable);
+        }
+    }
+
+    /// Up to `count` statements nested `depth` deep
+    fn block(&mut self, depth: usize, count: usize, callable: usize) {
+        for _ in 0..count {
+            if self.budget == 0 {
+                break;
+            }
+            self.statement(depth, callable);
+        }
+    }
+
+    fn statement(&mut self, depth: usize, callable: usize) {
+        self.budget -= 1;
+        let mut kinds = vec![Kind::Assign, Kind::Arithmetic, Kind::Arithmetic, Kind::Compare, Kind::Output, Kind::Output];
+        if depth < self.config.depth {
+            kinds.extend([Kind::Skip, Kind::Loop]);
+        }
+        if callable > 0 {
+            kinds.push(Kind::Call);
+        }
+        if self.config.arrays {
+            kinds.extend([Kind::ArrayWrite, Kind::ArrayRead]);
+        }
+        if self.config.strings {
+            kinds.push(Kind::Text);
+        }
+
+        let line = match *self.rng.pick(&kinds) {
+            Kind::Assign => format!("= {} {}", self.variable(), self.operand()),
+            Kind::Arithmetic => {
+                let mut ops = vec!["+", "-", "*"];
+                if self.config.division {
+                    ops.push("/");
+                }
+                if self.config.remainder {
+                    ops.push("%");
+                }
+                let op = *self.rng.pick(&ops);
+                let (result, a) = (self.variable(), self.operand());
+                let b = if op == "/" || op == "%" {
+                    let divisor = self.rng.between(1, self.config.max_constant.max(1));
+                    let sign = if self.rng.below(2) == 0 { 1 } else { -1 };
+                    (sign * divisor).to_string()
+                } else {
+                    self.operand()
+                };
+                format!("{} {} {} {}", op, result, a, b)
+            }
+            Kind::Compare => {
+                let op = *self.rng.pick(&["<", ">", "<=", ">=", "~", "&", "|", "!"]);
+                let (result, a) = (self.variable(), self.operand());
+                if op == "!" {
+                    format!("! {} {}", result, a)
+                } else {
+                    format!("{} {} {} {}", op, result, a, self.operand())
+                }
+            }
+            Kind::Output => format!(". {}", self.operand()),
+            Kind::Skip => {
+                // The block runs when the condition is false
+                let (condition, end) = (self.variable(), self.label());
+                self.lines.push(format!("? {} {}", condition, end));
+                let count = self.rng.between(1, 3) as usize;
+                self.block(depth + 1, count, callable);
+                format!(": {}", end)
+            }
+            Kind::Loop => {
+                let (counter, condition) = (self.counter(depth), self.condition());
+                let (top, end) = (self.label(), self.label());
+                let iterations = self.rng.between(0, self.config.max_iterations);
+                self.lines.push(format!("= {} 0", counter));
+                self.lines.push(format!(": {}", top));
+                self.lines.push(format!(">= {} {} {}", condition, counter, iterations));
+                self.lines.push(format!("? {} {}", condition, end));
+                let count = self.rng.between(1, 3) as usize;
+                self.block(depth + 1, count, callable);
+                self.lines.push(format!("+ {} {} 1", counter, counter));
+                self.lines.push(format!("@ {}", top));
+                format!(": {}", end)
+            }
+            Kind::Call => {
+                let id = self.rng.below(callable as u64) as usize;
+                let mut call = format!("$ {} {}", self.variable(), id);
+                for _ in 0..self.arities[id] {
+                    call.push(' ');
+                    call.push_str(&self.operand());
+                }
+                call
+            }
+            Kind::ArrayWrite => {
+                let index = self.rng.below(ARRAY_SIZE as u64);
+                format!("{{ {} {} {}", self.array(), index, self.operand())
+            }
+            Kind::ArrayRead => {
+                let index = self.rng.below(ARRAY_SIZE as u64);
+                format!("] {} {} {}", self.variable(), self.array(), index)
+            }
+            Kind::Text => format!(". \"{}\"", self.rng.pick(WORDS)),
+        };
+        self.lines.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, Parser};
+
+    #[test]
+    fn test_random_program() {
+        let config = GeneratorConfig::default();
+        assert_eq!(random_program(7, &config), random_program(7, &config));
+        assert_ne!(random_program(7, &config), random_program(8, &config));
+
+        for seed in 0..200 {
+            let code = random_program(seed, &config);
+            assert!(Parser::parse(&code).is_ok(), "seed {}:\n{}", seed, code);
+            let mut interp = Interpreter::new();
+            interp.set_max_steps(Some(1_000_000));
+            if let Err(e) = interp.run(&code, &[]) {
+                panic!("seed {}: {}\n{}", seed, e, code);
+            }
+        }
+    }
+
+    #[test]
+    fn test_config_bounds() {
+        let config = GeneratorConfig {
+            statements: 3,
+            functions: 0,
+            depth: 0,
+            remainder: false,
+            arrays: false,
+            strings: false,
+            ..GeneratorConfig::default()
+        };
+        for seed in 0..50 {
+            let code = random_program(seed, &config);
+            // Initializations, then the statements
+            assert_eq!(code.lines().count(), config.variables + 3);
+            for op in ["#", "@", "?", "/", "%", "[", "\""] {
+                assert!(!code.contains(op), "seed {}:\n{}", seed, code);
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Machine-readable grammars for constrained generation
+//!
+//! Exports the syntax of a Sui program as a grammar that constrained-decoding
+//! tools can enforce while a model generates code, such as llama.cpp
+//! grammars (GBNF), Lark, or any tool reading ISO EBNF. The instruction rules
+//! are derived from the [`INSTRUCTION_TABLE`], so the grammar always
+//! matches the instructions the parser accepts.
+//!
+//! The grammars describe the canonical form of each line: operands separated
+//! by a single space, one instruction or comment per line.
+//!
+//! ```rust
+//! use sui_lang::grammar::{export, Format};
+//!
+//! let gbnf = export(Format::Gbnf);
+//! assert!(gbnf.contains("assign ::= \"=\" \" \" variable \" \" value"));
+//! ```
+
+use std::str::FromStr;
+
+use crate::interpreter::{InstructionSyntax, OperandKind, INSTRUCTION_TABLE};
+
+/// Grammar notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// llama.cpp GBNF
+    Gbnf,
+    /// ISO/IEC 14977 EBNF
+    Ebnf,
+    /// Lark grammar (Earley parser)
+    Lark,
+}
+
+impl Format {
+    /// Conventional file extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Gbnf => "gbnf",
+            Format::Ebnf => "ebnf",
+            Format::Lark => "lark",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    /// Parse `gbnf`, `ebnf` or `lark`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gbnf" => Ok(Format::Gbnf),
+            "ebnf" => Ok(Format::Ebnf),
+            "lark" => Ok(Format::Lark),
+            _ => Err(format!("unknown grammar format '{}' (expected gbnf, ebnf or lark)", s)),
+        }
+    }
+}
+
+/// Grammar expression, rendered per format
+enum Expr {
+    /// Literal text
+    Lit(&'static str),
+    /// Reference to another rule
+    Rule(&'static str),
+    /// Expressions in order
+    Seq(Vec<Expr>),
+    /// Any one of the expressions
+    Alt(Vec<Expr>),
+    /// Zero or more repetitions
+    Star(Box<Expr>),
+    /// Zero or one occurrence
+    Opt(Box<Expr>),
+}
+
+/// Export the grammar of Sui source code
+pub fn export(format: Format) -> String {
+    let mut rules: Vec<(&str, Expr)> = vec![
+        ("root", Expr::Star(Box::new(Expr::Rule("line")))),
+        (
+            "line",
+            Expr::Seq(vec![
+                Expr::Opt(Box::new(Expr::Alt(vec![
+                    Expr::Rule("instruction"),
+                    Expr::Rule("comment"),
+                ]))),
+                Expr::Rule("newline"),
+            ]),
+        ),
+        (
+            "instruction",
+            Expr::Alt(INSTRUCTION_TABLE.iter().map(|s| Expr::Rule(s.name)).collect()),
+        ),
+    ];
+    rules.extend(INSTRUCTION_TABLE.iter().map(|s| (s.name, instruction_rule(s))));
+    rules.push((
+        "value",
+        Expr::Alt(vec![
+            Expr::Rule("variable"),
+            Expr::Rule("constant"),
+            Expr::Rule("literal"),
+        ]),
+    ));
+    rules.push((
+        "literal",
+        Expr::Alt(vec![Expr::Rule("number"), Expr::Rule("string")]),
+    ));
+
+    let mut out = String::new();
+    out.push_str(&comment(format, "Sui grammar (one instruction per line)"));
+    out.push('\n');
+    for (name, expr) in &rules {
+        out.push_str(&render_rule(format, name, expr));
+        out.push('\n');
+    }
+    out.push('\n');
+    out.push_str(terminals(format));
+    out
+}
+
+/// Rule for one instruction line built from its syntax
+fn instruction_rule(syntax: &InstructionSyntax) -> Expr {
+    let mut items = vec![Expr::Lit(syntax.op)];
+    for kind in syntax.operands {
+        let operand = match kind {
+            OperandKind::Variable => Expr::Rule("variable"),
+            OperandKind::Value => Expr::Rule("value"),
+            OperandKind::Integer => Expr::Rule("integer"),
+            OperandKind::String => Expr::Rule("string"),
+            OperandKind::Literal => Expr::Rule("literal"),
+            OperandKind::OpenBrace => Expr::Lit("{"),
+            OperandKind::Values | OperandKind::Labels => {
+                let rule = if *kind == OperandKind::Values { "value" } else { "integer" };
+                items.push(Expr::Star(Box::new(Expr::Seq(vec![
+                    Expr::Lit(" "),
+                    Expr::Rule(rule),
+                ]))));
+                continue;
+            }
+        };
+        items.push(Expr::Lit(" "));
+        items.push(operand);
+    }
+    Expr::Seq(items)
+}
+
+/// Rule name in the notation of the format
+fn rule_name(format: Format, name: &str) -> String {
+    match format {
+        // GBNF names may only contain letters, digits and dashes
+        Format::Gbnf => name.replace('_', "-"),
+        // Lark reserves `start` for the entry rule
+        Format::Lark if name == "root" => "start".to_string(),
+        // Terminals are upper case in Lark
+        Format::Lark if is_terminal(name) => name.to_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+/// Rules defined per format in [`terminals`]
+fn is_terminal(name: &str) -> bool {
+    matches!(
+        name,
+        "variable" | "constant" | "integer" | "number" | "string" | "comment" | "newline"
+    )
+}
+
+fn comment(format: Format, text: &str) -> String {
+    match format {
+        Format::Gbnf => format!("# {}\n", text),
+        Format::Ebnf => format!("(* {} *)\n", text),
+        Format::Lark => format!("// {}\n", text),
+    }
+}
+
+fn render_rule(format: Format, name: &str, expr: &Expr) -> String {
+    let body = render(format, expr, false);
+    match format {
+        Format::Gbnf => format!("{} ::= {}", rule_name(format, name), body),
+        Format::Ebnf => format!("{} = {} ;", rule_name(format, name), body),
+        Format::Lark => format!("{}: {}", rule_name(format, name), body),
+    }
+}
+
+/// Render an expression; `nested` adds grouping where precedence needs it
+fn render(format: Format, expr: &Expr, nested: bool) -> String {
+    match expr {
+        Expr::Lit(text) => literal(format, text),
+        Expr::Rule(name) => rule_name(format, name),
+        Expr::Seq(items) => {
+            let sep = if format == Format::Ebnf { ", " } else { " " };
+            let parts: Vec<String> = items.iter().map(|e| render(format, e, true)).collect();
+            group(parts.join(sep), nested && items.len() > 1)
+        }
+        Expr::Alt(items) => {
+            let parts: Vec<String> = items.iter().map(|e| render(format, e, false)).collect();
+            group(parts.join(" | "), nested && items.len() > 1)
+        }
+        Expr::Star(inner) => match format {
+            Format::Ebnf => format!("{{ {} }}", render(format, inner, false)),
+            _ => format!("{}*", group(render(format, inner, false), needs_group(inner))),
+        },
+        Expr::Opt(inner) => match format {
+            Format::Ebnf => format!("[ {} ]", render(format, inner, false)),
+            _ => format!("{}?", group(render(format, inner, false), needs_group(inner))),
+        },
+    }
+}
+
+fn needs_group(expr: &Expr) -> bool {
+    matches!(expr, Expr::Seq(items) | Expr::Alt(items) if items.len() > 1)
+}
+
+fn group(text: String, wrap: bool) -> String {
+    if wrap {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+/// Quote literal text for the format
+fn literal(format: Format, text: &str) -> String {
+    match format {
+        // ISO EBNF has no escapes; pick the quote the text does not contain
+        Format::Ebnf if text.contains('"') => format!("'{}'", text),
+        Format::Ebnf => format!("\"{}\"", text),
+        _ => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+/// Lexical rules, written out per format
+fn terminals(format: Format) -> &'static str {
+    match format {
+        Format::Gbnf => concat!(
+            "variable ::= [vga] [0-9]+\n",
+            "constant ::= \"c\" [0-9]+\n",
+            "integer ::= \"-\"? [0-9]+\n",
+            "number ::= integer (\".\" [0-9]+)?\n",
+            "string ::= \"\\\"\" ([^\"\\\\\\n] | \"\\\\\" [^\\n])* \"\\\"\"\n",
+            "comment ::= \";\" [^\\n]*\n",
+            "newline ::= \"\\n\"\n",
+        ),
+        Format::Ebnf => concat!(
+            "variable = ( \"v\" | \"g\" | \"a\" ), digit, { digit } ;\n",
+            "constant = \"c\", digit, { digit } ;\n",
+            "integer = [ \"-\" ], digit, { digit } ;\n",
+            "number = integer, [ \".\", digit, { digit } ] ;\n",
+            "string = '\"', { string_char | \"\\\", escaped_char }, '\"' ;\n",
+            "string_char = ? any character except '\"', '\\' and newline ? ;\n",
+            "escaped_char = ? any character except newline ? ;\n",
+            "comment = \";\", { ? any character except newline ? } ;\n",
+            "newline = ? line feed ? ;\n",
+            "digit = \"0\" | \"1\" | \"2\" | \"3\" | \"4\" | \"5\" | \"6\" | \"7\" | \"8\" | \"9\" ;\n",
+        ),
+        Format::Lark => concat!(
+            "VARIABLE: /[vga][0-9]+/\n",
+            "CONSTANT: /c[0-9]+/\n",
+            "INTEGER: /-?[0-9]+/\n",
+            "NUMBER: /-?[0-9]+(\\.[0-9]+)?/\n",
+            "STRING: /\"([^\"\\\\\\n]|\\\\.)*\"/\n",
+            "COMMENT: /;[^\\n]*/\n",
+            "NEWLINE: /\\n/\n",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_instruction_has_a_rule() {
+        for format in [Format::Gbnf, Format::Ebnf, Format::Lark] {
+            let grammar = export(format);
+            for syntax in INSTRUCTION_TABLE {
+                let name = rule_name(format, syntax.name);
+                assert!(
+                    grammar.lines().any(|line| line.starts_with(&format!("{} ", name))
+                        || line.starts_with(&format!("{}:", name))),
+                    "{:?} grammar has no rule for {}",
+                    format,
+                    syntax.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_gbnf_rules() {
+        let gbnf = export(Format::Gbnf);
+        assert!(gbnf.contains("root ::= line*"));
+        assert!(gbnf.contains("line ::= (instruction | comment)? newline"));
+        assert!(gbnf.contains("cond-jump ::= \"?\" \" \" value \" \" integer"));
+        assert!(gbnf.contains("call ::= \"$\" \" \" variable \" \" integer (\" \" value)*"));
+        assert!(gbnf.contains("func-def ::= \"#\" \" \" integer \" \" integer \" \" \"{\""));
+        for line in gbnf.lines().filter(|l| l.contains(" ::= ")) {
+            assert!(!line.split(" ::= ").next().unwrap().contains('_'), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_ebnf_and_lark_rules() {
+        let ebnf = export(Format::Ebnf);
+        assert!(ebnf.contains("root = { line } ;"));
+        assert!(ebnf.contains("add = \"+\", \" \", variable, \" \", value, \" \", value ;"));
+
+        let lark = export(Format::Lark);
+        assert!(lark.contains("start: line*"));
+        assert!(lark.contains("output: \".\" \" \" value"));
+        assert!(lark.contains("value: VARIABLE | CONSTANT | literal"));
+        assert!(lark.contains("literal: NUMBER | STRING"));
+        assert!(lark.contains("const: \"C\" \" \" INTEGER \" \" literal"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Quick fixes for diagnostics
+
+use super::{column, Document, Edit, LineCol};
+use crate::analysis::LintKind;
+use crate::interpreter::{Lexer, OperandKind, ParseError, Parser};
+use std::ops::Range;
+
+/// Fix offered for a diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickFix {
+    /// Description shown in the editor
+    pub title: String,
+    /// Line (0-based) of the diagnostic the fix resolves
+    pub line: usize,
+    /// Changes making up the fix
+    pub edits: Vec<Edit>,
+}
+
+/// Fixes for the parse errors and lint warnings on the lines in `lines`
+/// (0-based)
+pub fn quick_fixes(doc: &Document, lines: Range<usize>) -> Vec<QuickFix> {
+    let mut fixes = Vec::new();
+
+    for error in doc.errors() {
+        let line = error.line().saturating_sub(1);
+        if !lines.contains(&line) {
+            continue;
+        }
+        if let ParseError::MissingArguments(op, _, expected, got) = &error {
+            if let Some(fix) = missing_operands(doc, line, op, *got..*expected) {
+                fixes.push(fix);
+            }
+        }
+    }
+
+    for lint in doc.lints() {
+        let line = lint.line - 1;
+        if !lines.contains(&line) {
+            continue;
+        }
+        let source = doc.line(line).unwrap_or("");
+        let end_of_line = at(line, column(source, source.len()));
+        let fix = match lint.kind {
+            // Jumping to the next line keeps today's fall-through behavior
+            LintKind::UndefinedLabel(label) => {
+                let indent = &source[..source.len() - source.trim_start().len()];
+                QuickFix {
+                    title: format!("Insert missing label `: {}`", label),
+                    line,
+                    edits: vec![Edit {
+                        start: end_of_line,
+                        end: end_of_line,
+                        text: format!("\n{}: {}", indent, label),
+                    }],
+                }
+            }
+            LintKind::PythonFfi => {
+                let Some(&(byte, _)) = Lexer::token_spans(source).first() else {
+                    continue;
+                };
+                let start = column(source, byte);
+                QuickFix {
+                    title: "Convert `P` to `R`".to_string(),
+                    line,
+                    edits: vec![Edit {
+                        start: at(line, start),
+                        end: at(line, start + 1),
+                        text: "R".to_string(),
+                    }],
+                }
+            }
+            LintKind::Unreachable => QuickFix {
+                title: "Remove unreachable instruction".to_string(),
+                line,
+                edits: vec![Edit {
+                    start: at(line, 0),
+                    end: at(line + 1, 0),
+                    text: String::new(),
+                }],
+            },
+            // The variable always reads as 0, so assigning it first keeps behavior
+            LintKind::UndefinedVariable(name) if !name.starts_with('a') => {
+                let indent = &source[..source.len() - source.trim_start().len()];
+                QuickFix {
+                    title: format!("Initialize `{}` to 0", name),
+                    line,
+                    edits: vec![Edit {
+                        start: at(line, 0),
+                        end: at(line, 0),
+                        text: format!("{}= {} 0\n", indent, name),
+                    }],
+                }
+            }
+            LintKind::UndefinedFunction(_)
+            | LintKind::UndefinedVariable(_)
+            | LintKind::TypeMismatch(_)
+            | LintKind::ArgumentWrite(_) => continue,
+        };
+        fixes.push(fix);
+    }
+
+    fixes
+}
+
+/// Append placeholders for the operands at positions `missing`
+fn missing_operands(doc: &Document, line: usize, op: &str, missing: Range<usize>) -> Option<QuickFix> {
+    let syntax = Parser::syntax(op)?;
+    let placeholders: Vec<&str> = syntax.operands[missing]
+        .iter()
+        .map(|kind| match kind {
+            OperandKind::Variable => "v0",
+            OperandKind::String => "\"\"",
+            OperandKind::OpenBrace => "{",
+            OperandKind::Value
+            | OperandKind::Integer
+            | OperandKind::Values
+            | OperandKind::Labels
+            | OperandKind::Literal => "0",
+        })
+        .collect();
+
+    let source = doc.line(line)?;
+    let code_end = Lexer::token_spans(source)
+        .last()
+        .map_or(0, |(byte, text)| byte + text.len());
+    let end = at(line, column(source, code_end));
+    Some(QuickFix {
+        title: if placeholders.len() == 1 {
+            "Add missing operand placeholder".to_string()
+        } else {
+            "Add missing operand placeholders".to_string()
+        },
+        line,
+        edits: vec![Edit {
+            start: end,
+            end,
+            text: format!(" {}", placeholders.join(" ")),
+        }],
+    })
+}
+
+fn at(line: usize, character: usize) -> LineCol {
+    LineCol { line, character }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply the only fix offered for `code`
+    fn fix(code: &str) -> (String, String) {
+        let mut doc = Document::new(code);
+        let fixes = quick_fixes(&doc, 0..usize::MAX);
+        assert_eq!(fixes.len(), 1, "{:?}", fixes);
+        doc.apply(&fixes[0].edits);
+        (fixes[0].title.clone(), doc.text())
+    }
+
+    #[test]
+    fn test_missing_operands() {
+        assert_eq!(
+            fix("+ v0 ; sum"),
+            ("Add missing operand placeholders".to_string(), "+ v0 0 0 ; sum".to_string())
+        );
+        assert_eq!(fix("$").1, "$ v0 0");
+    }
+
+    #[test]
+    fn test_lint_fixes() {
+        assert_eq!(fix("  @ 3\n: 1").1, "  @ 3\n  : 3\n: 1");
+        assert_eq!(fix("P v0 \"len\" \"ab\"").1, "R v0 \"len\" \"ab\"");
+        assert_eq!(fix("^ 0\n. 1\n: 1\n@ 1").1, "^ 0\n: 1\n@ 1");
+        assert_eq!(fix("# 0 0 {\n  ^ v2\n}").1, "# 0 0 {\n  = v2 0\n  ^ v2\n}");
+    }
+
+    #[test]
+    fn test_range_filter() {
+        let doc = Document::new("@ 1\n. 1\nP v0 \"abs\" 1");
+        assert_eq!(quick_fixes(&doc, 0..usize::MAX).len(), 4);
+        assert_eq!(quick_fixes(&doc, 2..3).len(), 2);
+    }
+}
@@ -0,0 +1,102 @@
+//! Parse errors and lint warnings with positions
+
+use super::column;
+use crate::analysis::lint;
+use crate::interpreter::json::write_string;
+use crate::interpreter::Parser;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// The program does not parse
+    Error,
+    /// The program parses but likely has a mistake
+    Warning,
+}
+
+/// Problem on a line of source, positioned for an editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Column where the line's first token starts (1-based, in UTF-16
+    /// code units like editor positions)
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(lines: &[&str], line: usize, severity: Severity, message: String) -> Self {
+        let text = lines.get(line.saturating_sub(1)).copied().unwrap_or("");
+        let indent = text.len() - text.trim_start().len();
+        Diagnostic { line, column: column(text, indent) + 1, severity, message }
+    }
+
+    /// `{line, column, severity, message}` as compact JSON, the same as the
+    /// `serde` serialization but available without it
+    pub fn to_json(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut out = format!("{{\"line\":{},\"column\":{},\"severity\":\"{}\",\"message\":", self.line, self.column, severity);
+        write_string(&mut out, &self.message);
+        out.push('}');
+        out
+    }
+}
+
+/// Parse errors of `code`, in line order
+pub fn parse_diagnostics(code: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = code.lines().collect();
+    Parser::validate(code)
+        .into_iter()
+        .map(|error| Diagnostic::new(&lines, error.line(), Severity::Error, error.to_string()))
+        .collect()
+}
+
+/// Lint warnings of `code`, in line order
+pub fn lint_diagnostics(code: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = code.lines().collect();
+    lint(code)
+        .into_iter()
+        .map(|lint| Diagnostic::new(&lines, lint.line, Severity::Warning, lint.kind.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let code = "= v0 1\n  + v1 v0\n# 0 1 {\n\t. \"é\"\n  X 1\n}\n@ 9";
+        let errors = parse_diagnostics(code);
+        assert_eq!(errors.len(), 2);
+        assert_eq!((errors[0].line, errors[0].column, errors[0].severity), (2, 3, Severity::Error));
+        assert_eq!(errors[0].message, "Missing arguments for '+' at line 2: expected 3, got 2");
+        assert_eq!((errors[1].line, errors[1].column), (5, 3));
+
+        let warnings = lint_diagnostics(code);
+        let jump = warnings.iter().find(|w| w.line == 7).unwrap();
+        assert_eq!((jump.column, jump.severity), (1, Severity::Warning));
+        assert!(parse_diagnostics(". 1").is_empty());
+        assert_eq!(
+            parse_diagnostics("X")[0].to_json(),
+            r#"{"line":1,"column":1,"severity":"error","message":"Invalid instruction 'X' at line 1"}"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let json = serde_json::to_string(&parse_diagnostics("X")).unwrap();
+        assert_eq!(json, r#"[{"line":1,"column":1,"severity":"error","message":"Invalid instruction 'X' at line 1"}]"#);
+        let diagnostic = &lint_diagnostics("@ 1\n. 2")[0];
+        assert_eq!(serde_json::to_string(diagnostic).unwrap(), diagnostic.to_json());
+    }
+}
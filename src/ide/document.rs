@@ -0,0 +1,282 @@
+//! Incrementally analyzed source documents
+
+use crate::analysis::{lint_lines, Lint};
+use crate::interpreter::{Instruction, Lexer, ParseError, Parser};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Position in a document, counted the way the LSP counts it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct LineCol {
+    /// Line (0-based)
+    pub line: usize,
+    /// Column in UTF-16 code units (0-based)
+    pub character: usize,
+}
+
+/// Replacement of a range of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// Start of the range
+    pub start: LineCol,
+    /// End of the range (exclusive)
+    pub end: LineCol,
+    /// Replacement text
+    pub text: String,
+}
+
+/// Source text with a parsed instruction per line
+///
+/// Sui lines are independent, so an edit only re-parses the lines it
+/// touches. Parsed lines are also cached by content hash, which makes undo,
+/// re-typing and moving lines around free. Function blocks are re-matched
+/// after every change from the stored instructions, without parsing again.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    lines: Vec<String>,
+    /// One entry per line, as from [`Parser::parse_recovering`]
+    instructions: Vec<Instruction>,
+    /// Parsed lines by hash of their trimmed text
+    cache: HashMap<u64, Instruction>,
+    /// Unterminated function definitions
+    block_errors: Vec<ParseError>,
+    /// Lines parsed rather than served from the cache
+    parse_count: usize,
+}
+
+impl Document {
+    /// Parse a whole document
+    pub fn new(text: &str) -> Self {
+        let mut doc = Self::default();
+        doc.replace(text);
+        doc
+    }
+
+    /// Replace the whole text (full document sync)
+    pub fn replace(&mut self, text: &str) {
+        self.lines = text.split('\n').map(String::from).collect();
+        self.instructions = Vec::with_capacity(self.lines.len());
+        for i in 0..self.lines.len() {
+            let instr = self.parse(i);
+            self.instructions.push(instr);
+        }
+        self.match_blocks();
+    }
+
+    /// Replace the text between `start` and `end` (incremental sync)
+    ///
+    /// Positions past the end of a line or of the document are clamped.
+    pub fn edit(&mut self, start: LineCol, end: LineCol, text: &str) {
+        let (start, end) = if start <= end { (start, end) } else { (end, start) };
+        let (start_line, start_byte) = self.offset(start);
+        let (end_line, end_byte) = self.offset(end);
+
+        let spliced = format!(
+            "{}{}{}",
+            &self.lines[start_line][..start_byte],
+            text,
+            &self.lines[end_line][end_byte..]
+        );
+        let new_lines: Vec<String> = spliced.split('\n').map(String::from).collect();
+        let added = new_lines.len();
+        let removed = end_line - start_line + 1;
+
+        self.lines.splice(start_line..=end_line, new_lines);
+        let parsed: Vec<Instruction> = (start_line..start_line + added).map(|i| self.parse(i)).collect();
+        self.instructions.splice(start_line..=end_line, parsed);
+
+        // Errors after the edit moved with their lines
+        if added != removed {
+            for (i, instr) in self.instructions.iter_mut().enumerate().skip(start_line + added) {
+                if let Instruction::Invalid { error, .. } = instr {
+                    error.set_line(i + 1);
+                }
+            }
+        }
+
+        self.match_blocks();
+    }
+
+    /// Apply non-overlapping edits given in document order
+    pub fn apply(&mut self, edits: &[Edit]) {
+        // Later edits first, so earlier positions stay valid
+        for edit in edits.iter().rev() {
+            self.edit(edit.start, edit.end, &edit.text);
+        }
+    }
+
+    /// Current text
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Source lines
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Source of one line (0-based)
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.lines.get(line).map(String::as_str)
+    }
+
+    /// Instruction of each line, in line order
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Parse errors of every line plus unterminated functions, by line
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors: Vec<ParseError> = self
+            .instructions
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Invalid { error, .. } => Some(error.clone()),
+                _ => None,
+            })
+            .chain(self.block_errors.iter().cloned())
+            .collect();
+        errors.sort_by_key(ParseError::line);
+        errors
+    }
+
+    /// Lint warnings for the current text
+    pub fn lints(&self) -> Vec<Lint> {
+        lint_lines(&self.lines, &self.instructions)
+    }
+
+    /// Number of lines parsed so far rather than served from the cache
+    pub fn parse_count(&self) -> usize {
+        self.parse_count
+    }
+
+    /// Parse line `i`, reusing the result for identical text
+    fn parse(&mut self, i: usize) -> Instruction {
+        let raw = self.lines[i].trim();
+        let mut hasher = DefaultHasher::new();
+        raw.hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut instr = match self.cache.get(&key) {
+            Some(instr) => instr.clone(),
+            None => {
+                let instr = match Parser::parse_line(&Lexer::tokenize_line(raw), i + 1) {
+                    Ok(instr) => instr,
+                    Err(error) => Instruction::Invalid {
+                        raw: raw.to_string(),
+                        error,
+                    },
+                };
+                // Keep the cache proportional to the document
+                if self.cache.len() > 2 * self.lines.len() + 1024 {
+                    self.cache.clear();
+                }
+                self.cache.insert(key, instr.clone());
+                self.parse_count += 1;
+                instr
+            }
+        };
+
+        if let Instruction::Invalid { error, .. } = &mut instr {
+            error.set_line(i + 1);
+        }
+        instr
+    }
+
+    /// Check that every function definition is closed
+    fn match_blocks(&mut self) {
+        let mut open = Vec::new();
+        for (i, instr) in self.instructions.iter().enumerate() {
+            match instr {
+                Instruction::FuncDef { .. } => open.push(i),
+                Instruction::FuncEnd => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+        self.block_errors = open.into_iter().map(|i| ParseError::UnmatchedBrace(i + 1)).collect();
+    }
+
+    /// Line index and byte offset of a position, clamped to the text
+    pub(super) fn offset(&self, pos: LineCol) -> (usize, usize) {
+        let Some(line) = self.lines.get(pos.line) else {
+            let last = self.lines.len() - 1;
+            return (last, self.lines[last].len());
+        };
+
+        let mut units = 0;
+        for (byte, c) in line.char_indices() {
+            if units >= pos.character {
+                return (pos.line, byte);
+            }
+            units += c.len_utf16();
+        }
+        (pos.line, line.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: usize, character: usize) -> LineCol {
+        LineCol { line, character }
+    }
+
+    #[test]
+    fn test_matches_recovering_parser() {
+        let code = "= v0 1\n\n+ v0\n# 0 1 {\n^ a0\n}\nX";
+        let doc = Document::new(code);
+        let (instrs, errors) = Parser::parse_recovering(code);
+        assert_eq!(doc.instructions(), &instrs[..]);
+        assert_eq!(doc.errors(), errors);
+        assert_eq!(doc.text(), code);
+    }
+
+    #[test]
+    fn test_edit_reparses_only_changed_lines() {
+        let mut doc = Document::new("= v0 1\n+ v1 v0 2\n. v1");
+        let before = doc.parse_count();
+        doc.edit(at(1, 0), at(1, 1), "-");
+        assert_eq!(doc.parse_count(), before + 1);
+        assert_eq!(doc.line(1), Some("- v1 v0 2"));
+        assert!(matches!(doc.instructions()[1], Instruction::Sub { .. }));
+
+        // Undoing is served from the cache
+        doc.edit(at(1, 0), at(1, 1), "+");
+        assert_eq!(doc.parse_count(), before + 1);
+        assert!(matches!(doc.instructions()[1], Instruction::Add { .. }));
+    }
+
+    #[test]
+    fn test_inserted_lines_move_errors() {
+        let mut doc = Document::new("= v0 1\n. ");
+        assert_eq!(doc.errors()[0].line(), 2);
+        doc.edit(at(0, 0), at(0, 0), "; a\n; b\n");
+        assert_eq!(doc.lines().len(), 4);
+        assert_eq!(doc.errors()[0].line(), 4);
+        doc.edit(at(0, 0), at(2, 0), "");
+        assert_eq!(doc.text(), "= v0 1\n. ");
+        assert_eq!(doc.errors()[0].line(), 2);
+    }
+
+    #[test]
+    fn test_unterminated_function() {
+        let mut doc = Document::new("# 0 0 {\n^ 1\n}\n. 1");
+        assert!(doc.errors().is_empty());
+        doc.edit(at(2, 0), at(3, 0), "");
+        assert_eq!(doc.errors(), vec![ParseError::UnmatchedBrace(1)]);
+    }
+
+    #[test]
+    fn test_utf16_columns_and_clamping() {
+        let mut doc = Document::new(". \"😀\" ; x");
+        // The emoji is two UTF-16 code units
+        doc.edit(at(0, 6), at(0, 99), "");
+        assert_eq!(doc.text(), ". \"😀\"");
+        doc.edit(at(7, 0), at(9, 0), "\n. 2");
+        assert_eq!(doc.lines().len(), 2);
+    }
+}
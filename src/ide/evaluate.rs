@@ -0,0 +1,50 @@
+//! Evaluating a document up to a line
+
+use super::Document;
+use crate::interpreter::{Clock, Instruction, Interpreter};
+
+/// Instructions an evaluation may run before giving up
+const MAX_STEPS: u64 = 1_000_000;
+
+/// Lines (0-based) worth evaluating up to: the labels that loops and
+/// branches land on
+pub fn evaluation_lines(doc: &Document) -> Vec<usize> {
+    doc.instructions()
+        .iter()
+        .enumerate()
+        .filter(|(_, instr)| matches!(instr, Instruction::Label { .. }))
+        .map(|(line, _)| line)
+        .collect()
+}
+
+/// Run the document until `line` (0-based) is first about to run and
+/// describe the variables there, or why it did not get there
+///
+/// The run has no arguments or input, a virtual clock and a step limit.
+pub fn evaluate_to_line(doc: &Document, line: usize) -> String {
+    let mut interp = Interpreter::new();
+    interp.set_clock(Clock::Virtual { epoch: 0.0 });
+    interp.set_input_queue(Vec::new());
+    interp.set_max_steps(Some(MAX_STEPS));
+    match interp.run_until_line(&doc.text(), line + 1, &[]) {
+        Ok(state) if state.line.is_none() => format!("Line {} does not run. {}", line + 1, state),
+        Ok(state) => state.to_string(),
+        Err(e) => format!("Stopped before line {}: {}", line + 1, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let doc = Document::new("= v0 0\n: 0\n+ v0 v0 1\n< v1 v0 3\n? v1 0\n: 1\n. v0");
+        assert_eq!(evaluation_lines(&doc), [1, 5]);
+        assert_eq!(evaluate_to_line(&doc, 5), "Before line 6 after 13 steps\nv0 = 3\nv1 = 0\ng100 = 0");
+        assert!(evaluate_to_line(&doc, 9).starts_with("Line 10 does not run. Finished after 15 steps\nv0 = 3"));
+
+        let doc = Document::new(": 0\n@ 0\n. 1");
+        assert_eq!(evaluate_to_line(&doc, 2), "Stopped before line 3: Step limit exceeded: 1000000");
+    }
+}
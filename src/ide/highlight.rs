@@ -0,0 +1,192 @@
+//! Semantic highlighting
+
+use super::{column, Document};
+use crate::interpreter::{Lexer, OperandKind, ParsedValue, Parser};
+
+/// Classification of a highlighted token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Instruction character (`=`, `+`, `$`, ...)
+    Instruction,
+    /// Local variable (`v0`)
+    Local,
+    /// Global variable (`g0`)
+    Global,
+    /// Function argument (`a0`)
+    Argument,
+    /// Label in a definition or jump
+    Label,
+    /// Function ID in a definition or call
+    Function,
+    /// String literal
+    String,
+    /// Number literal or argument count
+    Number,
+    /// Comment text
+    Comment,
+}
+
+impl TokenKind {
+    /// Every kind, in legend order
+    pub const ALL: [TokenKind; 9] = [
+        TokenKind::Instruction,
+        TokenKind::Local,
+        TokenKind::Global,
+        TokenKind::Argument,
+        TokenKind::Label,
+        TokenKind::Function,
+        TokenKind::String,
+        TokenKind::Number,
+        TokenKind::Comment,
+    ];
+
+    /// Semantic token type name
+    ///
+    /// Standard LSP names where one fits; globals and labels have their own
+    /// types so themes can tell them apart from locals and numbers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Instruction => "keyword",
+            TokenKind::Local => "variable",
+            TokenKind::Global => "globalVariable",
+            TokenKind::Argument => "parameter",
+            TokenKind::Label => "label",
+            TokenKind::Function => "function",
+            TokenKind::String => "string",
+            TokenKind::Number => "number",
+            TokenKind::Comment => "comment",
+        }
+    }
+
+    /// Position in [`TokenKind::ALL`]
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// One classified token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighlightToken {
+    /// Line (0-based)
+    pub line: usize,
+    /// Start column in UTF-16 code units
+    pub start: usize,
+    /// Length in UTF-16 code units
+    pub length: usize,
+    /// Classification
+    pub kind: TokenKind,
+}
+
+/// Classify the tokens of a document, in position order
+///
+/// Operands are classified by the position they occupy in their
+/// instruction, so `3` is a label in `@ 3` and a number in `= v0 3`.
+/// Tokens of unknown instructions are left unclassified.
+pub fn highlight(doc: &Document) -> Vec<HighlightToken> {
+    let mut tokens = Vec::new();
+
+    for (line_idx, line) in doc.lines().iter().enumerate() {
+        let spans = Lexer::token_spans(line);
+        let mut push = |byte: usize, text: &str, kind: TokenKind| {
+            tokens.push(HighlightToken {
+                line: line_idx,
+                start: column(line, byte),
+                length: text.encode_utf16().count(),
+                kind,
+            });
+        };
+
+        if let Some(syntax) = spans.first().and_then(|(_, op)| Parser::syntax(op)) {
+            push(spans[0].0, spans[0].1, TokenKind::Instruction);
+            for (k, &(byte, text)) in spans[1..].iter().enumerate() {
+                let operand = syntax
+                    .operands
+                    .get(k)
+                    .or(syntax.operands.last().filter(|kind| kind.is_variadic()));
+                let kind = match operand {
+                    Some(OperandKind::Variable)
+                    | Some(OperandKind::Value)
+                    | Some(OperandKind::Values)
+                    | Some(OperandKind::Literal) => value_kind(text),
+                    Some(OperandKind::Integer) => match syntax.params[k] {
+                        "label" => Some(TokenKind::Label),
+                        "id" | "func" => Some(TokenKind::Function),
+                        _ => Some(TokenKind::Number),
+                    },
+                    Some(OperandKind::Labels) => Some(TokenKind::Label),
+                    Some(OperandKind::String) => Some(TokenKind::String),
+                    Some(OperandKind::OpenBrace) | None => None,
+                };
+                if let Some(kind) = kind {
+                    push(byte, text, kind);
+                }
+            }
+        }
+
+        // The lexer stops at the first `;` outside a string
+        let code_end = spans.last().map_or(0, |(byte, text)| byte + text.len());
+        if let Some(offset) = line[code_end..].find(';') {
+            let byte = code_end + offset;
+            push(byte, line[byte..].trim_end(), TokenKind::Comment);
+        }
+    }
+
+    tokens
+}
+
+/// Kind of a variable or literal operand
+fn value_kind(text: &str) -> Option<TokenKind> {
+    // Constants stand for literals
+    if Lexer::constant_ref(text).is_some() {
+        return Some(TokenKind::Number);
+    }
+    match Lexer::parse_value(text) {
+        ParsedValue::Variable(var) => match var.chars().next() {
+            Some('v') => Some(TokenKind::Local),
+            Some('g') => Some(TokenKind::Global),
+            _ => Some(TokenKind::Argument),
+        },
+        ParsedValue::String(_) => Some(TokenKind::String),
+        ParsedValue::Integer(_) | ParsedValue::Float(_) => Some(TokenKind::Number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<TokenKind> {
+        highlight(&Document::new(code)).into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn test_operand_positions() {
+        use TokenKind::*;
+        assert_eq!(kinds("@ 3"), [Instruction, Label]);
+        assert_eq!(kinds("= g1 3"), [Instruction, Global, Number]);
+        assert_eq!(kinds("# 2 1 {"), [Instruction, Function, Number]);
+        assert_eq!(
+            kinds("$ v0 2 a0 \"s\" 1.5"),
+            [Instruction, Local, Function, Argument, String, Number]
+        );
+        assert_eq!(kinds("R v0 \"math.sqrt\" 4"), [Instruction, Local, String, Number]);
+    }
+
+    #[test]
+    fn test_comments_and_positions() {
+        let tokens = highlight(&Document::new("\n  . \"a;b\" ; note  \n; only\nX 1"));
+        assert_eq!(tokens.len(), 4);
+        assert_eq!((tokens[0].line, tokens[0].start, tokens[0].length), (1, 2, 1));
+        assert_eq!((tokens[1].start, tokens[1].length), (4, 5));
+        assert_eq!(tokens[2].kind, TokenKind::Comment);
+        assert_eq!((tokens[2].start, tokens[2].length), (10, 6));
+        assert_eq!((tokens[3].line, tokens[3].start), (2, 0));
+    }
+
+    #[test]
+    fn test_legend_order() {
+        for (i, kind) in TokenKind::ALL.iter().enumerate() {
+            assert_eq!(kind.index(), i);
+        }
+    }
+}
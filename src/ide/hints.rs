@@ -0,0 +1,101 @@
+//! Operand role hints
+
+use super::{column, Document, LineCol};
+use crate::interpreter::{Lexer, OperandKind, Parser};
+use std::ops::Range;
+
+/// Inline label naming the role of an operand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperandHint {
+    /// Start of the operand; the hint is shown before it
+    pub position: LineCol,
+    /// Operand name from the instruction table, such as `result` or `label`
+    ///
+    /// Extra call and FFI arguments are numbered `arg0`, `arg1`, ...
+    pub role: String,
+}
+
+/// Role hints for every operand on the lines in `lines` (0-based)
+///
+/// Lines that fail to parse still get hints for the operands they have, so
+/// a half-typed instruction shows what comes next.
+pub fn operand_hints(doc: &Document, lines: Range<usize>) -> Vec<OperandHint> {
+    let mut hints = Vec::new();
+
+    for line_idx in lines.start..lines.end.min(doc.lines().len()) {
+        let line = &doc.lines()[line_idx];
+        let spans = Lexer::token_spans(line);
+        let Some(syntax) = spans.first().and_then(|(_, op)| Parser::syntax(op)) else {
+            continue;
+        };
+
+        let variadic = syntax.operands.last().is_some_and(OperandKind::is_variadic);
+        let fixed = syntax.operands.len() - usize::from(variadic);
+        for (k, &(byte, _)) in spans[1..].iter().enumerate() {
+            let role = if k < fixed {
+                if syntax.operands[k] == OperandKind::OpenBrace {
+                    continue;
+                }
+                syntax.params[k].to_string()
+            } else if variadic {
+                let stem = if syntax.operands[fixed] == OperandKind::Labels { "label" } else { "arg" };
+                format!("{}{}", stem, k - fixed)
+            } else {
+                break;
+            };
+            hints.push(OperandHint {
+                position: LineCol {
+                    line: line_idx,
+                    character: column(line, byte),
+                },
+                role,
+            });
+        }
+    }
+
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles(code: &str) -> Vec<(usize, usize, String)> {
+        let doc = Document::new(code);
+        operand_hints(&doc, 0..usize::MAX)
+            .into_iter()
+            .map(|h| (h.position.line, h.position.character, h.role))
+            .collect()
+    }
+
+    #[test]
+    fn test_binary_and_jump_roles() {
+        assert_eq!(
+            roles("+ v0 v1 2\n? v0 3"),
+            vec![
+                (0, 2, "result".to_string()),
+                (0, 5, "a".to_string()),
+                (0, 8, "b".to_string()),
+                (1, 2, "cond".to_string()),
+                (1, 5, "label".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_and_call_roles() {
+        let hints = roles("# 1 2 {\n$ v0 1 v1 \"😀\" 3\n}");
+        let names: Vec<&str> = hints.iter().map(|(_, _, role)| role.as_str()).collect();
+        assert_eq!(names, ["id", "argc", "result", "func", "arg0", "arg1", "arg2"]);
+        // Columns count UTF-16 code units
+        assert_eq!(hints[6].1, 15);
+    }
+
+    #[test]
+    fn test_partial_and_unknown_lines() {
+        assert_eq!(roles("= v0"), vec![(0, 2, "target".to_string())]);
+        assert!(roles("X v0 1\n; . v0\n. 1 2").len() == 1);
+        let doc = Document::new(". 1\n. 2\n. 3");
+        assert_eq!(operand_hints(&doc, 1..2)[0].position.line, 1);
+    }
+}
@@ -0,0 +1,119 @@
+//! Instruction docs, runtime values and inferred types on hover
+
+use super::{Document, LineCol, SymbolIndex, SymbolKind};
+use crate::analysis::{infer_types_lines, TypeSet};
+use crate::debugger::session::{Snapshot, SnapshotValue};
+use crate::interpreter::{instruction_set, Instruction, Lexer};
+
+/// Markdown describing the instruction on `line`, or `None` for blank and
+/// comment lines
+pub fn instruction_doc(doc: &Document, line: usize) -> Option<String> {
+    let line = doc.line(line)?.trim();
+    if line.is_empty() || line.starts_with(';') {
+        return None;
+    }
+
+    let op = Lexer::token_spans(line).first()?.1;
+    let syntax = instruction_set::find(op)?;
+    Some(format!("**{}**\n\n`{}`\n\n{}", syntax.title, syntax.signature(), syntax.description))
+}
+
+/// Types inferred for the variable at `pos`, with its name
+pub fn inferred_type(doc: &Document, pos: LineCol) -> Option<(String, TypeSet)> {
+    let symbol = SymbolIndex::new(doc).at(pos)?.symbol;
+    let func = match symbol.kind {
+        SymbolKind::Global => None,
+        SymbolKind::Local | SymbolKind::Argument => match symbol.scope {
+            None => None,
+            Some(header) => match doc.instructions().get(header)? {
+                Instruction::FuncDef { id, .. } => Some(*id),
+                _ => return None,
+            },
+        },
+        SymbolKind::Label | SymbolKind::Function => return None,
+    };
+
+    let name = symbol.text();
+    let types = infer_types_lines(doc.instructions()).var(func, &name)?;
+    Some((name, types))
+}
+
+/// Value of the variable at `pos` in a debugger snapshot, with its name
+///
+/// Locals and arguments are only resolved while the debugger is in the
+/// function the cursor is in, since every function has its own `v0`;
+/// globals resolve anywhere.
+pub fn debug_value<'a>(doc: &Document, pos: LineCol, snapshot: &'a Snapshot) -> Option<(String, &'a SnapshotValue)> {
+    let symbol = SymbolIndex::new(doc).at(pos)?.symbol;
+    match symbol.kind {
+        SymbolKind::Global => {}
+        SymbolKind::Local | SymbolKind::Argument => {
+            let func_id = match symbol.scope {
+                None => -1,
+                Some(header) => match doc.instructions().get(header)? {
+                    Instruction::FuncDef { id, .. } => *id,
+                    _ => return None,
+                },
+            };
+            if func_id != snapshot.func_id {
+                return None;
+            }
+        }
+        SymbolKind::Label | SymbolKind::Function => return None,
+    }
+
+    let name = symbol.text();
+    let value = snapshot.get(&name)?;
+    Some((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_doc() {
+        let doc = Document::new("= v0 1\n; note\n\n?? v0");
+        let hover = instruction_doc(&doc, 0).unwrap();
+        assert!(hover.starts_with("**"));
+        assert!(hover.contains("`= "));
+        assert!(instruction_doc(&doc, 1).is_none());
+        assert!(instruction_doc(&doc, 2).is_none());
+        assert!(instruction_doc(&doc, 3).is_none());
+        assert!(instruction_doc(&doc, 4).is_none());
+    }
+
+    #[test]
+    fn test_inferred_type() {
+        let doc = Document::new("# 0 1 {\n^ a0\n}\n$ v0 0 \"x\"\n= v0 1.5");
+        let at = |line, character| LineCol { line, character };
+        let (name, types) = inferred_type(&doc, at(1, 2)).unwrap();
+        assert_eq!((name.as_str(), types), ("a0", TypeSet::STRING));
+        assert_eq!(inferred_type(&doc, at(3, 2)).unwrap().1.to_string(), "float|string");
+        assert!(inferred_type(&doc, at(3, 5)).is_none());
+    }
+
+    #[test]
+    fn test_scoped_lookup() {
+        let doc = Document::new("# 0 1 {\n^ v0\n}\n= v0 g0");
+        let mut snapshot = Snapshot::default();
+        for name in ["v0", "g0"] {
+            snapshot.variables.insert(
+                name.to_string(),
+                SnapshotValue {
+                    type_name: "int".to_string(),
+                    value: "7".to_string(),
+                },
+            );
+        }
+
+        let at = |line, character| LineCol { line, character };
+        assert_eq!(debug_value(&doc, at(3, 2), &snapshot).unwrap().0, "v0");
+        assert_eq!(debug_value(&doc, at(3, 5), &snapshot).unwrap().0, "g0");
+        // The function's v0 is not the frame the debugger is in
+        assert!(debug_value(&doc, at(1, 2), &snapshot).is_none());
+        snapshot.func_id = 0;
+        assert!(debug_value(&doc, at(1, 2), &snapshot).is_some());
+        assert!(debug_value(&doc, at(3, 2), &snapshot).is_none());
+    }
+}
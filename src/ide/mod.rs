@@ -0,0 +1,57 @@
+//! Editor support for Sui source
+//!
+//! Protocol-independent building blocks for the `sui-lsp` language server.
+//! [`Document`] keeps the parse of every line up to date as the text is
+//! edited, so the server only re-parses what changed. [`operand_hints`]
+//! labels each operand with its role for inlay hints, and [`highlight`]
+//! classifies tokens for semantic highlighting. [`SymbolIndex`] resolves
+//! variables, labels and function IDs within their scopes for
+//! go-to-definition and [`rename`], and [`quick_fixes`] offers edits that
+//! resolve parse errors and lint warnings. [`instruction_doc`] describes
+//! the instruction on a line, [`debug_value`] looks up the
+//! variable under the cursor in a debugger snapshot, [`inferred_type`] shows
+//! the types it may hold, and [`signature_help`]
+//! shows the operands of the instruction being typed. [`evaluate_to_line`]
+//! runs the program up to a line and shows the variables there. [`workspace_symbols`]
+//! and [`unresolved_imports`] work across files. [`parse_diagnostics`] and
+//! [`lint_diagnostics`] position problems for editors without a server.
+//!
+//! ```rust
+//! use sui_lang::ide::{Document, LineCol};
+//!
+//! let mut doc = Document::new("= v0 1\n. v0");
+//! doc.edit(LineCol { line: 1, character: 0 }, LineCol { line: 1, character: 1 }, "+");
+//! assert_eq!(doc.errors().len(), 1);
+//! ```
+
+mod actions;
+mod diagnostics;
+mod document;
+mod evaluate;
+mod highlight;
+mod hints;
+mod hover;
+mod rename;
+mod signature;
+mod symbols;
+mod workspace;
+
+pub use actions::{quick_fixes, QuickFix};
+pub use diagnostics::{lint_diagnostics, parse_diagnostics, Diagnostic, Severity};
+pub use document::{Document, Edit, LineCol};
+pub use evaluate::{evaluate_to_line, evaluation_lines};
+pub use highlight::{highlight, HighlightToken, TokenKind};
+pub use hints::{operand_hints, OperandHint};
+pub use hover::{debug_value, inferred_type, instruction_doc};
+pub use rename::{rename, RenameError};
+pub use signature::{signature_help, SignatureHelp};
+pub use symbols::{Occurrence, Symbol, SymbolIndex, SymbolKind};
+pub use workspace::{
+    find_sui_files, unresolved_imports, workspace_symbols, UnresolvedImport, WorkspaceSymbol,
+    WorkspaceSymbolKind,
+};
+
+/// UTF-16 column of a byte offset within a line
+fn column(line: &str, byte: usize) -> usize {
+    line[..byte].encode_utf16().count()
+}
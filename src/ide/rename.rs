@@ -0,0 +1,111 @@
+//! Renumbering of variables, labels and function IDs
+
+use super::{Document, Edit, LineCol, Symbol, SymbolIndex};
+use thiserror::Error;
+
+/// Rename failures
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RenameError {
+    #[error("No variable, label or function at this position")]
+    NoSymbol,
+
+    #[error("Invalid new name for {0}: '{1}'")]
+    InvalidName(String, String),
+
+    #[error("{0} is already used in this scope")]
+    Conflict(String),
+}
+
+/// Renumber the symbol at `pos` everywhere in its scope
+///
+/// `new_name` is the new spelling (`v7`) or just the new number (`7`); a
+/// variable keeps its prefix, since changing it would change where the
+/// value lives. Renaming onto a symbol that is already used in the same
+/// scope is refused, as it would merge the two.
+pub fn rename(doc: &Document, pos: LineCol, new_name: &str) -> Result<Vec<Edit>, RenameError> {
+    let index = SymbolIndex::new(doc);
+    let symbol = index.at(pos).ok_or(RenameError::NoSymbol)?.symbol;
+
+    let new_name = new_name.trim();
+    let number = match symbol.kind.prefix() {
+        Some(prefix) => {
+            let digits = new_name.strip_prefix(prefix).unwrap_or(new_name);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                None
+            } else {
+                digits.parse().ok()
+            }
+        }
+        None => new_name.parse().ok(),
+    };
+    let Some(id) = number else {
+        return Err(RenameError::InvalidName(symbol.text(), new_name.to_string()));
+    };
+
+    let renamed = Symbol { id, ..symbol };
+    if renamed != symbol && index.references(&renamed).next().is_some() {
+        return Err(RenameError::Conflict(renamed.text()));
+    }
+
+    let text = renamed.text();
+    Ok(index
+        .references(&symbol)
+        .map(|occ| Edit {
+            start: LineCol {
+                line: occ.line,
+                character: occ.start,
+            },
+            end: LineCol {
+                line: occ.line,
+                character: occ.start + occ.length,
+            },
+            text: text.clone(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(code: &str, edits: &[Edit]) -> String {
+        let mut doc = Document::new(code);
+        doc.apply(edits);
+        doc.text()
+    }
+
+    const CODE: &str = "= v3 1\n# 0 1 {\n= v3 a0\n^ v3\n}\n$ v1 0 v3\n. v3";
+
+    #[test]
+    fn test_rename_variable_in_scope() {
+        let doc = Document::new(CODE);
+        let edits = rename(&doc, LineCol { line: 0, character: 3 }, "v7").unwrap();
+        assert_eq!(apply(CODE, &edits), "= v7 1\n# 0 1 {\n= v3 a0\n^ v3\n}\n$ v1 0 v7\n. v7");
+        let edits = rename(&doc, LineCol { line: 3, character: 2 }, "5").unwrap();
+        assert_eq!(apply(CODE, &edits), "= v3 1\n# 0 1 {\n= v5 a0\n^ v5\n}\n$ v1 0 v3\n. v3");
+    }
+
+    #[test]
+    fn test_rename_function_and_label() {
+        let code = "# 2 0 {\n^ 1\n}\n: 4\n$ v0 2\n@ 4";
+        let doc = Document::new(code);
+        let edits = rename(&doc, LineCol { line: 4, character: 5 }, "9").unwrap();
+        assert_eq!(apply(code, &edits), "# 9 0 {\n^ 1\n}\n: 4\n$ v0 9\n@ 4");
+        let edits = rename(&doc, LineCol { line: 5, character: 2 }, "0").unwrap();
+        assert_eq!(apply(code, &edits), "# 2 0 {\n^ 1\n}\n: 0\n$ v0 2\n@ 0");
+    }
+
+    #[test]
+    fn test_rename_errors() {
+        let doc = Document::new(CODE);
+        assert_eq!(rename(&doc, LineCol { line: 0, character: 0 }, "v1"), Err(RenameError::NoSymbol));
+        assert!(matches!(
+            rename(&doc, LineCol { line: 0, character: 2 }, "g1"),
+            Err(RenameError::InvalidName(..))
+        ));
+        assert_eq!(
+            rename(&doc, LineCol { line: 0, character: 2 }, "v1"),
+            Err(RenameError::Conflict("v1".to_string()))
+        );
+    }
+}
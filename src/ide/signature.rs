@@ -0,0 +1,119 @@
+//! Signature help for the instruction being typed
+
+use super::{Document, LineCol};
+use crate::interpreter::{InstructionSyntax, Lexer, OperandKind, Parser};
+
+/// Operand signature of the instruction at the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHelp {
+    /// Entry in the instruction table
+    pub syntax: &'static InstructionSyntax,
+    /// Usage line, as in [`InstructionSyntax::signature`]
+    pub label: String,
+    /// Character range of each operand name within `label`
+    pub parameters: Vec<(usize, usize)>,
+    /// Operand the cursor is on, or `None` past the last operand
+    pub active: Option<usize>,
+}
+
+/// Signature of the instruction on the cursor line, once its instruction
+/// character is followed by a space
+///
+/// The active operand is the one the cursor is inside or, between tokens,
+/// the next one to be typed; extra call and FFI arguments all map to the
+/// variadic operand.
+pub fn signature_help(doc: &Document, pos: LineCol) -> Option<SignatureHelp> {
+    let (line_idx, byte) = doc.offset(pos);
+    let prefix = &doc.line(line_idx)?[..byte];
+    let spans = Lexer::token_spans(prefix);
+    let (_, op) = spans.first()?;
+    let syntax = Parser::syntax(op)?;
+
+    let last = spans.last()?;
+    let last_end = last.0 + last.1.len();
+    let typed = &spans[1..];
+    let at_token_end = last_end == prefix.len();
+
+    // Still typing the instruction character, or inside a trailing comment
+    if (typed.is_empty() && at_token_end) || prefix[last_end..].contains(';') {
+        return None;
+    }
+
+    let index = if at_token_end {
+        typed.len() - 1
+    } else {
+        typed.len()
+    };
+    let variadic = syntax.operands.last().is_some_and(OperandKind::is_variadic);
+    let active = if index < syntax.operands.len() {
+        Some(index)
+    } else if variadic {
+        Some(syntax.operands.len() - 1)
+    } else {
+        None
+    };
+
+    // The label is ASCII, so byte offsets are also character offsets
+    let label = syntax.signature();
+    let mut parameters = Vec::new();
+    let mut start = syntax.op.len();
+    for param in syntax.params {
+        let begin = label[start..].find(param)? + start;
+        parameters.push((begin, begin + param.len()));
+        start = begin + param.len();
+    }
+
+    Some(SignatureHelp {
+        syntax,
+        label,
+        parameters,
+        active,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active(line: &str) -> Option<Option<usize>> {
+        let doc = Document::new(line);
+        let pos = LineCol {
+            line: 0,
+            character: line.encode_utf16().count(),
+        };
+        signature_help(&doc, pos).map(|help| help.active)
+    }
+
+    #[test]
+    fn test_active_operand() {
+        assert_eq!(active("+"), None);
+        assert_eq!(active("+ "), Some(Some(0)));
+        assert_eq!(active("+ v0"), Some(Some(0)));
+        assert_eq!(active("+ v0 "), Some(Some(1)));
+        assert_eq!(active("+ v0 v1 2"), Some(Some(2)));
+        assert_eq!(active("+ v0 v1 2 "), Some(None));
+        assert_eq!(active("X "), None);
+        assert_eq!(active("+ v0 ; sum "), None);
+    }
+
+    #[test]
+    fn test_variadic_and_strings() {
+        assert_eq!(active("$ v0 1 v1 v2 "), Some(Some(2)));
+        assert_eq!(active("R v0 \"a;b\" "), Some(Some(2)));
+        assert_eq!(active("R v0 \"a b"), Some(Some(1)));
+    }
+
+    #[test]
+    fn test_parameter_ranges() {
+        let doc = Document::new("$ v0 1 v1");
+        let help = signature_help(&doc, LineCol { line: 0, character: 2 }).unwrap();
+        assert_eq!(help.label, "$ result func args...");
+        let names: Vec<&str> = help
+            .parameters
+            .iter()
+            .map(|&(start, end)| &help.label[start..end])
+            .collect();
+        assert_eq!(names, ["result", "func", "args"]);
+        assert_eq!(help.active, Some(0));
+    }
+}
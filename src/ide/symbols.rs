@@ -0,0 +1,228 @@
+//! Symbol index: variables, labels and function IDs
+
+use super::{column, Document, LineCol};
+use crate::interpreter::{Instruction, Lexer, OperandKind, ParsedValue, Parser};
+
+/// Kind of a named entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// Local variable (`v0`), scoped to its function
+    Local,
+    /// Function argument (`a0`), scoped to its function
+    Argument,
+    /// Global variable (`g0`)
+    Global,
+    /// Jump target, scoped to its function
+    Label,
+    /// Function ID
+    Function,
+}
+
+impl SymbolKind {
+    /// Whether each function has its own set of these symbols
+    pub fn is_scoped(&self) -> bool {
+        matches!(self, SymbolKind::Local | SymbolKind::Argument | SymbolKind::Label)
+    }
+
+    /// Variable prefix (`v`, `a`, `g`), if the symbol is a variable
+    pub fn prefix(&self) -> Option<char> {
+        match self {
+            SymbolKind::Local => Some('v'),
+            SymbolKind::Argument => Some('a'),
+            SymbolKind::Global => Some('g'),
+            SymbolKind::Label | SymbolKind::Function => None,
+        }
+    }
+}
+
+/// A variable, label or function, identified across its scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    /// Number of the variable, label or function
+    pub id: i64,
+    /// Line (0-based) of the enclosing function header; `None` for main
+    /// code and for symbols that are not scoped
+    pub scope: Option<usize>,
+}
+
+impl Symbol {
+    /// Source spelling of the symbol
+    pub fn text(&self) -> String {
+        match self.kind.prefix() {
+            Some(prefix) => format!("{}{}", prefix, self.id),
+            None => self.id.to_string(),
+        }
+    }
+}
+
+/// One mention of a symbol in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    pub symbol: Symbol,
+    /// Line (0-based)
+    pub line: usize,
+    /// Start column in UTF-16 code units
+    pub start: usize,
+    /// Length in UTF-16 code units
+    pub length: usize,
+    /// Whether this mention defines the symbol: a label or function
+    /// definition, or a variable being written
+    pub definition: bool,
+}
+
+impl Occurrence {
+    /// Whether the occurrence covers `pos` (including its end)
+    pub fn contains(&self, pos: LineCol) -> bool {
+        pos.line == self.line && pos.character >= self.start && pos.character <= self.start + self.length
+    }
+}
+
+/// Every symbol occurrence in a document, in position order
+///
+/// Backs go-to-definition, references and rename, so they all agree on
+/// what a name refers to.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    occurrences: Vec<Occurrence>,
+}
+
+impl SymbolIndex {
+    /// Index a document
+    pub fn new(doc: &Document) -> Self {
+        let mut occurrences = Vec::new();
+        // Function headers enclosing the current line
+        let mut functions: Vec<usize> = Vec::new();
+
+        for (line_idx, (line, instr)) in doc.lines().iter().zip(doc.instructions()).enumerate() {
+            // A header belongs to the enclosing scope; its body to its own
+            let scope = functions.last().copied();
+            match instr {
+                Instruction::FuncDef { .. } => functions.push(line_idx),
+                Instruction::FuncEnd => {
+                    functions.pop();
+                }
+                _ => {}
+            }
+
+            let spans = Lexer::token_spans(line);
+            let Some(syntax) = spans.first().and_then(|(_, op)| Parser::syntax(op)) else {
+                continue;
+            };
+
+            for (k, &(byte, text)) in spans[1..].iter().enumerate() {
+                let operand = syntax
+                    .operands
+                    .get(k)
+                    .or(syntax.operands.last().filter(|kind| kind.is_variadic()));
+                let found = match operand {
+                    Some(OperandKind::Variable) | Some(OperandKind::Value) | Some(OperandKind::Values) => {
+                        variable(text).map(|(kind, id)| (kind, id, operand == Some(&OperandKind::Variable)))
+                    }
+                    Some(OperandKind::Labels) => text.parse().ok().map(|id| (SymbolKind::Label, id, false)),
+                    Some(OperandKind::Integer) => {
+                        let kind = match syntax.params[k] {
+                            "label" => SymbolKind::Label,
+                            "id" | "func" => SymbolKind::Function,
+                            _ => continue,
+                        };
+                        let definition = matches!(syntax.name, "label" | "func_def");
+                        text.parse().ok().map(|id| (kind, id, definition))
+                    }
+                    _ => None,
+                };
+
+                if let Some((kind, id, definition)) = found {
+                    occurrences.push(Occurrence {
+                        symbol: Symbol {
+                            kind,
+                            id,
+                            scope: if kind.is_scoped() { scope } else { None },
+                        },
+                        line: line_idx,
+                        start: column(line, byte),
+                        length: text.encode_utf16().count(),
+                        definition,
+                    });
+                }
+            }
+        }
+
+        Self { occurrences }
+    }
+
+    /// All occurrences
+    pub fn occurrences(&self) -> &[Occurrence] {
+        &self.occurrences
+    }
+
+    /// Occurrence under the cursor
+    pub fn at(&self, pos: LineCol) -> Option<&Occurrence> {
+        self.occurrences.iter().find(|occ| occ.contains(pos))
+    }
+
+    /// Every occurrence of a symbol
+    pub fn references(&self, symbol: &Symbol) -> impl Iterator<Item = &Occurrence> + '_ {
+        let symbol = *symbol;
+        self.occurrences.iter().filter(move |occ| occ.symbol == symbol)
+    }
+
+    /// Where a symbol is defined
+    ///
+    /// The last definition for labels, since later labels win at run time,
+    /// and the first for everything else.
+    pub fn definition(&self, symbol: &Symbol) -> Option<&Occurrence> {
+        let mut definitions = self.references(symbol).filter(|occ| occ.definition);
+        match symbol.kind {
+            SymbolKind::Label => definitions.last(),
+            _ => definitions.next(),
+        }
+    }
+}
+
+/// Kind and number of a variable operand
+fn variable(text: &str) -> Option<(SymbolKind, i64)> {
+    let ParsedValue::Variable(var) = Lexer::parse_value(text) else {
+        return None;
+    };
+    let (prefix, id) = Lexer::split_variable(&var);
+    let kind = match prefix {
+        'v' => SymbolKind::Local,
+        'g' => SymbolKind::Global,
+        _ => SymbolKind::Argument,
+    };
+    Some((kind, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "= v0 1\n# 0 1 {\n= v0 a0\n: 1\n? v0 1\n^ v0\n}\n$ g0 0 v0\n: 1\n@ 1";
+
+    #[test]
+    fn test_function_scopes() {
+        let index = SymbolIndex::new(&Document::new(CODE));
+        let main_v0 = index.at(LineCol { line: 0, character: 2 }).unwrap().symbol;
+        let func_v0 = index.at(LineCol { line: 2, character: 3 }).unwrap().symbol;
+        assert_eq!(main_v0.scope, None);
+        assert_eq!(func_v0.scope, Some(1));
+        let lines = |s: &Symbol| index.references(s).map(|o| o.line).collect::<Vec<_>>();
+        assert_eq!(lines(&main_v0), [0, 7]);
+        assert_eq!(lines(&func_v0), [2, 4, 5]);
+    }
+
+    #[test]
+    fn test_definitions() {
+        let index = SymbolIndex::new(&Document::new(CODE));
+        let call = index.at(LineCol { line: 7, character: 5 }).unwrap();
+        assert_eq!(call.symbol.kind, SymbolKind::Function);
+        assert_eq!(index.definition(&call.symbol).unwrap().line, 1);
+
+        let jump = index.at(LineCol { line: 9, character: 2 }).unwrap();
+        assert_eq!(jump.symbol.kind, SymbolKind::Label);
+        assert_eq!(index.definition(&jump.symbol).unwrap().line, 8);
+        // The label inside the function is a different symbol
+        assert_eq!(index.references(&jump.symbol).count(), 2);
+    }
+}
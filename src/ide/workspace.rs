@@ -0,0 +1,186 @@
+//! Cross-file features: workspace symbols and import resolution
+
+use super::Document;
+use crate::interpreter::Instruction;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Kind of a workspace-level symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkspaceSymbolKind {
+    Function,
+    Label,
+}
+
+/// A function or label definition somewhere in the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceSymbol {
+    /// Display name: `function 3`, `label 1`
+    pub name: String,
+    pub kind: WorkspaceSymbolKind,
+    /// File defining the symbol
+    pub path: PathBuf,
+    /// Line (0-based)
+    pub line: usize,
+    /// Enclosing function (`function 3`), for labels inside functions
+    pub container: Option<String>,
+}
+
+/// Function and label definitions whose name contains `query`
+///
+/// Matching ignores case and whitespace, so `fun3` and `function 3` both
+/// find function 3; an empty query matches everything.
+pub fn workspace_symbols(files: &[(PathBuf, &Document)], query: &str) -> Vec<WorkspaceSymbol> {
+    let query = normalize(query);
+    let mut symbols = Vec::new();
+
+    for (path, doc) in files {
+        let mut functions: Vec<i64> = Vec::new();
+        for (line, instr) in doc.instructions().iter().enumerate() {
+            let container = functions.last().map(|id| format!("function {}", id));
+            let (name, kind) = match instr {
+                Instruction::FuncDef { id, .. } => {
+                    functions.push(*id);
+                    (format!("function {}", id), WorkspaceSymbolKind::Function)
+                }
+                Instruction::FuncEnd => {
+                    functions.pop();
+                    continue;
+                }
+                Instruction::Label { id } => (format!("label {}", id), WorkspaceSymbolKind::Label),
+                _ => continue,
+            };
+            if normalize(&name).contains(&query) {
+                symbols.push(WorkspaceSymbol {
+                    name,
+                    kind,
+                    path: path.clone(),
+                    line,
+                    container,
+                });
+            }
+        }
+    }
+
+    symbols
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// `_` import whose module cannot be found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedImport {
+    /// Line (0-based) of the import
+    pub line: usize,
+    /// Import path as written
+    pub import: String,
+}
+
+impl fmt::Display for UnresolvedImport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Module not found: {}", self.import)
+    }
+}
+
+/// Imports of the document at `path` that resolve to no file
+///
+/// Paths resolve relative to the importing file, as the interpreter does.
+/// `exists` decides whether a resolved path is available, typically
+/// because it is open in the editor or present on disk.
+pub fn unresolved_imports(
+    path: &Path,
+    doc: &Document,
+    exists: impl Fn(&Path) -> bool,
+) -> Vec<UnresolvedImport> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    doc.instructions()
+        .iter()
+        .enumerate()
+        .filter_map(|(line, instr)| match instr {
+            Instruction::Import { path: import } if !exists(&dir.join(import)) => {
+                Some(UnresolvedImport {
+                    line,
+                    import: import.clone(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// `.sui` files under `root`, skipping hidden directories and `target`
+pub fn find_sui_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if path.is_dir() {
+                if !name.starts_with('.') && name != "target" {
+                    dirs.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "sui") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols_across_files() {
+        let a = Document::new("# 3 1 {\n: 1\n^ a0\n}\n: 2");
+        let b = Document::new("# 30 0 {\n^ 0\n}");
+        let files = vec![(PathBuf::from("a.sui"), &a), (PathBuf::from("b.sui"), &b)];
+
+        let all = workspace_symbols(&files, "");
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[1].container.as_deref(), Some("function 3"));
+        assert_eq!(all[2].container, None);
+
+        let found = workspace_symbols(&files, "Function3");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].path, PathBuf::from("b.sui"));
+        assert_eq!(workspace_symbols(&files, "label 2")[0].line, 4);
+    }
+
+    #[test]
+    fn test_unresolved_imports() {
+        let doc = Document::new("_ \"lib/math.sui\"\n_ \"missing.sui\"\n. 1");
+        let missing = unresolved_imports(Path::new("/proj/main.sui"), &doc, |p| {
+            p == Path::new("/proj/lib/math.sui")
+        });
+        assert_eq!(
+            missing,
+            vec![UnresolvedImport {
+                line: 1,
+                import: "missing.sui".to_string()
+            }]
+        );
+        assert_eq!(missing[0].to_string(), "Module not found: missing.sui");
+    }
+
+    #[test]
+    fn test_find_examples() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let files = find_sui_files(&root);
+        assert!(files.iter().any(|f| f.ends_with("fibonacci.sui")));
+        assert!(files.iter().all(|f| f.extension().unwrap() == "sui"));
+    }
+}
@@ -0,0 +1,465 @@
+//! Arbitrary-precision integers, for results that overflow `i64`
+//!
+//! Integer arithmetic in [`Value`](super::Value) is exact: a sum, product or
+//! power that does not fit in an `i64` becomes a [`BigInt`], and a `BigInt`
+//! result that fits again goes back to an ordinary integer. Only what the
+//! interpreter needs is implemented, with schoolbook multiplication and
+//! Knuth's long division, so integers are capped at [`MAX_INTEGER_BITS`]
+//! to keep every step fast.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Most bits an integer may have, about 4900 decimal digits; the
+/// interpreter fails with
+/// [`InterpreterError::IntegerTooLarge`](super::InterpreterError::IntegerTooLarge)
+/// past it
+pub const MAX_INTEGER_BITS: u64 = 1 << 14;
+
+/// Most significant decimal digits [`BigInt::parse`] accepts, enough for any
+/// integer of [`MAX_INTEGER_BITS`]
+const MAX_INTEGER_DIGITS: usize = 4933;
+
+/// Signed integer of any size
+///
+/// The magnitude is stored in base 2^32, least significant limb first,
+/// without leading zero limbs; zero has no limbs and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        let negative = negative && !limbs.is_empty();
+        Self { negative, limbs }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Number of bits in the magnitude
+    pub fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// The value as an `i64`, if it fits
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let magnitude = self.limbs.iter().rev().fold(0u64, |acc, &limb| (acc << 32) | limb as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    /// The integer a whole, finite `f64` equals; `None` for other floats
+    pub fn from_f64(x: f64) -> Option<BigInt> {
+        if !x.is_finite() || x.fract() != 0.0 {
+            return None;
+        }
+        let bits = x.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        let mantissa = if biased == 0 { fraction } else { fraction | 1 << 52 };
+        let exponent = biased - 1075;
+        let magnitude = if exponent < 0 {
+            // Whole, so the bits shifted out are zero
+            BigInt::from(mantissa.checked_shr(-exponent as u32).unwrap_or(0) as i64)
+        } else {
+            BigInt::from(mantissa as i64).mul(&BigInt::from(2).pow(exponent as u32))
+        };
+        Some(if x < 0.0 { magnitude.neg() } else { magnitude })
+    }
+
+    /// Nearest `f64`; infinite if out of range
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.limbs.iter().rev().fold(0.0, |acc, &limb| acc * 4294967296.0 + limb as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn neg(&self) -> BigInt {
+        Self::from_parts(!self.negative, self.limbs.clone())
+    }
+
+    pub fn abs(&self) -> BigInt {
+        Self::from_parts(false, self.limbs.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return Self::from_parts(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => Self::from_parts(other.negative, sub_magnitudes(&other.limbs, &self.limbs)),
+            _ => Self::from_parts(self.negative, sub_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let t = product[i + j] as u64 + a as u64 * b as u64 + carry;
+                product[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            product[i + other.limbs.len()] = carry as u32;
+        }
+        Self::from_parts(self.negative != other.negative, product)
+    }
+
+    /// Quotient rounded toward zero and the remainder, which takes the sign
+    /// of `self`; `None` if `other` is zero
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        let (quotient, remainder) = match other.limbs[..] {
+            [] => return None,
+            [divisor] => {
+                let (quotient, remainder) = div_rem_limb(&self.limbs, divisor);
+                (quotient, vec![remainder])
+            }
+            _ => div_rem_magnitudes(&self.limbs, &other.limbs),
+        };
+        let quotient = Self::from_parts(self.negative != other.negative, quotient);
+        Some((quotient, Self::from_parts(self.negative, remainder)))
+    }
+
+    /// Quotient rounded toward negative infinity; `None` if `other` is zero
+    pub fn floor_div(&self, other: &BigInt) -> Option<BigInt> {
+        let (quotient, remainder) = self.div_rem(other)?;
+        if !remainder.is_zero() && self.negative != other.negative {
+            Some(quotient.sub(&BigInt::from(1)))
+        } else {
+            Some(quotient)
+        }
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+
+    /// Digits of the magnitude in `radix` (2 to 36), without a sign
+    pub fn magnitude_str(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        // Divide by the largest power of `radix` in a limb, for that many
+        // digits at a time
+        let (mut chunk, mut width) = (radix, 1);
+        while let Some(next) = chunk.checked_mul(radix) {
+            (chunk, width) = (next, width + 1);
+        }
+        let mut limbs = self.limbs.clone();
+        let mut digits = Vec::new();
+        while !limbs.is_empty() {
+            let (quotient, mut rem) = div_rem_limb(&limbs, chunk);
+            limbs = quotient;
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+            for _ in 0..width {
+                if limbs.is_empty() && rem == 0 {
+                    break;
+                }
+                digits.push(std::char::from_digit(rem % radix, radix).unwrap_or('?'));
+                rem /= radix;
+            }
+        }
+        digits.iter().rev().collect()
+    }
+
+    /// Whether `text` is an optionally signed run of decimal digits
+    pub fn is_digits(text: &str) -> bool {
+        let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    /// Parse an optionally signed run of decimal digits
+    ///
+    /// `None` if `text` is not [digits](Self::is_digits) or has more
+    /// significant digits than an integer of [`MAX_INTEGER_BITS`] can.
+    pub fn parse(text: &str) -> Option<BigInt> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        if !Self::is_digits(digits) || digits.trim_start_matches('0').len() > MAX_INTEGER_DIGITS {
+            return None;
+        }
+        let mut limbs: Vec<u32> = Vec::new();
+        for b in digits.bytes() {
+            let mut carry = (b - b'0') as u64;
+            for limb in limbs.iter_mut() {
+                let t = *limb as u64 * 10 + carry;
+                *limb = t as u32;
+                carry = t >> 32;
+            }
+            if carry > 0 {
+                limbs.push(carry as u32);
+            }
+        }
+        Some(Self::from_parts(negative, limbs))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let magnitude = n.unsigned_abs();
+        Self::from_parts(n < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("-")?;
+        }
+        f.write_str(&self.magnitude_str(10))
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let t = limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(t as u32);
+        carry = t >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// `a / b` and `a % b` for a single-limb `b`
+fn div_rem_limb(a: &[u32], b: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for (i, &limb) in a.iter().enumerate().rev() {
+        let t = (rem << 32) | limb as u64;
+        quotient[i] = (t / b as u64) as u32;
+        rem = t % b as u64;
+    }
+    (quotient, rem as u32)
+}
+
+/// `a / b` and `a % b` for `b` of two or more limbs, by Knuth's algorithm D
+/// (The Art of Computer Programming, vol. 2, 4.3.1)
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    // Normalize so the divisor's top bit is set, which keeps each estimated
+    // quotient digit at most 2 too large
+    let shift = b[b.len() - 1].leading_zeros();
+    let mut v = shl_bits(b, shift);
+    v.pop();
+    let mut u = shl_bits(a, shift);
+    let n = v.len();
+    let m = u.len() - n;
+    let (top, next) = (v[n - 1] as u64, v[n - 2] as u64);
+    let mut quotient = vec![0u32; m];
+    for j in (0..m).rev() {
+        let numerator = (u[j + n] as u64) << 32 | u[j + n - 1] as u64;
+        let (mut qhat, mut rhat) = (numerator / top, numerator % top);
+        while qhat >> 32 != 0 || qhat * next > (rhat << 32 | u[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += top;
+            if rhat >> 32 != 0 {
+                break;
+            }
+        }
+        // Subtract qhat * v from the window of u
+        let (mut borrow, mut carry) = (0i64, 0u64);
+        for i in 0..n {
+            let product = qhat * v[i] as u64 + carry;
+            carry = product >> 32;
+            let t = u[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            u[i + j] = t as u32;
+            borrow = (t < 0) as i64;
+        }
+        let t = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = t as u32;
+        // qhat was one too large: add v back
+        if t < 0 {
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = qhat as u32;
+    }
+    (quotient, shr_bits(&u[..n], shift))
+}
+
+/// `a << shift` for `shift < 32`, one limb longer than `a`
+fn shl_bits(a: &[u32], shift: u32) -> Vec<u32> {
+    let mut shifted = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for &limb in a {
+        shifted.push(limb << shift | carry);
+        carry = if shift == 0 { 0 } else { limb >> (32 - shift) };
+    }
+    shifted.push(carry);
+    shifted
+}
+
+/// `a >> shift` for `shift < 32`
+fn shr_bits(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    (0..a.len()).map(|i| a[i] >> shift | a.get(i + 1).map_or(0, |&next| next << (32 - shift))).collect()
+}
+
+/// `a - b` for `a >= b`
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut t = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (t < 0) as i64;
+        if t < 0 {
+            t += 1 << 32;
+        }
+        difference.push(t as u32);
+    }
+    difference
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.sub(&b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(a.mul(&b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+        let (q, r) = b.div_rem(&a).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), ("-8".to_string(), "-9000000000900000000090".to_string()));
+        assert_eq!(b.floor_div(&a).unwrap().to_string(), "-9");
+        assert!(a.div_rem(&BigInt::default()).is_none());
+        assert_eq!(BigInt::from(2).pow(100).to_string(), "1267650600228229401496703205376");
+        assert_eq!(BigInt::from(-255).magnitude_str(16), "ff");
+        assert!(b < a && a.neg() > b);
+    }
+
+    #[test]
+    fn test_long_division() {
+        // Divisors of several limbs, including ones whose quotient digit
+        // is first guessed too large
+        let cases = [
+            ("340282366920938463463374607431768211455", "18446744073709551617"),
+            ("79228162514264337589248983040", "79228162514264337593543950335"),
+            ("1000000000000000000000000000000000000000000000000000007", "-4294967296000000000001"),
+            ("-6277101735386680763835789423207666416102355444464034512895", "340282366920938463444927863358058659840"),
+        ];
+        for (a, b) in cases {
+            let (a, b) = (big(a), big(b));
+            let (q, r) = a.div_rem(&b).unwrap();
+            assert_eq!(q.mul(&b).add(&r), a);
+            assert!(r.abs() < b.abs() && (r.is_zero() || r.is_negative() == a.is_negative()));
+        }
+        let a = big("123456789012345678901234567890");
+        assert_eq!(a.magnitude_str(10), "123456789012345678901234567890");
+        assert_eq!(a.magnitude_str(16), "18ee90ff6c373e0ee4e3f0ad2");
+        assert_eq!(a.magnitude_str(2).len() as u64, a.bits());
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        let digits = "9".repeat(MAX_INTEGER_DIGITS);
+        assert!(BigInt::parse(&digits).is_some());
+        assert!(BigInt::parse(&format!("-000{}", digits)).is_some());
+        assert!(BigInt::parse(&format!("1{}", digits)).is_none());
+        assert!(BigInt::is_digits("+12") && !BigInt::is_digits("-") && !BigInt::is_digits("1.5"));
+    }
+
+    #[test]
+    fn test_i64_round_trip() {
+        for n in [0, 1, -1, i64::MAX, i64::MIN, 1 << 32, -(1 << 40)] {
+            let b = BigInt::from(n);
+            assert_eq!(b.to_i64(), Some(n));
+            assert_eq!(b.to_string(), n.to_string());
+        }
+        assert_eq!(BigInt::from(i64::MAX).add(&BigInt::from(1)).to_i64(), None);
+        assert_eq!(big("-0"), BigInt::default());
+        assert!(BigInt::parse("1e3").is_none());
+    }
+
+    #[test]
+    fn test_from_f64() {
+        for x in [0.0, -0.0, 1.0, -7.0, 4503599627370496.0, 9007199254740993.0, -9.3e18, 1e300, f64::MAX] {
+            let n = BigInt::from_f64(x).unwrap();
+            assert_eq!(n.to_f64(), x);
+        }
+        assert_eq!(BigInt::from_f64(2f64.powi(64)).unwrap().to_string(), "18446744073709551616");
+        assert_eq!(BigInt::from_f64(-1e20).unwrap().to_string(), "-100000000000000000000");
+        for x in [0.5, -1.5, f64::INFINITY, f64::NAN] {
+            assert!(BigInt::from_f64(x).is_none());
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Message passing between Sui programs on different threads
+//!
+//! Programs sharing a [`Channels`] exchange values with
+//! `R v0 "chan.send" ch value` and `R v0 "chan.recv" ch`, where `ch` is any
+//! integer naming a channel. Channels are created on first use and hold at
+//! most the capacity given to [`Channels::new`]: `send` waits while its
+//! channel is full and `recv` while it is empty. [`spawn_program`] runs a
+//! program on a thread of its own.
+//!
+//! A program without shared channels has channels of its own, which nothing
+//! else can fill or empty, so there `send` on a full channel and `recv` on
+//! an empty one fail instead of waiting forever.
+//!
+//! ```rust
+//! use sui_lang::interpreter::{spawn_program, Channels, Interpreter};
+//!
+//! let channels = Channels::default();
+//! let worker = spawn_program("R v0 \"chan.recv\" 0\n* v0 v0 2\nR v1 \"chan.send\" 1 v0", &[], &channels);
+//!
+//! let mut interp = Interpreter::new();
+//! interp.set_channels(channels);
+//! let output = interp.run("R v0 \"chan.send\" 0 21\nR v1 \"chan.recv\" 1\n. v1", &[]).unwrap();
+//! assert_eq!(output, ["42"]);
+//! worker.join().unwrap().unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::TrySendError;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::{Interpreter, InterpreterError, Value};
+
+/// Values a channel holds before `send` waits, unless chosen otherwise
+pub const DEFAULT_CAPACITY: usize = 16;
+
+/// One queue; both ends stay open as long as the hub exists
+struct Channel {
+    sender: SyncSender<Value>,
+    receiver: Mutex<Receiver<Value>>,
+}
+
+/// Bounded channels shared by programs, by integer ID
+///
+/// Cloning gives another handle to the same channels.
+#[derive(Clone)]
+pub struct Channels {
+    channels: Arc<Mutex<HashMap<i64, Arc<Channel>>>>,
+    capacity: usize,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Channels {
+    /// Channels holding up to `capacity` values each; with 0, `send` waits
+    /// for a matching `recv`
+    pub fn new(capacity: usize) -> Self {
+        Self { channels: Arc::default(), capacity }
+    }
+
+    fn channel(&self, id: i64) -> Arc<Channel> {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        let channel = channels.entry(id).or_insert_with(|| {
+            let (sender, receiver) = sync_channel(self.capacity);
+            Arc::new(Channel { sender, receiver: Mutex::new(receiver) })
+        });
+        Arc::clone(channel)
+    }
+
+    /// Queue `value` on channel `id`, waiting while it is full
+    pub fn send(&self, id: i64, value: Value) {
+        // The channel keeps its receiver, so sending cannot fail
+        let _ = self.channel(id).sender.send(value);
+    }
+
+    /// Queue `value` on channel `id` if it has room, or give it back
+    pub fn try_send(&self, id: i64, value: Value) -> Result<(), Value> {
+        match self.channel(id).sender.try_send(value) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(value) | TrySendError::Disconnected(value)) => Err(value),
+        }
+    }
+
+    /// Queue `value` on channel `id`, waiting at most `timeout` for room;
+    /// gives the value back if there is none by then
+    pub fn send_timeout(&self, id: i64, mut value: Value, timeout: Duration) -> Result<(), Value> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.try_send(id, value) {
+                Ok(()) => return Ok(()),
+                Err(unsent) if Instant::now() >= deadline => return Err(unsent),
+                Err(unsent) => value = unsent,
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Take the oldest value from channel `id`, waiting while it is empty
+    pub fn recv(&self, id: i64) -> Value {
+        let channel = self.channel(id);
+        let receiver = channel.receiver.lock().unwrap_or_else(|e| e.into_inner());
+        receiver.recv().unwrap_or_default()
+    }
+
+    /// Take the oldest value from channel `id`, if any
+    pub fn try_recv(&self, id: i64) -> Option<Value> {
+        let channel = self.channel(id);
+        let receiver = channel.receiver.lock().unwrap_or_else(|e| e.into_inner());
+        receiver.try_recv().ok()
+    }
+
+    /// Take the oldest value from channel `id`, waiting at most `timeout`
+    /// for one
+    pub fn recv_timeout(&self, id: i64, timeout: Duration) -> Option<Value> {
+        let channel = self.channel(id);
+        let receiver = channel.receiver.lock().unwrap_or_else(|e| e.into_inner());
+        receiver.recv_timeout(timeout).ok()
+    }
+}
+
+/// Run `code` on a new thread with access to `channels`
+///
+/// The thread's result is the program's output, as from
+/// [`Interpreter::run`].
+pub fn spawn_program(
+    code: &str,
+    args: &[String],
+    channels: &Channels,
+) -> JoinHandle<Result<Vec<String>, InterpreterError>> {
+    let (code, args, channels) = (code.to_string(), args.to_vec(), channels.clone());
+    thread::spawn(move || {
+        let mut interp = Interpreter::new();
+        interp.set_channels(channels);
+        interp.run(&code, &args)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn test_producers_and_consumer() {
+        let channels = Channels::new(2);
+        // Each producer sends its argument three times, then -1
+        let producer = "= v0 0\n: 0\nR v1 \"chan.send\" 7 g101\n+ v0 v0 1\n< v2 v0 3\n? v2 0\nR v1 \"chan.send\" 7 -1";
+        let workers: Vec<_> =
+            ["1", "10"].iter().map(|n| spawn_program(producer, &[n.to_string()], &channels)).collect();
+
+        // Sum until both producers are done
+        let consumer = "= v0 0\n= v1 0\n: 0\nR v2 \"chan.recv\" 7\n~ v3 v2 -1\n? v3 1\n+ v0 v0 v2\n@ 0\n: 1\n+ v1 v1 1\n< v3 v1 2\n? v3 0\n. v0";
+        let mut interp = Interpreter::new();
+        interp.set_channels(channels.clone());
+        assert_eq!(interp.run(consumer, &[]).unwrap(), ["33"]);
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+        assert_eq!(channels.try_recv(7), None);
+    }
+
+    #[test]
+    fn test_private_channels() {
+        // Without shared channels, a program gets its own
+        let mut interp = Interpreter::new();
+        let output = interp.run("R v0 \"chan.send\" 0 \"hi\"\nR v1 \"chan.recv\" 0\n. v1", &[]).unwrap();
+        assert_eq!(output, ["hi"]);
+
+        // which nothing else can fill or empty
+        let err = interp.run("R v0 \"chan.recv\" 0", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at line 1: chan.recv: channel 0 is empty and nothing else can send to it");
+        let fill = format!("= v0 0\n: 0\nR v1 \"chan.send\" 3 v0\n+ v0 v0 1\n< v2 v0 {}\n? v2 0", DEFAULT_CAPACITY + 1);
+        let err = interp.run(&fill, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at line 3: chan.send: channel 3 is full and nothing else can receive from it");
+    }
+
+    #[test]
+    fn test_shared_wait_calls_step_hook() {
+        let timeout = |interp: &mut Interpreter| {
+            interp.set_step_hook(1000, |ctx| {
+                if ctx.elapsed > Duration::from_millis(50) { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            })
+        };
+        let mut interp = Interpreter::new();
+        interp.set_channels(Channels::default());
+        timeout(&mut interp);
+        assert!(matches!(interp.run("R v0 \"chan.recv\" 0", &[]), Err(InterpreterError::Interrupted { line: 1 })));
+
+        let mut interp = Interpreter::new();
+        interp.set_channels(Channels::new(1));
+        timeout(&mut interp);
+        let result = interp.run("R v0 \"chan.send\" 0 1\nR v0 \"chan.send\" 0 2", &[]);
+        assert!(matches!(result, Err(InterpreterError::Interrupted { line: 2 })));
+    }
+}
@@ -0,0 +1,371 @@
+//! Precompiled programs for warm starts
+//!
+//! `sui compile` parses a program and the modules it imports once and
+//! saves the instructions and function table as a `.suic` file.
+//! [`Interpreter::run_compiled_file`](super::Interpreter::run_compiled_file)
+//! runs it without lexing or parsing, which saves the startup cost when a
+//! service runs the same program with many different arguments.
+//!
+//! The file is only read back by the `sui` version that wrote it.
+
+use super::trace::{put_len, put_str, Reader};
+use super::{Function, Instruction};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Leading bytes of a compiled program, with the format version
+const MAGIC: &[u8; 8] = b"SUICPROG";
+const VERSION: u32 = 1;
+
+/// A parsed program with the functions of the modules it imports
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledProgram {
+    /// Source of the program, for traces and run logs
+    pub source: String,
+    /// Top-level instructions; imports are already resolved and left as
+    /// comments
+    pub instructions: Vec<Instruction>,
+    /// Source line (1-based) of each of `instructions`
+    pub lines: Vec<usize>,
+    /// Every function the program can call, in ID order
+    pub functions: Vec<Arc<Function>>,
+}
+
+impl CompiledProgram {
+    /// Program of `source` with its main `instructions` numbered by source
+    /// line and the `functions` loaded for it
+    pub fn new(source: String, numbered: Vec<(usize, Instruction)>, functions: Vec<Arc<Function>>) -> Self {
+        let (lines, instructions) = numbered.into_iter().map(|(line, instr)| (line, without_import(instr))).unzip();
+        let mut functions = functions;
+        functions.sort_by_key(|func| func.id);
+        CompiledProgram { source, instructions, lines, functions }
+    }
+
+    /// Binary form written by [`CompiledProgram::save`]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        put_str(&mut out, env!("CARGO_PKG_VERSION"));
+        put_str(&mut out, &self.source);
+        put_block(&mut out, &self.instructions, &self.lines);
+        put_len(&mut out, self.functions.len());
+        for func in &self.functions {
+            out.extend_from_slice(&func.id.to_le_bytes());
+            out.extend_from_slice(&func.arg_count.to_le_bytes());
+            put_block(&mut out, &func.body, &func.lines);
+        }
+        out
+    }
+
+    /// Parse the output of [`CompiledProgram::encode`]; `None` if it is
+    /// malformed or was written by another version
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC
+            || reader.u32()? != VERSION
+            || reader.string()? != env!("CARGO_PKG_VERSION")
+        {
+            return None;
+        }
+        let source = reader.string()?;
+        let (instructions, lines) = block(&mut reader)?;
+        let mut functions = Vec::new();
+        for _ in 0..reader.len()? {
+            let id = reader.i64()?;
+            let arg_count = reader.i64()?;
+            let (body, lines) = block(&mut reader)?;
+            let mut func = Function::new(id, arg_count, body);
+            func.lines = lines;
+            functions.push(Arc::new(func));
+        }
+        (reader.pos == bytes.len()).then_some(CompiledProgram { source, instructions, lines, functions })
+    }
+
+    /// Write the program to `path`
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    /// Read a program written by [`CompiledProgram::save`]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::decode(&bytes).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "malformed compiled program, or compiled by another version")
+        })
+    }
+}
+
+/// Imports were loaded at compile time; running them again would read the
+/// modules
+fn without_import(instr: Instruction) -> Instruction {
+    match instr {
+        Instruction::Import { .. } => Instruction::Comment,
+        other => other,
+    }
+}
+
+fn put_strs(out: &mut Vec<u8>, strings: &[String]) {
+    put_len(out, strings.len());
+    for s in strings {
+        put_str(out, s);
+    }
+}
+
+fn put_block(out: &mut Vec<u8>, instructions: &[Instruction], lines: &[usize]) {
+    put_len(out, instructions.len());
+    for instr in instructions {
+        put_instruction(out, instr);
+    }
+    put_len(out, lines.len());
+    for line in lines {
+        put_len(out, *line);
+    }
+}
+
+/// Binary operators, by their tag minus [`BINARY`]
+const BINARY: u8 = 3;
+
+fn put_instruction(out: &mut Vec<u8>, instr: &Instruction) {
+    let binary = |tag: u8, out: &mut Vec<u8>, fields: [&String; 3]| {
+        out.push(BINARY + tag);
+        fields.iter().for_each(|field| put_str(out, field));
+    };
+    match instr {
+        Instruction::Import { path } => {
+            out.push(0);
+            put_str(out, path);
+        }
+        Instruction::Const { id, value } => {
+            out.push(1);
+            out.extend_from_slice(&id.to_le_bytes());
+            put_str(out, value);
+        }
+        Instruction::Assign { target, value } => {
+            out.push(2);
+            put_str(out, target);
+            put_str(out, value);
+        }
+        Instruction::Add { result, a, b } => binary(0, out, [result, a, b]),
+        Instruction::Sub { result, a, b } => binary(1, out, [result, a, b]),
+        Instruction::Mul { result, a, b } => binary(2, out, [result, a, b]),
+        Instruction::Div { result, a, b } => binary(3, out, [result, a, b]),
+        Instruction::Mod { result, a, b } => binary(4, out, [result, a, b]),
+        Instruction::Lt { result, a, b } => binary(5, out, [result, a, b]),
+        Instruction::Gt { result, a, b } => binary(6, out, [result, a, b]),
+        Instruction::Le { result, a, b } => binary(7, out, [result, a, b]),
+        Instruction::Ge { result, a, b } => binary(8, out, [result, a, b]),
+        Instruction::Eq { result, a, b } => binary(9, out, [result, a, b]),
+        Instruction::And { result, a, b } => binary(10, out, [result, a, b]),
+        Instruction::Or { result, a, b } => binary(11, out, [result, a, b]),
+        Instruction::Not { result, a } => {
+            out.push(15);
+            put_str(out, result);
+            put_str(out, a);
+        }
+        Instruction::CondJump { cond, label } => {
+            out.push(16);
+            put_str(out, cond);
+            out.extend_from_slice(&label.to_le_bytes());
+        }
+        Instruction::Jump { label } => {
+            out.push(17);
+            out.extend_from_slice(&label.to_le_bytes());
+        }
+        Instruction::Switch { value, labels } => {
+            out.push(18);
+            put_str(out, value);
+            put_len(out, labels.len());
+            labels.iter().for_each(|label| out.extend_from_slice(&label.to_le_bytes()));
+        }
+        Instruction::Label { id } => {
+            out.push(19);
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        Instruction::FuncDef { id, argc } => {
+            out.push(20);
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&argc.to_le_bytes());
+        }
+        Instruction::FuncEnd => out.push(21),
+        Instruction::Call { result, func_id, args } => {
+            out.push(22);
+            put_str(out, result);
+            out.extend_from_slice(&func_id.to_le_bytes());
+            put_strs(out, args);
+        }
+        Instruction::Return { value } => {
+            out.push(23);
+            put_str(out, value);
+        }
+        Instruction::Try { err, label } => {
+            out.push(24);
+            put_str(out, err);
+            out.extend_from_slice(&label.to_le_bytes());
+        }
+        Instruction::EndTry => out.push(25),
+        Instruction::Raise { value } => {
+            out.push(26);
+            put_str(out, value);
+        }
+        Instruction::ArrayCreate { var, size } => {
+            out.push(27);
+            put_str(out, var);
+            put_str(out, size);
+        }
+        Instruction::ArrayRead { result, arr, idx, indices } => {
+            out.push(28);
+            put_str(out, result);
+            put_str(out, arr);
+            put_str(out, idx);
+            put_strs(out, indices);
+        }
+        Instruction::ArrayWrite { arr, idx, indices, value } => {
+            out.push(29);
+            put_str(out, arr);
+            put_str(out, idx);
+            put_strs(out, indices);
+            put_str(out, value);
+        }
+        Instruction::Iter { iter, source } => {
+            out.push(30);
+            put_str(out, iter);
+            put_str(out, source);
+        }
+        Instruction::Next { done, var, iter } => {
+            out.push(31);
+            put_str(out, done);
+            put_str(out, var);
+            put_str(out, iter);
+        }
+        Instruction::Output { value } => {
+            out.push(32);
+            put_str(out, value);
+        }
+        Instruction::Input { var } => {
+            out.push(33);
+            put_str(out, var);
+        }
+        Instruction::RustFFI { result, func, args } => {
+            out.push(34);
+            put_str(out, result);
+            put_str(out, func);
+            put_strs(out, args);
+        }
+        // A program that parsed has no invalid lines
+        Instruction::Comment | Instruction::Empty | Instruction::Invalid { .. } => out.push(35),
+    }
+}
+
+fn strings(reader: &mut Reader) -> Option<Vec<String>> {
+    let n = reader.len()?;
+    // Every string takes at least eight bytes
+    if n > reader.bytes.len() - reader.pos {
+        return None;
+    }
+    (0..n).map(|_| reader.string()).collect()
+}
+
+fn block(reader: &mut Reader) -> Option<(Vec<Instruction>, Vec<usize>)> {
+    let n = reader.len()?;
+    // Every instruction takes at least one byte
+    if n > reader.bytes.len() - reader.pos {
+        return None;
+    }
+    let instructions = (0..n).map(|_| instruction(reader)).collect::<Option<Vec<_>>>()?;
+    let n = reader.len()?;
+    if n > reader.bytes.len() - reader.pos {
+        return None;
+    }
+    let lines = (0..n).map(|_| reader.len()).collect::<Option<Vec<_>>>()?;
+    Some((instructions, lines))
+}
+
+fn instruction(reader: &mut Reader) -> Option<Instruction> {
+    let tag = reader.u8()?;
+    if (BINARY..BINARY + 12).contains(&tag) {
+        let (result, a, b) = (reader.string()?, reader.string()?, reader.string()?);
+        return Some(match tag - BINARY {
+            0 => Instruction::Add { result, a, b },
+            1 => Instruction::Sub { result, a, b },
+            2 => Instruction::Mul { result, a, b },
+            3 => Instruction::Div { result, a, b },
+            4 => Instruction::Mod { result, a, b },
+            5 => Instruction::Lt { result, a, b },
+            6 => Instruction::Gt { result, a, b },
+            7 => Instruction::Le { result, a, b },
+            8 => Instruction::Ge { result, a, b },
+            9 => Instruction::Eq { result, a, b },
+            10 => Instruction::And { result, a, b },
+            _ => Instruction::Or { result, a, b },
+        });
+    }
+    Some(match tag {
+        0 => Instruction::Import { path: reader.string()? },
+        1 => Instruction::Const { id: reader.i64()?, value: reader.string()? },
+        2 => Instruction::Assign { target: reader.string()?, value: reader.string()? },
+        15 => Instruction::Not { result: reader.string()?, a: reader.string()? },
+        16 => Instruction::CondJump { cond: reader.string()?, label: reader.i64()? },
+        17 => Instruction::Jump { label: reader.i64()? },
+        18 => {
+            let value = reader.string()?;
+            let n = reader.len()?;
+            if n > reader.bytes.len() - reader.pos {
+                return None;
+            }
+            let labels = (0..n).map(|_| reader.i64()).collect::<Option<Vec<_>>>()?;
+            Instruction::Switch { value, labels }
+        }
+        19 => Instruction::Label { id: reader.i64()? },
+        20 => Instruction::FuncDef { id: reader.i64()?, argc: reader.i64()? },
+        21 => Instruction::FuncEnd,
+        22 => Instruction::Call { result: reader.string()?, func_id: reader.i64()?, args: strings(reader)? },
+        23 => Instruction::Return { value: reader.string()? },
+        24 => Instruction::Try { err: reader.string()?, label: reader.i64()? },
+        25 => Instruction::EndTry,
+        26 => Instruction::Raise { value: reader.string()? },
+        27 => Instruction::ArrayCreate { var: reader.string()?, size: reader.string()? },
+        28 => Instruction::ArrayRead {
+            result: reader.string()?,
+            arr: reader.string()?,
+            idx: reader.string()?,
+            indices: strings(reader)?,
+        },
+        29 => Instruction::ArrayWrite {
+            arr: reader.string()?,
+            idx: reader.string()?,
+            indices: strings(reader)?,
+            value: reader.string()?,
+        },
+        30 => Instruction::Iter { iter: reader.string()?, source: reader.string()? },
+        31 => Instruction::Next { done: reader.string()?, var: reader.string()?, iter: reader.string()? },
+        32 => Instruction::Output { value: reader.string()? },
+        33 => Instruction::Input { var: reader.string()? },
+        34 => Instruction::RustFFI { result: reader.string()?, func: reader.string()?, args: strings(reader)? },
+        35 => Instruction::Comment,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Parser;
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let code = "_ \"lib.sui\"\n# 0 1 {\n] v0 a0 0 1\n{ v1 0 v0\n^ v1\n}\n[ g0 2\n$ g1 0 g0\nJ g1 1 2\n: 1\n. \"x y\"";
+        let (numbered, functions) = Parser::parse_numbered(code, crate::interpreter::LANGUAGE_VERSION).unwrap();
+        let program =
+            CompiledProgram::new(code.to_string(), numbered, functions.into_iter().map(Arc::new).collect());
+        assert_eq!(program.instructions[0], Instruction::Comment);
+        assert_eq!(program.lines[..2], [1, 7]);
+
+        let bytes = program.encode();
+        assert_eq!(CompiledProgram::decode(&bytes), Some(program));
+        assert_eq!(CompiledProgram::decode(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(CompiledProgram::decode(b"SUICPROG"), None);
+    }
+}
@@ -0,0 +1,426 @@
+//! Formatting for the `fmt`, `format` and `date.format` builtins
+
+use super::Value;
+
+/// Widest field a format spec may ask for
+pub(crate) const MAX_WIDTH: usize = 10_000;
+
+/// Largest precision a format spec may ask for
+pub(crate) const MAX_PRECISION: usize = 100;
+
+/// Fill the `{}` fields of `template` with `args`, like Python's
+/// `str.format`
+///
+/// `{}` takes the next argument and `{n}` argument n; `{{` and `}}` are
+/// literal braces. A field may end in a format spec, `{:>8.2f}`, with the
+/// parts `[[fill]align][sign][0][width][,][.precision][type]`: align is
+/// `<`, `>` or `^`, sign `+`, `-` or space, and type `s`, `d`, `f`, `e`,
+/// `%`, `x`, `o` or `b`. Without a type, precision counts significant
+/// digits of floats and characters of strings, as in Python. Fields Python
+/// would refuse, such as `{!r}`, `{:g}` or `{:d}` of a float, are errors.
+pub(crate) fn format_template(template: &str, args: &[Value]) -> Result<String, String> {
+    let mut result = String::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let field: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (index, spec) = field_parts(&field)?;
+                let index = index.unwrap_or_else(|| {
+                    next += 1;
+                    next - 1
+                });
+                match args.get(index) {
+                    Some(arg) => result.push_str(&format_value(arg, spec)?),
+                    None => {
+                        parse_spec(spec)?;
+                    }
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    Ok(result)
+}
+
+/// Check the fields of a template before any argument is known
+pub(crate) fn check_template(template: &str) -> Result<(), String> {
+    format_template(template, &[]).map(|_| ())
+}
+
+/// Argument index, if given, and format spec of the field `{field}`
+fn field_parts(field: &str) -> Result<(Option<usize>, &str), String> {
+    let (index, spec) = field.split_once(':').unwrap_or((field, ""));
+    let index = match index.trim() {
+        "" => None,
+        index => Some(index.parse::<usize>().map_err(|_| format!("invalid format field '{{{}}}'", field))?),
+    };
+    Ok((index, spec))
+}
+
+/// Parts of a format spec
+#[derive(Debug, Default, PartialEq)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: Option<char>,
+    zero: bool,
+    width: usize,
+    grouping: bool,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+/// Parse `[[fill]align][sign][0][width][,][.precision][type]`
+fn parse_spec(spec: &str) -> Result<Spec, String> {
+    let invalid = |why: &str| format!("format spec '{}' {}", spec, why);
+    let chars: Vec<char> = spec.chars().collect();
+    let mut parsed = Spec::default();
+    let mut i = 0;
+    let is_align = |c: Option<&char>| matches!(c, Some('<' | '>' | '^'));
+    if is_align(chars.get(1)) {
+        parsed.fill = Some(chars[0]);
+        parsed.align = Some(chars[1]);
+        i = 2;
+    } else if is_align(chars.first()) {
+        parsed.align = Some(chars[0]);
+        i = 1;
+    }
+    if let Some(&c @ ('+' | '-' | ' ')) = chars.get(i) {
+        parsed.sign = Some(c);
+        i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        parsed.zero = true;
+        i += 1;
+    }
+    // Too many digits for a usize are still too many
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+            *i += 1;
+        }
+        (*i > start).then(|| chars[start..*i].iter().collect::<String>().parse::<usize>().unwrap_or(usize::MAX))
+    };
+    parsed.width = digits(&mut i).unwrap_or(0);
+    if chars.get(i) == Some(&',') {
+        parsed.grouping = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        parsed.precision = Some(digits(&mut i).ok_or_else(|| invalid("is invalid"))?);
+    }
+    if let Some(&c @ ('s' | 'd' | 'f' | 'e' | '%' | 'x' | 'o' | 'b')) = chars.get(i) {
+        parsed.kind = Some(c);
+        i += 1;
+    }
+    if i != chars.len() {
+        return Err(invalid("is invalid"));
+    }
+    if parsed.width > MAX_WIDTH {
+        return Err(invalid(&format!("is wider than {} characters", MAX_WIDTH)));
+    }
+    if parsed.precision.is_some_and(|p| p > MAX_PRECISION) {
+        return Err(invalid(&format!("has a precision over {}", MAX_PRECISION)));
+    }
+    Ok(parsed)
+}
+
+impl Spec {
+    /// Whether Python accepts this spec for a value of this type
+    fn applies_to(&self, value: &Value) -> bool {
+        match value {
+            Value::String(_) => matches!(self.kind, None | Some('s')) && self.sign.is_none() && !self.grouping,
+            Value::Integer(_) | Value::BigInt(_) => match self.kind {
+                None | Some('d') => self.precision.is_none(),
+                Some('x' | 'o' | 'b') => self.precision.is_none() && !self.grouping,
+                Some(kind) => kind != 's',
+            },
+            Value::Float(_) => matches!(self.kind, None | Some('f' | 'e' | '%')),
+            _ => false,
+        }
+    }
+}
+
+/// Format one value by a spec
+pub(crate) fn format_value(value: &Value, spec: &str) -> Result<String, String> {
+    if spec.is_empty() {
+        return Ok(value.to_string());
+    }
+    let written = spec;
+    let spec = parse_spec(spec)?;
+    if !spec.applies_to(value) {
+        return Err(format!("format spec '{}' does not apply to type {}", written, value.type_name()));
+    }
+    if let Value::String(_) = value {
+        let mut text = value.to_string();
+        if let Some(precision) = spec.precision {
+            text = text.chars().take(precision).collect();
+        }
+        return Ok(pad(&text, &spec, '<'));
+    }
+
+    let (negative, mut digits) = match (spec.kind, value) {
+        (Some('f' | 'e' | '%'), _) | (None, Value::Float(_)) => {
+            let x = value.to_float();
+            let precision = spec.precision.unwrap_or(6);
+            let digits = if !x.is_finite() {
+                if x.is_nan() { "nan".to_string() } else { "inf".to_string() }
+            } else {
+                match (spec.kind, spec.precision) {
+                    (Some('f'), _) => format!("{:.*}", precision, x.abs()),
+                    (Some('%'), _) => format!("{:.*}", precision, x.abs() * 100.0),
+                    (Some(_), _) => scientific(x.abs(), precision),
+                    (None, Some(precision)) => general(x.abs(), precision),
+                    (None, None) => Value::Float(x.abs()).to_string(),
+                }
+            };
+            let digits = if spec.kind == Some('%') { digits + "%" } else { digits };
+            (x.is_sign_negative() && !x.is_nan(), digits)
+        }
+        (kind, Value::BigInt(n)) => {
+            let radix = match kind {
+                Some('x') => 16,
+                Some('o') => 8,
+                Some('b') => 2,
+                _ => 10,
+            };
+            (n.is_negative(), n.magnitude_str(radix))
+        }
+        (kind, _) => {
+            let n = value.to_int();
+            let digits = match kind {
+                Some('x') => format!("{:x}", n.unsigned_abs()),
+                Some('o') => format!("{:o}", n.unsigned_abs()),
+                Some('b') => format!("{:b}", n.unsigned_abs()),
+                _ => n.unsigned_abs().to_string(),
+            };
+            (n < 0, digits)
+        }
+    };
+    if spec.grouping && digits.starts_with(|c: char| c.is_ascii_digit()) {
+        let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        let (int, rest) = digits.split_at(end);
+        let mut grouped = String::new();
+        for (k, c) in int.chars().enumerate() {
+            if k > 0 && (int.len() - k) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(c);
+        }
+        digits = grouped + rest;
+    }
+    let sign = match (negative, spec.sign) {
+        (true, _) => "-",
+        (false, Some('+')) => "+",
+        (false, Some(' ')) => " ",
+        _ => "",
+    };
+    // `0` pads between the sign and the digits
+    if spec.zero && spec.align.is_none() {
+        let zeros = spec.width.saturating_sub(sign.len() + digits.chars().count());
+        return Ok(format!("{}{}{}", sign, "0".repeat(zeros), digits));
+    }
+    Ok(pad(&format!("{}{}", sign, digits), &spec, '>'))
+}
+
+/// Pad `text` to the spec's width
+fn pad(text: &str, spec: &Spec, default_align: char) -> String {
+    let fill = spec.fill.unwrap_or(if spec.zero { '0' } else { ' ' });
+    let missing = spec.width.saturating_sub(text.chars().count());
+    let left = match spec.align.unwrap_or(default_align) {
+        '>' => missing,
+        '^' => missing / 2,
+        _ => 0,
+    };
+    let fill = |n: usize| fill.to_string().repeat(n);
+    format!("{}{}{}", fill(left), text, fill(missing - left))
+}
+
+/// Python's float formatting with a precision and no type: `precision`
+/// significant digits, with at least one after the point, or exponent
+/// notation when that cannot show the number
+fn general(x: f64, precision: usize) -> String {
+    let precision = precision.max(1);
+    let scientific = scientific(x, precision - 1);
+    let (mantissa, exponent_text) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i64 = exponent_text.parse().unwrap_or(0);
+    if (-4..precision as i64 - 1).contains(&exponent) {
+        let fixed = format!("{:.*}", (precision as i64 - 1 - exponent) as usize, x);
+        let fixed = if fixed.contains('.') { fixed.trim_end_matches('0').trim_end_matches('.') } else { &fixed };
+        if fixed.contains('.') { fixed.to_string() } else { format!("{}.0", fixed) }
+    } else {
+        let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+        format!("{}e{}", mantissa, exponent_text)
+    }
+}
+
+/// `x` with `precision` digits after the point of the mantissa and an
+/// exponent of at least two digits, as Python's `e` type writes it
+fn scientific(x: f64, precision: usize) -> String {
+    let text = format!("{:.*e}", precision, x);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i64 = exponent.parse().unwrap_or(0);
+    format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+    "December",
+];
+
+/// Format a time, in seconds since 1970 in UTC, with strftime directives
+///
+/// Supported: `%Y %y %m %d %H %I %M %S %p %j %a %A %b %B %F %T %%`; other
+/// directives are kept as they are.
+pub(crate) fn format_date(timestamp: f64, pattern: &str) -> String {
+    let seconds = if timestamp.is_finite() { timestamp.floor() as i64 } else { 0 };
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (time / 3600, time / 60 % 60, time % 60);
+    let yday = days - days_from_civil(year, 1, 1) + 1;
+    // 1970-01-01 was a Thursday
+    let weekday = WEEKDAYS[(days + 3).rem_euclid(7) as usize];
+    let month_name = MONTHS[month as usize - 1];
+
+    let mut result = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        let Some(directive) = chars.next() else {
+            result.push('%');
+            break;
+        };
+        let text = match directive {
+            'Y' => year.to_string(),
+            'y' => format!("{:02}", year.rem_euclid(100)),
+            'm' => format!("{:02}", month),
+            'd' => format!("{:02}", day),
+            'H' => format!("{:02}", hour),
+            'I' => format!("{:02}", (hour + 11) % 12 + 1),
+            'M' => format!("{:02}", minute),
+            'S' => format!("{:02}", second),
+            'p' => (if hour < 12 { "AM" } else { "PM" }).to_string(),
+            'j' => format!("{:03}", yday),
+            'a' => weekday[..3].to_string(),
+            'A' => weekday.to_string(),
+            'b' => month_name[..3].to_string(),
+            'B' => month_name.to_string(),
+            'F' => format!("{}-{:02}-{:02}", year, month, day),
+            'T' => format!("{:02}:{:02}:{:02}", hour, minute, second),
+            '%' => "%".to_string(),
+            other => format!("%{}", other),
+        };
+        result.push_str(&text);
+    }
+    result
+}
+
+/// Year, month and day of a day counted from 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Days from 1970-01-01 to a date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_specs() {
+        let cases: &[(Value, &str, &str)] = &[
+            (Value::Float(1.23456), ".2f", "1.23"),
+            (Value::Integer(5), "08.3f", "0005.000"),
+            (Value::Integer(-42), "06d", "-00042"),
+            (Value::Integer(42), "+d", "+42"),
+            (Value::Integer(1234567), ",", "1,234,567"),
+            (Value::Float(1234.5), ",.1f", "1,234.5"),
+            (Value::Integer(255), "#>6x", "####ff"),
+            (Value::Integer(-5), "b", "-101"),
+            (Value::from("ab"), "^6", "  ab  "),
+            (Value::from("abcdef"), "*<5.3", "abc**"),
+            (Value::Integer(7), "<3", "7  "),
+            (Value::Float(1.23456), ".3", "1.23"),
+            (Value::Float(1234.5), ".2", "1.2e+03"),
+            (Value::Float(0.00012345), ".2", "0.00012"),
+            (Value::Float(100.0), ".3", "1e+02"),
+            (Value::Float(99.5), ".4", "99.5"),
+            (Value::Float(-0.0), ".1f", "-0.0"),
+            (Value::Float(2.5), ">6", "   2.5"),
+            (Value::Float(2.5), ".0f", "2"),
+            (Value::Float(0.125), ".2f", "0.12"),
+            (Value::Float(1234.5), ".2e", "1.23e+03"),
+            (Value::Integer(25), ".0e", "2e+01"),
+            (Value::Float(0.00012), "e", "1.200000e-04"),
+            (Value::Float(0.256), ".1%", "25.6%"),
+            (Value::Float(f64::INFINITY), "%", "inf%"),
+            (Value::Integer(3), "", "3"),
+        ];
+        for (value, spec, expected) in cases {
+            assert_eq!(format_value(value, spec).unwrap(), *expected, "{:?} with {:?}", value, spec);
+        }
+        let args = [Value::from("total"), Value::Float(9.5)];
+        assert_eq!(format_template("{:<6}|{:>7.2f}|{0}{{}}", &args).unwrap(), "total |   9.50|total{}");
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let cases: &[(Value, &str, &str)] = &[
+            (Value::Integer(3), "?!", "format spec '?!' is invalid"),
+            (Value::Integer(3), "g", "format spec 'g' is invalid"),
+            (Value::Integer(3), "100001", "format spec '100001' is wider than 10000 characters"),
+            (Value::Integer(3), "99999999999999999999999", "format spec '99999999999999999999999' is wider than 10000 characters"),
+            (Value::Float(3.0), ".101f", "format spec '.101f' has a precision over 100"),
+            (Value::Float(3.0), "d", "format spec 'd' does not apply to type float"),
+            (Value::Integer(3), ".2", "format spec '.2' does not apply to type int"),
+            (Value::Integer(3), ",x", "format spec ',x' does not apply to type int"),
+            (Value::from("ab"), "+", "format spec '+' does not apply to type str"),
+            (Value::from(vec![Value::Integer(1)]), ">4", "format spec '>4' does not apply to type array"),
+        ];
+        for (value, spec, expected) in cases {
+            assert_eq!(format_value(value, spec).unwrap_err(), *expected, "{:?} with {:?}", value, spec);
+        }
+        assert_eq!(format_template("{0!r}", &[Value::Integer(1)]).unwrap_err(), "invalid format field '{0!r}'");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0.0, "%Y-%m-%d %H:%M:%S"), "1970-01-01 00:00:00");
+        assert_eq!(format_date(951_827_696.9, "%F %T %a %b %j"), "2000-02-29 12:34:56 Tue Feb 060");
+        assert_eq!(format_date(1_700_000_000.0, "%A %d %B %y, %I:%M %p %% %q"), "Tuesday 14 November 23, 10:13 PM % %q");
+        assert_eq!(format_date(-86_400.0, "%F %A"), "1969-12-31 Wednesday");
+    }
+}
@@ -0,0 +1,630 @@
+//! Instruction metadata shared by the parser and tools
+//!
+//! [`INSTRUCTION_TABLE`] is the single description of every instruction:
+//! its character, operands, documentation and the language version that
+//! introduced it. The parser takes arity from it, and the grammar export,
+//! the LSP, the REPL help and `sui --explain` render it.
+//!
+//! A program can declare the language level it targets with a pragma line
+//! such as `;! sui 1.0`; the parser then rejects instructions introduced
+//! after that level, and programs targeting a level newer than
+//! [`LANGUAGE_VERSION`].
+
+use std::fmt::{self, Write};
+
+/// Language level, such as `1.0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LanguageVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl LanguageVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parse `major.minor`; a bare major version means `major.0`
+    pub fn parse(s: &str) -> Option<Self> {
+        let (major, minor) = s.split_once('.').unwrap_or((s, "0"));
+        Some(Self::new(major.parse().ok()?, minor.parse().ok()?))
+    }
+}
+
+impl fmt::Display for LanguageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Newest language level this implementation supports
+pub const LANGUAGE_VERSION: LanguageVersion = LanguageVersion::new(1, 3);
+
+/// Language level declared by a `;! sui X.Y` pragma line
+///
+/// Returns `None` if the line is not a pragma, and the offending text if
+/// the version does not parse.
+pub fn pragma(line: &str) -> Option<Result<LanguageVersion, String>> {
+    let rest = line.trim().strip_prefix(";!")?.trim_start();
+    let version = rest.strip_prefix("sui")?.trim();
+    Some(LanguageVersion::parse(version).ok_or_else(|| version.to_string()))
+}
+
+/// First language-level pragma in `code`, with its line (1-based)
+pub fn declared_version(code: &str) -> Option<(usize, Result<LanguageVersion, String>)> {
+    code.lines()
+        .enumerate()
+        .find_map(|(i, line)| pragma(line).map(|version| (i + 1, version)))
+}
+
+/// Kind of operand an instruction takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// Variable written by the instruction (`v0`, `g1`)
+    Variable,
+    /// Variable or literal read by the instruction
+    Value,
+    /// Integer constant (label, function ID, argument count)
+    Integer,
+    /// String literal (import path, FFI function name)
+    String,
+    /// Number or string literal (constant value)
+    Literal,
+    /// Any number of values (call and FFI arguments)
+    Values,
+    /// Any number of labels (jump table targets)
+    Labels,
+    /// The `{` that opens a function body
+    OpenBrace,
+}
+
+impl OperandKind {
+    /// Whether the operand repeats any number of times at the end
+    pub fn is_variadic(&self) -> bool {
+        matches!(self, OperandKind::Values | OperandKind::Labels)
+    }
+
+    /// Short description for documentation
+    pub fn describe(&self) -> &'static str {
+        match self {
+            OperandKind::Variable => "variable to write",
+            OperandKind::Value => "variable or literal",
+            OperandKind::Integer => "integer constant",
+            OperandKind::String => "string literal",
+            OperandKind::Literal => "number or string literal",
+            OperandKind::Values => "any number of values",
+            OperandKind::Labels => "any number of labels",
+            OperandKind::OpenBrace => "literal `{`",
+        }
+    }
+}
+
+/// Syntax and documentation of one instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionSyntax {
+    /// Instruction character(s)
+    pub op: &'static str,
+    /// Short name of the instruction
+    pub name: &'static str,
+    /// Operands in order
+    pub operands: &'static [OperandKind],
+    /// Name of each operand, as in the fields of [`Instruction`](super::Instruction)
+    pub params: &'static [&'static str],
+    /// Human-readable title, such as `Addition`
+    pub title: &'static str,
+    /// One-sentence description of what the instruction does
+    pub description: &'static str,
+    /// Language level that introduced the instruction
+    pub since: LanguageVersion,
+}
+
+impl InstructionSyntax {
+    /// Minimum number of operands the parser accepts
+    pub fn min_args(&self) -> usize {
+        self.operands
+            .iter()
+            .filter(|kind| !kind.is_variadic())
+            .count()
+    }
+
+    /// Maximum number of operands, or `None` for variadic instructions
+    pub fn max_args(&self) -> Option<usize> {
+        if self.operands.iter().any(|kind| kind.is_variadic()) {
+            None
+        } else {
+            Some(self.operands.len())
+        }
+    }
+
+    /// Whether the operand at `index` is read
+    ///
+    /// Variadic operands extend past the table; variables updated in place
+    /// are read as well.
+    pub fn reads(&self, index: usize) -> bool {
+        match self.operand(index) {
+            Some(OperandKind::Value | OperandKind::Values) => true,
+            Some(OperandKind::Variable) => self.updates(index),
+            _ => false,
+        }
+    }
+
+    /// Whether the operand at `index` is a variable the instruction assigns
+    pub fn writes(&self, index: usize) -> bool {
+        self.operand(index) == Some(OperandKind::Variable) && !self.updates(index)
+    }
+
+    /// Whether the operand at `index` is a variable updated in place: the
+    /// array of `{` and the iterator of `N`
+    pub fn updates(&self, index: usize) -> bool {
+        matches!((self.op, index), ("{", 0) | ("N", 2))
+    }
+
+    /// Kind of the operand at `index`, repeating a trailing variadic operand
+    pub fn operand(&self, index: usize) -> Option<OperandKind> {
+        match self.operands.get(index) {
+            Some(kind) => Some(*kind),
+            None => self.operands.last().copied().filter(|kind| kind.is_variadic()),
+        }
+    }
+
+    /// Usage line such as `+ result a b` or `$ result func args...`
+    pub fn signature(&self) -> String {
+        let mut signature = self.op.to_string();
+        for (kind, param) in self.operands.iter().zip(self.params) {
+            signature.push(' ');
+            signature.push_str(param);
+            if kind.is_variadic() {
+                signature.push_str("...");
+            }
+        }
+        signature
+    }
+
+    /// Full plain-text documentation, as printed by `sui --explain`
+    pub fn explain(&self) -> String {
+        let mut text = format!(
+            "{} ({}, since sui {})\n\n    {}\n\n{}\n",
+            self.title,
+            self.name,
+            self.since,
+            self.signature(),
+            self.description
+        );
+        if !self.operands.is_empty() {
+            text.push_str("\nOperands:\n");
+            let width = self.params.iter().map(|p| p.len()).max().unwrap_or(0);
+            for (kind, param) in self.operands.iter().zip(self.params) {
+                let _ = writeln!(text, "  {:width$}  {}", param, kind.describe(), width = width);
+            }
+        }
+        text
+    }
+}
+
+/// Look up an instruction by its character
+pub fn find(op: &str) -> Option<&'static InstructionSyntax> {
+    INSTRUCTION_TABLE.iter().find(|syntax| syntax.op == op)
+}
+
+/// Look up an instruction by its character or name (`+` or `add`)
+pub fn lookup(query: &str) -> Option<&'static InstructionSyntax> {
+    let query = query.trim();
+    find(query).or_else(|| {
+        INSTRUCTION_TABLE
+            .iter()
+            .find(|syntax| syntax.name.eq_ignore_ascii_case(query))
+    })
+}
+
+/// Every instruction the parser accepts, in the order of [`Instruction`](super::Instruction)
+pub const INSTRUCTION_TABLE: &[InstructionSyntax] = {
+    use OperandKind::*;
+    const V1_0: LanguageVersion = LanguageVersion::new(1, 0);
+    const V1_1: LanguageVersion = LanguageVersion::new(1, 1);
+    const V1_2: LanguageVersion = LanguageVersion::new(1, 2);
+    const V1_3: LanguageVersion = LanguageVersion::new(1, 3);
+    &[
+        InstructionSyntax {
+            op: "_",
+            name: "import",
+            operands: &[String],
+            params: &["path"],
+            title: "Import",
+            description: "Loads the functions of another Sui file, resolved relative to the importing file.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "C",
+            name: "const",
+            operands: &[Integer, Literal],
+            params: &["constant", "value"],
+            title: "Constant Definition",
+            description: "Defines constant cN; every cN operand is replaced by the literal when the program is parsed.",
+            since: V1_1,
+        },
+        InstructionSyntax {
+            op: "=",
+            name: "assign",
+            operands: &[Variable, Value],
+            params: &["target", "value"],
+            title: "Assignment",
+            description: "Assigns a value to a variable.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "+",
+            name: "add",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Addition",
+            description: "Adds two values and stores in result; strings are concatenated.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "-",
+            name: "sub",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Subtraction",
+            description: "Subtracts b from a and stores in result.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "*",
+            name: "mul",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Multiplication",
+            description: "Multiplies two values and stores in result.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "/",
+            name: "div",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Division",
+            description: "Divides a by b and stores the result as a float.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "%",
+            name: "mod",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Modulo",
+            description: "Computes a mod b and stores in result.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "<",
+            name: "lt",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Less Than",
+            description: "Stores 1 if a < b, else 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: ">",
+            name: "gt",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Greater Than",
+            description: "Stores 1 if a > b, else 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "<=",
+            name: "le",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Less Than or Equal",
+            description: "Stores 1 if a <= b, else 0.",
+            since: V1_3,
+        },
+        InstructionSyntax {
+            op: ">=",
+            name: "ge",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Greater Than or Equal",
+            description: "Stores 1 if a >= b, else 0.",
+            since: V1_3,
+        },
+        InstructionSyntax {
+            op: "~",
+            name: "eq",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Equality",
+            description: "Stores 1 if a == b, else 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "!",
+            name: "not",
+            operands: &[Variable, Value],
+            params: &["result", "a"],
+            title: "Logical NOT",
+            description: "Stores 1 if a is 0, else 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "&",
+            name: "and",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Logical AND",
+            description: "Stores 1 if both values are non-zero, else 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "|",
+            name: "or",
+            operands: &[Variable, Value, Value],
+            params: &["result", "a", "b"],
+            title: "Logical OR",
+            description: "Stores 1 if either value is non-zero, else 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "?",
+            name: "cond_jump",
+            operands: &[Value, Integer],
+            params: &["cond", "label"],
+            title: "Conditional Jump",
+            description: "Jumps to label if cond is non-zero.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "@",
+            name: "jump",
+            operands: &[Integer],
+            params: &["label"],
+            title: "Unconditional Jump",
+            description: "Jumps to the specified label.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "J",
+            name: "switch",
+            operands: &[Value, Labels],
+            params: &["value", "labels"],
+            title: "Jump Table",
+            description: "Jumps to the label at position value in the list, or continues if value is not a position in it.",
+            since: V1_3,
+        },
+        InstructionSyntax {
+            op: ":",
+            name: "label",
+            operands: &[Integer],
+            params: &["label"],
+            title: "Label Definition",
+            description: "Defines a jump target within the current function.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "#",
+            name: "func_def",
+            operands: &[Integer, Integer, OpenBrace],
+            params: &["id", "argc", "{"],
+            title: "Function Definition",
+            description: "Defines a function with the given id and argument count.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "}",
+            name: "func_end",
+            operands: &[],
+            params: &[],
+            title: "Function End",
+            description: "Ends a function definition.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "$",
+            name: "call",
+            operands: &[Variable, Integer, Values],
+            params: &["result", "func", "args"],
+            title: "Function Call",
+            description: "Calls a function with the given arguments and stores its return value.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "^",
+            name: "return",
+            operands: &[Value],
+            params: &["value"],
+            title: "Return",
+            description: "Returns a value from the current function.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "T",
+            name: "try",
+            operands: &[Variable, Integer],
+            params: &["err", "label"],
+            title: "Try",
+            description: "Starts a try region: until U, an error (raised or from the runtime) stores its \
+                          value in err and jumps to label, unwinding function calls.",
+            since: V1_2,
+        },
+        InstructionSyntax {
+            op: "U",
+            name: "end_try",
+            operands: &[],
+            params: &[],
+            title: "End Try",
+            description: "Ends the innermost try region of the current function.",
+            since: V1_2,
+        },
+        InstructionSyntax {
+            op: "E",
+            name: "raise",
+            operands: &[Value],
+            params: &["value"],
+            title: "Raise",
+            description: "Raises an error carrying value; without a try region the program stops.",
+            since: V1_2,
+        },
+        InstructionSyntax {
+            op: "[",
+            name: "array_create",
+            operands: &[Variable, Value],
+            params: &["var", "size"],
+            title: "Array Create",
+            description: "Creates an array of the given size filled with 0.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "]",
+            name: "array_read",
+            operands: &[Variable, Value, Value, Values],
+            params: &["result", "arr", "idx", "indices"],
+            title: "Array Read",
+            description: "Reads the value at an array index; further indices read nested arrays.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "{",
+            name: "array_write",
+            operands: &[Variable, Value, Value, Values],
+            params: &["arr", "idx", "value", "more"],
+            title: "Array Write",
+            description: "Writes a value to an array index; with more operands the last is the value and the \
+                          others index nested arrays.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "I",
+            name: "iter",
+            operands: &[Variable, Value],
+            params: &["iter", "source"],
+            title: "Iterator",
+            description: "Starts an iterator over an array, the characters of a string, or 0 to n-1 for a number n.",
+            since: V1_2,
+        },
+        InstructionSyntax {
+            op: "N",
+            name: "next",
+            operands: &[Variable, Variable, Variable],
+            params: &["done", "var", "iter"],
+            title: "Next Item",
+            description: "Advances an iterator: stores its next item in var and 0 in done, or 1 in done once \
+                          it is exhausted.",
+            since: V1_2,
+        },
+        InstructionSyntax {
+            op: ".",
+            name: "output",
+            operands: &[Value],
+            params: &["value"],
+            title: "Output",
+            description: "Prints the value followed by a newline.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: ",",
+            name: "input",
+            operands: &[Variable],
+            params: &["var"],
+            title: "Input",
+            description: "Reads a line of input into a variable, as a number when it parses as one.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "R",
+            name: "rust_ffi",
+            operands: &[Variable, String, Values],
+            params: &["result", "func", "args"],
+            title: "FFI Call",
+            description: "Calls a builtin function such as math.sqrt, len or str and stores the result.",
+            since: V1_0,
+        },
+        InstructionSyntax {
+            op: "P",
+            name: "python_ffi",
+            operands: &[Variable, String, Values],
+            params: &["result", "func", "args"],
+            title: "FFI Call (Python alias)",
+            description: "Same as R; accepted for compatibility with the Python implementation.",
+            since: V1_0,
+        },
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_consistent() {
+        for (i, syntax) in INSTRUCTION_TABLE.iter().enumerate() {
+            assert_eq!(syntax.params.len(), syntax.operands.len(), "{}", syntax.name);
+            assert!(syntax.description.ends_with('.'), "{}", syntax.name);
+            assert!(
+                INSTRUCTION_TABLE[..i].iter().all(|other| other.op != syntax.op),
+                "duplicate {}",
+                syntax.op
+            );
+        }
+    }
+
+    #[test]
+    fn test_signature_and_arity() {
+        let add = find("+").unwrap();
+        assert_eq!(add.signature(), "+ result a b");
+        assert_eq!((add.min_args(), add.max_args()), (3, Some(3)));
+        let call = find("$").unwrap();
+        assert_eq!(call.signature(), "$ result func args...");
+        assert_eq!((call.min_args(), call.max_args()), (2, None));
+        assert_eq!(find("#").unwrap().signature(), "# id argc {");
+        assert_eq!(find("}").unwrap().signature(), "}");
+        let switch = lookup("switch").unwrap();
+        assert_eq!(switch.signature(), "J value labels...");
+        assert_eq!((switch.min_args(), switch.max_args()), (1, None));
+        assert!(switch.reads(0) && !switch.reads(3));
+    }
+
+    #[test]
+    fn test_reads_and_writes() {
+        let add = find("+").unwrap();
+        assert!(add.writes(0) && !add.reads(0));
+        assert!(add.reads(2) && !add.reads(3));
+        let call = find("$").unwrap();
+        assert!(!call.reads(1) && call.reads(2) && call.reads(7));
+        let write = find("{").unwrap();
+        assert!(write.reads(0) && !write.writes(0));
+        let next = find("N").unwrap();
+        assert!(next.writes(0) && next.writes(1) && !next.reads(1));
+        assert!(next.reads(2) && !next.writes(2));
+    }
+
+    #[test]
+    fn test_pragma() {
+        assert_eq!(pragma(";! sui 1.2"), Some(Ok(LanguageVersion::new(1, 2))));
+        assert_eq!(pragma("  ;!sui 2"), Some(Ok(LanguageVersion::new(2, 0))));
+        assert_eq!(pragma(";! sui x.y"), Some(Err("x.y".to_string())));
+        assert_eq!(pragma("; sui 1.0"), None);
+        assert_eq!(pragma(". 1"), None);
+        assert_eq!(
+            declared_version("; demo\n;! sui 1.0\n;! sui 2.0"),
+            Some((2, Ok(LanguageVersion::new(1, 0))))
+        );
+        assert!(INSTRUCTION_TABLE.iter().all(|s| s.since <= LANGUAGE_VERSION));
+        assert!(LanguageVersion::new(1, 10) > LanguageVersion::new(1, 2));
+    }
+
+    #[test]
+    fn test_lookup_and_explain() {
+        assert_eq!(lookup("ADD"), find("+"));
+        assert_eq!(lookup(" ? "), lookup("cond_jump"));
+        assert!(lookup("X").is_none());
+
+        let text = lookup("+").unwrap().explain();
+        assert!(text.starts_with("Addition (add, since sui 1.0)"));
+        assert!(text.contains("    + result a b\n"));
+        assert!(text.contains("  result  variable to write\n"));
+    }
+}
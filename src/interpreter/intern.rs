@@ -0,0 +1,98 @@
+//! String constant interning
+//!
+//! String literals are unescaped once when a program is loaded. Each distinct
+//! string is stored once, so resolving a literal operand only clones an `Arc`.
+
+use super::{Function, Instruction, Lexer, ParsedValue};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Table of interned string constants
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    /// Distinct string contents
+    strings: HashSet<Arc<str>>,
+    /// Literal operand as written in the source (with quotes) to its string
+    literals: HashMap<String, Arc<str>>,
+}
+
+impl Interner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Intern a string, returning the shared copy
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(existing) = self.strings.get(s) {
+            return Arc::clone(existing);
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// Intern the string literal operands of a list of instructions
+    pub fn intern_instructions(&mut self, instructions: &[Instruction]) {
+        for instr in instructions {
+            for operand in instr.operands() {
+                if !operand.starts_with('"') || self.literals.contains_key(operand) {
+                    continue;
+                }
+                if let ParsedValue::String(s) = Lexer::parse_value(operand) {
+                    let interned = self.intern(&s);
+                    self.literals.insert(operand.clone(), interned);
+                }
+            }
+        }
+    }
+
+    /// Intern the string literal operands of a program
+    pub fn intern_program(&mut self, instructions: &[Instruction], functions: &[Function]) {
+        self.intern_instructions(instructions);
+        for func in functions {
+            self.intern_instructions(&func.body);
+        }
+    }
+
+    /// Look up a literal operand as written in the source
+    pub fn literal(&self, operand: &str) -> Option<Arc<str>> {
+        self.literals.get(operand).cloned()
+    }
+
+    /// Number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Forget all interned strings
+    pub fn clear(&mut self) {
+        self.strings.clear();
+        self.literals.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Parser;
+
+    #[test]
+    fn test_literals_share_storage() {
+        let (instructions, functions) =
+            Parser::parse(". \"hi\"\n= v0 \"hi\"\n# 0 0 {\n^ \"hi\"\n}\n. \"bye\"").unwrap();
+        let mut interner = Interner::new();
+        interner.intern_program(&instructions, &functions);
+
+        assert_eq!(interner.len(), 2);
+        let a = interner.literal("\"hi\"").unwrap();
+        let b = interner.intern("hi");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(interner.literal("v0"), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! One ordered stream for the output of programs and host builtins
+//!
+//! Output printed by `.` and output a registered builtin prints through its
+//! [`HostOutput`] both go through the interpreter's [`IoHandler`], so they
+//! reach stdout, the captured output and the transcript in the order they
+//! were produced, whatever the [`OutputMode`](super::OutputMode).
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Who printed a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputSource {
+    /// The program, with `.`
+    Program,
+    /// The registered builtin of this name, through [`HostOutput`]; empty
+    /// when printed outside a call of any
+    Host(String),
+}
+
+/// One line of output, with its place in the run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    /// Position in the run's output, from 0
+    pub seq: u64,
+    pub source: OutputSource,
+    /// Source line of the `.` or of the `R` calling the builtin; 0 outside
+    /// a call
+    pub line: usize,
+    pub text: String,
+}
+
+/// Sink of everything a run prints
+#[derive(Debug, Default)]
+pub(crate) struct IoHandler {
+    /// Print to stdout
+    pub(crate) print: bool,
+    /// Flush stdout after every line
+    pub(crate) autoflush: bool,
+    /// Keep a transcript
+    pub(crate) recording: bool,
+    pub(crate) transcript: Vec<TranscriptEntry>,
+    next_seq: u64,
+    /// Builtin being called and the line calling it
+    caller: Option<(String, usize)>,
+    /// What the builtin being called printed, for the captured output
+    pending: Vec<String>,
+}
+
+impl IoHandler {
+    /// Print and record a line
+    pub(crate) fn write(&mut self, source: OutputSource, line: usize, text: &str) {
+        if self.print {
+            let mut stdout = io::stdout().lock();
+            // Printing is best effort, as with println!
+            let _ = writeln!(stdout, "{}", text);
+            if self.autoflush {
+                let _ = stdout.flush();
+            }
+        }
+        if self.recording {
+            self.transcript.push(TranscriptEntry { seq: self.next_seq, source, line, text: text.to_string() });
+        }
+        self.next_seq += 1;
+    }
+
+    /// Start a new run
+    pub(crate) fn clear(&mut self) {
+        self.transcript.clear();
+        self.next_seq = 0;
+        self.caller = None;
+        self.pending.clear();
+    }
+
+    /// Attribute host output to `builtin`, called from `line`, until
+    /// [`end_call`](Self::end_call)
+    ///
+    /// Flushes stdout first, so the program's output so far comes before
+    /// anything the builtin writes to stdout itself.
+    pub(crate) fn begin_call(&mut self, builtin: &str, line: usize) {
+        if self.print {
+            let _ = io::stdout().flush();
+        }
+        self.caller = Some((builtin.to_string(), line));
+    }
+
+    /// What the builtin printed during the call
+    pub(crate) fn end_call(&mut self) -> Vec<String> {
+        self.caller = None;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Handle through which host builtins print into a program's output
+///
+/// From [`Interpreter::host_output`](super::Interpreter::host_output).
+/// Lines printed while the interpreter calls a registered builtin join the
+/// program's output at that point: they are printed and captured according
+/// to the output mode and recorded in the transcript with the builtin's
+/// name. Lines printed at other times are only printed and recorded.
+///
+/// ```rust
+/// use sui_lang::interpreter::OutputSource;
+/// use sui_lang::{Interpreter, Value};
+///
+/// let mut interp = Interpreter::new();
+/// let out = interp.host_output();
+/// interp.register_builtin("log", move |args| {
+///     out.print(&format!("log: {}", args[0]));
+///     Ok(Value::Integer(0))
+/// });
+/// interp.set_transcript(true);
+/// let output = interp.run(". 1\nR v0 \"log\" \"hi\"\n. 2", &[]).unwrap();
+/// assert_eq!(output, ["1", "log: hi", "2"]);
+/// let entry = &interp.transcript()[1];
+/// assert_eq!((&entry.source, entry.line), (&OutputSource::Host("log".to_string()), 2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HostOutput(pub(crate) Arc<Mutex<IoHandler>>);
+
+impl HostOutput {
+    /// Print `text` as a line of output
+    pub fn print(&self, text: &str) {
+        let mut io = lock(&self.0);
+        let (source, line) = match &io.caller {
+            Some((builtin, line)) => (OutputSource::Host(builtin.clone()), *line),
+            None => (OutputSource::Host(String::new()), 0),
+        };
+        io.write(source, line, text);
+        if io.caller.is_some() {
+            io.pending.push(text.to_string());
+        }
+    }
+
+    /// Flush stdout
+    pub fn flush(&self) {
+        let _io = lock(&self.0);
+        let _ = io::stdout().flush();
+    }
+}
+
+/// The handler, even if a builtin panicked while holding it
+pub(crate) fn lock(io: &Mutex<IoHandler>) -> MutexGuard<'_, IoHandler> {
+    io.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
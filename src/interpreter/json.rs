@@ -0,0 +1,279 @@
+//! JSON text for the `json.encode` and `json.decode` builtins
+//!
+//! Objects become structs and back, keeping their field order. Encoding is
+//! compact, like JavaScript's `JSON.stringify`; NaN and infinities, which
+//! JSON cannot express, encode as `null`.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use super::{BigInt, Value};
+
+/// Nesting deeper than this is rejected rather than overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+/// Compact JSON for a value
+pub(crate) fn encode(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Integer(n) => {
+            let _ = write!(out, "{}", n);
+        }
+        Value::BigInt(n) => {
+            let _ = write!(out, "{}", n);
+        }
+        Value::Float(x) if x.is_finite() => {
+            let _ = write!(out, "{}", value);
+        }
+        Value::Float(_) | Value::Null => out.push_str("null"),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Struct(fields) => {
+            out.push('{');
+            for (i, (name, v)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, name);
+                out.push(':');
+                write_value(out, v);
+            }
+            out.push('}');
+        }
+    }
+}
+
+pub(crate) fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Value for JSON text, or a message saying where it is invalid
+///
+/// Objects become structs, `true` and `false` become 1 and 0, and numbers
+/// without a fraction or exponent become integers.
+pub(crate) fn decode(text: &str) -> Result<Value, String> {
+    let mut parser = JsonParser { bytes: text.as_bytes(), pos: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.bytes.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("invalid JSON at offset {}: {}", self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.bytes.get(self.pos) == Some(&byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let name = self.string()?;
+                        if !self.eat(b':') {
+                            return Err(self.error("expected ':'"));
+                        }
+                        fields.push((Arc::from(name), self.value(depth + 1)?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error("expected ',' or '}'"));
+                        }
+                    }
+                }
+                Ok(Value::new_struct(fields))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error("expected ',' or ']'"));
+                        }
+                    }
+                }
+                Ok(Value::from(items))
+            }
+            Some(b'"') => Ok(Value::from(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => {
+                for (word, value) in [("true", Value::Integer(1)), ("false", Value::Integer(0)), ("null", Value::Null)] {
+                    if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        let mut integral = true;
+        while let Some(&b) = self.bytes.get(self.pos) {
+            match b {
+                b'0'..=b'9' | b'-' | b'+' => {}
+                b'.' | b'e' | b'E' => integral = false,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default();
+        let value = if integral {
+            BigInt::parse(text).map(Value::from)
+        } else {
+            text.parse().ok().map(Value::Float)
+        };
+        value.ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.bytes.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let start = self.pos;
+            while self.bytes.get(self.pos).is_some_and(|&b| b != b'"' && b != b'\\') {
+                self.pos += 1;
+            }
+            // The input is a str and the run stops at ASCII, so it is valid UTF-8
+            s.push_str(std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default());
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let unit = self.hex4()?;
+                            // A high surrogate pairs with a following \u escape
+                            let paired = self.bytes[self.pos + 1..].starts_with(b"\\u");
+                            let code = if (0xD800..0xDC00).contains(&unit) && paired {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                0x10000 + ((unit - 0xD800) << 10) + low.wrapping_sub(0xDC00)
+                            } else {
+                                unit
+                            };
+                            char::from_u32(code).unwrap_or('\u{fffd}')
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    s.push(escaped);
+                    self.pos += 1;
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// Four hex digits after the current position, leaving it on the last
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.bytes.get(self.pos + 1..self.pos + 5).and_then(|d| std::str::from_utf8(d).ok());
+        let code = digits.and_then(|d| u32::from_str_radix(d, 16).ok()).ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"{"name":"sui","tags":["a","b\n\"c\""],"n":-12,"x":1.5,"big":123456789012345678901234,"none":null}"#;
+        let value = decode(text).unwrap();
+        assert_eq!(value.field("n"), Some(&Value::Integer(-12)));
+        assert!(matches!(value.field("big"), Some(Value::BigInt(_))));
+        assert_eq!(encode(&value), text);
+
+        let value = decode(" { \"ok\" : true , \"e\": \"\\u00e9\\ud83d\\ude00\", \"f\": 2e3 } ").unwrap();
+        assert_eq!(encode(&value), "{\"ok\":1,\"e\":\"é😀\",\"f\":2000.0}");
+        assert_eq!(encode(&Value::Float(f64::NAN)), "null");
+    }
+
+    #[test]
+    fn test_invalid() {
+        for text in ["", "{", "[1,]", "{\"a\" 1}", "tru", "\"abc", "1 2", "\"\\x\"", "-"] {
+            assert!(decode(text).is_err(), "{:?} should not decode", text);
+        }
+        assert!(decode(&"[".repeat(10_000)).unwrap_err().contains("nested too deeply"));
+    }
+}
@@ -8,28 +8,37 @@ impl Lexer {
     ///
     /// Each line becomes a vector of tokens like ["=", "v0", "10"]
     pub fn tokenize_line(line: &str) -> Vec<String> {
+        Self::token_spans(line)
+            .into_iter()
+            .map(|(_, token)| token.to_string())
+            .collect()
+    }
+
+    /// Tokenize a single line, keeping the byte offset of each token
+    pub fn token_spans(line: &str) -> Vec<(usize, &str)> {
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let byte_at = |i: usize| chars.get(i).map_or(line.len(), |&(byte, _)| byte);
         let mut tokens = Vec::new();
-        let chars: Vec<char> = line.chars().collect();
         let mut i = 0;
 
         while i < chars.len() {
             // Skip whitespace
-            if chars[i].is_whitespace() {
+            if chars[i].1.is_whitespace() {
                 i += 1;
                 continue;
             }
 
             // Comment - ignore rest of line
-            if chars[i] == ';' {
+            if chars[i].1 == ';' {
                 break;
             }
 
-            // String literal
-            if chars[i] == '"' {
-                let start = i;
+            let start = i;
+            if chars[i].1 == '"' {
+                // String literal
                 i += 1;
-                while i < chars.len() && chars[i] != '"' {
-                    if chars[i] == '\\' && i + 1 < chars.len() {
+                while i < chars.len() && chars[i].1 != '"' {
+                    if chars[i].1 == '\\' && i + 1 < chars.len() {
                         i += 2;
                     } else {
                         i += 1;
@@ -38,18 +47,13 @@ impl Lexer {
                 if i < chars.len() {
                     i += 1; // Include closing quote
                 }
-                let token: String = chars[start..i].iter().collect();
-                tokens.push(token);
-                continue;
-            }
-
-            // Regular token (until whitespace)
-            let start = i;
-            while i < chars.len() && !chars[i].is_whitespace() {
-                i += 1;
+            } else {
+                // Regular token (until whitespace)
+                while i < chars.len() && !chars[i].1.is_whitespace() {
+                    i += 1;
+                }
             }
-            let token: String = chars[start..i].iter().collect();
-            tokens.push(token);
+            tokens.push((byte_at(start), &line[byte_at(start)..byte_at(i)]));
         }
 
         tokens
@@ -58,7 +62,7 @@ impl Lexer {
     /// Parse source code into lines of tokens
     pub fn parse(code: &str) -> Vec<Vec<String>> {
         code.lines()
-            .map(|line| Self::tokenize_line(line))
+            .map(Self::tokenize_line)
             .filter(|tokens| !tokens.is_empty())
             .collect()
     }
@@ -66,10 +70,11 @@ impl Lexer {
     /// Parse a value string to determine its type
     pub fn parse_value(val: &str) -> ParsedValue {
         // Variable reference
-        if val.starts_with('v') || val.starts_with('g') || val.starts_with('a') {
-            if val.len() > 1 && val[1..].chars().all(|c| c.is_ascii_digit()) {
-                return ParsedValue::Variable(val.to_string());
-            }
+        if (val.starts_with('v') || val.starts_with('g') || val.starts_with('a'))
+            && val.len() > 1
+            && val[1..].chars().all(|c| c.is_ascii_digit())
+        {
+            return ParsedValue::Variable(val.to_string());
         }
 
         // String literal
@@ -96,6 +101,25 @@ impl Lexer {
         ParsedValue::String(val.to_string())
     }
 
+    /// Index of a constant reference such as `c3`
+    pub fn constant_ref(val: &str) -> Option<i64> {
+        let digits = val.strip_prefix('c')?;
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+
+    /// Split a variable reference into its scope prefix and index
+    ///
+    /// Never panics: an empty operand reads as `v0` and a malformed index
+    /// reads as `0`.
+    pub fn split_variable(var: &str) -> (char, i64) {
+        let mut chars = var.chars();
+        let prefix = chars.next().unwrap_or('v');
+        (prefix, chars.as_str().parse().unwrap_or(0))
+    }
+
     /// Unescape a string literal
     fn unescape_string(s: &str) -> String {
         let mut result = String::new();
@@ -157,6 +181,13 @@ mod tests {
         assert_eq!(tokens, vec!["=", "v0", "10"]);
     }
 
+    #[test]
+    fn test_token_spans() {
+        let spans = Lexer::token_spans("  . \"a b\" v0 ; c");
+        assert_eq!(spans, vec![(2, "."), (4, "\"a b\""), (10, "v0")]);
+        assert_eq!(Lexer::token_spans(". \"é"), vec![(0, "."), (2, "\"é")]);
+    }
+
     #[test]
     fn test_parse_value_variable() {
         assert_eq!(Lexer::parse_value("v0"), ParsedValue::Variable("v0".to_string()));
@@ -172,7 +203,7 @@ mod tests {
 
     #[test]
     fn test_parse_value_float() {
-        assert_eq!(Lexer::parse_value("3.14"), ParsedValue::Float(3.14));
+        assert_eq!(Lexer::parse_value("2.5"), ParsedValue::Float(2.5));
     }
 
     #[test]
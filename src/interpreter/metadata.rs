@@ -0,0 +1,363 @@
+//! Usage metadata declared in a program's leading comments
+//!
+//! Comment lines starting with `;@` at the top of a program, before its
+//! first instruction, describe how to run it:
+//!
+//! ```text
+//! ;@ about "Prints the nth Fibonacci number"
+//! ;@ arg 0 int "n: index of the Fibonacci number"
+//! ;@ fn 0 i -> i
+//! ```
+//!
+//! [`Parser::metadata`](super::Parser::metadata) reads them into a
+//! [`Metadata`], which checks command-line arguments and renders usage.
+//! Other comments may sit in the block; `;@` lines after the first
+//! instruction are ordinary comments.
+//!
+//! Variable operands may also carry a type hint, as in `= v0:i 10`. The
+//! parser drops hints from the instructions it builds, so they never change
+//! how a program runs; [`Parser::annotations`](super::Parser::annotations)
+//! lists them for checking against inferred types.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Lexer, ParseError, ParsedValue};
+
+/// What an argument must look like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    /// A whole number, read as an integer
+    Int,
+    /// Any number
+    Float,
+    /// Anything
+    String,
+}
+
+impl ArgType {
+    /// Whether `arg` is a valid argument of this type
+    pub fn accepts(self, arg: &str) -> bool {
+        match self {
+            ArgType::Int => arg.parse::<i64>().is_ok(),
+            ArgType::Float => arg.parse::<f64>().is_ok(),
+            ArgType::String => true,
+        }
+    }
+}
+
+impl FromStr for ArgType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "int" => Ok(ArgType::Int),
+            "float" => Ok(ArgType::Float),
+            "string" => Ok(ArgType::String),
+            _ => Err(format!("Unknown argument type '{}' (expected int, float or string)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            ArgType::Int => "int",
+            ArgType::Float => "float",
+            ArgType::String => "string",
+        })
+    }
+}
+
+/// Type named by a hint on an operand (`v0:i`) or in a `;@ fn` signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeHint {
+    /// `i`
+    Int,
+    /// `f`
+    Float,
+    /// `n`: int or float
+    Number,
+    /// `s`
+    String,
+    /// `a`
+    Array,
+}
+
+impl FromStr for TypeHint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i" => Ok(TypeHint::Int),
+            "f" => Ok(TypeHint::Float),
+            "n" => Ok(TypeHint::Number),
+            "s" => Ok(TypeHint::String),
+            "a" => Ok(TypeHint::Array),
+            _ => Err(format!("Unknown type hint '{}' (expected i, f, n, s or a)", s)),
+        }
+    }
+}
+
+impl fmt::Display for TypeHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            TypeHint::Int => "int",
+            TypeHint::Float => "float",
+            TypeHint::Number => "number",
+            TypeHint::String => "string",
+            TypeHint::Array => "array",
+        })
+    }
+}
+
+/// Declared types of a function: `;@ fn ID ARGS... [-> RESULT]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnSignature {
+    /// Line of the declaration (1-based)
+    pub line: usize,
+    pub id: i64,
+    /// Types of the arguments, in order
+    pub params: Vec<TypeHint>,
+    /// Type of the return value, if declared
+    pub returns: Option<TypeHint>,
+}
+
+/// A type hint on a variable operand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Line number (1-based)
+    pub line: usize,
+    /// The variable, without its hint
+    pub var: String,
+    /// Position among the operands, from 0
+    pub operand: usize,
+    pub hint: TypeHint,
+}
+
+/// Split the type hints off the operands of a line's `tokens`
+pub(crate) fn strip_annotations(tokens: &[String], line: usize) -> Result<(Cow<'_, [String]>, Vec<Annotation>), ParseError> {
+    let mut annotations = Vec::new();
+    let mut stripped = Cow::Borrowed(tokens);
+    for (k, token) in tokens.iter().enumerate().skip(1) {
+        let Some((var, hint)) = token.split_once(':') else {
+            continue;
+        };
+        if !matches!(Lexer::parse_value(var), ParsedValue::Variable(_)) {
+            continue;
+        }
+        let hint = hint.parse().map_err(|e: String| ParseError::General(line, format!("{} on {}", e, var)))?;
+        annotations.push(Annotation { line, var: var.to_string(), operand: k - 1, hint });
+        stripped.to_mut()[k] = var.to_string();
+    }
+    Ok((stripped, annotations))
+}
+
+/// A declared command-line argument: `;@ arg INDEX TYPE "name: description"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgSpec {
+    /// Position among the arguments; the program reads it from `g(101 + index)`
+    pub index: usize,
+    pub ty: ArgType,
+    pub description: String,
+}
+
+impl ArgSpec {
+    /// Name for usage lines: the description up to a colon, or `argN`
+    pub fn name(&self) -> String {
+        match self.description.split_once(':') {
+            Some((name, _)) if !name.trim().is_empty() && !name.contains(' ') => name.trim().to_string(),
+            _ => format!("arg{}", self.index),
+        }
+    }
+}
+
+/// Usage metadata of a program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// What the program does: `;@ about "..."`
+    pub about: Option<String>,
+    /// Declared arguments, by index
+    pub args: Vec<ArgSpec>,
+    /// Declared function signatures, in source order
+    pub functions: Vec<FnSignature>,
+}
+
+impl Metadata {
+    /// Read the `;@` lines of the leading comment block of `code`
+    pub(crate) fn parse(code: &str) -> Result<Self, ParseError> {
+        let mut metadata = Metadata::default();
+        for (i, line) in code.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with(';') {
+                break;
+            }
+            let Some(rest) = line.strip_prefix(";@") else {
+                continue;
+            };
+            let tokens = Lexer::tokenize_line(rest);
+            let error = |message: String| ParseError::General(line_num, message);
+            match tokens.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                ["about", text] => metadata.about = Some(literal(text).ok_or_else(|| error(quote_error("about")))?),
+                ["arg", index, ty, description] => {
+                    let index: usize =
+                        index.parse().map_err(|_| error(format!("Invalid argument index: {}", index)))?;
+                    if index != metadata.args.len() {
+                        return Err(error(format!(
+                            "Argument {} declared out of order; expected argument {}",
+                            index,
+                            metadata.args.len()
+                        )));
+                    }
+                    let ty = ty.parse().map_err(error)?;
+                    let description = literal(description).ok_or_else(|| error(quote_error("arg")))?;
+                    metadata.args.push(ArgSpec { index, ty, description });
+                }
+                ["fn", id, ref types @ ..] => {
+                    let id = id.parse().map_err(|_| error(format!("Invalid function id: {}", id)))?;
+                    let (params, returns) = match types.iter().position(|&t| t == "->") {
+                        Some(arrow) if arrow + 2 == types.len() => (&types[..arrow], Some(types[arrow + 1])),
+                        Some(_) => return Err(error(format!("Malformed metadata; expected {}", usage_of("fn")))),
+                        None => (types, None),
+                    };
+                    let params = params.iter().map(|t| t.parse()).collect::<Result<_, _>>().map_err(error)?;
+                    let returns = returns.map(str::parse).transpose().map_err(error)?;
+                    metadata.functions.push(FnSignature { line: line_num, id, params, returns });
+                }
+                [key, ..] if ["about", "arg", "fn"].contains(&key) => {
+                    return Err(error(format!("Malformed metadata; expected {}", usage_of(key))))
+                }
+                [key, ..] => return Err(error(format!("Unknown metadata '{}'", key))),
+                [] => return Err(error("Empty metadata line".to_string())),
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Problems with running the program with `args`, none if it may run
+    ///
+    /// A program declaring no arguments accepts any.
+    pub fn check_args(&self, args: &[String]) -> Vec<String> {
+        if self.args.is_empty() {
+            return Vec::new();
+        }
+        let mut problems = Vec::new();
+        if args.len() != self.args.len() {
+            problems.push(format!(
+                "expected {} argument{}, got {}",
+                self.args.len(),
+                if self.args.len() == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        for (spec, arg) in self.args.iter().zip(args) {
+            if !spec.ty.accepts(arg) {
+                problems.push(format!("argument {} ({}) must be {}, got {:?}", spec.index, spec.name(), article(spec.ty), arg));
+            }
+        }
+        problems
+    }
+
+    /// Usage text for running the program as `program`
+    pub fn usage(&self, program: &str) -> String {
+        let mut text = format!("Usage: sui {}", program);
+        for spec in &self.args {
+            text.push_str(&format!(" <{}>", spec.name()));
+        }
+        if let Some(about) = &self.about {
+            text.push_str(&format!("\n\n{}", about));
+        }
+        if !self.args.is_empty() {
+            text.push_str("\n\nArguments:");
+            for spec in &self.args {
+                text.push_str(&format!("\n  {}  {:<6}  {}", spec.index, spec.ty, spec.description));
+            }
+        }
+        text
+    }
+}
+
+/// Text of a string literal token
+fn literal(token: &str) -> Option<String> {
+    match token.starts_with('"').then(|| Lexer::parse_value(token)) {
+        Some(ParsedValue::String(text)) => Some(text),
+        _ => None,
+    }
+}
+
+fn quote_error(key: &str) -> String {
+    format!("Metadata '{}' takes a quoted string", key)
+}
+
+fn usage_of(key: &str) -> &'static str {
+    match key {
+        "about" => ";@ about \"text\"",
+        "fn" => ";@ fn ID TYPES... -> TYPE",
+        _ => ";@ arg INDEX TYPE \"name: description\"",
+    }
+}
+
+fn article(ty: ArgType) -> &'static str {
+    match ty {
+        ArgType::Int => "an int",
+        ArgType::Float => "a number",
+        ArgType::String => "a string",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Parser;
+
+    #[test]
+    fn test_metadata() {
+        let code = ";! sui 1.0\n;@ about \"Repeats a word\"\n; notes\n\n;@ arg 0 int \"n: times; at least 1\"\n;@ arg 1 string \"the word\"\n. g101\n;@ arg 5 int \"ignored\"";
+        let metadata = Parser::metadata(code).unwrap();
+        assert_eq!(metadata.about.as_deref(), Some("Repeats a word"));
+        assert_eq!(metadata.args.len(), 2);
+        assert_eq!((metadata.args[0].name(), metadata.args[1].name()), ("n".to_string(), "arg1".to_string()));
+        assert_eq!(
+            metadata.usage("repeat.sui"),
+            "Usage: sui repeat.sui <n> <arg1>\n\nRepeats a word\n\nArguments:\n  0  int     n: times; at least 1\n  1  string  the word"
+        );
+
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(metadata.check_args(&args(&["3", "hi"])).is_empty());
+        assert_eq!(
+            metadata.check_args(&args(&["x"])),
+            ["expected 2 arguments, got 1", "argument 0 (n) must be an int, got \"x\""]
+        );
+        assert!(Parser::metadata(". 1").unwrap().check_args(&args(&["any"])).is_empty());
+
+        let metadata = Parser::metadata(";@ fn 2 i s -> a\n;@ fn 3 n").unwrap();
+        assert_eq!(
+            metadata.functions,
+            [
+                FnSignature { line: 1, id: 2, params: vec![TypeHint::Int, TypeHint::String], returns: Some(TypeHint::Array) },
+                FnSignature { line: 2, id: 3, params: vec![TypeHint::Number], returns: None },
+            ]
+        );
+        let annotations = Parser::annotations("= v0:i 1\n$ v1 0 \"a:s\" g0:a").unwrap();
+        assert_eq!(
+            annotations,
+            [
+                Annotation { line: 1, var: "v0".to_string(), operand: 0, hint: TypeHint::Int },
+                Annotation { line: 2, var: "g0".to_string(), operand: 3, hint: TypeHint::Array },
+            ]
+        );
+
+        for (code, error) in [
+            (";@ arg 1 int \"x\"", "Parse error at line 1: Argument 1 declared out of order; expected argument 0"),
+            (";@ arg 0 bool \"x\"", "Parse error at line 1: Unknown argument type 'bool' (expected int, float or string)"),
+            ("\n;@ arg 0 int", "Parse error at line 2: Malformed metadata; expected ;@ arg INDEX TYPE \"name: description\""),
+            (";@ about text", "Parse error at line 1: Metadata 'about' takes a quoted string"),
+            (";@ version 2", "Parse error at line 1: Unknown metadata 'version'"),
+            (";@ fn 0 i ->", "Parse error at line 1: Malformed metadata; expected ;@ fn ID TYPES... -> TYPE"),
+        ] {
+            assert_eq!(Parser::metadata(code).unwrap_err().to_string(), error);
+        }
+    }
+}
@@ -2,15 +2,45 @@
 //!
 //! This module contains the core interpreter for the Sui programming language.
 
+mod bigint;
+mod channels;
+mod compiled;
+pub(crate) mod digest;
+pub(crate) mod format;
+mod intern;
+mod io;
+pub(crate) mod json;
+pub mod instruction_set;
 pub mod lexer;
+mod metadata;
 mod parser;
+mod runlog;
 mod runtime;
+mod stats;
+mod trace;
 mod value;
 
+pub use bigint::{BigInt, MAX_INTEGER_BITS};
+pub use channels::{spawn_program, Channels};
+pub use compiled::CompiledProgram;
+pub use digest::code_hash;
+pub use intern::Interner;
+pub use io::{HostOutput, OutputSource, TranscriptEntry};
 pub use lexer::{Lexer, ParsedValue};
+pub use metadata::{Annotation, ArgSpec, ArgType, FnSignature, Metadata, TypeHint};
+pub use instruction_set::{
+    InstructionSyntax, LanguageVersion, OperandKind, INSTRUCTION_TABLE, LANGUAGE_VERSION,
+};
 pub use parser::{Parser, ParseError};
-pub use runtime::{Interpreter, InterpreterError};
-pub use value::Value;
+pub use runlog::RunLogger;
+pub use stats::{FunctionLimits, FunctionUsage, RunStats};
+pub use runtime::{Clock, DivisionPolicy, HookCtx, Interpreter, InterpreterError, OutputMode, StateSnapshot};
+pub(crate) use runtime::Program;
+pub use trace::{Trace, TraceEvent};
+pub use value::{Value, MAX_ARRAY_SIZE};
+
+use std::collections::HashMap;
+use std::fmt;
 
 /// Token types for the Sui language
 #[derive(Debug, Clone, PartialEq)]
@@ -38,6 +68,9 @@ pub enum Token {
 pub enum Instruction {
     /// Import: _ "path/to/module.sui"
     Import { path: String },
+    /// Constant definition: C id literal; `c<id>` operands are replaced by
+    /// the literal at parse time
+    Const { id: i64, value: String },
     /// Assignment: = var value
     Assign { target: String, value: String },
     /// Addition: + result a b
@@ -54,6 +87,10 @@ pub enum Instruction {
     Lt { result: String, a: String, b: String },
     /// Greater than: > result a b
     Gt { result: String, a: String, b: String },
+    /// Less than or equal: <= result a b
+    Le { result: String, a: String, b: String },
+    /// Greater than or equal: >= result a b
+    Ge { result: String, a: String, b: String },
     /// Equality: ~ result a b
     Eq { result: String, a: String, b: String },
     /// NOT: ! result a
@@ -66,6 +103,8 @@ pub enum Instruction {
     CondJump { cond: String, label: i64 },
     /// Unconditional jump: @ label
     Jump { label: i64 },
+    /// Jump table: J value labels...
+    Switch { value: String, labels: Vec<i64> },
     /// Label definition: : label
     Label { id: i64 },
     /// Function definition: # id argc {
@@ -76,12 +115,27 @@ pub enum Instruction {
     Call { result: String, func_id: i64, args: Vec<String> },
     /// Return: ^ value
     Return { value: String },
+    /// Try region start: T err label
+    ///
+    /// Until the matching `U`, an error stores its value in `err` and jumps
+    /// to `label` in the same function.
+    Try { err: String, label: i64 },
+    /// Try region end: U
+    EndTry,
+    /// Raise an error: E value
+    Raise { value: String },
     /// Array create: [ var size
     ArrayCreate { var: String, size: String },
-    /// Array read: ] result arr idx
-    ArrayRead { result: String, arr: String, idx: String },
-    /// Array write: { arr idx value
-    ArrayWrite { arr: String, idx: String, value: String },
+    /// Array read: ] result arr idx indices...
+    ///
+    /// Further `indices` read nested arrays: `] v0 v1 2 3` reads `v1[2][3]`.
+    ArrayRead { result: String, arr: String, idx: String, indices: Vec<String> },
+    /// Array write: { arr idx indices... value
+    ArrayWrite { arr: String, idx: String, indices: Vec<String>, value: String },
+    /// Iterator: I iter source
+    Iter { iter: String, source: String },
+    /// Advance iterator: N done var iter
+    Next { done: String, var: String, iter: String },
     /// Output: . value
     Output { value: String },
     /// Input: , var
@@ -92,12 +146,151 @@ pub enum Instruction {
     Comment,
     /// Empty line (ignored)
     Empty,
+    /// Line that failed to parse, from [`Parser::parse_recovering`]
+    Invalid { raw: String, error: ParseError },
+}
+
+impl Instruction {
+    /// Value and variable operands, in source order
+    ///
+    /// Label and function IDs, import paths and FFI function names are not
+    /// included.
+    pub fn operands(&self) -> Vec<&String> {
+        match self {
+            Instruction::Assign { target, value } => vec![target, value],
+            Instruction::Add { result, a, b }
+            | Instruction::Sub { result, a, b }
+            | Instruction::Mul { result, a, b }
+            | Instruction::Div { result, a, b }
+            | Instruction::Mod { result, a, b }
+            | Instruction::Lt { result, a, b }
+            | Instruction::Gt { result, a, b }
+            | Instruction::Le { result, a, b }
+            | Instruction::Ge { result, a, b }
+            | Instruction::Eq { result, a, b }
+            | Instruction::And { result, a, b }
+            | Instruction::Or { result, a, b } => vec![result, a, b],
+            Instruction::Not { result, a } => vec![result, a],
+            Instruction::CondJump { cond, .. } => vec![cond],
+            Instruction::Switch { value, .. } => vec![value],
+            Instruction::Call { result, args, .. } => std::iter::once(result).chain(args).collect(),
+            Instruction::Return { value } | Instruction::Raise { value } => vec![value],
+            Instruction::Try { err, .. } => vec![err],
+            Instruction::ArrayCreate { var, size } => vec![var, size],
+            Instruction::ArrayRead { result, arr, idx, indices } => {
+                [result, arr, idx].into_iter().chain(indices).collect()
+            }
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
+                [arr, idx].into_iter().chain(indices).chain([value]).collect()
+            }
+            Instruction::Iter { iter, source } => vec![iter, source],
+            Instruction::Next { done, var, iter } => vec![done, var, iter],
+            Instruction::Output { value } => vec![value],
+            Instruction::Input { var } => vec![var],
+            Instruction::RustFFI { result, args, .. } => std::iter::once(result).chain(args).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Renders the canonical source line: operands separated by single spaces
+///
+/// Comments render as a bare `;` since their text is not kept.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Import { path } => write!(f, "_ \"{}\"", path),
+            Instruction::Const { id, value } => write!(f, "C {} {}", id, value),
+            Instruction::Assign { target, value } => write!(f, "= {} {}", target, value),
+            Instruction::Add { result, a, b } => write!(f, "+ {} {} {}", result, a, b),
+            Instruction::Sub { result, a, b } => write!(f, "- {} {} {}", result, a, b),
+            Instruction::Mul { result, a, b } => write!(f, "* {} {} {}", result, a, b),
+            Instruction::Div { result, a, b } => write!(f, "/ {} {} {}", result, a, b),
+            Instruction::Mod { result, a, b } => write!(f, "% {} {} {}", result, a, b),
+            Instruction::Lt { result, a, b } => write!(f, "< {} {} {}", result, a, b),
+            Instruction::Gt { result, a, b } => write!(f, "> {} {} {}", result, a, b),
+            Instruction::Le { result, a, b } => write!(f, "<= {} {} {}", result, a, b),
+            Instruction::Ge { result, a, b } => write!(f, ">= {} {} {}", result, a, b),
+            Instruction::Eq { result, a, b } => write!(f, "~ {} {} {}", result, a, b),
+            Instruction::Not { result, a } => write!(f, "! {} {}", result, a),
+            Instruction::And { result, a, b } => write!(f, "& {} {} {}", result, a, b),
+            Instruction::Or { result, a, b } => write!(f, "| {} {} {}", result, a, b),
+            Instruction::CondJump { cond, label } => write!(f, "? {} {}", cond, label),
+            Instruction::Jump { label } => write!(f, "@ {}", label),
+            Instruction::Switch { value, labels } => {
+                write!(f, "J {}", value)?;
+                labels.iter().try_for_each(|label| write!(f, " {}", label))
+            }
+            Instruction::Label { id } => write!(f, ": {}", id),
+            Instruction::FuncDef { id, argc } => write!(f, "# {} {} {{", id, argc),
+            Instruction::FuncEnd => write!(f, "}}"),
+            Instruction::Call { result, func_id, args } => {
+                write!(f, "$ {} {}", result, func_id)?;
+                args.iter().try_for_each(|arg| write!(f, " {}", arg))
+            }
+            Instruction::Return { value } => write!(f, "^ {}", value),
+            Instruction::Try { err, label } => write!(f, "T {} {}", err, label),
+            Instruction::EndTry => write!(f, "U"),
+            Instruction::Raise { value } => write!(f, "E {}", value),
+            Instruction::ArrayCreate { var, size } => write!(f, "[ {} {}", var, size),
+            Instruction::ArrayRead { result, arr, idx, indices } => {
+                write!(f, "] {} {} {}", result, arr, idx)?;
+                indices.iter().try_for_each(|i| write!(f, " {}", i))
+            }
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
+                write!(f, "{{ {} {}", arr, idx)?;
+                indices.iter().try_for_each(|i| write!(f, " {}", i))?;
+                write!(f, " {}", value)
+            }
+            Instruction::Iter { iter, source } => write!(f, "I {} {}", iter, source),
+            Instruction::Next { done, var, iter } => write!(f, "N {} {} {}", done, var, iter),
+            Instruction::Output { value } => write!(f, ". {}", value),
+            Instruction::Input { var } => write!(f, ", {}", var),
+            Instruction::RustFFI { result, func, args } => {
+                write!(f, "R {} {}", result, func)?;
+                args.iter().try_for_each(|arg| write!(f, " {}", arg))
+            }
+            Instruction::Comment => write!(f, ";"),
+            Instruction::Empty => Ok(()),
+            Instruction::Invalid { raw, .. } => f.write_str(raw),
+        }
+    }
 }
 
 /// Function definition storage
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
     pub id: i64,
     pub arg_count: i64,
     pub body: Vec<Instruction>,
+    /// Label positions within `body`, computed once at parse time
+    pub labels: HashMap<i64, usize>,
+    /// Source line (1-based) of each instruction in `body`, if known
+    pub lines: Vec<usize>,
+}
+
+impl Function {
+    /// Create a function and precompute its label table
+    pub fn new(id: i64, arg_count: i64, body: Vec<Instruction>) -> Self {
+        let labels = Self::label_table(&body);
+        Self {
+            id,
+            arg_count,
+            body,
+            labels,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Map each label to its position; later definitions win
+    pub fn label_table(instructions: &[Instruction]) -> HashMap<i64, usize> {
+        instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instr)| match instr {
+                Instruction::Label { id } => Some((*id, i)),
+                _ => None,
+            })
+            .collect()
+    }
 }
@@ -1,10 +1,14 @@
 //! Parser for the Sui programming language
 
-use super::{Function, Instruction, Lexer};
+use super::instruction_set::{self, InstructionSyntax, LanguageVersion, LANGUAGE_VERSION};
+use super::metadata::strip_annotations;
+use super::{Annotation, Function, Instruction, Lexer, Metadata, ParsedValue};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use thiserror::Error;
 
 /// Parser errors
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum ParseError {
     #[error("Invalid instruction '{0}' at line {1}")]
     InvalidInstruction(String, usize),
@@ -18,19 +22,60 @@ pub enum ParseError {
     #[error("Unmatched function brace at line {0}")]
     UnmatchedBrace(usize),
 
+    #[error("Instruction '{0}' at line {1} requires sui >= {2}")]
+    RequiresVersion(String, usize, LanguageVersion),
+
+    #[error("Program at line {0} requires sui {1}, but this runtime supports up to {2}")]
+    UnsupportedVersion(usize, LanguageVersion, LanguageVersion),
+
     #[error("Parse error at line {0}: {1}")]
     General(usize, String),
 }
 
+impl ParseError {
+    /// Line the error refers to (1-based)
+    pub fn line(&self) -> usize {
+        match self {
+            ParseError::InvalidInstruction(_, line)
+            | ParseError::MissingArguments(_, line, _, _)
+            | ParseError::InvalidFunctionDef(line)
+            | ParseError::UnmatchedBrace(line)
+            | ParseError::RequiresVersion(_, line, _)
+            | ParseError::UnsupportedVersion(line, _, _)
+            | ParseError::General(line, _) => *line,
+        }
+    }
+
+    /// Move the error to another line
+    pub fn set_line(&mut self, line: usize) {
+        match self {
+            ParseError::InvalidInstruction(_, l)
+            | ParseError::MissingArguments(_, l, _, _)
+            | ParseError::InvalidFunctionDef(l)
+            | ParseError::UnmatchedBrace(l)
+            | ParseError::RequiresVersion(_, l, _)
+            | ParseError::UnsupportedVersion(l, _, _)
+            | ParseError::General(l, _) => *l = line,
+        }
+    }
+}
+
 /// Parser for Sui source code
 pub struct Parser;
 
 impl Parser {
+    /// Look up the syntax of an instruction character
+    pub fn syntax(op: &str) -> Option<&'static InstructionSyntax> {
+        instruction_set::find(op)
+    }
+
     /// Parse a single line of tokens into an instruction
     pub fn parse_line(tokens: &[String], line_num: usize) -> Result<Instruction, ParseError> {
         if tokens.is_empty() {
             return Ok(Instruction::Empty);
         }
+        // Type hints are for checkers; the instruction does not keep them
+        let (tokens, _) = strip_annotations(tokens, line_num)?;
 
         let op = tokens[0].as_str();
         let args: Vec<&str> = tokens[1..].iter().map(|s| s.as_str()).collect();
@@ -41,15 +86,35 @@ impl Parser {
 
             // Import: _ "path/to/module.sui"
             "_" => {
-                Self::check_args(op, &args, 1, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 // Remove quotes from path if present
                 let path = args[0].trim_matches('"').to_string();
                 Ok(Instruction::Import { path })
             }
 
+            // Constant: C id literal
+            "C" => {
+                Self::check_args(op, &args, line_num)?;
+                let id = args[0]
+                    .parse()
+                    .map_err(|_| ParseError::General(line_num, format!("Invalid constant id: {}", args[0])))?;
+                let value = args[1];
+                let variable = matches!(Lexer::parse_value(value), ParsedValue::Variable(_));
+                if variable || Lexer::constant_ref(value).is_some() {
+                    return Err(ParseError::General(
+                        line_num,
+                        format!("Constant value must be a literal: {}", value),
+                    ));
+                }
+                Ok(Instruction::Const {
+                    id,
+                    value: value.to_string(),
+                })
+            }
+
             // Assignment: = var value
             "=" => {
-                Self::check_args(op, &args, 2, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Assign {
                     target: args[0].to_string(),
                     value: args[1].to_string(),
@@ -58,7 +123,7 @@ impl Parser {
 
             // Addition: + result a b
             "+" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Add {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -68,7 +133,7 @@ impl Parser {
 
             // Subtraction: - result a b
             "-" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Sub {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -78,7 +143,7 @@ impl Parser {
 
             // Multiplication: * result a b
             "*" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Mul {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -88,7 +153,7 @@ impl Parser {
 
             // Division: / result a b
             "/" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Div {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -98,7 +163,7 @@ impl Parser {
 
             // Modulo: % result a b
             "%" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Mod {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -108,7 +173,7 @@ impl Parser {
 
             // Less than: < result a b
             "<" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Lt {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -118,7 +183,7 @@ impl Parser {
 
             // Greater than: > result a b
             ">" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Gt {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -126,9 +191,29 @@ impl Parser {
                 })
             }
 
+            // Less than or equal: <= result a b
+            "<=" => {
+                Self::check_args(op, &args, line_num)?;
+                Ok(Instruction::Le {
+                    result: args[0].to_string(),
+                    a: args[1].to_string(),
+                    b: args[2].to_string(),
+                })
+            }
+
+            // Greater than or equal: >= result a b
+            ">=" => {
+                Self::check_args(op, &args, line_num)?;
+                Ok(Instruction::Ge {
+                    result: args[0].to_string(),
+                    a: args[1].to_string(),
+                    b: args[2].to_string(),
+                })
+            }
+
             // Equality: ~ result a b
             "~" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Eq {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -138,7 +223,7 @@ impl Parser {
 
             // NOT: ! result a
             "!" => {
-                Self::check_args(op, &args, 2, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Not {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -147,7 +232,7 @@ impl Parser {
 
             // AND: & result a b
             "&" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::And {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -157,7 +242,7 @@ impl Parser {
 
             // OR: | result a b
             "|" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Or {
                     result: args[0].to_string(),
                     a: args[1].to_string(),
@@ -167,7 +252,7 @@ impl Parser {
 
             // Conditional jump: ? cond label
             "?" => {
-                Self::check_args(op, &args, 2, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 let label = args[1]
                     .parse()
                     .map_err(|_| ParseError::General(line_num, format!("Invalid label: {}", args[1])))?;
@@ -179,16 +264,32 @@ impl Parser {
 
             // Unconditional jump: @ label
             "@" => {
-                Self::check_args(op, &args, 1, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 let label = args[0]
                     .parse()
                     .map_err(|_| ParseError::General(line_num, format!("Invalid label: {}", args[0])))?;
                 Ok(Instruction::Jump { label })
             }
 
+            // Jump table: J value labels...
+            "J" => {
+                Self::check_args(op, &args, line_num)?;
+                let labels = args[1..]
+                    .iter()
+                    .map(|arg| {
+                        arg.parse()
+                            .map_err(|_| ParseError::General(line_num, format!("Invalid label: {}", arg)))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Instruction::Switch {
+                    value: args[0].to_string(),
+                    labels,
+                })
+            }
+
             // Label definition: : label
             ":" => {
-                Self::check_args(op, &args, 1, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 let id = args[0]
                     .parse()
                     .map_err(|_| ParseError::General(line_num, format!("Invalid label: {}", args[0])))?;
@@ -214,7 +315,7 @@ impl Parser {
 
             // Function call: $ result func_id args...
             "$" => {
-                Self::check_args(op, &args, 2, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 let func_id = args[1]
                     .parse()
                     .map_err(|_| ParseError::General(line_num, format!("Invalid function id: {}", args[1])))?;
@@ -228,37 +329,83 @@ impl Parser {
 
             // Return: ^ value
             "^" => {
-                Self::check_args(op, &args, 1, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Return {
                     value: args[0].to_string(),
                 })
             }
 
+            // Try region start: T err label
+            "T" => {
+                Self::check_args(op, &args, line_num)?;
+                let label = args[1]
+                    .parse()
+                    .map_err(|_| ParseError::General(line_num, format!("Invalid label: {}", args[1])))?;
+                Ok(Instruction::Try {
+                    err: args[0].to_string(),
+                    label,
+                })
+            }
+
+            // Try region end: U
+            "U" => Ok(Instruction::EndTry),
+
+            // Raise: E value
+            "E" => {
+                Self::check_args(op, &args, line_num)?;
+                Ok(Instruction::Raise {
+                    value: args[0].to_string(),
+                })
+            }
+
             // Array create: [ var size
             "[" => {
-                Self::check_args(op, &args, 2, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::ArrayCreate {
                     var: args[0].to_string(),
                     size: args[1].to_string(),
                 })
             }
 
-            // Array read: ] result arr idx
+            // Array read: ] result arr idx indices...
             "]" => {
-                Self::check_args(op, &args, 3, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::ArrayRead {
                     result: args[0].to_string(),
                     arr: args[1].to_string(),
                     idx: args[2].to_string(),
+                    indices: args[3..].iter().map(|s| s.to_string()).collect(),
                 })
             }
 
-            // Array write: { arr idx value (must have 3 args to distinguish from block start)
+            // Array write: { arr idx indices... value
+            // (must have 3 args to distinguish from block start)
             "{" if args.len() >= 3 => {
+                let last = args.len() - 1;
                 Ok(Instruction::ArrayWrite {
                     arr: args[0].to_string(),
                     idx: args[1].to_string(),
-                    value: args[2].to_string(),
+                    indices: args[2..last].iter().map(|s| s.to_string()).collect(),
+                    value: args[last].to_string(),
+                })
+            }
+
+            // Iterator: I iter source
+            "I" => {
+                Self::check_args(op, &args, line_num)?;
+                Ok(Instruction::Iter {
+                    iter: args[0].to_string(),
+                    source: args[1].to_string(),
+                })
+            }
+
+            // Advance iterator: N done var iter
+            "N" => {
+                Self::check_args(op, &args, line_num)?;
+                Ok(Instruction::Next {
+                    done: args[0].to_string(),
+                    var: args[1].to_string(),
+                    iter: args[2].to_string(),
                 })
             }
 
@@ -267,7 +414,7 @@ impl Parser {
 
             // Output: . value
             "." => {
-                Self::check_args(op, &args, 1, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Output {
                     value: args[0].to_string(),
                 })
@@ -275,7 +422,7 @@ impl Parser {
 
             // Input: , var
             "," => {
-                Self::check_args(op, &args, 1, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 Ok(Instruction::Input {
                     var: args[0].to_string(),
                 })
@@ -284,7 +431,7 @@ impl Parser {
             // Rust FFI: R result "func" args...
             // Also accept P for Python compatibility
             "R" | "P" => {
-                Self::check_args(op, &args, 2, line_num)?;
+                Self::check_args(op, &args, line_num)?;
                 let func_args = args[2..].iter().map(|s| s.to_string()).collect();
                 Ok(Instruction::RustFFI {
                     result: args[0].to_string(),
@@ -298,8 +445,98 @@ impl Parser {
         }
     }
 
-    /// Check minimum argument count
-    fn check_args(op: &str, args: &[&str], min: usize, line_num: usize) -> Result<(), ParseError> {
+    /// Parse a single line, rejecting instructions newer than `level`
+    pub fn parse_line_at(
+        tokens: &[String],
+        line_num: usize,
+        level: LanguageVersion,
+    ) -> Result<Instruction, ParseError> {
+        let instr = Self::parse_line(tokens, line_num)?;
+        if let Some(syntax) = tokens.first().and_then(|op| Self::syntax(op)) {
+            if syntax.since > level {
+                return Err(ParseError::RequiresVersion(
+                    syntax.op.to_string(),
+                    line_num,
+                    syntax.since,
+                ));
+            }
+        }
+        Ok(instr)
+    }
+
+    /// Language level a program is parsed at
+    ///
+    /// A `;! sui X.Y` pragma selects its level, which must not exceed the
+    /// runtime's `level`; without a pragma the runtime's level applies.
+    pub fn language_level(
+        code: &str,
+        level: LanguageVersion,
+    ) -> Result<LanguageVersion, ParseError> {
+        match instruction_set::declared_version(code) {
+            None => Ok(level),
+            Some((line, Err(version))) => Err(ParseError::General(
+                line,
+                format!("Invalid language version: {}", version),
+            )),
+            Some((line, Ok(version))) if version > level => {
+                Err(ParseError::UnsupportedVersion(line, version, level))
+            }
+            Some((_, Ok(version))) => Ok(version),
+        }
+    }
+
+    /// Values of the `C` definitions among `token_lines`
+    ///
+    /// Lines that fail to parse are left to the caller to report.
+    fn constant_pool(
+        token_lines: &[(usize, Vec<String>)],
+        level: LanguageVersion,
+    ) -> Result<HashMap<i64, String>, ParseError> {
+        let mut constants = HashMap::new();
+        for (line_num, tokens) in token_lines {
+            if tokens.first().map(String::as_str) != Some("C") {
+                continue;
+            }
+            if let Ok(Instruction::Const { id, value }) = Self::parse_line_at(tokens, *line_num, level) {
+                match constants.insert(id, value.clone()) {
+                    Some(previous) if previous != value => {
+                        return Err(ParseError::General(
+                            *line_num,
+                            format!("Constant c{} redefined", id),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(constants)
+    }
+
+    /// Replace `c<id>` operands with the literals they name
+    fn substitute<'a>(
+        tokens: &'a [String],
+        constants: &HashMap<i64, String>,
+        line_num: usize,
+    ) -> Result<Cow<'a, [String]>, ParseError> {
+        let uses_constants = tokens[1..].iter().any(|t| Lexer::constant_ref(t).is_some());
+        if tokens[0] == "C" || !uses_constants {
+            return Ok(Cow::Borrowed(tokens));
+        }
+        let mut resolved = tokens.to_vec();
+        for token in &mut resolved[1..] {
+            if let Some(id) = Lexer::constant_ref(token) {
+                *token = constants
+                    .get(&id)
+                    .ok_or_else(|| ParseError::General(line_num, format!("Undefined constant {}", token)))?
+                    .clone();
+            }
+        }
+        Ok(Cow::Owned(resolved))
+    }
+
+    /// Check the argument count against the instruction table
+    fn check_args(op: &str, args: &[&str], line_num: usize) -> Result<(), ParseError> {
+        let min = Self::syntax(op).map_or(0, InstructionSyntax::min_args);
         if args.len() < min {
             Err(ParseError::MissingArguments(
                 op.to_string(),
@@ -312,72 +549,140 @@ impl Parser {
         }
     }
 
+    /// Usage metadata from the `;@` lines leading `code`
+    pub fn metadata(code: &str) -> Result<Metadata, ParseError> {
+        Metadata::parse(code)
+    }
+
+    /// Type hints on the operands of `code`, such as `v0:i`, in line order
+    pub fn annotations(code: &str) -> Result<Vec<Annotation>, ParseError> {
+        let mut annotations = Vec::new();
+        for (i, line) in code.lines().enumerate() {
+            annotations.extend(strip_annotations(&Lexer::tokenize_line(line), i + 1)?.1);
+        }
+        Ok(annotations)
+    }
+
     /// Parse complete source code into instructions and collect functions
     pub fn parse(code: &str) -> Result<(Vec<Instruction>, Vec<Function>), ParseError> {
-        let token_lines = Lexer::parse(code);
+        Self::parse_with_level(code, LANGUAGE_VERSION)
+    }
+
+    /// Parse like [`Parser::parse`] for a runtime supporting up to `level`
+    pub fn parse_with_level(
+        code: &str,
+        level: LanguageVersion,
+    ) -> Result<(Vec<Instruction>, Vec<Function>), ParseError> {
+        let (main, functions) = Self::parse_with(code, level, None)?;
+        Ok((main.into_iter().map(|(_, instr)| instr).collect(), functions))
+    }
+
+    /// Parse like [`Parser::parse_with_level`], pairing each top-level
+    /// instruction with its source line (1-based)
+    ///
+    /// Function bodies carry their lines in [`Function::lines`].
+    #[allow(clippy::type_complexity)]
+    pub fn parse_numbered(
+        code: &str,
+        level: LanguageVersion,
+    ) -> Result<(Vec<(usize, Instruction)>, Vec<Function>), ParseError> {
+        Self::parse_with(code, level, None)
+    }
+
+    /// Parse source code, skipping lines that fail to parse
+    ///
+    /// Bad lines are left out of the result and reported in the returned
+    /// error list instead of aborting the parse. A function whose closing
+    /// brace is missing is kept with the body collected so far.
+    pub fn parse_lossy(code: &str) -> (Vec<Instruction>, Vec<Function>, Vec<ParseError>) {
+        let mut errors = Vec::new();
+        // Lossy parsing records every error, so this never fails
+        let (main, functions) =
+            Self::parse_with(code, LANGUAGE_VERSION, Some(&mut errors)).unwrap_or_default();
+        let instructions = main.into_iter().map(|(_, instr)| instr).collect();
+        (instructions, functions, errors)
+    }
+
+    /// Shared parser; with `errors` set, failures are recorded and skipped
+    #[allow(clippy::type_complexity)]
+    fn parse_with(
+        code: &str,
+        level: LanguageVersion,
+        mut errors: Option<&mut Vec<ParseError>>,
+    ) -> Result<(Vec<(usize, Instruction)>, Vec<Function>), ParseError> {
+        let level = match Self::language_level(code, level) {
+            Ok(level) => level,
+            Err(e) => Self::recover(&mut errors, e).map(|()| level)?,
+        };
+        // Non-empty lines with their source line numbers
+        let token_lines: Vec<(usize, Vec<String>)> = code
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, Lexer::tokenize_line(line)))
+            .filter(|(_, tokens)| !tokens.is_empty())
+            .collect();
+        let constants = Self::constant_pool(&token_lines, level)
+            .or_else(|e| Self::recover(&mut errors, e).map(|()| HashMap::new()))?;
+        let parse = |tokens: &[String], line_num: usize| {
+            Self::substitute(tokens, &constants, line_num)
+                .and_then(|tokens| Self::parse_line_at(&tokens, line_num, level))
+        };
         let mut instructions = Vec::new();
         let mut functions = Vec::new();
 
         let mut i = 0;
-        let mut line_num = 1;
-
         while i < token_lines.len() {
-            let tokens = &token_lines[i];
-            let instr = Self::parse_line(tokens, line_num)?;
+            let (line_num, tokens) = &token_lines[i];
+            let parsed = parse(tokens, *line_num);
+            let Some(instr) = Self::recover_line(&mut errors, parsed)? else {
+                i += 1;
+                continue;
+            };
 
             match &instr {
                 Instruction::FuncDef { id, argc } => {
                     // Collect function body
+                    let header = *line_num;
                     let func_id = *id;
                     let arg_count = *argc;
                     let mut body = Vec::new();
+                    let mut lines = Vec::new();
                     i += 1;
-                    line_num += 1;
                     let mut depth = 1;
 
                     while i < token_lines.len() && depth > 0 {
-                        let inner_tokens = &token_lines[i];
-                        let inner_instr = Self::parse_line(inner_tokens, line_num)?;
-
-                        match &inner_instr {
-                            Instruction::FuncDef { .. } => {
-                                depth += 1;
-                                body.push(inner_instr);
-                            }
-                            Instruction::FuncEnd => {
-                                depth -= 1;
-                                if depth > 0 {
-                                    body.push(inner_instr);
-                                }
+                        let (inner_line, inner_tokens) = &token_lines[i];
+                        let parsed = parse(inner_tokens, *inner_line);
+                        if let Some(inner_instr) = Self::recover_line(&mut errors, parsed)? {
+                            match &inner_instr {
+                                Instruction::FuncDef { .. } => depth += 1,
+                                Instruction::FuncEnd => depth -= 1,
+                                _ => {}
                             }
-                            _ => {
+                            if depth > 0 {
                                 body.push(inner_instr);
+                                lines.push(*inner_line);
                             }
                         }
 
                         i += 1;
-                        line_num += 1;
                     }
 
                     if depth != 0 {
-                        return Err(ParseError::UnmatchedBrace(line_num));
+                        Self::recover(&mut errors, ParseError::UnmatchedBrace(header))?;
                     }
 
-                    functions.push(Function {
-                        id: func_id,
-                        arg_count,
-                        body,
-                    });
+                    let mut function = Function::new(func_id, arg_count, body);
+                    function.lines = lines;
+                    functions.push(function);
                 }
                 Instruction::FuncEnd => {
                     // Standalone } - skip
                     i += 1;
-                    line_num += 1;
                 }
                 _ => {
-                    instructions.push(instr);
+                    instructions.push((*line_num, instr));
                     i += 1;
-                    line_num += 1;
                 }
             }
         }
@@ -385,24 +690,87 @@ impl Parser {
         Ok((instructions, functions))
     }
 
-    /// Validate source code without executing
-    pub fn validate(code: &str) -> Vec<ParseError> {
-        let token_lines = Lexer::parse(code);
-        let mut errors = Vec::new();
-
-        for (i, tokens) in token_lines.iter().enumerate() {
-            if let Err(e) = Self::parse_line(tokens, i + 1) {
+    /// Record `e` when parsing lossily, otherwise fail with it
+    fn recover(errors: &mut Option<&mut Vec<ParseError>>, e: ParseError) -> Result<(), ParseError> {
+        match errors {
+            Some(errors) => {
                 errors.push(e);
+                Ok(())
             }
+            None => Err(e),
         }
+    }
+
+    /// Like [`Parser::recover`] for a parsed line; a recorded line yields `None`
+    fn recover_line(
+        errors: &mut Option<&mut Vec<ParseError>>,
+        parsed: Result<Instruction, ParseError>,
+    ) -> Result<Option<Instruction>, ParseError> {
+        match parsed {
+            Ok(instr) => Ok(Some(instr)),
+            Err(e) => Self::recover(errors, e).map(|()| None),
+        }
+    }
+
+    /// Parse every source line, keeping lines that fail to parse
+    ///
+    /// Returns one instruction per source line, so the instruction at index
+    /// `n` comes from line `n + 1` and error line numbers match the editor.
+    /// Blank and comment-only lines become [`Instruction::Empty`], lines that
+    /// fail to parse become [`Instruction::Invalid`], and function
+    /// definitions stay inline as `FuncDef` / `FuncEnd` markers. Only
+    /// line-level and language-level errors are reported; braces are not
+    /// matched.
+    pub fn parse_recovering(code: &str) -> (Vec<Instruction>, Vec<ParseError>) {
+        let mut instructions = Vec::new();
+        let mut errors = Vec::new();
+        let level = Self::language_level(code, LANGUAGE_VERSION).unwrap_or_else(|e| {
+            errors.push(e);
+            LANGUAGE_VERSION
+        });
+
+        let token_lines: Vec<(usize, Vec<String>)> = code
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, Lexer::tokenize_line(line)))
+            .collect();
+        let constants = Self::constant_pool(&token_lines, level).unwrap_or_else(|e| {
+            errors.push(e);
+            HashMap::new()
+        });
+
+        for (line, (line_num, tokens)) in code.lines().zip(&token_lines) {
+            let parsed = if tokens.is_empty() {
+                Ok(Instruction::Empty)
+            } else {
+                Self::substitute(tokens, &constants, *line_num)
+                    .and_then(|tokens| Self::parse_line_at(&tokens, *line_num, level))
+            };
+            match parsed {
+                Ok(instr) => instructions.push(instr),
+                Err(e) => {
+                    instructions.push(Instruction::Invalid {
+                        raw: line.trim().to_string(),
+                        error: e.clone(),
+                    });
+                    errors.push(e);
+                }
+            }
+        }
+
+        (instructions, errors)
+    }
 
-        errors
+    /// Validate source code without executing
+    pub fn validate(code: &str) -> Vec<ParseError> {
+        Self::parse_recovering(code).1
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::interpreter::{OperandKind, INSTRUCTION_TABLE};
 
     #[test]
     fn test_parse_assignment() {
@@ -426,6 +794,27 @@ mod tests {
         assert_eq!(funcs[0].arg_count, 1);
     }
 
+    #[test]
+    fn test_parse_multi_index() {
+        let (instrs, _) = Parser::parse("] v0 v1 2 v3\n{ v1 2 v3 4\n{ v1 0 1").unwrap();
+        assert_eq!(
+            instrs[0],
+            Instruction::ArrayRead {
+                result: "v0".to_string(),
+                arr: "v1".to_string(),
+                idx: "2".to_string(),
+                indices: vec!["v3".to_string()],
+            }
+        );
+        assert!(matches!(
+            &instrs[1],
+            Instruction::ArrayWrite { indices, value, .. } if indices == &["v3"] && value == "4"
+        ));
+        assert!(matches!(&instrs[2], Instruction::ArrayWrite { indices, .. } if indices.is_empty()));
+        assert_eq!(instrs[0].to_string(), "] v0 v1 2 v3");
+        assert_eq!(instrs[1].to_string(), "{ v1 2 v3 4");
+    }
+
     #[test]
     fn test_validate() {
         let code = "= v0 10\n+ v1 v0 5";
@@ -439,4 +828,116 @@ mod tests {
         let errors = Parser::validate(code);
         assert!(!errors.is_empty());
     }
+
+    #[test]
+    fn test_instruction_table_matches_parser() {
+        for syntax in INSTRUCTION_TABLE {
+            let mut tokens = vec![syntax.op.to_string()];
+            for kind in syntax.operands {
+                match kind {
+                    OperandKind::Variable => tokens.push("v0".to_string()),
+                    OperandKind::Value | OperandKind::Integer | OperandKind::Literal => tokens.push("1".to_string()),
+                    OperandKind::String => tokens.push("\"x\"".to_string()),
+                    OperandKind::OpenBrace => tokens.push("{".to_string()),
+                    OperandKind::Values | OperandKind::Labels => {}
+                }
+            }
+            assert_eq!(syntax.params.len(), syntax.operands.len(), "{}", syntax.name);
+            let instr = Parser::parse_line(&tokens, 1).unwrap();
+            assert!(!matches!(instr, Instruction::Empty), "{}", syntax.name);
+            if syntax.min_args() > 0 {
+                tokens.pop();
+                let short = Parser::parse_line(&tokens, 1);
+                assert!(!matches!(short, Ok(ref i) if *i == instr), "{}", syntax.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_language_level_pragma() {
+        let old = LanguageVersion::new(0, 9);
+        let err = Parser::parse_with_level("= v0 1\n. v0", old).unwrap_err();
+        assert_eq!(err, ParseError::RequiresVersion("=".to_string(), 1, LanguageVersion::new(1, 0)));
+
+        // The pragma may lower the level but not raise it past the runtime
+        let err = Parser::parse_with_level(";! sui 1.0\n. 1", old).unwrap_err();
+        assert_eq!(err, ParseError::UnsupportedVersion(1, LanguageVersion::new(1, 0), old));
+        assert!(Parser::parse(";! sui 1.0\n# 0 0 {\n^ 1\n}").is_ok());
+
+        let errors = Parser::validate("; header\n;! sui one\n. 1");
+        assert_eq!(errors, vec![ParseError::General(2, "Invalid language version: one".to_string())]);
+    }
+
+    #[test]
+    fn test_constant_pool() {
+        let code = "C 0 \"a long message\"\nC 1 2.5\n. c0\n# 0 0 {\n^ c1\n}\n= v0 c1";
+        let (instrs, funcs) = Parser::parse(code).unwrap();
+        assert_eq!(instrs[2].to_string(), ". \"a long message\"");
+        assert_eq!(instrs[3].to_string(), "= v0 2.5");
+        assert_eq!(funcs[0].body[0].to_string(), "^ 2.5");
+
+        // Definitions are hoisted and may repeat with the same value
+        assert!(Parser::parse(". c0\nC 0 1\nC 0 1").is_ok());
+
+        let errors = Parser::validate("C 0 1\nC 0 2\n. c3\nC 1 v0\nC 2 c0");
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::General(2, "Constant c0 redefined".to_string()),
+                ParseError::General(3, "Undefined constant c3".to_string()),
+                ParseError::General(4, "Constant value must be a literal: v0".to_string()),
+                ParseError::General(5, "Constant value must be a literal: c0".to_string()),
+            ]
+        );
+
+        let err = Parser::parse(";! sui 1.0\nC 0 1").unwrap_err();
+        assert_eq!(err, ParseError::RequiresVersion("C".to_string(), 2, LanguageVersion::new(1, 1)));
+    }
+
+    #[test]
+    fn test_parse_lossy_skips_bad_lines() {
+        let code = "= v0 1\n= v1\nX v2 3\n# 0 0 {\n+ v0\n^ 1\n}\n. v0";
+        let (instrs, funcs, errors) = Parser::parse_lossy(code);
+        assert_eq!(instrs.len(), 2);
+        assert_eq!(funcs.len(), 1);
+        assert_eq!(funcs[0].body.len(), 1);
+        assert_eq!(errors.len(), 3);
+        assert!(Parser::parse(code).is_err());
+    }
+
+    #[test]
+    fn test_parse_recovering_keeps_lines() {
+        let code = "= v0 1\n\n; note\nX v2 3\n# 0 0 {\n+ v0\n}\n. v0";
+        let (instrs, errors) = Parser::parse_recovering(code);
+        assert_eq!(instrs.len(), 8);
+        assert_eq!(instrs[1], Instruction::Empty);
+        assert_eq!(instrs[2], Instruction::Empty);
+        assert!(matches!(&instrs[3], Instruction::Invalid { raw, .. } if raw == "X v2 3"));
+        assert_eq!(instrs[4], Instruction::FuncDef { id: 0, argc: 0 });
+        assert!(matches!(
+            &instrs[5],
+            Instruction::Invalid { error: ParseError::MissingArguments(_, 6, 3, 1), .. }
+        ));
+        assert_eq!(instrs[7].to_string(), ". v0");
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::InvalidInstruction("X".to_string(), 4),
+                ParseError::MissingArguments("+".to_string(), 6, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_source_lines() {
+        let errors = Parser::validate("; header\n\n= v0");
+        assert_eq!(errors, vec![ParseError::MissingArguments("=".to_string(), 3, 2, 1)]);
+    }
+
+    #[test]
+    fn test_parse_lossy_unterminated_function() {
+        let (_, funcs, errors) = Parser::parse_lossy("# 0 0 {\n^ 1");
+        assert_eq!(funcs.len(), 1);
+        assert!(matches!(errors[..], [ParseError::UnmatchedBrace(_)]));
+    }
 }
@@ -0,0 +1,100 @@
+//! JSON Lines records of runs, for building datasets of executions
+//!
+//! With [`Interpreter::set_run_logger`](super::Interpreter::set_run_logger),
+//! every [`run`](super::Interpreter::run),
+//! [`run_incremental`](super::Interpreter::run_incremental) and
+//! [`run_file`](super::Interpreter::run_file) appends one line:
+//!
+//! ```text
+//! {"code_hash":"9f86…","code":". 1","file":null,"args":[],"output":["1"],"error":null,
+//!  "steps":1,"started_at":1760000000.125,"finished_at":1760000000.126,"elapsed_ms":0.412}
+//! ```
+//!
+//! `code_hash` is the [`code_hash`](super::code_hash) of `code`; times are
+//! seconds since 1970 on the host clock, even under a virtual clock.
+//! Output is logged whatever the [`OutputMode`](super::OutputMode).
+
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::code_hash;
+use super::json::write_string;
+
+/// Destination of run records
+pub struct RunLogger {
+    writer: Box<dyn Write + Send>,
+    /// Lines printed by the current run
+    pub(crate) output: Vec<String>,
+}
+
+/// What a finished run reports to the logger
+pub(crate) struct RunRecord<'a> {
+    pub code: &'a str,
+    pub file: Option<&'a Path>,
+    pub args: &'a [String],
+    pub error: Option<String>,
+    pub steps: u64,
+    pub elapsed: Duration,
+}
+
+impl RunLogger {
+    /// Log to `writer`
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        RunLogger { writer: Box::new(writer), output: Vec::new() }
+    }
+
+    /// Log to the end of the file at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+
+    /// Write the record of a run that just finished
+    pub(crate) fn log(&mut self, record: &RunRecord) -> io::Result<()> {
+        let finished = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let started = finished.saturating_sub(record.elapsed);
+
+        let mut line = String::from("{\"code_hash\":\"");
+        line.push_str(&code_hash(record.code));
+        line.push_str("\",\"code\":");
+        write_string(&mut line, record.code);
+        line.push_str(",\"file\":");
+        match record.file {
+            Some(file) => write_string(&mut line, &file.display().to_string()),
+            None => line.push_str("null"),
+        }
+        line.push_str(",\"args\":");
+        write_strings(&mut line, record.args);
+        line.push_str(",\"output\":");
+        write_strings(&mut line, &std::mem::take(&mut self.output));
+        line.push_str(",\"error\":");
+        match &record.error {
+            Some(error) => write_string(&mut line, error),
+            None => line.push_str("null"),
+        }
+        let _ = write!(
+            line,
+            ",\"steps\":{},\"started_at\":{:.3},\"finished_at\":{:.3},\"elapsed_ms\":{:.3}}}",
+            record.steps,
+            started.as_secs_f64(),
+            finished.as_secs_f64(),
+            record.elapsed.as_secs_f64() * 1000.0
+        );
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+}
+
+fn write_strings(out: &mut String, items: &[String]) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(out, item);
+    }
+    out.push(']');
+}
@@ -1,10 +1,22 @@
 //! Runtime interpreter for the Sui programming language
 
-use super::{Function, Instruction, Lexer, Parser, ParseError, Value};
+use super::{BigInt, Channels, CompiledProgram, Function, FunctionLimits, FunctionUsage, Instruction, Interner, Lexer, Parser, ParseError, RunLogger, RunStats, Trace, TraceEvent, Value, MAX_ARRAY_SIZE, MAX_INTEGER_BITS};
+use super::runlog::RunRecord;
+use super::io::{lock, HostOutput, IoHandler, OutputSource, TranscriptEntry};
+use super::format::{format_date, format_template};
+use super::json;
 use super::lexer::ParsedValue;
-use std::collections::{HashMap, HashSet};
+use super::instruction_set::{LanguageVersion, LANGUAGE_VERSION};
+use regex::{NoExpand, Regex};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 /// Interpreter errors
@@ -19,8 +31,11 @@ pub enum InterpreterError {
     #[error("Undefined function: {0}")]
     UndefinedFunction(i64),
 
-    #[error("Undefined variable: {0}")]
-    UndefinedVariable(String),
+    #[error("Undefined variable {name} at line {line}")]
+    UndefinedVariable { name: String, line: usize },
+
+    #[error("Write to argument {name} at line {line}; arguments are read-only")]
+    ArgumentWrite { name: String, line: usize },
 
     #[error("Array index out of bounds: {index} (length: {length})")]
     IndexOutOfBounds { index: i64, length: usize },
@@ -28,8 +43,8 @@ pub enum InterpreterError {
     #[error("Type error: {0}")]
     TypeError(String),
 
-    #[error("Division by zero")]
-    DivisionByZero,
+    #[error("Division by zero at line {line}")]
+    DivisionByZero { line: usize },
 
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
@@ -42,8 +57,204 @@ pub enum InterpreterError {
 
     #[error("Circular import detected: {0}")]
     CircularImport(String),
+
+    #[error("Array size too large: {0} (maximum: {MAX_ARRAY_SIZE})")]
+    ArrayTooLarge(i64),
+
+    #[error("Integer too large: {0} bits (maximum: {MAX_INTEGER_BITS})")]
+    IntegerTooLarge(u64),
+
+    #[error("Step limit exceeded: {0}")]
+    StepLimitExceeded(u64),
+
+    /// A function used more than its [`FunctionLimits`] allow
+    #[error("Function {func_id} exceeded its limit of {limit} {resource} at line {line}")]
+    FunctionLimitExceeded { func_id: i64, resource: &'static str, limit: u64, line: usize },
+
+    #[error("Interrupted by step hook at line {line}")]
+    Interrupted { line: usize },
+
+    #[error("Uncaught error at line {line}: {value}")]
+    Raised { value: Value, line: usize },
+
+    /// A builtin needs a capability the host did not grant
+    #[error("Permission denied at line {line}: {builtin} needs {capability}")]
+    PermissionDenied { builtin: String, capability: &'static str, line: usize },
+
+    /// The run was suspended by `yield` or its time slice running out;
+    /// only seen by a [`Scheduler`](crate::concurrency::Scheduler)
+    #[error("Suspended at line {line}")]
+    Yielded { line: usize },
+}
+
+impl InterpreterError {
+    /// Whether a try region (`T`) may handle the error
+    ///
+    /// The step and function limits and step hook interruptions, which
+    /// guard the host, cannot be caught.
+    pub fn is_catchable(&self) -> bool {
+        !matches!(
+            self,
+            InterpreterError::StepLimitExceeded(_)
+                | InterpreterError::FunctionLimitExceeded { .. }
+                | InterpreterError::Interrupted { .. }
+                | InterpreterError::Yielded { .. }
+        )
+    }
+
+    /// Source line (1-based) the error happened at, if it names one
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            InterpreterError::Parse(e) => Some(e.line()),
+            InterpreterError::Runtime { line, .. }
+            | InterpreterError::UndefinedVariable { line, .. }
+            | InterpreterError::ArgumentWrite { line, .. }
+            | InterpreterError::DivisionByZero { line }
+            | InterpreterError::Interrupted { line }
+            | InterpreterError::Raised { line, .. }
+            | InterpreterError::PermissionDenied { line, .. }
+            | InterpreterError::FunctionLimitExceeded { line, .. }
+            | InterpreterError::Yielded { line } => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// Value a handler receives: the raised value, or the error message
+    fn handler_value(self) -> Value {
+        match self {
+            InterpreterError::Raised { value, .. } => value,
+            other => Value::from(other.to_string()),
+        }
+    }
+}
+
+/// What `/` and `%` produce when the divisor is zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivisionPolicy {
+    /// Produce NaN
+    #[default]
+    SilentNaN,
+    /// Fail with [`InterpreterError::DivisionByZero`], which `T` can catch
+    Error,
+    /// Produce 0
+    SaturateZero,
+}
+
+impl FromStr for DivisionPolicy {
+    type Err = String;
+
+    /// Parse `nan`, `error` or `zero`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nan" => Ok(DivisionPolicy::SilentNaN),
+            "error" => Ok(DivisionPolicy::Error),
+            "zero" => Ok(DivisionPolicy::SaturateZero),
+            _ => Err(format!("unknown division policy '{}' (expected nan, error or zero)", s)),
+        }
+    }
+}
+
+/// Where the `time` builtins get the time
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Clock {
+    /// The system clock; `time.sleep_ms` blocks
+    #[default]
+    System,
+    /// Simulated time that starts at `epoch` (seconds since 1970) with
+    /// each run and only moves when `time.sleep_ms` advances it, instantly;
+    /// `randint`, seeded from the time, repeats its sequence on every run
+    Virtual { epoch: f64 },
+}
+
+/// Where `.` output goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Collect it, to be returned by [`Interpreter::run`]
+    #[default]
+    Captured,
+    /// Print it to stdout as it happens
+    Stdout,
+    /// Collect and print it
+    Both,
+}
+
+impl OutputMode {
+    /// Whether output is collected
+    pub fn captures(self) -> bool {
+        self != OutputMode::Stdout
+    }
+
+    /// Whether output is printed
+    pub fn prints(self) -> bool {
+        self != OutputMode::Captured
+    }
+}
+
+/// Progress of a run, as seen by a step hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookCtx {
+    /// Source line of the instruction about to run
+    pub line: usize,
+    /// Instructions executed in this run, including that one
+    pub steps: u64,
+    /// Time since the run started
+    pub elapsed: Duration,
+}
+
+/// Variables of a run stopped before a line, from
+/// [`Interpreter::run_until_line`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateSnapshot {
+    /// Line the run stopped before, or `None` if it finished without
+    /// reaching it
+    pub line: Option<usize>,
+    /// Functions of the calls in progress, outermost first; empty in the
+    /// main code
+    pub call_stack: Vec<i64>,
+    /// Assigned globals, in index order
+    pub globals: Vec<(i64, Value)>,
+    /// Assigned locals of the current function or main code, in index order
+    pub locals: Vec<(i64, Value)>,
+    /// Arguments of the current function
+    pub args: Vec<Value>,
+    /// Output printed so far
+    pub output: Vec<String>,
+    /// Instructions executed before stopping
+    pub steps: u64,
+}
+
+impl fmt::Display for StateSnapshot {
+    /// Where the run stopped, then a line per variable, with values as
+    /// JSON so `5`, `5.0` and `"5"` stay apart
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Before line {}", line)?,
+            None => write!(f, "Finished")?,
+        }
+        if let Some(function) = self.call_stack.last() {
+            write!(f, " in function {}", function)?;
+        }
+        write!(f, " after {} steps", self.steps)?;
+        let args = self.args.iter().enumerate().map(|(i, value)| (format!("a{}", i), value));
+        let locals = self.locals.iter().map(|(i, value)| (format!("v{}", i), value));
+        let globals = self.globals.iter().map(|(i, value)| (format!("g{}", i), value));
+        for (name, value) in args.chain(locals).chain(globals) {
+            write!(f, "\n{} = {}", name, json::encode(value))?;
+        }
+        Ok(())
+    }
 }
 
+/// Callback deciding whether a run goes on
+type StepHook = Box<dyn FnMut(&HookCtx) -> ControlFlow<()> + Send>;
+
+/// How long `chan.send` and `chan.recv` wait on shared channels between
+/// calls of the step hook
+const CHANNEL_WAIT: Duration = Duration::from_millis(10);
+
+/// Builtin added by the host, called with the resolved arguments of `R`
+type HostBuiltin = Arc<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
 /// Execution context for a scope
 #[derive(Debug, Clone, Default)]
 struct Context {
@@ -55,28 +266,118 @@ struct Context {
     return_value: Value,
     /// Whether return was called
     returned: bool,
+    /// Active try regions, innermost last: error variable and handler label
+    handlers: Vec<(String, i64)>,
+    /// Function this context runs, `None` at top level
+    func_id: Option<i64>,
 }
 
 /// Sui interpreter
+///
+/// An interpreter is `Send`, so a pool of them can serve requests on
+/// different threads, but not `Sync`: each runs one program at a time from
+/// one thread. [`Value`]s are `Send + Sync` and share strings and arrays by
+/// reference count, so results can be handed between threads cheaply. `,`
+/// and printed output use the process-wide stdin and stdout, which is only
+/// meaningful for one program at a time; use [`OutputMode::Captured`]
+/// (the default) in servers.
 pub struct Interpreter {
     /// Global variables (g0, g1, ...)
     global_vars: HashMap<i64, Value>,
     /// Function definitions
-    functions: HashMap<i64, Function>,
+    functions: HashMap<i64, Arc<Function>>,
     /// Context stack for nested calls
     context_stack: Vec<Context>,
     /// Current context
     context: Context,
     /// Output buffer
     output: Vec<String>,
+    /// Whether output is collected, printed or both
+    output_mode: OutputMode,
+    /// Where printed output goes, shared with host builtins
+    io: Arc<Mutex<IoHandler>>,
+    /// Whether `io` keeps a transcript
+    transcript: bool,
+    /// Source line of the instruction running
+    line: usize,
     /// Maximum call stack depth
     max_stack_depth: usize,
+    /// Maximum number of executed instructions per run
+    max_steps: Option<u64>,
+    /// Instructions executed in the current run
+    steps: u64,
+    /// Whether calls, instructions and allocations are counted by function
+    accounting: bool,
+    /// What each function used in the current run
+    usage: HashMap<i64, FunctionUsage>,
+    /// Limits of single functions, by ID
+    function_limits: HashMap<i64, FunctionLimits>,
+    /// When the current run started
+    started: Instant,
+    /// Callback run every so many instructions
+    step_hook: Option<(u64, StepHook)>,
     /// Debug mode
     debug: bool,
+    /// Newest language level programs may use
+    language_level: LanguageVersion,
+    /// Reject reads of variables that were never assigned
+    strict: bool,
+    /// Result of dividing by zero
+    division_policy: DivisionPolicy,
     /// Current file path (for resolving relative imports)
     current_file: Option<PathBuf>,
     /// Loaded modules (for caching and cycle detection)
     loaded_modules: HashSet<PathBuf>,
+    /// Interned string constants of the loaded code
+    strings: Interner,
+    /// Record runs into `trace`
+    recording: bool,
+    /// Trace of the current or last run, when recording
+    trace: Option<Trace>,
+    /// Channels for `chan.send` and `chan.recv`, created on first use
+    channels: Option<Channels>,
+    /// Whether `channels` came from `set_channels`, so other programs use
+    /// them too
+    channels_shared: bool,
+    /// Whether `yield` and the time slice suspend the run
+    cooperative: bool,
+    /// Instructions a run executes before it is suspended
+    time_slice: Option<u64>,
+    /// Instructions executed since the run was last resumed
+    slice_steps: u64,
+    /// Where each active block of a suspended run continues, innermost first
+    resume_at: Vec<usize>,
+    /// Source of `time.now` and `time.monotonic`
+    clock: Clock,
+    /// Whether `env.get` and `env.set` may use environment variables
+    env_allowed: bool,
+    /// Whether `proc.run` may start processes
+    process_allowed: bool,
+    /// Whether `chan.send` and `chan.recv` may be used
+    channels_allowed: bool,
+    /// Compiled patterns of the `re` builtins
+    regexes: HashMap<String, Regex>,
+    /// Time slept in the current run under a virtual clock
+    slept: Duration,
+    /// State of the `randint` generator under a virtual clock
+    rng: Cell<u64>,
+    /// Line a run stops before, for `run_until_line`
+    stop_line: Option<usize>,
+    /// State where the run stopped
+    stopped: Option<StateSnapshot>,
+    /// Builtins added with `register_builtin`, by name
+    host_builtins: HashMap<String, HostBuiltin>,
+    /// Lines `,` reads instead of stdin, if set
+    input: Option<VecDeque<String>>,
+    /// Where to record each run, if anywhere
+    run_logger: Option<RunLogger>,
+}
+
+/// Main code of a program run in slices
+pub(crate) struct Program {
+    instructions: Vec<Instruction>,
+    labels: HashMap<i64, usize>,
+    lines: Vec<usize>,
 }
 
 impl Default for Interpreter {
@@ -94,10 +395,45 @@ impl Interpreter {
             context_stack: Vec::new(),
             context: Context::default(),
             output: Vec::new(),
+            output_mode: OutputMode::default(),
+            io: Arc::default(),
+            transcript: false,
+            line: 0,
             max_stack_depth: 1000,
+            max_steps: None,
+            steps: 0,
+            accounting: false,
+            usage: HashMap::new(),
+            function_limits: HashMap::new(),
+            started: Instant::now(),
+            step_hook: None,
             debug: false,
+            language_level: LANGUAGE_VERSION,
+            strict: false,
+            division_policy: DivisionPolicy::default(),
             current_file: None,
             loaded_modules: HashSet::new(),
+            strings: Interner::new(),
+            recording: false,
+            trace: None,
+            channels: None,
+            channels_shared: false,
+            cooperative: false,
+            time_slice: None,
+            slice_steps: 0,
+            resume_at: Vec::new(),
+            clock: Clock::default(),
+            slept: Duration::ZERO,
+            rng: Cell::new(0),
+            stop_line: None,
+            stopped: None,
+            host_builtins: HashMap::new(),
+            input: None,
+            run_logger: None,
+            env_allowed: false,
+            process_allowed: false,
+            channels_allowed: true,
+            regexes: HashMap::new(),
         }
     }
 
@@ -106,11 +442,318 @@ impl Interpreter {
         self.debug = debug;
     }
 
+    /// Emulate a runtime supporting language levels up to `level`
+    ///
+    /// Programs using newer instructions, or declaring a newer level with a
+    /// `;! sui X.Y` pragma, fail to parse. Defaults to [`LANGUAGE_VERSION`].
+    pub fn set_language_level(&mut self, level: LanguageVersion) {
+        self.language_level = level;
+    }
+
+    /// Enable or disable strict mode
+    ///
+    /// In strict mode, reading a `v`, `g` or `a` variable that was never
+    /// assigned fails with [`InterpreterError::UndefinedVariable`] instead of
+    /// reading 0, and assigning an `a` variable fails with
+    /// [`InterpreterError::ArgumentWrite`] instead of doing nothing.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Choose where the `time` builtins get the time
+    ///
+    /// With [`Clock::Virtual`], programs that sleep run without waiting and
+    /// see the same times on every run.
+    ///
+    /// ```rust
+    /// use sui_lang::interpreter::{Clock, Interpreter};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.set_clock(Clock::Virtual { epoch: 1000.0 });
+    /// let code = "R v0 \"time.sleep_ms\" 60000\nR v1 \"time.now\"\n. v1";
+    /// assert_eq!(interp.run(code, &[]).unwrap(), ["1060.0"]);
+    /// ```
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Let programs read and set environment variables with `env.get` and
+    /// `env.set`; off by default
+    pub fn allow_env(&mut self, allowed: bool) {
+        self.env_allowed = allowed;
+    }
+
+    /// Let programs run commands with `proc.run`; off by default
+    ///
+    /// Commands run with the host's privileges, so only allow this for
+    /// trusted programs.
+    pub fn allow_process(&mut self, allowed: bool) {
+        self.process_allowed = allowed;
+    }
+
+    /// Let programs use `chan.send` and `chan.recv`; on by default
+    ///
+    /// Servers running untrusted programs turn this off, since a program
+    /// waiting on a shared channel holds its thread until the step hook
+    /// stops it.
+    pub fn allow_channels(&mut self, allowed: bool) {
+        self.channels_allowed = allowed;
+    }
+
+    /// Read `,` input from these lines instead of stdin; when they run
+    /// out, `,` reads an empty line as at the end of stdin
+    ///
+    /// The queue is kept across runs, for hosts such as browsers that have
+    /// no stdin.
+    pub fn set_input_queue(&mut self, lines: Vec<String>) {
+        self.input = Some(lines.into());
+    }
+
+    /// Queue one more line of input
+    pub fn feed_input(&mut self, line: &str) {
+        self.input.get_or_insert_with(VecDeque::new).push_back(line.to_string());
+    }
+
+    /// Choose where `.` output goes
+    ///
+    /// Defaults to [`OutputMode::Captured`], so embedding programs keep
+    /// their stdout; the command-line tools print. Output that is not
+    /// captured is missing from what [`run`](Self::run) returns.
+    pub fn set_output_mode(&mut self, mode: OutputMode) {
+        self.output_mode = mode;
+        lock(&self.io).print = mode.prints();
+    }
+
+    /// Flush stdout after every printed line, not just before calls of
+    /// registered builtins
+    pub fn set_autoflush(&mut self, autoflush: bool) {
+        lock(&self.io).autoflush = autoflush;
+    }
+
+    /// Keep a [`transcript`](Self::transcript) of each run's output
+    pub fn set_transcript(&mut self, enabled: bool) {
+        self.transcript = enabled;
+        lock(&self.io).recording = enabled;
+    }
+
+    /// Every line the last run printed, from the program and from host
+    /// builtins through [`host_output`](Self::host_output), in order
+    ///
+    /// Empty unless enabled with [`set_transcript`](Self::set_transcript).
+    /// Lines are recorded whatever the output mode.
+    pub fn transcript(&self) -> Vec<TranscriptEntry> {
+        lock(&self.io).transcript.clone()
+    }
+
+    /// Handle for registered builtins to print through, so their output is
+    /// ordered with the program's
+    pub fn host_output(&self) -> HostOutput {
+        HostOutput(self.io.clone())
+    }
+
+    /// Print `text` as output of the program
+    fn write_output(&mut self, text: String) {
+        if self.output_mode.prints() || self.transcript {
+            lock(&self.io).write(OutputSource::Program, self.line, &text);
+        }
+        if self.output_mode.captures() {
+            self.output.push(text);
+        }
+    }
+
+    /// Choose what `/` and `%` do when the divisor is zero
+    pub fn set_division_policy(&mut self, policy: DivisionPolicy) {
+        self.division_policy = policy;
+    }
+
     /// Set maximum stack depth
     pub fn set_max_stack_depth(&mut self, depth: usize) {
         self.max_stack_depth = depth;
     }
 
+    /// Limit the number of instructions a run may execute
+    ///
+    /// Exceeding the limit aborts the run with
+    /// [`InterpreterError::StepLimitExceeded`]. `None` (the default) means no
+    /// limit.
+    pub fn set_max_steps(&mut self, steps: Option<u64>) {
+        self.max_steps = steps;
+    }
+
+    /// Count the calls, instructions and array elements of each function
+    /// for [`stats`](Self::stats); off by default
+    pub fn set_accounting(&mut self, enabled: bool) {
+        self.accounting = enabled;
+    }
+
+    /// Limit what function `id` may use in a run, over all its calls
+    ///
+    /// Exceeding a limit aborts the run with
+    /// [`InterpreterError::FunctionLimitExceeded`], so a host can run an
+    /// untrusted module's functions on a tighter budget than the rest of the
+    /// program. Limits are enforced with accounting on or off.
+    ///
+    /// ```rust
+    /// use sui_lang::interpreter::FunctionLimits;
+    /// use sui_lang::{Interpreter, InterpreterError};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.set_function_limits(0, FunctionLimits { max_steps: Some(100), max_allocated: None });
+    /// let result = interp.run("# 0 0 {\n: 0\n@ 0\n}\n$ v0 0", &[]);
+    /// assert!(matches!(result, Err(InterpreterError::FunctionLimitExceeded { func_id: 0, limit: 100, .. })));
+    /// ```
+    pub fn set_function_limits(&mut self, id: i64, limits: FunctionLimits) {
+        self.function_limits.insert(id, limits);
+    }
+
+    /// Call `hook` before every `every`-th instruction of a run
+    ///
+    /// Returning [`ControlFlow::Break`] aborts the run with
+    /// [`InterpreterError::Interrupted`], for cancellation, progress
+    /// reporting or timeouts finer than [`set_max_steps`](Self::set_max_steps).
+    ///
+    /// ```rust
+    /// use std::ops::ControlFlow;
+    /// use std::time::Duration;
+    /// use sui_lang::{Interpreter, InterpreterError};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.set_step_hook(1000, |ctx| {
+    ///     if ctx.elapsed > Duration::from_millis(50) { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    /// });
+    /// let result = interp.run(": 0\n@ 0", &[]);
+    /// assert!(matches!(result, Err(InterpreterError::Interrupted { line: 2 })));
+    /// ```
+    pub fn set_step_hook(&mut self, every: u64, hook: impl FnMut(&HookCtx) -> ControlFlow<()> + Send + 'static) {
+        self.step_hook = Some((every.max(1), Box::new(hook)));
+    }
+
+    /// Remove the step hook
+    pub fn clear_step_hook(&mut self) {
+        self.step_hook = None;
+    }
+
+    /// Append a JSON record of every run to `logger`: the code and its
+    /// hash, arguments, output, error, step count and times
+    ///
+    /// A record that cannot be written is dropped without failing the run.
+    ///
+    /// ```rust
+    /// use sui_lang::interpreter::{Interpreter, RunLogger};
+    ///
+    /// let path = std::env::temp_dir().join("sui-doc-runs.jsonl");
+    /// let mut interp = Interpreter::new();
+    /// interp.set_run_logger(RunLogger::open(&path).unwrap());
+    /// interp.run(". 42", &[]).unwrap();
+    /// let log = std::fs::read_to_string(&path).unwrap();
+    /// assert!(log.lines().last().unwrap().contains(r#""output":["42"]"#));
+    /// # std::fs::remove_file(&path).ok();
+    /// ```
+    pub fn set_run_logger(&mut self, logger: RunLogger) {
+        self.run_logger = Some(logger);
+    }
+
+    /// Stop recording runs
+    pub fn clear_run_logger(&mut self) {
+        self.run_logger = None;
+    }
+
+    /// Add a builtin that `R` calls by name, replacing any added before
+    ///
+    /// A registered name takes precedence over a standard builtin of the
+    /// same name. The function gets the resolved arguments; an `Err` becomes
+    /// a runtime error at the calling line, which `T` can catch.
+    ///
+    /// ```rust
+    /// use sui_lang::{Interpreter, Value};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.register_builtin("text.shout", |args| match args.first() {
+    ///     Some(Value::String(s)) => Ok(Value::from(format!("{}!", s.to_uppercase()))),
+    ///     _ => Err("text.shout expects a string".to_string()),
+    /// });
+    /// let output = interp.run("R v0 \"text.shout\" \"hi\"\n. v0", &[]).unwrap();
+    /// assert_eq!(output, vec!["HI!"]);
+    /// ```
+    pub fn register_builtin(
+        &mut self,
+        name: impl Into<String>,
+        builtin: impl Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    ) {
+        self.host_builtins.insert(name.into(), Arc::new(builtin));
+    }
+
+    /// Share `channels` with other programs for `chan.send` and `chan.recv`
+    ///
+    /// Without this, the interpreter has channels of its own.
+    pub fn set_channels(&mut self, channels: Channels) {
+        self.channels = Some(channels);
+        self.channels_shared = true;
+    }
+
+    /// Record each run as a [`Trace`], retrieved with
+    /// [`take_trace`](Self::take_trace)
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    /// Trace of the last recorded run, also when it failed
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Start a trace of a run of `source`, with the argument globals
+    /// already set
+    fn begin_trace(&mut self, source: &str, args: &[String]) {
+        if !self.recording {
+            return;
+        }
+        let events = self
+            .globals()
+            .into_iter()
+            .map(|(idx, value)| TraceEvent::Write { depth: 0, var: format!("g{}", idx), value: value.clone() })
+            .collect();
+        self.trace = Some(Trace { source: source.to_string(), args: args.to_vec(), events, error: None });
+    }
+
+    /// Log an instruction about to run with the operands it reads
+    fn record_step(&mut self, instr: &Instruction, line: usize) {
+        let tokens = Lexer::tokenize_line(&instr.to_string());
+        let operands = match tokens.first().and_then(|op| Parser::syntax(op)) {
+            Some(syntax) => tokens[1..]
+                .iter()
+                .enumerate()
+                .filter(|(k, _)| syntax.reads(*k))
+                .map(|(_, token)| (token.clone(), self.resolve(token)))
+                .collect(),
+            None => Vec::new(),
+        };
+        let event = TraceEvent::Step {
+            line,
+            func_id: self.context.func_id.unwrap_or(-1),
+            depth: self.context_stack.len(),
+            operands,
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.events.push(event);
+        }
+    }
+
+    /// Log the current value of a variable after an assignment
+    fn record_write(&mut self, var: &str) {
+        if self.trace.is_none() {
+            return;
+        }
+        let event = TraceEvent::Write {
+            depth: self.context_stack.len(),
+            var: var.to_string(),
+            value: self.resolve(var),
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.events.push(event);
+        }
+    }
+
     /// Reset interpreter state
     pub fn reset(&mut self) {
         self.global_vars.clear();
@@ -120,6 +763,10 @@ impl Interpreter {
         self.output.clear();
         self.current_file = None;
         self.loaded_modules.clear();
+        self.strings.clear();
+        self.steps = 0;
+        self.usage.clear();
+        self.trace = None;
     }
 
     /// Set the current file path (for resolving imports)
@@ -161,11 +808,12 @@ impl Interpreter {
         self.current_file = Some(canonical);
 
         // Parse module
-        let (instructions, functions) = Parser::parse(&code)?;
+        let (instructions, functions) = Parser::parse_with_level(&code, self.language_level)?;
+        self.strings.intern_program(&instructions, &functions);
 
         // Add functions from module
         for func in functions {
-            self.functions.insert(func.id, func);
+            self.functions.insert(func.id, Arc::new(func));
         }
 
         // Process any imports in the module
@@ -183,10 +831,12 @@ impl Interpreter {
 
     /// Resolve a value reference to an actual Value
     fn resolve(&self, val: &str) -> Value {
+        if let Some(s) = self.strings.literal(val) {
+            return Value::String(s);
+        }
         match Lexer::parse_value(val) {
             ParsedValue::Variable(var) => {
-                let prefix = var.chars().next().unwrap();
-                let idx: i64 = var[1..].parse().unwrap_or(0);
+                let (prefix, idx) = Lexer::split_variable(&var);
 
                 match prefix {
                     'v' => self.context.local_vars.get(&idx).cloned().unwrap_or_default(),
@@ -197,14 +847,49 @@ impl Interpreter {
             }
             ParsedValue::Integer(n) => Value::Integer(n),
             ParsedValue::Float(f) => Value::Float(f),
-            ParsedValue::String(s) => Value::String(s),
+            ParsedValue::String(s) => Value::from(s),
         }
     }
 
+    /// Whether a variable holds a value in the current scope
+    fn is_defined(&self, var: &str) -> bool {
+        let (prefix, idx) = Lexer::split_variable(var);
+        match prefix {
+            'v' => self.context.local_vars.contains_key(&idx),
+            'g' => self.global_vars.contains_key(&idx),
+            'a' => idx >= 0 && (idx as usize) < self.context.args.len(),
+            _ => true,
+        }
+    }
+
+    /// Strict mode check that every variable `instr` reads is defined and
+    /// that it assigns no argument
+    fn check_defined(&self, instr: &Instruction, line: usize) -> Result<(), InterpreterError> {
+        let tokens = Lexer::tokenize_line(&instr.to_string());
+        let Some(syntax) = tokens.first().and_then(|op| Parser::syntax(op)) else {
+            return Ok(());
+        };
+        for (k, token) in tokens[1..].iter().enumerate() {
+            if (syntax.writes(k) || syntax.updates(k)) && token.starts_with('a') {
+                if let ParsedValue::Variable(name) = Lexer::parse_value(token) {
+                    return Err(InterpreterError::ArgumentWrite { name, line });
+                }
+            }
+            if !syntax.reads(k) {
+                continue;
+            }
+            if let ParsedValue::Variable(var) = Lexer::parse_value(token) {
+                if !self.is_defined(&var) {
+                    return Err(InterpreterError::UndefinedVariable { name: var, line });
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Assign a value to a variable
     fn assign(&mut self, var: &str, value: Value) {
-        let prefix = var.chars().next().unwrap_or('v');
-        let idx: i64 = var[1..].parse().unwrap_or(0);
+        let (prefix, idx) = Lexer::split_variable(var);
 
         match prefix {
             'v' => {
@@ -213,8 +898,10 @@ impl Interpreter {
             'g' => {
                 self.global_vars.insert(idx, value);
             }
-            _ => {} // Can't assign to arguments
+            // Arguments are read-only; strict mode rejects the write
+            _ => return,
         }
+        self.record_write(var);
     }
 
     /// Execute a single instruction
@@ -223,10 +910,18 @@ impl Interpreter {
         instr: &Instruction,
     ) -> Result<(bool, Option<i64>), InterpreterError> {
         match instr {
-            Instruction::Empty | Instruction::Comment | Instruction::FuncDef { .. } | Instruction::FuncEnd => {
+            Instruction::Empty
+            | Instruction::Comment
+            | Instruction::Const { .. }
+            | Instruction::FuncDef { .. }
+            | Instruction::FuncEnd => {
                 // No-op
             }
 
+            Instruction::Invalid { error, .. } => {
+                return Err(InterpreterError::Parse(error.clone()));
+            }
+
             Instruction::Import { path } => {
                 // Load the imported module
                 self.load_module(path)?;
@@ -238,27 +933,34 @@ impl Interpreter {
             }
 
             Instruction::Add { result, a, b } => {
-                let val = self.resolve(a).add(&self.resolve(b));
+                let val = check_integer(self.resolve(a).add(&self.resolve(b)))?;
                 self.assign(result, val);
             }
 
             Instruction::Sub { result, a, b } => {
-                let val = self.resolve(a).sub(&self.resolve(b));
+                let val = check_integer(self.resolve(a).sub(&self.resolve(b)))?;
                 self.assign(result, val);
             }
 
             Instruction::Mul { result, a, b } => {
-                let val = self.resolve(a).mul(&self.resolve(b));
+                let (a, b) = (self.resolve(a), self.resolve(b));
+                // Refuse before multiplying: the product has at least this
+                // many bits
+                let bits = (a.integer_bits() + b.integer_bits()).saturating_sub(1);
+                if bits > MAX_INTEGER_BITS {
+                    return Err(InterpreterError::IntegerTooLarge(bits));
+                }
+                let val = check_integer(a.mul(&b))?;
                 self.assign(result, val);
             }
 
             Instruction::Div { result, a, b } => {
-                let val = self.resolve(a).div(&self.resolve(b));
+                let val = self.divide(&self.resolve(a), &self.resolve(b), Value::div)?;
                 self.assign(result, val);
             }
 
             Instruction::Mod { result, a, b } => {
-                let val = self.resolve(a).modulo(&self.resolve(b));
+                let val = self.divide(&self.resolve(a), &self.resolve(b), Value::modulo)?;
                 self.assign(result, val);
             }
 
@@ -272,6 +974,16 @@ impl Interpreter {
                 self.assign(result, val);
             }
 
+            Instruction::Le { result, a, b } => {
+                let val = self.resolve(a).le(&self.resolve(b));
+                self.assign(result, val);
+            }
+
+            Instruction::Ge { result, a, b } => {
+                let val = self.resolve(a).ge(&self.resolve(b));
+                self.assign(result, val);
+            }
+
             Instruction::Eq { result, a, b } => {
                 let val = self.resolve(a).eq_val(&self.resolve(b));
                 self.assign(result, val);
@@ -314,13 +1026,23 @@ impl Interpreter {
                 return Ok((true, Some(*label)));
             }
 
+            Instruction::Switch { value, labels } => {
+                if let Some(index) = self.resolve(value).table_index(labels.len()) {
+                    return Ok((true, Some(labels[index])));
+                }
+            }
+
             Instruction::Label { .. } => {
                 // Labels are handled during execution flow
             }
 
             Instruction::Call { result, func_id, args } => {
+                // Re-entering a call of a suspended run, whose context is
+                // already in place
+                let resuming = !self.resume_at.is_empty();
+
                 // Check stack depth
-                if self.context_stack.len() >= self.max_stack_depth {
+                if !resuming && self.context_stack.len() >= self.max_stack_depth {
                     return Err(InterpreterError::StackOverflow);
                 }
 
@@ -328,30 +1050,41 @@ impl Interpreter {
                 let func = self
                     .functions
                     .get(func_id)
-                    .cloned()
+                    .map(Arc::clone)
                     .ok_or(InterpreterError::UndefinedFunction(*func_id))?;
 
-                // Evaluate arguments
-                let call_args: Vec<Value> = args.iter().map(|a| self.resolve(a)).collect();
-
-                // Save context
-                let old_context = std::mem::replace(
-                    &mut self.context,
-                    Context {
-                        args: call_args,
-                        ..Default::default()
-                    },
-                );
-                self.context_stack.push(old_context);
+                if !resuming {
+                    // Evaluate arguments
+                    let call_args: Vec<Value> = args.iter().map(|a| self.resolve(a)).collect();
+
+                    // Save context
+                    let old_context = std::mem::replace(
+                        &mut self.context,
+                        Context {
+                            args: call_args,
+                            func_id: Some(*func_id),
+                            ..Default::default()
+                        },
+                    );
+                    self.context_stack.push(old_context);
+                    self.count_call(*func_id);
+                }
 
                 // Execute function body
-                self.execute_block(&func.body)?;
+                let outcome = self.execute_block(&func.body, &func.labels, &func.lines);
 
-                // Get return value
-                let return_val = self.context.return_value.clone();
+                // A suspended call keeps its context until it is resumed
+                if let Err(error @ InterpreterError::Yielded { .. }) = outcome {
+                    return Err(error);
+                }
 
-                // Restore context
-                self.context = self.context_stack.pop().unwrap();
+                // Restore context, also when unwinding to a handler of the caller
+                let caller = self.context_stack.pop().unwrap();
+                let callee = std::mem::replace(&mut self.context, caller);
+                outcome?;
+
+                // Get return value
+                let return_val = callee.return_value;
 
                 // Store result
                 self.assign(result, return_val);
@@ -363,36 +1096,45 @@ impl Interpreter {
                 return Ok((false, None));
             }
 
+            Instruction::Try { err, label } => {
+                self.context.handlers.push((err.clone(), *label));
+            }
+
+            Instruction::EndTry => {
+                self.context.handlers.pop();
+            }
+
+            Instruction::Raise { value } => {
+                // The line is filled in by `execute_block`
+                return Err(InterpreterError::Raised {
+                    value: self.resolve(value),
+                    line: 0,
+                });
+            }
+
             Instruction::ArrayCreate { var, size } => {
-                let size = self.resolve(size).to_int() as usize;
-                let arr = vec![Value::Integer(0); size];
-                self.assign(var, Value::Array(arr));
+                let size = self.resolve(size).to_int();
+                if size > MAX_ARRAY_SIZE as i64 {
+                    return Err(InterpreterError::ArrayTooLarge(size));
+                }
+                self.account(0, size.max(0) as u64, self.line)?;
+                let arr = vec![Value::Integer(0); size.max(0) as usize];
+                self.assign(var, Value::from(arr));
             }
 
-            Instruction::ArrayRead { result, arr, idx } => {
+            Instruction::ArrayRead { result, arr, idx, indices } => {
                 let array = self.resolve(arr);
-                let index = self.resolve(idx).to_int();
-
-                let val = match array {
-                    Value::Array(ref a) => {
-                        if index >= 0 && (index as usize) < a.len() {
-                            a[index as usize].clone()
-                        } else {
-                            Value::Integer(0)
-                        }
-                    }
-                    _ => Value::Integer(0),
-                };
+                let path = self.index_path(idx, indices);
+                let val = array.element(&path);
                 self.assign(result, val);
             }
 
-            Instruction::ArrayWrite { arr, idx, value } => {
-                let index = self.resolve(idx).to_int();
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
+                let path = self.index_path(idx, indices);
                 let val = self.resolve(value);
 
                 // Get the variable reference
-                let prefix = arr.chars().next().unwrap_or('v');
-                let var_idx: i64 = arr[1..].parse().unwrap_or(0);
+                let (prefix, var_idx) = Lexer::split_variable(arr);
 
                 let array = match prefix {
                     'v' => self.context.local_vars.get_mut(&var_idx),
@@ -400,33 +1142,60 @@ impl Interpreter {
                     _ => None,
                 };
 
-                if let Some(Value::Array(ref mut a)) = array {
-                    if index >= 0 && (index as usize) < a.len() {
-                        a[index as usize] = val;
+                if let Some(array) = array {
+                    // Copies the elements only if the array is shared
+                    array.set_element(&path, val);
+                    self.record_write(arr);
+                }
+            }
+
+            Instruction::Iter { iter, source } => {
+                let val = self.resolve(source).iterator();
+                self.assign(iter, val);
+            }
+
+            Instruction::Next { done, var, iter } => {
+                let mut state = self.resolve(iter);
+                match state.advance() {
+                    Some(item) => {
+                        self.assign(iter, state);
+                        self.assign(var, item);
+                        self.assign(done, Value::Integer(0));
                     }
+                    None => self.assign(done, Value::Integer(1)),
                 }
             }
 
             Instruction::Output { value } => {
                 let val = self.resolve(value);
                 let output = val.to_string();
-                self.output.push(output.clone());
-                println!("{}", output);
+                if let Some(trace) = &mut self.trace {
+                    trace.events.push(TraceEvent::Output(output.clone()));
+                }
+                if let Some(logger) = &mut self.run_logger {
+                    logger.output.push(output.clone());
+                }
+                self.write_output(output);
             }
 
             Instruction::Input { var } => {
-                print!("> ");
-                io::stdout().flush()?;
-
-                let stdin = io::stdin();
-                let line = stdin.lock().lines().next().unwrap_or(Ok(String::new()))?;
+                let line = match &mut self.input {
+                    Some(queue) => queue.pop_front().unwrap_or_default(),
+                    None => {
+                        if self.output_mode.prints() {
+                            print!("> ");
+                            io::stdout().flush()?;
+                        }
+                        io::stdin().lock().lines().next().unwrap_or(Ok(String::new()))?
+                    }
+                };
 
                 let val = if let Ok(n) = line.trim().parse::<i64>() {
                     Value::Integer(n)
                 } else if let Ok(f) = line.trim().parse::<f64>() {
                     Value::Float(f)
                 } else {
-                    Value::String(line.trim().to_string())
+                    Value::from(line.trim())
                 };
 
                 self.assign(var, val);
@@ -435,49 +1204,334 @@ impl Interpreter {
             Instruction::RustFFI { result, func, args } => {
                 let func_name = self.resolve(func).to_string();
                 let resolved_args: Vec<Value> = args.iter().map(|a| self.resolve(a)).collect();
-                let val = self.call_builtin(&func_name, &resolved_args);
-                self.assign(result, val);
+                let arg = |i: usize| resolved_args.get(i).cloned().unwrap_or(Value::Integer(0));
+                if let Some(builtin) = self.host_builtins.get(&func_name).cloned() {
+                    lock(&self.io).begin_call(&func_name, self.line);
+                    let outcome = builtin(&resolved_args);
+                    let printed = lock(&self.io).end_call();
+                    if self.output_mode.captures() {
+                        self.output.extend(printed);
+                    }
+                    let val = outcome.map_err(|message| InterpreterError::Runtime { line: 0, message })?;
+                    self.assign(result, val);
+                    return Ok((true, None));
+                }
+                let val = match func_name.rsplit('.').next() {
+                    Some("yield") if self.cooperative => {
+                        self.assign(result, Value::Integer(0));
+                        return Err(InterpreterError::Yielded { line: 0 });
+                    }
+                    // Follows the division policy like `/`
+                    Some("floordiv") => self.divide(&arg(0), &arg(1), Value::floor_div)?,
+                    Some("ipow") => {
+                        let (base, exp) = (arg(0), arg(1));
+                        match base.pow(&exp) {
+                            // Only a result too large for an integer becomes a float
+                            Value::Float(_) if base.is_integer() && exp.to_int() >= 0 && exp.is_integer() => {
+                                let bits = base.integer_bits().saturating_sub(1).saturating_mul(exp.to_int() as u64);
+                                return Err(InterpreterError::IntegerTooLarge(bits + 1));
+                            }
+                            power => power,
+                        }
+                    }
+                    // There is no integer to round infinity or NaN to
+                    Some("floor" | "ceil" | "round")
+                        if resolved_args.len() == 1 && !arg(0).is_integer() && !arg(0).to_float().is_finite() =>
+                    {
+                        return Err(InterpreterError::Runtime {
+                            line: 0,
+                            message: format!("{}: cannot convert {} to an integer", func_name, Value::Float(arg(0).to_float())),
+                        });
+                    }
+                    // Digits past the integer limit would otherwise read as 0
+                    Some("int") if matches!(&arg(0), Value::String(s) if BigInt::is_digits(s) && BigInt::parse(s).is_none()) => {
+                        let digits = arg(0).to_string().trim_start_matches(['-', '+', '0']).len();
+                        return Err(InterpreterError::IntegerTooLarge((digits as f64 * std::f64::consts::LOG2_10) as u64));
+                    }
+                    // Bitwise operations work on 64 bits, and would give a
+                    // wrong answer for anything wider
+                    Some("band" | "bor" | "bxor" | "bnot" | "shl" | "shr")
+                        if resolved_args.iter().any(|a| matches!(a, Value::BigInt(_))) =>
+                    {
+                        return Err(InterpreterError::Runtime {
+                            line: 0,
+                            message: format!("{} needs integers that fit in 64 bits", func_name),
+                        });
+                    }
+                    _ if matches!(func_name.as_str(), "format" | "fmt") => {
+                        let template = arg(0).to_string();
+                        let text = format_template(&template, resolved_args.get(1..).unwrap_or_default())
+                            .map_err(|message| InterpreterError::Runtime { line: 0, message: format!("{}: {}", func_name, message) })?;
+                        Value::from(text)
+                    }
+                    Some("send" | "recv") if !self.channels_allowed => {
+                        return Err(InterpreterError::PermissionDenied {
+                            builtin: func_name,
+                            capability: "channels",
+                            line: 0,
+                        });
+                    }
+                    Some("send") => {
+                        self.channel_send(arg(0).to_int(), arg(1))?;
+                        Value::Integer(1)
+                    }
+                    Some("recv") => self.channel_recv(arg(0).to_int())?,
+                    Some("sleep_ms") => {
+                        let ms = arg(0).to_float();
+                        // NaN sleeps as long as a negative time: not at all
+                        let duration = Duration::try_from_secs_f64(ms.max(0.0) / 1000.0).map_err(|_| InterpreterError::Runtime {
+                            line: 0,
+                            message: format!("{}: cannot sleep for {} ms", func_name, Value::Float(ms)),
+                        })?;
+                        match self.clock {
+                            Clock::System => std::thread::sleep(duration),
+                            Clock::Virtual { .. } => self.slept = self.slept.saturating_add(duration),
+                        }
+                        Value::Integer(0)
+                    }
+                    _ => match self
+                        .system_builtin(&func_name, &resolved_args)
+                        .or_else(|| self.regex_builtin(&func_name, &resolved_args))
+                        .or_else(|| self.data_builtin(&func_name, &resolved_args))
+                    {
+                        Some(result) => result?,
+                        None => self.call_builtin(&func_name, &resolved_args),
+                    },
+                };
+                self.assign(result, check_integer(val)?);
             }
         }
 
         Ok((true, None))
     }
 
-    /// Call a built-in function (Rust FFI)
-    fn call_builtin(&self, func: &str, args: &[Value]) -> Value {
-        // Extract the function name from module.func format
-        let func_name = func.rsplit('.').next().unwrap_or(func);
+    /// `chan.send`: queue `value` on channel `id`
+    ///
+    /// Nothing but this program can empty its own channels, so a full one
+    /// fails at once. A full shared channel is waited on, with the step hook
+    /// called in between so timeouts and cancellation still apply.
+    fn channel_send(&mut self, id: i64, mut value: Value) -> Result<(), InterpreterError> {
+        let channels = self.channels.get_or_insert_with(Channels::default).clone();
+        if !self.channels_shared {
+            return channels.try_send(id, value).map_err(|_| InterpreterError::Runtime {
+                line: 0,
+                message: format!("chan.send: channel {} is full and nothing else can receive from it", id),
+            });
+        }
+        loop {
+            match channels.send_timeout(id, value, CHANNEL_WAIT) {
+                Ok(()) => return Ok(()),
+                Err(unsent) => value = unsent,
+            }
+            self.call_hook_waiting()?;
+        }
+    }
 
-        match func_name {
-            // Math functions
-            "sqrt" => {
-                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                Value::Float(x.sqrt())
+    /// `chan.recv`: take the oldest value from channel `id`, failing or
+    /// waiting like [`channel_send`](Self::channel_send) when it is empty
+    fn channel_recv(&mut self, id: i64) -> Result<Value, InterpreterError> {
+        let channels = self.channels.get_or_insert_with(Channels::default).clone();
+        if !self.channels_shared {
+            return channels.try_recv(id).ok_or_else(|| InterpreterError::Runtime {
+                line: 0,
+                message: format!("chan.recv: channel {} is empty and nothing else can send to it", id),
+            });
+        }
+        loop {
+            if let Some(value) = channels.recv_timeout(id, CHANNEL_WAIT) {
+                return Ok(value);
             }
-            "pow" => {
-                let base = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                let exp = args.get(1).map(|v| v.to_float()).unwrap_or(0.0);
-                Value::Float(base.powf(exp))
+            self.call_hook_waiting()?;
+        }
+    }
+
+    /// Call the step hook while the current instruction waits, stopping the
+    /// run if it says so
+    fn call_hook_waiting(&mut self) -> Result<(), InterpreterError> {
+        if let Some((_, hook)) = &mut self.step_hook {
+            let ctx = HookCtx { line: self.line, steps: self.steps, elapsed: self.started.elapsed() };
+            if hook(&ctx).is_break() {
+                return Err(InterpreterError::Interrupted { line: self.line });
             }
-            "sin" => {
-                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                Value::Float(x.sin())
+        }
+        Ok(())
+    }
+
+    /// Call `env.get`, `env.set` or `proc.run`, which reach outside the
+    /// interpreter and need a capability; `None` for other builtins
+    fn system_builtin(&self, func: &str, args: &[Value]) -> Option<Result<Value, InterpreterError>> {
+        let (allowed, capability) = match func {
+            "env.get" | "env.set" => (self.env_allowed, "--allow-env"),
+            "proc.run" => (self.process_allowed, "--allow-process"),
+            _ => return None,
+        };
+        if !allowed {
+            return Some(Err(InterpreterError::PermissionDenied { builtin: func.to_string(), capability, line: 0 }));
+        }
+        let arg = |i: usize| args.get(i).map(|v| v.to_string()).unwrap_or_default();
+        Some(Ok(match func {
+            // Unset or invalid variables read as 0
+            "env.get" => std::env::var(arg(0)).map(Value::from).unwrap_or(Value::Integer(0)),
+            "env.set" => {
+                let (name, value) = (arg(0), arg(1));
+                if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+                    return Some(Err(InterpreterError::Runtime {
+                        line: 0,
+                        message: format!("invalid environment variable '{}'", name),
+                    }));
+                }
+                std::env::set_var(name, value);
+                Value::Integer(1)
             }
-            "cos" => {
-                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                Value::Float(x.cos())
+            _ => {
+                let output = match args.first() {
+                    // An array is the program and its arguments
+                    Some(Value::Array(argv)) if !argv.is_empty() => {
+                        std::process::Command::new(argv[0].to_string())
+                            .args(argv[1..].iter().map(|a| a.to_string()))
+                            .output()
+                    }
+                    // Anything else is a shell command line
+                    _ if cfg!(windows) => std::process::Command::new("cmd").args(["/C", &arg(0)]).output(),
+                    _ => std::process::Command::new("sh").args(["-c", &arg(0)]).output(),
+                };
+                match output {
+                    Ok(output) => Value::from(vec![
+                        Value::Integer(output.status.code().unwrap_or(-1) as i64),
+                        Value::from(String::from_utf8_lossy(&output.stdout).into_owned()),
+                    ]),
+                    Err(e) => {
+                        return Some(Err(InterpreterError::Runtime {
+                            line: 0,
+                            message: format!("proc.run failed: {}", e),
+                        }))
+                    }
+                }
             }
-            "tan" => {
+        }))
+    }
+
+    /// Call `re.match`, `re.find`, `re.findall` or `re.replace`; `None` for
+    /// other builtins
+    ///
+    /// The pattern comes first, then the string and, for `re.replace`, the
+    /// literal replacement.
+    fn regex_builtin(&mut self, func: &str, args: &[Value]) -> Option<Result<Value, InterpreterError>> {
+        if !matches!(func, "re.match" | "re.find" | "re.findall" | "re.replace") {
+            return None;
+        }
+        let arg = |i: usize| args.get(i).map(|v| v.to_string()).unwrap_or_default();
+        let pattern = arg(0);
+        if !self.regexes.contains_key(&pattern) {
+            let regex = match Regex::new(&pattern) {
+                Ok(regex) => regex,
+                Err(e) => {
+                    return Some(Err(InterpreterError::Runtime {
+                        line: 0,
+                        message: format!("invalid regex '{}': {}", pattern, e),
+                    }))
+                }
+            };
+            // Programs building patterns in a loop should not grow the cache
+            if self.regexes.len() >= 64 {
+                self.regexes.clear();
+            }
+            self.regexes.insert(pattern.clone(), regex);
+        }
+        let regex = &self.regexes[&pattern];
+        let text = arg(1);
+        Some(Ok(match func {
+            // Like Python's re.match: only at the start
+            "re.match" => Value::Integer(regex.find(&text).is_some_and(|m| m.start() == 0) as i64),
+            // First match, or 0 without one
+            "re.find" => regex.find(&text).map_or(Value::Integer(0), |m| Value::from(m.as_str())),
+            "re.findall" => Value::from(regex.find_iter(&text).map(|m| Value::from(m.as_str())).collect::<Vec<_>>()),
+            _ => Value::from(regex.replace_all(&text, NoExpand(&arg(2))).into_owned()),
+        }))
+    }
+
+    /// Call `struct.new`, `struct.get`, `struct.set`, `json.encode` or
+    /// `json.decode`; `None` for other builtins
+    fn data_builtin(&self, func: &str, args: &[Value]) -> Option<Result<Value, InterpreterError>> {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or_default();
+        Some(Ok(match func {
+            // Names alternate with values
+            "struct.new" => Value::new_struct(
+                args.chunks(2).map(|pair| (Arc::from(pair[0].to_string()), pair.get(1).cloned().unwrap_or_default())),
+            ),
+            // A missing field reads 0, like an index out of bounds
+            "struct.get" => arg(0).field(&arg(1).to_string()).cloned().unwrap_or_default(),
+            "struct.set" => {
+                let mut record = arg(0);
+                if !record.set_field(&arg(1).to_string(), arg(2)) {
+                    return Some(Err(InterpreterError::Runtime {
+                        line: 0,
+                        message: format!("struct.set needs a struct, not {}", record.type_name()),
+                    }));
+                }
+                record
+            }
+            "json.encode" => Value::from(json::encode(&arg(0))),
+            "json.decode" => match json::decode(&arg(0).to_string()) {
+                Ok(value) => value,
+                Err(message) => return Some(Err(InterpreterError::Runtime { line: 0, message })),
+            },
+            _ => return None,
+        }))
+    }
+
+    /// Resolve the indices of an array access, outermost first
+    fn index_path(&self, idx: &str, indices: &[String]) -> Vec<i64> {
+        std::iter::once(idx)
+            .chain(indices.iter().map(String::as_str))
+            .map(|i| self.resolve(i).to_int())
+            .collect()
+    }
+
+    /// Call a built-in function (Rust FFI)
+    fn call_builtin(&self, func: &str, args: &[Value]) -> Value {
+        // Extract the function name from module.func format
+        let func_name = func.rsplit('.').next().unwrap_or(func);
+
+        // Ends like `format`
+        if func == "date.format" {
+            let timestamp = args.first().map(|v| v.to_float()).unwrap_or(0.0);
+            let pattern = args.get(1).map_or("%Y-%m-%d %H:%M:%S".to_string(), |v| v.to_string());
+            return Value::from(format_date(timestamp, &pattern));
+        }
+
+        match func_name {
+            // Math functions
+            "sqrt" => {
+                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
+                Value::Float(x.sqrt())
+            }
+            "pow" => {
+                let base = args.first().map(|v| v.to_float()).unwrap_or(0.0);
+                let exp = args.get(1).map(|v| v.to_float()).unwrap_or(0.0);
+                Value::Float(base.powf(exp))
+            }
+            "sin" => {
+                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
+                Value::Float(x.sin())
+            }
+            "cos" => {
+                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
+                Value::Float(x.cos())
+            }
+            "tan" => {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
                 Value::Float(x.tan())
             }
+            // Integers are already whole, and may not fit in a float
+            "floor" | "ceil" | "round" if args.len() == 1 && args[0].is_integer() => args[0].clone(),
             "floor" => {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                Value::Integer(x.floor() as i64)
+                Value::from_whole(x.floor())
             }
             "ceil" => {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                Value::Integer(x.ceil() as i64)
+                Value::from_whole(x.ceil())
             }
             "round" => {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
@@ -486,16 +1540,19 @@ impl Interpreter {
                     let factor = 10_f64.powi(decimals);
                     Value::Float((x * factor).round() / factor)
                 } else {
-                    Value::Integer(x.round() as i64)
+                    Value::from_whole(x.round())
                 }
             }
             "abs" => {
-                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
-                if x.fract() == 0.0 {
-                    Value::Integer(x.abs() as i64)
-                } else {
-                    Value::Float(x.abs())
+                match args.first() {
+                    Some(Value::Integer(n)) => {
+                        return n.checked_abs().map_or_else(|| Value::from(BigInt::from(*n).abs()), Value::Integer)
+                    }
+                    Some(Value::BigInt(n)) => return Value::from(n.abs()),
+                    _ => {}
                 }
+                let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
+                Value::from_whole(x.abs())
             }
             "log" => {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
@@ -515,6 +1572,9 @@ impl Interpreter {
                 if args.is_empty() {
                     return Value::Integer(0);
                 }
+                if args.iter().all(Value::is_integer) {
+                    return args.iter().max_by(|a, b| sort_order(a, b)).cloned().unwrap_or_default();
+                }
                 let mut max_val = args[0].to_float();
                 for arg in &args[1..] {
                     let v = arg.to_float();
@@ -522,16 +1582,15 @@ impl Interpreter {
                         max_val = v;
                     }
                 }
-                if max_val.fract() == 0.0 {
-                    Value::Integer(max_val as i64)
-                } else {
-                    Value::Float(max_val)
-                }
+                Value::from_whole(max_val)
             }
             "min" => {
                 if args.is_empty() {
                     return Value::Integer(0);
                 }
+                if args.iter().all(Value::is_integer) {
+                    return args.iter().min_by(|a, b| sort_order(a, b)).cloned().unwrap_or_default();
+                }
                 let mut min_val = args[0].to_float();
                 for arg in &args[1..] {
                     let v = arg.to_float();
@@ -539,19 +1598,16 @@ impl Interpreter {
                         min_val = v;
                     }
                 }
-                if min_val.fract() == 0.0 {
-                    Value::Integer(min_val as i64)
-                } else {
-                    Value::Float(min_val)
-                }
+                Value::from_whole(min_val)
             }
 
             // String/length functions
             "len" => {
                 if let Some(arg) = args.first() {
                     match arg {
-                        Value::String(s) => Value::Integer(s.len() as i64),
+                        Value::String(s) => Value::Integer(s.chars().count() as i64),
                         Value::Array(a) => Value::Integer(a.len() as i64),
+                        Value::Struct(fields) => Value::Integer(fields.len() as i64),
                         _ => Value::Integer(0),
                     }
                 } else {
@@ -559,33 +1615,217 @@ impl Interpreter {
                 }
             }
 
-            // Type conversion
-            "int" => {
-                let x = args.first().map(|v| v.to_int()).unwrap_or(0);
-                Value::Integer(x)
+            // String functions, following Python's str methods
+            "upper" | "lower" | "strip" => {
+                let text = args.first().map(|v| v.to_string()).unwrap_or_default();
+                Value::from(match func_name {
+                    "upper" => text.to_uppercase(),
+                    "lower" => text.to_lowercase(),
+                    _ => text.trim().to_string(),
+                })
+            }
+            "split" => {
+                let text = args.first().map(|v| v.to_string()).unwrap_or_default();
+                let sep = args.get(1).map(|v| v.to_string()).unwrap_or_default();
+                // No (or an empty) separator splits on runs of whitespace
+                let parts: Vec<Value> = if sep.is_empty() {
+                    text.split_whitespace().map(Value::from).collect()
+                } else {
+                    text.split(sep.as_str()).map(Value::from).collect()
+                };
+                Value::from(parts)
+            }
+            "join" => {
+                let sep = args.get(1).map(|v| v.to_string()).unwrap_or_default();
+                let parts: Vec<String> = match args.first() {
+                    Some(Value::Array(a)) => a.iter().map(|v| v.to_string()).collect(),
+                    Some(other) => other.to_string().chars().map(String::from).collect(),
+                    None => Vec::new(),
+                };
+                Value::from(parts.join(&sep))
+            }
+            "replace" => {
+                let arg = |i: usize| args.get(i).map(|v| v.to_string()).unwrap_or_default();
+                Value::from(arg(0).replace(&arg(1), &arg(2)))
+            }
+            "find" => {
+                let text = args.first().map(|v| v.to_string()).unwrap_or_default();
+                let needle = args.get(1).map(|v| v.to_string()).unwrap_or_default();
+                // Character index, or -1 when absent
+                Value::Integer(text.find(&needle).map_or(-1, |i| text[..i].chars().count() as i64))
+            }
+            "contains" => {
+                let item = args.get(1).cloned().unwrap_or(Value::Integer(0));
+                let found = match args.first() {
+                    Some(Value::Array(a)) => a.iter().any(|v| v.eq_val(&item).is_truthy()),
+                    Some(Value::String(s)) => s.contains(&*item.to_string()),
+                    _ => false,
+                };
+                Value::Integer(found as i64)
+            }
+            "startswith" | "endswith" => {
+                let text = args.first().map(|v| v.to_string()).unwrap_or_default();
+                let affix = args.get(1).map(|v| v.to_string()).unwrap_or_default();
+                let found = if func_name == "startswith" {
+                    text.starts_with(&affix)
+                } else {
+                    text.ends_with(&affix)
+                };
+                Value::Integer(found as i64)
+            }
+            // Array construction
+            "array2d" => {
+                let rows = args.first().map(|v| v.to_int()).unwrap_or(0);
+                let cols = args.get(1).map(|v| v.to_int()).unwrap_or(0);
+                Value::array2d(rows, cols).unwrap_or_else(|| {
+                    eprintln!("Warning: array2d({}, {}) is too large", rows, cols);
+                    Value::Integer(0)
+                })
+            }
+
+            // Bulk array functions; arrays are values, so these return new arrays
+            "slice" => {
+                let len = match args.first() {
+                    Some(Value::Array(a)) => a.len(),
+                    Some(Value::String(s)) => s.chars().count(),
+                    _ => return Value::from(Vec::new()),
+                };
+                let bound = |i: usize, default: usize| {
+                    args.get(i).map_or(default, |v| v.to_int().clamp(0, len as i64) as usize)
+                };
+                let start = bound(1, 0);
+                let end = bound(2, len).max(start);
+                match &args[0] {
+                    Value::Array(a) => Value::from(a[start..end].to_vec()),
+                    s => {
+                        let text = s.to_string();
+                        Value::from(text.chars().skip(start).take(end - start).collect::<String>())
+                    }
+                }
+            }
+            "concat" => match (args.first(), args.get(1)) {
+                (Some(a @ Value::String(_)), Some(b @ Value::String(_))) => a.add(b),
+                _ => {
+                    let mut joined = Vec::new();
+                    for arg in args.iter().take(2) {
+                        match arg {
+                            Value::Array(a) => joined.extend(a.iter().cloned()),
+                            other => joined.push(other.clone()),
+                        }
+                    }
+                    Value::from(joined)
+                }
+            },
+            "fill" => {
+                let len = args.first().map_or(0, |v| match v {
+                    Value::Array(a) => a.len(),
+                    _ => 0,
+                });
+                let value = args.get(1).cloned().unwrap_or(Value::Integer(0));
+                Value::from(vec![value; len])
             }
+            "copy" => match (args.first(), args.get(1)) {
+                (Some(Value::Array(dst)), Some(Value::Array(src))) => {
+                    let mut copied = dst.to_vec();
+                    let n = src.len().min(copied.len());
+                    copied[..n].clone_from_slice(&src[..n]);
+                    Value::from(copied)
+                }
+                (Some(dst), _) => dst.clone(),
+                _ => Value::from(Vec::new()),
+            },
+            "sum" => match args.first() {
+                // Integer until a float (or a non-number, as a float) is added
+                Some(Value::Array(a)) => a.iter().fold(Value::Integer(0), |total, v| total.add(v)),
+                _ => Value::Integer(0),
+            },
+            "sort" => match args.first() {
+                Some(Value::Array(a)) => {
+                    let mut sorted = a.to_vec();
+                    sorted.sort_by(sort_order);
+                    Value::from(sorted)
+                }
+                Some(other) => other.clone(),
+                None => Value::Integer(0),
+            },
+            "reverse" => match args.first() {
+                Some(Value::Array(a)) => Value::from(a.iter().rev().cloned().collect::<Vec<_>>()),
+                Some(Value::String(s)) => Value::from(s.chars().rev().collect::<String>()),
+                Some(other) => other.clone(),
+                None => Value::Integer(0),
+            },
+
+            // Bitwise functions on i64; shift counts use their low 6 bits
+            "band" | "bor" | "bxor" | "shl" | "shr" => {
+                let a = args.first().map(|v| v.to_int()).unwrap_or(0);
+                let b = args.get(1).map(|v| v.to_int()).unwrap_or(0);
+                Value::Integer(match func_name {
+                    "band" => a & b,
+                    "bor" => a | b,
+                    "bxor" => a ^ b,
+                    "shl" => a.wrapping_shl(b as u32),
+                    _ => a.wrapping_shr(b as u32),
+                })
+            }
+            "bnot" => Value::Integer(!args.first().map(|v| v.to_int()).unwrap_or(0)),
+
+            // Type conversion
+            "int" => match args.first() {
+                // Strings of any length of digits parse, like Python's int()
+                Some(Value::String(s)) => BigInt::parse(s).map_or(Value::Integer(0), Value::from),
+                Some(n @ Value::BigInt(_)) => n.clone(),
+                other => Value::Integer(other.map_or(0, Value::to_int)),
+            },
             "float" => {
                 let x = args.first().map(|v| v.to_float()).unwrap_or(0.0);
                 Value::Float(x)
             }
             "str" => {
                 let s = args.first().map(|v| v.to_string()).unwrap_or_default();
-                Value::String(s)
+                Value::from(s)
             }
 
             // Random (simple pseudo-random)
             "randint" => {
                 let min = args.first().map(|v| v.to_int()).unwrap_or(0);
                 let max = args.get(1).map(|v| v.to_int()).unwrap_or(100);
-                // Simple pseudo-random using time
-                let seed = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map(|d| d.as_nanos() as i64)
-                    .unwrap_or(0);
-                let range = (max - min + 1).max(1);
-                Value::Integer(min + (seed.abs() % range))
+                // Simple pseudo-random using time; a virtual clock gives
+                // the same sequence on every run (splitmix64)
+                let seed = match self.clock {
+                    Clock::System => std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_nanos() as i64)
+                        .unwrap_or(0),
+                    Clock::Virtual { .. } => {
+                        let mut z = self.rng.get().wrapping_add(0x9e3779b97f4a7c15);
+                        self.rng.set(z);
+                        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                        (z ^ (z >> 31)) as i64
+                    }
+                };
+                let range = (max as i128 - min as i128 + 1).max(1);
+                Value::Integer((min as i128 + (seed as i128).rem_euclid(range)) as i64)
             }
 
+            // Time in seconds: since 1970, and since the run started
+            "now" => match self.clock {
+                Clock::System => Value::Float(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs_f64())
+                        .unwrap_or(0.0),
+                ),
+                Clock::Virtual { epoch } => Value::Float(epoch + self.slept.as_secs_f64()),
+            },
+            "monotonic" => match self.clock {
+                Clock::System => Value::Float(self.started.elapsed().as_secs_f64()),
+                Clock::Virtual { .. } => Value::Float(self.slept.as_secs_f64()),
+            },
+
+            // Outside a scheduler there is nothing to switch to
+            "yield" => Value::Integer(0),
+
             // Unknown function
             _ => {
                 eprintln!("Warning: Unknown builtin function '{}'", func);
@@ -594,23 +1834,69 @@ impl Interpreter {
         }
     }
 
-    /// Execute a block of instructions
-    fn execute_block(&mut self, instructions: &[Instruction]) -> Result<(), InterpreterError> {
-        // Collect label positions
-        let mut labels: HashMap<i64, usize> = HashMap::new();
-        for (i, instr) in instructions.iter().enumerate() {
-            if let Instruction::Label { id } = instr {
-                labels.insert(*id, i);
-            }
-        }
-
-        let mut i = 0;
+    /// Execute a block of instructions; `lines` gives their source lines
+    fn execute_block(
+        &mut self,
+        instructions: &[Instruction],
+        labels: &HashMap<i64, usize>,
+        lines: &[usize],
+    ) -> Result<(), InterpreterError> {
+        let mut i = self.resume_at.pop().unwrap_or(0);
         while i < instructions.len() {
             if self.context.returned {
                 break;
             }
 
-            let (cont, jump_label) = self.execute_instruction(&instructions[i])?;
+            let line = lines.get(i).copied().unwrap_or(0);
+            // While resuming, calls on the way to the suspended block are
+            // re-entered without counting as steps
+            if self.resume_at.is_empty() {
+                if self.stop_line == Some(line) {
+                    self.stopped = Some(self.snapshot(Some(line)));
+                    return Err(InterpreterError::Interrupted { line });
+                }
+                if self.cooperative && self.time_slice.is_some_and(|slice| self.slice_steps >= slice) {
+                    self.resume_at.push(i);
+                    return Err(InterpreterError::Yielded { line });
+                }
+                self.slice_steps += 1;
+
+                self.steps += 1;
+                if let Some(limit) = self.max_steps {
+                    if self.steps > limit {
+                        return Err(InterpreterError::StepLimitExceeded(limit));
+                    }
+                }
+                self.account(1, 0, line)?;
+
+                if let Some((every, hook)) = &mut self.step_hook {
+                    if self.steps % *every == 0 {
+                        let ctx = HookCtx { line, steps: self.steps, elapsed: self.started.elapsed() };
+                        if hook(&ctx).is_break() {
+                            return Err(InterpreterError::Interrupted { line });
+                        }
+                    }
+                }
+                if self.trace.is_some() {
+                    self.record_step(&instructions[i], line);
+                }
+            }
+            self.line = line;
+            let outcome = if self.strict {
+                self.check_defined(&instructions[i], line)
+            } else {
+                Ok(())
+            };
+            let (cont, jump_label) = match outcome.and_then(|()| self.execute_instruction(&instructions[i])) {
+                Ok(step) => step,
+                Err(InterpreterError::Yielded { line: at }) => {
+                    // The block that yielded continues after `yield`; the
+                    // blocks around it re-enter their calls
+                    self.resume_at.push(if self.resume_at.is_empty() { i + 1 } else { i });
+                    return Err(InterpreterError::Yielded { line: if at == 0 { line } else { at } });
+                }
+                Err(error) => (true, Some(self.catch(error, line)?)),
+            };
 
             if !cont {
                 break;
@@ -630,6 +1916,44 @@ impl Interpreter {
         Ok(())
     }
 
+    /// Apply `op` (`/` or `%`) under the division policy
+    fn divide(
+        &self,
+        a: &Value,
+        b: &Value,
+        op: fn(&Value, &Value) -> Value,
+    ) -> Result<Value, InterpreterError> {
+        if b.to_float() != 0.0 {
+            return Ok(op(a, b));
+        }
+        match self.division_policy {
+            DivisionPolicy::SilentNaN => Ok(op(a, b)),
+            DivisionPolicy::Error => Err(InterpreterError::DivisionByZero { line: 0 }),
+            DivisionPolicy::SaturateZero => Ok(Value::Integer(0)),
+        }
+    }
+
+    /// Hand an error to the innermost try region of the current function
+    ///
+    /// Returns the handler label, or the error if there is no handler.
+    fn catch(&mut self, mut error: InterpreterError, line: usize) -> Result<i64, InterpreterError> {
+        match &mut error {
+            InterpreterError::Raised { line: at @ 0, .. }
+            | InterpreterError::DivisionByZero { line: at @ 0 }
+            | InterpreterError::Runtime { line: at @ 0, .. }
+            | InterpreterError::PermissionDenied { line: at @ 0, .. } => *at = line,
+            _ => {}
+        }
+        if !error.is_catchable() {
+            return Err(error);
+        }
+        let Some((err, label)) = self.context.handlers.pop() else {
+            return Err(error);
+        };
+        self.assign(&err, error.handler_value());
+        Ok(label)
+    }
+
     /// Run Sui code
     ///
     /// # Arguments
@@ -640,10 +1964,203 @@ impl Interpreter {
     /// Vector of output strings
     pub fn run(&mut self, code: &str, args: &[String]) -> Result<Vec<String>, InterpreterError> {
         self.reset();
+        self.run_incremental(code, args)
+    }
 
-        // Set command-line arguments
-        // g100 = argc (number of arguments)
-        // g101, g102, ... = argv[0], argv[1], ...
+    /// Run Sui code, keeping the functions and globals of earlier runs
+    ///
+    /// Locals and output start fresh, and `args` replace the arguments in
+    /// g100 onward. Functions defined by `code` replace earlier ones with the
+    /// same ID.
+    ///
+    /// ```rust
+    /// use sui_lang::Interpreter;
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.load_functions("# 0 1 {\n* v0 a0 2\n^ v0\n}").unwrap();
+    /// interp.run_incremental("$ g0 0 21", &[]).unwrap();
+    /// assert_eq!(interp.run_incremental(". g0", &[]).unwrap(), ["42"]);
+    /// ```
+    pub fn run_incremental(&mut self, code: &str, args: &[String]) -> Result<Vec<String>, InterpreterError> {
+        self.begin_run(code, args);
+        let result = self.execute_main(code);
+        self.log_run(code, None, args, &result);
+        result
+    }
+
+    /// Load `code` and run its main code
+    fn execute_main(&mut self, code: &str) -> Result<Vec<String>, InterpreterError> {
+        let (lines, instructions) = self.load_program(code)?;
+
+        // Execute main code (imports will be skipped as already processed)
+        self.execute_block(&instructions, &Function::label_table(&instructions), &lines)?;
+
+        Ok(self.output.clone())
+    }
+
+    /// Run `code` from a clean state, as [`run`](Self::run) does, until
+    /// `line` (1-based) is first about to run, and return the state there
+    ///
+    /// If the program finishes without running `line`, such as for a
+    /// comment or code it skips, the snapshot is of the end of the run,
+    /// with no `line`. Lines of functions imported with `_` are not told
+    /// apart from the program's own. Fails if the program does not parse or
+    /// stops with an error first.
+    ///
+    /// ```rust
+    /// use sui_lang::{Interpreter, Value};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// let state = interp.run_until_line("= v0 0\n: 0\n+ v0 v0 1\n< v1 v0 3\n? v1 0", 3, &[]).unwrap();
+    /// assert_eq!((state.line, state.steps), (Some(3), 2));
+    /// assert_eq!(state.locals, [(0, Value::Integer(0))]);
+    /// ```
+    pub fn run_until_line(&mut self, code: &str, line: usize, args: &[String]) -> Result<StateSnapshot, InterpreterError> {
+        self.reset();
+        self.begin_run(code, args);
+        self.stop_line = Some(line);
+        let result = self.execute_main(code);
+        self.stop_line = None;
+        match (result, self.stopped.take()) {
+            (Err(InterpreterError::Interrupted { .. }), Some(snapshot)) => Ok(snapshot),
+            (Ok(_), _) => Ok(self.snapshot(None)),
+            (Err(error), _) => Err(error),
+        }
+    }
+
+    /// State of the current run, stopped before `line`
+    fn snapshot(&self, line: Option<usize>) -> StateSnapshot {
+        let mut locals: Vec<(i64, Value)> = self.context.local_vars.iter().map(|(i, value)| (*i, value.clone())).collect();
+        locals.sort_by_key(|(i, _)| *i);
+        StateSnapshot {
+            line,
+            call_stack: self.context_stack.iter().chain([&self.context]).filter_map(|context| context.func_id).collect(),
+            globals: self.globals().into_iter().map(|(i, value)| (i, value.clone())).collect(),
+            locals,
+            args: self.context.args.clone(),
+            output: self.output.clone(),
+            steps: self.steps,
+        }
+    }
+
+    /// Record a finished run with the run logger, if there is one
+    fn log_run(&mut self, code: &str, file: Option<&Path>, args: &[String], result: &Result<Vec<String>, InterpreterError>) {
+        let Some(logger) = &mut self.run_logger else {
+            return;
+        };
+        let record = RunRecord {
+            code,
+            file,
+            args,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            steps: self.steps,
+            elapsed: self.started.elapsed(),
+        };
+        // Logging is best effort; the run's own result matters more
+        let _ = logger.log(&record);
+    }
+
+    /// Load `code` from a clean state, as for [`run`](Self::run), to be run
+    /// with [`run_slice`](Self::run_slice)
+    pub(crate) fn start(&mut self, code: &str, args: &[String]) -> Result<Program, InterpreterError> {
+        self.reset();
+        self.begin_run(code, args);
+        let (lines, instructions) = self.load_program(code)?;
+        let labels = Function::label_table(&instructions);
+        Ok(Program { instructions, labels, lines })
+    }
+
+    /// Run `program` until it finishes, yields or has executed `time_slice`
+    /// instructions; `Ok(true)` once it has finished
+    ///
+    /// A suspended program continues where it left off on the next call.
+    pub(crate) fn run_slice(&mut self, program: &Program, time_slice: Option<u64>) -> Result<bool, InterpreterError> {
+        self.cooperative = true;
+        self.time_slice = time_slice.map(|slice| slice.max(1));
+        self.slice_steps = 0;
+        let outcome = self.execute_block(&program.instructions, &program.labels, &program.lines);
+        self.cooperative = false;
+        match outcome {
+            Ok(()) => Ok(true),
+            Err(InterpreterError::Yielded { .. }) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Clear the state of the previous run and store `args`
+    fn begin_run(&mut self, code: &str, args: &[String]) {
+        self.context_stack.clear();
+        self.context = Context::default();
+        self.resume_at.clear();
+        self.slept = Duration::ZERO;
+        self.rng.set(0);
+        self.output.clear();
+        lock(&self.io).clear();
+        self.steps = 0;
+        self.usage.clear();
+        self.started = Instant::now();
+        self.trace = None;
+        if let Some(logger) = &mut self.run_logger {
+            logger.output.clear();
+        }
+        self.set_args(args);
+        self.begin_trace(code, args);
+    }
+
+    /// Register the functions and imports of `code` without running its
+    /// main code, for later [`run_incremental`](Self::run_incremental) calls
+    pub fn load_functions(&mut self, code: &str) -> Result<(), InterpreterError> {
+        self.load_program(code).map(|_| ())
+    }
+
+    /// Call function `id` with `args` like `$` does and return its result
+    ///
+    /// The function must have been registered by
+    /// [`load_functions`](Self::load_functions) or an earlier run, so a file
+    /// of functions can serve as a module with several entry points. Globals
+    /// keep their values between calls, and output is appended to
+    /// [`get_output`](Self::get_output). The step limit counts each call
+    /// separately.
+    ///
+    /// ```rust
+    /// use sui_lang::{Interpreter, Value};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.load_functions("# 0 2 {\n+ v0 a0 a1\n^ v0\n}\n# 1 1 {\n. a0\n}").unwrap();
+    /// let sum = interp.call_function(0, &[Value::Integer(40), Value::Integer(2)]).unwrap();
+    /// assert_eq!(sum, Value::Integer(42));
+    /// interp.call_function(1, &[Value::from("hi")]).unwrap();
+    /// assert_eq!(interp.get_output(), ["hi"]);
+    /// ```
+    pub fn call_function(&mut self, id: i64, args: &[Value]) -> Result<Value, InterpreterError> {
+        let func = self
+            .functions
+            .get(&id)
+            .map(Arc::clone)
+            .ok_or(InterpreterError::UndefinedFunction(id))?;
+        self.resume_at.clear();
+        self.steps = 0;
+        self.usage.clear();
+        self.started = Instant::now();
+
+        let caller = std::mem::replace(
+            &mut self.context,
+            Context {
+                args: args.to_vec(),
+                func_id: Some(id),
+                ..Default::default()
+            },
+        );
+        self.context_stack.push(caller);
+        self.count_call(id);
+        let outcome = self.execute_block(&func.body, &func.labels, &func.lines);
+        let caller = self.context_stack.pop().unwrap_or_default();
+        let callee = std::mem::replace(&mut self.context, caller);
+        outcome.map(|()| callee.return_value)
+    }
+
+    /// Store command-line arguments: g100 = argc, g101, g102, ... = argv
+    fn set_args(&mut self, args: &[String]) {
         self.global_vars.insert(100, Value::Integer(args.len() as i64));
         for (i, arg) in args.iter().enumerate() {
             let val = if let Ok(n) = arg.parse::<i64>() {
@@ -651,17 +2168,22 @@ impl Interpreter {
             } else if let Ok(f) = arg.parse::<f64>() {
                 Value::Float(f)
             } else {
-                Value::String(arg.clone())
+                Value::from(arg.as_str())
             };
             self.global_vars.insert(101 + i as i64, val);
         }
+    }
 
-        // Parse code
-        let (instructions, functions) = Parser::parse(code)?;
+    /// Parse `code`, store its functions and load its imports, returning
+    /// the main code with its line numbers
+    fn load_program(&mut self, code: &str) -> Result<(Vec<usize>, Vec<Instruction>), InterpreterError> {
+        let (numbered, functions) = Parser::parse_numbered(code, self.language_level)?;
+        let (lines, instructions): (Vec<usize>, Vec<Instruction>) = numbered.into_iter().unzip();
+        self.strings.intern_program(&instructions, &functions);
 
         // Store functions
         for func in functions {
-            self.functions.insert(func.id, func);
+            self.functions.insert(func.id, Arc::new(func));
         }
 
         // Process imports first (to load function definitions from other modules)
@@ -670,11 +2192,71 @@ impl Interpreter {
                 self.load_module(path)?;
             }
         }
+        Ok((lines, instructions))
+    }
 
-        // Execute main code (imports will be skipped as already processed)
-        self.execute_block(&instructions)?;
+    /// Parse the program at `path` and the modules it imports into a
+    /// [`CompiledProgram`], to be run later without parsing
+    ///
+    /// Leaves the interpreter reset.
+    pub fn compile_file(&mut self, path: &Path) -> Result<CompiledProgram, InterpreterError> {
+        let canonical = path.canonicalize()
+            .map_err(|_| InterpreterError::ModuleNotFound(path.display().to_string()))?;
+        let code = std::fs::read_to_string(&canonical)
+            .map_err(|_| InterpreterError::ModuleNotFound(path.display().to_string()))?;
 
-        Ok(self.output.clone())
+        self.reset();
+        self.current_file = Some(canonical.clone());
+        self.loaded_modules.insert(canonical);
+        let loaded = self.load_program(&code);
+        let functions = self.functions.values().cloned().collect();
+        self.reset();
+        let (lines, instructions) = loaded?;
+        Ok(CompiledProgram::new(code, lines.into_iter().zip(instructions).collect(), functions))
+    }
+
+    /// Run a program compiled by [`compile_file`](Self::compile_file)
+    ///
+    /// The run is the same as [`run_file`](Self::run_file) on the source,
+    /// as of when it was compiled.
+    ///
+    /// ```rust
+    /// use sui_lang::interpreter::CompiledProgram;
+    /// use sui_lang::Interpreter;
+    ///
+    /// let mut interp = Interpreter::new();
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("double.sui");
+    /// std::fs::write(&path, "# 0 1 {\n* v0 a0 2\n^ v0\n}\n$ v0 0 g101\n. v0").unwrap();
+    /// let program = interp.compile_file(&path).unwrap();
+    /// assert_eq!(interp.run_compiled(&program, &["21".to_string()]).unwrap(), ["42"]);
+    /// assert_eq!(interp.run_compiled(&program, &["5".to_string()]).unwrap(), ["10"]);
+    /// ```
+    pub fn run_compiled(&mut self, program: &CompiledProgram, args: &[String]) -> Result<Vec<String>, InterpreterError> {
+        self.reset();
+        self.begin_run(&program.source, args);
+        self.strings.intern_instructions(&program.instructions);
+        for func in &program.functions {
+            self.strings.intern_instructions(&func.body);
+            self.functions.insert(func.id, Arc::clone(func));
+        }
+
+        let labels = Function::label_table(&program.instructions);
+        let result = self
+            .execute_block(&program.instructions, &labels, &program.lines)
+            .map(|_| self.output.clone());
+        self.log_run(&program.source, None, args, &result);
+        result
+    }
+
+    /// Run a `.suic` file written by `sui compile` or
+    /// [`CompiledProgram::save`]
+    pub fn run_compiled_file(&mut self, path: &Path, args: &[String]) -> Result<Vec<String>, InterpreterError> {
+        let program = CompiledProgram::load(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => InterpreterError::ModuleNotFound(path.display().to_string()),
+            _ => InterpreterError::Io(e),
+        })?;
+        self.run_compiled(&program, args)
     }
 
     /// Run Sui code from a file
@@ -696,7 +2278,15 @@ impl Interpreter {
         self.context_stack.clear();
         self.context = Context::default();
         self.output.clear();
+        lock(&self.io).clear();
         self.loaded_modules.clear();
+        self.strings.clear();
+        self.steps = 0;
+        self.usage.clear();
+        self.started = Instant::now();
+        self.slept = Duration::ZERO;
+        self.rng.set(0);
+        self.trace = None;
 
         // Set current file for import resolution
         self.current_file = Some(canonical.clone());
@@ -705,40 +2295,19 @@ impl Interpreter {
         self.loaded_modules.insert(canonical.clone());
 
         // Set command-line arguments
-        self.global_vars.insert(100, Value::Integer(args.len() as i64));
-        for (i, arg) in args.iter().enumerate() {
-            let val = if let Ok(n) = arg.parse::<i64>() {
-                Value::Integer(n)
-            } else if let Ok(f) = arg.parse::<f64>() {
-                Value::Float(f)
-            } else {
-                Value::String(arg.clone())
-            };
-            self.global_vars.insert(101 + i as i64, val);
-        }
+        self.set_args(args);
 
         // Read and parse the code
         let code = std::fs::read_to_string(&canonical)
             .map_err(|_| InterpreterError::ModuleNotFound(path.display().to_string()))?;
-
-        let (instructions, functions) = Parser::parse(&code)?;
-
-        // Store functions
-        for func in functions {
-            self.functions.insert(func.id, func);
+        self.begin_trace(&code, args);
+        if let Some(logger) = &mut self.run_logger {
+            logger.output.clear();
         }
 
-        // Process imports first
-        for instr in &instructions {
-            if let Instruction::Import { path } = instr {
-                self.load_module(path)?;
-            }
-        }
-
-        // Execute main code
-        self.execute_block(&instructions)?;
-
-        Ok(self.output.clone())
+        let result = self.execute_main(&code);
+        self.log_run(&code, Some(&canonical), args, &result);
+        result
     }
 
     /// Run a single line of code (for REPL)
@@ -748,13 +2317,16 @@ impl Interpreter {
             return Ok(None);
         }
 
-        let instr = Parser::parse_line(&tokens, 1)?;
+        let instr = Parser::parse_line_at(&tokens, 1, self.language_level)?;
+        if self.strict {
+            self.check_defined(&instr, 1)?;
+        }
 
         match &instr {
             Instruction::Output { value } => {
                 let val = self.resolve(value);
-                self.output.push(val.to_string());
-                println!("{}", val);
+                self.line = 1;
+                self.write_output(val.to_string());
                 Ok(Some(val))
             }
             _ => {
@@ -769,6 +2341,64 @@ impl Interpreter {
         &self.output
     }
 
+    /// Instructions executed by the current or last run
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Resources the current or last run used, by function with accounting
+    /// on
+    ///
+    /// ```rust
+    /// use sui_lang::Interpreter;
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.set_accounting(true);
+    /// interp.run("# 0 0 {\n[ v0 10\n^ v0\n}\n$ v0 0\n$ v0 0", &[]).unwrap();
+    /// let stats = interp.stats();
+    /// assert_eq!(stats.steps, 6);
+    /// assert_eq!((stats.functions[&0].calls, stats.functions[&0].steps, stats.functions[&0].allocated), (2, 4, 20));
+    /// ```
+    pub fn stats(&self) -> RunStats {
+        RunStats { steps: self.steps, functions: self.usage.iter().map(|(&id, &usage)| (id, usage)).collect() }
+    }
+
+    /// Whether calls, instructions and allocations are counted by function
+    fn accounts(&self) -> bool {
+        self.accounting || !self.function_limits.is_empty()
+    }
+
+    /// Count a call of function `id`
+    fn count_call(&mut self, id: i64) {
+        if self.accounts() {
+            self.usage.entry(id).or_default().calls += 1;
+        }
+    }
+
+    /// Count `steps` instructions and `allocated` array elements for the
+    /// running function, failing if that takes it over its limits
+    fn account(&mut self, steps: u64, allocated: u64, line: usize) -> Result<(), InterpreterError> {
+        let Some(func_id) = self.context.func_id else {
+            return Ok(());
+        };
+        if !self.accounts() {
+            return Ok(());
+        }
+        let usage = self.usage.entry(func_id).or_default();
+        usage.steps += steps;
+        usage.allocated += allocated;
+        let usage = *usage;
+        let Some(limits) = self.function_limits.get(&func_id) else {
+            return Ok(());
+        };
+        let exceeded = |limit: Option<u64>, used: u64, resource| match limit {
+            Some(limit) if used > limit => Err(InterpreterError::FunctionLimitExceeded { func_id, resource, limit, line }),
+            _ => Ok(()),
+        };
+        exceeded(limits.max_steps, usage.steps, "steps")?;
+        exceeded(limits.max_allocated, usage.allocated, "array elements")
+    }
+
     /// Get a global variable value
     pub fn get_global(&self, idx: i64) -> Option<&Value> {
         self.global_vars.get(&idx)
@@ -778,6 +2408,36 @@ impl Interpreter {
     pub fn set_global(&mut self, idx: i64, value: Value) {
         self.global_vars.insert(idx, value);
     }
+
+    /// Every assigned global variable with its index, in index order
+    pub fn globals(&self) -> Vec<(i64, &Value)> {
+        let mut globals: Vec<(i64, &Value)> = self.global_vars.iter().map(|(idx, value)| (*idx, value)).collect();
+        globals.sort_by_key(|(idx, _)| *idx);
+        globals
+    }
+}
+
+/// `value`, unless it is an integer over [`MAX_INTEGER_BITS`]
+fn check_integer(value: Value) -> Result<Value, InterpreterError> {
+    match value.integer_bits() {
+        bits if bits > MAX_INTEGER_BITS => Err(InterpreterError::IntegerTooLarge(bits)),
+        _ => Ok(value),
+    }
+}
+
+/// Order of `sort`: numbers by value, then strings, then other values
+fn sort_order(a: &Value, b: &Value) -> std::cmp::Ordering {
+    let rank = |v: &Value| match v {
+        Value::Integer(_) | Value::BigInt(_) | Value::Float(_) => 0,
+        Value::String(_) => 1,
+        _ => 2,
+    };
+    match (a, b) {
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        _ if a.is_integer() && b.is_integer() => a.cmp_integers(b).unwrap_or(std::cmp::Ordering::Equal),
+        _ if a.is_numeric() && b.is_numeric() => a.to_float().total_cmp(&b.to_float()),
+        _ => rank(a).cmp(&rank(b)),
+    }
 }
 
 #[cfg(test)]
@@ -804,6 +2464,191 @@ mod tests {
         assert_eq!(output, vec!["15"]);
     }
 
+    #[test]
+    fn test_jump_table() {
+        let mut interp = Interpreter::new();
+        // Prints the name of each value; anything else falls through to "?"
+        let code = "# 0 1 {\nJ a0 1 2 2\n. \"?\"\n^ 0\n: 1\n. \"zero\"\n^ 0\n: 2\n. \"small\"\n}\n\
+                    $ v0 0 0\n$ v0 0 2\n$ v0 0 1.0\n$ v0 0 1.5\n$ v0 0 3\n$ v0 0 -1\n$ v0 0 \"1\"\nJ 0 7\n. \"end\"";
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["zero", "small", "small", "?", "?", "?", "?", "end"]);
+    }
+
+    #[test]
+    fn test_call_function() {
+        let mut interp = Interpreter::new();
+        let module = "# 0 1 {\n+ g0 g0 a0\n$ v0 1 g0\n^ v0\n}\n# 1 1 {\n. a0\n* v0 a0 2\n^ v0\n}\n# 2 0 {\nE \"no\"\n}";
+        interp.load_functions(module).unwrap();
+        assert_eq!(interp.call_function(0, &[Value::Integer(5)]).unwrap(), Value::Integer(10));
+        assert_eq!(interp.call_function(0, &[Value::Float(0.5)]).unwrap(), Value::Float(11.0));
+        assert_eq!(interp.get_output(), ["5", "5.5"]);
+
+        assert!(matches!(interp.call_function(2, &[]), Err(InterpreterError::Raised { line: 12, .. })));
+        assert!(matches!(interp.call_function(3, &[]), Err(InterpreterError::UndefinedFunction(3))));
+        // A failed call leaves the interpreter usable
+        assert_eq!(interp.call_function(1, &[Value::Integer(4)]).unwrap(), Value::Integer(8));
+    }
+
+    #[test]
+    fn test_run_until_line() {
+        let mut interp = Interpreter::new();
+        let code = "# 0 1 {\n* v0 a0 2\n^ v0\n}\n= g0 \"x\"\n$ v0 0 4\n$ v1 0 v0\n. v1\n; end";
+        let state = interp.run_until_line(code, 3, &["7".to_string()]).unwrap();
+        assert_eq!((state.line, &state.call_stack[..]), (Some(3), &[0][..]));
+        assert_eq!((&state.args[..], &state.locals[..]), (&[Value::Integer(4)][..], &[(0, Value::Integer(8))][..]));
+        assert_eq!(state.globals[0], (0, Value::from("x")));
+        assert_eq!(state.to_string(), "Before line 3 in function 0 after 3 steps\na0 = 4\nv0 = 8\ng0 = \"x\"\ng100 = 1\ng101 = 7");
+
+        // The first time the line runs, and the state at the end if never
+        let state = interp.run_until_line(code, 8, &[]).unwrap();
+        assert_eq!((state.line, state.locals.len(), state.call_stack.len()), (Some(8), 2, 0));
+        let state = interp.run_until_line(code, 9, &[]).unwrap();
+        assert_eq!((state.line, &state.output[..]), (None, &["16".to_string()][..]));
+
+        assert!(matches!(interp.run_until_line("E 1\n. 2", 2, &[]), Err(InterpreterError::Raised { line: 1, .. })));
+        // The interpreter runs normally afterwards
+        assert_eq!(interp.run(code, &[]).unwrap(), ["16"]);
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut interp = Interpreter::new();
+        interp.set_global(10, Value::from("seed"));
+        interp.run_incremental("= g2 1\n+ g0 g2 g100", &["x".to_string()]).unwrap();
+        let globals: Vec<(i64, String)> = interp.globals().into_iter().map(|(i, v)| (i, v.to_string())).collect();
+        assert_eq!(globals, [(0, "2".into()), (2, "1".into()), (10, "seed".into()), (100, "1".into()), (101, "x".into())]);
+    }
+
+    #[test]
+    fn test_input_queue() {
+        let mut interp = Interpreter::new();
+        interp.set_input_queue(vec!["12".to_string(), " 2.5 ".to_string()]);
+        interp.feed_input("abc");
+        let code = ", v0\n, v1\n, v2\n, v3\n* v4 v0 2\n. v4\n. v1\n. v2\n. v3";
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["24", "2.5", "abc", ""]);
+    }
+
+    #[test]
+    fn test_run_logger() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("runs.jsonl");
+        let mut interp = Interpreter::new();
+        interp.set_output_mode(OutputMode::Stdout);
+        interp.set_run_logger(RunLogger::open(&path).unwrap());
+        interp.run(". \"a\\\"b\"\n. g101", &["7".to_string()]).unwrap();
+        interp.run("/ v0 1 0\nE \"boom\"", &[]).unwrap_err();
+        interp.clear_run_logger();
+        interp.run(". 1", &[]).unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<&str> = log.lines().collect();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].starts_with(&format!("{{\"code_hash\":\"{}\"", crate::interpreter::code_hash(". \"a\\\"b\"\n. g101"))));
+        assert!(records[0].contains(r#""args":["7"],"output":["a\"b","7"],"error":null,"steps":2,"#));
+        assert!(records[1].contains(r#""output":[],"error":"Uncaught error at line 2: boom","steps":2,"#));
+    }
+
+    #[test]
+    fn test_run_compiled_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().join("lib.sui");
+        let main = dir.path().join("main.sui");
+        std::fs::write(&module, "# 0 1 {\n+ v0 a0 1\n^ v0\n}").unwrap();
+        std::fs::write(&main, "_ \"lib.sui\"\n$ v0 0 g101\n. v0\nE v0").unwrap();
+
+        let mut interp = Interpreter::new();
+        let program = interp.compile_file(&main).unwrap();
+        let compiled = dir.path().join("main.suic");
+        program.save(&compiled).unwrap();
+        // The module was compiled in
+        std::fs::remove_file(&module).unwrap();
+
+        let err = interp.run_compiled_file(&compiled, &["41".to_string()]).unwrap_err();
+        assert_eq!(err.to_string(), "Uncaught error at line 4: 42");
+        assert_eq!((interp.get_output(), interp.steps()), (&["42".to_string()][..], 6));
+        assert!(matches!(interp.compile_file(&main), Err(InterpreterError::ModuleNotFound(_))));
+    }
+
+    #[test]
+    fn test_function_limits() {
+        let code = "# 1 1 {\n[ v0 a0\n^ v0\n}\n# 0 0 {\n$ v0 1 5\n$ v0 1 5\n^ 0\n}\nT v0 0\n$ v1 0\n: 0\n. v0";
+        let mut interp = Interpreter::new();
+        interp.run(code, &[]).unwrap();
+        assert!(interp.stats().functions.is_empty());
+
+        // Function 1 may not create more than 8 elements, and no handler
+        // may hide that
+        interp.set_function_limits(1, FunctionLimits { max_steps: None, max_allocated: Some(8) });
+        let err = interp.run(code, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Function 1 exceeded its limit of 8 array elements at line 2");
+        let stats = interp.stats();
+        assert_eq!(stats.functions[&0], FunctionUsage { calls: 1, steps: 2, allocated: 0 });
+        assert_eq!(stats.functions[&1], FunctionUsage { calls: 2, steps: 3, allocated: 10 });
+
+        interp.run(". 1", &[]).unwrap();
+        assert_eq!(interp.stats(), RunStats { steps: 1, functions: Default::default() });
+    }
+
+    #[test]
+    fn test_register_builtin() {
+        let mut interp = Interpreter::new();
+        interp.register_builtin("abs", |args| Ok(Value::Integer(args.len() as i64)));
+        interp.register_builtin("vec.norm", |args| match args {
+            [a, b] => Ok(Value::Float(a.to_float().hypot(b.to_float()))),
+            _ => Err("vec.norm takes 2 arguments".to_string()),
+        });
+        let output = interp.run("R v0 \"abs\" -1 -2\n. v0\nR v1 \"vec.norm\" 3 4\n. v1", &[]).unwrap();
+        assert_eq!(output, vec!["2", "5.0"]);
+
+        let err = interp.run("= v0 1\nR v1 \"vec.norm\" v0", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at line 2: vec.norm takes 2 arguments");
+    }
+
+    #[test]
+    fn test_transcript() {
+        let mut interp = Interpreter::new();
+        let out = interp.host_output();
+        interp.register_builtin("log", move |args| {
+            for arg in args {
+                out.print(&arg.to_string());
+            }
+            Ok(Value::Integer(0))
+        });
+        let code = ". \"a\"\nR v0 \"log\" \"b\" \"c\"\n. \"d\"";
+        assert_eq!(interp.run(code, &[]).unwrap(), ["a", "b", "c", "d"]);
+        assert!(interp.transcript().is_empty());
+
+        interp.set_transcript(true);
+        interp.set_output_mode(OutputMode::Stdout);
+        assert!(interp.run(code, &[]).unwrap().is_empty());
+        let host = OutputSource::Host("log".to_string());
+        let entries: Vec<(u64, OutputSource, usize, String)> =
+            interp.transcript().into_iter().map(|e| (e.seq, e.source, e.line, e.text)).collect();
+        assert_eq!(
+            entries,
+            [
+                (0, OutputSource::Program, 1, "a".to_string()),
+                (1, host.clone(), 2, "b".to_string()),
+                (2, host, 2, "c".to_string()),
+                (3, OutputSource::Program, 3, "d".to_string()),
+            ]
+        );
+
+        // Each run starts a new transcript
+        interp.run(". 1", &[]).unwrap();
+        assert_eq!(interp.transcript().len(), 1);
+    }
+
+    #[test]
+    fn test_le_ge() {
+        let mut interp = Interpreter::new();
+        let code = "<= v0 2 2\n. v0\n>= v1 1 2.5\n. v1\n<= v2 \"abc\" \"abd\"\n. v2\n>= v3 18446744073709551616 1\n. v3";
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["1", "0", "1", "1"]);
+
+        interp.set_language_level(LanguageVersion::new(1, 2));
+        let err = interp.run("<= v0 1 2", &[]).unwrap_err();
+        assert!(err.to_string().contains("Instruction '<=' at line 1 requires sui >= 1.3"));
+    }
+
     #[test]
     fn test_loop() {
         let mut interp = Interpreter::new();
@@ -875,6 +2720,39 @@ $ g1 0 g0
         assert_eq!(output, vec!["42"]);
     }
 
+    #[test]
+    fn test_array_copy_on_write() {
+        let mut interp = Interpreter::new();
+        let code = r#"
+[ v0 3
+= v1 v0
+{ v1 0 7
+. v0
+. v1
+"#;
+        let output = interp.run(code, &[]).unwrap();
+        assert_eq!(output, vec!["[0, 0, 0]", "[7, 0, 0]"]);
+    }
+
+    #[test]
+    fn test_interned_string_literals() {
+        let mut interp = Interpreter::new();
+        let code = r#"
+= v0 "ab"
+= v1 "ab"
++ v2 v0 "c"
+~ v3 v0 v1
+. v2
+. v3
+"#;
+        let output = interp.run(code, &[]).unwrap();
+        assert_eq!(output, vec!["abc", "1"]);
+        match (interp.context.local_vars.get(&0), interp.context.local_vars.get(&1)) {
+            (Some(Value::String(a)), Some(Value::String(b))) => assert!(Arc::ptr_eq(a, b)),
+            other => panic!("expected strings, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_string_output() {
         let mut interp = Interpreter::new();
@@ -895,4 +2773,461 @@ $ g1 0 g0
         let output = interp.run(code, &["42".to_string()]).unwrap();
         assert_eq!(output, vec!["1", "42"]);
     }
+
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send<T: Send>() {}
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send::<Interpreter>();
+        assert_send_sync::<Value>();
+        assert_send_sync::<InterpreterError>();
+
+        let handle = std::thread::spawn(|| {
+            let mut interp = Interpreter::new();
+            interp.run("[ g0 2\n{ g0 1 \"x\"\n. g0", &[]).map(|_| interp)
+        });
+        let mut interp = handle.join().unwrap().unwrap();
+        assert_eq!(interp.run_incremental(". g0", &[]).unwrap(), ["[0, x]"]);
+    }
+
+    #[test]
+    fn test_run_incremental() {
+        let mut interp = Interpreter::new();
+        // Main code of a library is not run
+        interp.load_functions("# 0 2 {\n+ v0 a0 a1\n^ v0\n}\n. \"loaded\"").unwrap();
+        assert_eq!(interp.run_incremental("$ g0 0 1 2\n= v0 5", &[]).unwrap(), Vec::<String>::new());
+        // Globals and functions survive; locals and output do not
+        let output = interp.run_incremental("$ g0 0 g0 g101\n. g0\n. v0", &["10".to_string()]).unwrap();
+        assert_eq!(output, vec!["13", "0"]);
+        // A full run starts over
+        assert!(interp.run("$ g0 0 1 2", &[]).is_err());
+    }
+
+    #[test]
+    fn test_language_level() {
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(";! sui 1.0\n. 1", &[]).unwrap(), vec!["1"]);
+
+        let err = interp.run(";! sui 9.1\n. 1", &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Parse error: Program at line 1 requires sui 9.1, but this runtime supports up to {}",
+                LANGUAGE_VERSION
+            )
+        );
+
+        interp.set_language_level(LanguageVersion::new(0, 9));
+        let err = interp.run("= v0 1", &[]).unwrap_err();
+        assert!(err.to_string().contains("Instruction '=' at line 1 requires sui >= 1.0"));
+        assert!(interp.run_line(". 1").is_err());
+    }
+
+    #[test]
+    fn test_nested_arrays() {
+        let code = "R v0 \"array2d\" 2 3\n{ v0 1 2 7\n= v1 v0\n{ v1 0 0 5\n\
+                    ] v2 v0 1 2\n] v3 v0 0 0\n] v4 v1 0 0\n] v5 v0 1 9\n] v6 v0 1 2 0\n\
+                    { v0 1 2 0 9\n] v7 v0 1 2\n. v2\n. v3\n. v4\n. v5\n. v6\n. v7";
+        let mut interp = Interpreter::new();
+        // Rows are independent, copies keep value semantics, and bad paths read 0
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["7", "0", "5", "0", "0", "7"]);
+    }
+
+    #[test]
+    fn test_array_builtins() {
+        let interp = Interpreter::new();
+        let call = |func: &str, args: &[Value]| interp.call_builtin(func, args);
+        let arr = |items: &[i64]| Value::from(items.iter().copied().map(Value::Integer).collect::<Vec<_>>());
+        let (int, s) = (Value::Integer, |text: &str| Value::from(text));
+
+        assert_eq!(call("slice", &[arr(&[1, 2, 3, 4]), int(1), int(3)]), arr(&[2, 3]));
+        assert_eq!(call("slice", &[arr(&[1, 2]), int(1), int(99)]), arr(&[2]));
+        assert_eq!(call("slice", &[s("héllo"), int(1), int(3)]), s("él"));
+        assert_eq!(call("concat", &[arr(&[1]), arr(&[2, 3])]), arr(&[1, 2, 3]));
+        assert_eq!(call("concat", &[s("a"), s("b")]), s("ab"));
+        assert_eq!(call("fill", &[arr(&[1, 2, 3]), int(7)]), arr(&[7, 7, 7]));
+        assert_eq!(call("copy", &[arr(&[0, 0, 0]), arr(&[5, 6, 7, 8])]), arr(&[5, 6, 7]));
+        assert_eq!(call("copy", &[arr(&[0, 0, 0]), arr(&[5])]), arr(&[5, 0, 0]));
+        assert_eq!(call("sum", &[arr(&[1, 2, 3])]), int(6));
+        assert_eq!(call("sum", &[Value::from(vec![int(1), Value::Float(0.5)])]), Value::Float(1.5));
+        assert_eq!(call("reverse", &[arr(&[1, 2, 3])]), arr(&[3, 2, 1]));
+        assert_eq!(call("reverse", &[s("abc")]), s("cba"));
+
+        // Numbers first, then strings
+        let mixed = Value::from(vec![s("b"), Value::Float(2.5), s("a"), int(1)]);
+        let sorted = Value::from(vec![int(1), Value::Float(2.5), s("a"), s("b")]);
+        assert_eq!(call("sort", &[mixed]), sorted);
+    }
+
+    #[test]
+    fn test_floordiv_ipow() {
+        let code = "R v0 \"floordiv\" 7 -2\nR v1 \"floordiv\" 7.5 2\nR v2 \"ipow\" 3 4\n\
+                    R v3 \"ipow\" 2 -1\nR v4 \"ipow\" 2 64\n. v0\n. v1\n. v2\n. v3\n. v4";
+        let mut interp = Interpreter::new();
+        assert_eq!(
+            interp.run(code, &[]).unwrap(),
+            vec!["-4", "3.0", "81", "0.5", "18446744073709551616"]
+        );
+
+        // Floor division by zero follows the division policy
+        interp.set_division_policy(DivisionPolicy::Error);
+        assert!(matches!(
+            interp.run("R v0 \"floordiv\" 1 0", &[]).unwrap_err(),
+            InterpreterError::DivisionByZero { line: 1 }
+        ));
+    }
+
+    #[test]
+    fn test_big_integers() {
+        // 25! overflows an i64 by the time n reaches 21
+        let factorial = "# 0 1 {\n< v0 a0 2\n? v0 1\n- v1 a0 1\n$ v2 0 v1\n* v3 a0 v2\n^ v3\n: 1\n^ 1\n}\n$ g0 0 25\n. g0\n\
+                         R g1 \"ipow\" 2 100\nR g2 \"floordiv\" g1 g0\n. g2\n% g3 g1 g0\n. g3\n- g4 0 g0\n. g4\n\
+                         R g5 \"floordiv\" g4 7\n. g5\n< g6 g4 g1\n. g6\n/ g7 g0 g1\n. g7\n- g8 g0 g0\n. g8";
+        let mut interp = Interpreter::new();
+        assert_eq!(
+            interp.run(factorial, &[]).unwrap(),
+            vec![
+                "15511210043330985984000000",
+                "81724",
+                "12470647047902940287205376",
+                "-15511210043330985984000000",
+                "-2215887149047283712000000",
+                "1",
+                "1.2236187195855331e-05",
+                "0",
+            ]
+        );
+        assert!(matches!(interp.get_global(8), Some(Value::Integer(0))));
+
+        let interp = Interpreter::new();
+        let call = |func: &str, args: &[Value]| interp.call_builtin(func, args);
+        let big = call("int", &[Value::from("-123456789012345678901234567890")]);
+        assert!(matches!(big, Value::BigInt(_)));
+        assert_eq!(call("abs", std::slice::from_ref(&big)).to_string(), "123456789012345678901234567890");
+        assert_eq!(call("min", &[Value::Integer(3), big.clone()]), big);
+        assert_eq!(Value::Integer(i64::MAX).add(&Value::Integer(1)).to_string(), "9223372036854775808");
+        assert_eq!(Value::Integer(i64::MIN).floor_div(&Value::Integer(-1)).to_string(), "9223372036854775808");
+        assert!(big.lt(&Value::Float(0.5)).is_truthy());
+        assert_eq!(big.sub(&big), Value::Integer(0));
+    }
+
+    #[test]
+    fn test_big_integer_limits() {
+        let mut interp = Interpreter::new();
+        let run = |interp: &mut Interpreter, code: &str| interp.run(code, &[]).map_err(|e| e.to_string());
+
+        // Squaring in a loop stops at the limit instead of growing for minutes
+        let squaring = "= v0 3\n= v1 0\n: 0\n* v0 v0 v0\n+ v1 v1 1\n< v2 v1 30\n? v2 0";
+        assert_eq!(run(&mut interp, squaring).unwrap_err(), "Integer too large: 25969 bits (maximum: 16384)");
+        assert_eq!(run(&mut interp, "R v0 \"ipow\" 2 20000").unwrap_err(), "Integer too large: 20001 bits (maximum: 16384)");
+        let output = run(&mut interp, "R v0 \"ipow\" 3 10000\nR v1 \"len\" v0\nR v2 \"str\" v0\nR v3 \"len\" v2\n. v3").unwrap();
+        assert_eq!(output, ["4772"]);
+        let digits = format!("R v0 \"int\" \"{}\"", "7".repeat(6000));
+        assert_eq!(run(&mut interp, &digits).unwrap_err(), "Integer too large: 19931 bits (maximum: 16384)");
+
+        // Exact answers or an error, never a rounded or saturated one
+        let code = "R v0 \"ipow\" 2 126\nT v1 0\nR v2 \"band\" v0 255\n: 0\n. v1\n\
+                    R v3 \"abs\" -9223372036854775808\n. v3\nR v4 \"abs\" -1152921504606846977\n. v4\n\
+                    R v5 \"floor\" v0\n. v5";
+        assert_eq!(
+            run(&mut interp, code).unwrap(),
+            [
+                "Runtime error at line 3: band needs integers that fit in 64 bits",
+                "9223372036854775808",
+                "1152921504606846977",
+                "85070591730234615865843651857942052864",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exact_whole_numbers() {
+        let mut interp = Interpreter::new();
+        let run = |interp: &mut Interpreter, code: &str| interp.run(code, &[]).map_err(|e| e.to_string());
+
+        // 0, 1 and -1 to any power are exact, as in Python
+        let code = "R v0 \"ipow\" 1 5000000000\n. v0\nR v0 \"ipow\" -1 5000000001\n. v0\n\
+                    R v0 \"ipow\" -1 5000000000\n. v0\nR v0 \"ipow\" 0 5000000000\n. v0\nR v0 \"ipow\" 0 0\n. v0\n\
+                    R v1 \"ipow\" 2 70\nR v0 \"ipow\" -1 v1\n. v0\nR v0 \"ipow\" -1 -3\n. v0";
+        assert_eq!(run(&mut interp, code).unwrap(), ["1", "-1", "1", "0", "1", "1", "-1.0"]);
+        assert_eq!(run(&mut interp, "R v0 \"ipow\" 2 5000000000").unwrap_err(), "Integer too large: 5000000001 bits (maximum: 16384)");
+
+        // Whole floats past 64 bits become big integers, not i64::MAX
+        let code = "R v0 \"floor\" 1e20\n. v0\nR v0 \"ceil\" -1e19\n. v0\nR v0 \"round\" 1.5e19\n. v0\n\
+                    R v0 \"abs\" -1e19\n. v0\nR v0 \"max\" 1e19 0.5\n. v0\nR v0 \"min\" -1e19 0.5\n. v0";
+        assert_eq!(
+            run(&mut interp, code).unwrap(),
+            [
+                "100000000000000000000",
+                "-10000000000000000000",
+                "15000000000000000000",
+                "10000000000000000000",
+                "10000000000000000000",
+                "-10000000000000000000",
+            ]
+        );
+        assert_eq!(run(&mut interp, "R v0 \"floor\" \"inf\"").unwrap_err(), "Runtime error at line 1: floor: cannot convert inf to an integer");
+        assert_eq!(run(&mut interp, "R v0 \"round\" \"nan\"").unwrap_err(), "Runtime error at line 1: round: cannot convert nan to an integer");
+        assert_eq!(run(&mut interp, "R v0 \"max\" \"inf\" 1\n. v0").unwrap(), ["inf"]);
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let interp = Interpreter::new();
+        let s = |text: &str| Value::from(text);
+        let call = |func: &str, args: &[Value]| interp.call_builtin(func, args);
+        assert_eq!(call("upper", &[s("abc")]), s("ABC"));
+        assert_eq!(call("strip", &[s("  a b ")]), s("a b"));
+        assert_eq!(call("split", &[s("a,b,,c"), s(",")]), Value::from(vec![s("a"), s("b"), s(""), s("c")]));
+        assert_eq!(call("split", &[s(" a  b ")]), Value::from(vec![s("a"), s("b")]));
+        let parts = Value::from(vec![s("x"), Value::Integer(1)]);
+        assert_eq!(call("join", &[parts, s("-")]), s("x-1"));
+        assert_eq!(call("replace", &[s("aXbX"), s("X"), s("-")]), s("a-b-"));
+        assert_eq!(call("find", &[s("héllo"), s("l")]), Value::Integer(2));
+        assert_eq!(call("find", &[s("abc"), s("z")]), Value::Integer(-1));
+        assert_eq!(call("endswith", &[s("main.sui"), s(".sui")]), Value::Integer(1));
+        let nums = Value::from(vec![Value::Integer(1), Value::Float(2.5)]);
+        assert_eq!(call("contains", &[nums.clone(), Value::Float(2.5)]), Value::Integer(1));
+        assert_eq!(call("contains", &[nums, Value::Integer(2)]), Value::Integer(0));
+        assert_eq!(call("contains", &[s("hello"), s("ell")]), Value::Integer(1));
+        assert_eq!(call("len", &[s("héllo")]), Value::Integer(5));
+    }
+
+    #[test]
+    fn test_fmt_builtin() {
+        let mut interp = Interpreter::new();
+        let code = "R v0 \"format\" \"{} + {1} = {{{}}}\" 1 2\n. v0\nR v1 \"fmt\" \"{:.0f} {:.1e} {:.1%}\" 2.5 1234.5 0.125\n. v1";
+        assert_eq!(interp.run(code, &[]).unwrap(), ["1 + 2 = {2}", "2 1.2e+03 12.5%"]);
+
+        // What Python refuses, or would take too much memory, is an error
+        for (template, message) in [
+            ("{!r}", "invalid format field '{!r}'"),
+            ("{:g}", "format spec 'g' is invalid"),
+            ("{:d}", "format spec 'd' does not apply to type float"),
+            ("{:>400000000}", "format spec '>400000000' is wider than 10000 characters"),
+        ] {
+            let code = format!("R v0 \"fmt\" \"{}\" 2.5", template);
+            let err = interp.run(&code, &[]).unwrap_err();
+            assert_eq!(err.to_string(), format!("Runtime error at line 1: fmt: {}", message));
+        }
+    }
+
+    #[test]
+    fn test_time_builtins() {
+        let code = "R v0 \"time.monotonic\"\nR v1 \"time.sleep_ms\" 1500\nR v2 \"time.sleep_ms\" -5\n\
+                    R v3 \"time.monotonic\"\n- v4 v3 v0\n. v4\nR v5 \"time.now\"\n. v5\n. v1";
+        let mut interp = Interpreter::new();
+        interp.set_clock(Clock::Virtual { epoch: 100.0 });
+        assert_eq!(interp.run(code, &[]).unwrap(), ["1.5", "101.5", "0"]);
+        // Each run starts over
+        assert_eq!(interp.run(code, &[]).unwrap(), ["1.5", "101.5", "0"]);
+
+        interp.set_clock(Clock::System);
+        let output = interp.run("R v0 \"time.sleep_ms\" 1\nR v1 \"time.now\"\n. v1", &[]).unwrap();
+        assert!(output[0].parse::<f64>().unwrap() > 1.6e9);
+
+        // Too long for a Duration, under either clock
+        for clock in [Clock::Virtual { epoch: 0.0 }, Clock::System] {
+            interp.set_clock(clock);
+            for ms in ["1e300", "\"inf\""] {
+                let err = interp.run(&format!("R v0 \"time.sleep_ms\" {}", ms), &[]).unwrap_err();
+                assert!(err.to_string().starts_with("Runtime error at line 1: time.sleep_ms: cannot sleep for "), "{}", err);
+            }
+        }
+        interp.set_clock(Clock::Virtual { epoch: 0.0 });
+        // Sleeping past the largest Duration stops there
+        let code = "R v0 \"time.sleep_ms\" 1.5e22\nR v0 \"time.sleep_ms\" 1.5e22\nR v1 \"time.now\"\n. v1";
+        assert_eq!(interp.run(code, &[]).unwrap(), [Value::Float(Duration::MAX.as_secs_f64()).to_string()]);
+    }
+
+    #[test]
+    fn test_randint_virtual_clock() {
+        let code = ": 0\nR v0 \"randint\" 1 6\n. v0\n+ v1 v1 1\n< v2 v1 20\n? v2 0";
+        let mut interp = Interpreter::new();
+        interp.set_clock(Clock::Virtual { epoch: 0.0 });
+        let first = interp.run(code, &[]).unwrap();
+        assert_eq!(interp.run(code, &[]).unwrap(), first);
+        assert!(first.iter().all(|n| (1..=6).contains(&n.parse::<i64>().unwrap())));
+        // Not the same number every time
+        assert!(first.iter().any(|n| n != &first[0]));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let mut interp = Interpreter::new();
+        assert!(matches!(
+            interp.run("= v0 1\nR v1 \"env.get\" \"PATH\"", &[]),
+            Err(InterpreterError::PermissionDenied { line: 2, capability: "--allow-env", .. })
+        ));
+        // Denials can be caught
+        let output = interp.run("T v0 1\nR v1 \"proc.run\" \"true\"\nU\n: 1\n. v0", &[]).unwrap();
+        assert_eq!(output, ["Permission denied at line 2: proc.run needs --allow-process"]);
+
+        interp.allow_channels(false);
+        let err = interp.run("R v0 \"chan.recv\" 0", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Permission denied at line 1: chan.recv needs channels");
+
+        interp.allow_env(true);
+        let code = "R v0 \"env.set\" \"SUI_TEST_CAPABILITIES\" \"on\"\nR v1 \"env.get\" \"SUI_TEST_CAPABILITIES\"\n. v1\n\
+                    R v2 \"env.get\" \"SUI_TEST_UNSET\"\n. v2";
+        assert_eq!(interp.run(code, &[]).unwrap(), ["on", "0"]);
+
+        #[cfg(unix)]
+        {
+            interp.allow_process(true);
+            let code = "R v0 \"proc.run\" \"echo hi; exit 3\"\n] v1 v0 0\n] v2 v0 1\n. v1\nR v2 \"strip\" v2\n. v2\n\
+                        [ v3 2\n{ v3 0 \"echo\"\n{ v3 1 \"a b\"\nR v4 \"proc.run\" v3\n] v5 v4 1\n. v5";
+            assert_eq!(interp.run(code, &[]).unwrap(), ["3", "hi", "a b\n"]);
+        }
+    }
+
+    #[test]
+    fn test_regex_builtins() {
+        let code = "R v0 \"re.match\" \"[a-z]+\" \"abc1\"\n. v0\nR v0 \"re.match\" \"\\\\d\" \"abc1\"\n. v0\n\
+                    R v1 \"re.find\" \"\\\\d+\" \"ab 12 34\"\n. v1\nR v1 \"re.find\" \"x\" \"ab\"\n. v1\n\
+                    R v2 \"re.findall\" \"\\\\d+\" \"ab 12 34\"\nR v3 \"join\" v2 \",\"\n. v3\n\
+                    R v4 \"re.replace\" \"\\\\s+\" \"a  b   c\" \"$1_\"\n. v4";
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), ["1", "0", "12", "0", "12,34", "a$1_b$1_c"]);
+
+        let output = interp.run("T v0 1\nR v1 \"re.find\" \"(\" \"x\"\nU\n: 1\n. v0", &[]).unwrap();
+        assert!(output[0].starts_with("Runtime error at line 2: invalid regex '('"));
+    }
+
+    #[test]
+    fn test_struct_builtins() {
+        let code = "R v0 \"struct.new\" \"id\" 7 \"tags\" v9\nR v1 \"struct.set\" v0 \"id\" 8\n\
+                    R v2 \"struct.set\" v1 \"ok\" 1\nR v3 \"struct.get\" v0 \"id\"\n. v3\n. v2\n\
+                    R v4 \"struct.get\" v2 \"nope\"\n. v4\nR v5 \"len\" v2\n. v5\nR v6 \"json.encode\" v2\n. v6\n\
+                    R v7 \"json.decode\" \"{\\\"id\\\": 8, \\\"tags\\\": 0, \\\"ok\\\": true}\"\n~ v8 v7 v2\n. v8";
+        let mut interp = Interpreter::new();
+        assert_eq!(
+            interp.run(code, &[]).unwrap(),
+            ["7", "{id: 8, tags: 0, ok: 1}", "0", "3", "{\"id\":8,\"tags\":0,\"ok\":1}", "1"]
+        );
+
+        let output = interp.run("T v0 1\nR v1 \"json.decode\" \"[1,\"\nU\n: 1\n. v0", &[]).unwrap();
+        assert!(output[0].starts_with("Runtime error at line 2: invalid JSON"));
+        assert!(matches!(
+            interp.run("R v0 \"struct.set\" 5 \"x\" 1", &[]),
+            Err(InterpreterError::Runtime { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bitwise_builtins() {
+        let interp = Interpreter::new();
+        let call = |func: &str, a: i64, b: i64| {
+            interp.call_builtin(func, &[Value::Integer(a), Value::Integer(b)])
+        };
+        assert_eq!(call("band", 12, 10), Value::Integer(8));
+        assert_eq!(call("bor", 12, 10), Value::Integer(14));
+        assert_eq!(call("bxor", 12, 10), Value::Integer(6));
+        assert_eq!(call("bnot", 12, 0), Value::Integer(-13));
+        assert_eq!(call("shl", 1, 63), Value::Integer(i64::MIN));
+        assert_eq!(call("shl", 3, 65), Value::Integer(6));
+        assert_eq!(call("shr", -16, 2), Value::Integer(-4));
+        // Floats are truncated like `int`
+        let shifted = interp.call_builtin("shr", &[Value::Float(9.7), Value::Integer(1)]);
+        assert_eq!(shifted, Value::Integer(4));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let code = "[ v0 2\n{ v0 0 \"a\"\n{ v0 1 \"b\"\n= v1 \"xy\"\n= v2 3\n\
+                    I v3 v0\n: 0\nN v4 v5 v3\n? v4 1\n. v5\n@ 0\n: 1\n\
+                    I v3 v1\n: 2\nN v4 v5 v3\n? v4 3\n. v5\n@ 2\n: 3\n\
+                    I v3 v2\n: 4\nN v4 v5 v3\n? v4 5\n. v5\n@ 4\n: 5\n\
+                    N v4 v5 v3\n. v4\n. v5";
+        let mut interp = Interpreter::new();
+        // Once exhausted, the done flag is set and the variable is left alone
+        assert_eq!(
+            interp.run(code, &[]).unwrap(),
+            vec!["a", "b", "x", "y", "0", "1", "2", "1", "2"]
+        );
+    }
+
+    #[test]
+    fn test_try_raise() {
+        // Raises unwind across calls to the innermost handler
+        let code = "# 0 1 {\nE a0\n^ 0\n}\nT v0 0\n$ v1 0 \"boom\"\n. 1\n: 0\n. v0\n\
+                    T v0 1\nT v0 2\nU\nE 5\n: 2\n. 2\n: 1\n. v0";
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["boom", "5"]);
+
+        // Runtime errors are caught with their message
+        let code = "T v0 0\n$ v1 9\n: 0\n. v0";
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["Undefined function: 9"]);
+
+        let mut interp = Interpreter::new();
+        match interp.run("T v0 0\nU\n. 1\nE 42\n: 0", &[]).unwrap_err() {
+            InterpreterError::Raised { value, line } => {
+                assert_eq!(value, Value::Integer(42));
+                assert_eq!(line, 4);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_division_policy() {
+        let code = "= v0 0\n/ v1 7 v0\n% v2 7 v0\n. v1\n. v2";
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["nan", "nan"]);
+
+        interp.set_division_policy(DivisionPolicy::SaturateZero);
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["0", "0"]);
+
+        interp.set_division_policy(DivisionPolicy::Error);
+        assert!(matches!(
+            interp.run(code, &[]).unwrap_err(),
+            InterpreterError::DivisionByZero { line: 2 }
+        ));
+        let code = "T v1 0\n% v2 7 0\n: 0\n. v1";
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["Division by zero at line 2"]);
+
+        assert_eq!("zero".parse(), Ok(DivisionPolicy::SaturateZero));
+        assert!("inf".parse::<DivisionPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut interp = Interpreter::new();
+        let code = "C 0 \"hello\"\nC 1 40\n+ v0 c1 2\n. c0\n. v0";
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["hello", "42"]);
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let code = "# 0 1 {\n+ v0 a0 1\n^ v0\n}\n\n= g0 1\n$ g1 0 g0\n. g1\n. g2";
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), vec!["2", "0"]);
+
+        let mut interp = Interpreter::new();
+        interp.set_strict(true);
+        match interp.run(code, &[]).unwrap_err() {
+            InterpreterError::UndefinedVariable { name, line } => {
+                assert_eq!((name.as_str(), line), ("g2", 9));
+            }
+            other => panic!("expected undefined variable, got {:?}", other),
+        }
+        assert_eq!(interp.get_output(), ["2"]);
+
+        // Missing argument inside a function reports the body line
+        let mut interp = Interpreter::new();
+        interp.set_strict(true);
+        let err = interp.run("# 0 2 {\n^ a1\n}\n$ v0 0 1", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Undefined variable a1 at line 2");
+        assert!(interp.run_line("{ v5 0 1").is_err());
+
+        // Writes to arguments are dropped, or rejected in strict mode
+        let code = "# 0 1 {\n- a0 a0 1\n^ a0\n}\n$ v0 0 5\n. v0";
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(code, &[]).unwrap(), ["5"]);
+        interp.set_strict(true);
+        let err = interp.run(code, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Write to argument a0 at line 2; arguments are read-only");
+        assert_eq!(err.line(), Some(2));
+    }
 }
@@ -0,0 +1,67 @@
+//! Resource accounting by function
+//!
+//! With accounting on, an [`Interpreter`](super::Interpreter) attributes
+//! each instruction and each array element created by `[` to the function
+//! whose body ran it, so a host running code from several sources, such as
+//! untrusted imported modules, can see which of them used what and cap each
+//! one with [`FunctionLimits`]. Instructions of the main code are not
+//! attributed to any function.
+
+use std::collections::BTreeMap;
+
+/// Resources one function used in a run, over all its calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionUsage {
+    /// Times it was called
+    pub calls: u64,
+    /// Instructions of its own body executed; those of functions it calls
+    /// count for them
+    pub steps: u64,
+    /// Array elements its `[` instructions created
+    pub allocated: u64,
+}
+
+/// Most one function may use in a run, over all its calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionLimits {
+    /// Instructions of its own body
+    pub max_steps: Option<u64>,
+    /// Array elements created by its `[` instructions
+    pub max_allocated: Option<u64>,
+}
+
+impl FunctionLimits {
+    /// The lower of each limit of `self` and `other`
+    pub fn min(self, other: FunctionLimits) -> FunctionLimits {
+        let min = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        FunctionLimits {
+            max_steps: min(self.max_steps, other.max_steps),
+            max_allocated: min(self.max_allocated, other.max_allocated),
+        }
+    }
+}
+
+/// Resources the current or last run used
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Instructions executed
+    pub steps: u64,
+    /// Usage of each function called, by ID; empty unless accounting is on
+    pub functions: BTreeMap<i64, FunctionUsage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_min() {
+        let a = FunctionLimits { max_steps: Some(10), max_allocated: None };
+        let b = FunctionLimits { max_steps: Some(20), max_allocated: Some(5) };
+        assert_eq!(a.min(b), FunctionLimits { max_steps: Some(10), max_allocated: Some(5) });
+        assert_eq!(FunctionLimits::default().min(FunctionLimits::default()), FunctionLimits::default());
+    }
+}
@@ -0,0 +1,287 @@
+//! Recorded executions for postmortem debugging
+//!
+//! With [`Interpreter::set_recording`](super::Interpreter::set_recording),
+//! a run logs every instruction it executes with the values it read, every
+//! assignment and every output line. `sui --record` saves the [`Trace`] in
+//! a compact binary form and `sui-debug --replay` navigates it.
+
+use super::{BigInt, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Leading bytes of an encoded trace, with the format version
+const MAGIC: &[u8; 8] = b"SUITRACE";
+const VERSION: u32 = 1;
+
+/// Something that happened during a recorded run
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// An instruction started
+    Step {
+        /// Source line (1-based); lines of functions imported from other
+        /// modules refer to those modules
+        line: usize,
+        /// Function running the instruction (-1 for the top level)
+        func_id: i64,
+        /// Number of calls in progress
+        depth: usize,
+        /// Operands the instruction reads, with their values before it ran
+        operands: Vec<(String, Value)>,
+    },
+    /// A variable was assigned; `v` variables belong to the frame at `depth`
+    Write { depth: usize, var: String, value: Value },
+    /// A line was printed
+    Output(String),
+}
+
+/// Complete record of one run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    /// Source of the program
+    pub source: String,
+    /// Command-line arguments
+    pub args: Vec<String>,
+    /// Events in the order they happened
+    pub events: Vec<TraceEvent>,
+    /// Error the run ended with, if any
+    pub error: Option<String>,
+}
+
+impl Trace {
+    /// Number of instructions executed
+    pub fn steps(&self) -> usize {
+        self.events.iter().filter(|e| matches!(e, TraceEvent::Step { .. })).count()
+    }
+
+    /// Binary form written by [`Trace::save`]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        put_str(&mut out, &self.source);
+        put_len(&mut out, self.args.len());
+        for arg in &self.args {
+            put_str(&mut out, arg);
+        }
+        match &self.error {
+            Some(error) => {
+                out.push(1);
+                put_str(&mut out, error);
+            }
+            None => out.push(0),
+        }
+        put_len(&mut out, self.events.len());
+        for event in &self.events {
+            match event {
+                TraceEvent::Step { line, func_id, depth, operands } => {
+                    out.push(0);
+                    put_len(&mut out, *line);
+                    out.extend_from_slice(&func_id.to_le_bytes());
+                    put_len(&mut out, *depth);
+                    put_len(&mut out, operands.len());
+                    for (operand, value) in operands {
+                        put_str(&mut out, operand);
+                        put_value(&mut out, value);
+                    }
+                }
+                TraceEvent::Write { depth, var, value } => {
+                    out.push(1);
+                    put_len(&mut out, *depth);
+                    put_str(&mut out, var);
+                    put_value(&mut out, value);
+                }
+                TraceEvent::Output(line) => {
+                    out.push(2);
+                    put_str(&mut out, line);
+                }
+            }
+        }
+        out
+    }
+
+    /// Parse the output of [`Trace::encode`]
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+            return None;
+        }
+        let source = reader.string()?;
+        let args = (0..reader.len()?).map(|_| reader.string()).collect::<Option<Vec<_>>>()?;
+        let error = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.string()?),
+        };
+        let mut events = Vec::new();
+        for _ in 0..reader.len()? {
+            let event = match reader.u8()? {
+                0 => {
+                    let line = reader.len()?;
+                    let func_id = reader.i64()?;
+                    let depth = reader.len()?;
+                    let operands = (0..reader.len()?)
+                        .map(|_| Some((reader.string()?, reader.value()?)))
+                        .collect::<Option<Vec<_>>>()?;
+                    TraceEvent::Step { line, func_id, depth, operands }
+                }
+                1 => TraceEvent::Write { depth: reader.len()?, var: reader.string()?, value: reader.value()? },
+                2 => TraceEvent::Output(reader.string()?),
+                _ => return None,
+            };
+            events.push(event);
+        }
+        (reader.pos == bytes.len()).then_some(Trace { source, args, events, error })
+    }
+
+    /// Write the trace to a file
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    /// Read a trace written by [`Trace::save`]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::decode(&bytes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed trace"))
+    }
+}
+
+pub(super) fn put_len(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u64).to_le_bytes());
+}
+
+pub(super) fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Integer(n) => {
+            out.push(0);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Value::Float(f) => {
+            out.push(1);
+            out.extend_from_slice(&f.to_bits().to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(2);
+            put_str(out, s);
+        }
+        Value::Array(items) => {
+            out.push(3);
+            put_len(out, items.len());
+            for item in items.iter() {
+                put_value(out, item);
+            }
+        }
+        Value::Null => out.push(4),
+        Value::BigInt(n) => {
+            out.push(5);
+            put_str(out, &n.to_string());
+        }
+        Value::Struct(fields) => {
+            out.push(6);
+            put_len(out, fields.len());
+            for (name, v) in fields.iter() {
+                put_str(out, name);
+                put_value(out, v);
+            }
+        }
+    }
+}
+
+/// Cursor over an encoded trace or compiled program
+pub(super) struct Reader<'a> {
+    pub(super) bytes: &'a [u8],
+    pub(super) pos: usize,
+}
+
+impl Reader<'_> {
+    pub(super) fn take(&mut self, n: usize) -> Option<&[u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    pub(super) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(super) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(super) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub(super) fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub(super) fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+
+    pub(super) fn string(&mut self) -> Option<String> {
+        let n = self.len()?;
+        String::from_utf8(self.take(n)?.to_vec()).ok()
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        Some(match self.u8()? {
+            0 => Value::Integer(self.i64()?),
+            1 => Value::Float(f64::from_bits(self.u64()?)),
+            2 => Value::String(Arc::from(self.string()?)),
+            3 => {
+                let n = self.len()?;
+                // Every element takes at least one byte
+                if n > self.bytes.len() - self.pos {
+                    return None;
+                }
+                Value::from((0..n).map(|_| self.value()).collect::<Option<Vec<_>>>()?)
+            }
+            4 => Value::Null,
+            5 => Value::from(BigInt::parse(&self.string()?)?),
+            6 => {
+                let n = self.len()?;
+                // Every field takes at least two bytes
+                if n > self.bytes.len() - self.pos {
+                    return None;
+                }
+                let fields = (0..n).map(|_| Some((Arc::from(self.string()?), self.value()?)));
+                Value::new_struct(fields.collect::<Option<Vec<_>>>()?)
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let trace = Trace {
+            source: "= v0 1\n. v0".to_string(),
+            args: vec!["x y".to_string()],
+            events: vec![
+                TraceEvent::Step { line: 1, func_id: -1, depth: 0, operands: vec![("1".to_string(), Value::Integer(1))] },
+                TraceEvent::Write {
+                    depth: 0,
+                    var: "v0".to_string(),
+                    value: Value::from(vec![Value::Float(0.5), Value::from("s"), Value::Null]),
+                },
+                TraceEvent::Output("1".to_string()),
+            ],
+            error: Some("boom".to_string()),
+        };
+        let bytes = trace.encode();
+        assert_eq!(Trace::decode(&bytes), Some(trace));
+        assert_eq!(Trace::decode(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Trace::decode(b"SUITRACE"), None);
+    }
+}
@@ -1,18 +1,51 @@
 //! Value types for the Sui language
 
+use std::cmp::Ordering;
 use std::fmt;
+use std::sync::Arc;
+
+use super::bigint::MAX_INTEGER_BITS;
+use super::BigInt;
+
+/// Largest array `[` may create
+pub const MAX_ARRAY_SIZE: usize = 1 << 24;
 
 /// Sui runtime value
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     /// Integer value
     Integer(i64),
+    /// Integer too large for an `i64`
+    ///
+    /// Arithmetic on integers never wraps: results that overflow become
+    /// `BigInt`, and results that fit again become [`Value::Integer`], so
+    /// both are `int` to programs.
+    BigInt(Arc<BigInt>),
     /// Floating point value
+    ///
+    /// Floats display exactly as Python's `repr` shows them: `0.1`, `3.0`,
+    /// `0.30000000000000004`, `1e+16`, `1.5e-05`, `inf` and `nan`, so output
+    /// matches programs transpiled by Sui2Py.
     Float(f64),
     /// String value
-    String(String),
+    ///
+    /// Strings are immutable and shared: cloning is O(1), and literals from
+    /// the same program share storage through the [`Interner`].
+    ///
+    /// [`Interner`]: super::Interner
+    String(Arc<str>),
     /// Array value
-    Array(Vec<Value>),
+    ///
+    /// Elements are shared copy-on-write: copying an array (assignment,
+    /// argument passing, `]` reads) is O(1), and `{` copies the elements only
+    /// if another variable still holds the same array. Arrays therefore keep
+    /// value semantics; writes are never visible through other variables.
+    Array(Arc<Vec<Value>>),
+    /// Record of named fields, in the order they were added
+    ///
+    /// Built and read with the `struct.*` builtins and shared copy-on-write
+    /// like arrays; `struct.set` gives a changed copy.
+    Struct(Arc<Vec<(Arc<str>, Value)>>),
     /// Null/None value
     Null,
 }
@@ -22,20 +55,35 @@ impl Value {
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Integer(n) => *n != 0,
+            Value::BigInt(_) => true,
             Value::Float(f) => *f != 0.0,
             Value::String(s) => !s.is_empty(),
             Value::Array(arr) => !arr.is_empty(),
+            Value::Struct(fields) => !fields.is_empty(),
             Value::Null => false,
         }
     }
 
-    /// Convert to integer
+    /// Entry a `J` jump table of `len` labels selects: a whole number
+    /// from 0 to `len - 1`, integer or float
+    pub fn table_index(&self, len: usize) -> Option<usize> {
+        let index = match self {
+            Value::Integer(n) => usize::try_from(*n).ok()?,
+            Value::Float(x) if x.fract() == 0.0 && *x >= 0.0 => *x as usize,
+            _ => return None,
+        };
+        (index < len).then_some(index)
+    }
+
+    /// Convert to integer; a big integer saturates
     pub fn to_int(&self) -> i64 {
         match self {
             Value::Integer(n) => *n,
+            Value::BigInt(n) => if n.is_negative() { i64::MIN } else { i64::MAX },
             Value::Float(f) => *f as i64,
             Value::String(s) => s.parse().unwrap_or(0),
             Value::Array(arr) => arr.len() as i64,
+            Value::Struct(fields) => fields.len() as i64,
             Value::Null => 0,
         }
     }
@@ -44,34 +92,230 @@ impl Value {
     pub fn to_float(&self) -> f64 {
         match self {
             Value::Integer(n) => *n as f64,
+            Value::BigInt(n) => n.to_f64(),
             Value::Float(f) => *f,
             Value::String(s) => s.parse().unwrap_or(0.0),
             Value::Array(arr) => arr.len() as f64,
+            Value::Struct(fields) => fields.len() as f64,
             Value::Null => 0.0,
         }
     }
 
+    /// Name of the value's type: `int`, `float`, `str`, `array`, `struct` or
+    /// `null`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) | Value::BigInt(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "str",
+            Value::Array(_) => "array",
+            Value::Struct(_) => "struct",
+            Value::Null => "null",
+        }
+    }
+
     /// Check if this value is numeric
     pub fn is_numeric(&self) -> bool {
-        matches!(self, Value::Integer(_) | Value::Float(_))
+        matches!(self, Value::Integer(_) | Value::BigInt(_) | Value::Float(_))
+    }
+
+    /// Check if this value is an integer of either size
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Value::Integer(_) | Value::BigInt(_))
+    }
+
+    /// Bits in the magnitude of an integer; 0 for other values
+    pub fn integer_bits(&self) -> u64 {
+        match self {
+            Value::Integer(n) => 64 - n.unsigned_abs().leading_zeros() as u64,
+            Value::BigInt(n) => n.bits(),
+            _ => 0,
+        }
+    }
+
+    /// The value as a big integer, if it is an integer
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Value::Integer(n) => Some(BigInt::from(*n)),
+            Value::BigInt(n) => Some(BigInt::clone(n)),
+            _ => None,
+        }
+    }
+
+    /// Exact integer arithmetic: `small` on two `i64`s, falling back to
+    /// `big` when it overflows or either side is already big
+    ///
+    /// `None` if either value is not an integer.
+    fn integer_op(
+        &self,
+        other: &Value,
+        small: fn(i64, i64) -> Option<i64>,
+        big: fn(&BigInt, &BigInt) -> BigInt,
+    ) -> Option<Value> {
+        if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
+            if let Some(n) = small(*a, *b) {
+                return Some(Value::Integer(n));
+            }
+        }
+        Some(Value::from(big(&self.to_bigint()?, &other.to_bigint()?)))
+    }
+
+    /// Order of two integers of either size, or `None` unless both are integers
+    pub(crate) fn cmp_integers(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            _ => Some(self.to_bigint()?.cmp(&other.to_bigint()?)),
+        }
+    }
+
+    /// Array of `rows` arrays of `cols` zeros, or `None` if too large
+    ///
+    /// The rows start out shared and are copied on their first write.
+    pub fn array2d(rows: i64, cols: i64) -> Option<Value> {
+        let (rows, cols) = (rows.max(0) as usize, cols.max(0) as usize);
+        if rows.checked_mul(cols).map_or(true, |n| n > MAX_ARRAY_SIZE) || rows > MAX_ARRAY_SIZE {
+            return None;
+        }
+        let row = Value::from(vec![Value::Integer(0); cols]);
+        Some(Value::from(vec![row; rows]))
+    }
+
+    /// Element at a path of indices into nested arrays
+    ///
+    /// An index out of bounds, or into a value that is not an array, reads 0.
+    pub fn element(&self, path: &[i64]) -> Value {
+        let mut current = self;
+        for &index in path {
+            match current {
+                Value::Array(arr) if index >= 0 && (index as usize) < arr.len() => {
+                    current = &arr[index as usize];
+                }
+                _ => return Value::Integer(0),
+            }
+        }
+        current.clone()
+    }
+
+    /// Replace the element at a path of indices into nested arrays
+    ///
+    /// Returns `false` and changes nothing if an index is out of bounds or
+    /// does not index an array. Arrays along the path are copied only if
+    /// they are shared.
+    pub fn set_element(&mut self, path: &[i64], value: Value) -> bool {
+        let Some((&index, rest)) = path.split_first() else {
+            return false;
+        };
+        match self {
+            Value::Array(arr) if index >= 0 && (index as usize) < arr.len() => {
+                if !rest.is_empty() && !matches!(arr[index as usize], Value::Array(_)) {
+                    return false;
+                }
+                let slot = &mut Arc::make_mut(arr)[index as usize];
+                if rest.is_empty() {
+                    *slot = value;
+                    true
+                } else {
+                    slot.set_element(rest, value)
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Struct with the given fields; a repeated name keeps its first
+    /// position and its last value
+    pub fn new_struct(fields: impl IntoIterator<Item = (Arc<str>, Value)>) -> Value {
+        let mut record = Value::Struct(Arc::default());
+        for (name, value) in fields {
+            record.set_field(&name, value);
+        }
+        record
+    }
+
+    /// Field `name` of a struct, or `None` if it has no such field or the
+    /// value is not a struct
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(fields) => fields.iter().find(|(n, _)| &**n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Set field `name` of a struct, adding it after the others if new
+    ///
+    /// Returns `false` and changes nothing if the value is not a struct. The
+    /// fields are copied only if they are shared.
+    pub fn set_field(&mut self, name: &str, value: Value) -> bool {
+        let Value::Struct(fields) = self else {
+            return false;
+        };
+        let fields = Arc::make_mut(fields);
+        match fields.iter_mut().find(|(n, _)| &**n == name) {
+            Some((_, slot)) => *slot = value,
+            None => fields.push((Arc::from(name), value)),
+        }
+        true
+    }
+
+    /// Iterator over an array, the characters of a string, or `0..n` for a number
+    ///
+    /// Iterators are ordinary arrays `[source, position]`, advanced by
+    /// [`Value::advance`].
+    pub fn iterator(&self) -> Value {
+        let source = match self {
+            Value::Array(_) => self.clone(),
+            Value::String(s) => {
+                Value::from(s.chars().map(|c| Value::from(c.to_string())).collect::<Vec<_>>())
+            }
+            other => Value::Integer(other.to_int()),
+        };
+        Value::from(vec![source, Value::Integer(0)])
+    }
+
+    /// Next item of an iterator from [`Value::iterator`], or `None` once it
+    /// is exhausted or if the value is not an iterator
+    pub fn advance(&mut self) -> Option<Value> {
+        let Value::Array(state) = self else {
+            return None;
+        };
+        let (source, position) = match state.as_slice() {
+            [source, Value::Integer(position)] => (source, *position),
+            _ => return None,
+        };
+        let item = match source {
+            Value::Array(items) => items.get(usize::try_from(position).ok()?).cloned()?,
+            Value::Integer(end) if (0..*end).contains(&position) => Value::Integer(position),
+            _ => return None,
+        };
+        Arc::make_mut(state)[1] = Value::Integer(position + 1);
+        Some(item)
     }
 
     /// Add two values
     pub fn add(&self, other: &Value) -> Value {
+        if let Some(sum) = self.integer_op(other, i64::checked_add, BigInt::add) {
+            return sum;
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a + b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 + b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a + *b as f64),
-            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+            (Value::String(a), Value::String(b)) => {
+                let mut s = String::with_capacity(a.len() + b.len());
+                s.push_str(a);
+                s.push_str(b);
+                Value::from(s)
+            }
             _ => Value::Float(self.to_float() + other.to_float()),
         }
     }
 
     /// Subtract two values
     pub fn sub(&self, other: &Value) -> Value {
+        if let Some(difference) = self.integer_op(other, i64::checked_sub, BigInt::sub) {
+            return difference;
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a - b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 - b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a - *b as f64),
@@ -81,8 +325,10 @@ impl Value {
 
     /// Multiply two values
     pub fn mul(&self, other: &Value) -> Value {
+        if let Some(product) = self.integer_op(other, i64::checked_mul, BigInt::mul) {
+            return product;
+        }
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a * b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 * b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a * *b as f64),
@@ -99,10 +345,70 @@ impl Value {
         Value::Float(self.to_float() / divisor)
     }
 
+    /// Divide two values, rounding toward negative infinity
+    ///
+    /// Integers give an integer; anything else gives a whole float.
+    pub fn floor_div(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) if *b != 0 && (*a, *b) != (i64::MIN, -1) => {
+                let q = a / b;
+                if a % b != 0 && (*a < 0) != (*b < 0) {
+                    Value::Integer(q - 1)
+                } else {
+                    Value::Integer(q)
+                }
+            }
+            _ if self.is_integer() && other.is_integer() && other.is_truthy() => {
+                let (a, b) = (self.to_bigint().unwrap_or_default(), other.to_bigint().unwrap_or_default());
+                a.floor_div(&b).map_or(Value::Integer(0), Value::from)
+            }
+            _ => match self.div(other) {
+                Value::Float(q) => Value::Float(q.floor()),
+                other => other,
+            },
+        }
+    }
+
+    /// Raise to a power
+    ///
+    /// An integer base and a non-negative integer exponent give an integer,
+    /// unless the result would have more than [`MAX_INTEGER_BITS`].
+    pub fn pow(&self, other: &Value) -> Value {
+        // 0, 1 and -1 stay small whatever the exponent
+        if let (Some(base @ -1..=1), Some(exp)) = (self.to_bigint().and_then(|b| b.to_i64()), other.to_bigint()) {
+            if !exp.is_negative() {
+                let odd = exp.div_rem(&BigInt::from(2)).is_some_and(|(_, r)| !r.is_zero());
+                return Value::Integer(match base {
+                    0 if exp.is_zero() => 1,
+                    -1 if !odd => 1,
+                    base => base,
+                });
+            }
+        }
+        if let (Some(base), Value::Integer(exp)) = (self.to_bigint(), other) {
+            if let Ok(exp) = u32::try_from(*exp) {
+                if let Value::Integer(n) = self {
+                    if let Some(n) = n.checked_pow(exp) {
+                        return Value::Integer(n);
+                    }
+                }
+                // |base|^exp has at least (bits - 1) * exp + 1 bits
+                if base.bits().saturating_sub(1).saturating_mul(exp as u64) < MAX_INTEGER_BITS {
+                    return Value::from(base.pow(exp));
+                }
+            }
+        }
+        Value::Float(self.to_float().powf(other.to_float()))
+    }
+
     /// Modulo two values
     pub fn modulo(&self, other: &Value) -> Value {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) if *b != 0 => Value::Integer(a % b),
+            (Value::Integer(a), Value::Integer(b)) if *b != 0 => Value::Integer(a.wrapping_rem(*b)),
+            _ if self.is_integer() && other.is_integer() && other.is_truthy() => {
+                let (a, b) = (self.to_bigint().unwrap_or_default(), other.to_bigint().unwrap_or_default());
+                a.div_rem(&b).map_or(Value::Integer(0), |(_, r)| Value::from(r))
+            }
             _ => {
                 let divisor = other.to_float();
                 if divisor == 0.0 {
@@ -117,9 +423,11 @@ impl Value {
     /// Less than comparison
     pub fn lt(&self, other: &Value) -> Value {
         let result = match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => a < b,
             (Value::String(a), Value::String(b)) => a < b,
-            _ => self.to_float() < other.to_float(),
+            _ => match self.cmp_integers(other) {
+                Some(order) => order.is_lt(),
+                None => self.to_float() < other.to_float(),
+            },
         };
         Value::Integer(if result { 1 } else { 0 })
     }
@@ -127,9 +435,35 @@ impl Value {
     /// Greater than comparison
     pub fn gt(&self, other: &Value) -> Value {
         let result = match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => a > b,
             (Value::String(a), Value::String(b)) => a > b,
-            _ => self.to_float() > other.to_float(),
+            _ => match self.cmp_integers(other) {
+                Some(order) => order.is_gt(),
+                None => self.to_float() > other.to_float(),
+            },
+        };
+        Value::Integer(if result { 1 } else { 0 })
+    }
+
+    /// Less than or equal comparison
+    pub fn le(&self, other: &Value) -> Value {
+        let result = match (self, other) {
+            (Value::String(a), Value::String(b)) => a <= b,
+            _ => match self.cmp_integers(other) {
+                Some(order) => order.is_le(),
+                None => self.to_float() <= other.to_float(),
+            },
+        };
+        Value::Integer(if result { 1 } else { 0 })
+    }
+
+    /// Greater than or equal comparison
+    pub fn ge(&self, other: &Value) -> Value {
+        let result = match (self, other) {
+            (Value::String(a), Value::String(b)) => a >= b,
+            _ => match self.cmp_integers(other) {
+                Some(order) => order.is_ge(),
+                None => self.to_float() >= other.to_float(),
+            },
         };
         Value::Integer(if result { 1 } else { 0 })
     }
@@ -139,8 +473,10 @@ impl Value {
         let result = match (self, other) {
             (Value::Integer(a), Value::Integer(b)) => a == b,
             (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
-            (Value::String(a), Value::String(b)) => a == b,
+            (Value::String(a), Value::String(b)) => Arc::ptr_eq(a, b) || a == b,
             (Value::Null, Value::Null) => true,
+            (Value::Struct(a), Value::Struct(b)) => a == b,
+            _ if self.is_integer() && other.is_integer() => self.cmp_integers(other).is_some_and(Ordering::is_eq),
             _ => self.to_float() == other.to_float(),
         };
         Value::Integer(if result { 1 } else { 0 })
@@ -157,15 +493,9 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Value::Integer(n) => write!(f, "{}", n),
-            Value::Float(n) => {
-                // Format like Python - remove trailing zeros
-                if n.fract() == 0.0 {
-                    write!(f, "{}.0", n.trunc())
-                } else {
-                    write!(f, "{}", n)
-                }
-            }
-            Value::String(s) => write!(f, "{}", s),
+            Value::BigInt(n) => write!(f, "{}", n),
+            Value::Float(n) => write_float(f, *n),
+            Value::String(s) => f.write_str(s),
             Value::Array(arr) => {
                 write!(f, "[")?;
                 for (i, v) in arr.iter().enumerate() {
@@ -176,17 +506,92 @@ impl fmt::Display for Value {
                 }
                 write!(f, "]")
             }
+            Value::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, v)?;
+                }
+                write!(f, "}}")
+            }
             Value::Null => write!(f, "null"),
         }
     }
 }
 
+/// Write a float like Python's `repr`: the shortest digits that read back
+/// as the same float, positional for exponents -4 to 15 and scientific with a
+/// signed, two-digit exponent otherwise
+fn write_float(f: &mut fmt::Formatter<'_>, x: f64) -> fmt::Result {
+    if x.is_nan() {
+        return f.write_str("nan");
+    }
+    if x.is_infinite() {
+        return f.write_str(if x < 0.0 { "-inf" } else { "inf" });
+    }
+    // Shortest round-trip digits, e.g. "1.5e-5" or "0e0". Of several
+    // equally short candidates Python takes the one nearest `x`, which is
+    // the correctly rounded one whenever it reads back as `x`
+    let mut scientific = format!("{:e}", x.abs());
+    let significant = scientific.find('e').map_or(1, |e| scientific[..e].replace('.', "").len());
+    let nearest = format!("{:.*e}", significant - 1, x.abs());
+    if nearest.parse::<f64>() == Ok(x.abs()) {
+        scientific = nearest;
+    }
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let digits = mantissa.replace('.', "");
+    if x.is_sign_negative() {
+        f.write_str("-")?;
+    }
+    match exponent {
+        -4..=-1 => write!(f, "0.{}{}", "0".repeat((-exponent - 1) as usize), digits),
+        0..=15 => {
+            let point = exponent as usize + 1;
+            if digits.len() > point {
+                write!(f, "{}.{}", &digits[..point], &digits[point..])
+            } else {
+                write!(f, "{}{}.0", digits, "0".repeat(point - digits.len()))
+            }
+        }
+        _ => {
+            let (first, rest) = digits.split_at(1);
+            let point = if rest.is_empty() { "" } else { "." };
+            let sign = if exponent < 0 { '-' } else { '+' };
+            write!(f, "{}{}{}e{}{:02}", first, point, rest, sign, exponent.abs())
+        }
+    }
+}
+
 impl From<i64> for Value {
     fn from(n: i64) -> Self {
         Value::Integer(n)
     }
 }
 
+impl Value {
+    /// The integer a whole, finite float equals, of any size; other floats
+    /// stay floats
+    pub fn from_whole(x: f64) -> Value {
+        match BigInt::from_f64(x) {
+            Some(n) => Value::from(n),
+            None => Value::Float(x),
+        }
+    }
+}
+
+/// A big integer that fits in an `i64` becomes [`Value::Integer`]
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(n) => Value::Integer(n),
+            None => Value::BigInt(Arc::new(n)),
+        }
+    }
+}
+
 impl From<f64> for Value {
     fn from(n: f64) -> Self {
         Value::Float(n)
@@ -195,18 +600,116 @@ impl From<f64> for Value {
 
 impl From<String> for Value {
     fn from(s: String) -> Self {
-        Value::String(s)
+        Value::String(Arc::from(s))
     }
 }
 
 impl From<&str> for Value {
     fn from(s: &str) -> Self {
-        Value::String(s.to_string())
+        Value::String(Arc::from(s))
+    }
+}
+
+impl From<Arc<str>> for Value {
+    fn from(s: Arc<str>) -> Self {
+        Value::String(s)
     }
 }
 
 impl From<Vec<Value>> for Value {
     fn from(arr: Vec<Value>) -> Self {
-        Value::Array(arr)
+        Value::Array(Arc::new(arr))
+    }
+}
+
+/// Values serialize as plain data: numbers, strings, arrays, maps and `null`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+        match self {
+            Value::Integer(n) => serializer.serialize_i64(*n),
+            // The nearest float; JSON readers rarely keep integers past 2^53 anyway
+            Value::BigInt(n) => serializer.serialize_f64(n.to_f64()),
+            Value::Float(n) => serializer.serialize_f64(*n),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Array(arr) => {
+                let mut seq = serializer.serialize_seq(Some(arr.len()))?;
+                for v in arr.iter() {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            Value::Struct(fields) => {
+                use serde::ser::SerializeMap;
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, v) in fields.iter() {
+                    map.serialize_entry(&**name, v)?;
+                }
+                map.end()
+            }
+            Value::Null => serializer.serialize_unit(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Value {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number, string, array, map or null")
+            }
+
+            fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+                Ok(Value::Integer(b as i64))
+            }
+
+            fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+                Ok(Value::Integer(n))
+            }
+
+            fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+                Ok(i64::try_from(n).map_or(Value::Float(n as f64), Value::Integer))
+            }
+
+            fn visit_f64<E>(self, n: f64) -> Result<Value, E> {
+                Ok(Value::Float(n))
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+                Ok(Value::from(s))
+            }
+
+            fn visit_unit<E>(self) -> Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_none<E>(self) -> Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Value::from(items))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+                let mut fields = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some((name, value)) = map.next_entry::<String, Value>()? {
+                    fields.push((Arc::from(name), value));
+                }
+                Ok(Value::new_struct(fields))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
     }
 }
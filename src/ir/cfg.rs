@@ -0,0 +1,364 @@
+//! Control-flow graph construction
+
+use crate::interpreter::Instruction;
+use std::collections::{HashMap, HashSet};
+
+/// Index of a basic block within its [`Cfg`]
+pub type BlockId = usize;
+
+/// How control leaves a basic block
+///
+/// Targets equal to [`Cfg::exit`] leave the function (or program).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    /// Unconditional transfer, including falling through to the next block
+    Goto(BlockId),
+    /// Transfer to `target` if `cond` is truthy, otherwise to `fallthrough`
+    Branch {
+        cond: String,
+        target: BlockId,
+        fallthrough: BlockId,
+    },
+    /// Transfer to `targets[k]` if `value` is the whole number `k`,
+    /// otherwise to `fallthrough`
+    Switch {
+        value: String,
+        targets: Vec<BlockId>,
+        fallthrough: BlockId,
+    },
+    /// Return a value from the function
+    Return(String),
+}
+
+impl Terminator {
+    /// Values of a `Switch` grouped by the block they select, in order of
+    /// the first value; values that select the fallthrough are left out
+    pub fn switch_cases(&self) -> Vec<(Vec<usize>, BlockId)> {
+        let Terminator::Switch { targets, fallthrough, .. } = self else {
+            return Vec::new();
+        };
+        let mut cases: Vec<(Vec<usize>, BlockId)> = Vec::new();
+        for (k, &target) in targets.iter().enumerate() {
+            if target == *fallthrough {
+                continue;
+            }
+            match cases.iter_mut().find(|(_, t)| *t == target) {
+                Some((values, _)) => values.push(k),
+                None => cases.push((vec![k], target)),
+            }
+        }
+        cases
+    }
+}
+
+/// A maximal run of straight-line instructions
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    /// Label defined at the start of this block, if any
+    pub label: Option<i64>,
+    /// Instructions without labels, jumps, returns, comments or empty lines
+    pub instructions: Vec<Instruction>,
+    /// Control transfer at the end of the block
+    pub terminator: Terminator,
+}
+
+/// Control-flow graph of a function body or of the top-level code
+///
+/// Blocks are kept in source order and block `0` is the entry. Blocks that
+/// cannot be reached from the entry or from a `T` handler label are dropped,
+/// so a block that falls through always falls through to the block with the
+/// next index. Exception edges into handlers are not modeled as successors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Build the graph for a list of instructions
+    ///
+    /// Jumps follow the interpreter: when a label is defined twice the last
+    /// definition wins, and jumps to undefined labels do nothing.
+    pub fn build(instructions: &[Instruction]) -> Self {
+        let defined: HashSet<i64> = instructions
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Label { id } => Some(*id),
+                _ => None,
+            })
+            .collect();
+        let splits = |instr: &Instruction| match instr {
+            Instruction::Label { .. } | Instruction::Return { .. } => true,
+            Instruction::CondJump { label, .. } | Instruction::Jump { label } => {
+                defined.contains(label)
+            }
+            Instruction::Switch { labels, .. } => labels.iter().any(|label| defined.contains(label)),
+            _ => false,
+        };
+
+        // Block index of every label; later definitions overwrite earlier ones
+        let mut labels: HashMap<i64, usize> = HashMap::new();
+        let mut block = 0;
+        for instr in instructions {
+            if splits(instr) {
+                block += 1;
+            }
+            if let Instruction::Label { id } = instr {
+                labels.insert(*id, block);
+            }
+        }
+
+        // Split at labels and after every control transfer; terminators
+        // refer to raw block indices until unreachable blocks are removed.
+        let mut raw: Vec<(Option<i64>, Vec<Instruction>, Option<Terminator>)> =
+            vec![(None, Vec::new(), None)];
+        for instr in instructions {
+            let current = raw.len() - 1;
+            match instr {
+                Instruction::Label { id } => raw.push((Some(*id), Vec::new(), None)),
+                Instruction::CondJump { cond, label } => {
+                    if let Some(&target) = labels.get(label) {
+                        raw[current].2 = Some(Terminator::Branch {
+                            cond: cond.clone(),
+                            target,
+                            fallthrough: current + 1,
+                        });
+                        raw.push((None, Vec::new(), None));
+                    }
+                }
+                Instruction::Jump { label } => {
+                    if let Some(&target) = labels.get(label) {
+                        raw[current].2 = Some(Terminator::Goto(target));
+                        raw.push((None, Vec::new(), None));
+                    }
+                }
+                Instruction::Switch { value, labels: table } => {
+                    if table.iter().any(|label| labels.contains_key(label)) {
+                        // An undefined label continues like a value out of range
+                        let targets = table.iter().map(|label| labels.get(label).copied().unwrap_or(current + 1));
+                        raw[current].2 = Some(Terminator::Switch {
+                            value: value.clone(),
+                            targets: targets.collect(),
+                            fallthrough: current + 1,
+                        });
+                        raw.push((None, Vec::new(), None));
+                    }
+                }
+                Instruction::Return { value } => {
+                    raw[current].2 = Some(Terminator::Return(value.clone()));
+                    raw.push((None, Vec::new(), None));
+                }
+                Instruction::Comment
+                | Instruction::Empty
+                | Instruction::Const { .. }
+                | Instruction::Invalid { .. }
+                | Instruction::FuncDef { .. }
+                | Instruction::FuncEnd => {}
+                other => raw[current].1.push(other.clone()),
+            }
+        }
+
+        // Blocks without an explicit transfer fall through
+        let count = raw.len();
+        let raw: Vec<(Option<i64>, Vec<Instruction>, Terminator)> = raw
+            .into_iter()
+            .enumerate()
+            .map(|(i, (label, instrs, term))| (label, instrs, term.unwrap_or(Terminator::Goto(i + 1))))
+            .collect();
+
+        // Keep the blocks reachable from the entry or from a try handler
+        let mut reachable = vec![false; count];
+        let mut work = vec![0];
+        work.extend(instructions.iter().filter_map(|instr| match instr {
+            Instruction::Try { label, .. } => labels.get(label).copied(),
+            _ => None,
+        }));
+        while let Some(b) = work.pop() {
+            if b >= count || reachable[b] {
+                continue;
+            }
+            reachable[b] = true;
+            work.extend(successors_of(&raw[b].2));
+        }
+        let mut remap = vec![0; count + 1];
+        let mut next = 0;
+        for (i, keep) in reachable.iter().enumerate() {
+            remap[i] = next;
+            if *keep {
+                next += 1;
+            }
+        }
+        remap[count] = next;
+
+        let blocks = raw
+            .into_iter()
+            .zip(reachable)
+            .filter(|(_, keep)| *keep)
+            .map(|((label, instructions, terminator), _)| BasicBlock {
+                label,
+                instructions,
+                terminator: match terminator {
+                    Terminator::Goto(t) => Terminator::Goto(remap[t]),
+                    Terminator::Branch {
+                        cond,
+                        target,
+                        fallthrough,
+                    } => Terminator::Branch {
+                        cond,
+                        target: remap[target],
+                        fallthrough: remap[fallthrough],
+                    },
+                    Terminator::Switch {
+                        value,
+                        targets,
+                        fallthrough,
+                    } => Terminator::Switch {
+                        value,
+                        targets: targets.into_iter().map(|t| remap[t]).collect(),
+                        fallthrough: remap[fallthrough],
+                    },
+                    ret => ret,
+                },
+            })
+            .collect();
+
+        Self { blocks }
+    }
+
+    /// All blocks in source order
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Get a block by ID
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id]
+    }
+
+    /// The pseudo block ID that stands for leaving the function
+    pub fn exit(&self) -> BlockId {
+        self.blocks.len()
+    }
+
+    /// The entry block
+    pub fn entry(&self) -> BlockId {
+        0
+    }
+
+    /// Successors of a block (may include [`Cfg::exit`])
+    pub fn successors(&self, id: BlockId) -> Vec<BlockId> {
+        successors_of(&self.blocks[id].terminator)
+    }
+
+    /// Predecessors of a block
+    pub fn predecessors(&self, id: BlockId) -> Vec<BlockId> {
+        (0..self.blocks.len())
+            .filter(|&b| self.successors(b).contains(&id))
+            .collect()
+    }
+
+    /// Block that a jump to `label` lands on
+    pub fn label_block(&self, label: i64) -> Option<BlockId> {
+        self.blocks.iter().rposition(|b| b.label == Some(label))
+    }
+
+    /// Distinct `(err, label)` try handlers registered in the graph
+    pub fn handlers(&self) -> Vec<(String, i64)> {
+        let mut handlers: Vec<(String, i64)> = Vec::new();
+        for instr in self.blocks.iter().flat_map(|b| &b.instructions) {
+            if let Instruction::Try { err, label } = instr {
+                if !handlers.iter().any(|(e, l)| e == err && l == label) {
+                    handlers.push((err.clone(), *label));
+                }
+            }
+        }
+        handlers
+    }
+
+    /// Whether any block registers a try handler
+    pub fn has_handlers(&self) -> bool {
+        self.blocks
+            .iter()
+            .any(|b| b.instructions.iter().any(|i| matches!(i, Instruction::Try { .. })))
+    }
+
+    /// Whether the graph is a single run of code without jumps
+    pub fn is_linear(&self) -> bool {
+        self.blocks.iter().enumerate().all(|(i, b)| match &b.terminator {
+            Terminator::Goto(t) => *t == i + 1,
+            Terminator::Return(_) => i + 1 == self.blocks.len(),
+            Terminator::Branch { .. } | Terminator::Switch { .. } => false,
+        })
+    }
+}
+
+fn successors_of(terminator: &Terminator) -> Vec<BlockId> {
+    match terminator {
+        Terminator::Goto(t) => vec![*t],
+        Terminator::Branch {
+            target,
+            fallthrough,
+            ..
+        } => vec![*target, *fallthrough],
+        Terminator::Switch {
+            targets,
+            fallthrough,
+            ..
+        } => targets.iter().chain([fallthrough]).copied().collect(),
+        Terminator::Return(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Parser;
+
+    fn cfg(code: &str) -> Cfg {
+        let (instructions, _) = Parser::parse(code).unwrap();
+        Cfg::build(&instructions)
+    }
+
+    #[test]
+    fn test_linear() {
+        let cfg = cfg("= v0 1\n. v0");
+        assert_eq!(cfg.blocks().len(), 1);
+        assert_eq!(cfg.block(0).instructions.len(), 2);
+        assert_eq!(cfg.block(0).terminator, Terminator::Goto(cfg.exit()));
+        assert!(cfg.is_linear());
+    }
+
+    #[test]
+    fn test_branch_and_labels() {
+        let cfg = cfg("? v0 1\n. 1\n: 1\n. 2");
+        assert_eq!(cfg.blocks().len(), 3);
+        assert_eq!(
+            cfg.block(0).terminator,
+            Terminator::Branch {
+                cond: "v0".to_string(),
+                target: 2,
+                fallthrough: 1
+            }
+        );
+        assert_eq!(cfg.label_block(1), Some(2));
+        assert_eq!(cfg.predecessors(2), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_handler_blocks_are_kept() {
+        // The handler is only entered by unwinding
+        let cfg = cfg("T v0 1\nE 5\n^ 0\n: 1\n^ v0");
+        assert_eq!(cfg.blocks().len(), 2);
+        assert_eq!(cfg.label_block(1), Some(1));
+        assert!(cfg.predecessors(1).is_empty());
+        assert!(cfg.has_handlers());
+        assert_eq!(cfg.handlers(), vec![("v0".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_unreachable_and_unknown_labels() {
+        // Code after a return is dropped; jumps to unknown labels are no-ops
+        let cfg = cfg("@ 9\n. 1\n^ 0\n. 2");
+        assert_eq!(cfg.blocks().len(), 1);
+        assert_eq!(cfg.block(0).terminator, Terminator::Return("0".to_string()));
+    }
+}
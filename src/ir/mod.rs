@@ -0,0 +1,82 @@
+//! Mid-level intermediate representation for Sui backends
+//!
+//! The parser produces a flat list of [`Instruction`]s in which control flow
+//! is expressed with labels and jumps. This module turns such a list into a
+//! control-flow graph of basic blocks ([`Cfg`]) and recovers structured
+//! regions (`if`/`else`, loops, `break`/`continue`) from it ([`Region`]).
+//!
+//! Backends walk the region tree and emit the straight-line instructions of
+//! each basic block; when the control flow cannot be expressed with nested
+//! regions, the tree contains a single [`Region::Dispatch`] describing a
+//! state machine over the blocks instead.
+//!
+//! ```rust
+//! use sui_lang::ir::{Program, Region};
+//!
+//! let code = r#"
+//! = v0 0
+//! : 0
+//! < v1 v0 3
+//! ! v2 v1
+//! ? v2 1
+//! + v0 v0 1
+//! @ 0
+//! : 1
+//! . v0
+//! "#;
+//!
+//! let program = Program::from_source(code).unwrap();
+//! let region = program.main.structure();
+//! assert!(matches!(region, Region::Seq(ref items) if matches!(items[1], Region::Loop(_))));
+//! ```
+
+mod cfg;
+mod region;
+
+pub use cfg::{BasicBlock, BlockId, Cfg, Terminator};
+pub use region::Region;
+
+use crate::interpreter::{Function, Instruction, ParseError, Parser};
+
+/// A function lowered to a control-flow graph
+#[derive(Debug, Clone)]
+pub struct IrFunction {
+    /// Function ID
+    pub id: i64,
+    /// Number of arguments
+    pub arg_count: i64,
+    /// Function body
+    pub body: Cfg,
+}
+
+/// A whole program lowered to control-flow graphs
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// Top-level code
+    pub main: Cfg,
+    /// Function definitions in source order
+    pub functions: Vec<IrFunction>,
+}
+
+impl Program {
+    /// Parse Sui source code and lower it
+    pub fn from_source(code: &str) -> Result<Self, ParseError> {
+        let (instructions, functions) = Parser::parse(code)?;
+        Ok(Self::from_parsed(&instructions, &functions))
+    }
+
+    /// Lower already parsed instructions and functions
+    pub fn from_parsed(instructions: &[Instruction], functions: &[Function]) -> Self {
+        Self {
+            main: Cfg::build(instructions),
+            functions: functions
+                .iter()
+                .map(|f| IrFunction {
+                    id: f.id,
+                    arg_count: f.arg_count,
+                    body: Cfg::build(&f.body),
+                })
+                .collect(),
+        }
+    }
+}
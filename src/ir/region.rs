@@ -0,0 +1,363 @@
+//! Structured regions recovered from a control-flow graph
+
+use super::cfg::{BlockId, Cfg, Terminator};
+
+/// Structured control flow over the blocks of a [`Cfg`]
+///
+/// `Block` regions stand for the straight-line instructions of a block only;
+/// its terminator is expressed by the surrounding regions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    /// Instructions of a basic block
+    Block(BlockId),
+    /// Regions executed in order
+    Seq(Vec<Region>),
+    /// Conditional; when `negate` is set the branch runs if `cond` is falsy
+    If {
+        cond: String,
+        negate: bool,
+        then_region: Box<Region>,
+        else_region: Option<Box<Region>>,
+    },
+    /// Jump table; each case runs for the values listed with it, and
+    /// `default` runs for any other value
+    Switch {
+        value: String,
+        cases: Vec<(Vec<usize>, Region)>,
+        default: Box<Region>,
+    },
+    /// Infinite loop, left with `Break`, `Return` or an outer transfer
+    Loop(Box<Region>),
+    /// Leave the innermost loop
+    Break,
+    /// Restart the innermost loop
+    Continue,
+    /// Return a value from the function
+    Return(String),
+    /// State machine over all blocks, used when the graph cannot be
+    /// structured. Each block runs its instructions and then follows its
+    /// terminator; the state starts at the entry block.
+    Dispatch(Vec<BlockId>),
+}
+
+impl Region {
+    /// Whether control never falls off the end of this region
+    pub fn ends_with_transfer(&self) -> bool {
+        match self {
+            Region::Break | Region::Continue | Region::Return(_) | Region::Dispatch(_) => true,
+            Region::Seq(items) => items.last().is_some_and(|r| r.ends_with_transfer()),
+            Region::If {
+                then_region,
+                else_region: Some(else_region),
+                ..
+            } => then_region.ends_with_transfer() && else_region.ends_with_transfer(),
+            Region::Switch { cases, default, .. } => {
+                cases.iter().all(|(_, case)| case.ends_with_transfer()) && default.ends_with_transfer()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a `Break` in this region leaves a loop around it
+    pub fn breaks_out(&self) -> bool {
+        match self {
+            Region::Break => true,
+            Region::Seq(items) => items.iter().any(Region::breaks_out),
+            Region::If {
+                then_region,
+                else_region,
+                ..
+            } => then_region.breaks_out() || else_region.as_ref().is_some_and(|r| r.breaks_out()),
+            Region::Switch { cases, default, .. } => {
+                cases.iter().any(|(_, case)| case.breaks_out()) || default.breaks_out()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Innermost enclosing loop while structuring
+#[derive(Clone, Copy)]
+struct LoopCtx {
+    header: BlockId,
+    exit: BlockId,
+}
+
+impl Cfg {
+    /// Recover structured regions, falling back to [`Region::Dispatch`]
+    ///
+    /// Code with try handlers always dispatches, since unwinding may enter a
+    /// handler from anywhere inside its try region.
+    pub fn structure(&self) -> Region {
+        let n = self.blocks().len();
+        if self.has_handlers() {
+            return Region::Dispatch((0..n).collect());
+        }
+        match (Structurer { cfg: self }).seq(0, n, n, None, None) {
+            Some(region) => region,
+            None => Region::Dispatch((0..n).collect()),
+        }
+    }
+}
+
+struct Structurer<'a> {
+    cfg: &'a Cfg,
+}
+
+impl Structurer<'_> {
+    /// Structure blocks `lo..hi`, where falling off the end of the range
+    /// reaches block `follow`. `building` is the header of a loop whose body
+    /// is being structured.
+    fn seq(
+        &self,
+        lo: BlockId,
+        hi: BlockId,
+        follow: BlockId,
+        ctx: Option<LoopCtx>,
+        building: Option<BlockId>,
+    ) -> Option<Region> {
+        // Block reached when control flows on to `x` in source order
+        let natural = |x: BlockId| if x < hi { x } else { follow };
+        let mut items = Vec::new();
+        let mut i = lo;
+
+        while i < hi {
+            // A back edge to `i` from inside the range makes `i` a loop header
+            if building != Some(i) {
+                let back_edge = (i..hi)
+                    .filter(|&j| self.cfg.successors(j).contains(&i))
+                    .max();
+                if let Some(end) = back_edge {
+                    let inner = LoopCtx {
+                        header: i,
+                        exit: end + 1,
+                    };
+                    let mut body = self.seq(i, end + 1, end + 1, Some(inner), Some(i))?;
+                    if !body.ends_with_transfer() {
+                        body = push(body, Region::Break);
+                    }
+                    items.push(Region::Loop(Box::new(body)));
+                    items.extend(self.edge(end + 1, natural(end + 1), ctx)?);
+                    i = end + 1;
+                    continue;
+                }
+            }
+
+            let block = self.cfg.block(i);
+            if !block.instructions.is_empty() {
+                items.push(Region::Block(i));
+            }
+            let next = i + 1;
+
+            match &block.terminator {
+                Terminator::Return(value) => {
+                    items.push(Region::Return(value.clone()));
+                    i = next;
+                }
+                Terminator::Goto(t) => {
+                    items.extend(self.edge(*t, natural(next), ctx)?);
+                    i = next;
+                }
+                Terminator::Switch { value, targets, .. } => {
+                    let (region, end) = self.switch(value, targets, next, hi, follow, ctx)?;
+                    items.push(region);
+                    i = end;
+                }
+                Terminator::Branch { cond, target, .. } => {
+                    let t = *target;
+                    if t > next && t <= hi && natural(t) == t {
+                        // Forward jump over `next..t`: the skipped blocks form
+                        // the region that runs when the condition is false.
+                        let else_end = match &self.cfg.block(t - 1).terminator {
+                            Terminator::Goto(e) if *e > t && *e <= hi && natural(*e) == *e => Some(*e),
+                            _ => None,
+                        };
+                        if let Some(e) = else_end {
+                            let skipped = self.seq(next, t, e, ctx, None)?;
+                            let taken = self.seq(t, e, e, ctx, None)?;
+                            items.push(Region::If {
+                                cond: cond.clone(),
+                                negate: false,
+                                then_region: Box::new(taken),
+                                else_region: Some(Box::new(skipped)),
+                            });
+                            i = e;
+                        } else {
+                            let skipped = self.seq(next, t, t, ctx, None)?;
+                            items.push(Region::If {
+                                cond: cond.clone(),
+                                negate: true,
+                                then_region: Box::new(skipped),
+                                else_region: None,
+                            });
+                            i = t;
+                        }
+                    } else {
+                        let transfer = self.edge(t, natural(next), ctx)?;
+                        if !transfer.is_empty() {
+                            items.push(Region::If {
+                                cond: cond.clone(),
+                                negate: false,
+                                then_region: Box::new(Region::Seq(transfer)),
+                                else_region: None,
+                            });
+                        }
+                        i = next;
+                    }
+                }
+            }
+        }
+
+        Some(Region::Seq(items))
+    }
+
+    /// Structure a jump table that falls through to `next`, returning the
+    /// switch and the block after it
+    ///
+    /// Forward targets inside the range start the cases, laid out one after
+    /// the other with the default code first; each case ends by jumping past
+    /// the last one or by leaving otherwise. Other targets must be loop
+    /// transfers.
+    fn switch(
+        &self,
+        value: &str,
+        targets: &[BlockId],
+        next: BlockId,
+        hi: BlockId,
+        follow: BlockId,
+        ctx: Option<LoopCtx>,
+    ) -> Option<(Region, BlockId)> {
+        let mut starts: Vec<BlockId> = targets.iter().copied().filter(|&t| t > next && t < hi).collect();
+        starts.sort_unstable();
+        starts.dedup();
+
+        // The cases end where the code before a case jumps forward past all of them
+        let last = starts.last().copied().unwrap_or(next);
+        let end = starts
+            .iter()
+            .find_map(|&s| match self.cfg.block(s - 1).terminator {
+                Terminator::Goto(e) if e > last && e <= hi && (e < hi || follow == hi) => Some(e),
+                _ => None,
+            })
+            .unwrap_or(if starts.is_empty() { next } else { hi });
+        let after = if end < hi { end } else { follow };
+        let segment_end = |s: BlockId| starts.iter().copied().find(|&t| t > s).unwrap_or(end);
+
+        let default = match starts.first() {
+            Some(&first) => self.seq(next, first, after, ctx, None)?,
+            None => Region::Seq(Vec::new()),
+        };
+        let mut cases: Vec<(BlockId, Vec<usize>, Region)> = Vec::new();
+        for (k, &t) in targets.iter().enumerate() {
+            if t == next {
+                continue;
+            }
+            if let Some(case) = cases.iter_mut().find(|(target, ..)| *target == t) {
+                case.1.push(k);
+                continue;
+            }
+            let body = if starts.contains(&t) {
+                self.seq(t, segment_end(t), after, ctx, None)?
+            } else {
+                Region::Seq(self.edge(t, after, ctx)?)
+            };
+            cases.push((t, vec![k], body));
+        }
+
+        let cases = cases.into_iter().map(|(_, values, body)| (values, body)).collect();
+        let region = Region::Switch {
+            value: value.to_string(),
+            cases,
+            default: Box::new(default),
+        };
+        Some((region, end))
+    }
+
+    /// Express a jump to `target` when the code would otherwise reach `natural`
+    fn edge(&self, target: BlockId, natural: BlockId, ctx: Option<LoopCtx>) -> Option<Vec<Region>> {
+        if target == natural {
+            return Some(Vec::new());
+        }
+        match ctx {
+            Some(ctx) if target == ctx.header => Some(vec![Region::Continue]),
+            Some(ctx) if target == ctx.exit => Some(vec![Region::Break]),
+            _ => None,
+        }
+    }
+}
+
+fn push(region: Region, item: Region) -> Region {
+    match region {
+        Region::Seq(mut items) => {
+            items.push(item);
+            Region::Seq(items)
+        }
+        other => Region::Seq(vec![other, item]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Parser;
+
+    fn structure(code: &str) -> Region {
+        let (instructions, _) = Parser::parse(code).unwrap();
+        Cfg::build(&instructions).structure()
+    }
+
+    #[test]
+    fn test_if_else() {
+        let region = structure("? v0 1\n. 1\n@ 2\n: 1\n. 2\n: 2\n. 3");
+        let Region::Seq(items) = region else {
+            panic!("expected a sequence");
+        };
+        assert!(matches!(
+            &items[0],
+            Region::If { negate: false, else_region: Some(_), .. }
+        ));
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let region = structure(": 0\n< v1 v0 3\n! v2 v1\n? v2 1\n+ v0 v0 1\n@ 0\n: 1\n. v0");
+        let Region::Seq(items) = region else {
+            panic!("expected a sequence");
+        };
+        let Region::Loop(body) = &items[0] else {
+            panic!("expected a loop");
+        };
+        assert!(body.ends_with_transfer());
+    }
+
+    #[test]
+    fn test_switch() {
+        // Values 0 and 2 share a case; the default code comes first
+        let region = structure("J v0 1 2 1\n. 0\n@ 9\n: 1\n. 1\n@ 9\n: 2\n. 2\n: 9\n. 9");
+        let Region::Seq(items) = region else {
+            panic!("expected a sequence");
+        };
+        let Region::Switch { cases, default, .. } = &items[0] else {
+            panic!("expected a switch");
+        };
+        let values: Vec<&[usize]> = cases.iter().map(|(values, _)| values.as_slice()).collect();
+        assert_eq!(values, [&[0, 2][..], &[1][..]]);
+        assert_eq!(**default, Region::Seq(vec![Region::Block(1)]));
+        assert_eq!(items.len(), 2);
+
+        // Inside a loop, entries can continue or leave it
+        let region = structure(": 0\nJ v0 0 5\n. 1\n@ 0\n: 5");
+        let Region::Seq(items) = region else {
+            panic!("expected a sequence");
+        };
+        assert!(matches!(&items[0], Region::Loop(_)));
+    }
+
+    #[test]
+    fn test_irreducible_falls_back_to_dispatch() {
+        // Two entries into the same cycle
+        let region = structure("? v0 1\n: 0\n. 0\n: 1\n. 1\n@ 0");
+        assert!(matches!(region, Region::Dispatch(_)));
+    }
+}
@@ -28,7 +28,23 @@
 
 pub mod interpreter;
 pub mod transpiler;
+pub mod ir;
+pub mod grammar;
+pub mod analysis;
+pub mod formatter;
+pub mod ide;
 pub mod debugger;
+pub mod concurrency;
+pub mod playground;
+pub mod cache;
+pub mod snap;
+pub mod corpus;
+pub mod mutate;
+pub mod generator;
+pub mod spec;
+pub mod prompt;
+pub mod feedback;
+pub mod cli;
 
 #[cfg(feature = "repl")]
 pub mod repl;
@@ -36,9 +52,21 @@ pub mod repl;
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
+#[cfg(feature = "plugins")]
+pub mod plugins;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "mcp")]
+pub mod mcp;
+
+#[cfg(feature = "serve")]
+pub mod serve;
+
 // Re-exports for convenience
 pub use interpreter::{Interpreter, InterpreterError, Value};
-pub use transpiler::{Sui2Py, Sui2Js, Py2Sui, TranspileError};
+pub use transpiler::{Sui2Py, Sui2Js, Sui2Wasm, Py2Sui, TranspileError};
 pub use debugger::Debugger;
 
 /// Sui language version
@@ -47,5 +75,5 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Prelude module for common imports
 pub mod prelude {
     pub use crate::interpreter::{Interpreter, InterpreterError, Value};
-    pub use crate::transpiler::{Sui2Py, Sui2Js, Py2Sui, TranspileError};
+    pub use crate::transpiler::{Sui2Py, Sui2Js, Sui2Wasm, Py2Sui, TranspileError};
 }
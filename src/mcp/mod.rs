@@ -0,0 +1,500 @@
+//! Model Context Protocol server
+//!
+//! [`McpServer`] lets LLM agents run and check the Sui they generate. It
+//! speaks JSON-RPC 2.0 over newline-delimited stdio, as `sui-mcp` does, and
+//! offers four tools:
+//!
+//! - `run_sui`: run a program (`code`, optional `args` and `input` lines)
+//!   and return its output
+//! - `validate_sui`: parse errors and lint warnings of `code`
+//! - `transpile_sui`: `code` in `target` (`python`, `javascript`,
+//!   `typescript` or `wat`)
+//! - `debug_step`: start a debugging session with `code`, or continue
+//!   `session`, stepping `steps` instructions and returning the variables
+//!
+//! Limits are enforced by the server: a call may ask for fewer steps or a
+//! shallower stack than [`Limits`] allow, never more, and may cap single
+//! functions, such as those of an untrusted module, lower still. Programs
+//! get a virtual clock, no environment variables, no commands, no
+//! channels, and input only from `input`, since stdin carries the protocol.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use serde_json::{json, Map, Value as Json};
+
+use crate::debugger::{DebugEvent, DebugState, Debugger};
+use crate::ide::{lint_diagnostics, parse_diagnostics};
+use crate::interpreter::{Clock, FunctionLimits, Interpreter, InterpreterError, Value};
+use crate::transpiler::{Sui2Js, Sui2Py, Sui2Wasm};
+
+/// Protocol version announced when the client asks for one this server
+/// does not know
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Protocol versions this server can speak
+const PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// Debugging sessions kept at once; starting another drops the oldest
+const MAX_SESSIONS: usize = 16;
+
+/// Most a tool call may use
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Instructions per `run_sui`, and steps per `debug_step`
+    pub max_steps: u64,
+    /// Call stack depth
+    pub max_depth: usize,
+    /// Wall-clock time per `run_sui`
+    pub timeout: Duration,
+    /// Limits of single functions per `run_sui`, by ID
+    pub functions: BTreeMap<i64, FunctionLimits>,
+}
+
+impl Limits {
+    /// These limits lowered to the `max_steps`, `max_depth` and
+    /// `timeout_ms` a caller asked for, and to its `functions` limits, as
+    /// in `{"3": {"max_steps": 100, "max_allocated": 1000}}`; asking for
+    /// more has no effect
+    pub fn narrow(&self, requested: &Json) -> Limits {
+        let field = |key: &str| requested.get(key).and_then(Json::as_u64);
+        let mut functions = self.functions.clone();
+        for (id, limits) in requested.get("functions").and_then(Json::as_object).into_iter().flatten() {
+            let Ok(id) = id.parse() else {
+                continue;
+            };
+            let field = |key: &str| limits.get(key).and_then(Json::as_u64);
+            let requested = FunctionLimits { max_steps: field("max_steps"), max_allocated: field("max_allocated") };
+            let limits = functions.entry(id).or_default();
+            *limits = limits.min(requested);
+        }
+        Limits {
+            max_steps: field("max_steps").map_or(self.max_steps, |n| n.min(self.max_steps)),
+            max_depth: field("max_depth").map_or(self.max_depth, |d| (d as usize).min(self.max_depth)),
+            timeout: field("timeout_ms").map_or(self.timeout, |ms| Duration::from_millis(ms).min(self.timeout)),
+            functions,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_steps: 1_000_000, max_depth: 256, timeout: Duration::from_secs(5), functions: BTreeMap::new() }
+    }
+}
+
+/// MCP server for Sui tools
+pub struct McpServer {
+    limits: Limits,
+    sessions: BTreeMap<u64, Debugger>,
+    next_session: u64,
+}
+
+impl McpServer {
+    pub fn new(limits: Limits) -> Self {
+        McpServer { limits, sessions: BTreeMap::new(), next_session: 1 }
+    }
+
+    /// Answer requests from `input`, one JSON-RPC message per line, until
+    /// it ends
+    pub fn serve(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = handle_caught(&line, || self.handle(&line)) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Response to one message, or `None` for a notification
+    pub fn handle(&mut self, message: &str) -> Option<Json> {
+        let request: Map<String, Json> = match serde_json::from_str(message) {
+            Ok(Json::Object(request)) => request,
+            Ok(_) => return Some(error_response(Json::Null, -32600, "invalid request")),
+            Err(_) => return Some(error_response(Json::Null, -32700, "parse error")),
+        };
+        // Requests without an id are notifications, which get no response
+        let id = request.get("id").cloned()?;
+        let method = request.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": tools()})),
+            "tools/call" => self.call_tool(&params),
+            _ => Err((-32601, format!("method not found: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn initialize(&self, params: &Json) -> Json {
+        let requested = params.get("protocolVersion").and_then(Json::as_str);
+        let version = requested.filter(|v| PROTOCOL_VERSIONS.contains(v)).unwrap_or(PROTOCOL_VERSION);
+        json!({
+            "protocolVersion": version,
+            "capabilities": {"tools": {}},
+            "serverInfo": {"name": "sui-mcp", "version": crate::VERSION},
+        })
+    }
+
+    fn call_tool(&mut self, params: &Json) -> Result<Json, (i64, String)> {
+        let name = params.get("name").and_then(Json::as_str).ok_or((-32602, "missing tool name".to_string()))?;
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        let outcome = match name {
+            "run_sui" => run_sui(&args, &self.limits.narrow(&args)),
+            "validate_sui" => validate_sui(&args),
+            "transpile_sui" => transpile_sui(&args),
+            "debug_step" => self.debug_step(&args),
+            _ => return Err((-32602, format!("unknown tool: {}", name))),
+        };
+        // Failures of the program are results the agent should see, not
+        // protocol errors
+        let (body, is_error) = match outcome {
+            Ok(body) => (body, false),
+            Err(body) => (body, true),
+        };
+        Ok(json!({"content": [{"type": "text", "text": body.to_string()}], "isError": is_error}))
+    }
+
+    fn debug_step(&mut self, args: &Json) -> Result<Json, Json> {
+        let id = if let Some(code) = args.get("code").and_then(Json::as_str) {
+            let mut debugger = Debugger::new();
+            debugger.load(code).map_err(|e| json!({"error": e.to_string()}))?;
+            debugger.set_input_queue(input_argument(args));
+            if self.sessions.len() >= MAX_SESSIONS {
+                self.sessions.pop_first();
+            }
+            let id = self.next_session;
+            self.next_session += 1;
+            self.sessions.insert(id, debugger);
+            id
+        } else {
+            let id = args.get("session").and_then(Json::as_u64).ok_or_else(|| json!({"error": "missing code or session"}))?;
+            let debugger = self.sessions.get_mut(&id).ok_or_else(|| json!({"error": format!("no session {}", id)}))?;
+            for line in input_argument(args) {
+                debugger.feed_input(&line);
+            }
+            id
+        };
+
+        let steps = args.get("steps").and_then(Json::as_u64).unwrap_or(1).clamp(1, self.limits.max_steps.max(1));
+        let debugger = self.sessions.get_mut(&id).expect("session was just found or inserted");
+        let mut event = DebugEvent::Step;
+        for _ in 0..steps {
+            event = debugger.step();
+            if !matches!(event, DebugEvent::Step) {
+                break;
+            }
+        }
+
+        let mut body = json!({
+            "session": id,
+            "line": debugger.current_line(),
+            "source": debugger.source_at(debugger.current_line()).unwrap_or(""),
+            "locals": variables('v', debugger.locals().iter()),
+            "globals": variables('g', debugger.globals().iter()),
+            "output": debugger.output(),
+        });
+        let (event_name, failed) = match &event {
+            DebugEvent::Finished => ("finished", false),
+            DebugEvent::InputNeeded => ("input", false),
+            DebugEvent::Error(message) => {
+                body["error"] = json!(message);
+                ("error", true)
+            }
+            DebugEvent::Step | DebugEvent::Breakpoint(_) | DebugEvent::Output(_) | DebugEvent::Ffi { .. } => ("step", false),
+        };
+        body["event"] = json!(event_name);
+        // A finished session has nothing left to step
+        if debugger.state() == DebugState::Finished {
+            self.sessions.remove(&id);
+        }
+        if failed { Err(body) } else { Ok(body) }
+    }
+}
+
+impl Default for McpServer {
+    fn default() -> Self {
+        Self::new(Limits::default())
+    }
+}
+
+/// Run `code` with `args` and `input` lines within `limits`
+///
+/// Failures carry the error and the output printed before it.
+pub(crate) fn run_sui(args: &Json, limits: &Limits) -> Result<Json, Json> {
+    let code = code_argument(args)?;
+    match run_sandboxed(code, &argv_argument(args), input_argument(args), limits) {
+        Ok(output) => Ok(json!({"output": output})),
+        Err((e, output)) => Err(json!({"error": e.to_string(), "output": output})),
+    }
+}
+
+/// Run `code` with a virtual clock, no environment, commands or channels,
+/// and `input` as its only input, within `limits`
+pub(crate) fn run_sandboxed(
+    code: &str,
+    argv: &[String],
+    input: Vec<String>,
+    limits: &Limits,
+) -> Result<Vec<String>, (InterpreterError, Vec<String>)> {
+    let mut interp = sandbox(input, limits);
+    interp.run(code, argv).map_err(|e| (e, interp.get_output().to_vec()))
+}
+
+/// Interpreter for [`run_sandboxed`], counting what each function uses
+pub(crate) fn sandbox(input: Vec<String>, limits: &Limits) -> Interpreter {
+    let mut interp = Interpreter::new();
+    interp.set_max_steps(Some(limits.max_steps));
+    interp.set_accounting(true);
+    interp.allow_channels(false);
+    for (&id, &function) in &limits.functions {
+        interp.set_function_limits(id, function);
+    }
+    interp.set_max_stack_depth(limits.max_depth);
+    interp.set_clock(Clock::Virtual { epoch: 0.0 });
+    interp.set_input_queue(input);
+    let timeout = limits.timeout;
+    interp.set_step_hook(1000, move |ctx| {
+        if ctx.elapsed > timeout { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    });
+    interp
+}
+
+/// Parse errors of `code`, or its lint warnings if it parses
+pub(crate) fn validate_sui(args: &Json) -> Result<Json, Json> {
+    let code = code_argument(args)?;
+    let mut diagnostics = parse_diagnostics(code);
+    let valid = diagnostics.is_empty();
+    if valid {
+        diagnostics = lint_diagnostics(code);
+    }
+    Ok(json!({"valid": valid, "diagnostics": diagnostics}))
+}
+
+/// `code` transpiled to `target`
+pub(crate) fn transpile_sui(args: &Json) -> Result<Json, Json> {
+    let code = code_argument(args)?;
+    let target = args.get("target").and_then(Json::as_str).unwrap_or("python");
+    let result = match target {
+        "python" => Sui2Py::new().transpile_to_python(code),
+        "javascript" => Sui2Js::new().transpile_to_js(code),
+        "typescript" => {
+            let mut transpiler = Sui2Js::new();
+            transpiler.set_typescript(true);
+            transpiler.transpile_to_js(code)
+        }
+        "wat" => Sui2Wasm::new().transpile_to_wat(code),
+        _ => return Err(json!({"error": format!("unknown target: {}", target)})),
+    };
+    match result {
+        Ok(text) => Ok(json!({"target": target, "code": text})),
+        Err(e) => Err(json!({"error": e.to_string()})),
+    }
+}
+
+pub(crate) fn code_argument(args: &Json) -> Result<&str, Json> {
+    args.get("code").and_then(Json::as_str).ok_or_else(|| json!({"error": "missing code"}))
+}
+
+/// Program arguments, with numbers written as text
+pub(crate) fn argv_argument(args: &Json) -> Vec<String> {
+    let values = args.get("args").and_then(Json::as_array);
+    values.map(|a| a.iter().map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string)).collect()).unwrap_or_default()
+}
+
+pub(crate) fn input_argument(args: &Json) -> Vec<String> {
+    let lines = args.get("input").and_then(Json::as_array);
+    lines.map(|a| a.iter().filter_map(Json::as_str).map(str::to_string).collect()).unwrap_or_default()
+}
+
+/// Variables as an object from name to printed value
+fn variables<'a>(prefix: char, vars: impl Iterator<Item = (&'a i64, &'a Value)>) -> Json {
+    let vars: BTreeMap<i64, String> = vars.map(|(idx, v)| (*idx, v.to_string())).collect();
+    Json::Object(vars.into_iter().map(|(idx, v)| (format!("{}{}", prefix, idx), json!(v))).collect())
+}
+
+/// `handle` answering `message`, or an internal error if it panics, so one
+/// bad call does not end the session
+fn handle_caught(message: &str, handle: impl FnOnce() -> Option<Json>) -> Option<Json> {
+    panic::catch_unwind(AssertUnwindSafe(handle)).unwrap_or_else(|_| {
+        let request: Json = serde_json::from_str(message).unwrap_or_default();
+        let id = request.get("id").cloned()?;
+        Some(error_response(id, -32603, "internal error"))
+    })
+}
+
+fn error_response(id: Json, code: i64, message: &str) -> Json {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// Tool descriptions for `tools/list`
+fn tools() -> Json {
+    let code = json!({"type": "string", "description": "Sui source code"});
+    let input = json!({"type": "array", "items": {"type": "string"}, "description": "Lines for `,` to read"});
+    json!([
+        {
+            "name": "run_sui",
+            "description": "Run a Sui program and return its output lines, or the error and the output before it",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "code": code,
+                    "args": {"type": "array", "items": {"type": ["string", "number"]}, "description": "Program arguments (g100 is the count, g101 onward the values)"},
+                    "input": input,
+                    "max_steps": {"type": "integer", "description": "Instruction limit, up to the server's"},
+                    "max_depth": {"type": "integer", "description": "Stack depth limit, up to the server's"},
+                    "timeout_ms": {"type": "integer", "description": "Time limit in milliseconds, up to the server's"},
+                    "functions": {"type": "object", "description": "Limits of single functions by ID, as {\"3\": {\"max_steps\": 100, \"max_allocated\": 1000}}"},
+                },
+                "required": ["code"],
+            },
+        },
+        {
+            "name": "validate_sui",
+            "description": "Check a Sui program without running it: parse errors, or lint warnings if it parses",
+            "inputSchema": {"type": "object", "properties": {"code": code}, "required": ["code"]},
+        },
+        {
+            "name": "transpile_sui",
+            "description": "Transpile a Sui program to Python, JavaScript, TypeScript or WebAssembly text",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "code": code,
+                    "target": {"type": "string", "enum": ["python", "javascript", "typescript", "wat"]},
+                },
+                "required": ["code"],
+            },
+        },
+        {
+            "name": "debug_step",
+            "description": "Step through a Sui program: pass `code` to start a session, or `session` to continue one; returns the line reached and the variables",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "code": code,
+                    "session": {"type": "integer", "description": "Session returned by an earlier call"},
+                    "steps": {"type": "integer", "description": "Instructions to run (default 1)"},
+                    "input": input,
+                },
+            },
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(server: &mut McpServer, tool: &str, arguments: Json) -> (Json, bool) {
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": tool, "arguments": arguments}});
+        let response = server.handle(&request.to_string()).unwrap();
+        let result = &response["result"];
+        let text = result["content"][0]["text"].as_str().unwrap();
+        (serde_json::from_str(text).unwrap(), result["isError"].as_bool().unwrap())
+    }
+
+    #[test]
+    fn test_protocol() {
+        let mut server = McpServer::default();
+        let init = server.handle(r#"{"jsonrpc":"2.0","id":0,"method":"initialize","params":{"protocolVersion":"2025-03-26"}}"#);
+        assert_eq!(init.unwrap()["result"]["protocolVersion"], "2025-03-26");
+        assert!(server.handle(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#).is_none());
+
+        let list = server.handle(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#).unwrap();
+        let names: Vec<&str> = list["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["run_sui", "validate_sui", "transpile_sui", "debug_step"]);
+
+        assert_eq!(server.handle("{").unwrap()["error"]["code"], -32700);
+        assert_eq!(server.handle(r#"{"jsonrpc":"2.0","id":2,"method":"nope"}"#).unwrap()["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn test_handle_caught() {
+        let response = handle_caught(r#"{"jsonrpc":"2.0","id":7,"method":"tools/call"}"#, || panic!("bug")).unwrap();
+        assert_eq!((&response["id"], &response["error"]["code"]), (&json!(7), &json!(-32603)));
+        // Notifications still get no response
+        assert!(handle_caught(r#"{"jsonrpc":"2.0","method":"x"}"#, || panic!("bug")).is_none());
+    }
+
+    #[test]
+    fn test_run_sui() {
+        let mut server = McpServer::default();
+        let (body, failed) = call(&mut server, "run_sui", json!({"code": "+ v0 g101 1\n. v0\n, v1\n. v1", "args": [41], "input": ["hi"]}));
+        assert!(!failed);
+        assert_eq!(body["output"], json!(["42", "hi"]));
+
+        let (body, failed) = call(&mut server, "run_sui", json!({"code": ". 1\n: 0\n@ 0", "max_steps": 100}));
+        assert!(failed);
+        assert!(body["error"].as_str().unwrap().contains("Step limit"));
+        assert_eq!(body["output"], json!(["1"]));
+
+        // Calls cannot raise the server's limits
+        let mut server = McpServer::new(Limits { max_steps: 50, ..Limits::default() });
+        let (body, failed) = call(&mut server, "run_sui", json!({"code": ": 0\n@ 0", "max_steps": 1_000_000}));
+        assert!(failed);
+        assert!(body["error"].as_str().unwrap().contains("50"));
+    }
+
+    #[test]
+    fn test_run_sui_sandbox() {
+        let mut server = McpServer::default();
+        let (body, failed) = call(&mut server, "run_sui", json!({"code": "R v0 \"env.get\" \"HOME\"\n. v0"}));
+        assert!(failed);
+        assert!(body["error"].as_str().unwrap().contains("Permission denied"));
+
+        // A program waiting on a channel would hold the server forever
+        let (body, failed) = call(&mut server, "run_sui", json!({"code": "R v0 \"chan.recv\" 0", "timeout_ms": 100}));
+        assert!(failed);
+        assert_eq!(body["error"], "Permission denied at line 1: chan.recv needs channels");
+        let (body, _) = call(&mut server, "run_sui", json!({"code": ". 1"}));
+        assert_eq!(body["output"], json!(["1"]));
+    }
+
+    #[test]
+    fn test_validate_and_transpile() {
+        let mut server = McpServer::default();
+        let (body, _) = call(&mut server, "validate_sui", json!({"code": "= v0 1\n. v0"}));
+        assert_eq!(body["valid"], true);
+        let (body, _) = call(&mut server, "validate_sui", json!({"code": "= v0\n. v0"}));
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["diagnostics"][0]["line"], 1);
+        assert_eq!(body["diagnostics"][0]["severity"], "error");
+
+        let (body, failed) = call(&mut server, "transpile_sui", json!({"code": "= v0 1\n. v0", "target": "javascript"}));
+        assert!(!failed);
+        assert!(body["code"].as_str().unwrap().contains("console.log"));
+        let (_, failed) = call(&mut server, "transpile_sui", json!({"code": ". 1", "target": "cobol"}));
+        assert!(failed);
+    }
+
+    #[test]
+    fn test_debug_step() {
+        let mut server = McpServer::default();
+        let (body, _) = call(&mut server, "debug_step", json!({"code": "= v0 1\n+ v0 v0 1\n. v0"}));
+        assert_eq!(body["event"], "step");
+        assert_eq!(body["locals"]["v0"], "1");
+        let session = body["session"].clone();
+
+        let (body, _) = call(&mut server, "debug_step", json!({"session": session, "steps": 10}));
+        assert_eq!(body["event"], "finished");
+        assert_eq!(body["output"], json!(["2"]));
+
+        // Finished sessions are dropped
+        let (_, failed) = call(&mut server, "debug_step", json!({"session": session}));
+        assert!(failed);
+    }
+}
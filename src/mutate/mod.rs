@@ -0,0 +1,324 @@
+//! Mutation testing
+//!
+//! [`mutants`] makes small systematic changes to a program, one per
+//! mutant: swapping an operator (`+` for `-`, `<` for `<=` or `>`, `&`
+//! for `|`), moving an integer constant by one, and pointing a jump at
+//! another label of the same function. [`test_mutants`] runs a test suite
+//! of [`CorpusProgram`] cases against each mutant. A mutant is killed when
+//! some case fails on it; a surviving mutant is a change to the program
+//! that the tests cannot tell apart from the original, so either the tests
+//! miss a behaviour or the mutant is equivalent to the original.
+//!
+//! `sui mutate program.sui` uses the program's `.snap` file as the test
+//! suite, or the cases of a corpus manifest naming it with `--manifest`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::corpus::{run_case, CorpusProgram};
+use crate::interpreter::{Interpreter, Lexer};
+
+/// What a mutant changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    /// An instruction's operator
+    Operator,
+    /// An integer literal operand
+    Constant,
+    /// The label a jump goes to
+    JumpTarget,
+}
+
+impl fmt::Display for MutationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MutationKind::Operator => write!(f, "operator"),
+            MutationKind::Constant => write!(f, "constant"),
+            MutationKind::JumpTarget => write!(f, "jump target"),
+        }
+    }
+}
+
+/// A program with one change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mutant {
+    /// Changed line, from 1
+    pub line: usize,
+    pub kind: MutationKind,
+    /// Token replaced
+    pub original: String,
+    /// Token put in its place
+    pub replacement: String,
+    /// Source of the mutated program
+    pub code: String,
+}
+
+impl fmt::Display for Mutant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {} {} → {}", self.line, self.kind, self.original, self.replacement)
+    }
+}
+
+/// Operators each operator is swapped for
+fn operator_swaps(op: &str) -> &'static [&'static str] {
+    match op {
+        "+" => &["-"],
+        "-" => &["+"],
+        "*" => &["/"],
+        "/" => &["*"],
+        "%" => &["/"],
+        "<" => &["<=", ">"],
+        "<=" => &["<", ">="],
+        ">" => &[">=", "<"],
+        ">=" => &[">", "<="],
+        "~" => &["<", ">"],
+        "&" => &["|"],
+        "|" => &["&"],
+        _ => &[],
+    }
+}
+
+/// Positions of the operands an instruction reads as values
+fn value_operands(op: &str, count: usize) -> Range<usize> {
+    let range = match op {
+        "=" => 2..3,
+        "+" | "-" | "*" | "/" | "%" | "<" | ">" | "<=" | ">=" | "~" | "&" | "|" => 2..4,
+        "." | "^" => 1..2,
+        "$" => 3..count,
+        _ => 0..0,
+    };
+    range.start.min(count)..range.end.min(count)
+}
+
+/// Positions of the label operands of a jump
+fn label_operands(op: &str, count: usize) -> Range<usize> {
+    let range = match op {
+        "@" => 1..2,
+        "?" => 2..3,
+        "J" => 2..count,
+        _ => 0..0,
+    };
+    range.start.min(count)..range.end.min(count)
+}
+
+/// Every mutant of `code`, in source order
+pub fn mutants(code: &str) -> Vec<Mutant> {
+    let lines: Vec<&str> = code.lines().collect();
+
+    // Labels defined in each function body, and at top level (scope 0)
+    let mut scopes = Vec::with_capacity(lines.len());
+    let mut labels: HashMap<usize, BTreeSet<i64>> = HashMap::new();
+    let (mut scope, mut functions) = (0, 0);
+    for line in &lines {
+        let tokens = Lexer::tokenize_line(line);
+        match tokens.first().map(String::as_str) {
+            Some("#") => {
+                functions += 1;
+                scope = functions;
+            }
+            Some("}") => scope = 0,
+            Some(":") => {
+                if let Some(id) = tokens.get(1).and_then(|id| id.parse().ok()) {
+                    labels.entry(scope).or_default().insert(id);
+                }
+            }
+            _ => {}
+        }
+        scopes.push(scope);
+    }
+
+    let mut mutants = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let spans = Lexer::token_spans(line);
+        let Some(&(_, op)) = spans.first() else {
+            continue;
+        };
+        let mut mutate = |kind, (start, token): (usize, &str), replacement: String| {
+            let mut changed = lines.clone();
+            let new_line = format!("{}{}{}", &line[..start], replacement, &line[start + token.len()..]);
+            changed[i] = &new_line;
+            mutants.push(Mutant {
+                line: i + 1,
+                kind,
+                original: token.to_string(),
+                replacement,
+                code: changed.join("\n"),
+            });
+        };
+
+        for swap in operator_swaps(op) {
+            mutate(MutationKind::Operator, spans[0], swap.to_string());
+        }
+        for &(start, token) in &spans[value_operands(op, spans.len())] {
+            if let Ok(n) = token.parse::<i64>() {
+                for tweaked in [n.checked_add(1), n.checked_sub(1)].into_iter().flatten() {
+                    mutate(MutationKind::Constant, (start, token), tweaked.to_string());
+                }
+            }
+        }
+        let defined = labels.get(&scopes[i]);
+        for &(start, token) in &spans[label_operands(op, spans.len())] {
+            let Ok(target) = token.parse::<i64>() else {
+                continue;
+            };
+            for other in defined.into_iter().flatten().filter(|&&label| label != target) {
+                mutate(MutationKind::JumpTarget, (start, token), other.to_string());
+            }
+        }
+    }
+    mutants
+}
+
+/// Outcome of testing one mutant
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutantResult {
+    pub mutant: Mutant,
+    /// Name of the first case that failed on the mutant, or `None` if it
+    /// survived
+    pub killed_by: Option<String>,
+}
+
+/// Outcome of testing every mutant of a program
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MutationReport {
+    pub results: Vec<MutantResult>,
+}
+
+impl MutationReport {
+    /// Mutants no case failed on
+    pub fn survivors(&self) -> impl Iterator<Item = &Mutant> {
+        self.results.iter().filter(|r| r.killed_by.is_none()).map(|r| &r.mutant)
+    }
+
+    /// Mutants some case failed on
+    pub fn killed(&self) -> usize {
+        self.results.len() - self.survivors().count()
+    }
+
+    /// Percentage of mutants killed; 100 when there are none
+    pub fn score(&self) -> f64 {
+        if self.results.is_empty() {
+            return 100.0;
+        }
+        self.killed() as f64 * 100.0 / self.results.len() as f64
+    }
+}
+
+impl fmt::Display for MutationReport {
+    /// The surviving mutants and a summary
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for mutant in self.survivors() {
+            writeln!(f, "survived {}", mutant)?;
+        }
+        write!(
+            f,
+            "{} mutants: {} killed, {} survived (score {:.1}%)",
+            self.results.len(),
+            self.killed(),
+            self.results.len() - self.killed(),
+            self.score()
+        )
+    }
+}
+
+/// Call depth mutants may reach, so that runaway recursion stops long
+/// before the native stack does
+const MAX_DEPTH: usize = 256;
+
+/// Run `cases` against every mutant of the program at `program`
+///
+/// A case may run a mutant for ten times the instructions it took on the
+/// original program, plus a thousand, and at most `max_steps` (or its own
+/// limit), so mutants that loop forever are killed quickly. Fails if the
+/// program cannot be read or a case fails on the original program.
+pub fn test_mutants(program: &Path, cases: &[CorpusProgram], max_steps: u64) -> Result<MutationReport, String> {
+    let code = fs::read_to_string(program).map_err(|e| format!("{}: {}", program.display(), e))?;
+    let path = program.canonicalize().map_err(|e| format!("{}: {}", program.display(), e))?;
+    let interpreter = |max_steps| {
+        let mut interp = Interpreter::new();
+        interp.set_max_steps(Some(max_steps));
+        interp.set_max_stack_depth(MAX_DEPTH);
+        interp
+    };
+
+    let mut limits = Vec::with_capacity(cases.len());
+    for case in cases {
+        let max_steps = case.max_steps.unwrap_or(max_steps);
+        let result = run_case(interpreter(max_steps), case, &path, None);
+        if let Some(failure) = result.failure {
+            return Err(format!("{} fails without mutations: {}", case.display_name(), failure));
+        }
+        limits.push(result.steps.saturating_mul(10).saturating_add(1000).min(max_steps));
+    }
+
+    let results = mutants(&code)
+        .into_iter()
+        .map(|mutant| {
+            let killed_by = cases
+                .iter()
+                .zip(&limits)
+                .find(|(case, &limit)| run_case(interpreter(limit), case, &path, Some(&mutant.code)).failure.is_some())
+                .map(|(case, _)| case.display_name());
+            MutantResult { mutant, killed_by }
+        })
+        .collect();
+    Ok(MutationReport { results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let code = "; max\n# 0 2 {\n> v0 a0 a1\n? v0 1\n^ a1\n: 1\n^ a0\n}\n: 0\n$ g0 0 3 4\n. g0\n@ 0";
+        let found: Vec<String> = mutants(code).iter().map(|m| m.to_string()).collect();
+        assert_eq!(
+            found,
+            [
+                "line 3: operator > → >=",
+                "line 3: operator > → <",
+                "line 10: constant 3 → 4",
+                "line 10: constant 3 → 2",
+                "line 10: constant 4 → 5",
+                "line 10: constant 4 → 3",
+            ]
+        );
+        let mutant = &mutants(code)[0];
+        assert_eq!(mutant.code.lines().nth(2), Some(">= v0 a0 a1"));
+        assert_eq!(mutant.code.lines().count(), code.lines().count());
+
+        // Jumps only move to other labels of their own function
+        let found: Vec<String> = mutants(": 0\n: 1\n? v0 0\n# 0 0 {\n: 5\n@ 5\n}").iter().map(|m| m.to_string()).collect();
+        assert_eq!(found, ["line 3: jump target 0 → 1"]);
+    }
+
+    #[test]
+    fn test_kill() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("big.sui");
+        fs::write(&program, "> v0 g101 10\n. v0").unwrap();
+        let case = |arg: &str, expected: &str| CorpusProgram {
+            name: Some(format!("big {}", arg)),
+            args: vec![arg.to_string()],
+            expected: Some(vec![expected.to_string()]),
+            ..Default::default()
+        };
+
+        let report = test_mutants(&program, &[case("20", "1")], 1000).unwrap();
+        let survivors: Vec<String> = report.survivors().map(|m| m.to_string()).collect();
+        assert_eq!(survivors, ["line 1: operator > → >=", "line 1: constant 10 → 11", "line 1: constant 10 → 9"]);
+        assert_eq!(report.results[1].killed_by.as_deref(), Some("big 20"));
+        assert!(report.to_string().ends_with("4 mutants: 1 killed, 3 survived (score 25.0%)"));
+
+        // Testing the boundary from both sides kills them all
+        let cases = [case("20", "1"), case("10", "0"), case("11", "1")];
+        assert_eq!(test_mutants(&program, &cases, 1000).unwrap().score(), 100.0);
+
+        let err = test_mutants(&program, &[case("5", "1")], 1000).unwrap_err();
+        assert!(err.starts_with("big 5 fails without mutations"));
+    }
+}
@@ -0,0 +1,94 @@
+//! Static playground site for hosting Sui in a browser
+//!
+//! [`build`] writes the playground page, with its editor and the buttons to
+//! run, step through and transpile code already wired up, next to a copy of
+//! the `wasm-pack` output it loads. The result is plain files that any
+//! static host can serve, so docs and courses can embed a playground
+//! without writing JavaScript.
+//!
+//! ```bash
+//! wasm-pack build --target web --features wasm
+//! sui playground build --out site
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The playground page; it loads `pkg/sui_lang.js`
+pub const INDEX_HTML: &str = include_str!("../../playground/index.html");
+
+/// Files of the `wasm-pack` output the page cannot run without
+const REQUIRED: [&str; 2] = ["sui_lang.js", "sui_lang_bg.wasm"];
+
+/// Playground build errors
+#[derive(Debug, Error)]
+pub enum PlaygroundError {
+    #[error("{0} is not wasm-pack output; build it with `wasm-pack build --target web --features wasm`")]
+    MissingPackage(PathBuf),
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Write the playground to `out_dir`, copying the `wasm-pack` output in
+/// `pkg_dir` to `out_dir/pkg`, and return the files written
+pub fn build(out_dir: &Path, pkg_dir: &Path) -> Result<Vec<PathBuf>, PlaygroundError> {
+    if REQUIRED.iter().any(|file| !pkg_dir.join(file).is_file()) {
+        return Err(PlaygroundError::MissingPackage(pkg_dir.to_path_buf()));
+    }
+
+    let target_pkg = out_dir.join("pkg");
+    fs::create_dir_all(&target_pkg)?;
+    let index = out_dir.join("index.html");
+    fs::write(&index, INDEX_HTML)?;
+    let mut written = vec![index];
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(pkg_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        // package.json and .gitignore only matter for publishing to npm
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "js" || ext == "wasm" || ext == "ts"))
+        .collect();
+    entries.sort();
+    for source in entries {
+        if let Some(name) = source.file_name() {
+            let target = target_pkg.join(name);
+            fs::copy(&source, &target)?;
+            written.push(target);
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir(&pkg).unwrap();
+        for file in ["sui_lang.js", "sui_lang_bg.wasm", "sui_lang.d.ts", "package.json", ".gitignore"] {
+            fs::write(pkg.join(file), file).unwrap();
+        }
+
+        let site = dir.path().join("site");
+        let written = build(&site, &pkg).unwrap();
+        let names: Vec<String> = written.iter().map(|p| p.strip_prefix(&site).unwrap().display().to_string()).collect();
+        assert_eq!(names, ["index.html", "pkg/sui_lang.d.ts", "pkg/sui_lang.js", "pkg/sui_lang_bg.wasm"]);
+        let page = fs::read_to_string(site.join("index.html")).unwrap();
+        assert!(page.contains("from './pkg/sui_lang.js'"));
+        assert!(page.contains("run_with_fuel"));
+    }
+
+    #[test]
+    fn test_missing_package() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("sui_lang.js"), "").unwrap();
+        let err = build(&dir.path().join("site"), dir.path()).unwrap_err();
+        assert!(matches!(err, PlaygroundError::MissingPackage(_)));
+        assert!(!dir.path().join("site").exists());
+    }
+}
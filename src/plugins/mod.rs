@@ -0,0 +1,356 @@
+//! Native builtin packs loaded from shared libraries
+//!
+//! A plugin is a shared library (`.so`, `.dylib`) exporting one function
+//! with a stable C ABI. When loaded, the host calls it with a callback the
+//! plugin uses to register its builtins; [`Plugin::install`] then makes them
+//! callable from `R` like the standard ones. Values cross the boundary as
+//! JSON text, the same encoding as `json.encode`, so a plugin needs no
+//! knowledge of the interpreter's types.
+//!
+//! ```c
+//! typedef void (*sui_write_fn)(void *out, const char *data, size_t len);
+//! typedef int (*sui_builtin_fn)(void *user_data, const char *args, size_t args_len,
+//!                               sui_write_fn write, void *out);
+//! typedef void (*sui_register_fn)(void *registry, const char *name,
+//!                                 sui_builtin_fn func, void *user_data);
+//!
+//! /* Exported by the plugin; returns 0 on success */
+//! int sui_plugin_register(uint32_t abi_version, sui_register_fn register_builtin, void *registry);
+//! ```
+//!
+//! A builtin gets its arguments as a NUL-terminated JSON array and returns
+//! 0 after writing its result as JSON, or any other status after writing an
+//! error message. `write` may be called several times; the pieces are
+//! joined. A plugin should refuse an `abi_version` it was not built for.
+//!
+//! Only Unix-like systems are supported, since loading uses `dlopen`.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::interpreter::json;
+use crate::{Interpreter, Value};
+
+/// Version of the plugin ABI, passed to `sui_plugin_register`
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Name of the registration function a plugin exports
+pub const REGISTER_SYMBOL: &str = "sui_plugin_register";
+
+/// Environment variable listing plugin directories, separated like `PATH`
+pub const PLUGIN_PATH_VAR: &str = "SUI_PLUGIN_PATH";
+
+/// Appends `len` bytes at `data` to the builtin's result
+pub type WriteFn = unsafe extern "C" fn(out: *mut c_void, data: *const c_char, len: usize);
+
+/// Builtin of a plugin; returns 0 on success
+pub type BuiltinFn = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    args: *const c_char,
+    args_len: usize,
+    write: WriteFn,
+    out: *mut c_void,
+) -> c_int;
+
+/// Callback a plugin calls once per builtin it provides
+pub type RegisterBuiltinFn =
+    unsafe extern "C" fn(registry: *mut c_void, name: *const c_char, func: BuiltinFn, user_data: *mut c_void);
+
+/// `sui_plugin_register`, exported by every plugin; returns 0 on success
+pub type RegisterFn = unsafe extern "C" fn(abi_version: u32, register: RegisterBuiltinFn, registry: *mut c_void) -> c_int;
+
+/// Plugin loading errors
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("Cannot load plugin {path}: {message}")]
+    Load { path: PathBuf, message: String },
+
+    #[error("Plugin {path} does not export {REGISTER_SYMBOL}")]
+    MissingSymbol { path: PathBuf },
+
+    #[error("Plugin {path} refused to register (status {status})")]
+    Refused { path: PathBuf, status: i32 },
+
+    #[error("Plugin not found: {0}")]
+    NotFound(String),
+}
+
+/// Handle of a loaded shared library, closed when the last user drops it
+struct Library(*mut c_void);
+
+// dlopen handles may be used and closed from any thread
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    fn open(path: &Path) -> Result<Self, PluginError> {
+        let load_error = |message: String| PluginError::Load { path: path.to_path_buf(), message };
+        let name = CString::new(path.as_os_str().as_bytes()).map_err(|e| load_error(e.to_string()))?;
+        // SAFETY: `name` is NUL-terminated; running the library's
+        // initializers is what loading a plugin means
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(load_error(last_dl_error()));
+        }
+        Ok(Library(handle))
+    }
+
+    fn symbol(&self, name: &str) -> Option<*mut c_void> {
+        let name = CString::new(name).ok()?;
+        // SAFETY: the handle is open until `self` is dropped
+        let symbol = unsafe { libc::dlsym(self.0, name.as_ptr()) };
+        (!symbol.is_null()).then_some(symbol)
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from dlopen and is closed only here
+        unsafe {
+            libc::dlclose(self.0);
+        }
+    }
+}
+
+fn last_dl_error() -> String {
+    // SAFETY: dlerror returns null or a NUL-terminated message
+    let message = unsafe { libc::dlerror() };
+    if message.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+    }
+}
+
+/// Builtin registered by a plugin
+struct PluginBuiltin {
+    name: String,
+    func: BuiltinFn,
+    /// `user_data` pointer, kept as an address so builtins are `Send`
+    user_data: usize,
+}
+
+/// Loaded plugin and the builtins it registered
+pub struct Plugin {
+    path: PathBuf,
+    builtins: Vec<PluginBuiltin>,
+    /// Keeps the code of `builtins` loaded; `None` for in-process plugins
+    library: Option<Arc<Library>>,
+}
+
+impl Plugin {
+    /// Load the shared library at `path` and let it register its builtins
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PluginError> {
+        let path = path.as_ref();
+        let library = Library::open(path)?;
+        let symbol = library
+            .symbol(REGISTER_SYMBOL)
+            .ok_or_else(|| PluginError::MissingSymbol { path: path.to_path_buf() })?;
+        // SAFETY: plugins export `sui_plugin_register` with this signature
+        let register = unsafe { std::mem::transmute::<*mut c_void, RegisterFn>(symbol) };
+        let mut plugin = Self::from_register(path, register)?;
+        plugin.library = Some(Arc::new(library));
+        Ok(plugin)
+    }
+
+    /// Plugin whose registration function is linked into this program
+    ///
+    /// `path` only names the plugin in errors.
+    pub fn from_register(path: impl AsRef<Path>, register: RegisterFn) -> Result<Self, PluginError> {
+        let path = path.as_ref().to_path_buf();
+        let mut builtins: Vec<PluginBuiltin> = Vec::new();
+        // SAFETY: `registry` points at `builtins` for the duration of the call
+        let status = unsafe { register(PLUGIN_ABI_VERSION, register_builtin, &mut builtins as *mut _ as *mut c_void) };
+        if status != 0 {
+            return Err(PluginError::Refused { path, status });
+        }
+        Ok(Self { path, builtins, library: None })
+    }
+
+    /// Find a plugin by path or name and load it
+    ///
+    /// A name without a directory is looked up in the
+    /// [search directories](search_dirs) as given, then with the platform's
+    /// library prefix and suffix: `--plugin sqlite` finds `libsqlite.so`.
+    pub fn find(name: &str) -> Result<Self, PluginError> {
+        let path = Path::new(name);
+        if path.components().count() > 1 {
+            return Self::load(path);
+        }
+        // dlopen searches the system directories for a bare file name
+        if path.is_file() {
+            return Self::load(Path::new(".").join(path));
+        }
+        let candidates = [
+            name.to_string(),
+            format!("{}{}", name, std::env::consts::DLL_SUFFIX),
+            format!("{}{}{}", std::env::consts::DLL_PREFIX, name, std::env::consts::DLL_SUFFIX),
+        ];
+        search_dirs()
+            .iter()
+            .flat_map(|dir| candidates.iter().map(move |file| dir.join(file)))
+            .find(|candidate| candidate.is_file())
+            .map(Self::load)
+            .unwrap_or_else(|| Err(PluginError::NotFound(name.to_string())))
+    }
+
+    /// Path the plugin was loaded from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Names of the registered builtins, in registration order
+    pub fn builtin_names(&self) -> Vec<&str> {
+        self.builtins.iter().map(|b| b.name.as_str()).collect()
+    }
+
+    /// Make the plugin's builtins callable from programs run by `interp`
+    pub fn install(&self, interp: &mut Interpreter) {
+        for builtin in &self.builtins {
+            let (name, func, user_data) = (builtin.name.clone(), builtin.func, builtin.user_data);
+            let library = self.library.clone();
+            interp.register_builtin(builtin.name.clone(), move |args| {
+                let _loaded = &library;
+                call_builtin(&name, func, user_data, args)
+            });
+        }
+    }
+}
+
+/// Directories searched for plugins by name: those in `SUI_PLUGIN_PATH`,
+/// then `~/.sui/plugins`
+pub fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os(PLUGIN_PATH_VAR)
+        .map(|paths| std::env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()).collect())
+        .unwrap_or_default();
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(Path::new(&home).join(".sui").join("plugins"));
+    }
+    dirs
+}
+
+/// Shared libraries in the search directories, sorted within each
+pub fn discover() -> Vec<PathBuf> {
+    let suffix = std::env::consts::DLL_SUFFIX.trim_start_matches('.');
+    let mut found = Vec::new();
+    for dir in search_dirs() {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        let mut libraries: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == suffix))
+            .collect();
+        libraries.sort();
+        found.extend(libraries);
+    }
+    found
+}
+
+unsafe extern "C" fn register_builtin(registry: *mut c_void, name: *const c_char, func: BuiltinFn, user_data: *mut c_void) {
+    if registry.is_null() || name.is_null() {
+        return;
+    }
+    // SAFETY: `registry` is the vector passed by `from_register`
+    let builtins = unsafe { &mut *(registry as *mut Vec<PluginBuiltin>) };
+    let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else { return };
+    builtins.push(PluginBuiltin { name: name.to_string(), func, user_data: user_data as usize });
+}
+
+unsafe extern "C" fn write_output(out: *mut c_void, data: *const c_char, len: usize) {
+    if out.is_null() || data.is_null() {
+        return;
+    }
+    // SAFETY: `out` is the buffer passed by `call_builtin`; the plugin
+    // vouches for `len` bytes at `data`
+    let buffer = unsafe { &mut *(out as *mut Vec<u8>) };
+    buffer.extend_from_slice(unsafe { std::slice::from_raw_parts(data as *const u8, len) });
+}
+
+fn call_builtin(name: &str, func: BuiltinFn, user_data: usize, args: &[Value]) -> Result<Value, String> {
+    // JSON escapes control characters, so the text has no interior NUL
+    let args = CString::new(json::encode(&Value::from(args.to_vec()))).map_err(|e| e.to_string())?;
+    let mut out: Vec<u8> = Vec::new();
+    // SAFETY: the library stays loaded while the closure holding it lives
+    let status = unsafe {
+        func(
+            user_data as *mut c_void,
+            args.as_ptr(),
+            args.as_bytes().len(),
+            write_output,
+            &mut out as *mut Vec<u8> as *mut c_void,
+        )
+    };
+    // Whatever the plugin printed with C stdio comes before the output that
+    // follows the call
+    // SAFETY: fflush(NULL) flushes every open output stream
+    unsafe { libc::fflush(std::ptr::null_mut()) };
+    let text = String::from_utf8_lossy(&out);
+    if status != 0 {
+        return Err(format!("{}: {}", name, text));
+    }
+    json::decode(&text).map_err(|e| format!("{} returned {}", name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn sum(_: *mut c_void, args: *const c_char, _: usize, write: WriteFn, out: *mut c_void) -> c_int {
+        let args = unsafe { CStr::from_ptr(args) }.to_str().unwrap();
+        let total: i64 = args.trim_matches(['[', ']']).split(',').filter_map(|n| n.parse::<i64>().ok()).sum();
+        let result = total.to_string();
+        unsafe { write(out, result.as_ptr() as *const c_char, result.len()) };
+        0
+    }
+
+    unsafe extern "C" fn fail(_: *mut c_void, _: *const c_char, _: usize, write: WriteFn, out: *mut c_void) -> c_int {
+        for piece in ["no ", "database"] {
+            unsafe { write(out, piece.as_ptr() as *const c_char, piece.len()) };
+        }
+        1
+    }
+
+    unsafe extern "C" fn register(abi_version: u32, register: RegisterBuiltinFn, registry: *mut c_void) -> c_int {
+        if abi_version != PLUGIN_ABI_VERSION {
+            return -1;
+        }
+        unsafe {
+            register(registry, b"test.sum\0".as_ptr() as *const c_char, sum, std::ptr::null_mut());
+            register(registry, b"test.fail\0".as_ptr() as *const c_char, fail, std::ptr::null_mut());
+        }
+        0
+    }
+
+    unsafe extern "C" fn refuse(_: u32, _: RegisterBuiltinFn, _: *mut c_void) -> c_int {
+        7
+    }
+
+    #[test]
+    fn test_install() {
+        let plugin = Plugin::from_register("test", register).unwrap();
+        assert_eq!(plugin.builtin_names(), ["test.sum", "test.fail"]);
+
+        let mut interp = Interpreter::new();
+        plugin.install(&mut interp);
+        let output = interp.run("R v0 \"test.sum\" 1 2 39\n. v0", &[]).unwrap();
+        assert_eq!(output, vec!["42"]);
+        let err = interp.run("R v0 \"test.fail\"", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error at line 1: test.fail: no database");
+    }
+
+    #[test]
+    fn test_load_errors() {
+        assert!(matches!(Plugin::from_register("x", refuse), Err(PluginError::Refused { status: 7, .. })));
+        assert!(matches!(Plugin::load("/nonexistent/plugin.so"), Err(PluginError::Load { .. })));
+        assert!(matches!(Plugin::find("no-such-sui-plugin"), Err(PluginError::NotFound(_))));
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_missing_symbol() {
+        // Any shared library without the registration function will do
+        assert!(matches!(Plugin::load("libc.so.6"), Err(PluginError::MissingSymbol { .. })));
+    }
+}
@@ -0,0 +1,237 @@
+//! Prompt context packs for code generation
+//!
+//! [`prompt_pack`] assembles what a model needs to write Sui into one
+//! Markdown document: the instruction table, the variable conventions,
+//! few-shot examples taken from a corpus [`Manifest`] by their `tags`, and
+//! the grammar. With a token budget, content is dropped until the pack
+//! fits: first the grammar, then the least relevant examples, and last the
+//! instruction descriptions, leaving only signatures.
+//!
+//! ```rust
+//! use sui_lang::analysis::Cl100kHeuristic;
+//! use sui_lang::prompt::{prompt_pack, PackOptions};
+//!
+//! let options = PackOptions { budget: Some(600), grammar: None, ..PackOptions::default() };
+//! let pack = prompt_pack(None, &options, &Cl100kHeuristic).unwrap();
+//! assert!(pack.contains("+ result a b"));
+//! ```
+
+use std::fs;
+
+use crate::analysis::Tokenizer;
+use crate::corpus::{CorpusProgram, Manifest};
+use crate::grammar::{export, Format};
+use crate::interpreter::INSTRUCTION_TABLE;
+
+/// What goes into a pack
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackOptions {
+    /// Most tokens the pack may take, or `None` for no limit
+    pub budget: Option<usize>,
+    /// Most few-shot examples
+    pub examples: usize,
+    /// Only take examples with one of these tags, those with more of them
+    /// first; empty takes any
+    pub tags: Vec<String>,
+    /// Grammar notation to include, or `None` for no grammar
+    pub grammar: Option<Format>,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        PackOptions { budget: None, examples: 3, tags: Vec::new(), grammar: Some(Format::Gbnf) }
+    }
+}
+
+const INTRODUCTION: &str = "# Sui\n\n\
+Write programs in Sui: one instruction per line, an instruction character followed by operands \
+separated by spaces. There are no nested expressions; compute into temporary variables.\n";
+
+const VARIABLES: &str = "## Variables\n\n\
+- `v0`, `v1`, ... are locals of the current function (or of the main code)\n\
+- `g0`, `g1`, ... are globals; `g100` holds the argument count and `g101`, ... the arguments\n\
+- `a0`, `a1`, ... are the arguments of the current function\n\
+- Literals are integers, floats (`3.14`) and strings (`\"text\"`); `;` starts a comment\n\
+- Number labels and functions from 0 within each function\n";
+
+/// Build a prompt pack from `corpus` (if given) within `options`, counting
+/// tokens with `tokenizer`
+///
+/// Fails if an example cannot be read or the pack does not fit the budget
+/// even with only instruction signatures.
+pub fn prompt_pack(corpus: Option<&Manifest>, options: &PackOptions, tokenizer: &dyn Tokenizer) -> Result<String, String> {
+    let mut examples = match corpus {
+        Some(manifest) => choose_examples(manifest, options)?,
+        None => Vec::new(),
+    };
+    let mut grammar = options.grammar;
+    let mut descriptions = true;
+    loop {
+        let pack = render(descriptions, &examples, grammar);
+        let Some(budget) = options.budget else {
+            return Ok(pack);
+        };
+        let tokens = tokenizer.count(&pack);
+        if tokens <= budget {
+            return Ok(pack);
+        }
+        if grammar.is_some() {
+            grammar = None;
+        } else if examples.pop().is_some() {
+        } else if descriptions {
+            descriptions = false;
+        } else {
+            return Err(format!("a budget of {} tokens is too small; the smallest pack takes {}", budget, tokens));
+        }
+    }
+}
+
+/// Rendered examples of `manifest`, most relevant first
+fn choose_examples(manifest: &Manifest, options: &PackOptions) -> Result<Vec<String>, String> {
+    let mut candidates: Vec<(usize, &CorpusProgram)> = manifest
+        .programs
+        .iter()
+        .map(|program| (program.tags.iter().filter(|tag| options.tags.contains(tag)).count(), program))
+        .filter(|(matches, _)| options.tags.is_empty() || *matches > 0)
+        .collect();
+    // Stable, so equally relevant examples keep manifest order
+    candidates.sort_by_key(|&(matches, _)| std::cmp::Reverse(matches));
+
+    let mut examples = Vec::new();
+    for (_, program) in candidates {
+        if examples.len() == options.examples {
+            break;
+        }
+        // Each program once, even if the manifest runs it several ways
+        if examples.iter().any(|(path, _)| path == &program.path) {
+            continue;
+        }
+        let path = manifest.base_dir.join(&program.path);
+        let code = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        examples.push((program.path.clone(), render_example(program, &code)));
+    }
+    Ok(examples.into_iter().map(|(_, example)| example).collect())
+}
+
+fn render_example(program: &CorpusProgram, code: &str) -> String {
+    let mut text = format!("### {}", program.display_name());
+    if !program.tags.is_empty() {
+        text.push_str(&format!(" ({})", program.tags.join(", ")));
+    }
+    text.push_str("\n\n");
+    if !program.args.is_empty() {
+        text.push_str(&format!("Arguments: {}\n\n", program.args.join(" ")));
+    }
+    text.push_str("```sui\n");
+    // Comment lines are for people reading the file
+    for line in code.lines().filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with(';')) {
+        text.push_str(line);
+        text.push('\n');
+    }
+    text.push_str("```\n");
+    if let Some(expected) = &program.expected {
+        text.push_str(&format!("\nOutput:\n\n```\n{}\n```\n", expected.join("\n")));
+    }
+    text
+}
+
+fn render(descriptions: bool, examples: &[String], grammar: Option<Format>) -> String {
+    let mut pack = format!("{}\n## Instructions\n\n```\n", INTRODUCTION);
+    let width = INSTRUCTION_TABLE.iter().map(|syntax| syntax.signature().len()).max().unwrap_or(0);
+    for syntax in INSTRUCTION_TABLE {
+        if descriptions {
+            pack.push_str(&format!("{:width$}  {}\n", syntax.signature(), syntax.description, width = width));
+        } else {
+            pack.push_str(&format!("{}\n", syntax.signature()));
+        }
+    }
+    pack.push_str("```\n\n");
+    pack.push_str(VARIABLES);
+    if !examples.is_empty() {
+        pack.push_str("\n## Examples\n");
+        for example in examples {
+            pack.push('\n');
+            pack.push_str(example);
+        }
+    }
+    if let Some(format) = grammar {
+        pack.push_str(&format!("\n## Grammar ({})\n\n```\n{}```\n", format.extension(), export(format)));
+    }
+    pack
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::CharRatio;
+
+    fn corpus(dir: &std::path::Path) -> Manifest {
+        fs::write(dir.join("double.sui"), "; Doubles its argument\n* v0 g101 2\n. v0\n").unwrap();
+        fs::write(dir.join("count.sui"), "= v0 0\n: 0\n. v0\n+ v0 v0 1\n< v1 v0 3\n? v1 0\n").unwrap();
+        let text = r#"
+[[program]]
+path = "double.sui"
+args = [21]
+expected = ["42"]
+tags = ["args"]
+
+[[program]]
+name = "double again"
+path = "double.sui"
+args = [1]
+tags = ["args"]
+
+[[program]]
+path = "count.sui"
+tags = ["loops", "args"]
+"#;
+        Manifest::parse(text, dir).unwrap()
+    }
+
+    #[test]
+    fn test_pack() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = corpus(dir.path());
+        let tokenizer = CharRatio::default();
+        let pack = prompt_pack(Some(&manifest), &PackOptions::default(), &tokenizer).unwrap();
+        assert!(pack.starts_with("# Sui\n"));
+        assert!(pack.contains("\n+ result a b "));
+        assert!(pack.contains("### double.sui (args)\n\nArguments: 21\n\n```sui\n* v0 g101 2\n. v0\n```\n\nOutput:\n\n```\n42\n```\n"));
+        assert!(!pack.contains("Doubles its argument"));
+        assert!(!pack.contains("double again"));
+        assert!(pack.contains("## Grammar (gbnf)\n"));
+
+        // More matching tags first; other tags excluded
+        let options = PackOptions { tags: vec!["loops".to_string(), "args".to_string()], ..PackOptions::default() };
+        let pack = prompt_pack(Some(&manifest), &options, &tokenizer).unwrap();
+        assert!(pack.find("### count.sui").unwrap() < pack.find("### double.sui").unwrap());
+        let options = PackOptions { tags: vec!["strings".to_string()], ..PackOptions::default() };
+        assert!(!prompt_pack(Some(&manifest), &options, &tokenizer).unwrap().contains("## Examples"));
+    }
+
+    #[test]
+    fn test_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = corpus(dir.path());
+        let tokenizer = CharRatio::default();
+        let size = |options: &PackOptions| tokenizer.count(&prompt_pack(Some(&manifest), options, &tokenizer).unwrap());
+        let full = size(&PackOptions::default());
+        let without_grammar = size(&PackOptions { grammar: None, ..PackOptions::default() });
+        let one_example = size(&PackOptions { grammar: None, examples: 1, ..PackOptions::default() });
+
+        // The grammar goes first, then examples from the least relevant
+        let options = PackOptions { budget: Some(full - 1), ..PackOptions::default() };
+        assert_eq!(size(&options), without_grammar);
+        let options = PackOptions { budget: Some(without_grammar - 1), ..PackOptions::default() };
+        let pack = prompt_pack(Some(&manifest), &options, &tokenizer).unwrap();
+        assert_eq!(tokenizer.count(&pack), one_example);
+        assert!(pack.contains("### double.sui"));
+
+        // Then descriptions, and then it cannot fit
+        let signatures = render(false, &[], None);
+        let options = PackOptions { budget: Some(tokenizer.count(&signatures)), ..PackOptions::default() };
+        assert_eq!(prompt_pack(Some(&manifest), &options, &tokenizer).unwrap(), signatures);
+        let options = PackOptions { budget: Some(10), ..PackOptions::default() };
+        assert!(prompt_pack(Some(&manifest), &options, &tokenizer).unwrap_err().starts_with("a budget of 10 tokens is too small"));
+    }
+}
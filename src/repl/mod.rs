@@ -1,6 +1,7 @@
 //! REPL (Read-Eval-Print Loop) for Sui
 
-use crate::interpreter::Interpreter;
+use crate::interpreter::{instruction_set, Interpreter, OutputMode, INSTRUCTION_TABLE};
+use crate::transpiler::expr2sui;
 use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor, Result as RlResult};
 use std::path::PathBuf;
@@ -43,18 +44,14 @@ impl Default for Repl {
 impl Repl {
     /// Create a new REPL with default configuration
     pub fn new() -> Self {
-        Self {
-            interpreter: Interpreter::new(),
-            config: ReplConfig::default(),
-        }
+        Self::with_config(ReplConfig::default())
     }
 
     /// Create a new REPL with custom configuration
     pub fn with_config(config: ReplConfig) -> Self {
-        Self {
-            interpreter: Interpreter::new(),
-            config,
-        }
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output_mode(OutputMode::Stdout);
+        Self { interpreter, config }
     }
 
     /// Show welcome message
@@ -82,6 +79,13 @@ impl Repl {
         println!("  :funcs, :f    - Show defined functions");
         println!("  :quit, :q     - Exit REPL");
         println!("  :debug        - Toggle debug mode");
+        println!("  :explain OP   - Explain an instruction (e.g. :explain +)");
+        println!("  :expr FORMULA - Compile and run a formula (e.g. :expr v0 = (v1 + 2) * 3)");
+        println!();
+        println!("Instructions:");
+        for syntax in INSTRUCTION_TABLE {
+            println!("  {:22}- {}", syntax.signature(), syntax.title);
+        }
         println!();
         println!("Examples:");
         println!("  = v0 10       - Assign 10 to v0");
@@ -119,6 +123,25 @@ impl Repl {
             ":debug" => {
                 println!("Debug mode toggled.");
             }
+            cmd if cmd.starts_with(":explain") => {
+                let query = cmd[":explain".len()..].trim();
+                match instruction_set::lookup(query) {
+                    Some(syntax) => print!("{}", syntax.explain()),
+                    None => println!("Unknown instruction: {}", query),
+                }
+            }
+            cmd if cmd.starts_with(":expr") => match expr2sui(cmd[":expr".len()..].trim()) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("  {}", line);
+                        if let Err(e) = self.interpreter.run_line(&line) {
+                            eprintln!("Error: {}", e);
+                            break;
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            },
             _ => {
                 println!("Unknown command: {}", cmd);
                 println!("Type :help for available commands.");
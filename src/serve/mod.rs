@@ -0,0 +1,480 @@
+//! HTTP execution service
+//!
+//! [`serve`] answers JSON requests over HTTP/1.1, one thread per
+//! connection, for teams sharing a Sui evaluation backend:
+//!
+//! ```text
+//! POST /run        {"code": "...", "args": [5], "input": ["line"], "limits": {"max_steps": 1000}}
+//!                  200 {"output": ["120"], "elapsed_ms": 0, "cached": false,
+//!                       "stats": {"steps": 38, "functions": {"0": {"calls": 5, "steps": 36, "allocated": 0}}}}
+//!                  422 {"error": "Step limit exceeded: 1000", "output": [], "elapsed_ms": 0, "cached": false, "stats": {...}}
+//! POST /validate   {"code": "..."}
+//!                  200 {"valid": true, "diagnostics": []}
+//! POST /transpile  {"code": "...", "target": "javascript"}
+//!                  200 {"target": "javascript", "code": "..."}
+//! ```
+//!
+//! `limits` (`max_steps`, `max_depth`, `timeout_ms`) may only lower the
+//! server's [`Limits`]. Its `functions` caps single functions by ID, as in
+//! `{"3": {"max_steps": 100, "max_allocated": 1000}}`, so an untrusted
+//! module's functions run on a tighter budget; a function going over fails
+//! the run. `stats` has the instructions run in all and, for each function
+//! called, its calls, the instructions of its own body and the array
+//! elements it created. Programs run sandboxed as for the MCP tools of the
+//! same names: a virtual clock, no environment, commands or channels, and
+//! input only from `input`. Malformed requests get 400, requests whose
+//! headers are too long get 431, a request that crashes the server's
+//! handler gets 500, and requests beyond [`ServeOptions::max_concurrent`]
+//! get 503. A client that takes longer than [`ServeOptions::io_timeout`]
+//! to send its request or read the response is dropped, so idle
+//! connections cannot hold every slot.
+//!
+//! With [`ServeOptions::cache_size`] or [`ServeOptions::cache_dir`] set, a
+//! [`RunCache`] answers repeated runs of the same code, arguments, input
+//! and limits without running them again; runs that timed out are not
+//! cached.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value as Json};
+
+use crate::cache::{CacheKey, CachedRun, RunCache};
+use crate::interpreter::{InterpreterError, RunStats};
+use crate::mcp::{argv_argument, code_argument, input_argument, sandbox, transpile_sui, validate_sui, Limits};
+
+/// Largest request body accepted
+const MAX_BODY: usize = 1 << 20;
+
+/// Most bytes accepted for the request line and headers together
+const MAX_HEADER: u64 = 16 * 1024;
+
+/// Most header lines accepted
+const MAX_HEADERS: usize = 100;
+
+/// Settings for [`serve`]
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    /// Most any request may use
+    pub limits: Limits,
+    /// Requests handled at once
+    pub max_concurrent: usize,
+    /// Run results kept in memory; 0 keeps none
+    pub cache_size: usize,
+    /// Directory keeping every run result, across restarts
+    pub cache_dir: Option<PathBuf>,
+    /// Longest a client may wait between sending parts of its request, or
+    /// reading parts of the response
+    pub io_timeout: Duration,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        ServeOptions {
+            limits: Limits::default(),
+            max_concurrent: 16,
+            cache_size: 0,
+            cache_dir: None,
+            io_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// One of the [`ServeOptions::max_concurrent`] slots, given back when
+/// dropped, even if handling the request panicked
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    /// Take a slot if fewer than `max` are taken
+    fn take(active: &Arc<AtomicUsize>, max: usize) -> Option<Slot> {
+        let slot = Slot(Arc::clone(active));
+        (active.fetch_add(1, Ordering::SeqCst) < max).then_some(slot)
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Serve requests on `addr` until the process ends
+pub fn serve(addr: impl ToSocketAddrs, options: ServeOptions) -> io::Result<()> {
+    serve_listener(TcpListener::bind(addr)?, options)
+}
+
+/// [`serve`] on a listener that is already bound
+pub fn serve_listener(listener: TcpListener, options: ServeOptions) -> io::Result<()> {
+    let cache = match &options.cache_dir {
+        Some(dir) => Some(RunCache::with_dir(options.cache_size, dir)?),
+        None if options.cache_size > 0 => Some(RunCache::new(options.cache_size)),
+        None => None,
+    };
+    let cache = Arc::new(cache.map(Mutex::new));
+    let options = Arc::new(options);
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let Some(slot) = Slot::take(&active, options.max_concurrent) else {
+            // The client may already have gone
+            let _ = respond(&mut stream, 503, &json!({"error": "server busy"}));
+            continue;
+        };
+        let options = Arc::clone(&options);
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || {
+            let _slot = slot;
+            let _ = handle_connection(stream, &options, cache.as_ref().as_ref());
+        });
+    }
+    Ok(())
+}
+
+/// Status and body answering `method` on `path` with `body`, reusing run
+/// results from `cache` if given
+pub fn handle(method: &str, path: &str, body: &str, limits: &Limits, cache: Option<&Mutex<RunCache>>) -> (u16, Json) {
+    if !matches!(path, "/run" | "/validate" | "/transpile") {
+        return (404, json!({"error": format!("no endpoint {}", path)}));
+    }
+    if method != "POST" {
+        return (405, json!({"error": "use POST"}));
+    }
+    let request: Json = match serde_json::from_str(body) {
+        Ok(request @ Json::Object(_)) => request,
+        _ => return (400, json!({"error": "body must be a JSON object"})),
+    };
+    if request.get("code").and_then(Json::as_str).is_none() {
+        return (400, json!({"error": "missing code"}));
+    }
+
+    let outcome = match path {
+        "/run" => run(&request, &limits.narrow(&request["limits"]), cache),
+        "/validate" => validate_sui(&request),
+        _ => transpile_sui(&request),
+    };
+    match outcome {
+        Ok(body) => (200, body),
+        Err(body) => (422, body),
+    }
+}
+
+/// Run the program of a `/run` request, or take its result from `cache`
+fn run(request: &Json, limits: &Limits, cache: Option<&Mutex<RunCache>>) -> Result<Json, Json> {
+    let start = Instant::now();
+    let code = code_argument(request)?;
+    let argv = argv_argument(request);
+    let input = input_argument(request);
+    let key = cache.map(|_| {
+        let mut parts = vec![
+            ("max_steps".to_string(), limits.max_steps),
+            ("max_depth".to_string(), limits.max_depth as u64),
+            ("timeout_ms".to_string(), limits.timeout.as_millis() as u64),
+        ];
+        for (id, function) in &limits.functions {
+            parts.extend(function.max_steps.map(|n| (format!("functions.{}.max_steps", id), n)));
+            parts.extend(function.max_allocated.map(|n| (format!("functions.{}.max_allocated", id), n)));
+        }
+        let parts: Vec<_> = parts.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+        CacheKey::new(code, &argv, &input, &parts)
+    });
+    let cached = match (cache, &key) {
+        (Some(cache), Some(key)) => cache.lock().unwrap_or_else(PoisonError::into_inner).get(key),
+        _ => None,
+    };
+
+    let hit = cached.is_some();
+    let run = match cached {
+        Some(run) => run,
+        None => {
+            let mut interp = sandbox(input, limits);
+            let result = interp.run(code, &argv);
+            // A timeout depends on the machine's load, not on the key
+            let timed_out = matches!(result, Err(InterpreterError::Interrupted { .. }));
+            let run = CachedRun {
+                output: interp.get_output().to_vec(),
+                error: result.err().map(|e| e.to_string()),
+                stats: interp.stats(),
+            };
+            if let (Some(cache), Some(key), false) = (cache, key, timed_out) {
+                cache.lock().unwrap_or_else(PoisonError::into_inner).insert(key, run.clone());
+            }
+            run
+        }
+    };
+
+    let mut body = json!({
+        "output": run.output,
+        "elapsed_ms": start.elapsed().as_millis() as u64,
+        "cached": hit,
+        "stats": stats_json(&run.stats),
+    });
+    match run.error {
+        Some(error) => {
+            body["error"] = json!(error);
+            Err(body)
+        }
+        None => Ok(body),
+    }
+}
+
+/// `{"steps", "functions": {"<id>": {"calls", "steps", "allocated"}}}`
+fn stats_json(stats: &RunStats) -> Json {
+    let functions: serde_json::Map<String, Json> = stats
+        .functions
+        .iter()
+        .map(|(id, usage)| {
+            (id.to_string(), json!({"calls": usage.calls, "steps": usage.steps, "allocated": usage.allocated}))
+        })
+        .collect();
+    json!({"steps": stats.steps, "functions": functions})
+}
+
+/// [`handle`], answering 500 if it panics rather than dropping the
+/// connection without a response
+fn handle_caught(handle: impl FnOnce() -> (u16, Json)) -> (u16, Json) {
+    panic::catch_unwind(AssertUnwindSafe(handle)).unwrap_or_else(|_| (500, json!({"error": "internal error"})))
+}
+
+fn handle_connection(stream: TcpStream, options: &ServeOptions, cache: Option<&Mutex<RunCache>>) -> io::Result<()> {
+    stream.set_read_timeout(Some(options.io_timeout))?;
+    stream.set_write_timeout(Some(options.io_timeout))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    // The request line and headers, each without its line ending, or None
+    // if they are longer than MAX_HEADER or MAX_HEADERS allow
+    let mut budget = MAX_HEADER;
+    let mut lines = Vec::new();
+    let head = loop {
+        let mut line = String::new();
+        let read = (&mut reader).take(budget).read_line(&mut line)?;
+        budget -= read as u64;
+        let cut_off = if read == 0 { budget == 0 } else { !line.ends_with('\n') };
+        if cut_off || lines.len() > MAX_HEADERS {
+            break None;
+        }
+        if read == 0 || line.trim().is_empty() && !lines.is_empty() {
+            break Some(lines);
+        }
+        lines.push(line);
+    };
+    let Some(head) = head else {
+        return respond(&mut writer, 431, &json!({"error": "headers too large"}));
+    };
+
+    let mut parts = head.first().map(|line| line.split_whitespace()).into_iter().flatten();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return respond(&mut writer, 400, &json!({"error": "malformed request"}));
+    };
+
+    let mut length = 0;
+    for header in &head[1..] {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(usize::MAX);
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return respond(&mut writer, 413, &json!({"error": "body too large"}));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let (status, response) = match String::from_utf8(body) {
+        Ok(body) => handle_caught(|| handle(method, path, &body, &options.limits, cache)),
+        Err(_) => (400, json!({"error": "body is not UTF-8"})),
+    };
+    respond(&mut writer, status, &response)
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Json) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn test_handle() {
+        let limits = Limits::default();
+        let (status, body) = handle("POST", "/run", r#"{"code": "+ v0 g101 1\n. v0", "args": [41]}"#, &limits, None);
+        assert_eq!(status, 200);
+        assert_eq!(body["output"], json!(["42"]));
+
+        let (status, body) = handle("POST", "/run", r#"{"code": ": 0\n@ 0", "limits": {"max_steps": 10}}"#, &limits, None);
+        assert_eq!(status, 422);
+        assert_eq!(body["error"], "Step limit exceeded: 10");
+
+        let (status, body) = handle("POST", "/validate", r#"{"code": "= v0"}"#, &limits, None);
+        assert_eq!((status, &body["valid"]), (200, &json!(false)));
+        let (status, _) = handle("POST", "/transpile", r#"{"code": ". 1", "target": "wat"}"#, &limits, None);
+        assert_eq!(status, 200);
+
+        assert_eq!(handle("GET", "/run", "", &limits, None).0, 405);
+        assert_eq!(handle("POST", "/nope", "{}", &limits, None).0, 404);
+        assert_eq!(handle("POST", "/run", "[1]", &limits, None).0, 400);
+        assert_eq!(handle("POST", "/run", "{}", &limits, None).0, 400);
+    }
+
+    #[test]
+    fn test_function_limits() {
+        let limits = Limits::default();
+        let request = r##"{"code": "# 0 1 {\n[ v0 a0\n^ v0\n}\n$ v0 0 3\n$ v0 0 4"}"##;
+        let (status, body) = handle("POST", "/run", request, &limits, None);
+        assert_eq!(status, 200);
+        assert_eq!(body["stats"], json!({"steps": 6, "functions": {"0": {"calls": 2, "steps": 4, "allocated": 7}}}));
+
+        let request = r##"{"code": "# 0 1 {\n[ v0 a0\n^ v0\n}\n$ v0 0 3\n$ v0 0 4", "limits": {"functions": {"0": {"max_allocated": 5}}}}"##;
+        let (status, body) = handle("POST", "/run", request, &limits, None);
+        assert_eq!(status, 422);
+        assert_eq!(body["error"], "Function 0 exceeded its limit of 5 array elements at line 2");
+        assert_eq!(body["stats"]["functions"]["0"]["allocated"], 7);
+    }
+
+    #[test]
+    fn test_cache() {
+        let limits = Limits::default();
+        let cache = Mutex::new(RunCache::new(16));
+        let request = r#"{"code": ". g101\nE \"no\"", "args": [1]}"#;
+        let (status, first) = handle("POST", "/run", request, &limits, Some(&cache));
+        let (_, second) = handle("POST", "/run", request, &limits, Some(&cache));
+        assert_eq!(status, 422);
+        assert_eq!((&first["cached"], &second["cached"]), (&json!(false), &json!(true)));
+        assert_eq!((&second["output"], &second["error"]), (&first["output"], &first["error"]));
+
+        // Other arguments or limits are another run
+        let (_, other) = handle("POST", "/run", r#"{"code": ". g101\nE \"no\"", "args": [2]}"#, &limits, Some(&cache));
+        assert_eq!(other["cached"], false);
+        let narrow = r#"{"code": ". g101\nE \"no\"", "args": [1], "limits": {"max_steps": 10}}"#;
+        assert_eq!(handle("POST", "/run", narrow, &limits, Some(&cache)).1["cached"], false);
+    }
+
+    /// Serve `options` on a free local port
+    fn start(options: ServeOptions) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve_listener(listener, options));
+        addr
+    }
+
+    /// Status line and body of `POST /run` with `body`
+    fn post(addr: SocketAddr, body: &str) -> (String, Json) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST /run HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (status, json) = response.split_once("\r\n\r\n").unwrap();
+        (status.lines().next().unwrap().to_string(), serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_serve() {
+        let addr = start(ServeOptions::default());
+        let (status, body) = post(addr, r#"{"code": ". \"hi\""}"#);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["output"], json!(["hi"]));
+    }
+
+    #[test]
+    fn test_handle_caught() {
+        let (status, body) = handle_caught(|| panic!("bug"));
+        assert_eq!((status, body), (500, json!({"error": "internal error"})));
+        assert_eq!(handle_caught(|| (200, json!({}))).0, 200);
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let slot = Slot::take(&active, 1).unwrap();
+        assert!(Slot::take(&active, 1).is_none());
+        // A panic while holding the slot gives it back
+        let _ = thread::spawn(move || {
+            let _slot = slot;
+            panic!("bug");
+        })
+        .join();
+        assert_eq!(active.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_serve_idle_client() {
+        let options = ServeOptions { max_concurrent: 1, io_timeout: Duration::from_millis(100), ..ServeOptions::default() };
+        let addr = start(options);
+        // Connects and sends nothing, which must not hold the only slot
+        let silent = TcpStream::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(300));
+        let (status, body) = post(addr, r#"{"code": ". 1"}"#);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["output"], json!(["1"]));
+        drop(silent);
+    }
+
+    #[test]
+    fn test_serve_long_headers() {
+        let addr = start(ServeOptions::default());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // One header line with no end, longer than any accepted
+        let _ = write!(stream, "POST /run HTTP/1.1\r\nX-Long: {}", "a".repeat(MAX_HEADER as usize));
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert!(response.starts_with("HTTP/1.1 431 "), "{}", response);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let _ = write!(stream, "POST /run HTTP/1.1\r\n{}", "X: y\r\n".repeat(MAX_HEADERS + 1));
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert!(response.starts_with("HTTP/1.1 431 "), "{}", response);
+    }
+
+    #[test]
+    fn test_serve_channels_denied() {
+        // Programs waiting on a channel must not keep every slot taken
+        let limits = Limits { timeout: Duration::from_millis(500), ..Limits::default() };
+        let addr = start(ServeOptions { limits, max_concurrent: 2, ..ServeOptions::default() });
+        let clients: Vec<_> =
+            (0..2).map(|_| thread::spawn(move || post(addr, r#"{"code": "R v0 \"chan.recv\" 0"}"#))).collect();
+        for client in clients {
+            let (status, body) = client.join().unwrap();
+            assert_eq!(status, "HTTP/1.1 422 Unprocessable Entity");
+            assert_eq!(body["error"], "Permission denied at line 1: chan.recv needs channels");
+        }
+
+        // A slot is freed just after its response is sent
+        let mut tries = 0;
+        let (status, body) = loop {
+            let (status, body) = post(addr, r#"{"code": ". 1"}"#);
+            tries += 1;
+            if !status.contains("503") || tries == 50 {
+                break (status, body);
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["output"], json!(["1"]));
+    }
+}
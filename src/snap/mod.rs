@@ -0,0 +1,270 @@
+//! Golden-output snapshots of programs
+//!
+//! A snapshot sits next to its program (`fib.sui` → `fib.snap`) and
+//! records the arguments and input lines to run it with, the output it
+//! printed and the error it failed with, if any:
+//!
+//! ```text
+//! ; Snapshot of fib_args.sui, written by `sui snap update`
+//! arg 15
+//! ---
+//! 610
+//! ```
+//!
+//! Header lines are `arg TEXT`, `input TEXT` (a line for `,` to read) and
+//! `error MESSAGE`; everything after `---` is the output, line for line.
+//! [`update`] runs the program with the arguments and input of the
+//! existing snapshot and rewrites it, so edit those lines by hand and
+//! update again. [`verify`] runs it again and compares.
+//!
+//! Programs run from their files, so imports resolve, with a virtual
+//! clock so time and `randint` are the same on every run, no environment
+//! or commands, and at most [`MAX_STEPS`] instructions.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ide::find_sui_files;
+use crate::interpreter::{Clock, Interpreter};
+
+/// Instructions a snapshot run may execute
+pub const MAX_STEPS: u64 = 10_000_000;
+
+/// Separates the header of a snapshot from the output
+const SEPARATOR: &str = "---";
+
+/// Recorded run of a program
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Arguments, as on the command line
+    pub args: Vec<String>,
+    /// Lines for `,` to read
+    pub input: Vec<String>,
+    /// Printed output, one entry per line
+    pub output: Vec<String>,
+    /// Error the run failed with
+    pub error: Option<String>,
+}
+
+impl Snapshot {
+    /// Read a snapshot file's text
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut snapshot = Snapshot::default();
+        let mut lines = text.lines().enumerate();
+        for (i, line) in lines.by_ref() {
+            if line == SEPARATOR {
+                snapshot.output = lines.map(|(_, line)| line.to_string()).collect();
+                return Ok(snapshot);
+            }
+            if line.trim().is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "arg" => snapshot.args.push(value.to_string()),
+                "input" => snapshot.input.push(value.to_string()),
+                "error" => snapshot.error = Some(value.to_string()),
+                _ => return Err(format!("line {}: unknown header '{}'", i + 1, key)),
+            }
+        }
+        Err(format!("missing '{}' line", SEPARATOR))
+    }
+
+    /// Text of the snapshot file for `program`
+    pub fn render(&self, program: &str) -> String {
+        let mut text = format!("; Snapshot of {}, written by `sui snap update`\n", program);
+        for arg in &self.args {
+            text.push_str(&format!("arg {}\n", arg));
+        }
+        for line in &self.input {
+            text.push_str(&format!("input {}\n", line));
+        }
+        if let Some(error) = &self.error {
+            text.push_str(&format!("error {}\n", error));
+        }
+        text.push_str(SEPARATOR);
+        text.push('\n');
+        for line in &self.output {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Run `program` with `args` and `input` and record what happens
+    pub fn take(program: &Path, args: &[String], input: &[String]) -> Self {
+        let mut interp = Interpreter::new();
+        interp.set_clock(Clock::Virtual { epoch: 0.0 });
+        interp.set_max_steps(Some(MAX_STEPS));
+        interp.set_input_queue(input.to_vec());
+        let result = interp.run_file(program, args);
+        // Entries holding newlines print as several lines
+        let output = interp.get_output().iter().flat_map(|entry| entry.split('\n')).map(str::to_string).collect();
+        Snapshot { args: args.to_vec(), input: input.to_vec(), output, error: result.err().map(|e| e.to_string()) }
+    }
+}
+
+/// Snapshot file of `program`
+pub fn snap_path(program: &Path) -> PathBuf {
+    program.with_extension("snap")
+}
+
+/// Programs named by `paths`: `.sui` files, and those in directories
+pub fn find_programs(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut programs = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            programs.extend(find_sui_files(path));
+        } else {
+            programs.push(path.clone());
+        }
+    }
+    programs
+}
+
+/// How a program's run compares with its snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapStatus {
+    /// The run matches the snapshot
+    Unchanged,
+    /// A snapshot was written where there was none
+    Created,
+    /// The snapshot was rewritten to match a different run
+    Updated,
+    /// The run differs from the snapshot; the diff of the expected and
+    /// actual snapshot text
+    Changed(String),
+    /// There is no snapshot to verify against
+    Missing,
+}
+
+impl fmt::Display for SnapStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapStatus::Unchanged => write!(f, "ok"),
+            SnapStatus::Created => write!(f, "created"),
+            SnapStatus::Updated => write!(f, "updated"),
+            SnapStatus::Changed(_) => write!(f, "changed"),
+            SnapStatus::Missing => write!(f, "no snapshot"),
+        }
+    }
+}
+
+/// Run `program` and write its snapshot, keeping the arguments and input
+/// of the existing one
+pub fn update(program: &Path) -> io::Result<SnapStatus> {
+    let path = snap_path(program);
+    let old = read_snapshot(&path)?;
+    let (args, input) = old.as_ref().map_or((&[][..], &[][..]), |old| (&old.args[..], &old.input[..]));
+    let new = Snapshot::take(program, args, input);
+    match old {
+        Some(old) if old == new => Ok(SnapStatus::Unchanged),
+        old => {
+            fs::write(&path, new.render(&file_name(program)))?;
+            Ok(if old.is_some() { SnapStatus::Updated } else { SnapStatus::Created })
+        }
+    }
+}
+
+/// Run `program` and compare the run with its snapshot
+pub fn verify(program: &Path) -> io::Result<SnapStatus> {
+    let Some(old) = read_snapshot(&snap_path(program))? else {
+        return Ok(SnapStatus::Missing);
+    };
+    let new = Snapshot::take(program, &old.args, &old.input);
+    if old == new {
+        return Ok(SnapStatus::Unchanged);
+    }
+    let name = file_name(program);
+    Ok(SnapStatus::Changed(diff(&old.render(&name), &new.render(&name))))
+}
+
+fn read_snapshot(path: &Path) -> io::Result<Option<Snapshot>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Snapshot::parse(&text)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn file_name(program: &Path) -> String {
+    program.file_name().map_or_else(|| program.display().to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+/// Line diff of `expected` and `actual`: `-` lines only expected, `+`
+/// lines only actual
+fn diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    // Longest common subsequence table, from the ends
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push_str(&format!("  {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", a[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        let snapshot = Snapshot {
+            args: vec!["5".to_string(), "two words".to_string()],
+            input: vec!["".to_string()],
+            output: vec!["--- not a separator".to_string(), "".to_string()],
+            error: Some("Uncaught error at line 3: boom".to_string()),
+        };
+        let text = snapshot.render("prog.sui");
+        assert!(text.starts_with("; Snapshot of prog.sui"));
+        assert_eq!(Snapshot::parse(&text), Ok(snapshot));
+        assert!(Snapshot::parse("arg 1\n").is_err());
+        assert!(Snapshot::parse("args 1\n---\n").is_err());
+    }
+
+    #[test]
+    fn test_update_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("sum.sui");
+        fs::write(&program, "+ v0 g101 g102\n. v0\n, v1\n. v1").unwrap();
+        assert_eq!(verify(&program).unwrap(), SnapStatus::Missing);
+        assert_eq!(update(&program).unwrap(), SnapStatus::Created);
+        assert_eq!(verify(&program).unwrap(), SnapStatus::Unchanged);
+
+        // Arguments and input edited into the snapshot are kept
+        let snap = snap_path(&program);
+        let text = fs::read_to_string(&snap).unwrap().replace("---", "arg 2\narg 3\ninput hi\n---");
+        fs::write(&snap, text).unwrap();
+        assert_eq!(update(&program).unwrap(), SnapStatus::Updated);
+        let snapshot = Snapshot::parse(&fs::read_to_string(&snap).unwrap()).unwrap();
+        assert_eq!(snapshot.output, ["5", "hi"]);
+
+        fs::write(&program, "+ v0 g101 g102\n. v0\n. \"extra\"").unwrap();
+        let SnapStatus::Changed(diff) = verify(&program).unwrap() else {
+            panic!("expected a change");
+        };
+        assert!(diff.contains("  5\n- hi\n+ extra\n"));
+    }
+}
@@ -0,0 +1,481 @@
+//! Executable reference semantics
+//!
+//! [`EXAMPLES`] describes what each instruction does as small programs with
+//! the state before and after them: globals set beforehand, lines of input,
+//! the code, and the globals, output or error it leaves. [`check`] runs the
+//! examples against the interpreter, so the reference cannot drift from the
+//! implementation, and [`semantics_json`] renders them with the syntax of
+//! every instruction for tool authors and prompt writers:
+//!
+//! ```rust
+//! use sui_lang::spec::{check, semantics_json};
+//!
+//! assert!(check().is_empty());
+//! assert!(semantics_json().contains(r#""op":"+","name":"add""#));
+//! ```
+//!
+//! Values in `before` are Sui literals; values in `after` are the JSON the
+//! interpreter's `json.encode` gives for the global, so `5`, `5.0` and
+//! `"5"` stay apart.
+
+use crate::interpreter::json::{encode, write_string};
+use crate::interpreter::{Clock, Interpreter, Lexer, ParsedValue, Value, INSTRUCTION_TABLE, LANGUAGE_VERSION};
+
+/// One executable example of an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Example {
+    /// Instruction character(s) the example shows
+    pub op: &'static str,
+    /// What the example shows
+    pub summary: &'static str,
+    /// Globals assigned before the code runs, as Sui literals
+    pub before: &'static [(&'static str, &'static str)],
+    /// Lines for `,` to read
+    pub input: &'static [&'static str],
+    pub code: &'static str,
+    /// Globals after the code ran, as JSON
+    pub after: &'static [(&'static str, &'static str)],
+    /// Everything printed
+    pub output: &'static [&'static str],
+    /// Text of the error the code stops with
+    pub error: Option<&'static str>,
+}
+
+const EXAMPLE: Example =
+    Example { op: "", summary: "", before: &[], input: &[], code: "", after: &[], output: &[], error: None };
+
+/// Examples of every instruction, in [`INSTRUCTION_TABLE`] order
+pub static EXAMPLES: &[Example] = &[
+    Example {
+        op: "_",
+        summary: "importing a file that does not exist fails",
+        code: "_ \"missing.sui\"",
+        error: Some("Module not found: missing.sui"),
+        ..EXAMPLE
+    },
+    Example {
+        op: "C",
+        summary: "constants are replaced by their literal",
+        code: "C 0 42\n= g0 c0",
+        after: &[("g0", "42")],
+        ..EXAMPLE
+    },
+    Example { op: "=", summary: "assign a literal", code: "= g0 5", after: &[("g0", "5")], ..EXAMPLE },
+    Example {
+        op: "=",
+        summary: "copy a variable",
+        before: &[("g1", "\"hi\"")],
+        code: "= g0 g1",
+        after: &[("g0", "\"hi\""), ("g1", "\"hi\"")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "+",
+        summary: "add integers",
+        before: &[("g1", "2"), ("g2", "3")],
+        code: "+ g0 g1 g2",
+        after: &[("g0", "5")],
+        ..EXAMPLE
+    },
+    Example { op: "+", summary: "add floats", code: "+ g0 1.5 1", after: &[("g0", "2.5")], ..EXAMPLE },
+    Example {
+        op: "+",
+        summary: "concatenate strings",
+        code: "+ g0 \"ab\" \"cd\"",
+        after: &[("g0", "\"abcd\"")],
+        ..EXAMPLE
+    },
+    Example { op: "-", summary: "subtract", code: "- g0 10 4", after: &[("g0", "6")], ..EXAMPLE },
+    Example { op: "*", summary: "multiply", code: "* g0 6 -7", after: &[("g0", "-42")], ..EXAMPLE },
+    Example { op: "/", summary: "division gives a float", code: "/ g0 7 2", after: &[("g0", "3.5")], ..EXAMPLE },
+    Example {
+        op: "/",
+        summary: "also when it is exact",
+        code: "/ g0 6 3",
+        after: &[("g0", "2.0")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "/",
+        summary: "dividing by zero gives NaN",
+        code: "/ g0 1 0\n. g0",
+        output: &["nan"],
+        ..EXAMPLE
+    },
+    Example { op: "%", summary: "remainder", code: "% g0 17 5", after: &[("g0", "2")], ..EXAMPLE },
+    Example {
+        op: "%",
+        summary: "the remainder takes the sign of the dividend",
+        code: "% g0 -7 3",
+        after: &[("g0", "-1")],
+        ..EXAMPLE
+    },
+    Example { op: "<", summary: "less than", code: "< g0 1 2\n< g1 2 2", after: &[("g0", "1"), ("g1", "0")], ..EXAMPLE },
+    Example { op: ">", summary: "greater than", code: "> g0 3 2\n> g1 2 2", after: &[("g0", "1"), ("g1", "0")], ..EXAMPLE },
+    Example {
+        op: "<=",
+        summary: "less than or equal",
+        code: "<= g0 2 2\n<= g1 3 2",
+        after: &[("g0", "1"), ("g1", "0")],
+        ..EXAMPLE
+    },
+    Example {
+        op: ">=",
+        summary: "greater than or equal",
+        code: ">= g0 2 2\n>= g1 1 2",
+        after: &[("g0", "1"), ("g1", "0")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "~",
+        summary: "equality of numbers and strings",
+        code: "~ g0 4 4\n~ g1 \"a\" \"b\"",
+        after: &[("g0", "1"), ("g1", "0")],
+        ..EXAMPLE
+    },
+    Example { op: "!", summary: "logical not", code: "! g0 0\n! g1 5", after: &[("g0", "1"), ("g1", "0")], ..EXAMPLE },
+    Example { op: "&", summary: "logical and", code: "& g0 2 3\n& g1 2 0", after: &[("g0", "1"), ("g1", "0")], ..EXAMPLE },
+    Example { op: "|", summary: "logical or", code: "| g0 0 3\n| g1 0 0", after: &[("g0", "1"), ("g1", "0")], ..EXAMPLE },
+    Example {
+        op: "?",
+        summary: "a non-zero condition jumps",
+        before: &[("g0", "0")],
+        code: "? 1 0\n= g0 1\n: 0",
+        after: &[("g0", "0")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "?",
+        summary: "zero falls through",
+        before: &[("g0", "0")],
+        code: "? 0 0\n= g0 1\n: 0",
+        after: &[("g0", "1")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "@",
+        summary: "jump forward over code",
+        before: &[("g0", "0")],
+        code: "@ 0\n= g0 1\n: 0",
+        after: &[("g0", "0")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "J",
+        summary: "jump to the label at a position",
+        code: "J 1 0 1\n. \"none\"\n@ 2\n: 0\n. \"zero\"\n@ 2\n: 1\n. \"one\"\n: 2",
+        output: &["one"],
+        ..EXAMPLE
+    },
+    Example {
+        op: "J",
+        summary: "positions outside the list fall through",
+        code: "J 5 0\n. \"none\"\n@ 1\n: 0\n. \"zero\"\n: 1",
+        output: &["none"],
+        ..EXAMPLE
+    },
+    Example {
+        op: ":",
+        summary: "a loop jumping back to a label",
+        before: &[("g0", "0")],
+        code: ": 0\n+ g0 g0 1\n< g1 g0 3\n? g1 0",
+        after: &[("g0", "3"), ("g1", "0")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "#",
+        summary: "define and call a function",
+        code: "# 0 2 {\n+ v0 a0 a1\n^ v0\n}\n$ g0 0 2 3",
+        after: &[("g0", "5")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "}",
+        summary: "a function without ^ returns 0",
+        code: "# 0 0 {\n. \"body\"\n}\n$ g0 0",
+        after: &[("g0", "0")],
+        output: &["body"],
+        ..EXAMPLE
+    },
+    Example {
+        op: "$",
+        summary: "recursion, with locals per call",
+        code: "# 0 1 {\n< v0 a0 2\n? v0 0\n- v1 a0 1\n$ v2 0 v1\n* v3 a0 v2\n^ v3\n: 0\n^ 1\n}\n$ g0 0 5",
+        after: &[("g0", "120")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "^",
+        summary: "return ends the function",
+        code: "# 0 1 {\n^ a0\n. \"unreached\"\n}\n$ g0 0 7",
+        after: &[("g0", "7")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "T",
+        summary: "an error inside a try region jumps to its label",
+        code: "T g0 0\nE \"boom\"\nU\n. \"unreached\"\n: 0",
+        after: &[("g0", "\"boom\"")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "U",
+        summary: "after U errors are no longer caught",
+        code: "T g0 0\nU\nE \"boom\"\n: 0",
+        error: Some("boom"),
+        ..EXAMPLE
+    },
+    Example {
+        op: "E",
+        summary: "an uncaught error stops the program",
+        code: ". 1\nE \"boom\"\n. 2",
+        output: &["1"],
+        error: Some("Uncaught error at line 2: boom"),
+        ..EXAMPLE
+    },
+    Example { op: "[", summary: "create an array of zeros", code: "[ g0 3", after: &[("g0", "[0,0,0]")], ..EXAMPLE },
+    Example {
+        op: "]",
+        summary: "read an element",
+        code: "[ g1 3\n{ g1 1 7\n] g0 g1 1",
+        after: &[("g0", "7")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "{",
+        summary: "write an element",
+        code: "[ g0 2\n{ g0 0 \"x\"",
+        after: &[("g0", "[\"x\",0]")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "I",
+        summary: "iterate over 0 to n-1",
+        before: &[("g0", "0")],
+        code: "I v0 4\n: 0\nN v1 v2 v0\n? v1 1\n+ g0 g0 v2\n@ 0\n: 1",
+        after: &[("g0", "6")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "N",
+        summary: "iterate over the characters of a string",
+        code: "I v0 \"ab\"\nN g0 g1 v0\nN g2 g3 v0\nN g4 g5 v0",
+        after: &[("g0", "0"), ("g1", "\"a\""), ("g2", "0"), ("g3", "\"b\""), ("g4", "1")],
+        ..EXAMPLE
+    },
+    Example { op: ".", summary: "print values", code: ". 42\n. \"hi\"\n. 2.0", output: &["42", "hi", "2.0"], ..EXAMPLE },
+    Example {
+        op: ",",
+        summary: "input that parses as a number is a number",
+        input: &["12", "twelve"],
+        code: ", g0\n, g1",
+        after: &[("g0", "12"), ("g1", "\"twelve\"")],
+        ..EXAMPLE
+    },
+    Example {
+        op: "R",
+        summary: "call builtins",
+        code: "R g0 \"math.sqrt\" 16\nR g1 \"len\" \"abc\"",
+        after: &[("g0", "4.0"), ("g1", "3")],
+        ..EXAMPLE
+    },
+    Example { op: "P", summary: "same as R", code: "P g0 \"abs\" -3", after: &[("g0", "3")], ..EXAMPLE },
+];
+
+/// Examples of the instruction `op`
+pub fn examples(op: &str) -> impl Iterator<Item = &'static Example> + '_ {
+    EXAMPLES.iter().filter(move |example| example.op == op)
+}
+
+impl Example {
+    /// The example as one program: assignments of `before`, then `code`
+    pub fn program(&self) -> String {
+        let mut program: String = self.before.iter().map(|(var, value)| format!("= {} {}\n", var, value)).collect();
+        program.push_str(self.code);
+        program
+    }
+
+    /// Run the example and compare the interpreter with it
+    pub fn check(&self) -> Result<(), String> {
+        let mut interp = Interpreter::new();
+        interp.set_clock(Clock::Virtual { epoch: 0.0 });
+        interp.set_max_steps(Some(10_000));
+        interp.set_input_queue(self.input.iter().map(|line| line.to_string()).collect());
+        let result = interp.run(&self.program(), &[]);
+
+        match (&result, self.error) {
+            (Ok(_), Some(expected)) => return Err(format!("succeeded, expected error '{}'", expected)),
+            (Err(e), None) => return Err(format!("failed: {}", e)),
+            (Err(e), Some(expected)) if !e.to_string().contains(expected) => {
+                return Err(format!("failed with '{}', expected '{}'", e, expected))
+            }
+            _ => {}
+        }
+        if interp.get_output() != self.output {
+            return Err(format!("printed {:?}, expected {:?}", interp.get_output(), self.output));
+        }
+        for (var, expected) in self.after {
+            let index = var.strip_prefix('g').and_then(|i| i.parse().ok()).ok_or(format!("{} is not a global", var))?;
+            let actual = interp.get_global(index).map_or("null".to_string(), encode);
+            if actual != *expected {
+                return Err(format!("{} is {}, expected {}", var, actual, expected));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Examples the interpreter disagrees with, and how
+pub fn check() -> Vec<(&'static Example, String)> {
+    EXAMPLES.iter().filter_map(|example| example.check().err().map(|e| (example, e))).collect()
+}
+
+/// JSON describing every instruction: syntax, documentation and examples
+///
+/// One instruction per line:
+///
+/// ```text
+/// {"language_version":"1.3","instructions":[
+/// {"op":"=","name":"assign","title":"Assignment","signature":"= target value","since":"1.0",
+///  "description":"...","operands":[{"name":"target","kind":"variable"},...],
+///  "examples":[{"summary":"assign a literal","before":{},"input":[],"code":"= g0 5",
+///               "after":{"g0":5},"output":[],"error":null}]},
+/// ...
+/// ]}
+/// ```
+pub fn semantics_json() -> String {
+    let mut out = format!("{{\"language_version\":\"{}\",\"instructions\":[\n", LANGUAGE_VERSION);
+    for (i, syntax) in INSTRUCTION_TABLE.iter().enumerate() {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        out.push_str("{\"op\":");
+        write_string(&mut out, syntax.op);
+        out.push_str(",\"name\":");
+        write_string(&mut out, syntax.name);
+        out.push_str(",\"title\":");
+        write_string(&mut out, syntax.title);
+        out.push_str(",\"signature\":");
+        write_string(&mut out, &syntax.signature());
+        out.push_str(&format!(",\"since\":\"{}\",\"description\":", syntax.since));
+        write_string(&mut out, syntax.description);
+        out.push_str(",\"operands\":[");
+        for (j, (name, kind)) in syntax.params.iter().zip(syntax.operands).enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            write_string(&mut out, name);
+            out.push_str(",\"kind\":");
+            write_string(&mut out, &format!("{:?}", kind).to_lowercase());
+            out.push('}');
+        }
+        out.push_str("],\"examples\":[");
+        for (j, example) in examples(syntax.op).enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            write_example(&mut out, example);
+        }
+        out.push_str("]}");
+    }
+    out.push_str("\n]}\n");
+    out
+}
+
+fn write_example(out: &mut String, example: &Example) {
+    out.push_str("{\"summary\":");
+    write_string(out, example.summary);
+    out.push_str(",\"before\":{");
+    for (i, (var, literal)) in example.before.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(out, var);
+        out.push(':');
+        out.push_str(&encode(&literal_value(literal)));
+    }
+    out.push_str("},\"input\":");
+    write_strings(out, example.input);
+    out.push_str(",\"code\":");
+    write_string(out, example.code);
+    out.push_str(",\"after\":{");
+    for (i, (var, json)) in example.after.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(out, var);
+        out.push(':');
+        out.push_str(json);
+    }
+    out.push_str("},\"output\":");
+    write_strings(out, example.output);
+    out.push_str(",\"error\":");
+    match example.error {
+        Some(error) => write_string(out, error),
+        None => out.push_str("null"),
+    }
+    out.push('}');
+}
+
+fn write_strings(out: &mut String, items: &[&str]) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(out, item);
+    }
+    out.push(']');
+}
+
+/// Value of a Sui literal
+fn literal_value(literal: &str) -> Value {
+    match Lexer::parse_value(literal) {
+        ParsedValue::Integer(n) => Value::Integer(n),
+        ParsedValue::Float(x) => Value::Float(x),
+        ParsedValue::String(s) | ParsedValue::Variable(s) => Value::from(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_examples_hold() {
+        let failures: Vec<String> =
+            check().iter().map(|(example, e)| format!("{} ({}): {}", example.op, example.summary, e)).collect();
+        assert_eq!(failures, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_every_instruction_has_examples() {
+        for syntax in INSTRUCTION_TABLE {
+            assert!(examples(syntax.op).next().is_some(), "no example of {}", syntax.op);
+        }
+        assert!(EXAMPLES.iter().all(|example| INSTRUCTION_TABLE.iter().any(|s| s.op == example.op)));
+    }
+
+    #[test]
+    fn test_check_reports_disagreement() {
+        let wrong = Example { op: "+", code: "+ g0 1 1", after: &[("g0", "3")], ..EXAMPLE };
+        assert_eq!(wrong.check(), Err("g0 is 2, expected 3".to_string()));
+        let wrong = Example { op: ".", code: ". 1", ..EXAMPLE };
+        assert_eq!(wrong.check(), Err("printed [\"1\"], expected []".to_string()));
+    }
+
+    #[test]
+    fn test_semantics_json() {
+        let json = semantics_json();
+        let value = crate::interpreter::json::decode(&json).unwrap();
+        let Value::Array(instructions) = value.field("instructions").unwrap() else {
+            panic!("no instructions");
+        };
+        assert_eq!(instructions.len(), INSTRUCTION_TABLE.len());
+        assert_eq!(json.lines().count(), INSTRUCTION_TABLE.len() + 2);
+        assert!(json.contains(
+            r#""examples":[{"summary":"add integers","before":{"g1":2,"g2":3},"input":[],"code":"+ g0 g1 g2","after":{"g0":5},"output":[],"error":null}"#
+        ));
+    }
+}
@@ -0,0 +1,352 @@
+//! Infix formula to Sui compiler
+//!
+//! Compiles a C-like assignment such as `v0 = (a0*3 + 7) % 10` into
+//! three-address Sui lines, using fresh `v` temporaries numbered after the
+//! highest `v` variable in the formula.
+
+use super::TranspileError;
+use crate::interpreter::{Lexer, ParsedValue};
+
+/// Compile `target = expression` into Sui lines
+///
+/// Operands are Sui variables and literals. Supported are `+ - * / %`,
+/// comparisons, `&& || !`, unary minus, parentheses, subscripts `g0[i]` and
+/// builtin calls such as `sqrt(v1)`. The result is stored in `target`.
+pub fn expr2sui(source: &str) -> Result<Vec<String>, TranspileError> {
+    let tokens = tokenize(source)?;
+    let (target, expr) = match tokens.as_slice() {
+        [(_, Token::Atom(target)), (_, Token::Punct("=")), ..] if is_variable(target) => {
+            (target.clone(), &tokens[2..])
+        }
+        _ => return Err(error(0, "expected `variable = expression`")),
+    };
+
+    let mut parser = Parser { tokens: expr, pos: 0, end: source.len() };
+    let tree = parser.parse_expr(0)?;
+    if let Some(&(col, _)) = parser.tokens.get(parser.pos) {
+        return Err(error(col, "unexpected token"));
+    }
+
+    let next_temp = tokens
+        .iter()
+        .filter_map(|(_, t)| match t {
+            Token::Atom(a) if a.starts_with('v') && is_variable(a) => a[1..].parse::<usize>().ok(),
+            _ => None,
+        })
+        .max()
+        .map_or(0, |n| n + 1);
+    let mut codegen = Codegen { lines: Vec::new(), next_temp };
+    codegen.lower(&tree, Some(&target));
+    Ok(codegen.lines)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Variable, number or string literal, as written in Sui
+    Atom(String),
+    /// Builtin function name
+    Name(String),
+    Punct(&'static str),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Atom(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+}
+
+/// Operators, longest first so `<=` is not read as `<`
+const PUNCT: [&str; 21] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "=", "(", ")",
+    "[", "]", ",", ";",
+];
+
+fn error(col: usize, message: &str) -> TranspileError {
+    TranspileError::Parse(format!("{} at column {}", message, col + 1))
+}
+
+fn is_variable(s: &str) -> bool {
+    matches!(Lexer::parse_value(s), ParsedValue::Variable(_))
+}
+
+/// Split a formula into tokens with their byte offsets
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, TranspileError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let col = source.len() - rest.len();
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let len = if c == '"' {
+            let mut escaped = false;
+            let close = rest[1..].find(|c| {
+                let end = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                end
+            });
+            let Some(close) = close else {
+                return Err(error(col, "unterminated string"));
+            };
+            tokens.push((col, Token::Atom(rest[..close + 2].to_string())));
+            close + 2
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.')).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let token = match Lexer::parse_value(word) {
+                ParsedValue::Integer(_) | ParsedValue::Float(_) | ParsedValue::Variable(_) => {
+                    Token::Atom(word.to_string())
+                }
+                _ if rest[len..].trim_start().starts_with('(') => Token::Name(word.to_string()),
+                _ => return Err(error(col, &format!("`{}` is not a Sui variable or number", word))),
+            };
+            tokens.push((col, token));
+            len
+        } else {
+            let Some(punct) = PUNCT.iter().find(|p| rest.starts_with(*p)) else {
+                return Err(error(col, &format!("unexpected character `{}`", c)));
+            };
+            tokens.push((col, Token::Punct(punct)));
+            punct.len()
+        };
+        rest = &rest[len..];
+    }
+    // A trailing `;` is allowed, as in C
+    if matches!(tokens.last(), Some((_, Token::Punct(";")))) {
+        tokens.pop();
+    }
+    Ok(tokens)
+}
+
+/// Binding power of a binary operator; higher binds tighter
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 1,
+        "&&" => 2,
+        "==" | "!=" => 3,
+        "<" | ">" | "<=" | ">=" => 4,
+        "+" | "-" => 5,
+        "*" | "/" | "%" => 6,
+        _ => return None,
+    })
+}
+
+/// Precedence-climbing parser over the tokens of the right-hand side
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    pos: usize,
+    /// Column reported for a missing token at the end
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn col(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |&(col, _)| col)
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), TranspileError> {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(error(self.col(), &format!("expected `{}`", punct)))
+        }
+    }
+
+    fn parse_expr(&mut self, min_prec: u8) -> Result<Expr, TranspileError> {
+        let mut left = self.parse_unary()?;
+        while let Some(&Token::Punct(op)) = self.peek() {
+            let Some(prec) = precedence(op).filter(|&p| p > min_prec) else {
+                break;
+            };
+            self.pos += 1;
+            let right = self.parse_expr(prec)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, TranspileError> {
+        match self.peek() {
+            Some(&Token::Punct(op @ ("-" | "!"))) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, TranspileError> {
+        let col = self.col();
+        let mut expr = match self.peek().cloned() {
+            Some(Token::Atom(atom)) => {
+                self.pos += 1;
+                Expr::Atom(atom)
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 2; // name and `(`
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::Punct(")")) {
+                    loop {
+                        args.push(self.parse_expr(0)?);
+                        if self.peek() != Some(&Token::Punct(",")) {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.expect(")")?;
+                Expr::Call(name, args)
+            }
+            Some(Token::Punct("(")) => {
+                self.pos += 1;
+                let inner = self.parse_expr(0)?;
+                self.expect(")")?;
+                inner
+            }
+            _ => return Err(error(col, "expected an operand")),
+        };
+        while self.peek() == Some(&Token::Punct("[")) {
+            self.pos += 1;
+            let index = self.parse_expr(0)?;
+            self.expect("]")?;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+}
+
+/// Lowers an expression tree into three-address Sui lines
+struct Codegen {
+    lines: Vec<String>,
+    next_temp: usize,
+}
+
+impl Codegen {
+    fn temp(&mut self) -> String {
+        self.next_temp += 1;
+        format!("v{}", self.next_temp - 1)
+    }
+
+    /// Operand for the value of a node: `dest` when given, else a temporary
+    fn result(&mut self, dest: Option<&str>) -> String {
+        dest.map_or_else(|| self.temp(), str::to_string)
+    }
+
+    /// Emit `expr` and return the operand holding its value, which is `dest`
+    /// when given
+    fn lower(&mut self, expr: &Expr, dest: Option<&str>) -> String {
+        match expr {
+            Expr::Atom(atom) => match dest {
+                Some(dest) => {
+                    self.lines.push(format!("= {} {}", dest, atom));
+                    dest.to_string()
+                }
+                None => atom.clone(),
+            },
+            Expr::Unary(op, operand) => {
+                let operand = self.lower(operand, None);
+                let result = self.result(dest);
+                match *op {
+                    "-" => self.lines.push(format!("- {} 0 {}", result, operand)),
+                    _ => self.lines.push(format!("! {} {}", result, operand)),
+                }
+                result
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.lower(left, None);
+                let right = self.lower(right, None);
+                // Sui has no `!=`; it is composed from `~` and `!`
+                let (line, result) = match *op {
+                    "!=" => {
+                        let eq = self.temp();
+                        self.lines.push(format!("~ {} {} {}", eq, left, right));
+                        let result = self.result(dest);
+                        (format!("! {} {}", result, eq), result)
+                    }
+                    op => {
+                        let sui_op = match op {
+                            "==" => "~",
+                            "&&" => "&",
+                            "||" => "|",
+                            op => op,
+                        };
+                        let result = self.result(dest);
+                        (format!("{} {} {} {}", sui_op, result, left, right), result)
+                    }
+                };
+                self.lines.push(line);
+                result
+            }
+            Expr::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(|a| self.lower(a, None)).collect();
+                let result = self.result(dest);
+                let call = std::iter::once(format!("R {} \"{}\"", result, name)).chain(args);
+                self.lines.push(call.collect::<Vec<_>>().join(" "));
+                result
+            }
+            Expr::Index(..) => {
+                // `g0[i][j]` reads with one instruction
+                let mut path = Vec::new();
+                let mut base = expr;
+                while let Expr::Index(inner, index) = base {
+                    path.push(index.as_ref());
+                    base = inner;
+                }
+                let array = self.lower(base, None);
+                let indices: Vec<String> = path.iter().rev().map(|i| self.lower(i, None)).collect();
+                let result = self.result(dest);
+                self.lines.push(format!("] {} {} {}", result, array, indices.join(" ")));
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_three_address_form() {
+        let lines = expr2sui("v0 = (a0*3 + 7) % 10").unwrap();
+        assert_eq!(lines, vec!["* v1 a0 3", "+ v2 v1 7", "% v0 v2 10"]);
+        assert_eq!(expr2sui("g1 = v4").unwrap(), vec!["= g1 v4"]);
+    }
+
+    #[test]
+    fn test_precedence_and_builtins() {
+        let formulas = [
+            "= g0 5\n[ g1 3\n{ g1 2 9",
+            "v0 = -g0 + 2 * 3 <= 1 && !(g0 == 4) || g1[2] != 9",
+            "v1 = max(g0, 2 * 4) - sqrt(16);",
+        ];
+        let mut code = formulas[0].to_string();
+        for formula in &formulas[1..] {
+            for line in expr2sui(formula).unwrap() {
+                code.push('\n');
+                code.push_str(&line);
+            }
+        }
+        code.push_str("\n. v0\n. v1");
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.run(&code, &[]).unwrap(), vec!["1", "4.0"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let message = |source: &str| expr2sui(source).unwrap_err().to_string();
+        assert_eq!(message("v0 = x + 1"), "Parse error: `x` is not a Sui variable or number at column 6");
+        assert_eq!(message("v0 = (a0 + 1"), "Parse error: expected `)` at column 13");
+        assert_eq!(message("a0 + 1"), "Parse error: expected `variable = expression` at column 1");
+    }
+}
@@ -6,11 +6,20 @@
 mod sui2py;
 mod sui2js;
 mod py2sui;
+mod sui2wasm;
+mod expr2sui;
+mod sui2pseudo;
 
 pub use sui2py::Sui2Py;
 pub use sui2js::Sui2Js;
 pub use py2sui::Py2Sui;
+pub use sui2wasm::Sui2Wasm;
+pub use expr2sui::expr2sui;
+pub use sui2pseudo::Sui2Pseudo;
 
+use crate::interpreter::format::check_template;
+use crate::interpreter::{Instruction, Lexer, ParsedValue};
+use crate::ir::{BlockId, Cfg, Program, Region};
 use thiserror::Error;
 
 /// Transpiler errors
@@ -22,6 +31,12 @@ pub enum TranspileError {
     #[error("Invalid instruction at line {line}: {message}")]
     InvalidInstruction { line: usize, message: String },
 
+    #[error("Code generation error: {0}")]
+    Codegen(String),
+
+    #[error("Unsupported construct at line {line}: {construct}")]
+    Unsupported { line: usize, construct: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -37,3 +52,150 @@ pub trait Transpiler {
     /// Get the target language name
     fn language(&self) -> &str;
 }
+
+/// Whether a divisor is a literal that cannot be zero, so `/` and `%` need no
+/// division policy guard
+fn nonzero_literal(value: &str) -> bool {
+    match Lexer::parse_value(value) {
+        ParsedValue::Integer(n) => n != 0,
+        ParsedValue::Float(f) => f != 0.0,
+        _ => false,
+    }
+}
+
+/// Python `case` patterns for the values of a jump table entry, such as `0 | 2`
+fn case_patterns(values: &[usize]) -> String {
+    values.iter().map(usize::to_string).collect::<Vec<_>>().join(" | ")
+}
+
+/// Fail on a `fmt` or `format` call with a literal template the interpreter
+/// would refuse, such as one with a `{!r}` field or an unknown format spec
+fn check_templates(program: &Program) -> Result<(), TranspileError> {
+    let instructions = std::iter::once(&program.main)
+        .chain(program.functions.iter().map(|f| &f.body))
+        .flat_map(|cfg| cfg.blocks())
+        .flat_map(|b| &b.instructions);
+    for instr in instructions {
+        let Instruction::RustFFI { func, args, .. } = instr else { continue };
+        let func = func.trim_matches('"');
+        if !matches!(func, "fmt" | "format") {
+            continue;
+        }
+        if let Some(template) = args.first().and_then(|t| t.strip_prefix('"')?.strip_suffix('"')) {
+            check_template(template).map_err(|e| TranspileError::Codegen(format!("{}: {}", func, e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Piece of a `fmt` template
+#[derive(Debug, PartialEq)]
+enum TemplatePart {
+    /// Text as written in the string literal, with `{{` and `}}` undoubled
+    Text(String),
+    /// Field taking the argument at `arg` (0 is the first after the
+    /// template), formatted by `spec`
+    Field { arg: usize, spec: String },
+}
+
+/// Text and fields of a `fmt` template, if it is a string literal
+fn template_parts(template: &str) -> Option<Vec<TemplatePart>> {
+    let inner = template.strip_prefix('"')?.strip_suffix('"')?;
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut next = 0;
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek() == Some(&c) => {
+                chars.next();
+                text.push(c);
+            }
+            '{' => {
+                let field: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (index, spec) = field.split_once(':').unwrap_or((&field, ""));
+                let arg = match index.trim().parse::<usize>() {
+                    Ok(i) => i,
+                    Err(_) => {
+                        next += 1;
+                        next - 1
+                    }
+                };
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(TemplatePart::Field { arg, spec: spec.to_string() });
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Some(parts)
+}
+
+/// Parts of a loop that starts with a block and leaves when `cond`, computed
+/// by that block, is truthy
+struct LoopTest<'a> {
+    header: BlockId,
+    cond: &'a str,
+    /// Regions run while `cond` is falsy
+    body: &'a [Region],
+    /// Whether falling off the end of `body` leaves the loop
+    exits: bool,
+}
+
+/// Recognize a loop whose first block decides whether to leave it
+fn loop_test(body: &Region) -> Option<LoopTest<'_>> {
+    let Region::Seq(items) = body else {
+        return None;
+    };
+    let (Region::Block(header), Region::If { cond, negate, then_region, else_region: None }) =
+        (items.first()?, items.get(1)?)
+    else {
+        return None;
+    };
+
+    let is_break = |region: &Region| match region {
+        Region::Break => true,
+        Region::Seq(inner) => matches!(inner.as_slice(), [Region::Break]),
+        _ => false,
+    };
+    let (body, exits) = match (negate, &items[2..]) {
+        // `if not cond: body...` followed by `break`
+        (true, [Region::Break]) => match then_region.as_ref() {
+            Region::Seq(inner) => (inner.as_slice(), true),
+            other => (std::slice::from_ref(other), true),
+        },
+        // `if cond: break` followed by the body
+        (false, rest) if is_break(then_region) => (rest, false),
+        _ => return None,
+    };
+    Some(LoopTest { header: *header, cond, body, exits })
+}
+
+/// Parts of a loop that starts with `N done var iter` and leaves when `done`
+/// is set, which becomes `for var in iter:`
+struct ForEach<'a> {
+    var: &'a str,
+    iter: &'a str,
+    /// Regions run for each item
+    body: &'a [Region],
+    /// Whether falling off the end of `body` leaves the loop
+    exits: bool,
+}
+
+/// Recognize a loop that can be written as a `for` loop
+///
+/// The done flag is not assigned by the `for` loop.
+fn foreach_loop<'a>(cfg: &'a Cfg, body: &'a Region) -> Option<ForEach<'a>> {
+    let test = loop_test(body)?;
+    let [Instruction::Next { done, var, iter }] = cfg.block(test.header).instructions.as_slice() else {
+        return None;
+    };
+    if test.cond != done {
+        return None;
+    }
+    Some(ForEach { var, iter, body: test.body, exits: test.exits })
+}
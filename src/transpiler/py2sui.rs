@@ -4,7 +4,7 @@
 
 use super::TranspileError;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Python to Sui transpiler
 pub struct Py2Sui {
@@ -14,19 +14,37 @@ pub struct Py2Sui {
     func_counter: i64,
     var_map: HashMap<String, String>,
     func_map: HashMap<String, i64>,
+    /// Default value expression of each parameter, by function name
+    func_defaults: HashMap<String, Vec<Option<String>>>,
     is_global: bool,
     func_args: Vec<String>,
-    indent_stack: Vec<IndentContext>,
+    /// Parameters each function assigns, by function name
+    func_mutated: HashMap<String, Vec<String>>,
+    /// Locals holding copies of the current function's assigned parameters,
+    /// since Sui arguments are read-only
+    param_copies: HashMap<String, String>,
+    /// Open blocks with the indentation of their header line
+    indent_stack: Vec<(usize, IndentContext)>,
+    /// First construct on the current line that cannot be translated
+    unsupported: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 enum IndentContext {
     If { end_label: i64 },
     IfElse { else_label: i64, end_label: i64 },
-    While { start_label: i64, end_label: i64 },
-    For { start_label: i64, end_label: i64, loop_var: String },
+    /// `while` or `for`; a `range()` loop steps its counter at the bottom
+    Loop {
+        start_label: i64,
+        end_label: i64,
+        break_label: i64,
+        counter: Option<String>,
+        broken: bool,
+    },
     Function,
     Else { end_label: i64 },
+    /// `else` of a loop, skipped by `break`
+    LoopElse { break_label: i64 },
 }
 
 impl Default for Py2Sui {
@@ -45,12 +63,22 @@ impl Py2Sui {
             func_counter: 0,
             var_map: HashMap::new(),
             func_map: HashMap::new(),
+            func_defaults: HashMap::new(),
             is_global: true,
             func_args: Vec::new(),
+            func_mutated: HashMap::new(),
+            param_copies: HashMap::new(),
             indent_stack: Vec::new(),
+            unsupported: None,
         }
     }
 
+    /// Record a construct that cannot be translated; the first one on a line
+    /// is reported
+    fn unsupported(&mut self, construct: String) {
+        self.unsupported.get_or_insert(construct);
+    }
+
     /// Emit a line of Sui code
     fn emit(&mut self, line: &str) {
         self.output.push(line.to_string());
@@ -72,7 +100,10 @@ impl Py2Sui {
 
     /// Get or create a variable for a Python name
     fn get_var(&mut self, name: &str) -> String {
-        // Check if it's a function argument
+        // Check if it's an assigned parameter, then a function argument
+        if let Some(var) = self.param_copies.get(name) {
+            return var.clone();
+        }
         if let Some(idx) = self.func_args.iter().position(|a| a == name) {
             return format!("a{}", idx);
         }
@@ -113,15 +144,17 @@ impl Py2Sui {
         }
 
         // String literal
-        if (expr.starts_with('"') && expr.ends_with('"'))
-            || (expr.starts_with('\'') && expr.ends_with('\''))
-        {
+        if let Some(content) = string_literal(expr) {
             let var = self.new_var();
-            let content = &expr[1..expr.len() - 1];
-            self.emit(&format!("= {} \"{}\"", var, content));
+            self.emit(&format!("= {} {}", var, sui_string(content)));
             return var;
         }
 
+        // f-string
+        if let Some(content) = expr.strip_prefix('f').and_then(string_literal) {
+            return self.parse_fstring(content);
+        }
+
         // Boolean and special values
         if expr == "True" {
             let var = self.new_var();
@@ -134,92 +167,93 @@ impl Py2Sui {
             return var;
         }
 
-        // Comparison operators (handle before arithmetic to get precedence right)
-        for (op_str, sui_op) in [
-            ("==", "~"),
-            ("!=", "!~"),
-            ("<=", "<="),
-            (">=", ">="),
-            ("<", "<"),
-            (">", ">"),
-        ] {
-            if let Some(idx) = self.find_operator(expr, op_str) {
-                let left = self.parse_expr(&expr[..idx]);
-                let right = self.parse_expr(&expr[idx + op_str.len()..]);
-                let result = self.new_var();
-
-                match sui_op {
-                    "~" => self.emit(&format!("~ {} {} {}", result, left, right)),
-                    "!~" => {
-                        let tmp = self.new_var();
-                        self.emit(&format!("~ {} {} {}", tmp, left, right));
-                        self.emit(&format!("! {} {}", result, tmp));
-                    }
-                    "<=" => {
-                        let tmp1 = self.new_var();
-                        let tmp2 = self.new_var();
-                        self.emit(&format!("< {} {} {}", tmp1, left, right));
-                        self.emit(&format!("~ {} {} {}", tmp2, left, right));
-                        self.emit(&format!("| {} {} {}", result, tmp1, tmp2));
-                    }
-                    ">=" => {
-                        let tmp1 = self.new_var();
-                        let tmp2 = self.new_var();
-                        self.emit(&format!("> {} {} {}", tmp1, left, right));
-                        self.emit(&format!("~ {} {} {}", tmp2, left, right));
-                        self.emit(&format!("| {} {} {}", result, tmp1, tmp2));
-                    }
-                    "<" => self.emit(&format!("< {} {} {}", result, left, right)),
-                    ">" => self.emit(&format!("> {} {} {}", result, left, right)),
-                    _ => {}
-                }
-                return result;
-            }
-        }
-
-        // Logical operators
-        if let Some(idx) = self.find_keyword(expr, " and ") {
+        // Logical operators, loosest first
+        if let Some(idx) = self.find_keyword(expr, " or ") {
             let left = self.parse_expr(&expr[..idx]);
-            let right = self.parse_expr(&expr[idx + 5..]);
+            let right = self.parse_expr(&expr[idx + 4..]);
             let result = self.new_var();
-            self.emit(&format!("& {} {} {}", result, left, right));
+            self.emit(&format!("| {} {} {}", result, left, right));
             return result;
         }
 
-        if let Some(idx) = self.find_keyword(expr, " or ") {
+        if let Some(idx) = self.find_keyword(expr, " and ") {
             let left = self.parse_expr(&expr[..idx]);
-            let right = self.parse_expr(&expr[idx + 4..]);
+            let right = self.parse_expr(&expr[idx + 5..]);
             let result = self.new_var();
-            self.emit(&format!("| {} {} {}", result, left, right));
+            self.emit(&format!("& {} {} {}", result, left, right));
             return result;
         }
 
-        if expr.starts_with("not ") {
-            let operand = self.parse_expr(&expr[4..]);
+        if let Some(rest) = expr.strip_prefix("not ") {
+            let operand = self.parse_expr(rest);
             let result = self.new_var();
             self.emit(&format!("! {} {}", result, operand));
             return result;
         }
 
-        // Arithmetic operators (lowest precedence first for correct parsing)
-        for (op_str, sui_op) in [("+", "+"), ("-", "-")] {
-            if let Some(idx) = self.find_operator_rtl(expr, op_str) {
-                if idx > 0 {
-                    let left = self.parse_expr(&expr[..idx]);
-                    let right = self.parse_expr(&expr[idx + 1..]);
-                    let result = self.new_var();
-                    self.emit(&format!("{} {} {} {}", sui_op, result, left, right));
+        // Membership
+        for (keyword, negated) in [(" not in ", true), (" in ", false)] {
+            if let Some(idx) = self.find_keyword(expr, keyword) {
+                let item = self.parse_expr(&expr[..idx]);
+                let container = self.parse_expr(&expr[idx + keyword.len()..]);
+                let result = self.new_var();
+                self.emit(&format!("R {} \"contains\" {} {}", result, container, item));
+                if !negated {
                     return result;
                 }
+                let not_result = self.new_var();
+                self.emit(&format!("! {} {}", not_result, result));
+                return not_result;
+            }
+        }
+
+        // Comparisons; a chain like `a < b <= c` compares each adjacent pair
+        // and is true only if every comparison is
+        let comparisons = self.find_comparisons(expr);
+        if !comparisons.is_empty() {
+            let mut bounds = vec![0];
+            for &(idx, op) in &comparisons {
+                bounds.push(idx);
+                bounds.push(idx + op.len());
+            }
+            bounds.push(expr.len());
+            let operands: Vec<String> = bounds.chunks(2).map(|b| self.parse_expr(&expr[b[0]..b[1]])).collect();
+
+            let mut chain: Option<String> = None;
+            for (k, &(_, op)) in comparisons.iter().enumerate() {
+                let (left, right) = (&operands[k], &operands[k + 1]);
+                let result = self.new_var();
+                match op {
+                    "==" => self.emit(&format!("~ {} {} {}", result, left, right)),
+                    "!=" => {
+                        let tmp = self.new_var();
+                        self.emit(&format!("~ {} {} {}", tmp, left, right));
+                        self.emit(&format!("! {} {}", result, tmp));
+                    }
+                    op => self.emit(&format!("{} {} {} {}", op, result, left, right)),
+                }
+                chain = Some(match chain {
+                    Some(previous) => {
+                        let both = self.new_var();
+                        self.emit(&format!("& {} {} {}", both, previous, result));
+                        both
+                    }
+                    None => result,
+                });
             }
+            return chain.unwrap_or_default();
         }
 
-        for (op_str, sui_op) in [("*", "*"), ("/", "/"), ("%", "%")] {
-            if let Some(idx) = self.find_operator_rtl(expr, op_str) {
+        // Arithmetic operators (lowest precedence first for correct parsing)
+        for additive in [true, false] {
+            if let Some((idx, op)) = self.find_arithmetic_rtl(expr, additive) {
                 let left = self.parse_expr(&expr[..idx]);
-                let right = self.parse_expr(&expr[idx + 1..]);
+                let right = self.parse_expr(&expr[idx + op.len()..]);
                 let result = self.new_var();
-                self.emit(&format!("{} {} {} {}", sui_op, result, left, right));
+                match op {
+                    "//" => self.emit(&format!("R {} \"floordiv\" {} {}", result, left, right)),
+                    _ => self.emit(&format!("{} {} {} {}", op, result, left, right)),
+                }
                 return result;
             }
         }
@@ -232,87 +266,126 @@ impl Py2Sui {
             return result;
         }
 
+        // Power (right-associative, binds tighter than unary minus)
+        if let Some(idx) = self.find_operator(expr, "**") {
+            let left = self.parse_expr(&expr[..idx]);
+            let right = self.parse_expr(&expr[idx + 2..]);
+            let result = self.new_var();
+            self.emit(&format!("R {} \"ipow\" {} {}", result, left, right));
+            return result;
+        }
+
         // Parenthesized expression
         if expr.starts_with('(') && expr.ends_with(')') {
             return self.parse_expr(&expr[1..expr.len() - 1]);
         }
 
         // Function call
-        if let Some(paren_idx) = expr.find('(') {
-            if expr.ends_with(')') {
-                let func_name = &expr[..paren_idx];
-                let args_str = &expr[paren_idx + 1..expr.len() - 1];
-
-                // Built-in functions
-                match func_name {
-                    "print" => {
-                        let args = self.split_args(args_str);
-                        for arg in args {
-                            let arg_var = self.parse_expr(&arg);
-                            self.emit(&format!(". {}", arg_var));
-                        }
-                        return self.new_var();
+        if let Some((func_name, args_str)) = split_call(expr) {
+            let args = self.split_args(args_str);
+            if let Some(kwarg) = args.iter().find(|a| self.find_assignment(a).is_some()) {
+                let construct = format!("keyword argument `{}` in call to `{}`", kwarg, func_name);
+                self.unsupported(construct);
+            }
+
+            // Method call
+            if let Some((receiver, method)) = split_method(func_name) {
+                if let Some(result) = self.parse_method(receiver, method, &args) {
+                    return result;
+                }
+            }
+
+            // Built-in functions
+            match func_name {
+                "print" => {
+                    let args = self.split_args(args_str);
+                    for arg in args {
+                        let arg_var = self.parse_expr(&arg);
+                        self.emit(&format!(". {}", arg_var));
                     }
-                    "input" => {
-                        let result = self.new_var();
-                        self.emit(&format!(", {}", result));
-                        return result;
+                    return self.new_var();
+                }
+                "input" => {
+                    let result = self.new_var();
+                    self.emit(&format!(", {}", result));
+                    return result;
+                }
+                "len" => {
+                    let result = self.new_var();
+                    let args = self.split_args(args_str);
+                    if !args.is_empty() {
+                        let arg_var = self.parse_expr(&args[0]);
+                        self.emit(&format!("R {} \"len\" {}", result, arg_var));
+                    } else {
+                        self.emit(&format!("= {} 0", result));
                     }
-                    "len" => {
-                        let result = self.new_var();
-                        let args = self.split_args(args_str);
-                        if !args.is_empty() {
-                            let arg_var = self.parse_expr(&args[0]);
-                            self.emit(&format!("R {} \"len\" {}", result, arg_var));
-                        } else {
-                            self.emit(&format!("= {} 0", result));
+                    return result;
+                }
+                "int" | "float" | "str" | "abs" | "round" | "max" | "min" => {
+                    let result = self.new_var();
+                    let args = self.split_args(args_str);
+                    let arg_vars: Vec<String> = args.iter().map(|a| self.parse_expr(a)).collect();
+                    self.emit(&format!("R {} \"{}\" {}", result, func_name, arg_vars.join(" ")));
+                    return result;
+                }
+                "range" => {
+                    // range() returns a placeholder - handled specially in for loops
+                    let result = self.new_var();
+                    self.emit(&format!("= {} 0", result));
+                    return result;
+                }
+                _ => {
+                    // User-defined function
+                    if let Some(&func_id) = self.func_map.get(func_name) {
+                        let mut arg_vars: Vec<String> =
+                            args.iter().map(|a| self.parse_expr(a)).collect();
+                        // Omitted arguments take their defaults at the call site
+                        let defaults = self.func_defaults.get(func_name).cloned().unwrap_or_default();
+                        if arg_vars.len() > defaults.len() {
+                            self.unsupported(format!(
+                                "call to `{}` with {} arguments (expects {})",
+                                func_name,
+                                arg_vars.len(),
+                                defaults.len()
+                            ));
+                        }
+                        for (i, default) in defaults.iter().enumerate().skip(arg_vars.len()) {
+                            match default {
+                                Some(value) => arg_vars.push(self.parse_expr(value)),
+                                None => self.unsupported(format!(
+                                    "call to `{}` without argument {}",
+                                    func_name,
+                                    i + 1
+                                )),
+                            }
                         }
-                        return result;
-                    }
-                    "int" | "float" | "str" | "abs" | "round" | "max" | "min" => {
-                        let result = self.new_var();
-                        let args = self.split_args(args_str);
-                        let arg_vars: Vec<String> = args.iter().map(|a| self.parse_expr(a)).collect();
-                        self.emit(&format!("R {} \"{}\" {}", result, func_name, arg_vars.join(" ")));
-                        return result;
-                    }
-                    "range" => {
-                        // range() returns a placeholder - handled specially in for loops
                         let result = self.new_var();
-                        self.emit(&format!("= {} 0", result));
+                        self.emit(&format!("$ {} {} {}", result, func_id, arg_vars.join(" ")));
                         return result;
                     }
-                    _ => {
-                        // User-defined function
-                        if let Some(&func_id) = self.func_map.get(func_name) {
-                            let args = self.split_args(args_str);
-                            let arg_vars: Vec<String> =
-                                args.iter().map(|a| self.parse_expr(a)).collect();
-                            let result = self.new_var();
-                            self.emit(&format!("$ {} {} {}", result, func_id, arg_vars.join(" ")));
-                            return result;
-                        }
-                    }
                 }
             }
         }
 
-        // Array subscript
-        if let Some(bracket_idx) = expr.find('[') {
-            if expr.ends_with(']') {
-                let arr_name = &expr[..bracket_idx];
-                let idx_str = &expr[bracket_idx + 1..expr.len() - 1];
-                let arr_var = self.get_var(arr_name);
-                let idx_var = self.parse_expr(idx_str);
-                let result = self.new_var();
-                self.emit(&format!("] {} {} {}", result, arr_var, idx_var));
-                return result;
-            }
+        // Array subscript, possibly nested, of a name or an expression
+        if let Some((name, indices)) = split_subscripts(expr) {
+            let arr_var = if is_identifier(name) {
+                self.get_var(name)
+            } else {
+                self.parse_expr(name)
+            };
+            let idx: Vec<String> = indices.iter().map(|i| self.parse_expr(i)).collect();
+            let result = self.new_var();
+            self.emit(&format!("] {} {} {}", result, arr_var, idx.join(" ")));
+            return result;
         }
 
-        // List literal
+        // List literal or comprehension
         if expr.starts_with('[') && expr.ends_with(']') {
             let content = &expr[1..expr.len() - 1];
+            if let Some(idx) = self.find_keyword(content, " for ") {
+                return self.parse_comprehension(&content[..idx], &content[idx + 5..]);
+            }
             let elements = self.split_args(content);
             let result = self.new_var();
             self.emit(&format!("[ {} {}", result, elements.len()));
@@ -324,89 +397,338 @@ impl Py2Sui {
         }
 
         // Simple variable name
+        if !is_identifier(expr) {
+            let construct = if expr.ends_with(')') && expr.contains('(') {
+                format!("call `{}`", expr)
+            } else if is_identifier_path(expr) {
+                format!("attribute access `{}`", expr)
+            } else {
+                format!("expression `{}`", expr)
+            };
+            self.unsupported(construct);
+        }
         self.get_var(expr)
     }
 
-    /// Find operator position, skipping parentheses
-    fn find_operator(&self, expr: &str, op: &str) -> Option<usize> {
-        let mut depth = 0;
-        let chars: Vec<char> = expr.chars().collect();
-        let op_chars: Vec<char> = op.chars().collect();
-
-        for i in 0..chars.len() {
-            match chars[i] {
-                '(' | '[' => depth += 1,
-                ')' | ']' => depth -= 1,
-                '"' | '\'' => {
-                    // Skip strings
-                    let quote = chars[i];
-                    let mut j = i + 1;
-                    while j < chars.len() && chars[j] != quote {
-                        j += 1;
-                    }
+    /// Translate the body of an f-string into a `format` call
+    fn parse_fstring(&mut self, content: &str) -> String {
+        let mut template = String::new();
+        let mut fields = Vec::new();
+        let mut chars = content.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '{' || chars.peek().is_some_and(|&(_, next)| next == '{') {
+                // Doubled braces stay escaped in the template
+                if c == '{' || (c == '}' && chars.peek().is_some_and(|&(_, next)| next == '}')) {
+                    chars.next();
+                    template.push(c);
                 }
-                _ => {}
+                template.push(c);
+                continue;
             }
-
-            if depth == 0 && i + op_chars.len() <= chars.len() {
-                let slice: String = chars[i..i + op_chars.len()].iter().collect();
-                if slice == op {
-                    return Some(i);
+            let mut depth = 1;
+            let end = chars.by_ref().find(|&(_, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
                 }
+                depth == 0
+            });
+            let Some((end, _)) = end else {
+                self.unsupported(format!("unterminated field in f-string `{}`", content));
+                break;
+            };
+            let field = content[i + 1..end].trim();
+            let has_spec = self.find_operator(field, ":").is_some()
+                || ["!r", "!s", "!a", "="].iter().any(|suffix| field.ends_with(suffix));
+            if field.is_empty() || has_spec {
+                self.unsupported(format!("f-string field `{{{}}}`", field));
             }
+            fields.push(self.parse_expr(field));
+            template.push_str("{}");
         }
-        None
+        let result = self.new_var();
+        let operands: Vec<String> = std::iter::once(sui_string(&template)).chain(fields).collect();
+        self.emit(&format!("R {} \"format\" {}", result, operands.join(" ")));
+        result
     }
 
-    /// Find operator from right to left (for left-associative operators)
-    fn find_operator_rtl(&self, expr: &str, op: &str) -> Option<usize> {
-        let mut depth = 0;
-        let chars: Vec<char> = expr.chars().collect();
+    /// Translate `[element for name in iterable if cond]` into a loop that
+    /// appends to a new list
+    fn parse_comprehension(&mut self, element: &str, clauses: &str) -> String {
+        let result = self.new_var();
+        self.emit(&format!("[ {} 0", result));
+        let comprehension = format!("[{} for {}]", element.trim(), clauses.trim());
 
-        for i in (0..chars.len()).rev() {
-            match chars[i] {
-                ')' | ']' => depth += 1,
-                '(' | '[' => depth -= 1,
+        let Some(in_idx) = self.find_keyword(clauses, " in ") else {
+            self.unsupported(format!("comprehension `{}`", comprehension));
+            return result;
+        };
+        let name = clauses[..in_idx].trim();
+        let rest = &clauses[in_idx + 4..];
+        let (iterable, cond) = match self.find_keyword(rest, " if ") {
+            Some(idx) => (&rest[..idx], Some(&rest[idx + 4..])),
+            None => (rest, None),
+        };
+        if !is_identifier(name) {
+            self.unsupported(format!("comprehension target `{}`", name));
+            return result;
+        }
+        if self.find_keyword(rest, " for ").is_some() {
+            self.unsupported(format!("nested comprehension `{}`", comprehension));
+            return result;
+        }
+        if cond.is_some_and(|cond| self.find_keyword(cond, " if ").is_some()) {
+            self.unsupported(format!("comprehension with several conditions `{}`", comprehension));
+            return result;
+        }
+
+        // The loop variable is local to the comprehension; the iterable is
+        // evaluated outside it
+        let var = self.new_var();
+        let (start_label, end_label, counter) = self.loop_header(&var, iterable);
+        let shadowed = self.var_map.insert(name.to_string(), var);
+
+        let skip_label = cond.map(|cond| {
+            let cond = self.parse_expr(cond);
+            let not_cond = self.new_var();
+            self.emit(&format!("! {} {}", not_cond, cond));
+            let label = self.new_label();
+            self.emit(&format!("? {} {}", not_cond, label));
+            label
+        });
+        let value = self.parse_expr(element);
+        self.emit_append(&result, &result, &value);
+        if let Some(label) = skip_label {
+            self.emit(&format!(": {}", label));
+        }
+        self.close_loop(start_label, end_label, counter);
+
+        match shadowed {
+            Some(outer) => self.var_map.insert(name.to_string(), outer),
+            None => self.var_map.remove(name),
+        };
+        result
+    }
+
+    /// Emit `target = list + [value]`
+    fn emit_append(&mut self, target: &str, list: &str, value: &str) {
+        let item = self.new_var();
+        self.emit(&format!("[ {} 1", item));
+        self.emit(&format!("{{ {} 0 {}", item, value));
+        self.emit(&format!("R {} \"concat\" {} {}", target, list, item));
+    }
+
+    /// Translate `xs.append(x)` and `xs.pop(i)`, which rebind `xs` to a new
+    /// list since Sui arrays are values
+    fn parse_list_method(&mut self, receiver: &str, method: &str, args: &[String]) -> String {
+        let list = self.parse_expr(receiver);
+        let updated = if is_identifier(receiver) { list.clone() } else { self.new_var() };
+        let result = self.new_var();
+        if method == "append" {
+            let value = self.parse_expr(&args[0]);
+            self.emit_append(&updated, &list, &value);
+        } else {
+            // Index from the end for `pop()` and negative literals
+            let from_end = match args.first() {
+                None => Some(-1),
+                Some(arg) => arg.parse::<i64>().ok().filter(|&i| i < 0),
+            };
+            let index = if let Some(offset) = from_end {
+                let len = self.new_var();
+                self.emit(&format!("R {} \"len\" {}", len, list));
+                let index = self.new_var();
+                self.emit(&format!("+ {} {} {}", index, len, offset));
+                index
+            } else {
+                self.parse_expr(&args[0])
+            };
+            self.emit(&format!("] {} {} {}", result, list, index));
+            let before = self.new_var();
+            self.emit(&format!("R {} \"slice\" {} 0 {}", before, list, index));
+            let next = self.new_var();
+            self.emit(&format!("+ {} {} 1", next, index));
+            let after = self.new_var();
+            self.emit(&format!("R {} \"slice\" {} {}", after, list, next));
+            self.emit(&format!("R {} \"concat\" {} {}", updated, before, after));
+        }
+        if updated != list {
+            self.assign_target(receiver, updated);
+        }
+        result
+    }
+
+    /// Translate `receiver.method(args)` for the str and list methods Sui has
+    /// builtins for; `None` for other methods
+    fn parse_method(&mut self, receiver: &str, method: &str, args: &[String]) -> Option<String> {
+        let arity = match method {
+            "append" => 1..=1,
+            "pop" => 0..=1,
+            "upper" | "lower" | "strip" => 0..=0,
+            "split" => 0..=1,
+            "join" | "find" | "startswith" | "endswith" => 1..=1,
+            "replace" => 2..=2,
+            "format" => 0..=usize::MAX,
+            _ => return None,
+        };
+        if !arity.contains(&args.len()) {
+            self.unsupported(format!("`{}()` with {} arguments", method, args.len()));
+            return Some(self.new_var());
+        }
+        if method == "append" || method == "pop" {
+            return Some(self.parse_list_method(receiver, method, args));
+        }
+        let receiver = self.parse_expr(receiver);
+        let mut operands: Vec<String> = args.iter().map(|a| self.parse_expr(a)).collect();
+        // `sep.join(xs)` is `join xs sep`; other builtins take the string first
+        if method == "join" {
+            operands.push(receiver);
+        } else {
+            operands.insert(0, receiver);
+        }
+        let result = self.new_var();
+        self.emit(&format!("R {} \"{}\" {}", result, method, operands.join(" ")));
+        Some(result)
+    }
+
+    /// Sui variable of an existing Python array name
+    fn get_array(&mut self, name: &str) -> String {
+        if !is_identifier(name) {
+            self.unsupported(format!("subscript of `{}`", name));
+        }
+        self.get_var(name)
+    }
+
+    /// Store `value` into an assignment target: a name or a subscript
+    fn assign_target(&mut self, target: &str, value: String) {
+        if let Some((name, indices)) = split_subscripts(target) {
+            let arr = self.get_array(name);
+            let idx: Vec<String> = indices.iter().map(|i| self.parse_expr(i)).collect();
+            self.emit(&format!("{{ {} {} {}", arr, idx.join(" "), value));
+        } else {
+            self.check_target(target);
+            let target_var = self.get_var(target);
+            self.emit(&format!("= {} {}", target_var, value));
+        }
+    }
+
+    /// Report assignment targets other than names and subscripts
+    fn check_target(&mut self, target: &str) {
+        if is_identifier(target) {
+            return;
+        }
+        let construct = if target.contains(',') {
+            format!("tuple assignment `{}`", target)
+        } else if is_identifier_path(target) {
+            format!("attribute assignment `{}`", target)
+        } else {
+            format!("assignment to `{}`", target)
+        };
+        self.unsupported(construct);
+    }
+
+    /// Find operator position, skipping parentheses and strings
+    fn find_operator(&self, expr: &str, op: &str) -> Option<usize> {
+        let mut depth = 0;
+        for ((i, c), is_code) in expr.char_indices().zip(code_mask(expr)) {
+            if !is_code {
+                continue;
+            }
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
                 _ => {}
             }
 
-            if depth == 0 && chars[i].to_string() == op {
-                // Make sure this isn't part of a two-char operator
-                if i > 0 && (chars[i - 1] == '=' || chars[i - 1] == '<' || chars[i - 1] == '>' || chars[i - 1] == '!') {
-                    continue;
-                }
-                if i + 1 < chars.len() && chars[i + 1] == '=' {
-                    continue;
-                }
+            if depth == 0 && expr[i..].starts_with(op) {
                 return Some(i);
             }
         }
         None
     }
 
-    /// Find a keyword in expression
-    fn find_keyword(&self, expr: &str, keyword: &str) -> Option<usize> {
+    /// Offsets of the comparison operators outside brackets, left to right
+    fn find_comparisons(&self, expr: &str) -> Vec<(usize, &'static str)> {
+        let mut found = Vec::new();
         let mut depth = 0;
-        let chars: Vec<char> = expr.chars().collect();
-        let kw_chars: Vec<char> = keyword.chars().collect();
-
-        for i in 0..chars.len() {
-            match chars[i] {
+        let mut skip_to = 0;
+        for ((i, c), is_code) in expr.char_indices().zip(code_mask(expr)) {
+            if !is_code || i < skip_to {
+                continue;
+            }
+            match c {
                 '(' | '[' => depth += 1,
                 ')' | ']' => depth -= 1,
                 _ => {}
             }
+            if depth != 0 {
+                continue;
+            }
+            // Two-character operators first, so `<=` is not read as `<`
+            if let Some(op) = ["==", "!=", "<=", ">=", "<", ">"].into_iter().find(|op| expr[i..].starts_with(op)) {
+                found.push((i, op));
+                skip_to = i + op.len();
+            }
+        }
+        found
+    }
 
-            if depth == 0 && i + kw_chars.len() <= chars.len() {
-                let slice: String = chars[i..i + kw_chars.len()].iter().collect();
-                if slice == keyword {
-                    return Some(i);
+    /// Find the rightmost arithmetic operator of one precedence level
+    ///
+    /// With `additive`, finds a binary `+` or `-` (not a unary sign);
+    /// otherwise finds `*`, `/`, `//` or `%` (not `**`). Returns the byte
+    /// offset and the operator.
+    fn find_arithmetic_rtl(&self, expr: &str, additive: bool) -> Option<(usize, &'static str)> {
+        let chars: Vec<(usize, char)> = expr.char_indices().collect();
+        let code = code_mask(expr);
+        let mut depth = 0;
+        let mut i = chars.len();
+        while i > 0 {
+            i -= 1;
+            let (pos, c) = chars[i];
+            if !code[i] {
+                continue;
+            }
+            match c {
+                ')' | ']' => depth += 1,
+                '(' | '[' => depth -= 1,
+                _ => {}
+            }
+            // Skip brackets and comparison or augmented operators
+            if depth != 0 || chars.get(i + 1).is_some_and(|&(_, n)| n == '=') {
+                continue;
+            }
+            let prev = chars[..i].iter().rev().map(|&(_, p)| p).find(|p| !p.is_whitespace());
+            match c {
+                // A sign follows an operator or starts the expression
+                '+' | '-' if additive && prev.is_some_and(|p| !"+-*/%(<>=!,".contains(p)) => {
+                    return Some((pos, if c == '+' { "+" } else { "-" }));
+                }
+                '*' if !additive => {
+                    if i > 0 && chars[i - 1].1 == '*' {
+                        i -= 1;
+                    } else {
+                        return Some((pos, "*"));
+                    }
+                }
+                '/' if !additive => {
+                    if i > 0 && chars[i - 1].1 == '/' {
+                        return Some((chars[i - 1].0, "//"));
+                    }
+                    return Some((pos, "/"));
                 }
+                '%' if !additive => return Some((pos, "%")),
+                _ => {}
             }
         }
         None
     }
 
+    /// Find a keyword in expression
+    fn find_keyword(&self, expr: &str, keyword: &str) -> Option<usize> {
+        self.find_operator(expr, keyword)
+    }
+
     /// Split function arguments
     fn split_args(&self, args_str: &str) -> Vec<String> {
         if args_str.trim().is_empty() {
@@ -456,22 +778,64 @@ impl Py2Sui {
     }
 
     /// Parse a line of Python code
-    fn parse_line(&mut self, line: &str, _current_indent: usize) {
+    fn parse_line(&mut self, line: &str, line_no: usize) -> Result<(), TranspileError> {
+        self.parse_statement(line);
+        match self.unsupported.take() {
+            Some(construct) => Err(TranspileError::Unsupported { line: line_no, construct }),
+            None => Ok(()),
+        }
+    }
+
+    /// Translate one statement, recording constructs it cannot translate
+    fn parse_statement(&mut self, line: &str) {
         let trimmed = line.trim();
+        let indent = self.get_indent(line);
 
         if trimmed.is_empty() || trimmed.starts_with('#') {
             return;
         }
 
         // Assignment with augmented operators
-        let aug_ops = [("+=", "+"), ("-=", "-"), ("*=", "*"), ("/=", "/"), ("%=", "%")];
+        let aug_ops = [
+            ("//=", "floordiv"),
+            ("**=", "ipow"),
+            ("+=", "+"),
+            ("-=", "-"),
+            ("*=", "*"),
+            ("/=", "/"),
+            ("%=", "%"),
+        ];
         for (py_op, sui_op) in aug_ops {
-            if let Some(idx) = trimmed.find(py_op) {
+            if let Some(idx) = self.find_operator(trimmed, py_op) {
                 let target = trimmed[..idx].trim();
-                let value = trimmed[idx + 2..].trim();
-                let target_var = self.get_var(target);
+                let value = trimmed[idx + py_op.len()..].trim();
+
+                // Read-modify-write of an element: `xs[i] += 1`
+                let subscript = split_subscripts(target).map(|(name, indices)| {
+                    let arr = self.get_array(name);
+                    let idx: Vec<String> = indices.iter().map(|i| self.parse_expr(i)).collect();
+                    (arr, idx.join(" "))
+                });
+                let target_var = match &subscript {
+                    Some((arr, idx)) => {
+                        let current = self.new_var();
+                        self.emit(&format!("] {} {} {}", current, arr, idx));
+                        current
+                    }
+                    None => {
+                        self.check_target(target);
+                        self.get_var(target)
+                    }
+                };
                 let value_var = self.parse_expr(value);
-                self.emit(&format!("{} {} {} {}", sui_op, target_var, target_var, value_var));
+                if sui_op.len() > 1 {
+                    self.emit(&format!("R {} \"{}\" {} {}", target_var, sui_op, target_var, value_var));
+                } else {
+                    self.emit(&format!("{} {} {} {}", sui_op, target_var, target_var, value_var));
+                }
+                if let Some((arr, idx)) = subscript {
+                    self.emit(&format!("{{ {} {} {}", arr, idx, target_var));
+                }
                 return;
             }
         }
@@ -480,23 +844,11 @@ impl Py2Sui {
         if let Some(idx) = self.find_assignment(trimmed) {
             let target = trimmed[..idx].trim();
             let value = trimmed[idx + 1..].trim();
-
-            // Array subscript assignment
-            if let Some(bracket_idx) = target.find('[') {
-                if target.ends_with(']') {
-                    let arr_name = &target[..bracket_idx];
-                    let idx_str = &target[bracket_idx + 1..target.len() - 1];
-                    let arr_var = self.get_var(arr_name);
-                    let idx_var = self.parse_expr(idx_str);
-                    let value_var = self.parse_expr(value);
-                    self.emit(&format!("{{ {} {} {}", arr_var, idx_var, value_var));
-                    return;
-                }
+            if split_subscripts(target).is_none() {
+                self.check_target(target);
             }
-
             let value_var = self.parse_expr(value);
-            let target_var = self.get_var(target);
-            self.emit(&format!("= {} {}", target_var, value_var));
+            self.assign_target(target, value_var);
             return;
         }
 
@@ -510,54 +862,63 @@ impl Py2Sui {
             let end_label = self.new_label();
             self.emit(&format!("? {} {}", not_cond, end_label));
 
-            self.indent_stack.push(IndentContext::If { end_label });
+            self.indent_stack.push((indent, IndentContext::If { end_label }));
             return;
         }
 
         // Elif statement
         if trimmed.starts_with("elif ") && trimmed.ends_with(':') {
             // Handle like else + if
-            if let Some(IndentContext::If { end_label }) = self.indent_stack.pop() {
-                let new_end = self.new_label();
-                self.emit(&format!("@ {}", new_end));
-                self.emit(&format!(": {}", end_label));
-
-                let cond_str = &trimmed[5..trimmed.len() - 1];
-                let cond = self.parse_expr(cond_str);
-                let not_cond = self.new_var();
-                self.emit(&format!("! {} {}", not_cond, cond));
-
-                let elif_end = self.new_label();
-                self.emit(&format!("? {} {}", not_cond, elif_end));
-
-                self.indent_stack.push(IndentContext::IfElse {
-                    else_label: elif_end,
-                    end_label: new_end,
-                });
-            }
+            let (skip_label, end_label) = match self.indent_stack.pop() {
+                Some((_, IndentContext::If { end_label })) => (end_label, self.new_label()),
+                Some((_, IndentContext::IfElse { else_label, end_label })) => (else_label, end_label),
+                other => {
+                    self.indent_stack.extend(other);
+                    self.unsupported("`elif` without `if`".to_string());
+                    return;
+                }
+            };
+            self.emit(&format!("@ {}", end_label));
+            self.emit(&format!(": {}", skip_label));
+
+            let cond_str = &trimmed[5..trimmed.len() - 1];
+            let cond = self.parse_expr(cond_str);
+            let not_cond = self.new_var();
+            self.emit(&format!("! {} {}", not_cond, cond));
+
+            let elif_end = self.new_label();
+            self.emit(&format!("? {} {}", not_cond, elif_end));
+
+            self.indent_stack.push((indent, IndentContext::IfElse { else_label: elif_end, end_label }));
             return;
         }
 
         // Else statement
         if trimmed == "else:" {
-            match self.indent_stack.pop() {
-                Some(IndentContext::If { end_label }) => {
+            let ctx = match self.indent_stack.pop() {
+                Some((_, IndentContext::If { end_label })) => {
                     let new_end = self.new_label();
                     self.emit(&format!("@ {}", new_end));
                     self.emit(&format!(": {}", end_label));
-                    self.indent_stack.push(IndentContext::Else { end_label: new_end });
+                    IndentContext::Else { end_label: new_end }
                 }
-                Some(IndentContext::IfElse { else_label, end_label }) => {
+                Some((_, IndentContext::IfElse { else_label, end_label })) => {
                     self.emit(&format!("@ {}", end_label));
                     self.emit(&format!(": {}", else_label));
-                    self.indent_stack.push(IndentContext::Else { end_label });
+                    IndentContext::Else { end_label }
+                }
+                // Runs when the loop ends without `break`
+                Some((_, IndentContext::Loop { start_label, end_label, break_label, counter, .. })) => {
+                    self.close_loop(start_label, end_label, counter);
+                    IndentContext::LoopElse { break_label }
                 }
                 other => {
-                    if let Some(ctx) = other {
-                        self.indent_stack.push(ctx);
-                    }
+                    self.indent_stack.extend(other);
+                    self.unsupported("`else` without `if` or loop".to_string());
+                    return;
                 }
-            }
+            };
+            self.indent_stack.push((indent, ctx));
             return;
         }
 
@@ -575,55 +936,27 @@ impl Py2Sui {
             self.emit(&format!("! {} {}", not_cond, cond));
             self.emit(&format!("? {} {}", not_cond, end_label));
 
-            self.indent_stack
-                .push(IndentContext::While { start_label, end_label });
+            self.open_loop(indent, start_label, end_label, None);
             return;
         }
 
-        // For statement (only range supported)
+        // For statement
         if trimmed.starts_with("for ") && trimmed.contains(" in ") && trimmed.ends_with(':') {
-            let re = Regex::new(r"for\s+(\w+)\s+in\s+range\s*\((.+)\)\s*:").unwrap();
+            let re = Regex::new(r"for\s+(\w+)\s+in\s+(.+):").unwrap();
             if let Some(caps) = re.captures(trimmed) {
-                let loop_var_name = caps.get(1).unwrap().as_str();
-                let range_args = caps.get(2).unwrap().as_str();
-                let args = self.split_args(range_args);
-
-                let (start_val, end_expr) = if args.len() == 1 {
-                    ("0".to_string(), args[0].clone())
-                } else {
-                    (args[0].clone(), args[1].clone())
-                };
-
-                let loop_var = self.get_var(loop_var_name);
-                let start_var = self.parse_expr(&start_val);
-                self.emit(&format!("= {} {}", loop_var, start_var));
-
-                let end_var = self.parse_expr(&end_expr);
-
-                let start_label = self.new_label();
-                let end_label = self.new_label();
-
-                self.emit(&format!(": {}", start_label));
-
-                let cond = self.new_var();
-                self.emit(&format!("< {} {} {}", cond, loop_var, end_var));
-                let not_cond = self.new_var();
-                self.emit(&format!("! {} {}", not_cond, cond));
-                self.emit(&format!("? {} {}", not_cond, end_label));
-
-                self.indent_stack.push(IndentContext::For {
-                    start_label,
-                    end_label,
-                    loop_var: loop_var.clone(),
-                });
+                let loop_var = self.get_var(caps.get(1).unwrap().as_str());
+                let (start_label, end_label, counter) =
+                    self.loop_header(&loop_var, caps.get(2).unwrap().as_str());
+                self.open_loop(indent, start_label, end_label, counter);
                 return;
             }
+            self.unsupported(format!("for loop `{}`", trimmed));
+            return;
         }
 
         // Function definition
         if trimmed.starts_with("def ") && trimmed.ends_with(':') {
-            let re = Regex::new(r"def\s+(\w+)\s*\(([^)]*)\)\s*:").unwrap();
-            if let Some(caps) = re.captures(trimmed) {
+            if let Some(caps) = def_regex().captures(trimmed) {
                 let func_name = caps.get(1).unwrap().as_str();
                 let params_str = caps.get(2).unwrap().as_str();
 
@@ -631,27 +964,37 @@ impl Py2Sui {
                 self.func_counter += 1;
                 self.func_map.insert(func_name.to_string(), func_id);
 
-                let params: Vec<String> = if params_str.trim().is_empty() {
-                    Vec::new()
-                } else {
-                    params_str.split(',').map(|s| s.trim().to_string()).collect()
+                let params = match self.parse_params(params_str) {
+                    Ok(params) => params,
+                    Err(construct) => {
+                        self.unsupported(construct);
+                        Vec::new()
+                    }
                 };
+                let (params, defaults): (Vec<String>, Vec<Option<String>>) = params.into_iter().unzip();
+                self.func_defaults.insert(func_name.to_string(), defaults);
 
                 self.emit(&format!("# {} {} {{", func_id, params.len()));
 
                 // Update context for function body
                 self.is_global = false;
                 self.var_counter = 0;
+                let mutated = self.func_mutated.get(func_name).cloned().unwrap_or_default();
+                for (i, param) in params.iter().enumerate().filter(|(_, p)| mutated.contains(p)) {
+                    let var = self.new_var();
+                    self.emit(&format!("= {} a{}", var, i));
+                    self.param_copies.insert(param.clone(), var);
+                }
                 self.func_args = params;
 
-                self.indent_stack.push(IndentContext::Function);
+                self.indent_stack.push((indent, IndentContext::Function));
                 return;
             }
         }
 
         // Return statement
-        if trimmed.starts_with("return") {
-            let value_str = trimmed[6..].trim();
+        if let Some(rest) = trimmed.strip_prefix("return") {
+            let value_str = rest.trim();
             if value_str.is_empty() {
                 self.emit("^ 0");
             } else {
@@ -661,6 +1004,33 @@ impl Py2Sui {
             return;
         }
 
+        // Loop control
+        if trimmed == "break" || trimmed == "continue" {
+            let innermost = self
+                .indent_stack
+                .iter_mut()
+                .rev()
+                .map(|(_, ctx)| ctx)
+                .take_while(|ctx| !matches!(ctx, IndentContext::Function))
+                .find(|ctx| matches!(ctx, IndentContext::Loop { .. }));
+            let Some(IndentContext::Loop { start_label, break_label, counter, broken, .. }) = innermost else {
+                self.unsupported(format!("`{}` outside a loop", trimmed));
+                return;
+            };
+            if trimmed == "break" {
+                *broken = true;
+                let break_label = *break_label;
+                self.emit(&format!("@ {}", break_label));
+            } else {
+                let (start_label, counter) = (*start_label, counter.clone());
+                if let Some(counter) = counter {
+                    self.emit(&format!("+ {} {} 1", counter, counter));
+                }
+                self.emit(&format!("@ {}", start_label));
+            }
+            return;
+        }
+
         // Print statement (Python 2 style, also catches function call)
         if trimmed.starts_with("print(") && trimmed.ends_with(')') {
             let args_str = &trimmed[6..trimmed.len() - 1];
@@ -672,15 +1042,63 @@ impl Py2Sui {
             return;
         }
 
-        // Pass statement
-        if trimmed == "pass" {
+        // Pass statement; `global` is implied since names are shared with the
+        // top level; a string statement is a docstring
+        if trimmed == "pass"
+            || trimmed.starts_with("global ")
+            || trimmed.starts_with('"')
+            || trimmed.starts_with('\'')
+        {
             return;
         }
 
         // Expression statement (function call, etc.)
-        if trimmed.contains('(') {
+        if trimmed.contains('(') && !starts_with_keyword(trimmed) {
             self.parse_expr(trimmed);
+            return;
         }
+
+        let keyword = trimmed
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .next()
+            .unwrap_or(trimmed);
+        self.unsupported(format!("statement `{}`", if keyword.is_empty() { trimmed } else { keyword }));
+    }
+
+    /// Parse `a, b: int = 2` into names and default expressions
+    ///
+    /// Returns the offending construct for parameters Sui cannot represent.
+    fn parse_params(&self, params_str: &str) -> Result<Vec<(String, Option<String>)>, String> {
+        let mut params = Vec::new();
+        for param in self.split_args(params_str) {
+            if param.starts_with('*') || param == "/" {
+                return Err(format!("variadic or marker parameter `{}`", param));
+            }
+            let (name, default) = match self.find_assignment(&param) {
+                Some(idx) => (&param[..idx], Some(param[idx + 1..].trim().to_string())),
+                None => (param.as_str(), None),
+            };
+            // Drop a type annotation
+            let name = name.split(':').next().unwrap_or(name).trim();
+            params.push((name.to_string(), default));
+        }
+        Ok(params)
+    }
+
+    /// Name a statement assigns, directly or through a subscript
+    fn assigned_name<'a>(&self, trimmed: &'a str) -> Option<&'a str> {
+        let target = if let Some(rest) = trimmed.strip_prefix("for ") {
+            rest.split(" in ").next()?
+        } else {
+            let ops = ["//=", "**=", "+=", "-=", "*=", "/=", "%="];
+            match ops.iter().find_map(|op| self.find_operator(trimmed, op)) {
+                Some(idx) => &trimmed[..idx],
+                None => &trimmed[..self.find_assignment(trimmed)?],
+            }
+        };
+        let target = target.trim();
+        let name = split_subscripts(target).map_or(target, |(name, _)| name);
+        is_identifier(name).then_some(name)
     }
 
     /// Find assignment operator (not comparison ==)
@@ -717,39 +1135,114 @@ impl Py2Sui {
         None
     }
 
-    /// Close a block based on indentation
-    fn close_blocks(&mut self, new_indent: usize, prev_indent: usize) {
-        // Close blocks when dedenting
-        while !self.indent_stack.is_empty() && new_indent < prev_indent {
-            if let Some(ctx) = self.indent_stack.pop() {
-                match ctx {
-                    IndentContext::If { end_label } => {
-                        self.emit(&format!(": {}", end_label));
-                    }
-                    IndentContext::IfElse { else_label, end_label } => {
-                        self.emit(&format!(": {}", else_label));
-                        self.emit(&format!(": {}", end_label));
-                    }
-                    IndentContext::Else { end_label } => {
-                        self.emit(&format!(": {}", end_label));
-                    }
-                    IndentContext::While { start_label, end_label } => {
-                        self.emit(&format!("@ {}", start_label));
-                        self.emit(&format!(": {}", end_label));
-                    }
-                    IndentContext::For { start_label, end_label, loop_var } => {
-                        self.emit(&format!("+ {} {} 1", loop_var, loop_var));
-                        self.emit(&format!("@ {}", start_label));
-                        self.emit(&format!(": {}", end_label));
+    /// Emit the header of a loop binding `var` to each item of `iterable`:
+    /// index arithmetic over `range()`, an iterator otherwise
+    ///
+    /// Returns the start and end labels and the counter to step at the bottom.
+    fn loop_header(&mut self, var: &str, iterable: &str) -> (i64, i64, Option<String>) {
+        let iterable = iterable.trim();
+        let range_args = split_call(iterable)
+            .filter(|(callee, _)| callee.trim() == "range")
+            .map(|(_, args)| self.split_args(args));
+
+        let start_label;
+        let end_label;
+        let counter = if let Some(args) = range_args {
+            let (start_val, end_expr) = match args.as_slice() {
+                [end] => ("0", end.as_str()),
+                [start, end] => (start.as_str(), end.as_str()),
+                _ => {
+                    self.unsupported(format!("`{}` with a step", iterable));
+                    ("0", "0")
+                }
+            };
+            let start_var = self.parse_expr(start_val);
+            self.emit(&format!("= {} {}", var, start_var));
+            let end_var = self.parse_expr(end_expr);
+
+            start_label = self.new_label();
+            end_label = self.new_label();
+            self.emit(&format!(": {}", start_label));
+
+            let cond = self.new_var();
+            self.emit(&format!("< {} {} {}", cond, var, end_var));
+            let not_cond = self.new_var();
+            self.emit(&format!("! {} {}", not_cond, cond));
+            self.emit(&format!("? {} {}", not_cond, end_label));
+            Some(var.to_string())
+        } else {
+            let source = self.parse_expr(iterable);
+            let iter = self.new_var();
+            self.emit(&format!("I {} {}", iter, source));
+
+            start_label = self.new_label();
+            end_label = self.new_label();
+            self.emit(&format!(": {}", start_label));
+            let done = self.new_var();
+            self.emit(&format!("N {} {} {}", done, var, iter));
+            self.emit(&format!("? {} {}", done, end_label));
+            None
+        };
+        (start_label, end_label, counter)
+    }
+
+    /// Enter a loop body whose exit test jumps to `end_label`
+    fn open_loop(&mut self, indent: usize, start_label: i64, end_label: i64, counter: Option<String>) {
+        let break_label = self.new_label();
+        let ctx = IndentContext::Loop { start_label, end_label, break_label, counter, broken: false };
+        self.indent_stack.push((indent, ctx));
+    }
+
+    /// Emit the bottom of a loop: step, jump back and the exit label
+    fn close_loop(&mut self, start_label: i64, end_label: i64, counter: Option<String>) {
+        if let Some(counter) = counter {
+            self.emit(&format!("+ {} {} 1", counter, counter));
+        }
+        self.emit(&format!("@ {}", start_label));
+        self.emit(&format!(": {}", end_label));
+    }
+
+    /// Close every block a line at `indent` ends
+    ///
+    /// A block whose header is at the same indentation stays open when the
+    /// line `continues` it, as `else:` and `elif` do.
+    fn close_blocks(&mut self, indent: usize, continues: bool) {
+        while let Some(&(header, _)) = self.indent_stack.last() {
+            if header < indent || (header == indent && continues) {
+                break;
+            }
+            let Some((_, ctx)) = self.indent_stack.pop() else { break };
+            match ctx {
+                IndentContext::If { end_label } => {
+                    self.emit(&format!(": {}", end_label));
+                }
+                IndentContext::IfElse { else_label, end_label } => {
+                    self.emit(&format!(": {}", else_label));
+                    self.emit(&format!(": {}", end_label));
+                }
+                IndentContext::Else { end_label } => {
+                    self.emit(&format!(": {}", end_label));
+                }
+                IndentContext::Loop { start_label, end_label, break_label, counter, broken } => {
+                    self.close_loop(start_label, end_label, counter);
+                    if broken {
+                        self.emit(&format!(": {}", break_label));
                     }
-                    IndentContext::Function => {
-                        self.emit("}");
-                        self.is_global = true;
-                        self.func_args.clear();
+                }
+                IndentContext::LoopElse { break_label } => {
+                    self.emit(&format!(": {}", break_label));
+                }
+                IndentContext::Function => {
+                    // Falling off the end returns 0, as a bare `return` does
+                    if !self.output.last().is_some_and(|l| l.starts_with("^ ")) {
+                        self.emit("^ 0");
                     }
+                    self.emit("}");
+                    self.is_global = true;
+                    self.func_args.clear();
+                    self.param_copies.clear();
                 }
             }
-            break;
         }
     }
 
@@ -762,26 +1255,45 @@ impl Py2Sui {
         self.indent_stack.clear();
         self.is_global = true;
         self.func_args.clear();
+        self.param_copies.clear();
+        self.unsupported = None;
 
         let lines: Vec<&str> = code.lines().collect();
-        let mut prev_indent = 0;
 
-        // First pass: collect function names
-        for line in &lines {
+        // First pass: collect function names, parameter defaults and the
+        // parameters each body assigns
+        self.func_defaults.clear();
+        self.func_mutated.clear();
+        let re = def_regex();
+        for (i, line) in lines.iter().enumerate() {
             let trimmed = line.trim();
             if trimmed.starts_with("def ") && trimmed.ends_with(':') {
-                let re = Regex::new(r"def\s+(\w+)\s*\(").unwrap();
                 if let Some(caps) = re.captures(trimmed) {
                     let func_name = caps.get(1).unwrap().as_str();
                     self.func_map.insert(func_name.to_string(), self.func_counter);
                     self.func_counter += 1;
+                    if let Ok(params) = self.parse_params(caps.get(2).unwrap().as_str()) {
+                        let indent = self.get_indent(line);
+                        let assigned: HashSet<&str> = lines[i + 1..]
+                            .iter()
+                            .filter(|l| !l.trim().is_empty())
+                            .take_while(|l| self.get_indent(l) > indent)
+                            .filter_map(|l| self.assigned_name(l.trim()))
+                            .collect();
+                        let mutated =
+                            params.iter().map(|(p, _)| p.clone()).filter(|p| assigned.contains(p.as_str())).collect();
+                        self.func_mutated.insert(func_name.to_string(), mutated);
+                        let defaults = params.into_iter().map(|(_, d)| d).collect();
+                        self.func_defaults.insert(func_name.to_string(), defaults);
+                    }
                 }
             }
         }
         self.func_counter = 0;
 
         // Second pass: transpile
-        for line in lines {
+        let mut docstring: Option<&str> = None;
+        for (line_no, line) in lines.into_iter().enumerate() {
             let current_indent = self.get_indent(line);
             let trimmed = line.trim();
 
@@ -789,22 +1301,190 @@ impl Py2Sui {
                 continue;
             }
 
-            // Handle dedent
-            if current_indent < prev_indent {
-                self.close_blocks(current_indent, prev_indent);
+            // Skip multi-line docstrings
+            if let Some(quote) = docstring {
+                if trimmed.contains(quote) {
+                    docstring = None;
+                }
+                continue;
+            }
+            if let Some(quote) = ["\"\"\"", "'''"].into_iter().find(|q| trimmed.starts_with(q)) {
+                if trimmed.matches(quote).count() == 1 {
+                    docstring = Some(quote);
+                }
+                continue;
             }
 
-            self.parse_line(line, current_indent);
-            prev_indent = current_indent;
+            // Handle dedent
+            let continues = trimmed == "else:" || trimmed.starts_with("elif ");
+            self.close_blocks(current_indent, continues);
+
+            self.parse_line(line, line_no + 1)?;
         }
 
         // Close any remaining blocks
-        self.close_blocks(0, prev_indent);
+        self.close_blocks(0, false);
 
         Ok(self.output.join("\n"))
     }
 }
 
+/// Pattern of a `def` line, with an optional return annotation
+fn def_regex() -> Regex {
+    Regex::new(r"def\s+(\w+)\s*\((.*)\)\s*(?:->\s*[^:]+)?:$").unwrap()
+}
+
+/// Body of `s` when it is exactly one quoted string literal
+fn string_literal(s: &str) -> Option<&str> {
+    let quote = s.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return (i == s.len() - 1).then(|| &s[1..i]),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Sui literal for a Python string body, escaping its double quotes
+fn sui_string(content: &str) -> String {
+    let mut escaped = false;
+    let mut literal = String::from("\"");
+    for c in content.chars() {
+        if c == '"' && !escaped {
+            literal.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        literal.push(c);
+    }
+    literal.push('"');
+    literal
+}
+
+/// For each character of `expr`, whether it lies outside string literals
+fn code_mask(expr: &str) -> Vec<bool> {
+    let mut quote = None;
+    let mut escaped = false;
+    expr.chars()
+        .map(|c| match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+                false
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                false
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Split `callee(args)` at the parenthesis closed by the final `)`
+fn split_call(expr: &str) -> Option<(&str, &str)> {
+    if !expr.ends_with(')') {
+        return None;
+    }
+    let mut depth = 0;
+    for ((i, c), is_code) in expr.char_indices().rev().zip(code_mask(expr).into_iter().rev()) {
+        match c {
+            _ if !is_code => {}
+            ')' | ']' => depth += 1,
+            '(' | '[' => {
+                depth -= 1;
+                if depth == 0 {
+                    return (c == '(' && i > 0).then(|| (&expr[..i], &expr[i + 1..expr.len() - 1]));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split `receiver.method` at its last top-level dot
+fn split_method(callee: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    let mut dot = None;
+    for ((i, c), is_code) in callee.char_indices().zip(code_mask(callee)) {
+        match c {
+            _ if !is_code => {}
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '.' if depth == 0 => dot = Some(i),
+            _ => {}
+        }
+    }
+    let dot = dot?;
+    let method = &callee[dot + 1..];
+    (dot > 0 && is_identifier(method)).then(|| (&callee[..dot], method))
+}
+
+/// Whether `s` is a plain Python name
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Whether `s` is a dotted name such as `obj.attr`
+fn is_identifier_path(s: &str) -> bool {
+    s.contains('.') && s.split('.').all(is_identifier)
+}
+
+/// Whether a statement starts with a keyword this translator does not handle
+fn starts_with_keyword(s: &str) -> bool {
+    const KEYWORDS: [&str; 16] = [
+        "assert", "async", "await", "break", "class", "continue", "del", "except", "finally",
+        "from", "import", "lambda", "nonlocal", "raise", "try", "with",
+    ];
+    let word = s.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+    KEYWORDS.contains(&word)
+}
+
+/// Split `name[i][j]` into the name and its index expressions
+fn split_subscripts(target: &str) -> Option<(&str, Vec<&str>)> {
+    let open = target.find('[')?;
+    let name = target[..open].trim();
+    if name.is_empty() || !target.ends_with(']') {
+        return None;
+    }
+    let mut indices = Vec::new();
+    let mut depth = 0;
+    let mut start = open;
+    for (i, c) in target.char_indices().skip_while(|&(i, _)| i < open) {
+        match c {
+            '[' | '(' => {
+                if depth == 0 {
+                    if c == '(' {
+                        return None;
+                    }
+                    start = i + 1;
+                }
+                depth += 1;
+            }
+            ']' | ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    indices.push(target[start..i].trim());
+                }
+            }
+            c if depth == 0 && !c.is_whitespace() => return None,
+            _ => {}
+        }
+    }
+    Some((name, indices))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -823,6 +1503,19 @@ mod tests {
         assert!(result.contains("+"));
     }
 
+    #[test]
+    fn test_floor_division_and_power() {
+        let mut t = Py2Sui::new();
+        let result = t.transpile_to_sui("x = 7\ny = x // 2 ** 3\nx **= 2").unwrap();
+        assert!(result.contains("R v3 \"ipow\" v1 v2"));
+        assert!(result.contains("R v4 \"floordiv\" g0 v3"));
+        assert!(result.contains("R g0 \"ipow\" g0 v5"));
+
+        // Signs after an operator are unary
+        let result = t.transpile_to_sui("x = 2 * -3").unwrap();
+        assert!(result.contains("= v1 -3\n* v2 v0 v1"));
+    }
+
     #[test]
     fn test_while_loop() {
         let mut t = Py2Sui::new();
@@ -852,4 +1545,155 @@ print(result)
         assert!(result.contains("^")); // Return
         assert!(result.contains("$")); // Function call
     }
+
+    #[test]
+    fn test_for_each_loop() {
+        let mut t = Py2Sui::new();
+        let code = "for c in \"abc\":\n    print(c)\nprint(1)";
+        let result = t.transpile_to_sui(code).unwrap();
+        assert!(result.contains("I "));
+        assert!(result.contains("N "));
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["a", "b", "c", "1"]);
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        let mut t = Py2Sui::new();
+        let code = "total = 0\nfor i in range(3):\n    for j in range(3):\n        if j > i:\n            \
+                    continue\n        total += j\nprint(total)\nn = 0\nwhile n < 2:\n    for c in \"ab\":\n        \
+                    print(c)\n    n += 1\nprint(n)";
+        let result = t.transpile_to_sui(code).unwrap();
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["4", "a", "b", "a", "b", "2"]);
+    }
+
+    #[test]
+    fn test_loop_else_and_elif_chain() {
+        let mut t = Py2Sui::new();
+        let code = "def find(n):\n    for i in range(5):\n        if i == n:\n            break\n    else:\n        \
+                    return -1\n    return i\n\nprint(find(3))\nprint(find(9))\nfor x in range(4):\n    \
+                    if x == 0:\n        print(10)\n    elif x == 1:\n        print(11)\n    elif x == 2:\n        \
+                    print(12)\n    else:\n        print(13)";
+        let result = t.transpile_to_sui(code).unwrap();
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["3", "-1", "10", "11", "12", "13"]);
+
+        let err = Py2Sui::new().transpile_to_sui("x = 1\nbreak").unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported construct at line 2: `break` outside a loop");
+    }
+
+    #[test]
+    fn test_string_methods() {
+        let mut t = Py2Sui::new();
+        let code = "line = \"  Ada, Grace , Alan \"\nnames = line.strip().split(\",\")\n\
+                    print(\"-\".join(names).replace(\" \", \"\"))\nfirst = names[0].upper()\n\
+                    print(f\"{first} has {len(first)} letters, {{ok}}\")\n\
+                    print(\"{} < {}\".format(names[1].strip().lower(), 'x \"y\"'))\n\
+                    print(line.find(\"Grace\"), line.endswith(\"n\"))";
+        let result = t.transpile_to_sui(code).unwrap();
+        assert!(result.contains("R v13 \"format\" \"{} has {} letters, {{ok}}\" g2 v12"));
+        let mut interp = crate::interpreter::Interpreter::new();
+        let output = interp.run(&result, &[]).unwrap();
+        assert_eq!(
+            output,
+            vec!["Ada-Grace-Alan", "ADA has 3 letters, {ok}", "grace < x \"y\"", "7", "0"]
+        );
+
+        let err = Py2Sui::new().transpile_to_sui("x = 1.5\nprint(f\"{x:.2f}\")").unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported construct at line 2: f-string field `{x:.2f}`");
+    }
+
+    #[test]
+    fn test_list_methods_and_comprehensions() {
+        let mut t = Py2Sui::new();
+        let code = "x = 5\nsquares = [x * x for x in range(1, 6) if x != 3]\nprint(x)\n\
+                    squares.append([0])\nlast = squares.pop()\nfirst = squares.pop(0)\n\
+                    print(len(last), first, squares[-1 + 3])\nprint(16 in squares, 9 not in squares)\n\
+                    words = [w.upper() for w in \"a b\".split()]\nprint(\"\".join(words))";
+        let result = t.transpile_to_sui(code).unwrap();
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["5", "1", "1", "25", "1", "1", "AB"]);
+
+        let unsupported = |code: &str| Py2Sui::new().transpile_to_sui(code).unwrap_err().to_string();
+        assert_eq!(
+            unsupported("xs = [a * b for a in range(2) for b in range(2)]"),
+            "Unsupported construct at line 1: nested comprehension `[a * b for a in range(2) for b in range(2)]`"
+        );
+        assert_eq!(
+            unsupported("xs = [a for a, b in pairs]"),
+            "Unsupported construct at line 1: comprehension target `a, b`"
+        );
+    }
+
+    #[test]
+    fn test_subscript_targets() {
+        let mut t = Py2Sui::new();
+        let code = "xs = [1, 2]\ngrid = [[0, 0], [0, 0]]\nxs[1] += 10\ngrid[1][0] = 7\n\
+                    grid[1][0] *= 3\nprint(xs[1])\nprint(grid[1][0])";
+        let result = t.transpile_to_sui(code).unwrap();
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["12", "21"]);
+    }
+
+    #[test]
+    fn test_assigned_params_are_copied() {
+        let mut t = Py2Sui::new();
+        let code = "def count(n, step):\n    total = 0\n    while n > 0:\n        n -= step\n        \
+                    total += 1\n    return total\n\ndef first(xs):\n    xs[0] = 9\n    return xs[0]\n\n\
+                    print(count(10, 3))\nprint(first([1, 2]))";
+        let result = t.transpile_to_sui(code).unwrap();
+        assert!(result.contains("# 0 2 {\n= v0 a0\n"));
+        assert!(result.contains("# 1 1 {\n= v0 a0\n"));
+        let mut interp = crate::interpreter::Interpreter::new();
+        interp.set_strict(true);
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["4", "9"]);
+    }
+
+    #[test]
+    fn test_comparison_chains() {
+        let mut t = Py2Sui::new();
+        let code = "a = 1\nb = 2\nprint(a < b < 3)\nprint(3 > b >= 2 == b)\nprint(a < b and 3 < b)\n\
+                    print(not a == b)\nprint(a > b or b <= 2 <= a)";
+        let result = t.transpile_to_sui(code).unwrap();
+        // `<=` and `>=` are single instructions, and `a < b < 3` never compares a 0 or 1 with 3
+        assert!(result.contains("\n>= ") && result.contains("\n<= "));
+        assert!(result.contains("= v2 3\n< v3 g0 g1\n< v4 g1 v2\n& v5 v3 v4\n. v5"));
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["1", "1", "0", "1", "0"]);
+    }
+
+    #[test]
+    fn test_default_params_and_implicit_return() {
+        let mut t = Py2Sui::new();
+        let code = "def area(w, h=2, scale: int = 1) -> int:\n    if w < 0:\n        return 0\n    \
+                    print(w * h * scale)\n\nr = area(3)\nprint(r)\narea(3, 4, 10)";
+        let result = t.transpile_to_sui(code).unwrap();
+        assert!(result.contains("# 0 3 {"));
+        assert!(result.contains(". v5\n^ 0\n}"));
+        let mut interp = crate::interpreter::Interpreter::new();
+        assert_eq!(interp.run(&result, &[]).unwrap(), vec!["6", "0", "120"]);
+
+        let err = Py2Sui::new().transpile_to_sui("def f(a):\n    return a\nprint(f(a=1))").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported construct at line 3: keyword argument `a=1` in call to `f`"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_constructs() {
+        let unsupported = |code: &str| match Py2Sui::new().transpile_to_sui(code) {
+            Err(TranspileError::Unsupported { line, construct }) => (line, construct),
+            other => panic!("expected an unsupported construct, got {:?}", other),
+        };
+        assert_eq!(unsupported("x = 1\nobj.n += x"), (2, "attribute assignment `obj.n`".to_string()));
+        assert_eq!(unsupported("a, b = 1, 2"), (1, "tuple assignment `a, b`".to_string()));
+        assert_eq!(unsupported("import os"), (1, "statement `import`".to_string()));
+        assert_eq!(unsupported("x = foo(1)"), (1, "call `foo(1)`".to_string()));
+
+        // Docstrings and `global` are skipped
+        let code = "\"\"\"Doc\nstring\"\"\"\ndef f():\n    global x\n    return 1";
+        assert!(Py2Sui::new().transpile_to_sui(code).is_ok());
+    }
 }
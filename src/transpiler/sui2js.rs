@@ -1,8 +1,120 @@
 //! Sui to JavaScript transpiler
 
-use super::{TranspileError, Transpiler};
-use crate::interpreter::{Instruction, Parser};
-use std::collections::{HashMap, HashSet};
+use super::{check_templates, nonzero_literal, template_parts, TemplatePart, TranspileError, Transpiler};
+use crate::analysis::{infer_types, TypeSet};
+use crate::interpreter::{DivisionPolicy, Instruction, Lexer};
+use crate::ir::{BlockId, Cfg, Program, Region, Terminator};
+
+/// Runtime for `fmt`: Python-style format specs; `$ANY` marks parameter
+/// types in TypeScript
+const FMT_HELPERS: &[&str] = &[
+    "function _fmt(v$ANY, spec$ANY) {",
+    "  const m = /^(?:(.)?([<>^]))?([-+ ])?(0)?(\\d*)(,)?(?:\\.(\\d+))?([sdfe%xob])?$/.exec(spec);",
+    "  if (spec === \"\") return v === undefined ? \"\" : String(v);",
+    "  if (!m) throw new Error(`format spec '${spec}' is invalid`);",
+    "  const [, fill, align, sign, zero, width, group, prec, type] = m;",
+    "  if (+width > 10000) throw new Error(`format spec '${spec}' is wider than 10000 characters`);",
+    "  if (+prec > 100) throw new Error(`format spec '${spec}' has a precision over 100`);",
+    "  if (v === undefined) return \"\";",
+    "  // Numbers with no fraction count as integers: 2.0 cannot be told from 2",
+    "  const kind = typeof v === \"string\" ? \"str\" : typeof v !== \"number\" ? \"other\" : Number.isInteger(v) ? \"int\" : \"float\";",
+    "  const applies = kind === \"str\" ? (!type || type === \"s\") && !sign && !group",
+    "    : kind === \"int\" ? (!type || type === \"d\" ? prec === undefined : \"xob\".includes(type) ? prec === undefined && !group : type !== \"s\")",
+    "    : kind === \"float\" && (!type || \"fe%\".includes(type));",
+    "  if (!applies) throw new Error(`format spec '${spec}' does not apply to type ${kind === \"other\" ? (Array.isArray(v) ? \"array\" : \"struct\") : kind}`);",
+    "  const numeric = kind !== \"str\";",
+    "  let body = String(v);",
+    "  let neg = false;",
+    "  if (!numeric) {",
+    "    if (prec !== undefined) body = body.slice(0, +prec);",
+    "  } else {",
+    "    const x = Math.abs(v);",
+    "    neg = (v < 0 || Object.is(v, -0)) && !isNaN(x);",
+    "    const p = prec === undefined ? 6 : +prec;",
+    "    if (!isFinite(x)) body = isNaN(x) ? \"nan\" : \"inf\";",
+    "    else if (type === \"f\") body = _fixed(x, p);",
+    "    else if (type === \"%\") body = _fixed(x * 100, p);",
+    "    else if (type === \"e\") body = _exp(x, p);",
+    "    else if (type && \"xob\".includes(type)) body = BigInt(x).toString({ x: 16, o: 8, b: 2 }[type]);",
+    "    else if (kind === \"int\") body = BigInt(x).toString();",
+    "    else if (prec === undefined) body = _repr(x);",
+    "    else {",
+    "      // Significant digits, with exponents like Python's",
+    "      const p = Math.max(+prec, 1);",
+    "      const e = +x.toExponential(p - 1).split(\"e\")[1];",
+    "      if (e >= -4 && e < p - 1) {",
+    "        body = _fixed(x, p - 1 - e).replace(/\\.?0+$/, \"\");",
+    "        if (!body.includes(\".\")) body += \".0\";",
+    "      } else {",
+    "        const [mant, exp] = _exp(x, p - 1).split(\"e\");",
+    "        body = mant.replace(/\\.0+$|(\\.\\d*?)0+$/, \"$1\") + \"e\" + exp;",
+    "      }",
+    "    }",
+    "    if (type === \"%\") body += \"%\";",
+    "    if (group) body = body.replace(/^\\d+/, (d$ANY) => d.replace(/\\B(?=(\\d{3})+$)/g, \",\"));",
+    "  }",
+    "  const signText = neg ? \"-\" : sign === \"+\" ? \"+\" : sign === \" \" ? \" \" : \"\";",
+    "  const w = +width || 0;",
+    "  if (zero && !align && numeric) return signText + body.padStart(w - signText.length, \"0\");",
+    "  const text = signText + body;",
+    "  const pad = Math.max(w - text.length, 0);",
+    "  const a = align || (numeric ? \">\" : \"<\");",
+    "  const left = a === \">\" ? pad : a === \"^\" ? Math.floor(pad / 2) : 0;",
+    "  const f = fill || (zero ? \"0\" : \" \");",
+    "  return f.repeat(left) + text + f.repeat(pad - left);",
+    "}",
+    "// toFixed and toExponential round exact ties up where Python rounds them",
+    "// to even: when `s`, rounded to `q` decimals, came from a tie and ends in",
+    "// an odd digit, step it back down",
+    "function _even(x$ANY, q$ANY, s$ANY) {",
+    "  const y = q >= 0 ? x * 2 ** (q + 1) : q >= -22 && (x * 2) % 10 ** -q === 0 ? (x * 2) / 10 ** -q : 0;",
+    "  const d = +s[s.length - 1];",
+    "  return y % 2 === 1 && d % 2 === 1 ? s.slice(0, -1) + (d - 1) : s;",
+    "}",
+    "function _fixed(x$ANY, p$ANY) {",
+    "  // toFixed switches to exponents from 1e21",
+    "  if (x >= 1e21) return BigInt(x).toString() + (p ? \".\" + \"0\".repeat(p) : \"\");",
+    "  return _even(x, p, x.toFixed(p));",
+    "}",
+    "// Python's repr: the shortest digits, with an exponent below 1e-4 and from 1e16",
+    "function _repr(x$ANY) {",
+    "  if (x >= 1e-4 && x < 1e16) return String(x);",
+    "  const [mant, e] = x.toExponential().split(\"e\");",
+    "  return mant + \"e\" + (+e < 0 ? \"-\" : \"+\") + String(Math.abs(+e)).padStart(2, \"0\");",
+    "}",
+    "function _exp(x$ANY, p$ANY) {",
+    "  const [mant, e] = x.toExponential(p).split(\"e\");",
+    "  return _even(x, p - +e, mant) + \"e\" + (+e < 0 ? \"-\" : \"+\") + String(Math.abs(+e)).padStart(2, \"0\");",
+    "}",
+    "function _fmtTemplate(t$ANY, a$ANY) {",
+    "  let n = 0;",
+    "  return String(t).replace(/\\{\\{|\\}\\}|\\{([^:}]*)(?::([^}]*))?\\}/g, (m$ANY, i$ANY, spec$ANY) => {",
+    "    if (i === undefined) return m[0];",
+    "    if (!/^\\s*\\d*\\s*$/.test(i)) throw new Error(`invalid format field '${m}'`);",
+    "    return _fmt(a[i.trim() === \"\" ? n++ : +i], spec || \"\");",
+    "  });",
+    "}",
+];
+
+/// Runtime for `date.format`: strftime in UTC
+const DATE_HELPERS: &[&str] = &[
+    "function _dateFormat(ts$ANY, p$ANY = \"%Y-%m-%d %H:%M:%S\") {",
+    "  const d = new Date(Math.floor(Number(ts)) * 1000);",
+    "  const z = (n$ANY, w = 2) => String(n).padStart(w, \"0\");",
+    "  const days = [\"Sunday\", \"Monday\", \"Tuesday\", \"Wednesday\", \"Thursday\", \"Friday\", \"Saturday\"];",
+    "  const months = [\"January\", \"February\", \"March\", \"April\", \"May\", \"June\", \"July\", \"August\",",
+    "    \"September\", \"October\", \"November\", \"December\"];",
+    "  const [Y, m, D, H, M, S] = [d.getUTCFullYear(), d.getUTCMonth(), d.getUTCDate(),",
+    "    d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds()];",
+    "  const f$ANY = {",
+    "    Y, y: z(Y % 100), m: z(m + 1), d: z(D), H: z(H), I: z((H + 11) % 12 + 1), M: z(M), S: z(S),",
+    "    p: H < 12 ? \"AM\" : \"PM\", j: z(Math.floor((d.getTime() - Date.UTC(Y, 0, 1)) / 864e5) + 1, 3),",
+    "    a: days[d.getUTCDay()].slice(0, 3), A: days[d.getUTCDay()], b: months[m].slice(0, 3), B: months[m],",
+    "    F: `${Y}-${z(m + 1)}-${z(D)}`, T: `${z(H)}:${z(M)}:${z(S)}`, \"%\": \"%\",",
+    "  };",
+    "  return String(p).replace(/%(.)/g, (s$ANY, c$ANY) => (c in f ? String(f[c]) : s));",
+    "}",
+];
 
 /// Sui to JavaScript transpiler
 pub struct Sui2Js {
@@ -12,6 +124,12 @@ pub struct Sui2Js {
     nodejs: bool,
     /// Whether to generate ES modules
     esm: bool,
+    /// Whether to generate TypeScript with inferred variable types
+    typescript: bool,
+    /// Try handlers `(err, label)` of the state machine being emitted
+    handlers: Vec<(String, i64)>,
+    /// Result of dividing by zero, as in the interpreter
+    division_policy: DivisionPolicy,
 }
 
 impl Default for Sui2Js {
@@ -28,9 +146,17 @@ impl Sui2Js {
             output: Vec::new(),
             nodejs: true,
             esm: false,
+            typescript: false,
+            handlers: Vec::new(),
+            division_policy: DivisionPolicy::default(),
         }
     }
 
+    /// Choose what `/` and `%` do when the divisor is zero
+    pub fn set_division_policy(&mut self, policy: DivisionPolicy) {
+        self.division_policy = policy;
+    }
+
     /// Set Node.js compatibility mode
     pub fn set_nodejs(&mut self, nodejs: bool) {
         self.nodejs = nodejs;
@@ -41,6 +167,12 @@ impl Sui2Js {
         self.esm = esm;
     }
 
+    /// Set TypeScript mode, declaring variables with the types
+    /// [`infer_types`] finds
+    pub fn set_typescript(&mut self, typescript: bool) {
+        self.typescript = typescript;
+    }
+
     /// Emit a line with current indentation
     fn emit(&mut self, line: &str) {
         let indent_str = "  ".repeat(self.indent);
@@ -52,120 +184,254 @@ impl Sui2Js {
         val.to_string()
     }
 
-    /// Transpile a block of instructions
-    fn transpile_block(&mut self, instructions: &[Instruction], is_function: bool) {
-        // Collect labels
-        let labels: HashSet<i64> = instructions
-            .iter()
-            .filter_map(|instr| {
-                if let Instruction::Label { id } = instr {
-                    Some(*id)
-                } else {
-                    None
-                }
-            })
-            .collect();
+    /// JavaScript subscripts for an array access, such as `[Math.floor(v1)][Math.floor(2)]`
+    fn subscripts(&self, idx: &str, indices: &[String]) -> String {
+        std::iter::once(idx)
+            .chain(indices.iter().map(String::as_str))
+            .map(|i| format!("[Math.floor({})]", self.resolve_value(i)))
+            .collect()
+    }
 
-        // Use state machine pattern if labels exist
-        if !labels.is_empty() {
-            self.emit("let _state = -1;");
-            self.emit("while (true) {");
+    /// Transpile a lowered function body or the main program
+    fn transpile_cfg(&mut self, cfg: &Cfg, is_function: bool) {
+        let region = cfg.structure();
+        // `return` is not allowed at top level, so the main program is
+        // wrapped in a labeled block that `^` breaks out of
+        let returns = !is_function
+            && cfg
+                .blocks()
+                .iter()
+                .any(|b| matches!(b.terminator, Terminator::Return(_)));
+        if returns {
+            self.emit("_main: {");
             self.indent += 1;
-            self.emit("_state++;");
-
-            // Map labels to state numbers
-            let mut state_map: HashMap<i64, usize> = HashMap::new();
-            state_map.insert(-1, 0);
-            let mut state_num = 1;
+        }
+        self.transpile_region(cfg, &region, is_function);
+        if returns {
+            self.indent -= 1;
+            self.emit("}");
+        }
+    }
 
-            for label in labels.iter() {
-                state_map.insert(*label, state_num);
-                state_num += 1;
+    /// Transpile a structured region
+    fn transpile_region(&mut self, cfg: &Cfg, region: &Region, is_function: bool) {
+        match region {
+            Region::Block(id) => {
+                for instr in &cfg.block(*id).instructions {
+                    self.transpile_instruction(instr);
+                }
             }
-
-            // Group instructions by state
-            let mut states: HashMap<usize, Vec<&Instruction>> = HashMap::new();
-            states.insert(0, Vec::new());
-            let mut current = 0;
-
-            for instr in instructions {
-                match instr {
-                    Instruction::Label { id } => {
-                        current = *state_map.get(id).unwrap_or(&0);
-                        states.entry(current).or_default();
+            Region::Seq(items) => {
+                for item in items {
+                    self.transpile_region(cfg, item, is_function);
+                }
+            }
+            Region::If {
+                cond,
+                negate,
+                then_region,
+                else_region,
+            } => {
+                let cond = self.resolve_value(cond);
+                if *negate {
+                    self.emit(&format!("if (!{}) {{", cond));
+                } else {
+                    self.emit(&format!("if ({}) {{", cond));
+                }
+                self.indent += 1;
+                self.transpile_region(cfg, then_region, is_function);
+                self.indent -= 1;
+                if let Some(else_region) = else_region {
+                    self.emit("} else {");
+                    self.indent += 1;
+                    self.transpile_region(cfg, else_region, is_function);
+                    self.indent -= 1;
+                }
+                self.emit("}");
+            }
+            Region::Switch { value, cases, default } => {
+                if cases.is_empty() {
+                    return self.transpile_region(cfg, default, is_function);
+                }
+                let value = self.resolve_value(value);
+                let has_default = !matches!(default.as_ref(), Region::Seq(items) if items.is_empty());
+                if cases.iter().any(|(_, body)| body.breaks_out()) || default.breaks_out() {
+                    // `break` inside `switch` would not leave the loop
+                    for (k, (values, body)) in cases.iter().enumerate() {
+                        let test: Vec<String> = values.iter().map(|v| format!("{} === {}", value, v)).collect();
+                        let keyword = if k == 0 { "if" } else { "} else if" };
+                        self.emit(&format!("{} ({}) {{", keyword, test.join(" || ")));
+                        self.indent += 1;
+                        self.transpile_region(cfg, body, is_function);
+                        self.indent -= 1;
+                    }
+                    if has_default {
+                        self.emit("} else {");
+                        self.indent += 1;
+                        self.transpile_region(cfg, default, is_function);
+                        self.indent -= 1;
+                    }
+                    self.emit("}");
+                } else {
+                    self.emit(&format!("switch ({}) {{", value));
+                    self.indent += 1;
+                    for (values, body) in cases {
+                        for v in values {
+                            self.emit(&format!("case {}:", v));
+                        }
+                        self.indent += 1;
+                        self.transpile_region(cfg, body, is_function);
+                        if !body.ends_with_transfer() {
+                            self.emit("break;");
+                        }
+                        self.indent -= 1;
                     }
-                    Instruction::FuncEnd => {}
-                    _ => {
-                        states.entry(current).or_default().push(instr);
+                    if has_default {
+                        self.emit("default:");
+                        self.indent += 1;
+                        self.transpile_region(cfg, default, is_function);
+                        self.indent -= 1;
                     }
+                    self.indent -= 1;
+                    self.emit("}");
                 }
             }
-
-            // Generate code for each state
-            let mut sorted_states: Vec<_> = states.keys().copied().collect();
-            sorted_states.sort();
-
-            self.emit("switch (_state) {");
-            self.indent += 1;
-
-            for state_id in sorted_states {
-                self.emit(&format!("case {}:", state_id));
+            Region::Loop(body) => {
+                self.emit("while (true) {");
                 self.indent += 1;
-
-                let state_lines = states.get(&state_id).map(|v| v.as_slice()).unwrap_or(&[]);
-                for instr in state_lines {
-                    self.transpile_instruction(instr, &state_map, is_function);
+                self.transpile_region(cfg, body, is_function);
+                self.indent -= 1;
+                self.emit("}");
+            }
+            Region::Break => self.emit("break;"),
+            Region::Continue => self.emit("continue;"),
+            Region::Return(value) => self.transpile_return(value, is_function),
+            Region::Dispatch(blocks) => {
+                // State machine over the basic blocks
+                let handlers = cfg.handlers();
+                self.emit(&format!("let _state = {};", cfg.entry()));
+                if !handlers.is_empty() {
+                    self.emit("const _handlers = [];");
                 }
-
-                // State transition
-                let needs_transition = state_lines.is_empty()
-                    || !matches!(
-                        state_lines.last(),
-                        Some(Instruction::CondJump { .. })
-                            | Some(Instruction::Jump { .. })
-                            | Some(Instruction::Return { .. })
-                    );
-
-                if needs_transition {
-                    let next_state = state_id + 1;
-                    if states.contains_key(&next_state) {
-                        self.emit(&format!("_state = {} - 1;", next_state));
-                        self.emit("continue;");
-                    } else {
-                        self.emit("break;");
+                self.emit("_dispatch: while (true) {");
+                self.indent += 1;
+                if !handlers.is_empty() {
+                    self.emit("try {");
+                    self.indent += 1;
+                }
+                let outer = std::mem::replace(&mut self.handlers, handlers);
+                self.emit("switch (_state) {");
+                self.indent += 1;
+                for &id in blocks {
+                    self.emit(&format!("case {}:", id));
+                    self.indent += 1;
+                    for instr in &cfg.block(id).instructions {
+                        self.transpile_instruction(instr);
+                    }
+                    match &cfg.block(id).terminator {
+                        Terminator::Goto(target) => self.transpile_transition(cfg, *target),
+                        Terminator::Branch {
+                            cond,
+                            target,
+                            fallthrough,
+                        } => {
+                            self.emit(&format!("if ({}) {{", self.resolve_value(cond)));
+                            self.indent += 1;
+                            self.transpile_transition(cfg, *target);
+                            self.indent -= 1;
+                            self.emit("}");
+                            self.transpile_transition(cfg, *fallthrough);
+                        }
+                        terminator @ Terminator::Switch { value, fallthrough, .. } => {
+                            self.emit(&format!("switch ({}) {{", self.resolve_value(value)));
+                            self.indent += 1;
+                            for (values, target) in terminator.switch_cases() {
+                                for v in values {
+                                    self.emit(&format!("case {}:", v));
+                                }
+                                self.indent += 1;
+                                self.transpile_transition(cfg, target);
+                                self.indent -= 1;
+                            }
+                            self.indent -= 1;
+                            self.emit("}");
+                            self.transpile_transition(cfg, *fallthrough);
+                        }
+                        Terminator::Return(value) => self.transpile_return(value, is_function),
                     }
+                    self.indent -= 1;
                 }
-
                 self.indent -= 1;
+                self.emit("}");
+                let handlers = std::mem::replace(&mut self.handlers, outer);
+                if !handlers.is_empty() {
+                    self.indent -= 1;
+                    self.transpile_handlers(cfg, &handlers);
+                }
+                self.emit("break;");
+                self.indent -= 1;
+                self.emit("}");
             }
+        }
+    }
 
+    /// Unwind to the innermost registered handler of a state machine
+    fn transpile_handlers(&mut self, cfg: &Cfg, handlers: &[(String, i64)]) {
+        self.emit("} catch (_e) {");
+        self.indent += 1;
+        self.emit("if (_handlers.length === 0) throw _e;");
+        self.emit("const _err = _e instanceof Error ? _e.message : _e;");
+        self.emit("switch (_handlers.pop()) {");
+        self.indent += 1;
+        for (k, (err, label)) in handlers.iter().enumerate() {
+            self.emit(&format!("case {}:", k));
+            self.indent += 1;
+            self.emit(&format!("{} = _err;", err));
+            // A handler without a defined label leaves the code
+            let target = cfg.label_block(*label).unwrap_or_else(|| cfg.exit());
+            self.transpile_transition(cfg, target);
             self.indent -= 1;
-            self.emit("}");
-            self.emit("break;");
-            self.indent -= 1;
-            self.emit("}");
+        }
+        self.indent -= 1;
+        self.emit("}");
+        self.indent -= 1;
+        self.emit("}");
+    }
+
+    /// Move the state machine to another block
+    fn transpile_transition(&mut self, cfg: &Cfg, target: BlockId) {
+        if target == cfg.exit() {
+            self.emit("break _dispatch;");
         } else {
-            // Simple case: no labels
-            for instr in instructions {
-                if !matches!(instr, Instruction::FuncEnd) {
-                    self.transpile_instruction(instr, &HashMap::new(), is_function);
-                }
-            }
+            self.emit(&format!("_state = {};", target));
+            self.emit("continue;");
+        }
+    }
+
+    /// Return from a function, or stop the program at top level
+    fn transpile_return(&mut self, value: &str, is_function: bool) {
+        if is_function {
+            self.emit(&format!("return {};", self.resolve_value(value)));
+        } else {
+            self.emit("break _main;");
         }
     }
 
     /// Transpile a single instruction
-    fn transpile_instruction(
-        &mut self,
-        instr: &Instruction,
-        state_map: &HashMap<i64, usize>,
-        _is_function: bool,
-    ) {
+    fn transpile_instruction(&mut self, instr: &Instruction) {
         match instr {
-            Instruction::Empty | Instruction::Comment | Instruction::Label { .. } | Instruction::Import { .. } => {
+            Instruction::Empty
+            | Instruction::Comment
+            | Instruction::Const { .. }
+            | Instruction::Label { .. }
+            | Instruction::Import { .. } => {
                 // Import is handled at runtime, skip in transpilation
             }
 
+            Instruction::Invalid { raw, .. } => {
+                self.emit(&format!("// invalid: {}", raw));
+            }
+
             Instruction::Assign { target, value } => {
                 self.emit(&format!("{} = {};", target, self.resolve_value(value)));
             }
@@ -188,45 +454,51 @@ impl Sui2Js {
                 ));
             }
 
+            // Sui integers have no -0, which `0 * -1` gives in JavaScript;
+            // adding 0 turns it into 0
             Instruction::Mul { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} * {};",
+                    "{} = {} * {} + 0;",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Div { result, a, b } => {
+            Instruction::Div { result, a, b } => self.transpile_division(result, a, "/", b),
+
+            Instruction::Mod { result, a, b } => self.transpile_division(result, a, "%", b),
+
+            Instruction::Lt { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} / {};",
+                    "{} = {} < {} ? 1 : 0;",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Mod { result, a, b } => {
+            Instruction::Gt { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} % {};",
+                    "{} = {} > {} ? 1 : 0;",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Lt { result, a, b } => {
+            Instruction::Le { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} < {} ? 1 : 0;",
+                    "{} = {} <= {} ? 1 : 0;",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Gt { result, a, b } => {
+            Instruction::Ge { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} > {} ? 1 : 0;",
+                    "{} = {} >= {} ? 1 : 0;",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
@@ -264,25 +536,13 @@ impl Sui2Js {
                 ));
             }
 
-            Instruction::CondJump { cond, label } => {
-                if let Some(&state) = state_map.get(label) {
-                    self.emit(&format!("if ({}) {{", self.resolve_value(cond)));
-                    self.indent += 1;
-                    self.emit(&format!("_state = {} - 1;", state));
-                    self.emit("continue;");
-                    self.indent -= 1;
-                    self.emit("}");
-                }
-            }
-
-            Instruction::Jump { label } => {
-                if let Some(&state) = state_map.get(label) {
-                    self.emit(&format!("_state = {} - 1;", state));
-                    self.emit("continue;");
-                }
-            }
-
-            Instruction::FuncDef { .. } | Instruction::FuncEnd => {}
+            // Control flow is lowered through the IR
+            Instruction::CondJump { .. }
+            | Instruction::Jump { .. }
+            | Instruction::Switch { .. }
+            | Instruction::Return { .. }
+            | Instruction::FuncDef { .. }
+            | Instruction::FuncEnd => {}
 
             Instruction::Call { result, func_id, args } => {
                 let args_str = args
@@ -293,10 +553,6 @@ impl Sui2Js {
                 self.emit(&format!("{} = f{}({});", result, func_id, args_str));
             }
 
-            Instruction::Return { value } => {
-                self.emit(&format!("return {};", self.resolve_value(value)));
-            }
-
             Instruction::ArrayCreate { var, size } => {
                 self.emit(&format!(
                     "{} = new Array({}).fill(0);",
@@ -305,24 +561,56 @@ impl Sui2Js {
                 ));
             }
 
-            Instruction::ArrayRead { result, arr, idx } => {
+            Instruction::ArrayRead { result, arr, idx, indices } => {
                 self.emit(&format!(
-                    "{} = {}[Math.floor({})];",
+                    "{} = {}{};",
                     result,
                     self.resolve_value(arr),
-                    self.resolve_value(idx)
+                    self.subscripts(idx, indices)
                 ));
             }
 
-            Instruction::ArrayWrite { arr, idx, value } => {
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
                 self.emit(&format!(
-                    "{}[Math.floor({})] = {};",
+                    "{}{} = {};",
                     self.resolve_value(arr),
-                    self.resolve_value(idx),
+                    self.subscripts(idx, indices),
                     self.resolve_value(value)
                 ));
             }
 
+            Instruction::Iter { iter, source } => {
+                self.emit(&format!(
+                    "{0} = (typeof {1} === \"number\" ? Array.from({{ length: {1} }}, (_, i) => i) : [...{1}])\
+                     [Symbol.iterator]();",
+                    iter,
+                    self.resolve_value(source)
+                ));
+            }
+
+            Instruction::Next { done, var, iter } => {
+                self.emit("{");
+                self.indent += 1;
+                self.emit(&format!("const _next = {}.next();", iter));
+                self.emit(&format!("{} = _next.done ? 1 : 0;", done));
+                self.emit(&format!("if (!_next.done) {} = _next.value;", var));
+                self.indent -= 1;
+                self.emit("}");
+            }
+
+            Instruction::Try { err, label } => {
+                let k = self.handlers.iter().position(|(e, l)| e == err && l == label);
+                self.emit(&format!("_handlers.push({});", k.unwrap_or_default()));
+            }
+
+            Instruction::EndTry => {
+                self.emit("_handlers.pop();");
+            }
+
+            Instruction::Raise { value } => {
+                self.emit(&format!("throw {};", self.resolve_value(value)));
+            }
+
             Instruction::Output { value } => {
                 self.emit(&format!("console.log({});", self.resolve_value(value)));
             }
@@ -349,6 +637,11 @@ impl Sui2Js {
                 // Remove quotes if present
                 let func_clean = func_str.trim_matches('"');
 
+                if let ("floordiv", [a, b, ..]) = (func_clean, args.as_slice()) {
+                    self.transpile_division(result, a, "//", b);
+                    return;
+                }
+
                 // Map Python/Rust functions to JavaScript equivalents
                 let js_call = match func_clean {
                     // Math functions
@@ -361,6 +654,10 @@ impl Sui2Js {
                     "math.floor" => format!("Math.floor({})", args_str),
                     "math.ceil" => format!("Math.ceil({})", args_str),
                     "math.round" | "round" => format!("Math.round({})", args_str),
+                    // Exact only up to 2^53, like every integer in the output
+                    "ipow" if args.len() >= 2 => {
+                        format!("{} ** {}", self.resolve_value(&args[0]), self.resolve_value(&args[1]))
+                    }
                     "max" => format!("Math.max({})", args_str),
                     "min" => format!("Math.min({})", args_str),
                     // String/type functions
@@ -371,6 +668,12 @@ impl Sui2Js {
                             "0".to_string()
                         }
                     }
+                    "fmt" => self.template_literal(args).unwrap_or_else(|| {
+                        let values: Vec<String> = args.iter().skip(1).map(|a| self.resolve_value(a)).collect();
+                        let template = args.first().map_or("\"\"".to_string(), |t| self.resolve_value(t));
+                        format!("_fmtTemplate({}, [{}])", template, values.join(", "))
+                    }),
+                    "date.format" => format!("_dateFormat({})", args_str),
                     "int" => format!("parseInt({})", args_str),
                     "float" => format!("parseFloat({})", args_str),
                     "str" => format!("String({})", args_str),
@@ -387,14 +690,15 @@ impl Sui2Js {
                             "0".to_string()
                         }
                     }
-                    // Default: try to call as-is
-                    _ => {
-                        if func_clean.contains('.') {
-                            format!("{}({})", func_clean, args_str)
-                        } else {
-                            format!("{}({})", func_clean, args_str)
-                        }
-                    }
+                    // Array functions, else try to call as-is
+                    _ => self
+                        .time_builtin(func_clean, args)
+                        .or_else(|| self.regex_builtin(func_clean, args))
+                        .or_else(|| self.data_builtin(func_clean, args))
+                        .or_else(|| self.array_builtin(func_clean, args))
+                        .or_else(|| self.bitwise_builtin(func_clean, args))
+                        .or_else(|| self.string_builtin(func_clean, args))
+                        .unwrap_or_else(|| format!("{}({})", func_clean, args_str)),
                 };
 
                 self.emit(&format!("{} = {};", result, js_call));
@@ -402,22 +706,236 @@ impl Sui2Js {
         }
     }
 
+    /// `/`, `%` or floor division (`//`) with the zero divisor result of the
+    /// division policy
+    fn transpile_division(&mut self, result: &str, a: &str, op: &str, b: &str) {
+        let (a, b) = (self.resolve_value(a), self.resolve_value(b));
+        let expr = match op {
+            "//" => format!("Math.floor({} / {})", a, b),
+            // `-4 % 2` is -0, like `0 * -1`
+            "%" => format!("{} % {} + 0", a, b),
+            _ => format!("{} {} {}", a, op, b),
+        };
+        let fallback = match self.division_policy {
+            _ if nonzero_literal(&b) => None,
+            // `x % 0` is already NaN
+            DivisionPolicy::SilentNaN if op == "%" => None,
+            DivisionPolicy::SilentNaN => Some("NaN"),
+            DivisionPolicy::Error => {
+                self.emit(&format!("if ({} == 0) throw new Error(\"Division by zero\");", b));
+                None
+            }
+            DivisionPolicy::SaturateZero => Some("0"),
+        };
+        match fallback {
+            Some(fallback) => {
+                self.emit(&format!("{} = {} != 0 ? {} : {};", result, b, expr, fallback))
+            }
+            None => self.emit(&format!("{} = {};", result, expr)),
+        }
+    }
+
+    /// Expression for a bitwise builtin such as `band`, computed on 64-bit
+    /// BigInts since JS bitwise operators truncate to 32 bits
+    fn bitwise_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let big = |i: usize| {
+            let arg = args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+            format!("BigInt(Math.trunc({}))", arg)
+        };
+        let expr = match func {
+            "band" => format!("{} & {}", big(0), big(1)),
+            "bor" => format!("{} | {}", big(0), big(1)),
+            "bxor" => format!("{} ^ {}", big(0), big(1)),
+            "bnot" => format!("~{}", big(0)),
+            "shl" => format!("{} << ({} & 63n)", big(0), big(1)),
+            "shr" => format!("{} >> ({} & 63n)", big(0), big(1)),
+            _ => return None,
+        };
+        Some(format!("Number(BigInt.asIntN(64, {}))", expr))
+    }
+
+    /// Expression for a string builtin such as `split`, matching Python's
+    /// str methods
+    fn string_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("\"\"".to_string(), |a| self.resolve_value(a));
+        let words = format!("String({}).split(/\\s+/).filter(Boolean)", arg(0));
+        let expr = match func {
+            "upper" => format!("String({}).toUpperCase()", arg(0)),
+            "lower" => format!("String({}).toLowerCase()", arg(0)),
+            "strip" => format!("String({}).trim()", arg(0)),
+            "split" if args.len() < 2 => words,
+            "split" => format!("({1} ? String({0}).split({1}) : {2})", arg(0), arg(1), words),
+            "join" => format!("[...{}].join({})", arg(0), arg(1)),
+            "replace" => format!("String({}).split({}).join({})", arg(0), arg(1), arg(2)),
+            "find" => format!("String({}).indexOf({})", arg(0), arg(1)),
+            "contains" => format!("({}.includes({}) ? 1 : 0)", arg(0), arg(1)),
+            "startswith" => format!("(String({}).startsWith({}) ? 1 : 0)", arg(0), arg(1)),
+            "endswith" => format!("(String({}).endsWith({}) ? 1 : 0)", arg(0), arg(1)),
+            // `{}` takes the next argument, `{n}` argument n; `{{`/`}}` escape
+            "format" => {
+                let values: Vec<String> = args.iter().skip(1).map(|a| self.resolve_value(a)).collect();
+                format!(
+                    "((f, a, n = 0) => f.replace(/\\{{\\{{|\\}}\\}}|\\{{(\\d*)\\}}/g, (m, i) => \
+                     i === undefined ? m[0] : String(a[i === \"\" ? n++ : +i])))(String({}), [{}])",
+                    arg(0),
+                    values.join(", ")
+                )
+            }
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Template literal for `fmt` with a literal template
+    fn template_literal(&self, args: &[String]) -> Option<String> {
+        let parts = template_parts(args.first()?)?;
+        let mut result = String::from("`");
+        for part in parts {
+            match part {
+                TemplatePart::Text(text) => result.push_str(&text.replace('`', "\\`").replace("${", "\\${")),
+                TemplatePart::Field { arg, spec } => {
+                    let expr = self.resolve_value(args.get(arg + 1)?);
+                    if spec.is_empty() {
+                        result.push_str(&format!("${{{}}}", expr));
+                    } else {
+                        result.push_str(&format!("${{_fmt({}, {:?})}}", expr, spec));
+                    }
+                }
+            }
+        }
+        result.push('`');
+        Some(result)
+    }
+
+    /// Emit the runtime helpers of the builtins `program` calls
+    fn emit_helpers(&mut self, program: &Program) {
+        let called = |name: &str| {
+            std::iter::once(&program.main)
+                .chain(program.functions.iter().map(|f| &f.body))
+                .flat_map(|cfg| cfg.blocks())
+                .flat_map(|b| &b.instructions)
+                .any(|instr| matches!(instr, Instruction::RustFFI { func, .. } if func.trim_matches('"') == name))
+        };
+        let mut helpers: Vec<&str> = Vec::new();
+        if called("fmt") {
+            helpers.extend(FMT_HELPERS);
+        }
+        if called("date.format") {
+            helpers.extend(DATE_HELPERS);
+        }
+        if helpers.is_empty() {
+            return;
+        }
+        let any = if self.typescript { ": any" } else { "" };
+        for line in helpers {
+            self.emit(&line.replace("$ANY", any));
+        }
+        self.emit("");
+    }
+
+    /// Expression for a time builtin such as `time.now`, in seconds like
+    /// Python's `time` module
+    fn time_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        let expr = match func.rsplit('.').next().unwrap_or(func) {
+            "now" => "Date.now() / 1000".to_string(),
+            "monotonic" => "performance.now() / 1000".to_string(),
+            // Blocks the thread, as the interpreter does (Node.js and workers)
+            "sleep_ms" => format!(
+                "(Atomics.wait(new Int32Array(new SharedArrayBuffer(4)), 0, 0, Math.max(Number({}), 0)), 0)",
+                arg(0)
+            ),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// `RegExp` expression for a regex builtin such as `re.findall`
+    fn regex_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("\"\"".to_string(), |a| self.resolve_value(a));
+        let (pattern, text) = (arg(0), arg(1));
+        let expr = match func {
+            // Sticky: only at the start, like Python's re.match
+            "re.match" => format!("(new RegExp({}, \"y\").test(String({})) ? 1 : 0)", pattern, text),
+            "re.find" => format!("(String({}).match(new RegExp({})) ?? [0])[0]", text, pattern),
+            "re.findall" => format!("[...String({}).matchAll(new RegExp({}, \"g\"))].map((m) => m[0])", text, pattern),
+            // A function keeps the replacement literal
+            "re.replace" => format!("String({}).replace(new RegExp({}, \"g\"), () => String({}))", text, pattern, arg(2)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Object or `JSON` expression for a `struct.*` or `json.*` builtin
+    fn data_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        let expr = match func {
+            "struct.new" => {
+                let fields: Vec<String> =
+                    (0..args.len()).step_by(2).map(|i| format!("[{}]: {}", arg(i), arg(i + 1))).collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            // A missing field reads 0, as in the interpreter
+            "struct.get" => format!("({}[{}] ?? 0)", arg(0), arg(1)),
+            "struct.set" => format!("{{...{}, [{}]: {}}}", arg(0), arg(1), arg(2)),
+            "json.encode" => format!("JSON.stringify({})", arg(0)),
+            "json.decode" => format!("JSON.parse({})", arg(0)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Native array expression for an array builtin such as `slice`
+    fn array_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        let expr = match func {
+            "array2d" => {
+                format!("Array.from({{ length: {} }}, () => new Array({}).fill(0))", arg(0), arg(1))
+            }
+            "slice" => match args.len() {
+                0 | 1 => format!("{}.slice()", arg(0)),
+                2 => format!("{}.slice(Math.floor({}))", arg(0), arg(1)),
+                _ => format!("{}.slice(Math.floor({}), Math.floor({}))", arg(0), arg(1), arg(2)),
+            },
+            "concat" => format!("{}.concat({})", arg(0), arg(1)),
+            "fill" => format!("new Array({}.length).fill({})", arg(0), arg(1)),
+            "copy" => {
+                format!("{1}.slice(0, {0}.length).concat({0}.slice({1}.length))", arg(0), arg(1))
+            }
+            "sum" => format!("{}.reduce((x, y) => x + y, 0)", arg(0)),
+            "sort" => format!("[...{}].sort((x, y) => (x < y ? -1 : x > y ? 1 : 0))", arg(0)),
+            "reverse" => format!(
+                "(typeof {0} === \"string\" ? [...{0}].reverse().join(\"\") : [...{0}].reverse())",
+                arg(0)
+            ),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
     /// Transpile Sui code to JavaScript
     pub fn transpile_to_js(&mut self, code: &str) -> Result<String, TranspileError> {
         self.output.clear();
         self.indent = 0;
 
-        // Parse the code
-        let (instructions, functions) =
-            Parser::parse(code).map_err(|e| TranspileError::Parse(e.to_string()))?;
+        // Parse and lower the code
+        let program = Program::from_source(code).map_err(|e| TranspileError::Parse(e.to_string()))?;
+        check_templates(&program)?;
+
+        let types = self.typescript.then(|| infer_types(code));
 
         // Header
         self.emit("// Auto-generated from Sui");
+        if self.typescript {
+            self.emit("// TypeScript");
+        }
         if self.esm {
             self.emit("// ES Module");
         }
         self.emit("");
 
+        self.emit_helpers(&program);
+
         // Global variables from command-line arguments
         self.emit("// Global variables from command-line arguments");
         if self.nodejs {
@@ -425,37 +943,74 @@ impl Sui2Js {
         } else {
             self.emit("const _args = [];");
         }
-        self.emit("let g100 = _args.length;");
-        self.emit("for (let _i = 0; _i < _args.length; _i++) {");
+        let global_this = if self.typescript { "(globalThis as any)" } else { "globalThis" };
+        self.emit(if self.typescript { "let g100: number = _args.length;" } else { "let g100 = _args.length;" });
+        // Arguments the program reads but was not given are 0, as in the interpreter
+        let arg_count = code
+            .lines()
+            .flat_map(Lexer::tokenize_line)
+            .filter_map(|token| token.strip_prefix('g').and_then(|n| n.parse::<i64>().ok()))
+            .filter(|&n| n > 100)
+            .max()
+            .map_or(0, |n| n - 100);
+        if arg_count > 0 {
+            self.emit(&format!("for (let _i = 0; _i < Math.max(_args.length, {}); _i++) {{", arg_count));
+        } else {
+            self.emit("for (let _i = 0; _i < _args.length; _i++) {");
+        }
         self.indent += 1;
-        self.emit("const _val = parseInt(_args[_i]);");
-        self.emit("globalThis[`g${101 + _i}`] = isNaN(_val) ? _args[_i] : _val;");
+        self.emit("const _arg = _i < _args.length ? _args[_i] : \"0\";");
+        // Numbers parse like the interpreter's arguments: all of the text or nothing
+        self.emit(&format!(
+            "{}[`g${{101 + _i}}`] = /^[+-]?(\\d+\\.?\\d*|\\.\\d+)([eE][+-]?\\d+)?$/.test(_arg) ? Number(_arg) : _arg;",
+            global_this
+        ));
         self.indent -= 1;
         self.emit("}");
         self.emit("");
 
         // Declare all variables
         self.emit("// Variable declarations");
-        self.emit("let v0, v1, v2, v3, v4, v5, v6, v7, v8, v9;");
-        self.emit("let g0, g1, g2, g3, g4, g5, g6, g7, g8, g9;");
+        match &types {
+            Some(types) => {
+                self.declare_typed(types.locals(None));
+                self.declare_typed(types.globals());
+            }
+            None => {
+                self.emit("let v0, v1, v2, v3, v4, v5, v6, v7, v8, v9;");
+                self.emit("let g0, g1, g2, g3, g4, g5, g6, g7, g8, g9;");
+            }
+        }
         self.emit("");
 
         // Output function definitions
-        for func in &functions {
+        for func in &program.functions {
+            let arg_type = |i: i64| match &types {
+                Some(types) => {
+                    let arg = types.var(Some(func.id), &format!("a{}", i)).unwrap_or(TypeSet::UNKNOWN);
+                    format!(": {}", ts_type(arg))
+                }
+                None => String::new(),
+            };
             let args_str = (0..func.arg_count)
-                .map(|i| format!("a{}", i))
+                .map(|i| format!("a{}{}", i, arg_type(i)))
                 .collect::<Vec<_>>()
                 .join(", ");
-            self.emit(&format!("function f{}({}) {{", func.id, args_str));
+            let return_type = match &types {
+                Some(types) => format!(": {}", ts_type(types.returns(func.id).unwrap_or(TypeSet::UNKNOWN))),
+                None => String::new(),
+            };
+            self.emit(&format!("function f{}({}){} {{", func.id, args_str, return_type));
             self.indent += 1;
 
             // Declare local variables
-            self.emit("let v0, v1, v2, v3, v4, v5, v6, v7, v8, v9;");
-
-            if !func.body.is_empty() {
-                self.transpile_block(&func.body, true);
+            match &types {
+                Some(types) => self.declare_typed(types.locals(Some(func.id))),
+                None => self.emit("let v0, v1, v2, v3, v4, v5, v6, v7, v8, v9;"),
             }
 
+            self.transpile_cfg(&func.body, true);
+
             self.indent -= 1;
             self.emit("}");
             self.emit("");
@@ -463,12 +1018,41 @@ impl Sui2Js {
 
         // Output main code
         self.emit("// Main");
-        if !instructions.is_empty() {
-            self.transpile_block(&instructions, false);
-        }
+        self.transpile_cfg(&program.main, false);
 
         Ok(self.output.join("\n"))
     }
+
+    /// Declare variables with TypeScript types; arguments are parameters and
+    /// command-line arguments are set through `globalThis`
+    fn declare_typed<'a>(&mut self, vars: impl Iterator<Item = (&'a str, TypeSet)>) {
+        for (name, types) in vars {
+            let (prefix, idx) = Lexer::split_variable(name);
+            match (prefix, idx) {
+                ('a', _) | ('g', 100) => {}
+                ('g', 101..) => self.emit(&format!("declare let {}: {};", name, ts_type(types))),
+                _ => self.emit(&format!("let {}: {};", name, ts_type(types))),
+            }
+        }
+    }
+}
+
+/// TypeScript type for a set of Sui types
+fn ts_type(types: TypeSet) -> String {
+    if types == TypeSet::UNKNOWN || types.is_empty() {
+        return "any".to_string();
+    }
+    let mut names = Vec::new();
+    if types.intersects(TypeSet::NUMBER) {
+        names.push("number");
+    }
+    if types.contains(TypeSet::STRING) {
+        names.push("string");
+    }
+    if types.contains(TypeSet::ARRAY) {
+        names.push("any[]");
+    }
+    names.join(" | ")
 }
 
 impl Transpiler for Sui2Js {
@@ -519,4 +1103,175 @@ $ g0 0 5
         assert!(result.contains("function f0(a0)"));
         assert!(result.contains("g0 = f0(5);"));
     }
+
+    #[test]
+    fn test_typescript() {
+        let code = "# 0 1 {\n+ v0 a0 1\n^ v0\n}\n$ g0 0 2.5\n= v0 \"x\"\n. g101";
+        let mut transpiler = Sui2Js::new();
+        transpiler.set_typescript(true);
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("function f0(a0: number): number {"));
+        assert!(result.contains("let g0: number;"));
+        assert!(result.contains("let v0: string;"));
+        assert!(result.contains("declare let g101: number | string;"));
+        assert!(result.contains("(globalThis as any)[`g${101 + _i}`]"));
+    }
+
+    #[test]
+    fn test_missing_args() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("+ v0 g101 g103\n. v0").unwrap();
+        assert!(result.contains("for (let _i = 0; _i < Math.max(_args.length, 3); _i++) {"));
+        assert!(result.contains("const _arg = _i < _args.length ? _args[_i] : \"0\";"));
+
+        let result = transpiler.transpile_to_js(". g100").unwrap();
+        assert!(result.contains("for (let _i = 0; _i < _args.length; _i++) {"));
+    }
+
+    #[test]
+    fn test_nested_arrays() {
+        let code = "R v0 \"array2d\" 2 3\n{ v0 1 2 7\n] v1 v0 1 2\n. v1";
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v0 = Array.from({ length: 2 }, () => new Array(3).fill(0));"));
+        assert!(result.contains("v0[Math.floor(1)][Math.floor(2)] = 7;"));
+        assert!(result.contains("v1 = v0[Math.floor(1)][Math.floor(2)];"));
+    }
+
+    #[test]
+    fn test_array_builtins() {
+        let code = "R v1 \"slice\" v0 1 3\nR v2 \"concat\" v0 v1\nR v3 \"sum\" v2";
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v1 = v0.slice(Math.floor(1), Math.floor(3));"));
+        assert!(result.contains("v2 = v0.concat(v1);"));
+        assert!(result.contains("v3 = v2.reduce((x, y) => x + y, 0);"));
+    }
+
+    #[test]
+    fn test_floordiv_ipow() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("R v0 \"floordiv\" v1 v2\nR v3 \"ipow\" v1 3").unwrap();
+        assert!(result.contains("v0 = v2 != 0 ? Math.floor(v1 / v2) : NaN;"));
+        assert!(result.contains("v3 = v1 ** 3;"));
+    }
+
+    #[test]
+    fn test_time_builtins() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("R v0 \"time.now\"\nR v1 \"time.sleep_ms\" v0").unwrap();
+        assert!(result.contains("v0 = Date.now() / 1000;"));
+        assert!(result.contains("Atomics.wait(new Int32Array(new SharedArrayBuffer(4)), 0, 0, Math.max(Number(v0), 0)), 0);"));
+    }
+
+    #[test]
+    fn test_regex_builtins() {
+        let mut transpiler = Sui2Js::new();
+        let code = "R v0 \"re.match\" \"a+\" v1\nR v2 \"re.findall\" \"b\" v1\nR v3 \"re.replace\" \"b\" v1 \"c\"";
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v0 = (new RegExp(\"a+\", \"y\").test(String(v1)) ? 1 : 0);"));
+        assert!(result.contains("v2 = [...String(v1).matchAll(new RegExp(\"b\", \"g\"))].map((m) => m[0]);"));
+        assert!(result.contains("v3 = String(v1).replace(new RegExp(\"b\", \"g\"), () => String(\"c\"));"));
+    }
+
+    #[test]
+    fn test_struct_json() {
+        let mut transpiler = Sui2Js::new();
+        let code = "R v0 \"struct.new\" \"a\" 1\nR v1 \"struct.get\" v0 \"a\"\nR v2 \"struct.set\" v0 \"b\" v1\n\
+                    R v3 \"json.encode\" v2\nR v4 \"json.decode\" v3";
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v0 = {[\"a\"]: 1};"));
+        assert!(result.contains("v1 = (v0[\"a\"] ?? 0);"));
+        assert!(result.contains("v2 = {...v0, [\"b\"]: v1};"));
+        assert!(result.contains("v3 = JSON.stringify(v2);"));
+        assert!(result.contains("v4 = JSON.parse(v3);"));
+    }
+
+    #[test]
+    fn test_fmt_date() {
+        let mut transpiler = Sui2Js::new();
+        let code = "R v0 \"fmt\" \"{:>8.2f} {{`}} {0}\" v1\nR v2 \"fmt\" v3 v1\nR v4 \"date.format\" v1 \"%F\"";
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v0 = `${_fmt(v1, \">8.2f\")} {\\`} ${v1}`;"));
+        assert!(result.contains("v2 = _fmtTemplate(v3, [v1]);"));
+        assert!(result.contains("v4 = _dateFormat(v1, \"%F\");"));
+        assert!(result.contains("function _fmt(v, spec) {"));
+        assert!(result.contains("function _dateFormat(ts, p = "));
+        assert!(!transpiler.transpile_to_js(". 1").unwrap().contains("function _fmt"));
+
+        // Templates the interpreter would refuse do not transpile
+        let err = transpiler.transpile_to_js("R v0 \"fmt\" \"{:>400000000}\" v1").unwrap_err();
+        assert_eq!(err.to_string(), "Code generation error: fmt: format spec '>400000000' is wider than 10000 characters");
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("R v1 \"upper\" v0\nR v2 \"join\" v1 \"-\"").unwrap();
+        assert!(result.contains("v1 = String(v0).toUpperCase();"));
+        assert!(result.contains("v2 = [...v1].join(\"-\");"));
+    }
+
+    #[test]
+    fn test_bitwise_builtins() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("R v1 \"shr\" v0 2").unwrap();
+        assert!(result.contains(
+            "v1 = Number(BigInt.asIntN(64, BigInt(Math.trunc(v0)) >> (BigInt(Math.trunc(2)) & 63n)));"
+        ));
+    }
+
+    #[test]
+    fn test_division_policy() {
+        let code = "/ v0 v1 v2\n% v0 v1 v2";
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v0 = v2 != 0 ? v1 / v2 : NaN;"));
+        assert!(result.contains("v0 = v1 % v2 + 0;"));
+
+        transpiler.set_division_policy(DivisionPolicy::SaturateZero);
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("v0 = v2 != 0 ? v1 % v2 + 0 : 0;"));
+
+        transpiler.set_division_policy(DivisionPolicy::Error);
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("if (v2 == 0) throw new Error(\"Division by zero\");\nv0 = v1 / v2;"));
+    }
+
+    #[test]
+    fn test_try_raise() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("T v0 0\nE \"boom\"\nU\n: 0\n. v0").unwrap();
+        assert!(result.contains("const _handlers = [];"));
+        assert!(result.contains("_handlers.push(0);\n        throw \"boom\";"));
+        assert!(result.contains("} catch (_e) {"));
+        assert!(result.contains("case 0:\n        v0 = _err;\n        _state = 1;"));
+    }
+
+    #[test]
+    fn test_jump_table() {
+        let mut transpiler = Sui2Js::new();
+        let code = "% v0 g0 3\nJ v0 1 2 1\n. \"other\"\n@ 9\n: 1\n. \"even\"\n@ 9\n: 2\n. \"one\"\n: 9";
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("switch (v0) {\n  case 0:\n  case 2:\n    console.log(\"even\");\n    break;"));
+        assert!(result.contains("  default:\n    console.log(\"other\");\n}"));
+
+        // A case that leaves the loop becomes an `if` chain, where `break` still works
+        let code = ": 0\n+ v0 v0 1\nJ v0 1 2\n. 0\n@ 8\n: 1\n@ 9\n: 2\n. 2\n: 8\n. 8\n@ 0\n: 9";
+        let result = transpiler.transpile_to_js(code).unwrap();
+        assert!(result.contains("  if (v0 === 0) {\n    break;\n  } else if (v0 === 1) {\n    console.log(2);\n  } else {"));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let mut transpiler = Sui2Js::new();
+        let result = transpiler.transpile_to_js("I v0 3\nN v1 v2 v0").unwrap();
+        assert!(result.contains(
+            "v0 = (typeof 3 === \"number\" ? Array.from({ length: 3 }, (_, i) => i) : [...3])\
+             [Symbol.iterator]();"
+        ));
+        assert!(result.contains("const _next = v0.next();"));
+        assert!(result.contains("v1 = _next.done ? 1 : 0;"));
+        assert!(result.contains("if (!_next.done) v2 = _next.value;"));
+    }
 }
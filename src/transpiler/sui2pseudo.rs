@@ -0,0 +1,958 @@
+//! Sui to annotated pseudocode, for reviewing generated programs
+//!
+//! Control flow is recovered through the IR like the Python backend, but the
+//! output is meant to be read rather than run: temporaries used once are
+//! folded into the expression that uses them, loops whose first block only
+//! computes their condition become `while` loops, and variables get names
+//! inferred from how they are used. Every renamed variable is listed with its
+//! original Sui name at the top of its scope.
+
+use super::{case_patterns, foreach_loop, loop_test, TranspileError, Transpiler};
+use crate::interpreter::{Instruction, Lexer, ParsedValue};
+use crate::ir::{BlockId, Cfg, Program, Region, Terminator};
+use std::collections::{HashMap, HashSet};
+
+/// Sui to pseudocode transpiler
+pub struct Sui2Pseudo {
+    indent: usize,
+    output: Vec<String>,
+    /// Readable names of globals, shared by all functions
+    globals: Names,
+    /// Readable names of the locals of the graph being emitted
+    locals: Names,
+    /// Instructions `(block, index)` folded into the single use of their result
+    folded: HashSet<(BlockId, usize)>,
+    /// Expressions of folded results that have not been used yet
+    pending: HashMap<String, Expr>,
+}
+
+impl Default for Sui2Pseudo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sui2Pseudo {
+    /// Create a new transpiler
+    pub fn new() -> Self {
+        Self {
+            indent: 0,
+            output: Vec::new(),
+            globals: Names::default(),
+            locals: Names::default(),
+            folded: HashSet::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Emit a line with current indentation
+    fn emit(&mut self, line: &str) {
+        let indent_str = "    ".repeat(self.indent);
+        self.output.push(format!("{}{}", indent_str, line));
+    }
+
+    /// Expression for an operand: a folded result, a renamed variable or a literal
+    fn operand(&mut self, val: &str) -> Expr {
+        if let Some(expr) = self.pending.remove(val) {
+            return expr;
+        }
+        let names = if val.starts_with('g') { &self.globals } else { &self.locals };
+        Expr::atom(names.get(val).unwrap_or(val))
+    }
+
+    /// Name of a variable that is assigned
+    fn target(&self, var: &str) -> String {
+        let names = if var.starts_with('g') { &self.globals } else { &self.locals };
+        names.get(var).unwrap_or(var).to_string()
+    }
+
+    /// Comma-separated operands
+    fn arguments(&mut self, args: &[String]) -> String {
+        args.iter().map(|a| self.operand(a).text).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Emit the renames of a scope
+    fn emit_legend(&mut self, names: &Names) {
+        for (var, name, role) in &names.legend {
+            self.emit(&format!("# {} = {} ({})", name, var, role.describe()));
+        }
+    }
+
+    /// Emit a lowered function body or the main program
+    fn transpile_cfg(&mut self, cfg: &Cfg, is_function: bool) {
+        let region = cfg.structure();
+        self.locals = Names::default();
+        for (var, role) in suggest_names(cfg, &region) {
+            if !var.starts_with('g') && !self.locals.contains(&var) {
+                self.locals.claim(var, role, &self.globals);
+            }
+        }
+        self.folded = plan_folding(cfg, &self.locals);
+        self.pending.clear();
+
+        let locals = std::mem::take(&mut self.locals);
+        self.emit_legend(&locals);
+        self.locals = locals;
+        self.transpile_nested(cfg, &region, is_function);
+    }
+
+    /// Emit a region, emitting `pass` if it produces no code
+    fn transpile_nested(&mut self, cfg: &Cfg, region: &Region, is_function: bool) {
+        let start = self.output.len();
+        self.transpile_region(cfg, region, is_function);
+        if self.output.len() == start {
+            self.emit("pass");
+        }
+    }
+
+    /// Emit regions in order, leaving out a final `continue`
+    fn transpile_loop_body(&mut self, cfg: &Cfg, items: &[Region], exits: bool, is_function: bool) {
+        let start = self.output.len();
+        let body = match items {
+            [init @ .., Region::Continue] => init,
+            items => items,
+        };
+        for item in body {
+            self.transpile_region(cfg, item, is_function);
+        }
+        if exits && !items.last().is_some_and(Region::ends_with_transfer) {
+            self.emit("break");
+        } else if self.output.len() == start {
+            self.emit("pass");
+        }
+    }
+
+    /// Emit a structured region
+    fn transpile_region(&mut self, cfg: &Cfg, region: &Region, is_function: bool) {
+        match region {
+            Region::Block(id) => self.transpile_block(cfg, *id),
+            Region::Seq(items) => {
+                for item in items {
+                    self.transpile_region(cfg, item, is_function);
+                }
+            }
+            Region::If {
+                cond,
+                negate,
+                then_region,
+                else_region,
+            } => {
+                let cond = self.operand(cond);
+                let cond = if *negate { cond.negate() } else { cond };
+                self.emit(&format!("if {}:", cond.text));
+                self.indent += 1;
+                self.transpile_nested(cfg, then_region, is_function);
+                self.indent -= 1;
+                if let Some(else_region) = else_region {
+                    self.emit("else:");
+                    self.indent += 1;
+                    self.transpile_nested(cfg, else_region, is_function);
+                    self.indent -= 1;
+                }
+            }
+            Region::Switch { value, cases, default } => {
+                let value = self.operand(value);
+                self.emit(&format!("match {}:", value.text));
+                self.indent += 1;
+                for (values, body) in cases {
+                    self.emit(&format!("case {}:", case_patterns(values)));
+                    self.indent += 1;
+                    self.transpile_nested(cfg, body, is_function);
+                    self.indent -= 1;
+                }
+                if !matches!(default.as_ref(), Region::Seq(items) if items.is_empty()) {
+                    self.emit("case _:");
+                    self.indent += 1;
+                    self.transpile_nested(cfg, default, is_function);
+                    self.indent -= 1;
+                }
+                self.indent -= 1;
+            }
+            Region::Loop(body) => self.transpile_loop(cfg, body, is_function),
+            Region::Break => self.emit("break"),
+            Region::Continue => self.emit("continue"),
+            Region::Return(value) => self.transpile_return(value, is_function),
+            Region::Dispatch(blocks) => {
+                // Blocks with explicit jumps between them
+                for (i, &id) in blocks.iter().enumerate() {
+                    self.emit(&format!("block {}:", id));
+                    self.indent += 1;
+                    self.transpile_block(cfg, id);
+                    // Falling off the last block leaves the code
+                    let next = blocks.get(i + 1).copied().unwrap_or_else(|| cfg.exit());
+                    match &cfg.block(id).terminator {
+                        Terminator::Goto(target) => {
+                            if *target != next {
+                                self.emit(&goto(cfg, *target));
+                            }
+                        }
+                        Terminator::Branch {
+                            cond,
+                            target,
+                            fallthrough,
+                        } => {
+                            let cond = self.operand(cond);
+                            self.emit(&format!("if {}: {}", cond.text, goto(cfg, *target)));
+                            if *fallthrough != next {
+                                self.emit(&goto(cfg, *fallthrough));
+                            }
+                        }
+                        terminator @ Terminator::Switch { value, fallthrough, .. } => {
+                            let value = self.operand(value);
+                            self.emit(&format!("match {}:", value.text));
+                            for (values, target) in terminator.switch_cases() {
+                                self.emit(&format!("    case {}: {}", case_patterns(&values), goto(cfg, target)));
+                            }
+                            if *fallthrough != next {
+                                self.emit(&goto(cfg, *fallthrough));
+                            }
+                        }
+                        Terminator::Return(value) => self.transpile_return(value, is_function),
+                    }
+                    self.indent -= 1;
+                }
+            }
+        }
+    }
+
+    /// Emit a loop as `for`, `while cond` or `while True`
+    fn transpile_loop(&mut self, cfg: &Cfg, body: &Region, is_function: bool) {
+        if let Some(foreach) = foreach_loop(cfg, body) {
+            let (var, iter) = (self.target(foreach.var), self.operand(foreach.iter));
+            self.emit(&format!("for {} in {}:", var, iter.text));
+            self.indent += 1;
+            self.transpile_loop_body(cfg, foreach.body, foreach.exits, is_function);
+            self.indent -= 1;
+            return;
+        }
+
+        let start = self.output.len();
+        self.indent += 1;
+        if let Some(test) = loop_test(body) {
+            // The header becomes the loop condition when all of it folds
+            self.transpile_block(cfg, test.header);
+            if self.output.len() == start {
+                let cond = self.operand(test.cond).negate();
+                self.indent -= 1;
+                self.emit(&format!("while {}:", cond.text));
+                self.indent += 1;
+                self.transpile_loop_body(cfg, test.body, test.exits, is_function);
+                self.indent -= 1;
+                return;
+            }
+            let Region::Seq(items) = body else {
+                unreachable!("loop tests are sequences");
+            };
+            self.transpile_loop_body(cfg, &items[1..], false, is_function);
+        } else {
+            match body {
+                Region::Seq(items) => self.transpile_loop_body(cfg, items, false, is_function),
+                body => self.transpile_loop_body(cfg, std::slice::from_ref(body), false, is_function),
+            }
+        }
+        self.indent -= 1;
+        let line = format!("{}while True:", "    ".repeat(self.indent));
+        self.output.insert(start, line);
+    }
+
+    /// Emit the instructions of a block, keeping folded results for later
+    fn transpile_block(&mut self, cfg: &Cfg, id: BlockId) {
+        for (index, instr) in cfg.block(id).instructions.iter().enumerate() {
+            if self.folded.contains(&(id, index)) {
+                if let Some((result, expr)) = self.expression(instr) {
+                    self.pending.insert(result.to_string(), expr);
+                }
+            } else {
+                self.transpile_instruction(cfg, instr);
+            }
+        }
+    }
+
+    /// Return from a function, or stop the program at top level
+    fn transpile_return(&mut self, value: &str, is_function: bool) {
+        if is_function {
+            let value = self.operand(value);
+            self.emit(&format!("return {}", value.text));
+        } else {
+            self.emit("exit()");
+        }
+    }
+
+    /// Result and value of an instruction that computes a single value
+    fn expression<'a>(&mut self, instr: &'a Instruction) -> Option<(&'a str, Expr)> {
+        let binary = |this: &mut Self, a: &str, op: &str, b: &str| {
+            let (a, b) = (this.operand(a), this.operand(b));
+            Expr::compound(format!("{} {} {}", a.operand(), op, b.operand()))
+        };
+        let expr = match instr {
+            Instruction::Assign { target, value } => (target, self.operand(value)),
+            Instruction::Add { result, a, b } => (result, binary(self, a, "+", b)),
+            Instruction::Sub { result, a, b } => (result, binary(self, a, "-", b)),
+            Instruction::Mul { result, a, b } => (result, binary(self, a, "*", b)),
+            Instruction::Div { result, a, b } => (result, binary(self, a, "/", b)),
+            Instruction::Mod { result, a, b } => (result, binary(self, a, "%", b)),
+            Instruction::Lt { result, a, b } => (result, binary(self, a, "<", b)),
+            Instruction::Gt { result, a, b } => (result, binary(self, a, ">", b)),
+            Instruction::Le { result, a, b } => (result, binary(self, a, "<=", b)),
+            Instruction::Ge { result, a, b } => (result, binary(self, a, ">=", b)),
+            Instruction::Eq { result, a, b } => (result, binary(self, a, "==", b)),
+            Instruction::And { result, a, b } => (result, binary(self, a, "and", b)),
+            Instruction::Or { result, a, b } => (result, binary(self, a, "or", b)),
+            Instruction::Not { result, a } => (result, self.operand(a).negate()),
+            Instruction::Call { result, func_id, args } => {
+                (result, Expr::atom(&format!("f{}({})", func_id, self.arguments(args))))
+            }
+            Instruction::ArrayRead { result, arr, idx, indices } => {
+                let mut text = self.operand(arr).operand();
+                for i in std::iter::once(idx).chain(indices) {
+                    text.push_str(&format!("[{}]", self.operand(i).text));
+                }
+                (result, Expr::atom(&text))
+            }
+            Instruction::RustFFI { result, func, args } => {
+                let func = func.trim_matches('"');
+                match (func, args.as_slice()) {
+                    ("floordiv", [a, b]) => (result, binary(self, a, "//", b)),
+                    ("ipow", [a, b]) => (result, binary(self, a, "**", b)),
+                    _ => (result, Expr::atom(&format!("{}({})", func, self.arguments(args)))),
+                }
+            }
+            _ => return None,
+        };
+        Some((expr.0.as_str(), expr.1))
+    }
+
+    /// Emit a single instruction as a statement
+    fn transpile_instruction(&mut self, cfg: &Cfg, instr: &Instruction) {
+        // Updates of a variable in place, such as `total += x`
+        let update = match instr {
+            Instruction::Add { result, a, b } if result == a => Some((result, "+=", b)),
+            Instruction::Sub { result, a, b } if result == a => Some((result, "-=", b)),
+            Instruction::Mul { result, a, b } if result == a => Some((result, "*=", b)),
+            _ => None,
+        };
+        if let Some((var, op, value)) = update {
+            let value = self.operand(value);
+            let line = format!("{} {} {}", self.target(var), op, value.text);
+            self.emit(&line);
+            return;
+        }
+        if let Some((result, expr)) = self.expression(instr) {
+            let line = format!("{} = {}", self.target(result), expr.text);
+            self.emit(&line);
+            return;
+        }
+
+        match instr {
+            Instruction::Empty
+            | Instruction::Comment
+            | Instruction::Const { .. }
+            | Instruction::Label { .. } => {}
+
+            Instruction::Import { path } => self.emit(&format!("import \"{}\"", path)),
+
+            Instruction::Invalid { raw, .. } => self.emit(&format!("# invalid: {}", raw)),
+
+            // Control flow is lowered through the IR
+            Instruction::CondJump { .. }
+            | Instruction::Jump { .. }
+            | Instruction::Return { .. }
+            | Instruction::FuncDef { .. }
+            | Instruction::FuncEnd => {}
+
+            Instruction::ArrayCreate { var, size } => {
+                let size = self.operand(size);
+                let line = format!("{} = [0] * {}", self.target(var), size.operand());
+                self.emit(&line);
+            }
+
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
+                let mut line = self.operand(arr).operand();
+                for i in std::iter::once(idx).chain(indices) {
+                    line.push_str(&format!("[{}]", self.operand(i).text));
+                }
+                let value = self.operand(value);
+                self.emit(&format!("{} = {}", line, value.text));
+            }
+
+            Instruction::Iter { iter, source } => {
+                let source = self.operand(source);
+                let line = format!("{} = iter({})", self.target(iter), source.text);
+                self.emit(&line);
+            }
+
+            Instruction::Next { done, var, iter } => {
+                let iter = self.operand(iter);
+                let line = format!("{}, {} = next({})", self.target(done), self.target(var), iter.text);
+                self.emit(&line);
+            }
+
+            Instruction::Try { err, label } => {
+                let handler = cfg.label_block(*label).unwrap_or_else(|| cfg.exit());
+                let line = format!("on error: {} = error, {}", self.target(err), goto(cfg, handler));
+                self.emit(&line);
+            }
+
+            Instruction::EndTry => self.emit("end try"),
+
+            Instruction::Raise { value } => {
+                let value = self.operand(value);
+                self.emit(&format!("raise {}", value.text));
+            }
+
+            Instruction::Output { value } => {
+                let value = self.operand(value);
+                self.emit(&format!("print({})", value.text));
+            }
+
+            Instruction::Input { var } => {
+                let line = format!("{} = input()", self.target(var));
+                self.emit(&line);
+            }
+
+            // Handled by `expression`
+            _ => {}
+        }
+    }
+
+    /// Transpile Sui code to pseudocode
+    pub fn transpile_to_pseudo(&mut self, code: &str) -> Result<String, TranspileError> {
+        let program = Program::from_source(code).map_err(|e| TranspileError::Parse(e.to_string()))?;
+
+        self.output.clear();
+        self.indent = 0;
+        self.globals = Names::default();
+        let cfgs = std::iter::once(&program.main).chain(program.functions.iter().map(|f| &f.body));
+        for cfg in cfgs {
+            for (var, role) in suggest_names(cfg, &cfg.structure()) {
+                if var.starts_with('g') && !self.globals.contains(&var) {
+                    self.globals.claim(var, role, &Names::default());
+                }
+            }
+        }
+
+        self.emit("# Pseudocode reconstructed from Sui");
+        let globals = std::mem::take(&mut self.globals);
+        self.emit_legend(&globals);
+        self.globals = globals;
+        self.emit("");
+
+        for func in &program.functions {
+            let args_str = (0..func.arg_count)
+                .map(|i| format!("a{}", i))
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.emit(&format!("def f{}({}):", func.id, args_str));
+            self.indent += 1;
+            self.transpile_cfg(&func.body, true);
+            self.indent -= 1;
+            self.emit("");
+        }
+
+        self.emit("# Main");
+        self.transpile_cfg(&program.main, false);
+
+        Ok(self.output.join("\n"))
+    }
+}
+
+impl Transpiler for Sui2Pseudo {
+    fn transpile(&self, code: &str) -> Result<String, TranspileError> {
+        let mut transpiler = Sui2Pseudo::new();
+        transpiler.transpile_to_pseudo(code)
+    }
+
+    fn extension(&self) -> &str {
+        "txt"
+    }
+
+    fn language(&self) -> &str {
+        "Pseudocode"
+    }
+}
+
+/// A pseudocode expression
+#[derive(Debug, Clone)]
+struct Expr {
+    text: String,
+    /// Whether the text needs no parentheses as an operand
+    atomic: bool,
+    /// The expression this one negates, so that `not` can be undone
+    negation: Option<Box<Expr>>,
+}
+
+impl Expr {
+    fn atom(text: &str) -> Self {
+        Self { text: text.to_string(), atomic: true, negation: None }
+    }
+
+    fn compound(text: String) -> Self {
+        Self { text, atomic: false, negation: None }
+    }
+
+    /// Text for use as an operand of an operator
+    fn operand(&self) -> String {
+        if self.atomic {
+            self.text.clone()
+        } else {
+            format!("({})", self.text)
+        }
+    }
+
+    /// Logical negation, removing a `not` instead of adding one if possible
+    fn negate(self) -> Self {
+        match self.negation {
+            Some(inner) => *inner,
+            None => Self {
+                text: format!("not {}", self.operand()),
+                atomic: false,
+                negation: Some(Box::new(self)),
+            },
+        }
+    }
+}
+
+/// What a variable is used for, which decides its readable name
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    /// Stepped by a constant and compared in the same loop
+    Counter,
+    /// Stepped by a constant in a loop without being compared
+    Count,
+    /// Has values added to it in a loop
+    Accumulator,
+    /// Multiplied by values in a loop
+    Product,
+    /// Created with `[`
+    Array,
+    /// Assigned by `N` on each step of an iterator
+    Item,
+}
+
+impl Role {
+    /// Names to choose from, in order of preference
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            Role::Counter => &["i", "j", "k"],
+            Role::Count => &["count"],
+            Role::Accumulator => &["total"],
+            Role::Product => &["product"],
+            Role::Array => &["items"],
+            Role::Item => &["item"],
+        }
+    }
+
+    /// Description for the legend
+    fn describe(self) -> &'static str {
+        match self {
+            Role::Counter => "loop counter",
+            Role::Count => "counter",
+            Role::Accumulator => "accumulator",
+            Role::Product => "running product",
+            Role::Array => "array",
+            Role::Item => "loop item",
+        }
+    }
+}
+
+/// Readable names chosen for the variables of a scope
+#[derive(Debug, Default)]
+struct Names {
+    names: HashMap<String, String>,
+    /// `(var, name, role)` in the order the names were chosen
+    legend: Vec<(String, String, Role)>,
+}
+
+impl Names {
+    fn get(&self, var: &str) -> Option<&str> {
+        self.names.get(var).map(String::as_str)
+    }
+
+    fn contains(&self, var: &str) -> bool {
+        self.names.contains_key(var)
+    }
+
+    /// Give a variable the first name for its role that is not taken here or
+    /// in `outer`, numbering the first one if all are
+    fn claim(&mut self, var: String, role: Role, outer: &Names) {
+        let taken = |name: &str| {
+            self.names.values().chain(outer.names.values()).any(|n| n == name)
+        };
+        let candidates = role.names();
+        let name = match candidates.iter().find(|name| !taken(name)) {
+            Some(name) => name.to_string(),
+            None => (2..)
+                .map(|n| format!("{}{}", candidates[0], n))
+                .find(|name| !taken(name))
+                .unwrap_or_default(),
+        };
+        self.names.insert(var.clone(), name.clone());
+        self.legend.push((var, name, role));
+    }
+}
+
+/// Roles of variables, from their use inside loops first (outer loops
+/// before inner ones) and then from how they are assigned
+fn suggest_names(cfg: &Cfg, region: &Region) -> Vec<(String, Role)> {
+    let mut loops = Vec::new();
+    collect_loops(region, &mut loops);
+
+    let mut roles: Vec<(String, Role)> = Vec::new();
+    let mut suggest = |var: &str, role: Role| {
+        if matches!(Lexer::parse_value(var), ParsedValue::Variable(_))
+            && !var.starts_with('a')
+            && !roles.iter().any(|(v, _)| v == var)
+        {
+            roles.push((var.to_string(), role));
+        }
+    };
+
+    for blocks in &loops {
+        let instrs = || blocks.iter().flat_map(|&id| &cfg.block(id).instructions);
+        let compared = |var: &str| {
+            instrs().any(|instr| match instr {
+                Instruction::Lt { a, b, .. }
+                | Instruction::Gt { a, b, .. }
+                | Instruction::Le { a, b, .. }
+                | Instruction::Ge { a, b, .. }
+                | Instruction::Eq { a, b, .. } => a == var || b == var,
+                _ => false,
+            })
+        };
+        for instr in instrs() {
+            let (result, other, op) = match instr {
+                Instruction::Add { result, a, b } if result == a => (result, b, '+'),
+                Instruction::Add { result, a, b } if result == b => (result, a, '+'),
+                Instruction::Sub { result, a, b } if result == a => (result, b, '-'),
+                Instruction::Mul { result, a, b } if result == a => (result, b, '*'),
+                Instruction::Mul { result, a, b } if result == b => (result, a, '*'),
+                _ => continue,
+            };
+            let constant = matches!(
+                Lexer::parse_value(other),
+                ParsedValue::Integer(_) | ParsedValue::Float(_)
+            );
+            match (op, constant) {
+                ('+' | '-', true) if compared(result) => suggest(result, Role::Counter),
+                ('+' | '-', true) => suggest(result, Role::Count),
+                ('+', false) => suggest(result, Role::Accumulator),
+                ('*', false) => suggest(result, Role::Product),
+                _ => {}
+            }
+        }
+    }
+
+    for instr in cfg.blocks().iter().flat_map(|b| &b.instructions) {
+        match instr {
+            Instruction::ArrayCreate { var, .. } => suggest(var, Role::Array),
+            Instruction::Next { var, .. } => suggest(var, Role::Item),
+            _ => {}
+        }
+    }
+    roles
+}
+
+/// Blocks of each loop, outer loops first, leaving out nested loops
+fn collect_loops(region: &Region, loops: &mut Vec<Vec<BlockId>>) {
+    /// Blocks of a region outside nested loops, and the nested loop bodies
+    fn own_blocks<'a>(region: &'a Region, blocks: &mut Vec<BlockId>, nested: &mut Vec<&'a Region>) {
+        match region {
+            Region::Block(id) => blocks.push(*id),
+            Region::Seq(items) => {
+                for item in items {
+                    own_blocks(item, blocks, nested);
+                }
+            }
+            Region::If { then_region, else_region, .. } => {
+                own_blocks(then_region, blocks, nested);
+                if let Some(else_region) = else_region {
+                    own_blocks(else_region, blocks, nested);
+                }
+            }
+            Region::Switch { cases, default, .. } => {
+                for (_, body) in cases {
+                    own_blocks(body, blocks, nested);
+                }
+                own_blocks(default, blocks, nested);
+            }
+            Region::Loop(body) => nested.push(body),
+            _ => {}
+        }
+    }
+
+    let mut nested = Vec::new();
+    own_blocks(region, &mut Vec::new(), &mut nested);
+    for body in nested {
+        let mut blocks = Vec::new();
+        own_blocks(body, &mut blocks, &mut Vec::new());
+        loops.push(blocks);
+        collect_loops(body, loops);
+    }
+}
+
+/// Variables an instruction assigns and reads
+fn effects(instr: &Instruction) -> (Vec<&String>, Vec<&String>) {
+    let operands = instr.operands();
+    let written = match instr {
+        Instruction::Assign { .. }
+        | Instruction::Add { .. }
+        | Instruction::Sub { .. }
+        | Instruction::Mul { .. }
+        | Instruction::Div { .. }
+        | Instruction::Mod { .. }
+        | Instruction::Lt { .. }
+        | Instruction::Gt { .. }
+        | Instruction::Le { .. }
+        | Instruction::Ge { .. }
+        | Instruction::Eq { .. }
+        | Instruction::Not { .. }
+        | Instruction::And { .. }
+        | Instruction::Or { .. }
+        | Instruction::Call { .. }
+        | Instruction::ArrayCreate { .. }
+        | Instruction::ArrayRead { .. }
+        | Instruction::Iter { .. }
+        | Instruction::Input { .. }
+        | Instruction::Try { .. }
+        | Instruction::RustFFI { .. } => 1,
+        Instruction::Next { .. } => 2,
+        _ => 0,
+    };
+    let (writes, reads) = operands.split_at(written);
+    let mut writes = writes.to_vec();
+    match instr {
+        // Arrays and iterators change in place
+        Instruction::ArrayWrite { arr, .. } => writes.push(arr),
+        Instruction::Next { iter, .. } => writes.push(iter),
+        _ => {}
+    }
+    let is_var = |v: &&String| matches!(Lexer::parse_value(v), ParsedValue::Variable(_));
+    (writes, reads.iter().copied().filter(is_var).collect())
+}
+
+/// A folding candidate: an unused single-assignment temporary
+struct Candidate {
+    index: usize,
+    /// Variables its expression reads, including through folded operands
+    reads: HashSet<String>,
+    /// Whether its value may change when other code runs
+    volatile: bool,
+}
+
+/// Choose the instructions whose result is folded into its single use
+///
+/// A result is folded when it is a local assigned once and used once, later
+/// in the same block, and moving the computation to the use cannot change its
+/// value or the order of side effects.
+fn plan_folding(cfg: &Cfg, names: &Names) -> HashSet<(BlockId, usize)> {
+    let mut defs: HashMap<&str, usize> = HashMap::new();
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+    for block in cfg.blocks() {
+        for instr in &block.instructions {
+            let (writes, reads) = effects(instr);
+            writes.into_iter().for_each(|v| *defs.entry(v).or_default() += 1);
+            reads.into_iter().for_each(|v| *uses.entry(v).or_default() += 1);
+        }
+        match &block.terminator {
+            Terminator::Branch { cond: v, .. } | Terminator::Switch { value: v, .. } | Terminator::Return(v) => {
+                *uses.entry(v).or_default() += 1
+            }
+            Terminator::Goto(_) => {}
+        }
+    }
+
+    let mut folded = HashSet::new();
+    for (id, block) in cfg.blocks().iter().enumerate() {
+        let mut open: HashMap<&str, Candidate> = HashMap::new();
+        for (index, instr) in block.instructions.iter().enumerate() {
+            let (writes, reads) = effects(instr);
+            let barrier = matches!(
+                instr,
+                Instruction::Call { .. }
+                    | Instruction::RustFFI { .. }
+                    | Instruction::Input { .. }
+                    | Instruction::Output { .. }
+                    | Instruction::ArrayWrite { .. }
+                    | Instruction::Iter { .. }
+                    | Instruction::Next { .. }
+                    | Instruction::Try { .. }
+                    | Instruction::EndTry
+                    | Instruction::Raise { .. }
+            );
+
+            let mut candidate = Candidate {
+                index,
+                reads: reads.iter().map(|v| v.to_string()).collect(),
+                volatile: matches!(
+                    instr,
+                    Instruction::Call { .. } | Instruction::RustFFI { .. } | Instruction::ArrayRead { .. }
+                ) || reads.iter().any(|v| v.starts_with('g')),
+            };
+            for var in &reads {
+                if let Some(used) = open.remove(var.as_str()) {
+                    folded.insert((id, used.index));
+                    candidate.reads.extend(used.reads);
+                    candidate.volatile |= used.volatile;
+                }
+            }
+            // Drop candidates whose value this instruction may change
+            open.retain(|_, c| {
+                let clobbered = writes.iter().any(|w| c.reads.contains(w.as_str()));
+                let moved_past_effect = barrier && c.volatile;
+                !(clobbered || moved_past_effect)
+            });
+
+            let foldable = matches!(
+                instr,
+                Instruction::Assign { .. }
+                    | Instruction::Add { .. }
+                    | Instruction::Sub { .. }
+                    | Instruction::Mul { .. }
+                    | Instruction::Div { .. }
+                    | Instruction::Mod { .. }
+                    | Instruction::Lt { .. }
+                    | Instruction::Gt { .. }
+                    | Instruction::Le { .. }
+                    | Instruction::Ge { .. }
+                    | Instruction::Eq { .. }
+                    | Instruction::Not { .. }
+                    | Instruction::And { .. }
+                    | Instruction::Or { .. }
+                    | Instruction::Call { .. }
+                    | Instruction::ArrayRead { .. }
+                    | Instruction::RustFFI { .. }
+            );
+            if let (true, [result]) = (foldable, writes.as_slice()) {
+                let result = result.as_str();
+                if result.starts_with('v')
+                    && !names.contains(result)
+                    && defs.get(result) == Some(&1)
+                    && uses.get(result) == Some(&1)
+                {
+                    open.insert(result, candidate);
+                }
+            }
+        }
+        match &block.terminator {
+            Terminator::Branch { cond: v, .. } | Terminator::Switch { value: v, .. } | Terminator::Return(v) => {
+                if let Some(used) = open.remove(v.as_str()) {
+                    folded.insert((id, used.index));
+                }
+            }
+            Terminator::Goto(_) => {}
+        }
+    }
+    folded
+}
+
+/// Jump to a block of a state machine
+fn goto(cfg: &Cfg, target: BlockId) -> String {
+    if target == cfg.exit() {
+        "goto end".to_string()
+    } else {
+        format!("goto block {}", target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_and_accumulator() {
+        let code = r#"
+[ g0 3
+{ g0 0 10
+{ g0 1 20
+{ g0 2 30
+= g1 0
+= v0 0
+: 0
+< v1 v0 3
+! v2 v1
+? v2 1
+] v3 g0 v0
++ g1 g1 v3
++ v0 v0 1
+@ 0
+: 1
+. g1
+"#;
+        let mut transpiler = Sui2Pseudo::new();
+        let result = transpiler.transpile_to_pseudo(code).unwrap();
+        assert!(result.contains("# items = g0 (array)"));
+        assert!(result.contains("# total = g1 (accumulator)"));
+        assert!(result.contains("# i = v0 (loop counter)"));
+        assert!(result.contains("while i < 3:\n    total += items[i]\n    i += 1\nprint(total)"));
+    }
+
+    #[test]
+    fn test_nested_loops_and_functions() {
+        let code = r#"
+# 0 1 {
++ v0 a0 1
+^ v0
+}
+= v0 0
+: 0
+< v1 v0 2
+! v2 v1
+? v2 3
+= v3 0
+: 1
+< v4 v3 2
+! v5 v4
+? v5 2
+$ v6 0 v3
+. v6
++ v3 v3 1
+@ 1
+: 2
++ v0 v0 1
+@ 0
+: 3
+"#;
+        let mut transpiler = Sui2Pseudo::new();
+        let result = transpiler.transpile_to_pseudo(code).unwrap();
+        assert!(result.contains("def f0(a0):\n    return a0 + 1"));
+        assert!(result.contains("while i < 2:\n    j = 0\n    while j < 2:\n        print(f0(j))"));
+    }
+
+    #[test]
+    fn test_folding_keeps_side_effects_in_order() {
+        let code = r#"
+$ v0 0
+. "first"
++ v1 v0 1
+. v1
+# 0 0 {
+. "called"
+^ 1
+}
+"#;
+        let mut transpiler = Sui2Pseudo::new();
+        let result = transpiler.transpile_to_pseudo(code).unwrap();
+        assert!(result.contains("v0 = f0()\nprint(\"first\")\nprint(v0 + 1)"));
+    }
+
+    #[test]
+    fn test_foreach_and_conditions() {
+        let code = r#"
+[ v0 2
+I v1 v0
+: 0
+N v2 v3 v1
+? v2 1
+> v4 v3 0
+! v5 v4
+? v5 0
+. v3
+@ 0
+: 1
+"#;
+        let mut transpiler = Sui2Pseudo::new();
+        let result = transpiler.transpile_to_pseudo(code).unwrap();
+        assert!(result.contains("# item = v3 (loop item)"));
+        assert!(result.contains("for item in v1:\n    if not (item > 0):\n        continue\n    print(item)"));
+    }
+}
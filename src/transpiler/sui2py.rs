@@ -1,13 +1,17 @@
 //! Sui to Python transpiler
 
-use super::{TranspileError, Transpiler};
-use crate::interpreter::{Parser, Instruction};
-use std::collections::{HashMap, HashSet};
+use super::{case_patterns, check_templates, foreach_loop, nonzero_literal, template_parts, TemplatePart, TranspileError, Transpiler};
+use crate::interpreter::{DivisionPolicy, Instruction, Lexer, ParsedValue};
+use crate::ir::{BlockId, Cfg, Program, Region, Terminator};
 
 /// Sui to Python transpiler
 pub struct Sui2Py {
     indent: usize,
     output: Vec<String>,
+    /// Try handlers `(err, label)` of the state machine being emitted
+    handlers: Vec<(String, i64)>,
+    /// Result of dividing by zero, as in the interpreter
+    division_policy: DivisionPolicy,
 }
 
 impl Default for Sui2Py {
@@ -22,9 +26,18 @@ impl Sui2Py {
         Self {
             indent: 0,
             output: Vec::new(),
+            handlers: Vec::new(),
+            division_policy: DivisionPolicy::default(),
         }
     }
 
+    /// Choose what `/` and `%` do when the divisor is zero
+    ///
+    /// [`DivisionPolicy::Error`] leaves Python's `ZeroDivisionError` in place.
+    pub fn set_division_policy(&mut self, policy: DivisionPolicy) {
+        self.division_policy = policy;
+    }
+
     /// Emit a line with current indentation
     fn emit(&mut self, line: &str) {
         let indent_str = "    ".repeat(self.indent);
@@ -37,119 +50,228 @@ impl Sui2Py {
         val.to_string()
     }
 
-    /// Transpile a block of instructions
-    fn transpile_block(&mut self, instructions: &[Instruction], is_function: bool) {
-        // Collect labels
-        let labels: HashSet<i64> = instructions
-            .iter()
-            .filter_map(|instr| {
-                if let Instruction::Label { id } = instr {
-                    Some(*id)
-                } else {
-                    None
-                }
-            })
-            .collect();
+    /// Python subscripts for an array access, such as `[int(v1)][int(2)]`
+    fn subscripts(&self, idx: &str, indices: &[String]) -> String {
+        std::iter::once(idx)
+            .chain(indices.iter().map(String::as_str))
+            .map(|i| format!("[int({})]", self.resolve_value(i)))
+            .collect()
+    }
 
-        // Use state machine pattern if labels exist
-        if !labels.is_empty() {
-            self.emit("_state = -1");
-            self.emit("while True:");
-            self.indent += 1;
-            self.emit("_state += 1");
+    /// Transpile a lowered function body or the main program
+    fn transpile_cfg(&mut self, cfg: &Cfg, is_function: bool) {
+        let region = cfg.structure();
+        self.transpile_nested(cfg, &region, is_function);
+    }
 
-            // Map labels to state numbers
-            let mut state_map: HashMap<i64, usize> = HashMap::new();
-            state_map.insert(-1, 0);
-            let mut state_num = 1;
+    /// Transpile a region, emitting `pass` if it produces no code
+    fn transpile_nested(&mut self, cfg: &Cfg, region: &Region, is_function: bool) {
+        let start = self.output.len();
+        self.transpile_region(cfg, region, is_function);
+        if self.output.len() == start {
+            self.emit("pass");
+        }
+    }
 
-            for label in labels.iter() {
-                state_map.insert(*label, state_num);
-                state_num += 1;
+    /// Transpile a structured region
+    fn transpile_region(&mut self, cfg: &Cfg, region: &Region, is_function: bool) {
+        match region {
+            Region::Block(id) => {
+                for instr in &cfg.block(*id).instructions {
+                    self.transpile_instruction(instr);
+                }
             }
-
-            // Group instructions by state
-            let mut states: HashMap<usize, Vec<&Instruction>> = HashMap::new();
-            states.insert(0, Vec::new());
-            let mut current = 0;
-
-            for instr in instructions {
-                match instr {
-                    Instruction::Label { id } => {
-                        current = *state_map.get(id).unwrap_or(&0);
-                        states.entry(current).or_default();
+            Region::Seq(items) => {
+                for item in items {
+                    self.transpile_region(cfg, item, is_function);
+                }
+            }
+            Region::If {
+                cond,
+                negate,
+                then_region,
+                else_region,
+            } => {
+                let cond = self.resolve_value(cond);
+                if *negate {
+                    self.emit(&format!("if not {}:", cond));
+                } else {
+                    self.emit(&format!("if {}:", cond));
+                }
+                self.indent += 1;
+                self.transpile_nested(cfg, then_region, is_function);
+                self.indent -= 1;
+                if let Some(else_region) = else_region {
+                    self.emit("else:");
+                    self.indent += 1;
+                    self.transpile_nested(cfg, else_region, is_function);
+                    self.indent -= 1;
+                }
+            }
+            Region::Switch { value, cases, default } => {
+                if cases.is_empty() {
+                    return self.transpile_region(cfg, default, is_function);
+                }
+                self.emit(&format!("match {}:", self.resolve_value(value)));
+                self.indent += 1;
+                for (values, body) in cases {
+                    self.emit(&format!("case {}:", case_patterns(values)));
+                    self.indent += 1;
+                    self.transpile_nested(cfg, body, is_function);
+                    self.indent -= 1;
+                }
+                if !matches!(default.as_ref(), Region::Seq(items) if items.is_empty()) {
+                    self.emit("case _:");
+                    self.indent += 1;
+                    self.transpile_nested(cfg, default, is_function);
+                    self.indent -= 1;
+                }
+                self.indent -= 1;
+            }
+            Region::Loop(body) => {
+                if let Some(foreach) = foreach_loop(cfg, body) {
+                    self.emit(&format!("for {} in {}:", foreach.var, foreach.iter));
+                    self.indent += 1;
+                    let start = self.output.len();
+                    let items = match foreach.body {
+                        [init @ .., Region::Continue] => init,
+                        items => items,
+                    };
+                    for item in items {
+                        self.transpile_region(cfg, item, is_function);
                     }
-                    Instruction::FuncEnd => {}
-                    _ => {
-                        states.entry(current).or_default().push(instr);
+                    if foreach.exits && !foreach.body.last().is_some_and(Region::ends_with_transfer) {
+                        self.emit("break");
+                    } else if self.output.len() == start {
+                        self.emit("pass");
                     }
+                    self.indent -= 1;
+                } else {
+                    self.emit("while True:");
+                    self.indent += 1;
+                    self.transpile_nested(cfg, body, is_function);
+                    self.indent -= 1;
                 }
             }
-
-            // Generate code for each state
-            let mut sorted_states: Vec<_> = states.keys().copied().collect();
-            sorted_states.sort();
-
-            for state_id in sorted_states {
-                self.emit(&format!("if _state == {}:", state_id));
+            Region::Break => self.emit("break"),
+            Region::Continue => self.emit("continue"),
+            Region::Return(value) => self.transpile_return(value, is_function),
+            Region::Dispatch(blocks) => {
+                // State machine over the basic blocks
+                let handlers = cfg.handlers();
+                self.emit(&format!("_state = {}", cfg.entry()));
+                if !handlers.is_empty() {
+                    self.emit("_handlers = []");
+                }
+                self.emit("while True:");
                 self.indent += 1;
-
-                let state_lines = states.get(&state_id).map(|v| v.as_slice()).unwrap_or(&[]);
-                if state_lines.is_empty() {
-                    self.emit("pass");
-                } else {
-                    for instr in state_lines {
-                        self.transpile_instruction(instr, &state_map, is_function);
-                    }
+                if !handlers.is_empty() {
+                    self.emit("try:");
+                    self.indent += 1;
                 }
-
-                // State transition
-                let _last_op = state_lines.last().map(|i| std::mem::discriminant(*i));
-                let needs_transition = state_lines.is_empty()
-                    || !matches!(
-                        state_lines.last(),
-                        Some(Instruction::CondJump { .. })
-                            | Some(Instruction::Jump { .. })
-                            | Some(Instruction::Return { .. })
-                    );
-
-                if needs_transition {
-                    let next_state = state_id + 1;
-                    if states.contains_key(&next_state) {
-                        self.emit(&format!("_state = {} - 1", next_state));
-                        self.emit("continue");
-                    } else {
-                        self.emit("break");
+                let outer = std::mem::replace(&mut self.handlers, handlers);
+                for (i, &id) in blocks.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { "elif" };
+                    self.emit(&format!("{} _state == {}:", keyword, id));
+                    self.indent += 1;
+                    for instr in &cfg.block(id).instructions {
+                        self.transpile_instruction(instr);
                     }
+                    match &cfg.block(id).terminator {
+                        Terminator::Goto(target) => self.transpile_transition(cfg, *target),
+                        Terminator::Branch {
+                            cond,
+                            target,
+                            fallthrough,
+                        } => {
+                            self.emit(&format!("if {}:", self.resolve_value(cond)));
+                            self.indent += 1;
+                            self.transpile_transition(cfg, *target);
+                            self.indent -= 1;
+                            self.transpile_transition(cfg, *fallthrough);
+                        }
+                        terminator @ Terminator::Switch { value, fallthrough, .. } => {
+                            self.emit(&format!("match {}:", self.resolve_value(value)));
+                            self.indent += 1;
+                            for (values, target) in terminator.switch_cases() {
+                                self.emit(&format!("case {}:", case_patterns(&values)));
+                                self.indent += 1;
+                                self.transpile_transition(cfg, target);
+                                self.indent -= 1;
+                            }
+                            self.indent -= 1;
+                            self.transpile_transition(cfg, *fallthrough);
+                        }
+                        Terminator::Return(value) => self.transpile_return(value, is_function),
+                    }
+                    self.indent -= 1;
+                }
+                self.emit("break");
+                let handlers = std::mem::replace(&mut self.handlers, outer);
+                if !handlers.is_empty() {
+                    self.indent -= 1;
+                    self.transpile_handlers(cfg, &handlers);
                 }
-
                 self.indent -= 1;
             }
+        }
+    }
 
-            self.emit("break");
+    /// Unwind to the innermost registered handler of a state machine
+    fn transpile_handlers(&mut self, cfg: &Cfg, handlers: &[(String, i64)]) {
+        self.emit("except Exception as _e:");
+        self.indent += 1;
+        self.emit("if not _handlers:");
+        self.emit("    raise");
+        self.emit("_err = _e.args[0] if isinstance(_e, SuiError) else str(_e)");
+        self.emit("_h = _handlers.pop()");
+        for (k, (err, label)) in handlers.iter().enumerate() {
+            let keyword = if k == 0 { "if" } else { "elif" };
+            self.emit(&format!("{} _h == {}:", keyword, k));
+            self.indent += 1;
+            self.emit(&format!("{} = _err", err));
+            // A handler without a defined label leaves the code
+            let target = cfg.label_block(*label).unwrap_or_else(|| cfg.exit());
+            self.transpile_transition(cfg, target);
             self.indent -= 1;
+        }
+        self.indent -= 1;
+    }
+
+    /// Move the state machine to another block
+    fn transpile_transition(&mut self, cfg: &Cfg, target: BlockId) {
+        if target == cfg.exit() {
+            self.emit("break");
         } else {
-            // Simple case: no labels
-            for instr in instructions {
-                if !matches!(instr, Instruction::FuncEnd) {
-                    self.transpile_instruction(instr, &HashMap::new(), is_function);
-                }
-            }
+            self.emit(&format!("_state = {}", target));
+            self.emit("continue");
+        }
+    }
+
+    /// Return from a function, or stop the program at top level
+    fn transpile_return(&mut self, value: &str, is_function: bool) {
+        if is_function {
+            self.emit(&format!("return {}", self.resolve_value(value)));
+        } else {
+            self.emit("sys.exit(0)");
         }
     }
 
     /// Transpile a single instruction
-    fn transpile_instruction(
-        &mut self,
-        instr: &Instruction,
-        state_map: &HashMap<i64, usize>,
-        _is_function: bool,
-    ) {
+    fn transpile_instruction(&mut self, instr: &Instruction) {
         match instr {
-            Instruction::Empty | Instruction::Comment | Instruction::Label { .. } | Instruction::Import { .. } => {
+            Instruction::Empty
+            | Instruction::Comment
+            | Instruction::Const { .. }
+            | Instruction::Label { .. }
+            | Instruction::Import { .. } => {
                 // Import is handled at runtime, skip in transpilation
             }
 
+            Instruction::Invalid { raw, .. } => {
+                self.emit(&format!("# invalid: {}", raw));
+            }
+
             Instruction::Assign { target, value } => {
                 self.emit(&format!("{} = {}", target, self.resolve_value(value)));
             }
@@ -181,36 +303,40 @@ impl Sui2Py {
                 ));
             }
 
-            Instruction::Div { result, a, b } => {
+            Instruction::Div { result, a, b } => self.transpile_division(result, a, "/", b),
+
+            Instruction::Mod { result, a, b } => self.transpile_division(result, a, "%", b),
+
+            Instruction::Lt { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} / {}",
+                    "{} = 1 if {} < {} else 0",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Mod { result, a, b } => {
+            Instruction::Gt { result, a, b } => {
                 self.emit(&format!(
-                    "{} = {} % {}",
+                    "{} = 1 if {} > {} else 0",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Lt { result, a, b } => {
+            Instruction::Le { result, a, b } => {
                 self.emit(&format!(
-                    "{} = 1 if {} < {} else 0",
+                    "{} = 1 if {} <= {} else 0",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
                 ));
             }
 
-            Instruction::Gt { result, a, b } => {
+            Instruction::Ge { result, a, b } => {
                 self.emit(&format!(
-                    "{} = 1 if {} > {} else 0",
+                    "{} = 1 if {} >= {} else 0",
                     result,
                     self.resolve_value(a),
                     self.resolve_value(b)
@@ -252,24 +378,13 @@ impl Sui2Py {
                 ));
             }
 
-            Instruction::CondJump { cond, label } => {
-                if let Some(&state) = state_map.get(label) {
-                    self.emit(&format!("if {}:", self.resolve_value(cond)));
-                    self.indent += 1;
-                    self.emit(&format!("_state = {} - 1", state));
-                    self.emit("continue");
-                    self.indent -= 1;
-                }
-            }
-
-            Instruction::Jump { label } => {
-                if let Some(&state) = state_map.get(label) {
-                    self.emit(&format!("_state = {} - 1", state));
-                    self.emit("continue");
-                }
-            }
-
-            Instruction::FuncDef { .. } | Instruction::FuncEnd => {}
+            // Control flow is lowered through the IR
+            Instruction::CondJump { .. }
+            | Instruction::Jump { .. }
+            | Instruction::Switch { .. }
+            | Instruction::Return { .. }
+            | Instruction::FuncDef { .. }
+            | Instruction::FuncEnd => {}
 
             Instruction::Call { result, func_id, args } => {
                 let args_str = args
@@ -280,32 +395,69 @@ impl Sui2Py {
                 self.emit(&format!("{} = f{}({})", result, func_id, args_str));
             }
 
-            Instruction::Return { value } => {
-                self.emit(&format!("return {}", self.resolve_value(value)));
-            }
-
             Instruction::ArrayCreate { var, size } => {
                 self.emit(&format!("{} = [0] * {}", var, self.resolve_value(size)));
             }
 
-            Instruction::ArrayRead { result, arr, idx } => {
+            Instruction::ArrayRead { result, arr, idx, indices } => {
                 self.emit(&format!(
-                    "{} = {}[int({})]",
+                    "{} = {}{}",
                     result,
                     self.resolve_value(arr),
-                    self.resolve_value(idx)
+                    self.subscripts(idx, indices)
                 ));
             }
 
-            Instruction::ArrayWrite { arr, idx, value } => {
+            Instruction::ArrayWrite { arr, idx, indices, value } => {
                 self.emit(&format!(
-                    "{}[int({})] = {}",
+                    "{}{} = {}",
                     self.resolve_value(arr),
-                    self.resolve_value(idx),
+                    self.subscripts(idx, indices),
                     self.resolve_value(value)
                 ));
             }
 
+            Instruction::Iter { iter, source } => {
+                let source = self.resolve_value(source);
+                let iterable = match Lexer::parse_value(&source) {
+                    ParsedValue::Integer(_) | ParsedValue::Float(_) => {
+                        format!("range(int({}))", source)
+                    }
+                    ParsedValue::Variable(_) => format!(
+                        "range(int({0})) if isinstance({0}, (int, float)) else {0}",
+                        source
+                    ),
+                    ParsedValue::String(_) => source,
+                };
+                self.emit(&format!("{} = iter({})", iter, iterable));
+            }
+
+            Instruction::Next { done, var, iter } => {
+                self.emit("try:");
+                self.indent += 1;
+                self.emit(&format!("{} = next({})", var, iter));
+                self.emit(&format!("{} = 0", done));
+                self.indent -= 1;
+                self.emit("except StopIteration:");
+                self.indent += 1;
+                self.emit(&format!("{} = 1", done));
+                self.indent -= 1;
+            }
+
+            Instruction::Try { err, label } => {
+                let k = self.handlers.iter().position(|(e, l)| e == err && l == label);
+                self.emit(&format!("_handlers.append({})", k.unwrap_or_default()));
+            }
+
+            Instruction::EndTry => {
+                self.emit("if _handlers:");
+                self.emit("    _handlers.pop()");
+            }
+
+            Instruction::Raise { value } => {
+                self.emit(&format!("raise SuiError({})", self.resolve_value(value)));
+            }
+
             Instruction::Output { value } => {
                 self.emit(&format!("print({})", self.resolve_value(value)));
             }
@@ -334,13 +486,44 @@ impl Sui2Py {
                 // Remove quotes if present
                 let func_clean = func_str.trim_matches('"');
 
-                if func_clean.contains('.') {
+                if let ("floordiv", [a, b, ..]) = (func_clean, args.as_slice()) {
+                    self.transpile_division(result, a, "//", b);
+                } else if let ("ipow", [a, b, ..]) = (func_clean, args.as_slice()) {
+                    let (a, b) = (self.resolve_value(a), self.resolve_value(b));
+                    self.emit(&format!("{} = {} ** {}", result, a, b));
+                } else if let Some(expr) = self.time_builtin(func_clean, args) {
+                    self.emit("import time");
+                    self.emit(&format!("{} = {}", result, expr));
+                } else if func_clean == "date.format" {
+                    let arg = |i: usize| args.get(i).map(|a| self.resolve_value(a));
+                    let timestamp = arg(0).unwrap_or_else(|| "0".to_string());
+                    let pattern = arg(1).unwrap_or_else(|| "\"%Y-%m-%d %H:%M:%S\"".to_string());
+                    self.emit("import datetime");
+                    self.emit(&format!(
+                        "{} = datetime.datetime.fromtimestamp(float({}), datetime.timezone.utc).strftime(str({}))",
+                        result, timestamp, pattern
+                    ));
+                } else if let Some(expr) = self.regex_builtin(func_clean, args) {
+                    self.emit("import re");
+                    self.emit(&format!("{} = {}", result, expr));
+                } else if let Some(expr) = self.data_builtin(func_clean, args) {
+                    if func_clean.starts_with("json.") {
+                        self.emit("import json");
+                    }
+                    self.emit(&format!("{} = {}", result, expr));
+                } else if func_clean.contains('.') {
                     // Module function: import and call
                     let parts: Vec<&str> = func_clean.rsplitn(2, '.').collect();
                     let func_name = parts[0];
                     let module = parts.get(1).unwrap_or(&"");
                     self.emit(&format!("import {}", module));
                     self.emit(&format!("{} = {}.{}({})", result, module, func_name, args_str));
+                } else if let Some(expr) = self
+                    .array_builtin(func_clean, args)
+                    .or_else(|| self.bitwise_builtin(func_clean, args))
+                    .or_else(|| self.string_builtin(func_clean, args))
+                {
+                    self.emit(&format!("{} = {}", result, expr));
                 } else {
                     // Builtin function
                     self.emit(&format!("{} = {}({})", result, func_clean, args_str));
@@ -349,14 +532,189 @@ impl Sui2Py {
         }
     }
 
+    /// `/`, `%` or `//` with the zero divisor result of the division policy
+    fn transpile_division(&mut self, result: &str, a: &str, op: &str, b: &str) {
+        let (a, b) = (self.resolve_value(a), self.resolve_value(b));
+        let fallback = match self.division_policy {
+            _ if nonzero_literal(&b) => None,
+            DivisionPolicy::SilentNaN => Some("float(\"nan\")"),
+            DivisionPolicy::Error => None,
+            DivisionPolicy::SaturateZero => Some("0"),
+        };
+        let expr = if op == "%" {
+            // Python's % takes the sign of the divisor and Sui's, like C's,
+            // that of the dividend
+            self.emit("import math");
+            format!(
+                "(math.fmod({a}, {b}) if isinstance({a}, float) or isinstance({b}, float) else (-1 if {a} < 0 else 1) * (abs({a}) % abs({b})))",
+                a = a,
+                b = b
+            )
+        } else {
+            format!("{} {} {}", a, op, b)
+        };
+        match fallback {
+            Some(fallback) => self.emit(&format!("{} = {} if {} != 0 else {}", result, expr, b, fallback)),
+            None => self.emit(&format!("{} = {}", result, expr)),
+        }
+    }
+
+    /// f-string for `fmt` with a literal template, if its arguments can be
+    /// written inside one
+    fn fstring(&self, args: &[String]) -> Option<String> {
+        let parts = template_parts(args.first()?)?;
+        let mut result = String::from("f\"");
+        for part in parts {
+            match part {
+                TemplatePart::Text(text) => result.push_str(&text.replace('{', "{{").replace('}', "}}")),
+                TemplatePart::Field { arg, spec } => {
+                    let expr = self.resolve_value(args.get(arg + 1)?);
+                    // Quotes and backslashes are not allowed in the braces
+                    // before Python 3.12
+                    if expr.contains(['"', '\\', '{', '}']) || spec.contains('{') {
+                        return None;
+                    }
+                    result.push('{');
+                    result.push_str(&expr);
+                    if !spec.is_empty() {
+                        result.push(':');
+                        result.push_str(&spec);
+                    }
+                    result.push('}');
+                }
+            }
+        }
+        result.push('"');
+        Some(result)
+    }
+
+    /// `time` module expression for a time builtin such as `time.now`
+    fn time_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        let expr = match func.rsplit('.').next().unwrap_or(func) {
+            "now" => "time.time()".to_string(),
+            "monotonic" => "time.monotonic()".to_string(),
+            // time.sleep returns None; the builtin gives 0
+            "sleep_ms" => format!("time.sleep(max({}, 0) / 1000) or 0", arg(0)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// `re` module expression for a regex builtin such as `re.findall`
+    fn regex_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("\"\"".to_string(), |a| self.resolve_value(a));
+        let (pattern, text) = (arg(0), arg(1));
+        let expr = match func {
+            "re.match" => format!("int(re.match({}, str({})) is not None)", pattern, text),
+            "re.find" => format!("(lambda m: m.group(0) if m else 0)(re.search({}, str({})))", pattern, text),
+            "re.findall" => format!("[m.group(0) for m in re.finditer({}, str({}))]", pattern, text),
+            // A function keeps the replacement literal
+            "re.replace" => format!("re.sub({}, lambda _: str({}), str({}))", pattern, arg(2), text),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Dict or `json` expression for a `struct.*` or `json.*` builtin
+    fn data_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        // Field names are strings, as in the interpreter
+        let name = |i: usize| match arg(i) {
+            name if name.starts_with('"') => name,
+            name => format!("str({})", name),
+        };
+        let expr = match func {
+            "struct.new" => {
+                let fields: Vec<String> =
+                    (0..args.len()).step_by(2).map(|i| format!("{}: {}", name(i), arg(i + 1))).collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            "struct.get" => format!("{}.get({}, 0)", arg(0), name(1)),
+            "struct.set" => format!("{{**{}, {}: {}}}", arg(0), name(1), arg(2)),
+            // Compact, like the interpreter and JSON.stringify
+            "json.encode" => format!("json.dumps({}, separators=(\",\", \":\"), ensure_ascii=False)", arg(0)),
+            "json.decode" => format!("json.loads({})", arg(0)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Native list expression for an array builtin such as `slice`
+    fn array_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        let expr = match func {
+            // Independent rows, unlike [[0] * cols] * rows
+            "array2d" => format!("[[0] * int({}) for _ in range(int({}))]", arg(1), arg(0)),
+            "slice" => match args.len() {
+                0 | 1 => format!("{}[:]", arg(0)),
+                2 => format!("{}[int({}):]", arg(0), arg(1)),
+                _ => format!("{}[int({}):int({})]", arg(0), arg(1), arg(2)),
+            },
+            "concat" => format!("{} + {}", arg(0), arg(1)),
+            "fill" => format!("[{}] * len({})", arg(1), arg(0)),
+            "copy" => format!("{1}[:len({0})] + {0}[len({1}):]", arg(0), arg(1)),
+            "sum" => format!("sum({})", arg(0)),
+            "sort" => format!("sorted({})", arg(0)),
+            "reverse" => format!("{}[::-1]", arg(0)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Python expression for a bitwise builtin such as `band`, wrapped to i64
+    fn bitwise_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("0".to_string(), |a| self.resolve_value(a));
+        let expr = match func {
+            "band" => format!("int({}) & int({})", arg(0), arg(1)),
+            "bor" => format!("int({}) | int({})", arg(0), arg(1)),
+            "bxor" => format!("int({}) ^ int({})", arg(0), arg(1)),
+            "bnot" => format!("~int({})", arg(0)),
+            "shl" => format!(
+                "((int({}) << (int({}) & 63)) + 2**63) % 2**64 - 2**63",
+                arg(0),
+                arg(1)
+            ),
+            "shr" => format!("int({}) >> (int({}) & 63)", arg(0), arg(1)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    /// Python expression for a string builtin such as `split`
+    fn string_builtin(&self, func: &str, args: &[String]) -> Option<String> {
+        let arg = |i: usize| args.get(i).map_or("\"\"".to_string(), |a| self.resolve_value(a));
+        let expr = match func {
+            "upper" | "lower" | "strip" => format!("str({}).{}()", arg(0), func),
+            // An empty separator splits on whitespace, like no separator
+            "split" if args.len() < 2 => format!("str({}).split()", arg(0)),
+            "split" => format!("str({}).split({} or None)", arg(0), arg(1)),
+            "join" => format!("{}.join(map(str, {}))", arg(1), arg(0)),
+            "replace" => format!("str({}).replace({}, {})", arg(0), arg(1), arg(2)),
+            "find" => format!("str({}).find({})", arg(0), arg(1)),
+            "contains" => format!("int({} in {})", arg(1), arg(0)),
+            "startswith" | "endswith" => format!("int(str({}).{}({}))", arg(0), func, arg(1)),
+            "format" => {
+                let values: Vec<String> = args.iter().skip(1).map(|a| self.resolve_value(a)).collect();
+                format!("str({}).format({})", arg(0), values.join(", "))
+            }
+            "fmt" => self.fstring(args).unwrap_or_else(|| {
+                let values: Vec<String> = args.iter().skip(1).map(|a| self.resolve_value(a)).collect();
+                format!("str({}).format({})", arg(0), values.join(", "))
+            }),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
     /// Transpile Sui code to Python
     pub fn transpile_to_python(&mut self, code: &str) -> Result<String, TranspileError> {
         self.output.clear();
         self.indent = 0;
 
-        // Parse the code
-        let (instructions, functions) =
-            Parser::parse(code).map_err(|e| TranspileError::Parse(e.to_string()))?;
+        // Parse and lower the code
+        let program = Program::from_source(code).map_err(|e| TranspileError::Parse(e.to_string()))?;
+        check_templates(&program)?;
 
         // Header
         self.emit("#!/usr/bin/env python3");
@@ -380,8 +738,15 @@ impl Sui2Py {
         self.indent -= 1;
         self.emit("");
 
+        let mut cfgs = std::iter::once(&program.main).chain(program.functions.iter().map(|f| &f.body));
+        if cfgs.any(uses_exceptions) {
+            self.emit("class SuiError(Exception):");
+            self.emit("    pass");
+            self.emit("");
+        }
+
         // Output function definitions
-        for func in &functions {
+        for func in &program.functions {
             let args_str = (0..func.arg_count)
                 .map(|i| format!("a{}", i))
                 .collect::<Vec<_>>()
@@ -389,11 +754,7 @@ impl Sui2Py {
             self.emit(&format!("def f{}({}):", func.id, args_str));
             self.indent += 1;
 
-            if func.body.is_empty() {
-                self.emit("pass");
-            } else {
-                self.transpile_block(&func.body, true);
-            }
+            self.transpile_cfg(&func.body, true);
 
             self.indent -= 1;
             self.emit("");
@@ -401,11 +762,7 @@ impl Sui2Py {
 
         // Output main code
         self.emit("# Main");
-        if instructions.is_empty() {
-            self.emit("pass");
-        } else {
-            self.transpile_block(&instructions, false);
-        }
+        self.transpile_cfg(&program.main, false);
 
         Ok(self.output.join("\n"))
     }
@@ -426,6 +783,13 @@ impl Transpiler for Sui2Py {
     }
 }
 
+/// Whether a graph raises errors or handles them
+fn uses_exceptions(cfg: &Cfg) -> bool {
+    cfg.blocks().iter().flat_map(|b| &b.instructions).any(|instr| {
+        matches!(instr, Instruction::Try { .. } | Instruction::Raise { .. })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -459,4 +823,203 @@ $ g0 0 5
         assert!(result.contains("def f0(a0):"));
         assert!(result.contains("g0 = f0(5)"));
     }
+
+    #[test]
+    fn test_structured_loop() {
+        let code = r#"
+= v0 0
+: 0
+< v1 v0 3
+! v2 v1
+? v2 1
++ v0 v0 1
+@ 0
+: 1
+. v0
+"#;
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("while True:"));
+        assert!(result.contains("if not v2:"));
+        assert!(!result.contains("_state"));
+    }
+
+    #[test]
+    fn test_jump_table() {
+        let code = "% v0 g0 3\nJ v0 1 2 1\n. \"other\"\n@ 9\n: 1\n. \"even\"\n@ 9\n: 2\n. \"one\"\n: 9";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains(
+            "match v0:\n    case 0 | 2:\n        print(\"even\")\n    case 1:\n        print(\"one\")\n    \
+             case _:\n        print(\"other\")"
+        ));
+        assert!(!result.contains("_state"));
+
+        // Cases that fall into each other need the state machine
+        let result = transpiler.transpile_to_python("J v0 1 2\n: 1\n. 1\n: 2\n. 2").unwrap();
+        assert!(result.contains("match v0:\n            case 0:\n                _state = 2"));
+    }
+
+    #[test]
+    fn test_nested_arrays() {
+        let code = "R v0 \"array2d\" 2 3\n{ v0 1 2 7\n] v1 v0 1 2\n. v1";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = [[0] * int(3) for _ in range(int(2))]"));
+        assert!(result.contains("v0[int(1)][int(2)] = 7"));
+        assert!(result.contains("v1 = v0[int(1)][int(2)]"));
+    }
+
+    #[test]
+    fn test_array_builtins() {
+        let code = "R v1 \"slice\" v0 1 3\nR v2 \"concat\" v0 v1\n\
+                    R v3 \"sort\" v2\nR v4 \"reverse\" v3";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v1 = v0[int(1):int(3)]"));
+        assert!(result.contains("v2 = v0 + v1"));
+        assert!(result.contains("v3 = sorted(v2)"));
+        assert!(result.contains("v4 = v3[::-1]"));
+    }
+
+    #[test]
+    fn test_floordiv_ipow() {
+        let code = "R v0 \"floordiv\" v1 2\nR v2 \"floordiv\" v1 v3\nR v4 \"ipow\" v1 3";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = v1 // 2\n"));
+        assert!(result.contains("v2 = v1 // v3 if v3 != 0 else float(\"nan\")"));
+        assert!(result.contains("v4 = v1 ** 3"));
+    }
+
+    #[test]
+    fn test_time_builtins() {
+        let code = "R v0 \"time.now\"\nR v1 \"time.monotonic\"\nR v2 \"time.sleep_ms\" 250";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("import time\nv0 = time.time()\n"));
+        assert!(result.contains("v1 = time.monotonic()"));
+        assert!(result.contains("v2 = time.sleep(max(250, 0) / 1000) or 0"));
+    }
+
+    #[test]
+    fn test_regex_builtins() {
+        let code = "R v0 \"re.match\" \"a+\" v1\nR v2 \"re.find\" \"b\" v1\nR v3 \"re.findall\" \"b\" v1\n\
+                    R v4 \"re.replace\" \"b\" v1 \"c\"";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("import re\nv0 = int(re.match(\"a+\", str(v1)) is not None)\n"));
+        assert!(result.contains("v2 = (lambda m: m.group(0) if m else 0)(re.search(\"b\", str(v1)))"));
+        assert!(result.contains("v3 = [m.group(0) for m in re.finditer(\"b\", str(v1))]"));
+        assert!(result.contains("v4 = re.sub(\"b\", lambda _: str(\"c\"), str(v1))"));
+    }
+
+    #[test]
+    fn test_struct_json() {
+        let code = "R v0 \"struct.new\" \"a\" 1 v9 2\nR v1 \"struct.get\" v0 \"a\"\nR v2 \"struct.set\" v0 \"b\" v1\n\
+                    R v3 \"json.encode\" v2\nR v4 \"json.decode\" v3";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = {\"a\": 1, str(v9): 2}\n"));
+        assert!(result.contains("v1 = v0.get(\"a\", 0)\n"));
+        assert!(result.contains("v2 = {**v0, \"b\": v1}\n"));
+        assert!(result.contains("import json\nv3 = json.dumps(v2, separators=(\",\", \":\"), ensure_ascii=False)\n"));
+        assert!(result.contains("import json\nv4 = json.loads(v3)"));
+    }
+
+    #[test]
+    fn test_fmt_date() {
+        let code = "R v0 \"fmt\" \"{:>8.2f} {{x}} {0}\" v1\nR v2 \"fmt\" v3 v1\nR v4 \"fmt\" \"{}\" \"s\"\n\
+                    R v5 \"date.format\" v1 \"%F\"";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = f\"{v1:>8.2f} {{x}} {v1}\""));
+        assert!(result.contains("v2 = str(v3).format(v1)"));
+        assert!(result.contains("v4 = str(\"{}\").format(\"s\")"));
+        assert!(result.contains(
+            "import datetime\nv5 = datetime.datetime.fromtimestamp(float(v1), datetime.timezone.utc).strftime(str(\"%F\"))"
+        ));
+
+        let err = transpiler.transpile_to_python("R v0 \"fmt\" \"{!r}\" v1").unwrap_err();
+        assert_eq!(err.to_string(), "Code generation error: fmt: invalid format field '{!r}'");
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let code = "R v1 \"split\" v0 \",\"\nR v2 \"join\" v1\nR v3 \"endswith\" v0 \"x\"\n\
+                    R v4 \"format\" \"{} {}\" v1 v2";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v1 = str(v0).split(\",\" or None)"));
+        assert!(result.contains("v2 = \"\".join(map(str, v1))"));
+        assert!(result.contains("v3 = int(str(v0).endswith(\"x\"))"));
+        assert!(result.contains("v4 = str(\"{} {}\").format(v1, v2)"));
+        let result = transpiler.transpile_to_python("R v1 \"contains\" v0 3").unwrap();
+        assert!(result.contains("v1 = int(3 in v0)"));
+    }
+
+    #[test]
+    fn test_bitwise_builtins() {
+        let code = "R v1 \"bxor\" v0 255\nR v2 \"shl\" v0 3\nR v3 \"bnot\" v0";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v1 = int(v0) ^ int(255)"));
+        assert!(result.contains("v2 = ((int(v0) << (int(3) & 63)) + 2**63) % 2**64 - 2**63"));
+        assert!(result.contains("v3 = ~int(v0)"));
+    }
+
+    #[test]
+    fn test_division_policy() {
+        let code = "/ v0 v1 v2\n% v0 v1 3";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = v1 / v2 if v2 != 0 else float(\"nan\")"));
+        assert!(result.ends_with(
+            "import math\nv0 = (math.fmod(v1, 3) if isinstance(v1, float) or isinstance(3, float) \
+             else (-1 if v1 < 0 else 1) * (abs(v1) % abs(3)))"
+        ));
+
+        transpiler.set_division_policy(DivisionPolicy::SaturateZero);
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = v1 / v2 if v2 != 0 else 0"));
+
+        transpiler.set_division_policy(DivisionPolicy::Error);
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = v1 / v2\n"));
+    }
+
+    #[test]
+    fn test_try_raise() {
+        let code = "T v0 0\nE \"boom\"\n: 0\n. v0";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("class SuiError(Exception):"));
+        assert!(result.contains("_handlers = []\nwhile True:\n    try:\n        if _state == 0:"));
+        assert!(result.contains("_handlers.append(0)\n            raise SuiError(\"boom\")"));
+        assert!(result.contains(
+            "    except Exception as _e:\n        if not _handlers:\n            raise\n"
+        ));
+        assert!(result.contains("if _h == 0:\n            v0 = _err\n            _state = 1\n            continue"));
+
+        // Without exceptions there is no error class
+        let result = transpiler.transpile_to_python(". 1").unwrap();
+        assert!(!result.contains("SuiError"));
+    }
+
+    #[test]
+    fn test_foreach_loop() {
+        let code = "I v0 v1\n: 0\nN v2 v3 v0\n? v2 1\n< v4 v3 2\n? v4 0\n. v3\n@ 0\n: 1\n. 9";
+        let mut transpiler = Sui2Py::new();
+        let result = transpiler.transpile_to_python(code).unwrap();
+        assert!(result.contains("v0 = iter(range(int(v1)) if isinstance(v1, (int, float)) else v1)"));
+        assert!(result.contains(
+            "for v3 in v0:\n    v4 = 1 if v3 < 2 else 0\n    if v4:\n        continue\n    print(v3)\nprint(9)"
+        ));
+        assert!(!result.contains("next("));
+
+        // A lone advance is not a loop
+        let result = transpiler.transpile_to_python("I v0 \"ab\"\nN v1 v2 v0\n. v2").unwrap();
+        assert!(result.contains("v0 = iter(\"ab\")"));
+        assert!(result.contains("try:\n    v2 = next(v0)\n    v1 = 0\nexcept StopIteration:\n    v1 = 1"));
+    }
 }
@@ -0,0 +1,2174 @@
+//! Sui to WebAssembly compiler
+//!
+//! Compiles Sui programs to a WebAssembly module, emitted either as WAT text
+//! or as a binary `.wasm` image. Both forms are rendered from the same
+//! instruction list, so they always describe the same module.
+//!
+//! Sui is dynamically typed, so every variable slot (`v`, `g`, `a`, function
+//! results and array elements) is assigned a static type by a whole-program
+//! fixpoint before code generation:
+//!
+//! - integer-only slots become `i64` locals/globals
+//! - slots that ever hold a float (or the result of `/`) become `f64`
+//! - strings and arrays are `i32` pointers into linear memory
+//!
+//! Programs that mix strings or arrays with numbers in the same slot, read
+//! input, import modules, or call builtins without a WebAssembly lowering are
+//! rejected with [`TranspileError::Codegen`].
+//!
+//! The module imports its output functions from the host (`env.print_i64`,
+//! `env.print_f64`, `env.print_str`, `env.print_array`) and transcendental
+//! math from `math.*`; [`Sui2Wasm::js_host`] returns a loader that provides
+//! them. Arrays follow reference semantics, like the Python and JavaScript
+//! backends.
+
+use super::{TranspileError, Transpiler};
+use crate::interpreter::{Function, Instruction, Lexer, ParsedValue, Parser};
+use crate::ir::{BlockId, Cfg, Terminator};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Sui to WebAssembly compiler
+pub struct Sui2Wasm {
+    types: HashMap<Slot, Ty>,
+    arities: HashMap<i64, i64>,
+    strings: BTreeMap<String, u32>,
+    data: Vec<u8>,
+}
+
+impl Default for Sui2Wasm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Static type of a variable slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    I64,
+    F64,
+    Str,
+    Arr,
+}
+
+impl Ty {
+    fn val_type(self) -> ValType {
+        match self {
+            Ty::I64 => ValType::I64,
+            Ty::F64 => ValType::F64,
+            Ty::Str | Ty::Arr => ValType::I32,
+        }
+    }
+
+    fn join(self, other: Ty) -> Option<Ty> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (Ty::I64, Ty::F64) | (Ty::F64, Ty::I64) => Some(Ty::F64),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Ty::I64 | Ty::F64)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Ty::I64 => "int",
+            Ty::F64 => "float",
+            Ty::Str => "string",
+            Ty::Arr => "array",
+        }
+    }
+}
+
+/// A typed storage location. `None` scopes refer to the main program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Slot {
+    Global(i64),
+    Local(Option<i64>, i64),
+    Arg(i64, i64),
+    Ret(i64),
+    Elem,
+}
+
+/// WebAssembly value types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValType {
+    I32,
+    I64,
+    F64,
+}
+
+impl ValType {
+    fn byte(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+            ValType::F64 => 0x7C,
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F64 => "f64",
+        }
+    }
+}
+
+/// The subset of WebAssembly instructions used by the code generator
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Block,
+    Loop,
+    If(Option<ValType>),
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    BrTable(Vec<u32>, u32),
+    Return,
+    Call(u32),
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Load(u32),
+    I64Load(u32),
+    F64Load(u32),
+    I32Load8U(u32),
+    I32Store(u32),
+    I64Store(u32),
+    F64Store(u32),
+    I32Store8(u32),
+    MemorySize,
+    MemoryGrow,
+    I32Const(i32),
+    I64Const(i64),
+    F64Const(f64),
+    I32Eqz,
+    I32Ne,
+    I32GeU,
+    I32LeU,
+    I32Add,
+    I32Sub,
+    I32And,
+    I32Or,
+    I32Shl,
+    I32ShrU,
+    I64Eqz,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64GtS,
+    I64LeS,
+    I64GeS,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64RemS,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    F64Abs,
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Sqrt,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
+    F64Copysign,
+    I32WrapI64,
+    I64ExtendI32U,
+    F64ConvertI64S,
+    I64TruncSatF64S,
+}
+
+impl Op {
+    /// Render the instruction in WAT syntax
+    fn text(&self, module: &Module, func: &Func) -> String {
+        let local = |idx: &u32| format!("${}", func.local_name(*idx));
+        match self {
+            Op::Block => "block".to_string(),
+            Op::Loop => "loop".to_string(),
+            Op::If(None) => "if".to_string(),
+            Op::If(Some(t)) => format!("if (result {})", t.text()),
+            Op::Else => "else".to_string(),
+            Op::End => "end".to_string(),
+            Op::Br(d) => format!("br {}", d),
+            Op::BrIf(d) => format!("br_if {}", d),
+            Op::BrTable(targets, default) => {
+                let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+                format!("br_table {} {}", targets.join(" "), default)
+            }
+            Op::Return => "return".to_string(),
+            Op::Call(idx) => format!("call ${}", module.func_name(*idx)),
+            Op::Drop => "drop".to_string(),
+            Op::Select => "select".to_string(),
+            Op::LocalGet(i) => format!("local.get {}", local(i)),
+            Op::LocalSet(i) => format!("local.set {}", local(i)),
+            Op::LocalTee(i) => format!("local.tee {}", local(i)),
+            Op::GlobalGet(i) => format!("global.get ${}", module.globals[*i as usize].name),
+            Op::GlobalSet(i) => format!("global.set ${}", module.globals[*i as usize].name),
+            Op::I32Load(o) => mem_text("i32.load", *o),
+            Op::I64Load(o) => mem_text("i64.load", *o),
+            Op::F64Load(o) => mem_text("f64.load", *o),
+            Op::I32Load8U(o) => mem_text("i32.load8_u", *o),
+            Op::I32Store(o) => mem_text("i32.store", *o),
+            Op::I64Store(o) => mem_text("i64.store", *o),
+            Op::F64Store(o) => mem_text("f64.store", *o),
+            Op::I32Store8(o) => mem_text("i32.store8", *o),
+            Op::MemorySize => "memory.size".to_string(),
+            Op::MemoryGrow => "memory.grow".to_string(),
+            Op::I32Const(n) => format!("i32.const {}", n),
+            Op::I64Const(n) => format!("i64.const {}", n),
+            Op::F64Const(f) => {
+                if f.is_nan() {
+                    "f64.const nan".to_string()
+                } else {
+                    format!("f64.const {:?}", f)
+                }
+            }
+            other => other.mnemonic().to_string(),
+        }
+    }
+
+    /// Mnemonic for operand-less numeric instructions
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Op::I32Eqz => "i32.eqz",
+            Op::I32Ne => "i32.ne",
+            Op::I32GeU => "i32.ge_u",
+            Op::I32LeU => "i32.le_u",
+            Op::I32Add => "i32.add",
+            Op::I32Sub => "i32.sub",
+            Op::I32And => "i32.and",
+            Op::I32Or => "i32.or",
+            Op::I32Shl => "i32.shl",
+            Op::I32ShrU => "i32.shr_u",
+            Op::I64Eqz => "i64.eqz",
+            Op::I64Eq => "i64.eq",
+            Op::I64Ne => "i64.ne",
+            Op::I64LtS => "i64.lt_s",
+            Op::I64GtS => "i64.gt_s",
+            Op::I64LeS => "i64.le_s",
+            Op::I64GeS => "i64.ge_s",
+            Op::I64Add => "i64.add",
+            Op::I64Sub => "i64.sub",
+            Op::I64Mul => "i64.mul",
+            Op::I64RemS => "i64.rem_s",
+            Op::F64Eq => "f64.eq",
+            Op::F64Ne => "f64.ne",
+            Op::F64Lt => "f64.lt",
+            Op::F64Gt => "f64.gt",
+            Op::F64Le => "f64.le",
+            Op::F64Ge => "f64.ge",
+            Op::F64Abs => "f64.abs",
+            Op::F64Ceil => "f64.ceil",
+            Op::F64Floor => "f64.floor",
+            Op::F64Trunc => "f64.trunc",
+            Op::F64Sqrt => "f64.sqrt",
+            Op::F64Add => "f64.add",
+            Op::F64Sub => "f64.sub",
+            Op::F64Mul => "f64.mul",
+            Op::F64Div => "f64.div",
+            Op::F64Min => "f64.min",
+            Op::F64Max => "f64.max",
+            Op::F64Copysign => "f64.copysign",
+            Op::I32WrapI64 => "i32.wrap_i64",
+            Op::I64ExtendI32U => "i64.extend_i32_u",
+            Op::F64ConvertI64S => "f64.convert_i64_s",
+            Op::I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            _ => "",
+        }
+    }
+
+    /// Encode the instruction in the binary format
+    fn encode(&self, out: &mut Vec<u8>) {
+        let simple = |out: &mut Vec<u8>, byte: u8| out.push(byte);
+        let mem = |out: &mut Vec<u8>, byte: u8, align: u32, offset: u32| {
+            out.push(byte);
+            write_u32(out, align);
+            write_u32(out, offset);
+        };
+        match self {
+            Op::Block => out.extend_from_slice(&[0x02, 0x40]),
+            Op::Loop => out.extend_from_slice(&[0x03, 0x40]),
+            Op::If(None) => out.extend_from_slice(&[0x04, 0x40]),
+            Op::If(Some(t)) => out.extend_from_slice(&[0x04, t.byte()]),
+            Op::Else => simple(out, 0x05),
+            Op::End => simple(out, 0x0B),
+            Op::Br(d) => {
+                out.push(0x0C);
+                write_u32(out, *d);
+            }
+            Op::BrIf(d) => {
+                out.push(0x0D);
+                write_u32(out, *d);
+            }
+            Op::BrTable(targets, default) => {
+                out.push(0x0E);
+                write_u32(out, targets.len() as u32);
+                for t in targets {
+                    write_u32(out, *t);
+                }
+                write_u32(out, *default);
+            }
+            Op::Return => simple(out, 0x0F),
+            Op::Call(idx) => {
+                out.push(0x10);
+                write_u32(out, *idx);
+            }
+            Op::Drop => simple(out, 0x1A),
+            Op::Select => simple(out, 0x1B),
+            Op::LocalGet(i) => {
+                out.push(0x20);
+                write_u32(out, *i);
+            }
+            Op::LocalSet(i) => {
+                out.push(0x21);
+                write_u32(out, *i);
+            }
+            Op::LocalTee(i) => {
+                out.push(0x22);
+                write_u32(out, *i);
+            }
+            Op::GlobalGet(i) => {
+                out.push(0x23);
+                write_u32(out, *i);
+            }
+            Op::GlobalSet(i) => {
+                out.push(0x24);
+                write_u32(out, *i);
+            }
+            Op::I32Load(o) => mem(out, 0x28, 2, *o),
+            Op::I64Load(o) => mem(out, 0x29, 3, *o),
+            Op::F64Load(o) => mem(out, 0x2B, 3, *o),
+            Op::I32Load8U(o) => mem(out, 0x2D, 0, *o),
+            Op::I32Store(o) => mem(out, 0x36, 2, *o),
+            Op::I64Store(o) => mem(out, 0x37, 3, *o),
+            Op::F64Store(o) => mem(out, 0x39, 3, *o),
+            Op::I32Store8(o) => mem(out, 0x3A, 0, *o),
+            Op::MemorySize => out.extend_from_slice(&[0x3F, 0x00]),
+            Op::MemoryGrow => out.extend_from_slice(&[0x40, 0x00]),
+            Op::I32Const(n) => {
+                out.push(0x41);
+                write_i64(out, *n as i64);
+            }
+            Op::I64Const(n) => {
+                out.push(0x42);
+                write_i64(out, *n);
+            }
+            Op::F64Const(f) => {
+                out.push(0x44);
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+            Op::I32Eqz => simple(out, 0x45),
+            Op::I32Ne => simple(out, 0x47),
+            Op::I32GeU => simple(out, 0x4F),
+            Op::I32LeU => simple(out, 0x4D),
+            Op::I32Add => simple(out, 0x6A),
+            Op::I32Sub => simple(out, 0x6B),
+            Op::I32And => simple(out, 0x71),
+            Op::I32Or => simple(out, 0x72),
+            Op::I32Shl => simple(out, 0x74),
+            Op::I32ShrU => simple(out, 0x76),
+            Op::I64Eqz => simple(out, 0x50),
+            Op::I64Eq => simple(out, 0x51),
+            Op::I64Ne => simple(out, 0x52),
+            Op::I64LtS => simple(out, 0x53),
+            Op::I64GtS => simple(out, 0x55),
+            Op::I64LeS => simple(out, 0x57),
+            Op::I64GeS => simple(out, 0x59),
+            Op::I64Add => simple(out, 0x7C),
+            Op::I64Sub => simple(out, 0x7D),
+            Op::I64Mul => simple(out, 0x7E),
+            Op::I64RemS => simple(out, 0x81),
+            Op::F64Eq => simple(out, 0x61),
+            Op::F64Ne => simple(out, 0x62),
+            Op::F64Lt => simple(out, 0x63),
+            Op::F64Gt => simple(out, 0x64),
+            Op::F64Le => simple(out, 0x65),
+            Op::F64Ge => simple(out, 0x66),
+            Op::F64Abs => simple(out, 0x99),
+            Op::F64Ceil => simple(out, 0x9B),
+            Op::F64Floor => simple(out, 0x9C),
+            Op::F64Trunc => simple(out, 0x9D),
+            Op::F64Sqrt => simple(out, 0x9F),
+            Op::F64Add => simple(out, 0xA0),
+            Op::F64Sub => simple(out, 0xA1),
+            Op::F64Mul => simple(out, 0xA2),
+            Op::F64Div => simple(out, 0xA3),
+            Op::F64Min => simple(out, 0xA4),
+            Op::F64Max => simple(out, 0xA5),
+            Op::F64Copysign => simple(out, 0xA6),
+            Op::I32WrapI64 => simple(out, 0xA7),
+            Op::I64ExtendI32U => simple(out, 0xAD),
+            Op::F64ConvertI64S => simple(out, 0xB9),
+            Op::I64TruncSatF64S => out.extend_from_slice(&[0xFC, 0x06]),
+        }
+    }
+}
+
+fn mem_text(name: &str, offset: u32) -> String {
+    if offset == 0 {
+        name.to_string()
+    } else {
+        format!("{} offset={}", name, offset)
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, mut n: u32) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_i64(out: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        let done = (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, body: Vec<u8>) {
+    out.push(id);
+    write_u32(out, body.len() as u32);
+    out.extend(body);
+}
+
+/// A host function imported by the module
+struct Import {
+    module: &'static str,
+    field: &'static str,
+    params: Vec<ValType>,
+    result: Option<ValType>,
+}
+
+/// A mutable global of the module
+struct Global {
+    name: String,
+    ty: ValType,
+}
+
+/// A function defined in the module
+struct Func {
+    name: String,
+    export: Option<&'static str>,
+    params: Vec<(String, ValType)>,
+    locals: Vec<(String, ValType)>,
+    result: Option<ValType>,
+    body: Vec<Op>,
+}
+
+impl Func {
+    fn new(name: &str, params: &[(&str, ValType)], result: Option<ValType>) -> Self {
+        Self {
+            name: name.to_string(),
+            export: None,
+            params: params.iter().map(|(n, t)| (n.to_string(), *t)).collect(),
+            locals: Vec::new(),
+            result,
+            body: Vec::new(),
+        }
+    }
+
+    /// Declare a local and return its index
+    fn local(&mut self, name: &str, ty: ValType) -> u32 {
+        self.locals.push((name.to_string(), ty));
+        (self.params.len() + self.locals.len() - 1) as u32
+    }
+
+    fn local_name(&self, idx: u32) -> &str {
+        let idx = idx as usize;
+        if idx < self.params.len() {
+            &self.params[idx].0
+        } else {
+            &self.locals[idx - self.params.len()].0
+        }
+    }
+
+    fn ops(&mut self, ops: &[Op]) {
+        self.body.extend_from_slice(ops);
+    }
+}
+
+/// An in-memory WebAssembly module
+struct Module {
+    imports: Vec<Import>,
+    globals: Vec<Global>,
+    funcs: Vec<Func>,
+    data: Vec<u8>,
+    heap_start: u32,
+}
+
+impl Module {
+    fn func_name(&self, idx: u32) -> String {
+        let idx = idx as usize;
+        if idx < self.imports.len() {
+            self.imports[idx].field.to_string()
+        } else {
+            self.funcs[idx - self.imports.len()].name.clone()
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = Vec::new();
+        out.push(";; Auto-generated from Sui".to_string());
+        out.push("(module".to_string());
+        for imp in &self.imports {
+            out.push(format!(
+                "  (import \"{}\" \"{}\" (func ${}{}))",
+                imp.module,
+                imp.field,
+                imp.field,
+                signature_text(&imp.params, imp.result)
+            ));
+        }
+        out.push("  (memory (export \"memory\") 1)".to_string());
+        for global in &self.globals {
+            let init = match global.ty {
+                ValType::I32 => "i32.const 0".to_string(),
+                ValType::I64 => "i64.const 0".to_string(),
+                ValType::F64 => "f64.const 0".to_string(),
+            };
+            let init = if global.name == "heap" {
+                format!("i32.const {}", self.heap_start)
+            } else {
+                init
+            };
+            out.push(format!(
+                "  (global ${} (mut {}) ({}))",
+                global.name,
+                global.ty.text(),
+                init
+            ));
+        }
+        if !self.data.is_empty() {
+            let escaped: String = self.data.iter().map(|b| format!("\\{:02x}", b)).collect();
+            out.push(format!("  (data (i32.const 0) \"{}\")", escaped));
+        }
+        for func in &self.funcs {
+            let mut header = format!("  (func ${}", func.name);
+            if let Some(export) = func.export {
+                header.push_str(&format!(" (export \"{}\")", export));
+            }
+            for (name, ty) in &func.params {
+                header.push_str(&format!(" (param ${} {})", name, ty.text()));
+            }
+            if let Some(result) = func.result {
+                header.push_str(&format!(" (result {})", result.text()));
+            }
+            out.push(header);
+            for (name, ty) in &func.locals {
+                out.push(format!("    (local ${} {})", name, ty.text()));
+            }
+            let mut depth = 2;
+            for op in &func.body {
+                if matches!(op, Op::End | Op::Else) {
+                    depth -= 1;
+                }
+                out.push(format!("{}{}", "  ".repeat(depth), op.text(self, func)));
+                if matches!(op, Op::Block | Op::Loop | Op::If(_) | Op::Else) {
+                    depth += 1;
+                }
+            }
+            out.push("  )".to_string());
+        }
+        out.push(")".to_string());
+        out.join("\n")
+    }
+
+    fn to_binary(&self) -> Vec<u8> {
+        // Deduplicate function signatures
+        let mut sigs: Vec<(Vec<ValType>, Option<ValType>)> = Vec::new();
+        let mut sig_index = |params: Vec<ValType>, result: Option<ValType>| -> u32 {
+            let sig = (params, result);
+            match sigs.iter().position(|s| *s == sig) {
+                Some(i) => i as u32,
+                None => {
+                    sigs.push(sig);
+                    (sigs.len() - 1) as u32
+                }
+            }
+        };
+        let import_sigs: Vec<u32> = self
+            .imports
+            .iter()
+            .map(|i| sig_index(i.params.clone(), i.result))
+            .collect();
+        let func_sigs: Vec<u32> = self
+            .funcs
+            .iter()
+            .map(|f| sig_index(f.params.iter().map(|p| p.1).collect(), f.result))
+            .collect();
+
+        let mut out = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+
+        // Type section
+        let mut body = Vec::new();
+        write_u32(&mut body, sigs.len() as u32);
+        for (params, result) in &sigs {
+            body.push(0x60);
+            write_u32(&mut body, params.len() as u32);
+            body.extend(params.iter().map(|p| p.byte()));
+            match result {
+                Some(r) => body.extend_from_slice(&[0x01, r.byte()]),
+                None => body.push(0x00),
+            }
+        }
+        write_section(&mut out, 1, body);
+
+        // Import section
+        let mut body = Vec::new();
+        write_u32(&mut body, self.imports.len() as u32);
+        for (imp, sig) in self.imports.iter().zip(&import_sigs) {
+            write_name(&mut body, imp.module);
+            write_name(&mut body, imp.field);
+            body.push(0x00);
+            write_u32(&mut body, *sig);
+        }
+        write_section(&mut out, 2, body);
+
+        // Function section
+        let mut body = Vec::new();
+        write_u32(&mut body, func_sigs.len() as u32);
+        for sig in &func_sigs {
+            write_u32(&mut body, *sig);
+        }
+        write_section(&mut out, 3, body);
+
+        // Memory section: one memory, min 1 page
+        write_section(&mut out, 5, vec![0x01, 0x00, 0x01]);
+
+        // Global section
+        let mut body = Vec::new();
+        write_u32(&mut body, self.globals.len() as u32);
+        for global in &self.globals {
+            body.extend_from_slice(&[global.ty.byte(), 0x01]);
+            let init = match global.ty {
+                ValType::I32 if global.name == "heap" => Op::I32Const(self.heap_start as i32),
+                ValType::I32 => Op::I32Const(0),
+                ValType::I64 => Op::I64Const(0),
+                ValType::F64 => Op::F64Const(0.0),
+            };
+            init.encode(&mut body);
+            body.push(0x0B);
+        }
+        write_section(&mut out, 6, body);
+
+        // Export section
+        let mut body = Vec::new();
+        let exports: Vec<(usize, &'static str)> = self
+            .funcs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| f.export.map(|e| (i, e)))
+            .collect();
+        write_u32(&mut body, exports.len() as u32 + 1);
+        write_name(&mut body, "memory");
+        body.extend_from_slice(&[0x02, 0x00]);
+        for (i, name) in exports {
+            write_name(&mut body, name);
+            body.push(0x00);
+            write_u32(&mut body, (self.imports.len() + i) as u32);
+        }
+        write_section(&mut out, 7, body);
+
+        // Code section
+        let mut body = Vec::new();
+        write_u32(&mut body, self.funcs.len() as u32);
+        for func in &self.funcs {
+            let mut code = Vec::new();
+            // Run-length encode local declarations
+            let mut groups: Vec<(u32, ValType)> = Vec::new();
+            for (_, ty) in &func.locals {
+                match groups.last_mut() {
+                    Some((count, last)) if last == ty => *count += 1,
+                    _ => groups.push((1, *ty)),
+                }
+            }
+            write_u32(&mut code, groups.len() as u32);
+            for (count, ty) in groups {
+                write_u32(&mut code, count);
+                code.push(ty.byte());
+            }
+            for op in &func.body {
+                op.encode(&mut code);
+            }
+            code.push(0x0B);
+            write_u32(&mut body, code.len() as u32);
+            body.extend(code);
+        }
+        write_section(&mut out, 10, body);
+
+        // Data section
+        if !self.data.is_empty() {
+            let mut body = Vec::new();
+            write_u32(&mut body, 1);
+            body.push(0x00);
+            Op::I32Const(0).encode(&mut body);
+            body.push(0x0B);
+            write_u32(&mut body, self.data.len() as u32);
+            body.extend_from_slice(&self.data);
+            write_section(&mut out, 11, body);
+        }
+
+        out
+    }
+}
+
+fn signature_text(params: &[ValType], result: Option<ValType>) -> String {
+    let mut s = String::new();
+    for p in params {
+        s.push_str(&format!(" (param {})", p.text()));
+    }
+    if let Some(r) = result {
+        s.push_str(&format!(" (result {})", r.text()));
+    }
+    s
+}
+
+// Function indices of the imports and runtime helpers
+const PRINT_I64: u32 = 0;
+const PRINT_F64: u32 = 1;
+const PRINT_STR: u32 = 2;
+const PRINT_ARRAY: u32 = 3;
+const MATH_IMPORTS: [&str; 7] = ["sin", "cos", "tan", "log", "log10", "exp", "pow"];
+const RANDINT: u32 = 11;
+const RT_ALLOC: u32 = 12;
+const RT_ARR_NEW: u32 = 13;
+const RT_LEN: u32 = 14;
+const RT_ARR_GET: u32 = 15;
+const RT_ARR_SET: u32 = 16;
+const RT_STR_CONCAT: u32 = 17;
+const RT_STR_EQ: u32 = 18;
+const RT_PRINT_STR: u32 = 19;
+const RT_PRINT_ARR: u32 = 20;
+const FIRST_USER_FUNC: u32 = 21;
+
+/// Global index of the heap pointer
+const HEAP: u32 = 0;
+
+/// Per-function code generation state
+struct FuncCtx<'a> {
+    scope: Option<i64>,
+    func: Func,
+    slots: HashMap<Slot, u32>,
+    global_index: &'a HashMap<i64, u32>,
+    func_index: &'a HashMap<i64, u32>,
+    state: u32,
+    scratch_f: [u32; 2],
+    scratch_i: u32,
+}
+
+impl Sui2Wasm {
+    /// Create a new compiler
+    pub fn new() -> Self {
+        Self {
+            types: HashMap::new(),
+            arities: HashMap::new(),
+            strings: BTreeMap::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Compile Sui code to WebAssembly text format (WAT)
+    pub fn transpile_to_wat(&mut self, code: &str) -> Result<String, TranspileError> {
+        Ok(self.compile(code)?.to_text())
+    }
+
+    /// Compile Sui code to a binary WebAssembly module
+    pub fn compile_to_wasm(&mut self, code: &str) -> Result<Vec<u8>, TranspileError> {
+        Ok(self.compile(code)?.to_binary())
+    }
+
+    /// JavaScript (Node.js) loader that instantiates a compiled module and
+    /// runs its exported `main`, formatting output like the interpreter.
+    ///
+    /// The script reads the module from the path given as its first argument.
+    pub fn js_host() -> &'static str {
+        r#"// Auto-generated host for Sui WebAssembly modules
+const fs = require('fs');
+const bytes = fs.readFileSync(process.argv[2]);
+let memory;
+const fmtFloat = (x) => Number.isNaN(x) ? 'NaN'
+  : !Number.isFinite(x) ? (x > 0 ? 'inf' : '-inf')
+  : Number.isInteger(x) ? x.toFixed(1) : String(x);
+const imports = {
+  env: {
+    print_i64: (x) => console.log(String(x)),
+    print_f64: (x) => console.log(fmtFloat(x)),
+    print_str: (p, n) => console.log(new TextDecoder().decode(new Uint8Array(memory.buffer, p, n))),
+    print_array: (p, n, isFloat) => {
+      const items = isFloat ? Array.from(new Float64Array(memory.buffer, p, n), fmtFloat)
+        : Array.from(new BigInt64Array(memory.buffer, p, n), String);
+      console.log('[' + items.join(', ') + ']');
+    },
+  },
+  math: {
+    sin: Math.sin, cos: Math.cos, tan: Math.tan, log: Math.log,
+    log10: Math.log10, exp: Math.exp, pow: Math.pow,
+    randint: (a, b) => a + BigInt(Math.floor(Math.random() * Number(b - a + 1n))),
+  },
+};
+WebAssembly.instantiate(bytes, imports).then(({ instance }) => {
+  memory = instance.exports.memory;
+  instance.exports.main();
+});
+"#
+    }
+
+    /// Parse, type-check and lower a program into a module
+    fn compile(&mut self, code: &str) -> Result<Module, TranspileError> {
+        self.types.clear();
+        self.strings.clear();
+        // Offset 0 holds an empty object so that null pointers read as
+        // empty strings/arrays.
+        self.data = vec![0; 8];
+
+        let (instructions, functions) =
+            Parser::parse(code).map_err(|e| TranspileError::Parse(e.to_string()))?;
+
+        let mut functions: Vec<Function> = functions;
+        functions.sort_by_key(|f| f.id);
+        // Later definitions of the same id win, as in the interpreter
+        functions.dedup_by(|later, earlier| {
+            if later.id == earlier.id {
+                std::mem::swap(later, earlier);
+                true
+            } else {
+                false
+            }
+        });
+        let arities: HashMap<i64, i64> = functions.iter().map(|f| (f.id, f.arg_count)).collect();
+
+        self.infer_types(&instructions, &functions, &arities)?;
+        self.arities = arities;
+        self.intern_strings(&instructions, &functions);
+
+        // Globals: heap pointer followed by every g-slot in use
+        let mut global_ids = BTreeSet::new();
+        for instr in instructions.iter().chain(functions.iter().flat_map(|f| f.body.iter())) {
+            for operand in instr.operands() {
+                if let Some(('g', idx)) = split_var(operand) {
+                    global_ids.insert(idx);
+                }
+            }
+        }
+        let mut globals = vec![Global {
+            name: "heap".to_string(),
+            ty: ValType::I32,
+        }];
+        let mut global_index = HashMap::new();
+        for id in global_ids {
+            global_index.insert(id, globals.len() as u32);
+            let ty = self.slot_ty(Slot::Global(id)).val_type();
+            globals.push(Global {
+                name: format!("g{}", id),
+                ty,
+            });
+        }
+
+        let func_index: HashMap<i64, u32> = functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.id, FIRST_USER_FUNC + i as u32))
+            .collect();
+
+        let mut funcs = self.runtime_functions();
+        for function in &functions {
+            let params: Vec<(String, ValType)> = (0..function.arg_count)
+                .map(|i| {
+                    let ty = self.slot_ty(Slot::Arg(function.id, i)).val_type();
+                    (format!("a{}", i), ty)
+                })
+                .collect();
+            let result = self.slot_ty(Slot::Ret(function.id)).val_type();
+            let mut func = Func::new(&format!("f{}", function.id), &[], Some(result));
+            func.params = params;
+            let func = self.lower_block(
+                Some(function.id),
+                func,
+                &function.body,
+                &global_index,
+                &func_index,
+            )?;
+            funcs.push(func);
+        }
+        let mut main = Func::new("main", &[], None);
+        main.export = Some("main");
+        let main = self.lower_block(None, main, &instructions, &global_index, &func_index)?;
+        funcs.push(main);
+
+        let heap_start = (self.data.len() as u32 + 7) & !7;
+        Ok(Module {
+            imports: Self::imports(),
+            globals,
+            funcs,
+            data: std::mem::take(&mut self.data),
+            heap_start,
+        })
+    }
+
+    fn imports() -> Vec<Import> {
+        let mut imports = vec![
+            Import {
+                module: "env",
+                field: "print_i64",
+                params: vec![ValType::I64],
+                result: None,
+            },
+            Import {
+                module: "env",
+                field: "print_f64",
+                params: vec![ValType::F64],
+                result: None,
+            },
+            Import {
+                module: "env",
+                field: "print_str",
+                params: vec![ValType::I32, ValType::I32],
+                result: None,
+            },
+            Import {
+                module: "env",
+                field: "print_array",
+                params: vec![ValType::I32, ValType::I32, ValType::I32],
+                result: None,
+            },
+        ];
+        for name in MATH_IMPORTS {
+            let params = if name == "pow" {
+                vec![ValType::F64, ValType::F64]
+            } else {
+                vec![ValType::F64]
+            };
+            imports.push(Import {
+                module: "math",
+                field: name,
+                params,
+                result: Some(ValType::F64),
+            });
+        }
+        imports.push(Import {
+            module: "math",
+            field: "randint",
+            params: vec![ValType::I64, ValType::I64],
+            result: Some(ValType::I64),
+        });
+        imports
+    }
+
+    /// Type of a slot after inference (unassigned slots hold integer zero)
+    fn slot_ty(&self, slot: Slot) -> Ty {
+        self.types.get(&slot).copied().unwrap_or(Ty::I64)
+    }
+
+    /// Static type of an operand in a scope
+    fn operand_ty(&self, scope: Option<i64>, operand: &str) -> Ty {
+        match Lexer::parse_value(operand) {
+            ParsedValue::Variable(_) => match var_slot(scope, operand) {
+                Some(slot) => self.slot_ty(slot),
+                None => Ty::I64,
+            },
+            ParsedValue::Integer(_) => Ty::I64,
+            ParsedValue::Float(_) => Ty::F64,
+            ParsedValue::String(_) => Ty::Str,
+        }
+    }
+
+    /// Widen a slot's type, failing on incompatible mixes
+    fn widen(&mut self, slot: Slot, ty: Ty, changed: &mut bool) -> Result<(), TranspileError> {
+        match self.types.get(&slot).copied() {
+            None => {
+                self.types.insert(slot, ty);
+                *changed = true;
+            }
+            Some(old) => {
+                let joined = old.join(ty).ok_or_else(|| {
+                    TranspileError::Codegen(format!(
+                        "{} holds both {} and {} values",
+                        describe_slot(slot),
+                        old.name(),
+                        ty.name()
+                    ))
+                })?;
+                if joined != old {
+                    self.types.insert(slot, joined);
+                    *changed = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whole-program type inference to a fixpoint
+    fn infer_types(
+        &mut self,
+        main: &[Instruction],
+        functions: &[Function],
+        arities: &HashMap<i64, i64>,
+    ) -> Result<(), TranspileError> {
+        loop {
+            let mut changed = false;
+            let blocks = std::iter::once((None, main))
+                .chain(functions.iter().map(|f| (Some(f.id), f.body.as_slice())));
+            for (scope, body) in blocks {
+                for instr in body {
+                    self.infer_instruction(scope, instr, arities, &mut changed)?;
+                }
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+
+    fn infer_instruction(
+        &mut self,
+        scope: Option<i64>,
+        instr: &Instruction,
+        arities: &HashMap<i64, i64>,
+        changed: &mut bool,
+    ) -> Result<(), TranspileError> {
+        let numeric = |this: &Self, a: &str, b: &str, what: &str| -> Result<Ty, TranspileError> {
+            let (ta, tb) = (this.operand_ty(scope, a), this.operand_ty(scope, b));
+            if !ta.is_numeric() || !tb.is_numeric() {
+                return Err(TranspileError::Codegen(format!(
+                    "{} of {} and {} is not supported",
+                    what,
+                    ta.name(),
+                    tb.name()
+                )));
+            }
+            Ok(ta.join(tb).unwrap_or(Ty::F64))
+        };
+
+        let (target, ty) = match instr {
+            Instruction::Assign { target, value } => (target, self.operand_ty(scope, value)),
+            Instruction::Add { result, a, b } => {
+                let (ta, tb) = (self.operand_ty(scope, a), self.operand_ty(scope, b));
+                if ta == Ty::Str && tb == Ty::Str {
+                    (result, Ty::Str)
+                } else {
+                    (result, numeric(self, a, b, "addition")?)
+                }
+            }
+            Instruction::Sub { result, a, b } => (result, numeric(self, a, b, "subtraction")?),
+            Instruction::Mul { result, a, b } => (result, numeric(self, a, b, "multiplication")?),
+            Instruction::Div { result, a, b } => {
+                numeric(self, a, b, "division")?;
+                (result, Ty::F64)
+            }
+            Instruction::Mod { result, a, b } => (result, numeric(self, a, b, "modulo")?),
+            Instruction::Lt { result, a, b }
+            | Instruction::Gt { result, a, b }
+            | Instruction::Le { result, a, b }
+            | Instruction::Ge { result, a, b } => {
+                numeric(self, a, b, "comparison")?;
+                (result, Ty::I64)
+            }
+            Instruction::Eq { result, a, b } => {
+                let (ta, tb) = (self.operand_ty(scope, a), self.operand_ty(scope, b));
+                if !(ta == Ty::Str && tb == Ty::Str) {
+                    numeric(self, a, b, "equality")?;
+                }
+                (result, Ty::I64)
+            }
+            Instruction::Not { result, .. }
+            | Instruction::And { result, .. }
+            | Instruction::Or { result, .. } => (result, Ty::I64),
+            Instruction::Call {
+                result,
+                func_id,
+                args,
+            } => {
+                let argc = *arities
+                    .get(func_id)
+                    .ok_or_else(|| TranspileError::Codegen(format!("undefined function {}", func_id)))?;
+                for (i, arg) in args.iter().enumerate().take(argc as usize) {
+                    let ty = self.operand_ty(scope, arg);
+                    self.widen(Slot::Arg(*func_id, i as i64), ty, changed)?;
+                }
+                (result, self.slot_ty(Slot::Ret(*func_id)))
+            }
+            Instruction::Return { value } => {
+                if let Some(id) = scope {
+                    let ty = self.operand_ty(scope, value);
+                    self.widen(Slot::Ret(id), ty, changed)?;
+                }
+                return Ok(());
+            }
+            Instruction::ArrayRead { indices, .. } | Instruction::ArrayWrite { indices, .. }
+                if !indices.is_empty() =>
+            {
+                return Err(TranspileError::Codegen("nested arrays are not supported".to_string()));
+            }
+            Instruction::ArrayCreate { var, .. } => (var, Ty::Arr),
+            Instruction::ArrayRead { result, .. } => (result, self.slot_ty(Slot::Elem)),
+            Instruction::ArrayWrite { value, .. } => {
+                let ty = self.operand_ty(scope, value);
+                if !ty.is_numeric() {
+                    return Err(TranspileError::Codegen(format!(
+                        "arrays of {} values are not supported",
+                        ty.name()
+                    )));
+                }
+                self.widen(Slot::Elem, ty, changed)?;
+                return Ok(());
+            }
+            Instruction::RustFFI { result, func, args } => {
+                let name = builtin_name(func)?;
+                let arg_tys: Vec<Ty> = args.iter().map(|a| self.operand_ty(scope, a)).collect();
+                (result, builtin_result(&name, &arg_tys)?)
+            }
+            Instruction::Input { .. } => {
+                return Err(TranspileError::Codegen("input (`,`) is not supported".to_string()))
+            }
+            Instruction::Iter { .. } | Instruction::Next { .. } => {
+                return Err(TranspileError::Codegen(
+                    "iterators (`I`, `N`) are not supported".to_string(),
+                ))
+            }
+            Instruction::Try { .. } | Instruction::EndTry | Instruction::Raise { .. } => {
+                return Err(TranspileError::Codegen(
+                    "try regions and raise (`T`, `U`, `E`) are not supported".to_string(),
+                ))
+            }
+            Instruction::Import { path } => {
+                return Err(TranspileError::Codegen(format!(
+                    "imports are not supported (`_ \"{}\"`)",
+                    path
+                )))
+            }
+            _ => return Ok(()),
+        };
+
+        if let Some(slot) = var_slot(scope, target) {
+            if !matches!(slot, Slot::Arg(..)) {
+                self.widen(slot, ty, changed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lay out every string literal in the data segment
+    fn intern_strings(&mut self, main: &[Instruction], functions: &[Function]) {
+        for instr in main.iter().chain(functions.iter().flat_map(|f| f.body.iter())) {
+            let candidates = match instr {
+                Instruction::RustFFI { args, .. } => args.iter().collect::<Vec<_>>(),
+                other => other.operands(),
+            };
+            for operand in candidates {
+                if let ParsedValue::String(s) = Lexer::parse_value(operand) {
+                    if !s.is_empty() && !self.strings.contains_key(&s) {
+                        let offset = self.data.len() as u32;
+                        self.data.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                        self.data.extend_from_slice(&[0; 4]);
+                        self.data.extend_from_slice(s.as_bytes());
+                        while self.data.len() % 8 != 0 {
+                            self.data.push(0);
+                        }
+                        self.strings.insert(s, offset);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hand-written runtime support functions
+    fn runtime_functions(&self) -> Vec<Func> {
+        use ValType::{F64, I32, I64};
+        let elem = self.slot_ty(Slot::Elem);
+        let (elem_ty, elem_load, elem_store, elem_zero) = if elem == Ty::F64 {
+            (F64, Op::F64Load(8), Op::F64Store(8), Op::F64Const(0.0))
+        } else {
+            (I64, Op::I64Load(8), Op::I64Store(8), Op::I64Const(0))
+        };
+
+        // (func $alloc (param $size i32) (result i32))
+        let mut alloc = Func::new("alloc", &[("size", I32)], Some(I32));
+        let ptr = alloc.local("ptr", I32);
+        let page_bytes = [Op::MemorySize, Op::I32Const(16), Op::I32Shl];
+        alloc.ops(&[
+            Op::GlobalGet(HEAP),
+            Op::LocalSet(ptr),
+            Op::GlobalGet(HEAP),
+            Op::LocalGet(0),
+            Op::I32Add,
+            Op::I32Const(7),
+            Op::I32Add,
+            Op::I32Const(-8),
+            Op::I32And,
+            Op::GlobalSet(HEAP),
+            Op::Block,
+            Op::GlobalGet(HEAP),
+        ]);
+        alloc.ops(&page_bytes);
+        alloc.ops(&[Op::I32LeU, Op::BrIf(0), Op::GlobalGet(HEAP)]);
+        alloc.ops(&page_bytes);
+        alloc.ops(&[
+            Op::I32Sub,
+            Op::I32Const(16),
+            Op::I32ShrU,
+            Op::I32Const(1),
+            Op::I32Add,
+            Op::MemoryGrow,
+            Op::Drop,
+            Op::End,
+            Op::LocalGet(ptr),
+        ]);
+
+        // (func $arr_new (param $len i64) (result i32))
+        let mut arr_new = Func::new("arr_new", &[("len", I64)], Some(I32));
+        let n = arr_new.local("n", I32);
+        let p = arr_new.local("p", I32);
+        arr_new.ops(&[
+            Op::LocalGet(0),
+            Op::I64Const(0),
+            Op::I64LtS,
+            Op::If(None),
+            Op::I64Const(0),
+            Op::LocalSet(0),
+            Op::End,
+            Op::LocalGet(0),
+            Op::I32WrapI64,
+            Op::LocalSet(n),
+            Op::LocalGet(n),
+            Op::I32Const(3),
+            Op::I32Shl,
+            Op::I32Const(8),
+            Op::I32Add,
+            Op::Call(RT_ALLOC),
+            Op::LocalSet(p),
+            Op::LocalGet(p),
+            Op::LocalGet(n),
+            Op::I32Store(0),
+            Op::LocalGet(p),
+        ]);
+
+        // (func $len (param $p i32) (result i64))
+        let mut len = Func::new("len", &[("p", I32)], Some(I64));
+        len.ops(&[Op::LocalGet(0), Op::I32Load(0), Op::I64ExtendI32U]);
+
+        // Bounds check shared by reads and writes: leaves "out of bounds" on the stack
+        let out_of_bounds = [
+            Op::LocalGet(1),
+            Op::I64Const(0),
+            Op::I64LtS,
+            Op::LocalGet(1),
+            Op::LocalGet(0),
+            Op::I32Load(0),
+            Op::I64ExtendI32U,
+            Op::I64GeS,
+            Op::I32Or,
+        ];
+        let element_addr = [
+            Op::LocalGet(0),
+            Op::LocalGet(1),
+            Op::I32WrapI64,
+            Op::I32Const(3),
+            Op::I32Shl,
+            Op::I32Add,
+        ];
+
+        // (func $arr_get (param $p i32) (param $i i64) (result elem))
+        let mut arr_get = Func::new("arr_get", &[("p", I32), ("i", I64)], Some(elem_ty));
+        arr_get.ops(&out_of_bounds);
+        arr_get.ops(&[Op::If(Some(elem_ty)), elem_zero, Op::Else]);
+        arr_get.ops(&element_addr);
+        arr_get.ops(&[elem_load, Op::End]);
+
+        // (func $arr_set (param $p i32) (param $i i64) (param $v elem))
+        let mut arr_set = Func::new("arr_set", &[("p", I32), ("i", I64), ("v", elem_ty)], None);
+        arr_set.ops(&out_of_bounds);
+        arr_set.ops(&[Op::BrIf(0)]);
+        arr_set.ops(&element_addr);
+        arr_set.ops(&[Op::LocalGet(2), elem_store]);
+
+        // (func $str_concat (param $a i32) (param $b i32) (result i32))
+        let mut concat = Func::new("str_concat", &[("a", I32), ("b", I32)], Some(I32));
+        let la = concat.local("la", I32);
+        let lb = concat.local("lb", I32);
+        let p = concat.local("p", I32);
+        let k = concat.local("k", I32);
+        concat.ops(&[
+            Op::LocalGet(0),
+            Op::I32Load(0),
+            Op::LocalSet(la),
+            Op::LocalGet(1),
+            Op::I32Load(0),
+            Op::LocalSet(lb),
+            Op::LocalGet(la),
+            Op::LocalGet(lb),
+            Op::I32Add,
+            Op::I32Const(8),
+            Op::I32Add,
+            Op::Call(RT_ALLOC),
+            Op::LocalSet(p),
+            Op::LocalGet(p),
+            Op::LocalGet(la),
+            Op::LocalGet(lb),
+            Op::I32Add,
+            Op::I32Store(0),
+        ]);
+        for (src, count, dst_base) in [(0u32, la, None), (1u32, lb, Some(la))] {
+            concat.ops(&[
+                Op::I32Const(0),
+                Op::LocalSet(k),
+                Op::Block,
+                Op::Loop,
+                Op::LocalGet(k),
+                Op::LocalGet(count),
+                Op::I32GeU,
+                Op::BrIf(1),
+                Op::LocalGet(p),
+                Op::LocalGet(k),
+                Op::I32Add,
+            ]);
+            if let Some(base) = dst_base {
+                concat.ops(&[Op::LocalGet(base), Op::I32Add]);
+            }
+            concat.ops(&[
+                Op::LocalGet(src),
+                Op::LocalGet(k),
+                Op::I32Add,
+                Op::I32Load8U(8),
+                Op::I32Store8(8),
+                Op::LocalGet(k),
+                Op::I32Const(1),
+                Op::I32Add,
+                Op::LocalSet(k),
+                Op::Br(0),
+                Op::End,
+                Op::End,
+            ]);
+        }
+        concat.ops(&[Op::LocalGet(p)]);
+
+        // (func $str_eq (param $a i32) (param $b i32) (result i64))
+        let mut str_eq = Func::new("str_eq", &[("a", I32), ("b", I32)], Some(I64));
+        let k = str_eq.local("k", I32);
+        str_eq.ops(&[
+            Op::LocalGet(0),
+            Op::I32Load(0),
+            Op::LocalGet(1),
+            Op::I32Load(0),
+            Op::I32Ne,
+            Op::If(None),
+            Op::I64Const(0),
+            Op::Return,
+            Op::End,
+            Op::Block,
+            Op::Loop,
+            Op::LocalGet(k),
+            Op::LocalGet(0),
+            Op::I32Load(0),
+            Op::I32GeU,
+            Op::BrIf(1),
+            Op::LocalGet(0),
+            Op::LocalGet(k),
+            Op::I32Add,
+            Op::I32Load8U(8),
+            Op::LocalGet(1),
+            Op::LocalGet(k),
+            Op::I32Add,
+            Op::I32Load8U(8),
+            Op::I32Ne,
+            Op::If(None),
+            Op::I64Const(0),
+            Op::Return,
+            Op::End,
+            Op::LocalGet(k),
+            Op::I32Const(1),
+            Op::I32Add,
+            Op::LocalSet(k),
+            Op::Br(0),
+            Op::End,
+            Op::End,
+            Op::I64Const(1),
+        ]);
+
+        // (func $print_str_obj (param $p i32)) and (func $print_arr_obj (param $p i32))
+        let mut print_str = Func::new("print_str_obj", &[("p", I32)], None);
+        print_str.ops(&[
+            Op::LocalGet(0),
+            Op::I32Const(8),
+            Op::I32Add,
+            Op::LocalGet(0),
+            Op::I32Load(0),
+            Op::Call(PRINT_STR),
+        ]);
+        let mut print_arr = Func::new("print_arr_obj", &[("p", I32)], None);
+        print_arr.ops(&[
+            Op::LocalGet(0),
+            Op::I32Const(8),
+            Op::I32Add,
+            Op::LocalGet(0),
+            Op::I32Load(0),
+            Op::I32Const(if elem == Ty::F64 { 1 } else { 0 }),
+            Op::Call(PRINT_ARRAY),
+        ]);
+
+        vec![
+            alloc, arr_new, len, arr_get, arr_set, concat, str_eq, print_str, print_arr,
+        ]
+    }
+
+    /// Lower a function body (or the main program) into `func`
+    fn lower_block(
+        &self,
+        scope: Option<i64>,
+        func: Func,
+        body: &[Instruction],
+        global_index: &HashMap<i64, u32>,
+        func_index: &HashMap<i64, u32>,
+    ) -> Result<Func, TranspileError> {
+        let mut ctx = FuncCtx {
+            scope,
+            func,
+            slots: HashMap::new(),
+            global_index,
+            func_index,
+            state: 0,
+            scratch_f: [0, 0],
+            scratch_i: 0,
+        };
+
+        for (i, _) in ctx.func.params.iter().enumerate() {
+            if let Some(id) = scope {
+                ctx.slots.insert(Slot::Arg(id, i as i64), i as u32);
+            }
+        }
+        let mut local_ids = BTreeSet::new();
+        for instr in body {
+            for operand in instr.operands() {
+                if let Some(('v', idx)) = split_var(operand) {
+                    local_ids.insert(idx);
+                }
+            }
+        }
+        for idx in local_ids {
+            let ty = self.slot_ty(Slot::Local(scope, idx)).val_type();
+            let local = ctx.func.local(&format!("v{}", idx), ty);
+            ctx.slots.insert(Slot::Local(scope, idx), local);
+        }
+        ctx.state = ctx.func.local("state", ValType::I32);
+        ctx.scratch_f = [
+            ctx.func.local("tf0", ValType::F64),
+            ctx.func.local("tf1", ValType::F64),
+        ];
+        ctx.scratch_i = ctx.func.local("ti0", ValType::I64);
+
+        // Every basic block becomes one segment of a dispatch loop
+        let cfg = Cfg::build(body);
+        let last = (cfg.blocks().len() - 1) as u32;
+        if last == 0 {
+            for instr in &cfg.block(0).instructions {
+                self.lower_instruction(&mut ctx, instr)?;
+            }
+            self.lower_terminator(&mut ctx, &cfg, 0, 0)?;
+        } else {
+            // loop { block*{ br_table } seg0 } seg1 } ... segN }
+            ctx.func.ops(&[Op::Loop]);
+            for _ in 0..=last {
+                ctx.func.ops(&[Op::Block]);
+            }
+            let targets: Vec<u32> = (0..=last).collect();
+            ctx.func.ops(&[Op::LocalGet(ctx.state), Op::BrTable(targets, last)]);
+            for (seg, block) in cfg.blocks().iter().enumerate() {
+                ctx.func.ops(&[Op::End]);
+                for instr in &block.instructions {
+                    self.lower_instruction(&mut ctx, instr)?;
+                }
+                self.lower_terminator(&mut ctx, &cfg, seg, last - seg as u32)?;
+            }
+            ctx.func.ops(&[Op::End]);
+        }
+
+        // Falling off the end of a function returns integer zero
+        if let Some(result) = ctx.func.result {
+            ctx.func.ops(&[zero(result)]);
+        }
+        Ok(ctx.func)
+    }
+
+    /// Push an operand converted to `want`
+    fn load(&self, ctx: &mut FuncCtx, operand: &str, want: Ty) {
+        let have = self.operand_ty(ctx.scope, operand);
+        match Lexer::parse_value(operand) {
+            ParsedValue::Variable(_) => match var_slot(ctx.scope, operand) {
+                Some(Slot::Global(idx)) => ctx.func.ops(&[Op::GlobalGet(ctx.global_index[&idx])]),
+                Some(slot) => match ctx.slots.get(&slot) {
+                    Some(&local) => ctx.func.ops(&[Op::LocalGet(local)]),
+                    None => ctx.func.ops(&[zero(have.val_type())]),
+                },
+                None => ctx.func.ops(&[zero(have.val_type())]),
+            },
+            ParsedValue::Integer(n) => ctx.func.ops(&[Op::I64Const(n)]),
+            ParsedValue::Float(f) => ctx.func.ops(&[Op::F64Const(f)]),
+            ParsedValue::String(s) => {
+                let offset = self.strings.get(&s).copied().unwrap_or(0);
+                ctx.func.ops(&[Op::I32Const(offset as i32)]);
+            }
+        }
+        if have == Ty::I64 && want == Ty::F64 {
+            ctx.func.ops(&[Op::F64ConvertI64S]);
+        }
+    }
+
+    /// Push the truthiness of an operand as an i32
+    fn load_truthy(&self, ctx: &mut FuncCtx, operand: &str) {
+        let ty = self.operand_ty(ctx.scope, operand);
+        self.load(ctx, operand, ty);
+        match ty {
+            Ty::I64 => ctx.func.ops(&[Op::I64Const(0), Op::I64Ne]),
+            Ty::F64 => ctx.func.ops(&[Op::F64Const(0.0), Op::F64Ne]),
+            Ty::Str | Ty::Arr => ctx.func.ops(&[Op::I32Load(0), Op::I32Const(0), Op::I32Ne]),
+        }
+    }
+
+    /// Pop the stack into a variable (writes to arguments are dropped)
+    fn store(&self, ctx: &mut FuncCtx, target: &str, have: Ty) {
+        match var_slot(ctx.scope, target) {
+            Some(Slot::Arg(..)) | None => ctx.func.ops(&[Op::Drop]),
+            Some(slot) => {
+                let want = self.slot_ty(slot);
+                if have == Ty::I64 && want == Ty::F64 {
+                    ctx.func.ops(&[Op::F64ConvertI64S]);
+                }
+                match slot {
+                    Slot::Global(idx) => ctx.func.ops(&[Op::GlobalSet(ctx.global_index[&idx])]),
+                    _ => {
+                        let local = ctx.slots[&slot];
+                        ctx.func.ops(&[Op::LocalSet(local)]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emit `result = a <op> b` for numeric operands
+    fn arith(&self, ctx: &mut FuncCtx, result: &str, a: &str, b: &str, int_op: Op, float_op: Op) {
+        let ty = self
+            .operand_ty(ctx.scope, a)
+            .join(self.operand_ty(ctx.scope, b))
+            .unwrap_or(Ty::F64);
+        self.load(ctx, a, ty);
+        self.load(ctx, b, ty);
+        ctx.func.ops(&[if ty == Ty::I64 { int_op } else { float_op }]);
+        self.store(ctx, result, ty);
+    }
+
+    /// Emit `result = (a <op> b) ? 1 : 0`
+    fn compare(&self, ctx: &mut FuncCtx, result: &str, a: &str, b: &str, int_op: Op, float_op: Op) {
+        let ty = self
+            .operand_ty(ctx.scope, a)
+            .join(self.operand_ty(ctx.scope, b))
+            .unwrap_or(Ty::F64);
+        self.load(ctx, a, ty);
+        self.load(ctx, b, ty);
+        ctx.func.ops(&[if ty == Ty::I64 { int_op } else { float_op }, Op::I64ExtendI32U]);
+        self.store(ctx, result, Ty::I64);
+    }
+
+    /// Jump to a segment; `depth` is the branch depth of the dispatch loop
+    fn jump(ctx: &mut FuncCtx, segment: BlockId, depth: u32) {
+        ctx.func
+            .ops(&[Op::I32Const(segment as i32), Op::LocalSet(ctx.state), Op::Br(depth)]);
+    }
+
+    /// Leave a segment; falling through reaches the next segment (or the end)
+    fn lower_terminator(
+        &self,
+        ctx: &mut FuncCtx,
+        cfg: &Cfg,
+        id: BlockId,
+        depth: u32,
+    ) -> Result<(), TranspileError> {
+        match &cfg.block(id).terminator {
+            Terminator::Goto(target) => {
+                if *target != id + 1 {
+                    Self::jump(ctx, *target, depth);
+                }
+            }
+            Terminator::Branch { cond, target, .. } => {
+                self.load_truthy(ctx, cond);
+                ctx.func.ops(&[Op::If(None)]);
+                Self::jump(ctx, *target, depth + 1);
+                ctx.func.ops(&[Op::End]);
+            }
+            terminator @ Terminator::Switch { value, .. } => {
+                // One comparison per entry; strings and arrays never match
+                let ty = self.operand_ty(ctx.scope, value);
+                if matches!(ty, Ty::I64 | Ty::F64) {
+                    for (values, target) in terminator.switch_cases() {
+                        for (i, &k) in values.iter().enumerate() {
+                            self.load(ctx, value, ty);
+                            if ty == Ty::I64 {
+                                ctx.func.ops(&[Op::I64Const(k as i64), Op::I64Eq]);
+                            } else {
+                                ctx.func.ops(&[Op::F64Const(k as f64), Op::F64Eq]);
+                            }
+                            if i > 0 {
+                                ctx.func.ops(&[Op::I32Or]);
+                            }
+                        }
+                        ctx.func.ops(&[Op::If(None)]);
+                        Self::jump(ctx, target, depth + 1);
+                        ctx.func.ops(&[Op::End]);
+                    }
+                }
+            }
+            Terminator::Return(value) => match ctx.scope {
+                Some(id) => {
+                    self.load(ctx, value, self.slot_ty(Slot::Ret(id)));
+                    ctx.func.ops(&[Op::Return]);
+                }
+                None => ctx.func.ops(&[Op::Return]),
+            },
+        }
+        Ok(())
+    }
+
+    fn lower_instruction(&self, ctx: &mut FuncCtx, instr: &Instruction) -> Result<(), TranspileError> {
+        match instr {
+            Instruction::Assign { target, value } => {
+                let ty = self.operand_ty(ctx.scope, value);
+                self.load(ctx, value, ty);
+                self.store(ctx, target, ty);
+            }
+            Instruction::Add { result, a, b } => {
+                if self.operand_ty(ctx.scope, a) == Ty::Str {
+                    self.load(ctx, a, Ty::Str);
+                    self.load(ctx, b, Ty::Str);
+                    ctx.func.ops(&[Op::Call(RT_STR_CONCAT)]);
+                    self.store(ctx, result, Ty::Str);
+                } else {
+                    self.arith(ctx, result, a, b, Op::I64Add, Op::F64Add);
+                }
+            }
+            Instruction::Sub { result, a, b } => self.arith(ctx, result, a, b, Op::I64Sub, Op::F64Sub),
+            Instruction::Mul { result, a, b } => self.arith(ctx, result, a, b, Op::I64Mul, Op::F64Mul),
+            Instruction::Div { result, a, b } => {
+                // Division by zero yields NaN, as in the interpreter
+                let divisor = ctx.scratch_f[0];
+                self.load(ctx, a, Ty::F64);
+                self.load(ctx, b, Ty::F64);
+                ctx.func.ops(&[
+                    Op::LocalTee(divisor),
+                    Op::F64Div,
+                    Op::F64Const(f64::NAN),
+                    Op::LocalGet(divisor),
+                    Op::F64Const(0.0),
+                    Op::F64Ne,
+                    Op::Select,
+                ]);
+                self.store(ctx, result, Ty::F64);
+            }
+            Instruction::Mod { result, a, b } => {
+                let ty = self
+                    .operand_ty(ctx.scope, a)
+                    .join(self.operand_ty(ctx.scope, b))
+                    .unwrap_or(Ty::F64);
+                if ty == Ty::I64 {
+                    // Integer modulo by zero yields 0 instead of trapping
+                    let divisor = ctx.scratch_i;
+                    self.load(ctx, b, Ty::I64);
+                    ctx.func.ops(&[
+                        Op::LocalTee(divisor),
+                        Op::I64Eqz,
+                        Op::If(Some(ValType::I64)),
+                        Op::I64Const(0),
+                        Op::Else,
+                    ]);
+                    self.load(ctx, a, Ty::I64);
+                    ctx.func.ops(&[Op::LocalGet(divisor), Op::I64RemS, Op::End]);
+                } else {
+                    // fmod(a, b) = a - b * trunc(a / b)
+                    let [ta, tb] = ctx.scratch_f;
+                    self.load(ctx, a, Ty::F64);
+                    ctx.func.ops(&[Op::LocalSet(ta)]);
+                    self.load(ctx, b, Ty::F64);
+                    ctx.func.ops(&[
+                        Op::LocalSet(tb),
+                        Op::LocalGet(ta),
+                        Op::LocalGet(tb),
+                        Op::LocalGet(ta),
+                        Op::LocalGet(tb),
+                        Op::F64Div,
+                        Op::F64Trunc,
+                        Op::F64Mul,
+                        Op::F64Sub,
+                    ]);
+                }
+                self.store(ctx, result, ty);
+            }
+            Instruction::Lt { result, a, b } => self.compare(ctx, result, a, b, Op::I64LtS, Op::F64Lt),
+            Instruction::Gt { result, a, b } => self.compare(ctx, result, a, b, Op::I64GtS, Op::F64Gt),
+            Instruction::Le { result, a, b } => self.compare(ctx, result, a, b, Op::I64LeS, Op::F64Le),
+            Instruction::Ge { result, a, b } => self.compare(ctx, result, a, b, Op::I64GeS, Op::F64Ge),
+            Instruction::Eq { result, a, b } => {
+                if self.operand_ty(ctx.scope, a) == Ty::Str {
+                    self.load(ctx, a, Ty::Str);
+                    self.load(ctx, b, Ty::Str);
+                    ctx.func.ops(&[Op::Call(RT_STR_EQ)]);
+                    self.store(ctx, result, Ty::I64);
+                } else {
+                    self.compare(ctx, result, a, b, Op::I64Eq, Op::F64Eq);
+                }
+            }
+            Instruction::Not { result, a } => {
+                self.load_truthy(ctx, a);
+                ctx.func.ops(&[Op::I32Eqz, Op::I64ExtendI32U]);
+                self.store(ctx, result, Ty::I64);
+            }
+            Instruction::And { result, a, b } | Instruction::Or { result, a, b } => {
+                self.load_truthy(ctx, a);
+                self.load_truthy(ctx, b);
+                let op = if matches!(instr, Instruction::And { .. }) {
+                    Op::I32And
+                } else {
+                    Op::I32Or
+                };
+                ctx.func.ops(&[op, Op::I64ExtendI32U]);
+                self.store(ctx, result, Ty::I64);
+            }
+            Instruction::Call {
+                result,
+                func_id,
+                args,
+            } => {
+                let index = *ctx
+                    .func_index
+                    .get(func_id)
+                    .ok_or_else(|| TranspileError::Codegen(format!("undefined function {}", func_id)))?;
+                let argc = self.arity(*func_id);
+                for i in 0..argc {
+                    let want = self.slot_ty(Slot::Arg(*func_id, i as i64));
+                    match args.get(i) {
+                        Some(arg) => self.load(ctx, arg, want),
+                        None => ctx.func.ops(&[zero(want.val_type())]),
+                    }
+                }
+                ctx.func.ops(&[Op::Call(index)]);
+                self.store(ctx, result, self.slot_ty(Slot::Ret(*func_id)));
+            }
+            Instruction::ArrayCreate { var, size } => {
+                self.load(ctx, size, Ty::I64);
+                if self.operand_ty(ctx.scope, size) == Ty::F64 {
+                    ctx.func.ops(&[Op::I64TruncSatF64S]);
+                }
+                ctx.func.ops(&[Op::Call(RT_ARR_NEW)]);
+                self.store(ctx, var, Ty::Arr);
+            }
+            Instruction::ArrayRead { result, arr, idx, .. } => {
+                self.load_array(ctx, arr);
+                self.load_index(ctx, idx);
+                ctx.func.ops(&[Op::Call(RT_ARR_GET)]);
+                self.store(ctx, result, self.slot_ty(Slot::Elem));
+            }
+            Instruction::ArrayWrite { arr, idx, value, .. } => {
+                // Writes through argument arrays are ignored by the interpreter
+                if !matches!(var_slot(ctx.scope, arr), Some(Slot::Arg(..)) | None) {
+                    self.load_array(ctx, arr);
+                    self.load_index(ctx, idx);
+                    self.load(ctx, value, self.slot_ty(Slot::Elem));
+                    ctx.func.ops(&[Op::Call(RT_ARR_SET)]);
+                }
+            }
+            Instruction::Output { value } => {
+                let ty = self.operand_ty(ctx.scope, value);
+                self.load(ctx, value, ty);
+                let printer = match ty {
+                    Ty::I64 => PRINT_I64,
+                    Ty::F64 => PRINT_F64,
+                    Ty::Str => RT_PRINT_STR,
+                    Ty::Arr => RT_PRINT_ARR,
+                };
+                ctx.func.ops(&[Op::Call(printer)]);
+            }
+            Instruction::RustFFI { result, func, args } => {
+                let name = builtin_name(func)?;
+                let ty = self.lower_builtin(ctx, &name, args)?;
+                self.store(ctx, result, ty);
+            }
+            Instruction::Input { .. }
+            | Instruction::Import { .. }
+            | Instruction::Iter { .. }
+            | Instruction::Next { .. }
+            | Instruction::Try { .. }
+            | Instruction::EndTry
+            | Instruction::Raise { .. }
+            | Instruction::Invalid { .. } => {
+                return Err(TranspileError::Codegen(format!(
+                    "unsupported instruction: {:?}",
+                    instr
+                )))
+            }
+            // Control flow is lowered through the IR
+            Instruction::Label { .. }
+            | Instruction::CondJump { .. }
+            | Instruction::Jump { .. }
+            | Instruction::Switch { .. }
+            | Instruction::Return { .. }
+            | Instruction::FuncDef { .. }
+            | Instruction::FuncEnd
+            | Instruction::Comment
+            | Instruction::Const { .. }
+            | Instruction::Empty => {}
+        }
+        Ok(())
+    }
+
+    fn arity(&self, func_id: i64) -> usize {
+        self.arities.get(&func_id).copied().unwrap_or(0).max(0) as usize
+    }
+
+    /// Push an array pointer (non-array operands read as the empty array)
+    fn load_array(&self, ctx: &mut FuncCtx, operand: &str) {
+        if self.operand_ty(ctx.scope, operand) == Ty::Arr {
+            self.load(ctx, operand, Ty::Arr);
+        } else {
+            ctx.func.ops(&[Op::I32Const(0)]);
+        }
+    }
+
+    /// Push an index operand as i64
+    fn load_index(&self, ctx: &mut FuncCtx, operand: &str) {
+        let ty = self.operand_ty(ctx.scope, operand);
+        self.load(ctx, operand, ty);
+        match ty {
+            Ty::F64 => ctx.func.ops(&[Op::I64TruncSatF64S]),
+            Ty::Str | Ty::Arr => ctx.func.ops(&[Op::Drop, Op::I64Const(0)]),
+            Ty::I64 => {}
+        }
+    }
+
+    /// Lower a builtin call, returning the static type left on the stack
+    fn lower_builtin(&self, ctx: &mut FuncCtx, name: &str, args: &[String]) -> Result<Ty, TranspileError> {
+        let arg_tys: Vec<Ty> = args.iter().map(|a| self.operand_ty(ctx.scope, a)).collect();
+        let ty = builtin_result(name, &arg_tys)?;
+        let float_arg = |this: &Self, ctx: &mut FuncCtx, i: usize| match args.get(i) {
+            Some(arg) => this.load(ctx, arg, Ty::F64),
+            None => ctx.func.ops(&[Op::F64Const(0.0)]),
+        };
+        match name {
+            "sqrt" => {
+                float_arg(self, ctx, 0);
+                ctx.func.ops(&[Op::F64Sqrt]);
+            }
+            "floor" | "ceil" => {
+                float_arg(self, ctx, 0);
+                let op = if name == "floor" { Op::F64Floor } else { Op::F64Ceil };
+                ctx.func.ops(&[op, Op::I64TruncSatF64S]);
+            }
+            "round" => {
+                // Round half away from zero: copysign(floor(|x| + 0.5), x)
+                let x = ctx.scratch_f[0];
+                float_arg(self, ctx, 0);
+                if args.len() >= 2 {
+                    let factor = ctx.scratch_f[1];
+                    ctx.func.ops(&[Op::F64Const(10.0)]);
+                    float_arg(self, ctx, 1);
+                    ctx.func.ops(&[
+                        Op::F64Trunc,
+                        Op::Call(4 + 6),
+                        Op::LocalTee(factor),
+                        Op::F64Mul,
+                    ]);
+                    ctx.func.ops(&round_half_away(x));
+                    ctx.func.ops(&[Op::LocalGet(factor), Op::F64Div]);
+                } else {
+                    ctx.func.ops(&round_half_away(x));
+                    ctx.func.ops(&[Op::I64TruncSatF64S]);
+                }
+            }
+            "abs" => {
+                if ty == Ty::I64 {
+                    let x = ctx.scratch_i;
+                    self.load(ctx, &args[0], Ty::I64);
+                    ctx.func.ops(&[
+                        Op::LocalTee(x),
+                        Op::I64Const(0),
+                        Op::LocalGet(x),
+                        Op::I64Sub,
+                        Op::LocalGet(x),
+                        Op::I64Const(0),
+                        Op::I64GeS,
+                        Op::Select,
+                    ]);
+                } else {
+                    float_arg(self, ctx, 0);
+                    ctx.func.ops(&[Op::F64Abs]);
+                }
+            }
+            "max" | "min" => {
+                if args.is_empty() {
+                    ctx.func.ops(&[Op::I64Const(0)]);
+                } else if ty == Ty::I64 {
+                    // best = select(best, x, best <op> x)
+                    let best = ctx.scratch_i;
+                    let cmp = if name == "max" { Op::I64GtS } else { Op::I64LtS };
+                    self.load(ctx, &args[0], Ty::I64);
+                    for arg in &args[1..] {
+                        ctx.func.ops(&[Op::LocalSet(best), Op::LocalGet(best)]);
+                        self.load(ctx, arg, Ty::I64);
+                        ctx.func.ops(&[Op::LocalGet(best)]);
+                        self.load(ctx, arg, Ty::I64);
+                        ctx.func.ops(&[cmp.clone(), Op::Select]);
+                    }
+                } else {
+                    let op = if name == "max" { Op::F64Max } else { Op::F64Min };
+                    self.load(ctx, &args[0], Ty::F64);
+                    for arg in &args[1..] {
+                        self.load(ctx, arg, Ty::F64);
+                        ctx.func.ops(std::slice::from_ref(&op));
+                    }
+                }
+            }
+            "int" => match arg_tys.first() {
+                Some(Ty::F64) => {
+                    float_arg(self, ctx, 0);
+                    ctx.func.ops(&[Op::I64TruncSatF64S]);
+                }
+                Some(Ty::I64) => self.load(ctx, &args[0], Ty::I64),
+                _ => ctx.func.ops(&[Op::I64Const(0)]),
+            },
+            "float" => float_arg(self, ctx, 0),
+            "len" => match arg_tys.first() {
+                Some(Ty::Str) | Some(Ty::Arr) => {
+                    let ty = arg_tys[0];
+                    self.load(ctx, &args[0], ty);
+                    ctx.func.ops(&[Op::Call(RT_LEN)]);
+                }
+                _ => ctx.func.ops(&[Op::I64Const(0)]),
+            },
+            "randint" => {
+                for i in 0..2 {
+                    match args.get(i) {
+                        Some(arg) if self.operand_ty(ctx.scope, arg) == Ty::I64 => self.load(ctx, arg, Ty::I64),
+                        Some(arg) => {
+                            self.load(ctx, arg, Ty::F64);
+                            ctx.func.ops(&[Op::I64TruncSatF64S]);
+                        }
+                        None => ctx.func.ops(&[Op::I64Const(if i == 0 { 0 } else { 100 })]),
+                    }
+                }
+                ctx.func.ops(&[Op::Call(RANDINT)]);
+            }
+            _ => {
+                let import = MATH_IMPORTS
+                    .iter()
+                    .position(|m| *m == name)
+                    .map(|i| 4 + i as u32)
+                    .ok_or_else(|| TranspileError::Codegen(format!("unsupported builtin '{}'", name)))?;
+                float_arg(self, ctx, 0);
+                if name == "pow" {
+                    float_arg(self, ctx, 1);
+                }
+                ctx.func.ops(&[Op::Call(import)]);
+            }
+        }
+        Ok(ty)
+    }
+}
+
+/// `copysign(floor(|x| + 0.5), x)` for the value on top of the stack
+fn round_half_away(scratch: u32) -> [Op; 7] {
+    [
+        Op::LocalTee(scratch),
+        Op::F64Abs,
+        Op::F64Const(0.5),
+        Op::F64Add,
+        Op::F64Floor,
+        Op::LocalGet(scratch),
+        Op::F64Copysign,
+    ]
+}
+
+fn zero(ty: ValType) -> Op {
+    match ty {
+        ValType::I32 => Op::I32Const(0),
+        ValType::I64 => Op::I64Const(0),
+        ValType::F64 => Op::F64Const(0.0),
+    }
+}
+
+/// Split `v3` into `('v', 3)`
+fn split_var(operand: &str) -> Option<(char, i64)> {
+    match Lexer::parse_value(operand) {
+        ParsedValue::Variable(var) => {
+            let prefix = var.chars().next()?;
+            Some((prefix, var[1..].parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Storage slot of a variable operand in a scope
+fn var_slot(scope: Option<i64>, operand: &str) -> Option<Slot> {
+    match split_var(operand)? {
+        ('v', idx) => Some(Slot::Local(scope, idx)),
+        ('g', idx) => Some(Slot::Global(idx)),
+        ('a', idx) => scope.map(|id| Slot::Arg(id, idx)),
+        _ => None,
+    }
+}
+
+fn describe_slot(slot: Slot) -> String {
+    match slot {
+        Slot::Global(i) => format!("g{}", i),
+        Slot::Local(None, i) => format!("v{} in main", i),
+        Slot::Local(Some(f), i) => format!("v{} in function {}", i, f),
+        Slot::Arg(f, i) => format!("a{} of function {}", i, f),
+        Slot::Ret(f) => format!("the return value of function {}", f),
+        Slot::Elem => "array elements".to_string(),
+    }
+}
+
+/// Resolve the builtin name of an `R`/`P` call (`"math.sqrt"` -> `sqrt`)
+fn builtin_name(func: &str) -> Result<String, TranspileError> {
+    match Lexer::parse_value(func) {
+        ParsedValue::String(s) => Ok(s.rsplit('.').next().unwrap_or(&s).to_string()),
+        _ => Err(TranspileError::Codegen(format!(
+            "builtin name must be a literal, got '{}'",
+            func
+        ))),
+    }
+}
+
+/// Static result type of a builtin
+fn builtin_result(name: &str, args: &[Ty]) -> Result<Ty, TranspileError> {
+    let numeric_args = args.iter().all(|t| t.is_numeric());
+    let ty = match name {
+        "sqrt" | "sin" | "cos" | "tan" | "log" | "log10" | "exp" | "pow" | "float" if numeric_args => {
+            Ty::F64
+        }
+        "floor" | "ceil" | "int" | "randint" if numeric_args => Ty::I64,
+        "round" if numeric_args => {
+            if args.len() >= 2 {
+                Ty::F64
+            } else {
+                Ty::I64
+            }
+        }
+        "abs" | "max" | "min" if numeric_args => {
+            if args.iter().all(|t| *t == Ty::I64) {
+                Ty::I64
+            } else {
+                Ty::F64
+            }
+        }
+        "len" => Ty::I64,
+        _ => {
+            return Err(TranspileError::Codegen(format!(
+                "unsupported builtin '{}' for argument types ({})",
+                name,
+                args.iter().map(|t| t.name()).collect::<Vec<_>>().join(", ")
+            )))
+        }
+    };
+    Ok(ty)
+}
+
+impl Transpiler for Sui2Wasm {
+    fn transpile(&self, code: &str) -> Result<String, TranspileError> {
+        let mut compiler = Sui2Wasm::new();
+        compiler.transpile_to_wat(code)
+    }
+
+    fn extension(&self) -> &str {
+        "wat"
+    }
+
+    fn language(&self) -> &str {
+        "WebAssembly"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_compile() {
+        let code = r#"
+= v0 10
++ v1 v0 5
+. v1
+"#;
+        let mut compiler = Sui2Wasm::new();
+        let wat = compiler.transpile_to_wat(code).unwrap();
+        assert!(wat.contains("(func $main (export \"main\")"));
+        assert!(wat.contains("(local $v1 i64)"));
+        assert!(wat.contains("i64.add"));
+        assert!(wat.contains("call $print_i64"));
+    }
+
+    #[test]
+    fn test_function_types() {
+        let code = r#"
+# 0 1 {
+/ v0 a0 2
+^ v0
+}
+$ g0 0 5
+. g0
+"#;
+        let mut compiler = Sui2Wasm::new();
+        let wat = compiler.transpile_to_wat(code).unwrap();
+        assert!(wat.contains("(func $f0 (param $a0 i64) (result f64)"));
+        assert!(wat.contains("(global $g0 (mut f64)"));
+    }
+
+    #[test]
+    fn test_binary_header() {
+        let mut compiler = Sui2Wasm::new();
+        let wasm = compiler.compile_to_wasm(". \"hi\"").unwrap();
+        assert_eq!(&wasm[..8], &[0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_mixed_types_rejected() {
+        let mut compiler = Sui2Wasm::new();
+        let result = compiler.transpile_to_wat("= v0 \"a\"\n= v0 1");
+        assert!(matches!(result, Err(TranspileError::Codegen(_))));
+    }
+
+    #[test]
+    fn test_nested_arrays_rejected() {
+        let mut compiler = Sui2Wasm::new();
+        let result = compiler.transpile_to_wat("[ v0 2\n] v1 v0 0 1");
+        assert!(matches!(result, Err(TranspileError::Codegen(_))));
+    }
+}
@@ -4,16 +4,26 @@
 use wasm_bindgen::prelude::*;
 
 #[cfg(feature = "wasm")]
-use crate::interpreter::Interpreter;
+use crate::interpreter::{Interpreter, Program, Value};
 
 #[cfg(feature = "wasm")]
 use crate::transpiler::{Sui2Py, Sui2Js};
 
+#[cfg(feature = "wasm")]
+use crate::debugger::headless::DebugSession;
+
+#[cfg(feature = "wasm")]
+use crate::ide::{lint_diagnostics, parse_diagnostics};
+
 /// WebAssembly bindings for the Sui interpreter
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
 pub struct WasmSui {
     interpreter: Interpreter,
+    /// Run started by `run_with_fuel` that ran out of fuel
+    suspended: Option<Program>,
+    /// Output lines of the suspended run already returned
+    reported: usize,
 }
 
 #[cfg(feature = "wasm")]
@@ -22,8 +32,13 @@ impl WasmSui {
     /// Create a new Sui interpreter
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
+        let mut interpreter = Interpreter::new();
+        // There is no stdin to read in a browser
+        interpreter.set_input_queue(Vec::new());
         Self {
-            interpreter: Interpreter::new(),
+            interpreter,
+            suspended: None,
+            reported: 0,
         }
     }
 
@@ -60,10 +75,104 @@ impl WasmSui {
         Ok(json)
     }
 
+    /// Run Sui code keeping the globals and functions of earlier runs and
+    /// those set with `set_global`; output as a JSON array
+    #[wasm_bindgen]
+    pub fn run_incremental(&mut self, code: &str) -> Result<String, JsValue> {
+        let output = self
+            .interpreter
+            .run_incremental(code, &[])
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        serde_json::to_string(&output).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
     /// Reset the interpreter state
     #[wasm_bindgen]
     pub fn reset(&mut self) {
         self.interpreter.reset();
+        self.suspended = None;
+    }
+
+    /// Run Sui code for at most `fuel` instructions
+    ///
+    /// Returns JSON `{"status": "done" | "suspended", "output": [...]}` with
+    /// the lines printed until it stopped. A suspended run continues with `resume`,
+    /// so a page can run long programs in chunks without freezing; `yield`
+    /// also suspends.
+    #[wasm_bindgen]
+    pub fn run_with_fuel(&mut self, code: &str, fuel: u32) -> Result<String, JsValue> {
+        self.suspended = None;
+        self.reported = 0;
+        let program = self
+            .interpreter
+            .start(code, &[])
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.advance(program, fuel)
+    }
+
+    /// Continue a suspended run for at most `fuel` more instructions,
+    /// returning the lines printed since the last call like `run_with_fuel`
+    #[wasm_bindgen]
+    pub fn resume(&mut self, fuel: u32) -> Result<String, JsValue> {
+        let program = self
+            .suspended
+            .take()
+            .ok_or_else(|| JsValue::from_str("No suspended run to resume"))?;
+        self.advance(program, fuel)
+    }
+
+    /// Abandon a suspended run
+    #[wasm_bindgen]
+    pub fn cancel(&mut self) {
+        self.suspended = None;
+    }
+
+    /// Whether a run is waiting for `resume`
+    #[wasm_bindgen]
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    /// Lines for `,` to read, as a JSON array, since browsers have no stdin
+    ///
+    /// Numbers are read as if typed; once the lines run out `,` reads an
+    /// empty line. The queue replaces any earlier one and is kept across
+    /// runs.
+    #[wasm_bindgen]
+    pub fn set_input_lines(&mut self, json_array: &str) -> Result<(), JsValue> {
+        let lines: Vec<Value> = serde_json::from_str(json_array)
+            .map_err(|e| JsValue::from_str(&format!("Invalid input JSON: {}", e)))?;
+        self.interpreter.set_input_queue(lines.iter().map(Value::to_string).collect());
+        Ok(())
+    }
+
+    /// Global `g{idx}` as JSON, or `null` if it was never assigned
+    #[wasm_bindgen]
+    pub fn get_global(&self, idx: u32) -> Result<String, JsValue> {
+        serde_json::to_string(&self.interpreter.get_global(idx as i64)).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Set global `g{idx}` from JSON for `run_incremental`; objects become
+    /// structs and booleans 1 or 0
+    #[wasm_bindgen]
+    pub fn set_global(&mut self, idx: u32, json_value: &str) -> Result<(), JsValue> {
+        let value: Value = serde_json::from_str(json_value)
+            .map_err(|e| JsValue::from_str(&format!("Invalid value JSON: {}", e)))?;
+        self.interpreter.set_global(idx as i64, value);
+        Ok(())
+    }
+
+    /// Every assigned global as a JSON array of `{name, value}`, in index
+    /// order
+    #[wasm_bindgen]
+    pub fn globals_json(&self) -> Result<String, JsValue> {
+        let globals: Vec<serde_json::Value> = self
+            .interpreter
+            .globals()
+            .into_iter()
+            .map(|(idx, value)| serde_json::json!({ "name": format!("g{}", idx), "value": value }))
+            .collect();
+        serde_json::to_string(&globals).map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
     /// Transpile Sui code to Python
@@ -84,6 +193,18 @@ impl WasmSui {
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
+    /// Parse errors as a JSON array of `{line, column, severity, message}`
+    #[wasm_bindgen]
+    pub fn validate(code: &str) -> Result<String, JsValue> {
+        serde_json::to_string(&parse_diagnostics(code)).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Lint warnings as a JSON array of `{line, column, severity, message}`
+    #[wasm_bindgen]
+    pub fn lint(code: &str) -> Result<String, JsValue> {
+        serde_json::to_string(&lint_diagnostics(code)).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
     /// Get Sui language version
     #[wasm_bindgen]
     pub fn version() -> String {
@@ -91,9 +212,82 @@ impl WasmSui {
     }
 }
 
+#[cfg(feature = "wasm")]
+impl WasmSui {
+    /// Run `program` for up to `fuel` instructions and report its new output
+    fn advance(&mut self, program: Program, fuel: u32) -> Result<String, JsValue> {
+        let finished = self
+            .interpreter
+            .run_slice(&program, Some(fuel as u64))
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let output = &self.interpreter.get_output()[self.reported..];
+        self.reported += output.len();
+        let status = if finished { "done" } else { "suspended" };
+        let json = serde_json::json!({ "status": status, "output": output });
+        if !finished {
+            self.suspended = Some(program);
+        }
+        Ok(json.to_string())
+    }
+}
+
 #[cfg(feature = "wasm")]
 impl Default for WasmSui {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Step debugger for the playground; updates and state are returned as JSON
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub struct WasmDebugSession {
+    session: DebugSession,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl WasmDebugSession {
+    /// Load Sui code, paused before its first instruction
+    #[wasm_bindgen(constructor)]
+    pub fn new(code: &str) -> Result<WasmDebugSession, JsValue> {
+        DebugSession::new(code)
+            .map(|session| Self { session })
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Run one instruction and return the update as JSON
+    #[wasm_bindgen]
+    pub fn step(&mut self) -> String {
+        self.session.step().to_json()
+    }
+
+    /// Run until a breakpoint or the end and return the update as JSON
+    #[wasm_bindgen]
+    pub fn resume(&mut self) -> String {
+        self.session.resume().to_json()
+    }
+
+    /// Current state as JSON
+    #[wasm_bindgen]
+    pub fn state(&self) -> String {
+        self.session.state().to_json()
+    }
+
+    #[wasm_bindgen(js_name = setBreakpoint)]
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.session.set_breakpoint(line);
+    }
+
+    #[wasm_bindgen(js_name = removeBreakpoint)]
+    pub fn remove_breakpoint(&mut self, line: usize) {
+        self.session.remove_breakpoint(line);
+    }
+
+    /// Evaluate an expression in the current frame, returning JSON
+    #[wasm_bindgen]
+    pub fn evaluate(&self, expr: &str) -> Result<String, JsValue> {
+        let value = self.session.evaluate(expr).map_err(|e| JsValue::from_str(&e))?;
+        serde_json::to_string(&value).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
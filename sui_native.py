@@ -0,0 +1,181 @@
+#!/usr/bin/env python3
+"""
+Sui (粋) native bindings
+Calls the Rust interpreter and transpilers through the C API of the
+shared library built with `cargo build --release --features ffi`
+
+The pip package does not include that library; set SUI_LIB to its path.
+"""
+
+import ctypes
+import ctypes.util
+import os
+from pathlib import Path
+from typing import Optional, Sequence, Union
+
+SUI_OK = 0
+
+
+class SuiError(Exception):
+    """Error raised by a run or a transpilation"""
+
+    def __init__(self, message: str, output: Optional[list[str]] = None):
+        super().__init__(message)
+        self.output = output or []
+
+
+def _find_library() -> str:
+    """Path of libsui_lang: $SUI_LIB, a Cargo build next to this file, or
+    the system library path"""
+    if os.environ.get("SUI_LIB"):
+        return os.environ["SUI_LIB"]
+    names = ["libsui_lang.so", "libsui_lang.dylib", "sui_lang.dll"]
+    here = Path(__file__).resolve().parent
+    for profile in ("release", "debug"):
+        for name in names:
+            candidate = here / "target" / profile / name
+            if candidate.exists():
+                return str(candidate)
+    found = ctypes.util.find_library("sui_lang")
+    if found is None:
+        raise OSError("libsui_lang not found; build it with `cargo build --release --features ffi` or set SUI_LIB")
+    return found
+
+
+def _load(path: str) -> ctypes.CDLL:
+    lib = ctypes.CDLL(path)
+    handle = ctypes.c_void_p
+    text = ctypes.c_char_p
+    signatures = {
+        "sui_new": ([], handle),
+        "sui_free": ([handle], None),
+        "sui_run": ([handle, text, ctypes.POINTER(text), ctypes.c_size_t], ctypes.c_int),
+        "sui_transpile": ([handle, text, text], ctypes.c_int),
+        "sui_validate": ([handle, text], ctypes.c_int),
+        "sui_output_count": ([handle], ctypes.c_size_t),
+        "sui_get_output_line": ([handle, ctypes.c_size_t], text),
+        "sui_result": ([handle], text),
+        "sui_last_error": ([handle], text),
+        "sui_set_limit": ([handle, ctypes.c_uint64], None),
+        "sui_set_max_depth": ([handle, ctypes.c_size_t], None),
+        "sui_version": ([], text),
+    }
+    for name, (argtypes, restype) in signatures.items():
+        func = getattr(lib, name)
+        func.argtypes = argtypes
+        func.restype = restype
+    return lib
+
+
+_lib: Optional[ctypes.CDLL] = None
+
+
+def _library() -> ctypes.CDLL:
+    global _lib
+    if _lib is None:
+        _lib = _load(_find_library())
+    return _lib
+
+
+class Interpreter:
+    """Rust Sui interpreter; usable as a context manager"""
+
+    _handle = None
+
+    def __init__(self, max_steps: Optional[int] = None, max_depth: Optional[int] = None):
+        self._lib = _library()
+        self._handle = self._lib.sui_new()
+        if max_steps is not None:
+            self._lib.sui_set_limit(self._handle, max_steps)
+        if max_depth is not None:
+            self._lib.sui_set_max_depth(self._handle, max_depth)
+
+    def run(self, code: str, args: Sequence[Union[str, int, float]] = ()) -> list[str]:
+        """Run a program and return its output lines
+
+        Raises SuiError on failure, with the output printed so far in
+        its `output` attribute.
+        """
+        encoded = [str(a).encode() for a in args]
+        argv = (ctypes.c_char_p * len(encoded))(*encoded)
+        status = self._lib.sui_run(self._handle, code.encode(), argv, len(encoded))
+        output = self._output()
+        if status != SUI_OK:
+            raise SuiError(self._error(), output)
+        return output
+
+    def transpile(self, code: str, target: str) -> str:
+        """Code for `target`: "python", "javascript", "typescript" or "wat\""""
+        if self._lib.sui_transpile(self._handle, target.encode(), code.encode()) != SUI_OK:
+            raise SuiError(self._error())
+        return self._lib.sui_result(self._handle).decode()
+
+    def validate(self, code: str) -> list[str]:
+        """Messages for the problems in a program; empty if it is valid"""
+        if self._lib.sui_validate(self._handle, code.encode()) < 0:
+            raise SuiError(self._error())
+        return self._output()
+
+    def close(self) -> None:
+        """Free the interpreter; later calls fail"""
+        if self._handle:
+            self._lib.sui_free(self._handle)
+            self._handle = None
+
+    def _output(self) -> list[str]:
+        count = self._lib.sui_output_count(self._handle)
+        return [self._lib.sui_get_output_line(self._handle, i).decode() for i in range(count)]
+
+    def _error(self) -> str:
+        error = self._lib.sui_last_error(self._handle)
+        return error.decode() if error else "unknown error"
+
+    def __enter__(self) -> "Interpreter":
+        return self
+
+    def __exit__(self, *exc) -> None:
+        self.close()
+
+    def __del__(self) -> None:
+        self.close()
+
+
+def run(code: str, args: Sequence[Union[str, int, float]] = ()) -> list[str]:
+    """Run a program in a fresh interpreter and return its output lines"""
+    with Interpreter() as interp:
+        return interp.run(code, args)
+
+
+def validate(code: str) -> list[str]:
+    """Messages for the problems in a program; empty if it is valid"""
+    with Interpreter() as interp:
+        return interp.validate(code)
+
+
+def to_python(code: str) -> str:
+    """Transpile Sui to Python"""
+    with Interpreter() as interp:
+        return interp.transpile(code, "python")
+
+
+def to_javascript(code: str) -> str:
+    """Transpile Sui to JavaScript"""
+    with Interpreter() as interp:
+        return interp.transpile(code, "javascript")
+
+
+def to_typescript(code: str) -> str:
+    """Transpile Sui to TypeScript"""
+    with Interpreter() as interp:
+        return interp.transpile(code, "typescript")
+
+
+def to_wat(code: str) -> str:
+    """Compile Sui to WebAssembly text"""
+    with Interpreter() as interp:
+        return interp.transpile(code, "wat")
+
+
+def version() -> str:
+    """Version of the loaded library"""
+    return _library().sui_version().decode()
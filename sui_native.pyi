@@ -0,0 +1,24 @@
+from typing import Optional, Sequence, Union
+
+SUI_OK: int
+
+class SuiError(Exception):
+    output: list[str]
+    def __init__(self, message: str, output: Optional[list[str]] = ...) -> None: ...
+
+class Interpreter:
+    def __init__(self, max_steps: Optional[int] = ..., max_depth: Optional[int] = ...) -> None: ...
+    def run(self, code: str, args: Sequence[Union[str, int, float]] = ...) -> list[str]: ...
+    def transpile(self, code: str, target: str) -> str: ...
+    def validate(self, code: str) -> list[str]: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> Interpreter: ...
+    def __exit__(self, *exc: object) -> None: ...
+
+def run(code: str, args: Sequence[Union[str, int, float]] = ...) -> list[str]: ...
+def validate(code: str) -> list[str]: ...
+def to_python(code: str) -> str: ...
+def to_javascript(code: str) -> str: ...
+def to_typescript(code: str) -> str: ...
+def to_wat(code: str) -> str: ...
+def version() -> str: ...
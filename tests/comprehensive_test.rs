@@ -538,6 +538,21 @@ mod float_arithmetic {
         let value: f64 = result[0].parse().unwrap();
         assert!(value > 3.0 && value < 4.0);
     }
+
+    #[test]
+    fn test_python_repr() {
+        // Output matches Python's repr() of the same floats
+        let mut interp = Interpreter::new();
+        let code = "+ v0 0.1 0.2\n. v0\n* v1 1.0 1.0e16\n. v1\n/ v2 3 200000\n. v2\n\
+                    = v3 1.0e15\n. v3\n/ v4 -1 0\n. v4\n/ v5 1 3\n. v5\n- v6 0.0 0.0\n. v6";
+        let result = interp.run(code, &[]).unwrap();
+        assert_eq!(
+            result,
+            vec!["0.30000000000000004", "1e+16", "1.5e-05", "1000000000000000.0", "nan", "0.3333333333333333", "0.0"]
+        );
+        assert_eq!(sui_lang::interpreter::Value::Float(857995106662166.2).to_string(), "857995106662166.2");
+        assert_eq!(sui_lang::interpreter::Value::Float(f64::NEG_INFINITY).to_string(), "-inf");
+    }
 }
 
 // ============================================================================
@@ -676,54 +691,29 @@ mod edge_cases {
 // ============================================================================
 
 mod example_files {
-    use super::*;
-    use std::fs;
-    use std::path::Path;
-
-    fn run_example(filename: &str) -> Vec<String> {
-        let path = Path::new("examples").join(filename);
-        let code = fs::read_to_string(&path).expect(&format!("Failed to read {}", path.display()));
-        let mut interp = Interpreter::new();
-        interp.run(&code, &[]).expect(&format!("Failed to run {}", filename))
-    }
-
-    fn run_example_with_args(filename: &str, args: &[String]) -> Vec<String> {
-        let path = Path::new("examples").join(filename);
-        let code = fs::read_to_string(&path).expect(&format!("Failed to read {}", path.display()));
-        let mut interp = Interpreter::new();
-        interp.run(&code, args).expect(&format!("Failed to run {}", filename))
-    }
-
-    #[test]
-    fn test_fibonacci_example() {
-        let result = run_example("fibonacci.sui");
-        assert_eq!(result, vec!["55"]);
-    }
-
-    #[test]
-    fn test_fizzbuzz_example() {
-        let result = run_example("fizzbuzz.sui");
-        assert!(result.contains(&"FizzBuzz".to_string()));
-        assert!(result.contains(&"Fizz".to_string()));
-        assert!(result.contains(&"Buzz".to_string()));
-    }
-
-    #[test]
-    fn test_list_sum_example() {
-        let result = run_example("list_sum.sui");
-        // Output is "Sum:" and "150"
-        assert!(result.contains(&"150".to_string()));
-    }
-
-    #[test]
-    fn test_fib_args_example() {
-        let result = run_example_with_args("fib_args.sui", &["15".to_string()]);
-        assert_eq!(result, vec!["610"]);
-    }
-
-    #[test]
-    fn test_args_demo_example() {
-        let result = run_example_with_args("args_demo.sui", &["5".to_string(), "3".to_string()]);
-        assert!(result.contains(&"8".to_string())); // 5 + 3 = 8
+    use std::path::{Path, PathBuf};
+    use sui_lang::corpus::{CorpusRunner, Manifest};
+    use sui_lang::snap::{find_programs, verify, SnapStatus};
+
+    /// Expected outputs live in the `.snap` files next to the examples;
+    /// refresh them with `sui snap update examples/`
+    #[test]
+    fn test_example_snapshots() {
+        let programs = find_programs(&[PathBuf::from("examples")]);
+        assert!(programs.len() >= 8);
+        for program in programs {
+            match verify(&program).unwrap() {
+                SnapStatus::Unchanged => {}
+                SnapStatus::Changed(diff) => panic!("{} differs from its snapshot:\n{}", program.display(), diff),
+                status => panic!("{}: {}", program.display(), status),
+            }
+        }
+    }
+
+    #[test]
+    fn test_example_corpus() {
+        let manifest = Manifest::load(Path::new("examples/corpus.toml")).unwrap();
+        let report = CorpusRunner::new().run(&manifest);
+        assert!(report.success(), "{}", report);
     }
 }
@@ -0,0 +1,143 @@
+//! Crash-resistance tests: arbitrary input must never panic
+//!
+//! These mirror the cargo-fuzz targets in `fuzz/` with a fixed seed so they
+//! run as part of the normal test suite.
+
+use sui_lang::analysis::minify;
+use sui_lang::interpreter::{Instruction, Interpreter, Parser};
+use sui_lang::transpiler::{Sui2Js, Sui2Py, Sui2Wasm};
+
+/// Operand and opcode fragments that exercise edge cases
+const FRAGMENTS: &[&str] = &[
+    "=", "+", "-", "*", "/", "%", "<", ">", "~", "!", "&", "|", "?", "@", ":", "#", "}", "{", "$",
+    "^", "[", "]", ".", "R", "P", "v0", "v1", "g0", "g100", "a0", "a9", "v", "g", "a", "é", "vé",
+    "0", "1", "-1", "3", "9223372036854775807", "-9223372036854775808", "1e308", "0.5", "-0.0",
+    "\"\"", "\"a\"", "\"\\\"", "\"", "\"math.sqrt\"", "\"randint\"", "\"len\"", "\"str\"", "x",
+    "99999999999", ";", "\t",
+];
+
+/// Deterministic pseudo-random source (64-bit LCG)
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound
+    }
+}
+
+fn random_program(rng: &mut Lcg) -> String {
+    let mut code = String::new();
+    for _ in 0..rng.next(12) {
+        for _ in 0..rng.next(6) {
+            code.push_str(FRAGMENTS[rng.next(FRAGMENTS.len())]);
+            code.push(' ');
+        }
+        code.push('\n');
+    }
+    code
+}
+
+/// Whether running the program could block on stdin or touch the filesystem
+fn has_side_effects(code: &str) -> bool {
+    let (instructions, functions, _) = Parser::parse_lossy(code);
+    instructions
+        .iter()
+        .chain(functions.iter().flat_map(|f| f.body.iter()))
+        .any(|instr| matches!(instr, Instruction::Input { .. } | Instruction::Import { .. }))
+}
+
+fn exercise(code: &str) {
+    let _ = Parser::parse(code);
+    let _ = Parser::parse_lossy(code);
+    let _ = Parser::validate(code);
+    let (lines, _) = Parser::parse_recovering(code);
+    assert_eq!(lines.len(), code.lines().count());
+    let _ = Sui2Py::new().transpile_to_python(code);
+    let _ = Sui2Js::new().transpile_to_js(code);
+    let _ = Sui2Wasm::new().transpile_to_wat(code);
+
+    let minified = minify(code);
+
+    if !has_side_effects(code) {
+        let original = run_bounded(code);
+
+        // Minifying must not change what a program prints
+        if let (Ok(minified), Ok(original)) = (&minified, &original) {
+            if !code.contains("randint") {
+                if let Ok(output) = run_bounded(minified) {
+                    assert_eq!(&output, original, "minify changed\n{}\ninto\n{}", code, minified);
+                }
+            }
+        }
+    }
+}
+
+fn run_bounded(code: &str) -> Result<Vec<String>, sui_lang::InterpreterError> {
+    let mut interp = Interpreter::new();
+    interp.set_max_steps(Some(10_000));
+    interp.set_max_stack_depth(64);
+    interp.run(code, &[])
+}
+
+#[test]
+fn test_random_programs_do_not_panic() {
+    let mut rng = Lcg(0x5eed);
+    for _ in 0..3000 {
+        exercise(&random_program(&mut rng));
+    }
+}
+
+#[test]
+fn test_known_edge_cases() {
+    let cases = [
+        "",
+        "\"",
+        "= é 1",
+        "{ é 0 1",
+        "= v0 9223372036854775807\n+ v0 v0 1\n* v0 v0 v0\n. v0",
+        "= v0 -9223372036854775808\n% v1 v0 -1\n. v1",
+        "[ v0 -5\n. v0",
+        "[ v0 99999999999",
+        "R v0 \"randint\" -9223372036854775808 9223372036854775807",
+        "# 0 0 {\n$ v0 0\n}\n$ v0 0",
+        ": 0\n@ 0",
+        "# 0 0 {\n",
+        "}\n}\n# 1 1 {",
+    ];
+    for code in cases {
+        exercise(code);
+    }
+}
+
+#[test]
+fn test_step_limit() {
+    let mut interp = Interpreter::new();
+    interp.set_max_steps(Some(100));
+    assert!(interp.run(": 0\n@ 0", &[]).is_err());
+    assert!(interp.run("= v0 1\n. v0", &[]).is_ok());
+}
+
+#[test]
+fn test_step_hook() {
+    use std::ops::ControlFlow;
+    use std::sync::{Arc, Mutex};
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut interp = Interpreter::new();
+    let log = Arc::clone(&seen);
+    interp.set_step_hook(4, move |ctx| {
+        log.lock().unwrap().push((ctx.steps, ctx.line));
+        if ctx.steps >= 12 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    });
+    let result = interp.run("= v0 0\n: 0\n+ v0 v0 1\n@ 0", &[]);
+    assert!(matches!(result, Err(sui_lang::InterpreterError::Interrupted { line: 3 })));
+    assert_eq!(*seen.lock().unwrap(), [(4, 4), (8, 2), (12, 3)]);
+
+    // Interruptions cannot be caught
+    seen.lock().unwrap().clear();
+    assert!(interp.run("T v1 1\n: 0\n@ 0\n: 1\n. v1", &[]).is_err());
+
+    interp.clear_step_hook();
+    assert!(interp.run("= v0 1\n. v0", &[]).is_ok());
+}
@@ -0,0 +1,53 @@
+"""Tests for the native bindings (needs `cargo build --features ffi`)"""
+
+import pytest
+import sys
+import os
+
+sys.path.insert(0, os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+
+import sui_native
+
+try:
+    sui_native.version()
+except OSError:
+    pytest.skip("libsui_lang is not built with the ffi feature", allow_module_level=True)
+
+
+class TestInterpreter:
+    """Test runs through the C API"""
+
+    def test_run(self):
+        assert sui_native.run("= v0 10\n+ v1 v0 5\n. v1") == ["15"]
+
+    def test_args(self):
+        assert sui_native.run(". g100\n* v0 g101 2\n. v0\n. g102", [21, "x"]) == ["2", "42", "x"]
+
+    def test_error_keeps_output(self):
+        with pytest.raises(sui_native.SuiError) as info:
+            sui_native.run(". 1\nE \"boom\"")
+        assert "boom" in str(info.value)
+        assert info.value.output == ["1"]
+
+    def test_step_limit(self):
+        with sui_native.Interpreter(max_steps=100) as interp:
+            with pytest.raises(sui_native.SuiError, match="Step limit exceeded"):
+                interp.run(": 0\n@ 0")
+            assert interp.run(". 2") == ["2"]
+
+
+class TestTools:
+    """Test the transpilers and the validator"""
+
+    def test_transpile(self):
+        assert "print(v0)" in sui_native.to_python("= v0 1\n. v0")
+        assert "console.log(v0);" in sui_native.to_javascript("= v0 1\n. v0")
+        assert "(module" in sui_native.to_wat(". 1")
+        with pytest.raises(sui_native.SuiError, match="unknown target"):
+            sui_native.Interpreter().transpile(". 1", "cobol")
+
+    def test_validate(self):
+        assert sui_native.validate(". 1") == []
+        errors = sui_native.validate("+ v1\nX 1")
+        assert len(errors) == 2
+        assert "line 2" in errors[1]